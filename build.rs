@@ -0,0 +1,26 @@
+use std::path::Path;
+use std::process::Command;
+
+fn main() {
+    built::write_built_file().expect("Failed to collect build information");
+
+    // Docker builds have no `.git` in their context; they pass the commit as GIT_COMMIT
+    println!("cargo:rerun-if-env-changed=GIT_COMMIT");
+    for path in [".git/HEAD", ".git/refs", ".git/packed-refs"] {
+        if Path::new(path).exists() {
+            println!("cargo:rerun-if-changed={}", path);
+        }
+    }
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=Cargo.toml");
+
+    let commit = std::env::var("GIT_COMMIT")
+        .ok()
+        .filter(|c| !c.trim().is_empty())
+        .or_else(|| {
+            let output = Command::new("git").args(["rev-parse", "--short=12", "HEAD"]).output().ok()?;
+            output.status.success().then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        })
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=MBK_GIT_COMMIT={}", commit.trim());
+}
@@ -6,6 +6,45 @@ mod m20241202_000003_create_projects_table;
 mod m20241202_000004_create_api_keys_table;
 mod m20241204_000005_create_files_table;
 mod m20241204_000006_create_jobs_table;
+mod m20241205_000007_create_audit_logs_table;
+mod m20241210_000008_add_hot_path_indexes;
+mod m20241211_000009_add_api_key_expiry_notified_at;
+mod m20241212_000010_add_file_content_hash;
+mod m20241213_000011_add_user_role_check;
+mod m20241214_000012_add_refresh_token_client_metadata;
+mod m20241215_000013_create_job_events_table;
+mod m20241216_000014_create_project_settings_history_table;
+mod m20241217_000015_add_file_folder;
+mod m20241218_000016_create_project_storage_configs_table;
+mod m20241219_000017_add_file_derived_from;
+mod m20241220_000018_add_file_variants_source_hash;
+mod m20241221_000019_add_file_variant_errors;
+mod m20241222_000020_create_request_logs_table;
+mod m20241223_000021_add_project_storage_prefix;
+mod m20241224_000022_add_file_variant_dimensions;
+mod m20241225_000023_add_user_tokens_not_before;
+mod m20241226_000024_add_api_key_failure_counter;
+mod m20241227_000025_add_user_email;
+mod m20241228_000026_create_password_reset_tokens_table;
+mod m20241229_000027_add_api_key_scopes;
+mod m20241230_000028_add_user_must_change_password;
+mod m20241231_000029_add_project_storage_reconciliation;
+mod m20250102_000030_add_user_totp;
+mod m20250103_000031_create_file_tombstones_table;
+mod m20250104_000032_add_refresh_token_family;
+mod m20250105_000033_add_user_login_activity;
+mod m20250106_000034_add_version_columns;
+mod m20250107_000035_add_project_suspended_at;
+mod m20250108_000036_add_file_variant_hashes;
+mod m20250109_000037_use_timestamptz;
+mod m20250110_000038_add_user_token_version;
+mod m20250111_000039_create_login_attempts_table;
+mod m20250112_000040_drop_user_tokens_not_before;
+mod m20250113_000041_seal_user_totp_secret;
+mod m20250114_000042_create_project_webhooks_table;
+mod m20250115_000043_add_file_deleted_at;
+mod m20250116_000044_add_job_project_id;
+mod m20250117_000045_create_project_members_table;
 
 pub struct Migrator;
 
@@ -19,6 +58,45 @@ impl MigratorTrait for Migrator {
             Box::new(m20241202_000004_create_api_keys_table::Migration),
             Box::new(m20241204_000005_create_files_table::Migration),
             Box::new(m20241204_000006_create_jobs_table::Migration),
+            Box::new(m20241205_000007_create_audit_logs_table::Migration),
+            Box::new(m20241210_000008_add_hot_path_indexes::Migration),
+            Box::new(m20241211_000009_add_api_key_expiry_notified_at::Migration),
+            Box::new(m20241212_000010_add_file_content_hash::Migration),
+            Box::new(m20241213_000011_add_user_role_check::Migration),
+            Box::new(m20241214_000012_add_refresh_token_client_metadata::Migration),
+            Box::new(m20241215_000013_create_job_events_table::Migration),
+            Box::new(m20241216_000014_create_project_settings_history_table::Migration),
+            Box::new(m20241217_000015_add_file_folder::Migration),
+            Box::new(m20241218_000016_create_project_storage_configs_table::Migration),
+            Box::new(m20241219_000017_add_file_derived_from::Migration),
+            Box::new(m20241220_000018_add_file_variants_source_hash::Migration),
+            Box::new(m20241221_000019_add_file_variant_errors::Migration),
+            Box::new(m20241222_000020_create_request_logs_table::Migration),
+            Box::new(m20241223_000021_add_project_storage_prefix::Migration),
+            Box::new(m20241224_000022_add_file_variant_dimensions::Migration),
+            Box::new(m20241225_000023_add_user_tokens_not_before::Migration),
+            Box::new(m20241226_000024_add_api_key_failure_counter::Migration),
+            Box::new(m20241227_000025_add_user_email::Migration),
+            Box::new(m20241228_000026_create_password_reset_tokens_table::Migration),
+            Box::new(m20241229_000027_add_api_key_scopes::Migration),
+            Box::new(m20241230_000028_add_user_must_change_password::Migration),
+            Box::new(m20241231_000029_add_project_storage_reconciliation::Migration),
+            Box::new(m20250102_000030_add_user_totp::Migration),
+            Box::new(m20250103_000031_create_file_tombstones_table::Migration),
+            Box::new(m20250104_000032_add_refresh_token_family::Migration),
+            Box::new(m20250105_000033_add_user_login_activity::Migration),
+            Box::new(m20250106_000034_add_version_columns::Migration),
+            Box::new(m20250107_000035_add_project_suspended_at::Migration),
+            Box::new(m20250108_000036_add_file_variant_hashes::Migration),
+            Box::new(m20250109_000037_use_timestamptz::Migration),
+            Box::new(m20250110_000038_add_user_token_version::Migration),
+            Box::new(m20250111_000039_create_login_attempts_table::Migration),
+            Box::new(m20250112_000040_drop_user_tokens_not_before::Migration),
+            Box::new(m20250113_000041_seal_user_totp_secret::Migration),
+            Box::new(m20250114_000042_create_project_webhooks_table::Migration),
+            Box::new(m20250115_000043_add_file_deleted_at::Migration),
+            Box::new(m20250116_000044_add_job_project_id::Migration),
+            Box::new(m20250117_000045_create_project_members_table::Migration),
         ]
     }
 }
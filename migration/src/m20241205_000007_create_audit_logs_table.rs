@@ -0,0 +1,74 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Authentication and admin events. No FKs, so entries outlive the users, projects
+        // and keys they mention.
+        manager
+            .create_table(
+                Table::create()
+                    .table(AuditLogs::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(AuditLogs::Id)
+                            .big_integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(AuditLogs::ActorUserId).uuid().null())
+                    .col(ColumnDef::new(AuditLogs::Action).string_len(64).not_null())
+                    .col(ColumnDef::new(AuditLogs::TargetType).string_len(32).not_null())
+                    .col(ColumnDef::new(AuditLogs::TargetId).uuid().null())
+                    .col(ColumnDef::new(AuditLogs::Metadata).json_binary().not_null().default(Expr::cust("'{}'::jsonb")))
+                    .col(ColumnDef::new(AuditLogs::CreatedAt).timestamp().not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx_audit_logs_created_at")
+                    .table(AuditLogs::Table)
+                    .col(AuditLogs::CreatedAt)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx_audit_logs_actor_user_id_created_at")
+                    .table(AuditLogs::Table)
+                    .col(AuditLogs::ActorUserId)
+                    .col(AuditLogs::CreatedAt)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(AuditLogs::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum AuditLogs {
+    Table,
+    Id,
+    ActorUserId,
+    Action,
+    TargetType,
+    TargetId,
+    Metadata,
+    CreatedAt,
+}
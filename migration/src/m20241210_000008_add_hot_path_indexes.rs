@@ -0,0 +1,148 @@
+use sea_orm_migration::prelude::*;
+
+// Indexes backing the most frequent queries.
+//
+// Postgres runs each migration inside a transaction, and `CREATE INDEX CONCURRENTLY`
+// cannot run in one, so these are plain `CREATE INDEX IF NOT EXISTS`. On very large
+// tables, create them by hand with `CONCURRENTLY` (same names) before deploying and
+// this migration becomes a no-op.
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // GET /files?project_id=..: `WHERE project_id = $1 ORDER BY created_at DESC LIMIT n`
+        // Expected plan: Index Scan Backward using idx_files_project_id_created_at.
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx_files_project_id_created_at")
+                    .table(Files::Table)
+                    .col(Files::ProjectId)
+                    .col(Files::CreatedAt)
+                    .to_owned(),
+            )
+            .await?;
+
+        // Worker claim: `WHERE status = 'pending' ORDER BY created_at LIMIT 1 FOR UPDATE SKIP LOCKED`
+        // Expected plan: Index Scan using idx_jobs_status_created_at, stopping at the first unlocked row.
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx_jobs_status_created_at")
+                    .table(Jobs::Table)
+                    .col(Jobs::Status)
+                    .col(Jobs::CreatedAt)
+                    .to_owned(),
+            )
+            .await?;
+
+        // GET /jobs and /admin/jobs join jobs to files on file_id; also serves the FK cascade on file delete.
+        // Expected plan: Nested Loop / Hash Join with Index Scan using idx_jobs_file_id.
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx_jobs_file_id")
+                    .table(Jobs::Table)
+                    .col(Jobs::FileId)
+                    .to_owned(),
+            )
+            .await?;
+
+        // Per-user refresh token lookups and the FK cascade on user delete.
+        // Expected plan: Index Scan using idx_refresh_tokens_user_id.
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx_refresh_tokens_user_id")
+                    .table(RefreshTokens::Table)
+                    .col(RefreshTokens::UserId)
+                    .to_owned(),
+            )
+            .await?;
+
+        // GET /projects/{id}/keys: `WHERE project_id = $1 ORDER BY created_at DESC`
+        // Expected plan: Index Scan using idx_api_keys_project_id, then a small sort.
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx_api_keys_project_id")
+                    .table(ApiKeys::Table)
+                    .col(ApiKeys::ProjectId)
+                    .to_owned(),
+            )
+            .await?;
+
+        // GET /projects: `WHERE owner_id = $1 AND deleted_at IS NULL`
+        // Expected plan: Index Scan using idx_projects_owner_id_deleted_at.
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx_projects_owner_id_deleted_at")
+                    .table(Projects::Table)
+                    .col(Projects::OwnerId)
+                    .col(Projects::DeletedAt)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for (name, table) in [
+            ("idx_files_project_id_created_at", Files::Table.into_iden()),
+            ("idx_jobs_status_created_at", Jobs::Table.into_iden()),
+            ("idx_jobs_file_id", Jobs::Table.into_iden()),
+            ("idx_refresh_tokens_user_id", RefreshTokens::Table.into_iden()),
+            ("idx_api_keys_project_id", ApiKeys::Table.into_iden()),
+            ("idx_projects_owner_id_deleted_at", Projects::Table.into_iden()),
+        ] {
+            manager
+                .drop_index(Index::drop().if_exists().name(name).table(table).to_owned())
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Files {
+    Table,
+    ProjectId,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum Jobs {
+    Table,
+    FileId,
+    Status,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum RefreshTokens {
+    Table,
+    UserId,
+}
+
+#[derive(DeriveIden)]
+enum ApiKeys {
+    Table,
+    ProjectId,
+}
+
+#[derive(DeriveIden)]
+enum Projects {
+    Table,
+    OwnerId,
+    DeletedAt,
+}
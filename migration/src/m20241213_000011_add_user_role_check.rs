@@ -0,0 +1,28 @@
+use sea_orm_migration::prelude::*;
+
+// `users.role` is a plain string column; pin it to the values the `Role` enum knows,
+// including the read-only `viewer` role.
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "ALTER TABLE users ADD CONSTRAINT chk_users_role CHECK (role IN ('su', 'admin', 'user', 'viewer'))",
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .get_connection()
+            .execute_unprepared("ALTER TABLE users DROP CONSTRAINT IF EXISTS chk_users_role")
+            .await?;
+        Ok(())
+    }
+}
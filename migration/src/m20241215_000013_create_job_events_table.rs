@@ -0,0 +1,71 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Worker lifecycle events (only written when JOB_EVENTS_ENABLED=true).
+        // The serial id keeps events from one batch in insertion order.
+        manager
+            .create_table(
+                Table::create()
+                    .table(JobEvents::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(JobEvents::Id)
+                            .big_integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(JobEvents::JobId).uuid().not_null())
+                    .col(ColumnDef::new(JobEvents::Event).string().not_null())
+                    .col(ColumnDef::new(JobEvents::Data).json().not_null())
+                    .col(ColumnDef::new(JobEvents::CreatedAt).timestamp().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_job_events_job_id")
+                            .from(JobEvents::Table, JobEvents::JobId)
+                            .to(Jobs::Table, Jobs::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx_job_events_job_id")
+                    .table(JobEvents::Table)
+                    .col(JobEvents::JobId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(JobEvents::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum JobEvents {
+    Table,
+    Id,
+    JobId,
+    Event,
+    Data,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum Jobs {
+    Table,
+    Id,
+}
@@ -0,0 +1,88 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(ProjectSettingsHistory::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ProjectSettingsHistory::Id)
+                            .uuid()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(ProjectSettingsHistory::ProjectId).uuid().not_null())
+                    .col(ColumnDef::new(ProjectSettingsHistory::OldSettings).json().not_null())
+                    .col(ColumnDef::new(ProjectSettingsHistory::NewSettings).json().not_null())
+                    // Kept when the user is deleted so the history stays readable
+                    .col(ColumnDef::new(ProjectSettingsHistory::ChangedBy).uuid())
+                    // Set on entries written by a rollback: the entry that was undone
+                    .col(ColumnDef::new(ProjectSettingsHistory::RollbackOf).uuid())
+                    .col(ColumnDef::new(ProjectSettingsHistory::CreatedAt).timestamp().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_project_settings_history_project_id")
+                            .from(ProjectSettingsHistory::Table, ProjectSettingsHistory::ProjectId)
+                            .to(Projects::Table, Projects::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_project_settings_history_changed_by")
+                            .from(ProjectSettingsHistory::Table, ProjectSettingsHistory::ChangedBy)
+                            .to(Users::Table, Users::Id)
+                            .on_delete(ForeignKeyAction::SetNull),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx_project_settings_history_project_id_created_at")
+                    .table(ProjectSettingsHistory::Table)
+                    .col(ProjectSettingsHistory::ProjectId)
+                    .col(ProjectSettingsHistory::CreatedAt)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ProjectSettingsHistory::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum ProjectSettingsHistory {
+    Table,
+    Id,
+    ProjectId,
+    OldSettings,
+    NewSettings,
+    ChangedBy,
+    RollbackOf,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum Projects {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum Users {
+    Table,
+    Id,
+}
@@ -0,0 +1,54 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Normalized folder path with a trailing slash (`a/b/`); '' is the project root
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Files::Table)
+                    .add_column_if_not_exists(
+                        ColumnDef::new(Files::Folder)
+                            .string_len(512)
+                            .not_null()
+                            .default(""),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // varchar_pattern_ops so `folder LIKE 'a/b/%'` can use the index under any collation
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "CREATE INDEX IF NOT EXISTS idx_files_project_id_folder ON files (project_id, folder varchar_pattern_ops)",
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .get_connection()
+            .execute_unprepared("DROP INDEX IF EXISTS idx_files_project_id_folder")
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Files::Table)
+                    .drop_column(Files::Folder)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Files {
+    Table,
+    Folder,
+}
@@ -0,0 +1,71 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // At most one per project; projects without a row use the global S3_* bucket
+        manager
+            .create_table(
+                Table::create()
+                    .table(ProjectStorageConfigs::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ProjectStorageConfigs::ProjectId)
+                            .uuid()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(ProjectStorageConfigs::Bucket).string_len(255).not_null())
+                    .col(ColumnDef::new(ProjectStorageConfigs::Region).string_len(64).not_null())
+                    .col(ColumnDef::new(ProjectStorageConfigs::Endpoint).string_len(512))
+                    .col(ColumnDef::new(ProjectStorageConfigs::AccessKeyId).string_len(255).not_null())
+                    // AES-256-GCM sealed with STORAGE_CREDENTIALS_KEY; never returned by the API
+                    .col(ColumnDef::new(ProjectStorageConfigs::SecretAccessKeySealed).text().not_null())
+                    .col(ColumnDef::new(ProjectStorageConfigs::ForcePathStyle).boolean().not_null().default(false))
+                    .col(ColumnDef::new(ProjectStorageConfigs::PublicObjects).boolean().not_null().default(false))
+                    .col(ColumnDef::new(ProjectStorageConfigs::VerifiedAt).timestamp())
+                    .col(ColumnDef::new(ProjectStorageConfigs::CreatedAt).timestamp().not_null())
+                    .col(ColumnDef::new(ProjectStorageConfigs::UpdatedAt).timestamp().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_project_storage_configs_project_id")
+                            .from(ProjectStorageConfigs::Table, ProjectStorageConfigs::ProjectId)
+                            .to(Projects::Table, Projects::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ProjectStorageConfigs::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum ProjectStorageConfigs {
+    Table,
+    ProjectId,
+    Bucket,
+    Region,
+    Endpoint,
+    AccessKeyId,
+    SecretAccessKeySealed,
+    ForcePathStyle,
+    PublicObjects,
+    VerifiedAt,
+    CreatedAt,
+    UpdatedAt,
+}
+
+#[derive(DeriveIden)]
+enum Projects {
+    Table,
+    Id,
+}
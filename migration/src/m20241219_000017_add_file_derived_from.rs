@@ -0,0 +1,57 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Source file of a derived one (video poster, PDF page render); NULL for uploads
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Files::Table)
+                    .add_column_if_not_exists(ColumnDef::new(Files::DerivedFrom).uuid())
+                    .add_foreign_key(
+                        TableForeignKey::new()
+                            .name("fk_files_derived_from")
+                            .from_tbl(Files::Table)
+                            .from_col(Files::DerivedFrom)
+                            .to_tbl(Files::Table)
+                            .to_col(Files::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx_files_derived_from")
+                    .table(Files::Table)
+                    .col(Files::DerivedFrom)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Files::Table)
+                    .drop_column(Files::DerivedFrom)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Files {
+    Table,
+    Id,
+    DerivedFrom,
+}
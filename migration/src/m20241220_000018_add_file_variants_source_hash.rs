@@ -0,0 +1,48 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Hex SHA-256 of the original the current variants were generated from
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Files::Table)
+                    .add_column_if_not_exists(ColumnDef::new(Files::VariantsSourceHash).string_len(64))
+                    .to_owned(),
+            )
+            .await?;
+
+        // Originals could not be replaced before this column existed, so processed
+        // images were generated from their current content
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "UPDATE files SET variants_source_hash = content_hash \
+                 WHERE status = 'ready' AND mime_type LIKE 'image/%' AND variants_source_hash IS NULL",
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Files::Table)
+                    .drop_column(Files::VariantsSourceHash)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Files {
+    Table,
+    VariantsSourceHash,
+}
@@ -0,0 +1,41 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Last failure per variant name (`{"thumb": {"error": "...", "failed_at": "..."}}`)
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Files::Table)
+                    .add_column_if_not_exists(
+                        ColumnDef::new(Files::VariantErrors)
+                            .json()
+                            .not_null()
+                            .default(Expr::cust("'{}'::json")),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Files::Table)
+                    .drop_column(Files::VariantErrors)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Files {
+    Table,
+    VariantErrors,
+}
@@ -0,0 +1,80 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // API-key requests of projects with `request_logs` enabled. `api_key_id` has no FK
+        // so entries outlive deleted keys; old rows are removed by the cleanup service.
+        manager
+            .create_table(
+                Table::create()
+                    .table(RequestLogs::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(RequestLogs::Id)
+                            .big_integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(RequestLogs::ProjectId).uuid().not_null())
+                    .col(ColumnDef::new(RequestLogs::ApiKeyId).uuid().not_null())
+                    .col(ColumnDef::new(RequestLogs::Method).string_len(16).not_null())
+                    .col(ColumnDef::new(RequestLogs::Path).text().not_null())
+                    .col(ColumnDef::new(RequestLogs::Status).small_integer().not_null())
+                    .col(ColumnDef::new(RequestLogs::Bytes).big_integer().not_null())
+                    .col(ColumnDef::new(RequestLogs::DurationMs).integer().not_null())
+                    .col(ColumnDef::new(RequestLogs::CreatedAt).timestamp().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_request_logs_project_id")
+                            .from(RequestLogs::Table, RequestLogs::ProjectId)
+                            .to(Projects::Table, Projects::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx_request_logs_project_id_created_at")
+                    .table(RequestLogs::Table)
+                    .col(RequestLogs::ProjectId)
+                    .col(RequestLogs::CreatedAt)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(RequestLogs::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum RequestLogs {
+    Table,
+    Id,
+    ProjectId,
+    ApiKeyId,
+    Method,
+    Path,
+    Status,
+    Bytes,
+    DurationMs,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum Projects {
+    Table,
+    Id,
+}
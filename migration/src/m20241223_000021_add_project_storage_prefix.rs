@@ -0,0 +1,59 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Key prefix frozen at creation; renaming a project must not move its objects
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Projects::Table)
+                    .add_column_if_not_exists(ColumnDef::new(Projects::StoragePrefix).string())
+                    .to_owned(),
+            )
+            .await?;
+
+        // Existing projects keep the `{sanitized name}-{id}` layout built from their current
+        // name, so their future variants land next to the existing objects. Objects are
+        // never moved: `files.s3_key` and `variants_json` store keys verbatim, so files
+        // written under an earlier name (before a rename) stay reachable. New projects
+        // use their bare id.
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "UPDATE projects SET storage_prefix = \
+                 regexp_replace(lower(name), '[^[:alnum:]]', '-', 'g') || '-' || id::text \
+                 WHERE storage_prefix IS NULL",
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Projects::Table)
+                    .modify_column(ColumnDef::new(Projects::StoragePrefix).string().not_null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Projects::Table)
+                    .drop_column(Projects::StoragePrefix)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Projects {
+    Table,
+    StoragePrefix,
+}
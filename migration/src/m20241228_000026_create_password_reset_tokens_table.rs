@@ -0,0 +1,71 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(PasswordResetToken::PasswordResetTokens)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(PasswordResetToken::Id)
+                            .uuid()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(PasswordResetToken::UserId).uuid().not_null())
+                    .col(ColumnDef::new(PasswordResetToken::TokenHash).string().not_null().unique_key())
+                    .col(ColumnDef::new(PasswordResetToken::ExpiresAt).timestamp().not_null())
+                    .col(ColumnDef::new(PasswordResetToken::Used).boolean().not_null().default(false))
+                    .col(ColumnDef::new(PasswordResetToken::CreatedAt).timestamp().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_password_reset_token_user")
+                            .from(PasswordResetToken::PasswordResetTokens, PasswordResetToken::UserId)
+                            .to(User::Users, User::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // A successful reset marks every outstanding token of the user as used
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_password_reset_tokens_user_id")
+                    .table(PasswordResetToken::PasswordResetTokens)
+                    .col(PasswordResetToken::UserId)
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(PasswordResetToken::PasswordResetTokens).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum PasswordResetToken {
+    PasswordResetTokens,
+    Id,
+    UserId,
+    TokenHash,
+    ExpiresAt,
+    Used,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum User {
+    Users,
+    Id,
+}
@@ -0,0 +1,43 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Result of the last `reconcile_storage` job: bytes actually stored under the project
+        // prefix and how far that is from what `files.size` reports
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Projects::Table)
+                    .add_column_if_not_exists(ColumnDef::new(Projects::LastReconciledAt).timestamp().null())
+                    .add_column_if_not_exists(ColumnDef::new(Projects::ReconciledBytes).big_integer().null())
+                    .add_column_if_not_exists(ColumnDef::new(Projects::ReconciledDriftBytes).big_integer().null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Projects::Table)
+                    .drop_column(Projects::LastReconciledAt)
+                    .drop_column(Projects::ReconciledBytes)
+                    .drop_column(Projects::ReconciledDriftBytes)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Projects {
+    Table,
+    LastReconciledAt,
+    ReconciledBytes,
+    ReconciledDriftBytes,
+}
@@ -0,0 +1,47 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Base32 TOTP secret; login only asks for a code once totp_enabled_at is set
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Users::Table)
+                    .add_column_if_not_exists(ColumnDef::new(Users::TotpSecret).string_len(64).null())
+                    .add_column_if_not_exists(ColumnDef::new(Users::TotpEnabledAt).timestamp().null())
+                    .add_column_if_not_exists(
+                        ColumnDef::new(Users::TotpRecoveryCodes)
+                            .json()
+                            .not_null()
+                            .default(Expr::cust("'[]'::json")),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Users::Table)
+                    .drop_column(Users::TotpSecret)
+                    .drop_column(Users::TotpEnabledAt)
+                    .drop_column(Users::TotpRecoveryCodes)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Users {
+    Table,
+    TotpSecret,
+    TotpEnabledAt,
+    TotpRecoveryCodes,
+}
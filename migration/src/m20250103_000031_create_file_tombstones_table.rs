@@ -0,0 +1,50 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // One row per permanently deleted file, so lookups can answer 410 instead of 404.
+        // No FK: the file and usually its project are gone.
+        manager
+            .create_table(
+                Table::create()
+                    .table(FileTombstones::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(FileTombstones::FileId).uuid().not_null().primary_key())
+                    .col(ColumnDef::new(FileTombstones::ProjectId).uuid().not_null())
+                    .col(ColumnDef::new(FileTombstones::Reason).string_len(32).not_null())
+                    .col(ColumnDef::new(FileTombstones::DeletedAt).timestamp().not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx_file_tombstones_deleted_at")
+                    .table(FileTombstones::Table)
+                    .col(FileTombstones::DeletedAt)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(FileTombstones::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum FileTombstones {
+    Table,
+    FileId,
+    ProjectId,
+    Reason,
+    DeletedAt,
+}
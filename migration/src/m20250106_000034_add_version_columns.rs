@@ -0,0 +1,50 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Bumped by every API edit; clients send it back in If-Match to detect concurrent edits
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Projects::Table)
+                    .add_column_if_not_exists(ColumnDef::new(Projects::Version).integer().not_null().default(1))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Files::Table)
+                    .add_column_if_not_exists(ColumnDef::new(Files::Version).integer().not_null().default(1))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(Table::alter().table(Files::Table).drop_column(Files::Version).to_owned())
+            .await?;
+
+        manager
+            .alter_table(Table::alter().table(Projects::Table).drop_column(Projects::Version).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Projects {
+    Table,
+    Version,
+}
+
+#[derive(DeriveIden)]
+enum Files {
+    Table,
+    Version,
+}
@@ -0,0 +1,57 @@
+use sea_orm_migration::prelude::*;
+
+// Every timestamp was written as naive UTC (`Utc::now().naive_utc()`) into `timestamp`
+// columns, which carry no zone. On a database whose session time zone is local time,
+// anything comparing them with `NOW()` or reading them as local time was off by the UTC
+// offset, and by a different amount on each side of a DST change. The stored values are
+// reinterpreted as UTC (`AT TIME ZONE 'UTC'`), which is what they always were, whatever the
+// session time zone of the migrating connection.
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+const COLUMNS: &[(&str, &[&str])] = &[
+    ("users", &["created_at", "tokens_not_before", "totp_enabled_at", "last_login_at"]),
+    ("refresh_tokens", &["expires_at", "created_at"]),
+    ("projects", &["created_at", "updated_at", "deleted_at", "last_reconciled_at", "suspended_at"]),
+    ("api_keys", &["created_at", "expires_at", "expiry_notified_at", "last_failed_at"]),
+    ("files", &["created_at", "updated_at"]),
+    ("jobs", &["created_at", "updated_at"]),
+    ("job_events", &["created_at"]),
+    ("project_settings_history", &["created_at"]),
+    ("project_storage_configs", &["verified_at", "created_at", "updated_at"]),
+    ("request_logs", &["created_at"]),
+    ("password_reset_tokens", &["expires_at", "created_at"]),
+    ("audit_logs", &["created_at"]),
+    ("file_tombstones", &["deleted_at"]),
+];
+
+fn alter_statements(to: &str) -> Vec<String> {
+    COLUMNS
+        .iter()
+        .map(|(table, columns)| {
+            let alters: Vec<String> = columns
+                .iter()
+                .map(|c| format!("ALTER COLUMN {c} TYPE {to} USING {c} AT TIME ZONE 'UTC'"))
+                .collect();
+            format!("ALTER TABLE {} {}", table, alters.join(", "))
+        })
+        .collect()
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for statement in alter_statements("timestamptz") {
+            manager.get_connection().execute_unprepared(&statement).await?;
+        }
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for statement in alter_statements("timestamp") {
+            manager.get_connection().execute_unprepared(&statement).await?;
+        }
+        Ok(())
+    }
+}
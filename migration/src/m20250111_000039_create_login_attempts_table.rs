@@ -0,0 +1,74 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Every `/auth/login` outcome, pruned by the cleanup service. No FK on `user_id`, so
+        // attempts against deleted accounts stay on record.
+        manager
+            .create_table(
+                Table::create()
+                    .table(LoginAttempts::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(LoginAttempts::Id)
+                            .big_integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(LoginAttempts::Username).string_len(255).not_null())
+                    .col(ColumnDef::new(LoginAttempts::UserId).uuid().null())
+                    .col(ColumnDef::new(LoginAttempts::Success).boolean().not_null())
+                    .col(ColumnDef::new(LoginAttempts::FailureReason).string_len(32).null())
+                    .col(ColumnDef::new(LoginAttempts::Ip).string_len(64).null())
+                    .col(ColumnDef::new(LoginAttempts::CreatedAt).timestamp_with_time_zone().not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx_login_attempts_created_at")
+                    .table(LoginAttempts::Table)
+                    .col(LoginAttempts::CreatedAt)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx_login_attempts_username_created_at")
+                    .table(LoginAttempts::Table)
+                    .col(LoginAttempts::Username)
+                    .col(LoginAttempts::CreatedAt)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(LoginAttempts::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum LoginAttempts {
+    Table,
+    Id,
+    Username,
+    UserId,
+    Success,
+    FailureReason,
+    Ip,
+    CreatedAt,
+}
@@ -0,0 +1,59 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // At most one endpoint per project; events are only delivered while a row exists
+        manager
+            .create_table(
+                Table::create()
+                    .table(ProjectWebhooks::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ProjectWebhooks::ProjectId)
+                            .uuid()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(ProjectWebhooks::Url).string_len(2048).not_null())
+                    // AES-256-GCM sealed with WEBHOOK_SECRET_KEY; only returned when it is generated
+                    .col(ColumnDef::new(ProjectWebhooks::SecretSealed).text().not_null())
+                    .col(ColumnDef::new(ProjectWebhooks::CreatedAt).timestamp_with_time_zone().not_null())
+                    .col(ColumnDef::new(ProjectWebhooks::UpdatedAt).timestamp_with_time_zone().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_project_webhooks_project_id")
+                            .from(ProjectWebhooks::Table, ProjectWebhooks::ProjectId)
+                            .to(Projects::Table, Projects::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ProjectWebhooks::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum ProjectWebhooks {
+    Table,
+    ProjectId,
+    Url,
+    SecretSealed,
+    CreatedAt,
+    UpdatedAt,
+}
+
+#[derive(DeriveIden)]
+enum Projects {
+    Table,
+    Id,
+}
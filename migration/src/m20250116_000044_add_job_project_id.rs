@@ -0,0 +1,85 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Every job belongs to a project; only jobs about one file also point at that file.
+        // Null for a backfill across all projects.
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Jobs::Table)
+                    .add_column_if_not_exists(ColumnDef::new(Jobs::ProjectId).uuid().null())
+                    .modify_column(ColumnDef::new(Jobs::FileId).uuid().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        // Project-level jobs were anchored to an arbitrary file of the project; detach them
+        // so deleting that file no longer takes the job with it
+        let db = manager.get_connection();
+        db.execute_unprepared("UPDATE jobs SET project_id = files.project_id FROM files WHERE files.id = jobs.file_id")
+            .await?;
+        db.execute_unprepared(
+            "UPDATE jobs SET file_id = NULL, \
+             project_id = (SELECT id FROM projects WHERE projects.id::text = jobs.payload->>'project_id') \
+             WHERE jobs.payload->>'type' IN ('sync_project_variants', 'plan_project_sync', 'backfill', 'reconcile_storage')",
+        )
+        .await?;
+
+        manager
+            .create_foreign_key(
+                ForeignKey::create()
+                    .name("fk_jobs_project_id")
+                    .from(Jobs::Table, Jobs::ProjectId)
+                    .to(Projects::Table, Projects::Id)
+                    .on_delete(ForeignKeyAction::Cascade)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx_jobs_project_id")
+                    .table(Jobs::Table)
+                    .col(Jobs::ProjectId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Jobs without a file cannot be kept under the old schema
+        manager
+            .get_connection()
+            .execute_unprepared("DELETE FROM jobs WHERE file_id IS NULL")
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Jobs::Table)
+                    .drop_column(Jobs::ProjectId)
+                    .modify_column(ColumnDef::new(Jobs::FileId).uuid().not_null())
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Jobs {
+    Table,
+    FileId,
+    ProjectId,
+}
+
+#[derive(DeriveIden)]
+enum Projects {
+    Table,
+    Id,
+}
@@ -0,0 +1,75 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Projects Su has shared with a Viewer, who can read them without owning them
+        manager
+            .create_table(
+                Table::create()
+                    .table(ProjectMembers::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(ProjectMembers::ProjectId).uuid().not_null())
+                    .col(ColumnDef::new(ProjectMembers::UserId).uuid().not_null())
+                    .col(ColumnDef::new(ProjectMembers::CreatedAt).timestamp_with_time_zone().not_null())
+                    .primary_key(Index::create().col(ProjectMembers::ProjectId).col(ProjectMembers::UserId))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_project_members_project_id")
+                            .from(ProjectMembers::Table, ProjectMembers::ProjectId)
+                            .to(Projects::Table, Projects::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_project_members_user_id")
+                            .from(ProjectMembers::Table, ProjectMembers::UserId)
+                            .to(Users::Table, Users::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Scope checks look memberships up by user
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx_project_members_user_id")
+                    .table(ProjectMembers::Table)
+                    .col(ProjectMembers::UserId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ProjectMembers::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum ProjectMembers {
+    Table,
+    ProjectId,
+    UserId,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum Projects {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum Users {
+    Table,
+    Id,
+}
@@ -0,0 +1,53 @@
+//! What this binary was built from, so instances can be told apart during a rollout.
+//! Collected at compile time by `build.rs`.
+
+use serde::Serialize;
+
+use crate::config::get_config;
+
+#[allow(dead_code)]
+mod built {
+    include!(concat!(env!("OUT_DIR"), "/built.rs"));
+}
+
+/// Crate version from `Cargo.toml`.
+pub const VERSION: &str = built::PKG_VERSION;
+
+/// Short hash of the commit built, `GIT_COMMIT` for builds without `.git` (e.g. Docker),
+/// `unknown` when neither was available.
+pub const COMMIT: &str = env!("MBK_GIT_COMMIT");
+
+/// `0.1.0+3f2a9c1d4e5b`: the value of the `X-MBK-Version` header.
+pub fn version_string() -> String {
+    format!("{}+{}", VERSION, COMMIT)
+}
+
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub struct BuildInfo {
+    pub version: &'static str,
+    pub commit: &'static str,
+    /// When the binary was compiled (honours `SOURCE_DATE_EPOCH` for reproducible builds)
+    #[schema(value_type = String)]
+    pub built_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// `debug` or `release`
+    pub profile: &'static str,
+    /// Cargo features the binary was compiled with
+    pub features: Vec<&'static str>,
+    pub rustc: &'static str,
+    /// Storage provider of the default bucket (`S3_PROVIDER`): `aws`, `minio`, `r2` or `custom`
+    pub storage: &'static str,
+}
+
+pub fn info() -> BuildInfo {
+    BuildInfo {
+        version: VERSION,
+        commit: COMMIT,
+        built_at: chrono::DateTime::parse_from_rfc2822(built::BUILT_TIME_UTC)
+            .ok()
+            .map(|t| t.to_utc()),
+        profile: built::PROFILE,
+        features: built::FEATURES.to_vec(),
+        rustc: built::RUSTC_VERSION,
+        storage: get_config().s3_provider.as_str(),
+    }
+}
@@ -1,16 +1,304 @@
+use std::collections::HashMap;
 use std::env;
+use std::fs;
 use std::sync::OnceLock;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use sha2::{Digest, Sha256};
+
+use crate::pagination::PaginationConfig;
+use crate::services::password_policy::PasswordPolicy;
+use crate::utils::secret_box;
+
+/// Shortest JWT secret accepted without `ALLOW_WEAK_JWT_SECRET=true`.
+const MIN_JWT_SECRET_LEN: usize = 32;
+
+/// Env prefix for named external processing command templates (`EXTERNAL_PROCESSOR_CMD_<NAME>`).
+const EXTERNAL_PROCESSOR_PREFIX: &str = "EXTERNAL_PROCESSOR_CMD_";
+
+/// Env prefix for the extra input extensions a named processor accepts (`EXTERNAL_PROCESSOR_INPUTS_<NAME>`).
+const EXTERNAL_PROCESSOR_INPUTS_PREFIX: &str = "EXTERNAL_PROCESSOR_INPUTS_";
+
+/// Extensions that make ImageMagick and similar tools run scripts, draw vector commands or
+/// read other files. Never accepted as an external processor input, whatever the config says.
+const SCRIPTED_INPUT_EXTENSIONS: &[&str] = &[
+    "msl", "mvg", "svg", "svgz", "txt", "text", "html", "htm", "url", "http", "https", "ftp", "file",
+    "ephemeral", "label", "caption", "pango", "inline", "xc", "x", "ps", "pdf", "epi", "epsi", "ai",
+];
+
+/// Values that show up in examples and tutorials; refused regardless of length.
+const WEAK_JWT_SECRETS: &[&str] = &[
+    "secret",
+    "changeme",
+    "change_me",
+    "password",
+    "jwt_secret",
+    "jwtsecret",
+    "your_super_secret_jwt_key",
+    "replace_with_at_least_32_random_bytes",
+    "your-256-bit-secret",
+    "supersecret",
+    "mysecret",
+];
+
+/// How access tokens are signed (`JWT_ALGORITHM`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JwtAlgorithm {
+    /// Shared `JWT_SECRET`; only this server can verify tokens
+    Hs256,
+    /// PEM keypair; anyone with the public key (`GET /.well-known/jwks.json`) can verify tokens
+    Rs256,
+}
+
+impl JwtAlgorithm {
+    fn from_env() -> Self {
+        match env::var("JWT_ALGORITHM").ok().as_deref() {
+            None | Some("HS256") => JwtAlgorithm::Hs256,
+            Some("RS256") => JwtAlgorithm::Rs256,
+            Some(other) => panic!("Invalid JWT_ALGORITHM '{}': expected HS256 or RS256", other),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            JwtAlgorithm::Hs256 => "HS256",
+            JwtAlgorithm::Rs256 => "RS256",
+        }
+    }
+}
+
+/// S3-compatible backend, as hinted by `S3_PROVIDER`. Picks defaults for features
+/// that differ between providers; the individual `S3_*` flags still override them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum S3Provider {
+    Aws,
+    Minio,
+    /// Cloudflare R2: no object ACLs or bucket policies, account endpoint required
+    R2,
+    /// Any other S3-compatible endpoint; behaves as before the hint existed
+    Custom,
+}
+
+impl S3Provider {
+    /// Defaults to `custom` when `S3_ENDPOINT` is set, `aws` otherwise.
+    fn from_env(has_endpoint: bool) -> Self {
+        match env::var("S3_PROVIDER").ok().as_deref() {
+            Some("aws") => S3Provider::Aws,
+            Some("minio") => S3Provider::Minio,
+            Some("r2") => S3Provider::R2,
+            Some("custom") => S3Provider::Custom,
+            Some(other) => panic!("Invalid S3_PROVIDER '{}': expected aws, minio, r2 or custom", other),
+            None if has_endpoint => S3Provider::Custom,
+            None => S3Provider::Aws,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            S3Provider::Aws => "aws",
+            S3Provider::Minio => "minio",
+            S3Provider::R2 => "r2",
+            S3Provider::Custom => "custom",
+        }
+    }
+
+    /// Whether `PutObject` ACLs and `PutBucketPolicy` are accepted.
+    pub fn supports_public_acls(self) -> bool {
+        !matches!(self, S3Provider::R2)
+    }
+
+    pub fn default_path_style(self) -> bool {
+        matches!(self, S3Provider::Minio | S3Provider::Custom)
+    }
+
+    /// `CreateBucket` only takes a location constraint on AWS outside us-east-1;
+    /// MinIO and R2 reject one that doesn't match their own region.
+    pub fn bucket_location_constraint(self, region: &str) -> Option<&str> {
+        (self == S3Provider::Aws && region != "us-east-1").then_some(region)
+    }
+}
+
+/// How file URLs in API responses are built (`FILE_URL_MODE`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileUrlMode {
+    /// The bucket's own endpoint URL; only reachable when objects are public
+    Direct,
+    /// `CDN_BASE_URL` + key for the default bucket; project buckets stay direct
+    Cdn { base: String },
+    /// Presigned GET URLs valid for `FILE_URL_PRESIGN_SECS`
+    Presigned { expires_in_secs: u64 },
+}
+
+impl FileUrlMode {
+    fn from_env() -> Self {
+        match env::var("FILE_URL_MODE").ok().as_deref() {
+            None | Some("direct") => FileUrlMode::Direct,
+            Some("cdn") => {
+                let base = env::var("CDN_BASE_URL")
+                    .ok()
+                    .filter(|v| !v.is_empty())
+                    .expect("CDN_BASE_URL must be set when FILE_URL_MODE=cdn");
+                FileUrlMode::Cdn { base: base.trim_end_matches('/').to_string() }
+            }
+            Some("presigned") => FileUrlMode::Presigned {
+                expires_in_secs: env::var("FILE_URL_PRESIGN_SECS")
+                    .ok()
+                    .and_then(|v| v.parse().ok())
+                    .filter(|n| *n > 0)
+                    .unwrap_or(3600),
+            },
+            Some(other) => panic!("Invalid FILE_URL_MODE '{}': expected direct, cdn or presigned", other),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FileUrlMode::Direct => "direct",
+            FileUrlMode::Cdn { .. } => "cdn",
+            FileUrlMode::Presigned { .. } => "presigned",
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct Config {
     pub database_url: String,
+    pub jwt_algorithm: JwtAlgorithm,
+    /// HMAC secret for HS256; empty with RS256, which does not read it
     pub jwt_secret: String,
+    /// PEM private key signing RS256 tokens (`JWT_PRIVATE_KEY_PATH`)
+    pub jwt_private_key_path: Option<String>,
+    /// PEM public key verifying RS256 tokens and published as JWKS (`JWT_PUBLIC_KEY_PATH`)
+    pub jwt_public_key_path: Option<String>,
+    /// PEM public key of the pair being rotated out; its tokens still verify (`JWT_PREVIOUS_PUBLIC_KEY_PATH`)
+    pub jwt_previous_public_key_path: Option<String>,
     pub aws_region: String,
     pub aws_access_key_id: String,
     pub aws_secret_access_key: String,
     pub s3_bucket_name: String,
     pub s3_endpoint: Option<String>,
+    pub s3_provider: S3Provider,
+    /// Path-style addressing (`S3_FORCE_PATH_STYLE`, defaults per provider)
+    pub s3_force_path_style: bool,
+    /// Uploaded objects are public-read (bucket policy + object ACL); off by default on R2
+    pub s3_public_objects: bool,
+    /// Whole-operation deadline for S3 calls, retries included (`S3_OPERATION_TIMEOUT_SECS`)
+    pub s3_operation_timeout_secs: u64,
+    pub s3_connect_timeout_secs: u64,
+    /// Consecutive storage failures that open the circuit breaker (0 disables it)
+    pub s3_circuit_breaker_threshold: u32,
+    /// How long an open breaker fails fast before letting a trial request through
+    pub s3_circuit_breaker_cooldown_secs: u64,
+    /// Key sealing per-project storage secrets (`STORAGE_CREDENTIALS_KEY`, base64 of 32 bytes)
+    pub storage_credentials_key: Option<[u8; secret_box::KEY_LEN]>,
+    /// Per-project S3 clients kept in memory (`STORAGE_CLIENT_CACHE_SIZE`)
+    pub storage_client_cache_size: usize,
+    /// Seconds a per-project S3 client is reused before its config is read again (0 = no cache)
+    pub storage_client_cache_ttl_secs: u64,
+    /// Endpoint hosts a project's bucket may use even though they resolve to a private address
+    pub storage_private_endpoint_hosts: Vec<String>,
+    pub file_url_mode: FileUrlMode,
+    /// Size of the default job pool; also used for every job type when no per-type pool is set
     pub worker_concurrency: usize,
+    /// Dedicated pool for CPU-bound image jobs (`WORKER_CONCURRENCY_IMAGE`)
+    pub worker_concurrency_image: Option<usize>,
+    /// Dedicated pool for IO-bound jobs such as fan-out and verification (`WORKER_CONCURRENCY_IO`)
+    pub worker_concurrency_io: Option<usize>,
+    /// Default and maximum `limit`, globally and per listing group (`PAGINATION_*`)
+    pub pagination: PaginationConfig,
+    /// Record worker lifecycle events in `job_events` (`JOB_EVENTS_ENABLED`)
+    pub job_events_enabled: bool,
+    pub api_key_expiry_notice_days: i64,
+    /// Key sealing project webhook secrets (`WEBHOOK_SECRET_KEY`, base64 of 32 bytes)
+    pub webhook_secret_key: Option<[u8; secret_box::KEY_LEN]>,
+    /// Deadline for one webhook POST, in seconds
+    pub webhook_timeout_secs: u64,
+    /// Tries per event before it is dropped, the first included
+    pub webhook_max_attempts: u32,
+    /// Wait before the first retry, in milliseconds; doubled after each failed try
+    pub webhook_retry_delay_ms: u64,
+    /// Webhook hosts allowed even though they resolve to a private address
+    pub webhook_private_hosts: Vec<String>,
+    pub auto_migrate: bool,
+    pub batch_upload_max_files: usize,
+    /// Largest `file` part an upload may carry, in bytes (`UPLOAD_MAX_PART_BYTES`)
+    pub upload_max_part_bytes: usize,
+    /// Max variants in a per-upload `variants` override on `POST /upload/image`
+    pub upload_override_max_variants: usize,
+    /// Largest width/height (or max_width/max_height) an override variant may ask for
+    pub upload_override_max_dimension: u32,
+    pub verify_inline_max_bytes: u64,
+    /// Projects with more images than this get their sync dry run as a background job
+    pub sync_dry_run_inline_max_files: u64,
+    /// Projects with more images than this do not get `auto_sync_on_settings_change` jobs
+    pub auto_sync_max_files: u64,
+    /// Original downloads per second for a `backfill` job (0 = unthrottled)
+    pub backfill_reads_per_sec: u32,
+    /// Object listing pages (up to 1000 keys each) per second for a `reconcile_storage` job (0 = unthrottled)
+    pub reconcile_list_pages_per_sec: u32,
+    /// Days between scheduled storage reconciliations of a project (0 = only on demand)
+    pub reconcile_interval_days: i64,
+    /// Drift, in bytes either way, above which a project is flagged on `GET /admin/storage/drift`
+    pub storage_drift_threshold_bytes: i64,
+    /// Days of `request_logs` kept by the cleanup service
+    pub request_log_retention_days: i64,
+    /// Days of `login_attempts` kept by the cleanup service
+    pub login_attempt_retention_days: i64,
+    /// Days a soft-deleted project is kept before the cleanup service purges it
+    pub project_trash_days: i64,
+    /// Days a deleted file is kept in the trash before the cleanup service purges it
+    /// (0 = no trash, files are deleted right away)
+    pub file_trash_days: i64,
+    /// Days tombstones of permanently deleted files answer `410` before they are pruned (0 = kept forever)
+    pub file_tombstone_retention_days: i64,
+    /// Days finished (`completed`/`failed`) jobs are kept (0 = kept forever)
+    pub job_history_days: i64,
+    /// Longest `?fallback=wait` hold on `GET /files/{id}/content`, in seconds (capped at 20)
+    pub variant_wait_timeout_secs: u64,
+    /// Budget for auth and JSON endpoints, in seconds (0 = no limit)
+    pub request_timeout_secs: u64,
+    /// Budget for the API-key upload routes, in seconds (0 = no limit)
+    pub upload_timeout_secs: u64,
+    /// Uploads handled at once by this instance; more get 429 (0 = unlimited)
+    pub upload_max_concurrent: usize,
+    /// Uploads handled at once per API key; more get 429 (0 = unlimited)
+    pub upload_max_concurrent_per_key: usize,
+    /// Seconds a resolved API key and its project are served from memory (0 = no cache)
+    pub api_key_cache_ttl_secs: u64,
+    pub api_key_cache_size: usize,
+    /// Seconds a user's `token_version` is served from memory by `auth_middleware` (0 = no cache)
+    pub token_version_cache_ttl_secs: u64,
+    /// Active refresh tokens kept per user; the oldest are revoked past this (0 = unlimited)
+    pub max_sessions_per_user: u64,
+    /// Days expired or revoked refresh tokens are kept before the cleanup service deletes them
+    pub refresh_token_grace_days: i64,
+    /// Lifetime of password reset tokens, in minutes
+    pub password_reset_ttl_mins: i64,
+    /// `POST /auth/forgot-password` prints the token to the log (there is no email sender)
+    pub password_reset_log_tokens: bool,
+    /// Rules for new passwords (`PASSWORD_MIN_LENGTH`, `PASSWORD_REQUIRE_*`)
+    pub password_policy: PasswordPolicy,
+    /// Issuer shown by authenticator apps for two-factor secrets (`TOTP_ISSUER`)
+    pub totp_issuer: String,
+    /// Key sealing two-factor secrets (`TOTP_SECRET_KEY`, base64 of 32 bytes)
+    pub totp_secret_key: Option<[u8; secret_box::KEY_LEN]>,
+    /// Shared secret gateways send to `POST /auth/introspect`; the endpoint is off while unset
+    pub introspection_secret: Option<String>,
+    /// Backend rendering variants (`IMAGE_PROCESSOR`); only `default` exists today
+    pub image_processor: String,
+    /// Variants may use `external_command` templates (`ALLOW_EXTERNAL_PROCESSORS`)
+    pub allow_external_processors: bool,
+    /// Command templates by lowercase name; settings can only reference these names
+    pub external_processors: HashMap<String, String>,
+    /// Input extensions each named processor accepts besides the formats sniffed from the
+    /// bytes (`EXTERNAL_PROCESSOR_INPUTS_<NAME>`, comma-separated)
+    pub external_processor_inputs: HashMap<String, Vec<String>>,
+    pub external_processor_timeout_secs: u64,
+    pub external_processor_max_output_bytes: u64,
+    pub docs_enabled: bool,
+    /// Send `X-MBK-Version: <version>+<commit>` on every response
+    pub version_header: bool,
+    /// `user:pass` required for the docs routes when set
+    pub docs_basic_auth: Option<String>,
     pub su_username: Option<String>,
     pub su_password: Option<String>,
 }
@@ -18,33 +306,389 @@ pub struct Config {
 impl Config {
     pub fn from_env() -> Self {
         let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-        let jwt_secret = env::var("JWT_SECRET").expect("JWT_SECRET must be set");
+        let jwt_algorithm = JwtAlgorithm::from_env();
+        let (jwt_secret, jwt_private_key_path, jwt_public_key_path) = match jwt_algorithm {
+            JwtAlgorithm::Hs256 => (load_jwt_secret(), None, None),
+            JwtAlgorithm::Rs256 => (
+                String::new(),
+                Some(env::var("JWT_PRIVATE_KEY_PATH").expect("JWT_PRIVATE_KEY_PATH must be set when JWT_ALGORITHM=RS256")),
+                Some(env::var("JWT_PUBLIC_KEY_PATH").expect("JWT_PUBLIC_KEY_PATH must be set when JWT_ALGORITHM=RS256")),
+            ),
+        };
+        let jwt_previous_public_key_path = match jwt_algorithm {
+            JwtAlgorithm::Hs256 => None,
+            JwtAlgorithm::Rs256 => env::var("JWT_PREVIOUS_PUBLIC_KEY_PATH").ok().filter(|p| !p.is_empty()),
+        };
         let aws_region = env::var("AWS_REGION").expect("AWS_REGION must be set");
         let aws_access_key_id = env::var("AWS_ACCESS_KEY_ID").expect("AWS_ACCESS_KEY_ID must be set");
         let aws_secret_access_key = env::var("AWS_SECRET_ACCESS_KEY").expect("AWS_SECRET_ACCESS_KEY must be set");
         let s3_bucket_name = env::var("S3_BUCKET_NAME").expect("S3_BUCKET_NAME must be set");
         let s3_endpoint = env::var("S3_ENDPOINT").ok();
+        let s3_provider = S3Provider::from_env(s3_endpoint.is_some());
+        if matches!(s3_provider, S3Provider::Minio | S3Provider::R2) && s3_endpoint.is_none() {
+            panic!("S3_ENDPOINT must be set when S3_PROVIDER={}", s3_provider.as_str());
+        }
         let su_username = env::var("SU_USERNAME").ok();
         let su_password = env::var("SU_PASSWORD").ok();
 
         Self {
             database_url,
+            jwt_algorithm,
             jwt_secret,
+            jwt_private_key_path,
+            jwt_public_key_path,
+            jwt_previous_public_key_path,
             aws_region,
             aws_access_key_id,
             aws_secret_access_key,
             s3_bucket_name,
             s3_endpoint,
+            s3_provider,
+            s3_force_path_style: env::var("S3_FORCE_PATH_STYLE")
+                .map(|v| v == "true")
+                .unwrap_or(s3_provider.default_path_style()),
+            s3_public_objects: env::var("S3_PUBLIC_OBJECTS")
+                .map(|v| v != "false")
+                .unwrap_or(s3_provider.supports_public_acls()),
+            s3_operation_timeout_secs: env::var("S3_OPERATION_TIMEOUT_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|n| *n > 0)
+                .unwrap_or(30),
+            s3_connect_timeout_secs: env::var("S3_CONNECT_TIMEOUT_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|n| *n > 0)
+                .unwrap_or(5),
+            s3_circuit_breaker_threshold: env::var("S3_CIRCUIT_BREAKER_THRESHOLD")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(5),
+            s3_circuit_breaker_cooldown_secs: env::var("S3_CIRCUIT_BREAKER_COOLDOWN_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|n| *n > 0)
+                .unwrap_or(30),
+            storage_credentials_key: load_secret_key("STORAGE_CREDENTIALS_KEY"),
+            file_url_mode: FileUrlMode::from_env(),
+            storage_client_cache_size: env::var("STORAGE_CLIENT_CACHE_SIZE")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|n| *n > 0)
+                .unwrap_or(64),
+            storage_client_cache_ttl_secs: env::var("STORAGE_CLIENT_CACHE_TTL_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(60),
+            storage_private_endpoint_hosts: env::var("STORAGE_PRIVATE_ENDPOINT_HOSTS")
+                .map(|v| {
+                    v.split(',')
+                        .map(|host| host.trim().to_lowercase())
+                        .filter(|host| !host.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default(),
             worker_concurrency: env::var("WORKER_CONCURRENCY")
                 .ok()
                 .and_then(|v| v.parse().ok())
                 .unwrap_or(1),
+            worker_concurrency_image: env::var("WORKER_CONCURRENCY_IMAGE")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|n| *n > 0),
+            worker_concurrency_io: env::var("WORKER_CONCURRENCY_IO")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|n| *n > 0),
+            job_events_enabled: env::var("JOB_EVENTS_ENABLED")
+                .map(|v| v == "true")
+                .unwrap_or(false),
+            pagination: PaginationConfig::from_env(),
+            api_key_expiry_notice_days: env::var("API_KEY_EXPIRY_NOTICE_DAYS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|d| *d > 0)
+                .unwrap_or(14),
+            webhook_secret_key: load_secret_key("WEBHOOK_SECRET_KEY"),
+            webhook_timeout_secs: env::var("WEBHOOK_TIMEOUT_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|n| *n > 0)
+                .unwrap_or(10),
+            webhook_max_attempts: env::var("WEBHOOK_MAX_ATTEMPTS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|n| *n > 0)
+                .unwrap_or(5),
+            webhook_retry_delay_ms: env::var("WEBHOOK_RETRY_DELAY_MS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(2000),
+            webhook_private_hosts: env::var("WEBHOOK_PRIVATE_HOSTS")
+                .map(|v| {
+                    v.split(',')
+                        .map(|host| host.trim().to_lowercase())
+                        .filter(|host| !host.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            auto_migrate: env::var("AUTO_MIGRATE")
+                .map(|v| v == "true")
+                .unwrap_or(false),
+            batch_upload_max_files: env::var("BATCH_UPLOAD_MAX_FILES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(10),
+            upload_max_part_bytes: env::var("UPLOAD_MAX_PART_BYTES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|n| *n > 0)
+                .unwrap_or(25 * 1024 * 1024),
+            upload_override_max_variants: env::var("UPLOAD_OVERRIDE_MAX_VARIANTS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(3),
+            upload_override_max_dimension: env::var("UPLOAD_OVERRIDE_MAX_DIMENSION")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(4096),
+            verify_inline_max_bytes: env::var("VERIFY_INLINE_MAX_BYTES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(10 * 1024 * 1024),
+            sync_dry_run_inline_max_files: env::var("SYNC_DRY_RUN_INLINE_MAX_FILES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(5000),
+            auto_sync_max_files: env::var("AUTO_SYNC_MAX_FILES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(1000),
+            backfill_reads_per_sec: env::var("BACKFILL_READS_PER_SEC")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(5),
+            reconcile_list_pages_per_sec: env::var("RECONCILE_LIST_PAGES_PER_SEC")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(2),
+            reconcile_interval_days: env::var("RECONCILE_INTERVAL_DAYS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|d| *d >= 0)
+                .unwrap_or(30),
+            storage_drift_threshold_bytes: env::var("STORAGE_DRIFT_THRESHOLD_BYTES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|n| *n >= 0)
+                .unwrap_or(100 * 1024 * 1024),
+            request_log_retention_days: env::var("REQUEST_LOG_RETENTION_DAYS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(14),
+            login_attempt_retention_days: env::var("LOGIN_ATTEMPT_RETENTION_DAYS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(30),
+            file_tombstone_retention_days: env::var("FILE_TOMBSTONE_RETENTION_DAYS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|n| *n >= 0)
+                .unwrap_or(365),
+            project_trash_days: env::var("PROJECT_TRASH_DAYS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|d| *d > 0)
+                .unwrap_or(30),
+            file_trash_days: env::var("FILE_TRASH_DAYS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|d| *d >= 0)
+                .unwrap_or(0),
+            job_history_days: env::var("JOB_HISTORY_DAYS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|d| *d >= 0)
+                .unwrap_or(0),
+            variant_wait_timeout_secs: env::var("VARIANT_WAIT_TIMEOUT_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|n| *n > 0)
+                .unwrap_or(10)
+                .min(20),
+            request_timeout_secs: env::var("REQUEST_TIMEOUT_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(30),
+            upload_timeout_secs: env::var("UPLOAD_TIMEOUT_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(300),
+            upload_max_concurrent: env::var("UPLOAD_MAX_CONCURRENT")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(64),
+            upload_max_concurrent_per_key: env::var("UPLOAD_MAX_CONCURRENT_PER_KEY")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(8),
+            api_key_cache_ttl_secs: env::var("API_KEY_CACHE_TTL_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(15),
+            api_key_cache_size: env::var("API_KEY_CACHE_SIZE")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|n| *n > 0)
+                .unwrap_or(1024),
+            token_version_cache_ttl_secs: env::var("TOKEN_VERSION_CACHE_TTL_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(15),
+            max_sessions_per_user: env::var("MAX_SESSIONS_PER_USER")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(10),
+            refresh_token_grace_days: env::var("REFRESH_TOKEN_GRACE_DAYS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|n| *n >= 0)
+                .unwrap_or(7),
+            password_reset_ttl_mins: env::var("PASSWORD_RESET_TTL_MINS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|n| *n > 0)
+                .unwrap_or(30),
+            password_reset_log_tokens: env::var("PASSWORD_RESET_LOG_TOKENS")
+                .map(|v| v == "true")
+                .unwrap_or(false),
+            password_policy: PasswordPolicy::from_env(),
+            totp_issuer: env::var("TOTP_ISSUER")
+                .ok()
+                .filter(|v| !v.is_empty())
+                .unwrap_or_else(|| "Media Blob Kit".to_string()),
+            totp_secret_key: load_secret_key("TOTP_SECRET_KEY"),
+            introspection_secret: env::var("INTROSPECTION_SECRET")
+                .ok()
+                .filter(|v| !v.is_empty()),
+            image_processor: env::var("IMAGE_PROCESSOR")
+                .ok()
+                .map(|v| v.trim().to_lowercase())
+                .filter(|v| !v.is_empty())
+                .unwrap_or_else(|| "default".to_string()),
+            allow_external_processors: env::var("ALLOW_EXTERNAL_PROCESSORS")
+                .map(|v| v == "true")
+                .unwrap_or(false),
+            external_processors: env::vars()
+                .filter_map(|(key, template)| {
+                    key.strip_prefix(EXTERNAL_PROCESSOR_PREFIX)
+                        .filter(|name| !name.is_empty())
+                        .map(|name| (name.to_lowercase(), template))
+                })
+                .collect(),
+            external_processor_inputs: env::vars()
+                .filter_map(|(key, list)| {
+                    let name = key.strip_prefix(EXTERNAL_PROCESSOR_INPUTS_PREFIX).filter(|name| !name.is_empty())?;
+                    Some((name.to_lowercase(), external_processor_inputs(&key, &list)))
+                })
+                .collect(),
+            external_processor_timeout_secs: env::var("EXTERNAL_PROCESSOR_TIMEOUT_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|n| *n > 0)
+                .unwrap_or(60),
+            external_processor_max_output_bytes: env::var("EXTERNAL_PROCESSOR_MAX_OUTPUT_BYTES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(50 * 1024 * 1024),
+            docs_enabled: env::var("DOCS_ENABLED")
+                .map(|v| v != "false")
+                .unwrap_or(true),
+            version_header: env::var("VERSION_HEADER_ENABLED")
+                .map(|v| v != "false")
+                .unwrap_or(true),
+            docs_basic_auth: env::var("DOCS_BASIC_AUTH")
+                .ok()
+                .filter(|v| !v.is_empty()),
             su_username,
             su_password,
         }
     }
 }
 
+impl Config {
+    /// First 4 bytes of the secret's SHA-256, so rotations can be verified from logs.
+    pub fn jwt_secret_fingerprint(&self) -> String {
+        let digest = Sha256::digest(self.jwt_secret.as_bytes());
+        digest[..4].iter().map(|b| format!("{:02x}", b)).collect()
+    }
+}
+
+/// A `secret_box` key from `var`; the features needing it are refused while unset, and a malformed key stops startup.
+fn load_secret_key(var: &str) -> Option<[u8; secret_box::KEY_LEN]> {
+    let encoded = env::var(var).ok().filter(|v| !v.is_empty())?;
+    let bytes = STANDARD
+        .decode(encoded.trim())
+        .unwrap_or_else(|_| panic!("{} must be base64", var));
+    let key: [u8; secret_box::KEY_LEN] = bytes
+        .try_into()
+        .unwrap_or_else(|_| panic!("{} must decode to {} bytes", var, secret_box::KEY_LEN));
+    Some(key)
+}
+
+/// Parses one `EXTERNAL_PROCESSOR_INPUTS_<NAME>` list; a scripted or vector extension stops startup.
+fn external_processor_inputs(var: &str, list: &str) -> Vec<String> {
+    let mut extensions: Vec<String> = list
+        .split(',')
+        .map(|ext| ext.trim().trim_start_matches('.').to_lowercase())
+        .filter(|ext| !ext.is_empty())
+        .collect();
+    if let Some(ext) = extensions.iter().find(|ext| scripted_input_extension(ext)) {
+        panic!("{} may not list '{}': it makes the tool run scripts or read other files", var, ext);
+    }
+    extensions.sort();
+    extensions.dedup();
+    extensions
+}
+
+pub(crate) fn scripted_input_extension(ext: &str) -> bool {
+    SCRIPTED_INPUT_EXTENSIONS.contains(&ext)
+}
+
+/// Reads the secret from `JWT_SECRET_FILE` (trailing newline stripped) or `JWT_SECRET`,
+/// refusing weak values unless `ALLOW_WEAK_JWT_SECRET=true`.
+fn load_jwt_secret() -> String {
+    let secret = match env::var("JWT_SECRET_FILE") {
+        Ok(path) => fs::read_to_string(&path)
+            .unwrap_or_else(|e| panic!("Failed to read JWT_SECRET_FILE '{}': {}", path, e))
+            .trim_end_matches(['\r', '\n'])
+            .to_string(),
+        Err(_) => env::var("JWT_SECRET").expect("JWT_SECRET or JWT_SECRET_FILE must be set"),
+    };
+
+    if let Some(reason) = jwt_secret_weakness(&secret) {
+        let allow_weak = env::var("ALLOW_WEAK_JWT_SECRET")
+            .map(|v| v == "true")
+            .unwrap_or(false);
+        if !allow_weak {
+            panic!("Refusing to start: JWT secret {}. Set ALLOW_WEAK_JWT_SECRET=true for local development only.", reason);
+        }
+        eprintln!("WARNING: JWT secret {} (allowed by ALLOW_WEAK_JWT_SECRET)", reason);
+    }
+
+    secret
+}
+
+pub(crate) fn jwt_secret_weakness(secret: &str) -> Option<String> {
+    if WEAK_JWT_SECRETS.contains(&secret.to_lowercase().as_str()) {
+        return Some("is a well-known default value".to_string());
+    }
+    if secret.len() < MIN_JWT_SECRET_LEN {
+        return Some(format!("is shorter than {} bytes", MIN_JWT_SECRET_LEN));
+    }
+    None
+}
+
 pub static CONFIG: OnceLock<Config> = OnceLock::new();
 
 pub fn get_config() -> &'static Config {
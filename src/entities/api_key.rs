@@ -11,9 +11,33 @@ pub struct Model {
     pub name: String,
     #[sea_orm(unique)]
     pub key_hash: String,
-    pub created_at: DateTime,
-    pub expires_at: Option<DateTime>,
+    pub created_at: DateTimeUtc,
+    pub expires_at: Option<DateTimeUtc>,
     pub is_active: bool,
+    /// Set once the "expiring soon" notice has gone out, so it is not repeated.
+    pub expiry_notified_at: Option<DateTimeUtc>,
+    /// Requests refused because this key was inactive or expired (written in batches)
+    pub failed_attempts: i64,
+    pub last_failed_at: Option<DateTimeUtc>,
+    /// `ApiKeyScope` names granted on top of uploading, e.g. `["delete"]`
+    pub scopes: Json,
+}
+
+/// Permissions a key needs to be granted explicitly. Uploading, listing jobs and `/whoami`
+/// need no scope.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiKeyScope {
+    /// `DELETE /files/{id}` for files of the key's project
+    Delete,
+}
+
+impl ApiKeyScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiKeyScope::Delete => "delete",
+        }
+    }
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -33,4 +57,16 @@ impl Related<super::project::Entity> for Entity {
     }
 }
 
+impl Model {
+    /// Granted scopes; unknown names (from a newer deployment) are skipped.
+    pub fn scopes(&self) -> Vec<ApiKeyScope> {
+        self.scopes
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|s| serde_json::from_value(s.clone()).ok())
+            .collect()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
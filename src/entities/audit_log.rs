@@ -0,0 +1,23 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "audit_logs")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i64,
+    /// User who performed the action; `None` for events no user can be attributed to
+    pub actor_user_id: Option<Uuid>,
+    /// Dotted event name such as `auth.login` or `api_key.delete`
+    pub action: String,
+    /// `user`, `project` or `api_key`
+    pub target_type: String,
+    pub target_id: Option<Uuid>,
+    pub metadata: Json,
+    pub created_at: DateTimeUtc,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
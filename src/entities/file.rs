@@ -1,4 +1,5 @@
 use sea_orm::entity::prelude::*;
+use sea_orm::{JoinType, QuerySelect};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
@@ -15,8 +16,28 @@ pub struct Model {
     pub size: i64,
     pub status: String, // uploaded, processing, ready, error
     pub variants_json: Json,
-    pub created_at: DateTime,
-    pub updated_at: DateTime,
+    /// Hex SHA-256 of the original upload (None for files stored before hashing was added)
+    pub content_hash: Option<String>,
+    /// Pseudo-folder, normalized with a trailing slash (`a/b/`); empty at the project root
+    pub folder: String,
+    /// Source file this one was generated from (poster, page render); deleting it deletes this
+    pub derived_from: Option<Uuid>,
+    /// `content_hash` of the original the current variants were generated from
+    pub variants_source_hash: Option<String>,
+    /// Last failure per variant name, `{"error", "failed_at"}`; cleared when the variant is generated
+    pub variant_errors: Json,
+    /// Pixel size per generated variant, `{"thumb": {"width", "height"}}`; replaced with `variants_json`
+    pub variant_dimensions: Json,
+    /// Hex SHA-256 per generated variant, `{"thumb": "9f86d0..."}`; replaced with `variants_json`.
+    /// A regenerated variant with the same hash is not uploaded again.
+    pub variant_hashes: Json,
+    pub created_at: DateTimeUtc,
+    pub updated_at: DateTimeUtc,
+    /// Bumped by `PATCH /files/{id}`; its `If-Match` precondition compares against it
+    pub version: i32,
+    /// Set when the file is moved to the trash; the cleanup service purges it after the
+    /// project's `file_trash_days`
+    pub deleted_at: Option<DateTimeUtc>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -36,4 +57,28 @@ impl Related<super::project::Entity> for Entity {
     }
 }
 
+impl Entity {
+    /// Files that may be listed, served or (re)processed.
+    ///
+    /// Excludes files in the trash (`files.deleted_at` set) and files whose project is in
+    /// the trash (`projects.deleted_at` set). Every read, content and processing path should
+    /// start from this rather than `find()`. Trashed files still occupy storage until purged,
+    /// so purge paths and storage accounting (project hard delete, cleanup, bucket diffs)
+    /// deliberately use `find()`.
+    pub fn find_active() -> Select<Entity> {
+        Entity::find()
+            .filter(Column::DeletedAt.is_null())
+            .join(JoinType::InnerJoin, Relation::Project.def())
+            .filter(super::project::Column::DeletedAt.is_null())
+    }
+}
+
+impl Model {
+    /// The original changed after its variants were generated. Unknown (no hash on
+    /// either side) counts as fresh.
+    pub fn variants_stale(&self) -> bool {
+        matches!((&self.content_hash, &self.variants_source_hash), (Some(current), Some(source)) if current != source)
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
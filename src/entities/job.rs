@@ -6,11 +6,14 @@ use serde::{Deserialize, Serialize};
 pub struct Model {
     #[sea_orm(primary_key, auto_increment = false)]
     pub id: Uuid,
-    pub file_id: Uuid,
+    /// Set for jobs about one file; project-level jobs have none
+    pub file_id: Option<Uuid>,
+    /// `None` only for a backfill across all projects
+    pub project_id: Option<Uuid>,
     pub status: String,
     pub payload: Json,
-    pub created_at: DateTime,
-    pub updated_at: DateTime,
+    pub created_at: DateTimeUtc,
+    pub updated_at: DateTimeUtc,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -23,6 +26,14 @@ pub enum Relation {
         on_delete = "Cascade"
     )]
     File,
+    #[sea_orm(
+        belongs_to = "super::project::Entity",
+        from = "Column::ProjectId",
+        to = "super::project::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    Project,
 }
 
 impl Related<super::file::Entity> for Entity {
@@ -31,4 +42,10 @@ impl Related<super::file::Entity> for Entity {
     }
 }
 
+impl Related<super::project::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Project.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
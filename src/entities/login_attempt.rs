@@ -0,0 +1,23 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "login_attempts")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i64,
+    /// Login value as sent: a username, or an email when it contains `@`
+    pub username: String,
+    /// Account the value resolved to; `None` when it matched none
+    pub user_id: Option<Uuid>,
+    pub success: bool,
+    /// `unknown_user`, `invalid_password`, `otp_required` or `invalid_otp`; `None` on success
+    pub failure_reason: Option<String>,
+    pub ip: Option<String>,
+    pub created_at: DateTimeUtc,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
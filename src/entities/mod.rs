@@ -1,7 +1,17 @@
 pub mod user;
 pub mod refresh_token;
+pub mod password_reset_token;
 pub mod project;
+pub mod project_member;
+pub mod project_settings_history;
+pub mod project_storage_config;
+pub mod project_webhook;
 pub mod api_key;
 pub mod file;
 pub mod job;
+pub mod job_event;
+pub mod request_log;
+pub mod audit_log;
+pub mod file_tombstone;
 
+pub mod login_attempt;
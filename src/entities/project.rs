@@ -11,9 +11,23 @@ pub struct Model {
     pub name: String,
     pub description: Option<String>,
     pub settings: Json,
-    pub created_at: DateTime,
-    pub updated_at: DateTime,
-    pub deleted_at: Option<DateTime>,
+    /// First path segment of every object key written for the project. Set once at
+    /// creation and never derived from `name`, so renames don't move new objects.
+    pub storage_prefix: String,
+    pub created_at: DateTimeUtc,
+    pub updated_at: DateTimeUtc,
+    pub deleted_at: Option<DateTimeUtc>,
+    /// When the last `reconcile_storage` job finished
+    pub last_reconciled_at: Option<DateTimeUtc>,
+    /// Bytes stored under the project prefix at that time
+    pub reconciled_bytes: Option<i64>,
+    /// Stored bytes, variants excluded, minus `SUM(files.size)`; negative when objects are missing
+    pub reconciled_drift_bytes: Option<i64>,
+    /// Bumped by `PUT /projects/{id}` and settings rollbacks; their `If-Match` precondition compares against it
+    pub version: i32,
+    /// Set by `POST /admin/projects/{id}/suspend`: API keys are refused and jobs are not claimed
+    /// until it is cleared. Unlike `deleted_at`, nothing is ever purged because of it.
+    pub suspended_at: Option<DateTimeUtc>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
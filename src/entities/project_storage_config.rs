@@ -0,0 +1,42 @@
+use sea_orm::entity::prelude::*;
+use uuid::Uuid;
+
+/// A project's own bucket and credentials. Not `Serialize`: the sealed secret must only
+/// ever leave through `services::project_storage`.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "project_storage_configs")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub project_id: Uuid,
+    pub bucket: String,
+    pub region: String,
+    pub endpoint: Option<String>,
+    pub access_key_id: String,
+    /// `secret_box`-sealed secret access key, bound to `project_id`
+    pub secret_access_key_sealed: String,
+    pub force_path_style: bool,
+    pub public_objects: bool,
+    /// Last successful probe (test put/delete) against the bucket
+    pub verified_at: Option<DateTimeUtc>,
+    pub created_at: DateTimeUtc,
+    pub updated_at: DateTimeUtc,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::project::Entity",
+        from = "Column::ProjectId",
+        to = "super::project::Column::Id",
+        on_delete = "Cascade"
+    )]
+    Project,
+}
+
+impl Related<super::project::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Project.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -10,9 +10,14 @@ pub struct Model {
     pub user_id: Uuid,
     #[sea_orm(unique)]
     pub token_hash: String,
-    pub expires_at: DateTime,
-    pub created_at: DateTime,
+    pub expires_at: DateTimeUtc,
+    pub created_at: DateTimeUtc,
     pub revoked: bool,
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+    /// Same for a login's token and every token rotated from it; presenting a revoked
+    /// token revokes the whole family
+    pub family_id: Uuid,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
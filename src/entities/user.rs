@@ -11,7 +11,27 @@ pub struct Model {
     pub username: String,
     pub password: String,
     pub role: Role,
-    pub created_at: DateTime,
+    pub created_at: DateTimeUtc,
+    /// Optional login alternative to `username`; unique ignoring case (`idx_users_email_lower`)
+    pub email: Option<String>,
+    /// Only `/auth/change-password` is allowed until the user changes their password
+    pub must_change_password: bool,
+    /// Base32 TOTP secret sealed with `TOTP_SECRET_KEY`, set by `/auth/2fa/enable`
+    #[serde(skip_serializing)]
+    pub totp_secret: Option<String>,
+    /// Set once `/auth/2fa/verify` confirmed the secret; login then requires `otp`
+    pub totp_enabled_at: Option<DateTimeUtc>,
+    /// SHA-256 hex of the unused recovery codes
+    #[serde(skip_serializing)]
+    pub totp_recovery_codes: Json,
+    /// 30-second step of the last accepted code, so each code works only once
+    #[serde(skip_serializing)]
+    pub totp_last_step: Option<i64>,
+    /// Last successful `/auth/login`; `None` if the user never signed in
+    pub last_login_at: Option<DateTimeUtc>,
+    pub login_count: i64,
+    /// Copied into access tokens; `auth_middleware` rejects tokens carrying an older value
+    pub token_version: i32,
 }
 
 #[derive(EnumIter, DeriveActiveEnum, Clone, Debug, PartialEq, Eq, Deserialize, Serialize, utoipa::ToSchema)]
@@ -23,6 +43,25 @@ pub enum Role {
     Admin,
     #[sea_orm(string_value = "user")]
     User,
+    /// Read-only access to all projects, files and jobs
+    #[sea_orm(string_value = "viewer")]
+    Viewer,
+}
+
+impl Role {
+    /// Privilege order used by `require_role_at_least`: Viewer < User < Admin < Su.
+    pub fn rank(&self) -> u8 {
+        match self {
+            Role::Viewer => 0,
+            Role::User => 1,
+            Role::Admin => 2,
+            Role::Su => 3,
+        }
+    }
+
+    pub fn is_at_least(&self, min: &Role) -> bool {
+        self.rank() >= min.rank()
+    }
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
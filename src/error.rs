@@ -1,45 +1,100 @@
 use axum::{
-    http::StatusCode,
+    http::{header, StatusCode},
     response::{IntoResponse, Response, Json},
 };
 use serde_json::json;
+use crate::services::password_policy::PasswordViolation;
 
 #[derive(Debug)]
 pub enum AppError {
     DatabaseError(sea_orm::DbErr),
     NotFound(String),
     Unauthorized(String),
+    /// 401 whose body also carries a stable `code` clients can match on
+    UnauthorizedWithCode(&'static str, String),
     BadRequest(String),
     InternalServerError(String),
     Conflict(String),
+    /// 409 whose body also carries a stable `code` clients can match on
+    ConflictWithCode(&'static str, String),
     Forbidden(String),
+    /// 403 whose body also carries a stable `code` clients can match on
+    ForbiddenWithCode(&'static str, String),
+    UnsupportedMediaType(String),
+    UnprocessableEntity(String),
+    /// 422 listing every password policy rule the new password broke
+    WeakPassword(Vec<PasswordViolation>),
+    ServiceUnavailable(String),
+    /// 413 with a stable `code`, for an upload part or body over its limit
+    PayloadTooLarge(&'static str, String),
+    /// 429 with a stable `code` and `Retry-After: 1`
+    TooManyRequests(&'static str, String),
+    GatewayTimeout(String),
+    /// 410 for a permanently deleted resource, with when and why it was deleted
+    Gone { message: String, deleted_at: chrono::DateTime<chrono::Utc>, reason: String },
+    /// 412 when an `If-Match` / `expected_version` no longer matches, with the version now stored
+    PreconditionFailed { message: String, current_version: i32 },
 }
 
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
+        let code = match &self {
+            AppError::UnauthorizedWithCode(code, _)
+            | AppError::ForbiddenWithCode(code, _)
+            | AppError::ConflictWithCode(code, _)
+            | AppError::PayloadTooLarge(code, _)
+            | AppError::TooManyRequests(code, _) => Some(*code),
+            AppError::WeakPassword(_) => Some("weak_password"),
+            AppError::Gone { .. } => Some("gone"),
+            AppError::PreconditionFailed { .. } => Some("version_mismatch"),
+            _ => None,
+        };
+
         let (status, error_message) = match &self {
             AppError::DatabaseError(e) => {
                 eprintln!("Database error: {}", e);
                 (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error".to_string())
             }
             AppError::NotFound(msg) => (StatusCode::NOT_FOUND, msg.clone()),
-            AppError::Unauthorized(msg) => (StatusCode::UNAUTHORIZED, msg.clone()),
+            AppError::Unauthorized(msg) | AppError::UnauthorizedWithCode(_, msg) => (StatusCode::UNAUTHORIZED, msg.clone()),
             AppError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg.clone()),
             AppError::InternalServerError(msg) => {
                 eprintln!("Internal server error: {}", msg);
                 (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error".to_string())
             }
-            AppError::Conflict(msg) => (StatusCode::CONFLICT, msg.clone()),
-            AppError::Forbidden(msg) => (StatusCode::FORBIDDEN, msg.clone()),
+            AppError::Conflict(msg) | AppError::ConflictWithCode(_, msg) => (StatusCode::CONFLICT, msg.clone()),
+            AppError::Forbidden(msg) | AppError::ForbiddenWithCode(_, msg) => (StatusCode::FORBIDDEN, msg.clone()),
+            AppError::UnsupportedMediaType(msg) => (StatusCode::UNSUPPORTED_MEDIA_TYPE, msg.clone()),
+            AppError::UnprocessableEntity(msg) => (StatusCode::UNPROCESSABLE_ENTITY, msg.clone()),
+            AppError::WeakPassword(_) => (StatusCode::UNPROCESSABLE_ENTITY, "Password does not meet the password policy".to_string()),
+            AppError::ServiceUnavailable(msg) => (StatusCode::SERVICE_UNAVAILABLE, msg.clone()),
+            AppError::PayloadTooLarge(_, msg) => (StatusCode::PAYLOAD_TOO_LARGE, msg.clone()),
+            AppError::TooManyRequests(_, msg) => (StatusCode::TOO_MANY_REQUESTS, msg.clone()),
+            AppError::GatewayTimeout(msg) => (StatusCode::GATEWAY_TIMEOUT, msg.clone()),
+            AppError::Gone { message, .. } => (StatusCode::GONE, message.clone()),
+            AppError::PreconditionFailed { message, .. } => (StatusCode::PRECONDITION_FAILED, message.clone()),
         };
 
         // Log all errors with status code
         println!("Error | res={} | {}", status.as_u16(), error_message);
 
-        let body = Json(json!({
-            "error": error_message,
-        }));
+        let body = match (code, &self) {
+            (Some(code), AppError::WeakPassword(violations)) => {
+                Json(json!({ "error": error_message, "code": code, "violations": violations }))
+            }
+            (Some(code), AppError::Gone { deleted_at, reason, .. }) => {
+                Json(json!({ "error": error_message, "code": code, "deleted_at": deleted_at, "reason": reason }))
+            }
+            (Some(code), AppError::PreconditionFailed { current_version, .. }) => {
+                Json(json!({ "error": error_message, "code": code, "current_version": current_version }))
+            }
+            (Some(code), _) => Json(json!({ "error": error_message, "code": code })),
+            (None, _) => Json(json!({ "error": error_message })),
+        };
 
+        if matches!(self, AppError::TooManyRequests(..)) {
+            return (status, [(header::RETRY_AFTER, "1")], body).into_response();
+        }
         (status, body).into_response()
     }
 }
@@ -49,11 +104,25 @@ impl std::fmt::Display for AppError {
         match self {
             AppError::DatabaseError(e) => write!(f, "Database error: {}", e),
             AppError::NotFound(msg) => write!(f, "Not found: {}", msg),
-            AppError::Unauthorized(msg) => write!(f, "Unauthorized: {}", msg),
+            AppError::Unauthorized(msg) | AppError::UnauthorizedWithCode(_, msg) => write!(f, "Unauthorized: {}", msg),
             AppError::BadRequest(msg) => write!(f, "Bad request: {}", msg),
             AppError::InternalServerError(msg) => write!(f, "Internal server error: {}", msg),
-            AppError::Conflict(msg) => write!(f, "Conflict: {}", msg),
-            AppError::Forbidden(msg) => write!(f, "Forbidden: {}", msg),
+            AppError::Conflict(msg) | AppError::ConflictWithCode(_, msg) => write!(f, "Conflict: {}", msg),
+            AppError::Forbidden(msg) | AppError::ForbiddenWithCode(_, msg) => write!(f, "Forbidden: {}", msg),
+            AppError::UnsupportedMediaType(msg) => write!(f, "Unsupported media type: {}", msg),
+            AppError::UnprocessableEntity(msg) => write!(f, "Unprocessable entity: {}", msg),
+            AppError::WeakPassword(violations) => {
+                let rules: Vec<&str> = violations.iter().map(|v| v.rule).collect();
+                write!(f, "Weak password: {}", rules.join(", "))
+            }
+            AppError::ServiceUnavailable(msg) => write!(f, "Service unavailable: {}", msg),
+            AppError::PayloadTooLarge(_, msg) => write!(f, "Payload too large: {}", msg),
+            AppError::TooManyRequests(_, msg) => write!(f, "Too many requests: {}", msg),
+            AppError::GatewayTimeout(msg) => write!(f, "Gateway timeout: {}", msg),
+            AppError::Gone { message, reason, .. } => write!(f, "Gone: {} ({})", message, reason),
+            AppError::PreconditionFailed { message, current_version } => {
+                write!(f, "Precondition failed: {} (current version {})", message, current_version)
+            }
         }
     }
 }
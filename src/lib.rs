@@ -0,0 +1,15 @@
+//! Media Blob Kit as a library: the server binary is a thin wrapper around
+//! [`create_app`], which embedders can mount or drive in-process.
+
+pub mod entities;
+pub mod routes;
+pub mod middleware;
+pub mod config;
+pub mod error;
+pub mod pagination;
+pub mod services;
+pub mod models;
+pub mod utils;
+pub mod build_info;
+
+pub use routes::{create_app, create_routes, AppState};
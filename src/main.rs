@@ -1,24 +1,15 @@
-mod entities;
-mod routes;
-mod middleware;
-pub mod config;
-mod error;
-mod pagination;
-pub mod services;
-pub mod models;
-pub mod utils;
-
-
-
 use argon2::{
     password_hash::{rand_core::OsRng, PasswordHasher, SaltString},
     Argon2,
 };
 use clap::{Parser, Subcommand};
-use entities::user;
+use std::io::{self, BufRead, IsTerminal, Write};
+use media_blob_kit::entities::{project, user};
+use media_blob_kit::models::settings::{variant_name_problems, ProjectSettings};
+use media_blob_kit::{config, create_routes, services};
+use media_blob_kit::services::password_policy::PasswordPolicy;
 use migration::{Migrator, MigratorTrait};
-use routes::create_routes;
-use sea_orm::{ActiveModelTrait, ColumnTrait, Database, EntityTrait, QueryFilter, Set};
+use sea_orm::{ActiveModelTrait, ColumnTrait, ConnectOptions, ConnectionTrait, Database, DbErr, EntityTrait, QueryFilter, QueryOrder, Set, Statement};
 use uuid::Uuid;
 
 #[derive(Parser)]
@@ -39,20 +30,60 @@ enum Commands {
         #[arg(short, long)]
         username: String,
     },
+    /// Check configuration, database, storage, worker and external tools (exits 1 on failures)
+    Doctor {
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Inspect stored project settings
+    Settings {
+        #[command(subcommand)]
+        command: SettingsCommands,
+    },
+}
+
+#[derive(Subcommand)]
+enum SettingsCommands {
+    /// Report projects whose variant names are rejected by current validation (exits 1 if any)
+    Lint,
 }
 
 #[tokio::main]
 async fn main() {
     dotenvy::dotenv().ok();
+    let cli = Cli::parse();
+
+    // Runs before the config is loaded, so a bad config is reported instead of panicking
+    if let Some(Commands::Doctor { json }) = &cli.command {
+        let report = services::doctor::run().await;
+        if *json {
+            println!("{}", serde_json::to_string_pretty(&report).expect("report serializes"));
+        } else {
+            report.print_table();
+        }
+        std::process::exit(if report.failures > 0 { 1 } else { 0 });
+    }
+
     // Initialize config
     let config = config::get_config();
+    println!("MediaBlobKit {} | commit={}", media_blob_kit::build_info::VERSION, media_blob_kit::build_info::COMMIT);
+    match services::jwt::keys().kid() {
+        Some(kid) => println!("JWT signing: {} | kid={}", config.jwt_algorithm.as_str(), kid),
+        None => println!("JWT secret fingerprint: {}", config.jwt_secret_fingerprint()),
+    }
+    println!(
+        "S3 provider: {} | path_style={} | public_objects={} | file_urls={}",
+        config.s3_provider.as_str(),
+        config.s3_force_path_style,
+        config.s3_public_objects,
+        config.file_url_mode.as_str()
+    );
     
     let db = Database::connect(&config.database_url)
         .await
         .expect("Failed to connect to database");
 
-    let cli = Cli::parse();
-
     match &cli.command {
         Some(Commands::Migrate) => {
             Migrator::up(&db, None).await.expect("Migration failed");
@@ -62,8 +93,21 @@ async fn main() {
             Migrator::refresh(&db).await.expect("Migration refresh failed");
             println!("Database reset successfully");
         }
+        Some(Commands::Settings { command: SettingsCommands::Lint }) => {
+            match lint_settings(&db).await {
+                Ok(0) => println!("Settings lint | All project settings pass"),
+                Ok(affected) => {
+                    println!("Settings lint | {} project(s) need their variant names fixed", affected);
+                    std::process::exit(1);
+                }
+                Err(e) => {
+                    eprintln!("Settings lint | Failed to load projects: {}", e);
+                    std::process::exit(2);
+                }
+            }
+        }
         Some(Commands::CreateSuperuser { username }) => {
-            let password = rpassword::prompt_password("Enter password: ").unwrap();
+            let password = prompt_new_password(&config.password_policy);
             let salt = SaltString::generate(&mut OsRng);
             let argon2 = Argon2::default();
             let password_hash = argon2
@@ -76,8 +120,16 @@ async fn main() {
                 username: Set(username.clone()),
                 password: Set(password_hash),
                 role: Set(user::Role::Su),
-                created_at: Set(chrono::Utc::now().naive_utc()),
-                ..Default::default()
+                created_at: Set(chrono::Utc::now()),
+                email: Set(None),
+                must_change_password: Set(false),
+                totp_secret: Set(None),
+                totp_enabled_at: Set(None),
+                totp_recovery_codes: Set(serde_json::json!([])),
+                totp_last_step: Set(None),
+                last_login_at: Set(None),
+                login_count: Set(0),
+                token_version: Set(0),
             };
 
             match user.insert(&db).await {
@@ -85,7 +137,15 @@ async fn main() {
                 Err(e) => eprintln!("Failed to create superuser: {}", e),
             }
         }
+        Some(Commands::Doctor { .. }) => unreachable!("handled before connecting"),
         None => {
+            if config.auto_migrate {
+                if let Err(e) = auto_migrate(&config.database_url).await {
+                    eprintln!("AUTO_MIGRATE failed, refusing to start: {}", e);
+                    std::process::exit(1);
+                }
+            }
+
             // build our application using the routes module
             let app = create_routes(db.clone())
                 .layer(tower_http::cors::CorsLayer::permissive());
@@ -98,7 +158,11 @@ async fn main() {
                     .await
                     .expect("Failed to check for existing user");
 
-                if user_exists.is_none() {
+                let violations = config.password_policy.violations(password);
+                if user_exists.is_none() && !violations.is_empty() {
+                    let rules: Vec<&str> = violations.iter().map(|v| v.rule).collect();
+                    eprintln!("Not auto-creating superuser '{}': SU_PASSWORD breaks the password policy ({})", username, rules.join(", "));
+                } else if user_exists.is_none() {
                     let salt = SaltString::generate(&mut OsRng);
                     let argon2 = Argon2::default();
                     let password_hash = argon2
@@ -111,8 +175,16 @@ async fn main() {
                         username: Set(username.clone()),
                         password: Set(password_hash),
                         role: Set(user::Role::Su),
-                        created_at: Set(chrono::Utc::now().naive_utc()),
-                        ..Default::default()
+                        created_at: Set(chrono::Utc::now()),
+                        email: Set(None),
+                        must_change_password: Set(false),
+                        totp_secret: Set(None),
+                        totp_enabled_at: Set(None),
+                        totp_recovery_codes: Set(serde_json::json!([])),
+                        totp_last_step: Set(None),
+                        last_login_at: Set(None),
+                        login_count: Set(0),
+                        token_version: Set(0),
                     };
 
                     match user.insert(&db).await {
@@ -141,7 +213,185 @@ async fn main() {
             // run our app with hyper, listening globally on port 3000
             let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
             println!("Listening on {}", listener.local_addr().unwrap());
-            axum::serve(listener, app).await.unwrap();
+            axum::serve(listener, app.into_make_service_with_connect_info::<std::net::SocketAddr>())
+                .await
+                .unwrap();
+        }
+    }
+}
+
+/// Prints one line per problem in each project's variant names (trashed projects included,
+/// since restoring them brings their settings back) and returns the number of affected projects.
+async fn lint_settings(db: &sea_orm::DatabaseConnection) -> Result<usize, DbErr> {
+    let mut affected = 0;
+    for p in project::Entity::find().order_by_asc(project::Column::Name).all(db).await? {
+        let problems = match serde_json::from_value::<ProjectSettings>(p.settings) {
+            Ok(settings) => variant_name_problems(settings.variants.iter().flat_map(|v| v.keys())),
+            Err(e) => vec![format!("settings do not parse: {}", e)],
+        };
+        if problems.is_empty() {
+            continue;
+        }
+        affected += 1;
+        for problem in problems {
+            println!("Settings lint | project={} ({}) | {}", p.name, p.id, problem);
+        }
+    }
+    Ok(affected)
+}
+
+/// Arbitrary key shared by every replica for `pg_advisory_lock` around startup migrations.
+const MIGRATION_LOCK_KEY: i64 = 0x6d62_6b5f_6d69_6772; // "mbk_migr"
+
+/// Applies pending migrations before serving, holding a Postgres advisory lock so replicas
+/// starting together migrate one at a time (the others find nothing pending).
+async fn auto_migrate(database_url: &str) -> Result<(), DbErr> {
+    // Session-level advisory locks belong to a connection, so use a dedicated single-connection pool
+    let mut options = ConnectOptions::new(database_url.to_owned());
+    options.max_connections(1).min_connections(1);
+    let conn = Database::connect(options).await?;
+    let backend = conn.get_database_backend();
+
+    println!("Auto-migrate | Waiting for migration lock");
+    conn.execute(Statement::from_string(backend, format!("SELECT pg_advisory_lock({})", MIGRATION_LOCK_KEY)))
+        .await?;
+
+    let result = async {
+        let pending = Migrator::get_pending_migrations(&conn).await?;
+        if pending.is_empty() {
+            println!("Auto-migrate | Schema is up to date");
+            return Ok(());
+        }
+
+        let names: Vec<String> = pending.iter().map(|m| m.name().to_string()).collect();
+        Migrator::up(&conn, None).await?;
+        println!("Auto-migrate | Applied {} migrations: {}", names.len(), names.join(", "));
+        Ok(())
+    }
+    .await;
+
+    let unlock = conn
+        .execute(Statement::from_string(backend, format!("SELECT pg_advisory_unlock({})", MIGRATION_LOCK_KEY)))
+        .await;
+    let _ = conn.close().await;
+
+    result.and(unlock.map(|_| ()))
+}
+
+/// Asks for the superuser password until one meets the policy: hidden on a terminal, read
+/// line by line when stdin is piped (`echo "$PASSWORD" | media-blob-kit create-superuser --username ...`).
+fn prompt_new_password(policy: &PasswordPolicy) -> String {
+    let stdout = &mut io::stdout();
+    let result = if io::stdin().is_terminal() {
+        ask_new_password(policy, stdout, |_| rpassword::prompt_password("Enter password: ").map(Some))
+    } else {
+        read_new_password(policy, &mut io::stdin().lock(), stdout)
+    };
+    match result {
+        Ok(Some(password)) => password,
+        Ok(None) => {
+            eprintln!("No password meeting the requirements was given");
+            std::process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("Failed to read password: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// [`ask_new_password`] taking one password per line of `input`; `None` once it runs out.
+fn read_new_password(policy: &PasswordPolicy, input: &mut impl BufRead, output: &mut impl Write) -> io::Result<Option<String>> {
+    ask_new_password(policy, output, |output| {
+        write!(output, "Enter password: ")?;
+        output.flush()?;
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        Ok(Some(line.trim_end_matches(['\r', '\n']).to_string()))
+    })
+}
+
+/// Prints the requirements, then calls `read` until it returns a password meeting them (or
+/// `None`), listing the broken rules after each attempt.
+fn ask_new_password<W: Write>(
+    policy: &PasswordPolicy,
+    output: &mut W,
+    mut read: impl FnMut(&mut W) -> io::Result<Option<String>>,
+) -> io::Result<Option<String>> {
+    writeln!(output, "Password requirements: {}", policy.describe())?;
+    while let Some(password) = read(output)? {
+        let violations = policy.violations(&password);
+        if violations.is_empty() {
+            return Ok(Some(password));
+        }
+        for violation in violations {
+            writeln!(output, "  - {}", violation.message)?;
         }
     }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> PasswordPolicy {
+        PasswordPolicy {
+            min_length: 8,
+            require_uppercase: true,
+            require_lowercase: false,
+            require_digit: true,
+            require_symbol: false,
+        }
+    }
+
+    fn read(input: &str) -> (Option<String>, String) {
+        let mut output = Vec::new();
+        let password = read_new_password(&policy(), &mut input.as_bytes(), &mut output).unwrap();
+        (password, String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn accepts_the_first_valid_password() {
+        let (password, output) = read("Passw0rdX\nignored\n");
+        assert_eq!(password.as_deref(), Some("Passw0rdX"));
+        assert!(output.starts_with("Password requirements: at least 8 characters, an uppercase letter, a digit\n"));
+        assert_eq!(output.matches("Enter password: ").count(), 1);
+    }
+
+    #[test]
+    fn asks_again_until_the_policy_is_met() {
+        let (password, output) = read("short\nlongbutweak\nLongButWeak\nLongAndStr0ng\n");
+        assert_eq!(password.as_deref(), Some("LongAndStr0ng"));
+        assert_eq!(output.matches("Enter password: ").count(), 4);
+        assert_eq!(output.matches("Must be at least 8 characters long").count(), 1);
+        assert_eq!(output.matches("Must contain an uppercase letter").count(), 2);
+        assert_eq!(output.matches("Must contain a digit").count(), 3);
+    }
+
+    #[test]
+    fn boundary_lengths() {
+        assert_eq!(read("Abcdef1\n").0, None);
+        assert_eq!(read("Abcdef12\n").0.as_deref(), Some("Abcdef12"));
+        // Characters, not bytes: seven of them in fourteen bytes are still too short
+        assert_eq!(read("Ééééé1é\n").0, None);
+        assert_eq!(read("Éééééé1é\n").0.as_deref(), Some("Éééééé1é"));
+    }
+
+    #[test]
+    fn line_endings_are_not_part_of_the_password() {
+        assert_eq!(read("Passw0rdX\r\n").0.as_deref(), Some("Passw0rdX"));
+        // Other whitespace is
+        assert_eq!(read(" Passw0rd \n").0.as_deref(), Some(" Passw0rd "));
+    }
+
+    #[test]
+    fn gives_up_when_input_runs_out() {
+        let (password, output) = read("weak\n");
+        assert_eq!(password, None);
+        assert_eq!(output.matches("Enter password: ").count(), 2);
+        assert_eq!(read("").0, None);
+    }
 }
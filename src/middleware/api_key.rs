@@ -2,13 +2,15 @@ use axum::{
     extract::Request,
     http::HeaderMap,
     middleware::Next,
-    response::Response,
+    response::{IntoResponse, Response},
 };
 use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
 use sha2::{Digest, Sha256};
-use crate::entities::api_key::{self, Entity as ApiKey};
+use crate::entities::api_key::{self, ApiKeyScope, Entity as ApiKey};
 use crate::entities::project::Entity as Project;
 use crate::error::AppError;
+use crate::services::key_cache;
+use crate::services::key_failures::KeyFailureRecorder;
 
 use crate::models::settings::ProjectSettings;
 
@@ -17,25 +19,50 @@ pub struct ProjectContext {
     pub id: uuid::Uuid,
     pub name: String,
     pub settings: ProjectSettings,
+    pub storage_prefix: String,
+    /// The API key that authenticated this request
+    pub api_key_id: uuid::Uuid,
+    pub api_key_name: String,
+    pub api_key_expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub api_key_scopes: Vec<ApiKeyScope>,
 }
 
+/// State for `api_key_auth`.
+#[derive(Clone)]
+pub struct ApiKeyAuthState {
+    pub db: DatabaseConnection,
+    pub failures: KeyFailureRecorder,
+}
+
+/// Prefix of every issued key; anything else in the header cannot be a key.
+const KEY_PREFIX: &str = "mbk_";
+
+/// 401s carry a stable `code` (`missing_key`, `malformed_key`, `unknown_key`, `inactive_key`,
+/// `expired_key`) next to the message. Inactive and expired attempts are also counted on the key.
+/// Keys of a suspended project get a 403 with code `project_suspended`.
+///
+/// Resolved keys come from `key_cache` when fresh; the checks below still run on every request.
 pub async fn api_key_auth(
-    axum::extract::State(db): axum::extract::State<DatabaseConnection>,
+    axum::extract::State(state): axum::extract::State<ApiKeyAuthState>,
     headers: HeaderMap,
     mut request: Request,
     next: Next,
 ) -> Result<Response, AppError> {
+    let ApiKeyAuthState { db, failures } = state;
     let method = request.method().to_string();
     let uri = request.uri().to_string();
 
     let api_key_header = match headers.get("x-api-key") {
-        Some(header) => header.to_str().map_err(|_| {
-            println!("Auth | {} {} | res=401 | Invalid API Key format", method, uri);
-            AppError::Unauthorized("Invalid API Key format".to_string())
-        })?,
+        Some(header) => match header.to_str() {
+            Ok(value) if value.starts_with(KEY_PREFIX) => value,
+            _ => {
+                println!("Auth | {} {} | res=401 | Invalid API Key format", method, uri);
+                return Err(AppError::UnauthorizedWithCode("malformed_key", "Invalid API Key format".to_string()));
+            }
+        },
         None => {
             println!("Auth | {} {} | res=401 | Missing API Key", method, uri);
-            return Err(AppError::Unauthorized("Missing API Key".to_string()));
+            return Err(AppError::UnauthorizedWithCode("missing_key", "Missing API Key".to_string()));
         }
     };
 
@@ -43,39 +70,59 @@ pub async fn api_key_auth(
     hasher.update(api_key_header.as_bytes());
     let key_hash = format!("{:x}", hasher.finalize());
 
-    // Find API Key and related Project
-    let result = ApiKey::find()
-        .filter(api_key::Column::KeyHash.eq(&key_hash))
-        .find_also_related(Project)
-        .one(&db)
-        .await
-        .map_err(AppError::DatabaseError)?;
-
-    let (api_key, project) = match result {
-        Some(r) => r,
+    let (api_key, project) = match key_cache::get(&key_hash) {
+        Some(cached) => cached,
         None => {
-            println!("Auth | {} {} | res=401 | Invalid API Key", method, uri);
-            return Err(AppError::Unauthorized("Invalid API Key".to_string()));
-        }
-    };
+            // Find API Key and related Project
+            let result = ApiKey::find()
+                .filter(api_key::Column::KeyHash.eq(&key_hash))
+                .find_also_related(Project)
+                .one(&db)
+                .await
+                .map_err(AppError::DatabaseError)?;
 
-    let project = match project {
-        Some(p) => p,
-        None => {
-            println!("Auth | {} {} | res=500 | Orphaned API Key", method, uri);
-            return Err(AppError::InternalServerError("Orphaned API Key".to_string()));
+            match result {
+                Some((api_key, Some(project))) => {
+                    key_cache::put(&key_hash, &api_key, &project);
+                    (api_key, project)
+                }
+                Some((_, None)) => {
+                    println!("Auth | {} {} | res=500 | Orphaned API Key", method, uri);
+                    return Err(AppError::InternalServerError("Orphaned API Key".to_string()));
+                }
+                None => {
+                    println!("Auth | {} {} | res=401 | Invalid API Key", method, uri);
+                    return Err(AppError::UnauthorizedWithCode("unknown_key", "Invalid API Key".to_string()));
+                }
+            }
         }
     };
 
+    // Trashed projects are purged by the cleanup service, so nothing may be uploaded
+    // into them. Restoring clears `deleted_at` and the keys work again.
+    if project.deleted_at.is_some() {
+        println!("Auth | {} {} | project={} | res=403 | Project is deleted", method, uri, project.name);
+        return Err(AppError::Forbidden("Project is deleted".to_string()));
+    }
+
+    // Suspension blocks every key of the project but keeps its data; the owner still sees the
+    // project through bearer routes. `key_cache` is invalidated on suspend and unsuspend.
+    if project.suspended_at.is_some() {
+        println!("Auth | {} {} | project={} | res=403 | Project is suspended", method, uri, project.name);
+        return Err(AppError::ForbiddenWithCode("project_suspended", "Project is suspended".to_string()));
+    }
+
     if !api_key.is_active {
-        println!("Auth | {} {} | project={} | res=401 | API Key is inactive", method, uri, project.name);
-        return Err(AppError::Unauthorized("API Key is inactive".to_string()));
+        println!("Auth | {} {} | project={} | key={} | res=401 | API Key is inactive", method, uri, project.name, api_key.name);
+        failures.record(api_key.id);
+        return Err(AppError::UnauthorizedWithCode("inactive_key", "API Key is inactive".to_string()));
     }
 
     if let Some(expires_at) = api_key.expires_at {
-        if expires_at < chrono::Utc::now().naive_utc() {
-            println!("Auth | {} {} | project={} | res=401 | API Key has expired", method, uri, project.name);
-            return Err(AppError::Unauthorized("API Key has expired".to_string()));
+        if expires_at < chrono::Utc::now() {
+            println!("Auth | {} {} | project={} | key={} | res=401 | API Key has expired", method, uri, project.name, api_key.name);
+            failures.record(api_key.id);
+            return Err(AppError::UnauthorizedWithCode("expired_key", "API Key has expired".to_string()));
         }
     }
 
@@ -86,11 +133,34 @@ pub async fn api_key_auth(
         })
         .unwrap_or_default();
 
+    let api_key_scopes = api_key.scopes();
     request.extensions_mut().insert(ProjectContext {
         id: project.id,
         name: project.name,
         settings,
+        storage_prefix: project.storage_prefix,
+        api_key_id: api_key.id,
+        api_key_name: api_key.name,
+        api_key_expires_at: api_key.expires_at,
+        api_key_scopes,
     });
 
     Ok(next.run(request).await)
 }
+
+/// For routes served to both callers: requests carrying `x-api-key` go through
+/// `api_key_auth`, everything else through the JWT `auth_middleware`. Handlers take
+/// `Option<Extension<ProjectContext>>` and `Option<Extension<AuthUser>>` and do their own
+/// role and scope checks.
+pub async fn api_key_or_bearer_auth(
+    state: axum::extract::State<ApiKeyAuthState>,
+    headers: HeaderMap,
+    request: Request,
+    next: Next,
+) -> Response {
+    if headers.contains_key("x-api-key") {
+        return api_key_auth(state, headers, request, next).await.into_response();
+    }
+    let db = state.db.clone();
+    crate::middleware::auth::auth_middleware(axum::extract::State(db), request, next).await.into_response()
+}
@@ -1,12 +1,12 @@
 use axum::{
-    extract::Request,
+    extract::{Request, State},
     http::{StatusCode, header},
     middleware::Next,
     response::Response,
 };
-use jsonwebtoken::{decode, DecodingKey, Validation};
+use sea_orm::DatabaseConnection;
 use serde::{Deserialize, Serialize};
-use crate::config::get_config;
+use crate::services::{jwt, token_versions};
 use uuid::Uuid;
 use crate::entities::user;
 
@@ -15,17 +15,30 @@ pub struct AuthUser {
     pub id: Uuid,
     pub username: String,
     pub role: user::Role,
+    /// Set when the token was minted by a superuser via `/admin/impersonate`.
+    pub impersonated_by: Option<Uuid>,
 }
 
+/// The only route open to tokens carrying `must_change_password`.
+const PASSWORD_CHANGE_PATH: &str = "/auth/change-password";
+
 #[derive(Serialize, Deserialize)]
 struct Claims {
     sub: String,
     exp: usize,
     role: user::Role,
     user_id: Uuid,
+    #[serde(default)]
+    impersonated_by: Option<Uuid>,
+    #[serde(default)]
+    must_change_password: bool,
+    /// Tokens from before the claim existed count as version 0, the column's default
+    #[serde(default)]
+    token_version: i32,
 }
 
 pub async fn auth_middleware(
+    State(db): State<DatabaseConnection>,
     mut req: Request,
     next: Next,
 ) -> Result<Response, StatusCode> {
@@ -44,23 +57,47 @@ pub async fn auth_middleware(
     let token = &auth_header[7..]; // Remove "Bearer " prefix
 
     // Decode and validate JWT
-    let token_data = decode::<Claims>(
-        token,
-        &DecodingKey::from_secret(get_config().jwt_secret.as_ref()),
-        &Validation::default(),
-    )
-    .map_err(|e| {
+    let token_data = jwt::decode::<Claims>(token).map_err(|e| {
         eprintln!("JWT decode error: {}", e);
         StatusCode::UNAUTHORIZED
     })?;
 
+    // Revoked by `POST /users/{id}/revoke-tokens` or a role change, or the user is gone
+    let current_version = token_versions::current(&db, token_data.claims.user_id).await.map_err(|e| {
+        eprintln!("Token version lookup error: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    if current_version != Some(token_data.claims.token_version) {
+        println!("Auth | {} {} | user={} | res=401 | Token revoked", req.method(), req.uri(), token_data.claims.sub);
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
     // Create AuthUser from claims
     let auth_user = AuthUser {
         id: token_data.claims.user_id,
         username: token_data.claims.sub,
         role: token_data.claims.role,
+        impersonated_by: token_data.claims.impersonated_by,
     };
 
+    // Record both identities whenever an impersonation token is used
+    if let Some(impersonator) = auth_user.impersonated_by {
+        println!(
+            "Auth | {} {} | user={} | impersonated_by={}",
+            req.method(),
+            req.uri(),
+            auth_user.username,
+            impersonator
+        );
+    }
+
+    // Users with a temporary password may only replace it. Setting or clearing the flag bumps
+    // token_version, so a token that got this far carries the flag as it is now
+    if token_data.claims.must_change_password && req.uri().path() != PASSWORD_CHANGE_PATH {
+        println!("Auth | {} {} | user={} | res=403 | Password change required", req.method(), req.uri(), auth_user.username);
+        return Err(StatusCode::FORBIDDEN);
+    }
+
     // Insert auth user into request extensions
     req.extensions_mut().insert(auth_user);
 
@@ -0,0 +1,37 @@
+use axum::{
+    extract::Request,
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use base64::{Engine as _, engine::general_purpose};
+use crate::config::get_config;
+
+/// HTTP Basic auth guard for the Swagger UI and OpenAPI document (`DOCS_BASIC_AUTH=user:pass`).
+pub async fn docs_basic_auth(
+    req: Request,
+    next: Next,
+) -> Response {
+    let Some(expected) = &get_config().docs_basic_auth else {
+        return next.run(req).await;
+    };
+
+    let provided = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Basic "))
+        .and_then(|encoded| general_purpose::STANDARD.decode(encoded).ok())
+        .and_then(|decoded| String::from_utf8(decoded).ok());
+
+    if provided.as_deref() == Some(expected.as_str()) {
+        return next.run(req).await;
+    }
+
+    println!("Docs | {} {} | res=401 | Invalid docs credentials", req.method(), req.uri());
+    (
+        StatusCode::UNAUTHORIZED,
+        [(header::WWW_AUTHENTICATE, "Basic realm=\"API docs\"")],
+    )
+        .into_response()
+}
@@ -2,4 +2,8 @@ pub mod auth;
 
 pub mod role;
 pub mod api_key;
+pub mod docs_auth;
+pub mod request_log;
+pub mod timeout;
+pub mod upload_limit;
 
@@ -0,0 +1,52 @@
+use axum::{
+    extract::{Request, State},
+    http::header,
+    middleware::Next,
+    response::Response,
+};
+use sea_orm::Set;
+
+use crate::entities::request_log;
+use crate::middleware::api_key::ProjectContext;
+use crate::services::request_log::RequestLogRecorder;
+
+/// Records API-key requests of projects that enable `request_logs` in their settings.
+///
+/// Runs inside `api_key_auth`, so requests rejected there (no project yet) are not logged.
+pub async fn request_log(
+    State(recorder): State<RequestLogRecorder>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(project) = request.extensions().get::<ProjectContext>().filter(|p| p.settings.request_logs) else {
+        return next.run(request).await;
+    };
+
+    let project_id = project.id;
+    let api_key_id = project.api_key_id;
+    let method = request.method().to_string();
+    let path = request.uri().path().to_string();
+    let bytes = request
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<i64>().ok())
+        .unwrap_or(0);
+    let start = std::time::Instant::now();
+
+    let response = next.run(request).await;
+
+    recorder.record(request_log::ActiveModel {
+        project_id: Set(project_id),
+        api_key_id: Set(api_key_id),
+        method: Set(method),
+        path: Set(path),
+        status: Set(response.status().as_u16() as i16),
+        bytes: Set(bytes),
+        duration_ms: Set(start.elapsed().as_millis().min(i32::MAX as u128) as i32),
+        created_at: Set(chrono::Utc::now()),
+        ..Default::default()
+    });
+
+    response
+}
@@ -7,17 +7,35 @@ use axum::{
 use crate::entities::user::Role;
 use crate::middleware::auth::AuthUser;
 
+/// Superuser routes. Impersonation tokens are refused here whatever role they carry, so they
+/// never reach `/users` or the other admin endpoints.
 pub async fn require_su(
     req: Request,
     next: Next,
+) -> Result<Response, StatusCode> {
+    if let Some(auth_user) = req.extensions().get::<AuthUser>() {
+        if auth_user.impersonated_by.is_some() {
+            eprintln!("Access denied: impersonation token for '{}' used on {} {}", auth_user.username, req.method(), req.uri());
+            return Err(StatusCode::FORBIDDEN);
+        }
+    }
+    require_role_at_least(Role::Su, req, next).await
+}
+
+/// Rejects callers ranked below `min` (see `Role::rank`). Use with a closure:
+/// `middleware::from_fn(|req, next| require_role_at_least(Role::User, req, next))`.
+pub async fn require_role_at_least(
+    min: Role,
+    req: Request,
+    next: Next,
 ) -> Result<Response, StatusCode> {
     let auth_user = req
         .extensions()
         .get::<AuthUser>()
         .ok_or(StatusCode::UNAUTHORIZED)?;
 
-    if auth_user.role != Role::Su {
-        eprintln!("Access denied: user '{}' is not superuser", auth_user.username);
+    if !auth_user.role.is_at_least(&min) {
+        eprintln!("Access denied: user '{}' ({:?}) needs at least {:?} for {} {}", auth_user.username, auth_user.role, min, req.method(), req.uri());
         return Err(StatusCode::FORBIDDEN);
     }
 
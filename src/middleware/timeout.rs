@@ -0,0 +1,53 @@
+use std::time::Duration;
+
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+use uuid::Uuid;
+
+use crate::error::AppError;
+
+/// Identifies a request in log lines, set by `request_timeout` before the handler runs.
+#[derive(Clone, Copy, Debug)]
+pub struct RequestId(pub Uuid);
+
+impl std::fmt::Display for RequestId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Fails the request with `504` once `budget` has elapsed (a zero budget disables it).
+///
+/// The handler future is dropped at that point, so anything it had not finished is
+/// abandoned mid-way. Handlers doing several storage calls in a row should log what they
+/// completed when that happens; `RequestId` links those lines to the timeout.
+pub async fn request_timeout(
+    State(budget): State<Duration>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let request_id = RequestId(Uuid::new_v4());
+    request.extensions_mut().insert(request_id);
+
+    if budget.is_zero() {
+        return Ok(next.run(request).await);
+    }
+
+    let method = request.method().to_string();
+    let path = request.uri().path().to_string();
+
+    match tokio::time::timeout(budget, next.run(request)).await {
+        Ok(response) => Ok(response),
+        Err(_) => {
+            println!("Timeout | {} {} | request={} | res=504 | No response within {}s", method, path, request_id, budget.as_secs());
+            Err(AppError::GatewayTimeout(format!(
+                "Request {} timed out after {}s",
+                request_id,
+                budget.as_secs()
+            )))
+        }
+    }
+}
@@ -0,0 +1,153 @@
+//! Concurrency ceilings for the upload routes.
+//!
+//! An upload holds its multipart body and a database connection for as long as storage takes,
+//! so a client opening hundreds of uploads at once exhausts memory and the pool before any
+//! request-count limit notices. [`upload_limit`] admits at most `UPLOAD_MAX_CONCURRENT`
+//! uploads per instance and `UPLOAD_MAX_CONCURRENT_PER_KEY` per API key, and answers `429`
+//! past either. It runs before the handler, which is what reads the body, so rejected
+//! requests never buffer theirs.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex};
+
+use axum::{extract::Request, middleware::Next, response::Response};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::config::get_config;
+use crate::error::AppError;
+use crate::middleware::api_key::ProjectContext;
+
+#[derive(Default)]
+struct InFlight {
+    total: usize,
+    per_key: HashMap<Uuid, usize>,
+}
+
+static IN_FLIGHT: LazyLock<Mutex<InFlight>> = LazyLock::new(Mutex::default);
+static PEAK: AtomicU64 = AtomicU64::new(0);
+static REJECTED_INSTANCE: AtomicU64 = AtomicU64::new(0);
+static REJECTED_KEY: AtomicU64 = AtomicU64::new(0);
+
+/// One admitted upload; gives its place back when dropped, including when the request times out.
+struct Slot {
+    key_id: Uuid,
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        let mut in_flight = IN_FLIGHT.lock().unwrap();
+        in_flight.total -= 1;
+        if let Some(count) = in_flight.per_key.get_mut(&self.key_id) {
+            *count -= 1;
+            if *count == 0 {
+                in_flight.per_key.remove(&self.key_id);
+            }
+        }
+    }
+}
+
+/// Which ceiling turned a request away.
+enum Saturated {
+    Instance(usize),
+    Key(usize),
+}
+
+fn acquire(key_id: Uuid) -> Result<Slot, Saturated> {
+    let config = get_config();
+    let mut in_flight = IN_FLIGHT.lock().unwrap();
+
+    // A limit of 0 means unlimited
+    if config.upload_max_concurrent > 0 && in_flight.total >= config.upload_max_concurrent {
+        REJECTED_INSTANCE.fetch_add(1, Ordering::Relaxed);
+        return Err(Saturated::Instance(config.upload_max_concurrent));
+    }
+    let key_count = in_flight.per_key.get(&key_id).copied().unwrap_or(0);
+    if config.upload_max_concurrent_per_key > 0 && key_count >= config.upload_max_concurrent_per_key {
+        REJECTED_KEY.fetch_add(1, Ordering::Relaxed);
+        return Err(Saturated::Key(config.upload_max_concurrent_per_key));
+    }
+
+    in_flight.total += 1;
+    *in_flight.per_key.entry(key_id).or_default() += 1;
+    PEAK.fetch_max(in_flight.total as u64, Ordering::Relaxed);
+    Ok(Slot { key_id })
+}
+
+/// Runs inside `api_key_auth`, whose `ProjectContext` names the key a request counts against.
+pub async fn upload_limit(request: Request, next: Next) -> Result<Response, AppError> {
+    let Some(key_id) = request.extensions().get::<ProjectContext>().map(|p| p.api_key_id) else {
+        return Ok(next.run(request).await);
+    };
+
+    let _slot = acquire(key_id).map_err(|saturated| {
+        let (code, message) = match saturated {
+            Saturated::Instance(limit) => ("upload_capacity", format!("Server is handling {} uploads; retry shortly", limit)),
+            Saturated::Key(limit) => ("key_upload_limit", format!("At most {} concurrent uploads per API key", limit)),
+        };
+        println!(
+            "Upload | {} {} | key={} | in_flight={} | res=429 | {}",
+            request.method(),
+            request.uri().path(),
+            key_id,
+            in_flight(),
+            code
+        );
+        AppError::TooManyRequests(code, message)
+    })?;
+
+    Ok(next.run(request).await)
+}
+
+fn in_flight() -> usize {
+    IN_FLIGHT.lock().unwrap().total
+}
+
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub struct UploadConcurrency {
+    /// Uploads being handled by this instance right now
+    pub in_flight: usize,
+    /// Highest `in_flight` since the process started
+    pub peak: u64,
+    /// `UPLOAD_MAX_CONCURRENT` (0 = unlimited)
+    pub max_concurrent: usize,
+    /// `UPLOAD_MAX_CONCURRENT_PER_KEY` (0 = unlimited)
+    pub max_concurrent_per_key: usize,
+    /// Requests refused because the instance was at `max_concurrent`
+    pub rejected_instance: u64,
+    /// Requests refused because their key was at `max_concurrent_per_key`
+    pub rejected_key: u64,
+    /// Keys with uploads in flight, busiest first (at most 10)
+    pub busiest_keys: Vec<KeyConcurrency>,
+}
+
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub struct KeyConcurrency {
+    #[schema(value_type = String)]
+    pub api_key_id: Uuid,
+    pub in_flight: usize,
+}
+
+pub fn stats() -> UploadConcurrency {
+    let config = get_config();
+    let in_flight = IN_FLIGHT.lock().unwrap();
+
+    let mut busiest_keys: Vec<KeyConcurrency> = in_flight
+        .per_key
+        .iter()
+        .map(|(key, count)| KeyConcurrency { api_key_id: *key, in_flight: *count })
+        .collect();
+    busiest_keys.sort_by_key(|k| std::cmp::Reverse(k.in_flight));
+    busiest_keys.truncate(10);
+
+    UploadConcurrency {
+        in_flight: in_flight.total,
+        peak: PEAK.load(Ordering::Relaxed),
+        max_concurrent: config.upload_max_concurrent,
+        max_concurrent_per_key: config.upload_max_concurrent_per_key,
+        rejected_instance: REJECTED_INSTANCE.load(Ordering::Relaxed),
+        rejected_key: REJECTED_KEY.load(Ordering::Relaxed),
+        busiest_keys,
+    }
+}
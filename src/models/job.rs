@@ -0,0 +1,125 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::models::settings::VariantConfig;
+
+/// Typed payload stored in `jobs.payload`, tagged by `type`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum JobPayload {
+    /// Generate variants for a freshly uploaded image.
+    ProcessImage {
+        #[serde(default)]
+        variants: Option<HashMap<String, VariantConfig>>,
+    },
+    /// Regenerate a single file's variants from a settings snapshot.
+    SyncFileVariants {
+        #[serde(default)]
+        variants_config: Option<HashMap<String, VariantConfig>>,
+    },
+    /// Fan out `SyncFileVariants` jobs for every image in a project.
+    SyncProjectVariants {
+        project_id: Uuid,
+    },
+    /// Re-hash the job's file and compare it with the recorded checksum.
+    VerifyFile,
+    /// Compute a sync-variants dry-run report for a large project; the report is
+    /// stored under `result` in the completed job's payload.
+    PlanProjectSync {
+        project_id: Uuid,
+        #[serde(default)]
+        variants_config: Option<HashMap<String, VariantConfig>>,
+        #[serde(default)]
+        breakdown_limit: u64,
+        #[serde(default)]
+        only_stale: bool,
+    },
+    /// Compute missing `attributes` for existing files, optionally in a single project.
+    Backfill {
+        attributes: Vec<BackfillAttribute>,
+        #[serde(default)]
+        project_id: Option<Uuid>,
+        #[serde(default)]
+        progress: BackfillProgress,
+    },
+    /// Sum the sizes of every object under a project's prefix and compare them with `files.size`.
+    ReconcileStorage {
+        project_id: Uuid,
+        #[serde(default)]
+        progress: ReconcileProgress,
+    },
+}
+
+/// A file column a `Backfill` job can compute for rows that predate it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BackfillAttribute {
+    /// Hex SHA-256 of the original (`files.content_hash`)
+    ContentHash,
+    /// Bare object keys in `files.variants_json` in place of the full URLs older uploads stored
+    VariantKeys,
+}
+
+/// How far a `Backfill` job got; saved after every page so a rerun continues from here.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct BackfillProgress {
+    /// Files are visited in id order; everything up to this id has been handled
+    #[schema(value_type = Option<String>)]
+    pub last_file_id: Option<Uuid>,
+    pub processed: u64,
+    pub updated: u64,
+    /// Files whose original could not be read; left as they were
+    pub failed: u64,
+}
+
+/// How far a `ReconcileStorage` job got; saved after every listing page so a requeued job
+/// resumes the listing instead of starting over.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct ReconcileProgress {
+    /// ListObjectsV2 token of the next page; `None` before the first page
+    pub continuation_token: Option<String>,
+    pub objects: u64,
+    pub stored_bytes: i64,
+    /// Part of `stored_bytes` that belongs to keys listed in some file's `variants_json`
+    pub variant_bytes: i64,
+}
+
+impl JobPayload {
+    /// Parses a stored payload, upgrading legacy untagged shapes on the fly.
+    ///
+    /// Image jobs enqueued before payloads were tagged look like
+    /// `{"variants": {...}}` and are treated as `ProcessImage`.
+    pub fn from_value(value: &serde_json::Value) -> Result<Self, String> {
+        let object = value.as_object().ok_or("Invalid payload")?;
+
+        if object.contains_key("type") {
+            return serde_json::from_value(value.clone())
+                .map_err(|e| format!("Invalid job payload: {}", e));
+        }
+
+        if let Some(variants) = object.get("variants") {
+            let variants = serde_json::from_value(variants.clone())
+                .map_err(|e| format!("Invalid variants in legacy payload: {}", e))?;
+            return Ok(JobPayload::ProcessImage { variants });
+        }
+
+        Err("Unknown job payload structure".to_string())
+    }
+
+    /// The `type` tag of a stored payload without fully parsing it.
+    /// Legacy untagged payloads are image jobs (see `from_value`).
+    pub fn type_of(value: &serde_json::Value) -> &str {
+        value
+            .get("type")
+            .and_then(|t| t.as_str())
+            .unwrap_or(Self::LEGACY_TYPE)
+    }
+
+    /// Type assumed for payloads stored before tagging; SQL filters must `COALESCE` to it.
+    pub const LEGACY_TYPE: &'static str = "process_image";
+
+    pub fn to_value(&self) -> serde_json::Value {
+        serde_json::to_value(self).unwrap_or_else(|_| serde_json::json!({}))
+    }
+}
@@ -1,12 +1,338 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+use crate::services::webhooks;
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct ProjectSettings {
     pub variants: Option<HashMap<String, VariantConfig>>,
+    /// Extensions refused on upload regardless of content type (lowercase, no leading dot)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blocked_extensions: Option<Vec<String>>,
+    /// When set, only these extensions may be uploaded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allowed_extensions: Option<Vec<String>>,
+    /// `sizes` attribute handed back next to `srcset` on image files, e.g. `(max-width: 600px) 100vw, 600px`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sizes: Option<String>,
+    /// Record API-key requests for `GET /projects/{id}/request-logs`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub request_logs: bool,
+    /// Events sent to the project's webhook (`file.created`, `file.ready`, `file.deleted`, `api_key.expiring`); none by default
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub webhook_events: Vec<String>,
+    /// Overrides of the server's retention windows for this project
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retention: Option<RetentionSettings>,
+    /// What `GET /files/{id}/content?variant=` does while the variant is not generated yet
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub variant_fallback: Option<VariantFallback>,
+    /// Serve `text/html` and `image/svg+xml` files inline; by default they are attachments
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub inline_html: bool,
+    /// Queue a `sync_project_variants` job whenever an edit changes `variants`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub auto_sync_on_settings_change: bool,
+}
+
+/// Answer for a variant that has not been generated (and has not failed).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, utoipa::ToSchema)]
+pub enum VariantFallback {
+    /// `404`, as before fallbacks existed
+    #[default]
+    #[serde(rename = "404")]
+    NotFound,
+    /// Redirect to the original instead
+    #[serde(rename = "original")]
+    Original,
+    /// Poll the file for up to `VARIANT_WAIT_TIMEOUT_SECS` while it is still processing, then `503`
+    #[serde(rename = "wait")]
+    Wait,
+}
+
+/// Per-project retention in days; unset fields fall back to `PROJECT_TRASH_DAYS` /
+/// `FILE_TRASH_DAYS` / `JOB_HISTORY_DAYS`.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct RetentionSettings {
+    /// Days the project is kept after `DELETE /projects/{id}` before it is purged
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project_trash_days: Option<i64>,
+    /// Days a file is kept after `DELETE /files/{id}` before it is purged
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_trash_days: Option<i64>,
+    /// Days finished jobs of the project are kept
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub job_history_days: Option<i64>,
+}
+
+impl RetentionSettings {
+    const FIELDS: [&'static str; 3] = ["project_trash_days", "file_trash_days", "job_history_days"];
+    const MAX_DAYS: i64 = 365;
+}
+
+/// Variant names that would clash with other key segments or file kinds.
+pub const RESERVED_VARIANT_NAMES: [&str; 2] = ["original", "poster"];
+
+/// Why `ProjectSettings::normalize_value` refused a settings object.
+#[derive(Debug)]
+pub enum SettingsError {
+    /// Malformed settings (400)
+    Invalid(String),
+    /// Well-formed, but variant names collide or are reserved (422)
+    VariantNames(String),
+}
+
+impl SettingsError {
+    pub fn status(&self) -> u16 {
+        match self {
+            SettingsError::Invalid(_) => 400,
+            SettingsError::VariantNames(_) => 422,
+        }
+    }
+}
+
+impl std::fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SettingsError::Invalid(msg) | SettingsError::VariantNames(msg) => f.write_str(msg),
+        }
+    }
+}
+
+impl From<SettingsError> for crate::error::AppError {
+    fn from(err: SettingsError) -> Self {
+        match err {
+            SettingsError::Invalid(msg) => crate::error::AppError::BadRequest(msg),
+            SettingsError::VariantNames(msg) => crate::error::AppError::UnprocessableEntity(msg),
+        }
+    }
+}
+
+/// Key-safe form of a variant name: lowercase ASCII letters, digits, `_` and `-`, with every
+/// other run of characters collapsed into a single `-` (`Thumb Large` -> `thumb-large`).
+pub fn variant_slug(name: &str) -> String {
+    let mut slug = String::with_capacity(name.len());
+    for c in name.trim().chars().flat_map(char::to_lowercase) {
+        if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+            slug.push(c);
+        } else if !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_matches('-').to_string()
+}
+
+/// Variant names that cannot be used as key segments: empty or reserved once slugged, or
+/// colliding with another name after slugging. Sorted, one message per problem.
+pub fn variant_name_problems<'a>(names: impl IntoIterator<Item = &'a String>) -> Vec<String> {
+    let mut by_slug: HashMap<String, Vec<&String>> = HashMap::new();
+    for name in names {
+        by_slug.entry(variant_slug(name)).or_default().push(name);
+    }
+
+    let mut problems = Vec::new();
+    for (slug, mut names) in by_slug {
+        names.sort();
+        let quoted: Vec<String> = names.iter().map(|n| format!("'{}'", n)).collect();
+        if slug.is_empty() {
+            problems.push(format!("{} has no usable characters", quoted.join(", ")));
+        } else if RESERVED_VARIANT_NAMES.contains(&slug.as_str()) {
+            problems.push(format!("{} is reserved", quoted.join(", ")));
+        } else if names.len() > 1 {
+            problems.push(format!("{} all become '{}'", quoted.join(", "), slug));
+        }
+    }
+    problems.sort();
+    problems
+}
+
+impl ProjectSettings {
+    const EXTENSION_LISTS: [&'static str; 2] = ["blocked_extensions", "allowed_extensions"];
+
+    /// Validates raw settings JSON, renames variants to their `variant_slug` and normalizes
+    /// the extension lists in place (lowercased, leading dots stripped, deduplicated).
+    /// Unknown keys are kept as-is.
+    pub fn normalize_value(value: serde_json::Value) -> Result<serde_json::Value, SettingsError> {
+        let mut value = match value {
+            serde_json::Value::Null => serde_json::json!({}),
+            serde_json::Value::Object(_) => value,
+            _ => return Err(SettingsError::Invalid("Invalid project settings: expected an object".to_string())),
+        };
+
+        let settings = serde_json::from_value::<ProjectSettings>(value.clone())
+            .map_err(|e| SettingsError::Invalid(format!("Invalid project settings: {}", e)))?;
+        settings
+            .check_external_commands()
+            .map_err(|e| SettingsError::Invalid(format!("Invalid project settings: {}", e)))?;
+
+        if let Some(unknown) = settings.webhook_events.iter().find(|e| !webhooks::EVENTS.contains(&e.as_str())) {
+            return Err(SettingsError::Invalid(format!(
+                "Invalid project settings: unknown webhook event '{}', expected one of {}",
+                unknown,
+                webhooks::EVENTS.join(", ")
+            )));
+        }
+
+        Self::check_retention(&value, settings.retention.as_ref())
+            .map_err(|e| SettingsError::Invalid(format!("Invalid project settings: {}", e)))?;
+
+        let problems = variant_name_problems(settings.variants.iter().flat_map(|v| v.keys()));
+        if !problems.is_empty() {
+            return Err(SettingsError::VariantNames(format!("Invalid variant names: {}", problems.join("; "))));
+        }
+
+        let object = value.as_object_mut().expect("checked above");
+        if let Some(serde_json::Value::Object(variants)) = object.get_mut("variants") {
+            *variants = std::mem::take(variants)
+                .into_iter()
+                .map(|(name, config)| (variant_slug(&name), config))
+                .collect();
+        }
+
+        for key in Self::EXTENSION_LISTS {
+            let Some(serde_json::Value::Array(items)) = object.get(key) else {
+                continue;
+            };
+
+            let mut normalized: Vec<String> = Vec::with_capacity(items.len());
+            for item in items {
+                let ext = item.as_str().unwrap_or_default().trim().trim_start_matches('.').to_lowercase();
+                if ext.is_empty() {
+                    return Err(SettingsError::Invalid(format!("Invalid project settings: empty entry in {}", key)));
+                }
+                if !normalized.contains(&ext) {
+                    normalized.push(ext);
+                }
+            }
+            object.insert(key.to_string(), serde_json::json!(normalized));
+        }
+
+        Ok(value)
+    }
+
+    /// Retention keys must be known and each value within 1..=365 days.
+    fn check_retention(value: &serde_json::Value, retention: Option<&RetentionSettings>) -> Result<(), String> {
+        if let Some(serde_json::Value::Object(raw)) = value.get("retention") {
+            if let Some(unknown) = raw.keys().find(|k| !RetentionSettings::FIELDS.contains(&k.as_str())) {
+                return Err(format!(
+                    "unknown retention setting '{}', expected one of {}",
+                    unknown,
+                    RetentionSettings::FIELDS.join(", ")
+                ));
+            }
+        }
+
+        let Some(retention) = retention else {
+            return Ok(());
+        };
+        for (name, days) in [
+            ("project_trash_days", retention.project_trash_days),
+            ("file_trash_days", retention.file_trash_days),
+            ("job_history_days", retention.job_history_days),
+        ] {
+            if days.is_some_and(|d| !(1..=RetentionSettings::MAX_DAYS).contains(&d)) {
+                return Err(format!("retention.{} must be between 1 and {}", name, RetentionSettings::MAX_DAYS));
+            }
+        }
+        Ok(())
+    }
+
+    /// Days this project stays in the trash before it is purged.
+    pub fn project_trash_days(&self) -> i64 {
+        self.retention
+            .as_ref()
+            .and_then(|r| r.project_trash_days)
+            .unwrap_or(crate::config::get_config().project_trash_days)
+    }
+
+    /// Days a deleted file of this project stays in the trash (0 = no trash).
+    pub fn file_trash_days(&self) -> i64 {
+        self.retention
+            .as_ref()
+            .and_then(|r| r.file_trash_days)
+            .unwrap_or(crate::config::get_config().file_trash_days)
+    }
+
+    /// Days finished jobs of this project are kept (0 = kept forever).
+    pub fn job_history_days(&self) -> i64 {
+        self.retention
+            .as_ref()
+            .and_then(|r| r.job_history_days)
+            .unwrap_or(crate::config::get_config().job_history_days)
+    }
+
+    /// Variants may only name server-defined command templates, and only when the server allows them.
+    fn check_external_commands(&self) -> Result<(), String> {
+        let config = crate::config::get_config();
+        let mut variants: Vec<(&String, &VariantConfig)> = self.variants.iter().flatten().collect();
+        variants.sort_by_key(|(name, _)| *name);
+
+        for (name, variant) in variants {
+            let Some(command) = &variant.external_command else {
+                continue;
+            };
+            if !config.allow_external_processors {
+                return Err(format!("variant '{}' uses external_command, which is disabled on this server", name));
+            }
+            if !config.external_processors.contains_key(&command.to_lowercase()) {
+                return Err(format!("variant '{}' references unknown external_command '{}'", name, command));
+            }
+            if variant.format.as_deref().and_then(crate::utils::image_processor::output_format).is_none() {
+                return Err(format!("variant '{}' needs an explicit output format (avif, webp, png or jpeg) for external_command", name));
+            }
+        }
+        Ok(())
+    }
+
+    /// Parses the `variants` part of a single upload: same schema and name rules as project
+    /// settings, plus the `UPLOAD_OVERRIDE_*` caps. Returned names are already slugged.
+    pub fn parse_variant_override(raw: &str) -> Result<HashMap<String, VariantConfig>, SettingsError> {
+        let config = crate::config::get_config();
+        let invalid = |msg: String| SettingsError::Invalid(format!("Invalid variants override: {}", msg));
+
+        let variants: HashMap<String, VariantConfig> = serde_json::from_str(raw).map_err(|e| invalid(e.to_string()))?;
+        if variants.len() > config.upload_override_max_variants {
+            return Err(invalid(format!("at most {} variants per upload", config.upload_override_max_variants)));
+        }
+
+        let problems = variant_name_problems(variants.keys());
+        if !problems.is_empty() {
+            return Err(SettingsError::VariantNames(format!("Invalid variant names: {}", problems.join("; "))));
+        }
+
+        let settings = ProjectSettings { variants: Some(variants), ..Default::default() };
+        settings.check_external_commands().map_err(invalid)?;
+
+        let mut variants: Vec<(String, VariantConfig)> = settings.variants.unwrap_or_default().into_iter().collect();
+        variants.sort_by(|a, b| a.0.cmp(&b.0));
+        for (name, variant) in &variants {
+            if let Some(format) = variant.format.as_deref().filter(|f| !crate::utils::image_processor::is_supported_format(f)) {
+                return Err(invalid(format!("variant '{}' has unsupported format '{}'", name, format)));
+            }
+            let largest = [variant.width, variant.height, variant.max_width, variant.max_height].into_iter().flatten().max();
+            if largest.is_some_and(|px| px > config.upload_override_max_dimension) {
+                return Err(invalid(format!("variant '{}' exceeds {}px", name, config.upload_override_max_dimension)));
+            }
+        }
+
+        Ok(variants.into_iter().map(|(name, variant)| (variant_slug(&name), variant)).collect())
+    }
+
+    /// Returns the offending extension when `extension` (lowercase, `None` if the
+    /// name has none) is blocked or missing from the allow-list.
+    pub fn rejected_extension(&self, extension: Option<&str>) -> Option<String> {
+        let matches = |list: &Vec<String>| {
+            extension.is_some_and(|ext| list.iter().any(|entry| entry.eq_ignore_ascii_case(ext)))
+        };
+
+        let blocked = self.blocked_extensions.as_ref().is_some_and(matches);
+        let not_allowed = self.allowed_extensions.as_ref().is_some_and(|list| !matches(list));
+
+        (blocked || not_allowed).then(|| extension.unwrap_or("(none)").to_string())
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct VariantConfig {
     pub format: Option<String>,
     pub quality: Option<u8>,
@@ -15,4 +341,8 @@ pub struct VariantConfig {
     pub max_width: Option<u32>,
     pub max_height: Option<u32>,
     pub fit: Option<String>, // cover, contain, inside, fill
+    /// Name of a server-configured command template (`EXTERNAL_PROCESSOR_CMD_<NAME>`) used
+    /// instead of the built-in processor, e.g. for RAW or EPS originals
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub external_command: Option<String>,
 }
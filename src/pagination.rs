@@ -1,14 +1,124 @@
+use std::env;
+
 use serde::{Deserialize, Serialize};
 use utoipa::{IntoParams, ToSchema};
+use crate::config::get_config;
+use crate::error::AppError;
+
+pub const DEFAULT_PAGE_SIZE: u64 = 10;
+pub const DEFAULT_MAX_LIMIT: u64 = 100;
+
+/// Listings whose page sizes can be configured apart from the global ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageGroup {
+    Files,
+    Jobs,
+    Users,
+    Projects,
+    /// Everything else: API keys, sessions, audit log, ...
+    Other,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PageLimits {
+    /// `limit` used when the query has none
+    pub default_limit: u64,
+    /// Larger `limit`s are clamped down to this
+    pub max_limit: u64,
+}
+
+impl PageLimits {
+    /// At least 1 for both, and the default never above the maximum.
+    fn normalized(self) -> Self {
+        let max_limit = self.max_limit.max(1);
+        Self { default_limit: self.default_limit.clamp(1, max_limit), max_limit }
+    }
+}
+
+/// Configured from `PAGINATION_DEFAULT_LIMIT` / `PAGINATION_MAX_LIMIT` (10 / 100), overridable
+/// per group with `PAGINATION_{FILES,JOBS,USERS,PROJECTS}_{DEFAULT,MAX}_LIMIT`.
+#[derive(Debug, Clone)]
+pub struct PaginationConfig {
+    global: PageLimits,
+    files: PageLimits,
+    jobs: PageLimits,
+    users: PageLimits,
+    projects: PageLimits,
+}
+
+impl PaginationConfig {
+    pub fn from_env() -> Self {
+        let read = |name: &str| env::var(name).ok().and_then(|v| v.parse::<u64>().ok());
+        let global = PageLimits {
+            default_limit: read("PAGINATION_DEFAULT_LIMIT").unwrap_or(DEFAULT_PAGE_SIZE),
+            max_limit: read("PAGINATION_MAX_LIMIT").unwrap_or(DEFAULT_MAX_LIMIT),
+        }
+        .normalized();
+        let group = |name: &str| {
+            PageLimits {
+                default_limit: read(&format!("PAGINATION_{}_DEFAULT_LIMIT", name)).unwrap_or(global.default_limit),
+                max_limit: read(&format!("PAGINATION_{}_MAX_LIMIT", name)).unwrap_or(global.max_limit),
+            }
+            .normalized()
+        };
+
+        Self {
+            global,
+            files: group("FILES"),
+            jobs: group("JOBS"),
+            users: group("USERS"),
+            projects: group("PROJECTS"),
+        }
+    }
+
+    pub fn limits(&self, group: PageGroup) -> PageLimits {
+        match group {
+            PageGroup::Files => self.files,
+            PageGroup::Jobs => self.jobs,
+            PageGroup::Users => self.users,
+            PageGroup::Projects => self.projects,
+            PageGroup::Other => self.global,
+        }
+    }
+}
 
 #[derive(Deserialize, IntoParams)]
 pub struct Pagination {
     #[param(default = 1, minimum = 1)]
     pub page: Option<u64>,
-    #[param(default = 10, minimum = 1, maximum = 100)]
+    /// Items per page; the default and the maximum are configured per server (see `page_size` and `max_limit` in responses)
+    #[param(minimum = 1)]
     pub limit: Option<u64>,
 }
 
+impl Pagination {
+    /// Validated `(page, limit)` with the global default and maximum.
+    pub fn effective(&self) -> Result<(u64, u64), AppError> {
+        self.effective_in(PageGroup::Other)
+    }
+
+    /// Validated `(page, limit)` with the default and maximum configured for `group`.
+    pub fn effective_in(&self, group: PageGroup) -> Result<(u64, u64), AppError> {
+        effective(self.page, self.limit, get_config().pagination.limits(group))
+    }
+}
+
+/// Resolves raw `page`/`limit` query values. `page` is 1-based; zero for either is a 400,
+/// a missing limit takes the default and one above the maximum is clamped down to it.
+pub fn effective(page: Option<u64>, limit: Option<u64>, limits: PageLimits) -> Result<(u64, u64), AppError> {
+    let page = page.unwrap_or(1);
+    let limit = limit.unwrap_or(limits.default_limit);
+
+    if page == 0 {
+        return Err(AppError::BadRequest("page must be at least 1".to_string()));
+    }
+    if limit == 0 {
+        return Err(AppError::BadRequest("limit must be at least 1".to_string()));
+    }
+
+    Ok((page, limit.min(limits.max_limit)))
+}
+
 
 #[derive(Serialize, ToSchema)]
 pub struct PaginatedResponse<T> {
@@ -16,23 +126,72 @@ pub struct PaginatedResponse<T> {
     pub total_items: u64,
     pub total_pages: u64,
     pub current_page: u64,
+    /// Effective `limit` of this page
     pub page_size: u64,
+    /// Largest `limit` this listing accepts
+    pub max_limit: u64,
 }
 
 impl<T> PaginatedResponse<T> {
+    /// Page of a listing in `PageGroup::Other`.
     pub fn new(data: Vec<T>, total_items: u64, page: u64, page_size: u64) -> Self {
-        let total_pages = if page_size == 0 {
-            0
-        } else {
-            (total_items as f64 / page_size as f64).ceil() as u64
-        };
-        
+        Self::new_in(PageGroup::Other, data, total_items, page, page_size)
+    }
+
+    pub fn new_in(group: PageGroup, data: Vec<T>, total_items: u64, page: u64, page_size: u64) -> Self {
         Self {
             data,
             total_items,
-            total_pages,
+            total_pages: if page_size == 0 { 0 } else { total_items.div_ceil(page_size) },
             current_page: page,
             page_size,
+            max_limit: get_config().pagination.limits(group).max_limit,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIMITS: PageLimits = PageLimits { default_limit: 10, max_limit: 100 };
+
+    fn status(result: Result<(u64, u64), AppError>) -> Option<u16> {
+        result.err().map(|e| axum::response::IntoResponse::into_response(e).status().as_u16())
+    }
+
+    #[test]
+    fn defaults_apply_when_absent() {
+        assert_eq!(effective(None, None, LIMITS).unwrap(), (1, 10));
+        assert_eq!(effective(Some(3), None, LIMITS).unwrap(), (3, 10));
+    }
+
+    #[test]
+    fn zero_page_or_limit_is_a_bad_request() {
+        assert_eq!(status(effective(Some(0), None, LIMITS)), Some(400));
+        assert_eq!(status(effective(None, Some(0), LIMITS)), Some(400));
+        assert_eq!(status(effective(Some(0), Some(0), LIMITS)), Some(400));
+    }
+
+    #[test]
+    fn limit_boundaries() {
+        assert_eq!(effective(None, Some(1), LIMITS).unwrap(), (1, 1));
+        assert_eq!(effective(None, Some(99), LIMITS).unwrap(), (1, 99));
+        assert_eq!(effective(None, Some(100), LIMITS).unwrap(), (1, 100));
+        assert_eq!(effective(None, Some(101), LIMITS).unwrap(), (1, 100));
+        assert_eq!(effective(None, Some(u64::MAX), LIMITS).unwrap(), (1, 100));
+    }
+
+    #[test]
+    fn page_has_no_upper_bound() {
+        assert_eq!(effective(Some(u64::MAX), Some(100), LIMITS).unwrap(), (u64::MAX, 100));
+    }
+
+    #[test]
+    fn configured_limits_are_normalized() {
+        let zero = PageLimits { default_limit: 0, max_limit: 0 }.normalized();
+        assert_eq!((zero.default_limit, zero.max_limit), (1, 1));
+        let inverted = PageLimits { default_limit: 50, max_limit: 20 }.normalized();
+        assert_eq!((inverted.default_limit, inverted.max_limit), (20, 20));
+    }
+}
@@ -4,29 +4,83 @@ use axum::{
 };
 use sea_orm::{
     ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, IntoActiveModel, QueryFilter,
-    Set, QueryOrder, PaginatorTrait,
+    Set, QueryOrder, PaginatorTrait, Order,
 };
+use sea_orm::sea_query::NullOrdering;
+use std::collections::{HashMap, HashSet};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use uuid::Uuid;
 use rand::{RngCore, thread_rng};
 use base64::{Engine as _, engine::general_purpose};
 
-use crate::entities::{api_key::{self, Entity as ApiKey}, project};
+use crate::entities::{api_key::{self, ApiKeyScope, Entity as ApiKey}, project, user::{self, Role}};
 use crate::middleware::auth::AuthUser;
 use crate::error::AppError;
 use crate::pagination::{Pagination, PaginatedResponse};
+use crate::routes::{auth::ErrorResponse, created, Created};
+use crate::services::audit;
+use crate::services::cleanup::find_expiring_keys;
+use crate::services::key_cache::{self, KeyCacheStats};
 use axum::extract::Query;
 
 #[derive(Deserialize, utoipa::ToSchema)]
 pub struct CreateApiKeyRequest {
     name: String,
+    /// Interpreted as UTC
     expires_at: Option<chrono::NaiveDateTime>,
+    /// Permissions beyond uploading; none by default
+    #[serde(default)]
+    scopes: Vec<ApiKeyScope>,
 }
 
 #[derive(Deserialize, utoipa::ToSchema)]
 pub struct UpdateApiKeyRequest {
+    is_active: Option<bool>,
+    /// Replaces the key's scopes when present
+    scopes: Option<Vec<ApiKeyScope>>,
+    /// New expiry, interpreted as UTC; `null` makes the key never expire
+    #[serde(default, deserialize_with = "present")]
+    #[schema(value_type = Option<chrono::NaiveDateTime>)]
+    expires_at: Option<Option<chrono::NaiveDateTime>>,
+}
+
+/// Tells an explicit `null` (`Some(None)`) apart from a missing field (`None`).
+fn present<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct ListApiKeysQuery {
+    pub page: Option<u64>,
+    pub limit: Option<u64>,
+    /// `created` (newest first, default) or `expiry` (soonest expiry first, keys without expiry last)
+    pub sort: Option<String>,
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct ExpiringKeysQuery {
+    /// Window in days from now (default 14, max 365)
+    pub within_days: Option<i64>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct ExpiringApiKeyResponse {
+    #[schema(value_type = String)]
+    id: Uuid,
+    name: String,
+    expires_at: Option<chrono::DateTime<chrono::Utc>>,
     is_active: bool,
+    #[schema(value_type = String)]
+    project_id: Uuid,
+    project_name: String,
+    #[schema(value_type = String)]
+    owner_id: Uuid,
+    owner_username: String,
 }
 
 #[derive(Serialize, utoipa::ToSchema)]
@@ -34,9 +88,14 @@ pub struct ApiKeyResponse {
     #[schema(value_type = String)]
     id: Uuid,
     name: String,
-    created_at: chrono::NaiveDateTime,
-    expires_at: Option<chrono::NaiveDateTime>,
+    created_at: chrono::DateTime<chrono::Utc>,
+    expires_at: Option<chrono::DateTime<chrono::Utc>>,
     is_active: bool,
+    /// Requests refused because this key was inactive or expired; a dead key still in use
+    /// shows up here, so update that integration before deleting the key
+    failed_attempts: i64,
+    last_failed_at: Option<chrono::DateTime<chrono::Utc>>,
+    scopes: Vec<ApiKeyScope>,
     // Only returned on creation
     #[serde(skip_serializing_if = "Option::is_none")]
     key: Option<String>,
@@ -44,28 +103,72 @@ pub struct ApiKeyResponse {
 
 impl From<api_key::Model> for ApiKeyResponse {
     fn from(model: api_key::Model) -> Self {
+        let scopes = model.scopes();
         ApiKeyResponse {
             id: model.id,
             name: model.name,
             created_at: model.created_at,
             expires_at: model.expires_at,
             is_active: model.is_active,
+            failed_attempts: model.failed_attempts,
+            last_failed_at: model.last_failed_at,
+            scopes,
             key: None,
         }
     }
 }
 
+/// `scopes` column value: unique names in request order.
+fn scopes_json(scopes: &[ApiKeyScope]) -> serde_json::Value {
+    let mut names: Vec<&str> = Vec::new();
+    for scope in scopes {
+        if !names.contains(&scope.as_str()) {
+            names.push(scope.as_str());
+        }
+    }
+    serde_json::json!(names)
+}
+
+/// Loads a live project the caller may manage keys for.
+///
+/// Owners see their own projects; Su can manage keys on any project so a
+/// compromised key in a customer's project can be deactivated.
+async fn find_manageable_project(
+    db: &DatabaseConnection,
+    auth_user: &AuthUser,
+    project_id: Uuid,
+    action: &str,
+) -> Result<Option<project::Model>, AppError> {
+    let mut query = project::Entity::find_by_id(project_id)
+        .filter(project::Column::DeletedAt.is_null());
+    if auth_user.role != Role::Su {
+        query = query.filter(project::Column::OwnerId.eq(auth_user.id));
+    }
+
+    let project = query.one(db).await?;
+
+    if let Some(p) = &project {
+        if p.owner_id != auth_user.id {
+            println!("ApiKey | {} | user={} | project={} | owner={} | Su acting on project it does not own", action, auth_user.username, p.id, p.owner_id);
+        }
+    }
+
+    Ok(project)
+}
+
 #[utoipa::path(
     post,
     path = "/projects/{id}/keys",
+    description = "Create an API key for a project you own. Su can create keys on any project.",
     params(
         ("id" = String, Path, description = "Project ID")
     ),
     request_body = CreateApiKeyRequest,
     responses(
-        (status = 201, description = "API Key created successfully", body = ApiKeyResponse),
-        (status = 404, description = "Project not found"),
-        (status = 500, description = "Internal server error")
+        (status = 201, description = "API Key created successfully", body = ApiKeyResponse,
+            headers(("Location" = String, description = "Path of the created key"))),
+        (status = 404, description = "Project not found or not owned by the caller", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
     ),
     security(
         ("bearer_auth" = [])
@@ -77,13 +180,8 @@ pub async fn create_api_key(
     auth_user: axum::Extension<AuthUser>,
     Path(project_id): Path<Uuid>,
     Json(payload): Json<CreateApiKeyRequest>,
-) -> Result<Json<ApiKeyResponse>, AppError> {
-    // Verify project ownership
-    let project = project::Entity::find_by_id(project_id)
-        .filter(project::Column::OwnerId.eq(auth_user.id))
-        .filter(project::Column::DeletedAt.is_null())
-        .one(&db)
-        .await?;
+) -> Result<Created<ApiKeyResponse>, AppError> {
+    let project = find_manageable_project(&db, &auth_user, project_id, "POST /projects/{id}/keys").await?;
 
     match project {
         Some(p) => {
@@ -102,17 +200,25 @@ pub async fn create_api_key(
                 project_id: Set(p.id),
                 name: Set(payload.name),
                 key_hash: Set(key_hash),
-                created_at: Set(chrono::Utc::now().naive_utc()),
-                expires_at: Set(payload.expires_at),
+                created_at: Set(chrono::Utc::now()),
+                expires_at: Set(payload.expires_at.map(|t| t.and_utc())),
                 is_active: Set(true),
+                expiry_notified_at: Set(None),
+                failed_attempts: Set(0),
+                last_failed_at: Set(None),
+                scopes: Set(scopes_json(&payload.scopes)),
             };
 
             let created_key = api_key.insert(&db).await?;
+            audit::record_by(&db, &auth_user, "api_key.create", "api_key", Some(created_key.id), serde_json::json!({
+                "project_id": project_id,
+                "name": created_key.name,
+            })).await;
 
             let mut response = ApiKeyResponse::from(created_key);
             response.key = Some(raw_key);
             println!("ApiKey | POST /projects/{}/keys | user={} | res=201", project_id, auth_user.username);
-            Ok(Json(response))
+            Ok(created(format!("/projects/{}/keys/{}", project_id, response.id), response))
         }
         None => {
             println!("ApiKey | POST /projects/{}/keys | user={} | res=404 | Project not found", project_id, auth_user.username);
@@ -124,15 +230,16 @@ pub async fn create_api_key(
 #[utoipa::path(
     get,
     path = "/projects/{id}/keys",
+    description = "List API keys for a project you own. Su can list keys on any project.",
     params(
         ("id" = String, Path, description = "Project ID"),
-        ("page" = Option<u64>, Query, description = "Page number"),
-        ("limit" = Option<u64>, Query, description = "Items per page")
+        ListApiKeysQuery
     ),
     responses(
         (status = 200, description = "List of API Keys", body = PaginatedResponse<ApiKeyResponse>),
-        (status = 404, description = "Project not found"),
-        (status = 500, description = "Internal server error")
+        (status = 400, description = "Invalid pagination or sort parameters", body = ErrorResponse),
+        (status = 404, description = "Project not found or not owned by the caller", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
     ),
     security(
         ("bearer_auth" = [])
@@ -143,31 +250,33 @@ pub async fn list_api_keys(
     State(db): State<DatabaseConnection>,
     auth_user: axum::Extension<AuthUser>,
     Path(project_id): Path<Uuid>,
-    Query(pagination): Query<Pagination>,
+    Query(query): Query<ListApiKeysQuery>,
 ) -> Result<Json<PaginatedResponse<ApiKeyResponse>>, AppError> {
-    // Verify project ownership
-    let project = project::Entity::find_by_id(project_id)
-        .filter(project::Column::OwnerId.eq(auth_user.id))
-        .filter(project::Column::DeletedAt.is_null())
-        .one(&db)
-        .await
-        .map_err(AppError::DatabaseError)?;
+    let project = find_manageable_project(&db, &auth_user, project_id, "GET /projects/{id}/keys").await?;
 
     if project.is_none() {
         println!("ApiKey | GET /projects/{}/keys | user={} | res=404 | Project not found", project_id, auth_user.username);
         return Err(AppError::NotFound("Project not found".to_string()));
     }
 
-    let page = pagination.page.unwrap_or(1);
-    let limit = pagination.limit.unwrap_or(10);
+    let (page, limit) = Pagination { page: query.page, limit: query.limit }.effective()?;
+
+    let select = ApiKey::find().filter(api_key::Column::ProjectId.eq(project_id));
+    let select = match query.sort.as_deref() {
+        None | Some("created") => select.order_by_desc(api_key::Column::CreatedAt),
+        Some("expiry") => select
+            .order_by_with_nulls(api_key::Column::ExpiresAt, Order::Asc, NullOrdering::Last)
+            .order_by_desc(api_key::Column::CreatedAt),
+        Some(other) => {
+            println!("ApiKey | GET /projects/{}/keys | user={} | res=400 | Invalid sort", project_id, auth_user.username);
+            return Err(AppError::BadRequest(format!("Invalid sort '{}', expected 'created' or 'expiry'", other)));
+        }
+    };
 
-    let paginator = ApiKey::find()
-        .filter(api_key::Column::ProjectId.eq(project_id))
-        .order_by_desc(api_key::Column::CreatedAt)
-        .paginate(&db, limit);
+    let paginator = select.paginate(&db, limit);
 
     let total_items = paginator.num_items().await.map_err(AppError::DatabaseError)?;
-    let api_keys = paginator.fetch_page(page - 1).await.map_err(AppError::DatabaseError)?;
+    let api_keys = paginator.fetch_page(page.saturating_sub(1)).await.map_err(AppError::DatabaseError)?;
 
     let responses: Vec<ApiKeyResponse> = api_keys.into_iter().map(ApiKeyResponse::from).collect();
     
@@ -175,9 +284,53 @@ pub async fn list_api_keys(
     Ok(Json(PaginatedResponse::new(responses, total_items, page, limit)))
 }
 
+#[utoipa::path(
+    get,
+    path = "/projects/{id}/keys/{key_id}",
+    description = "One API key of a project you own, without the raw key. Su can read keys on any project.",
+    params(
+        ("id" = String, Path, description = "Project ID"),
+        ("key_id" = String, Path, description = "API Key ID")
+    ),
+    responses(
+        (status = 200, description = "API Key", body = ApiKeyResponse),
+        (status = 404, description = "Project not found or not owned by the caller, or API Key not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Project API Keys"
+)]
+pub async fn get_api_key(
+    State(db): State<DatabaseConnection>,
+    auth_user: axum::Extension<AuthUser>,
+    Path((project_id, key_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<ApiKeyResponse>, AppError> {
+    let project = find_manageable_project(&db, &auth_user, project_id, "GET /projects/{id}/keys/{key_id}").await?;
+
+    let Some(p) = project else {
+        println!("ApiKey | GET /projects/{}/keys/{} | user={} | res=404 | Project not found", project_id, key_id, auth_user.username);
+        return Err(AppError::NotFound("Project not found".to_string()));
+    };
+
+    let key = api_key::Entity::find_by_id(key_id)
+        .filter(api_key::Column::ProjectId.eq(p.id))
+        .one(&db)
+        .await?
+        .ok_or_else(|| {
+            println!("ApiKey | GET /projects/{}/keys/{} | user={} | res=404 | API Key not found", project_id, key_id, auth_user.username);
+            AppError::NotFound("API Key not found".to_string())
+        })?;
+
+    println!("ApiKey | GET /projects/{}/keys/{} | user={} | res=200", project_id, key_id, auth_user.username);
+    Ok(Json(ApiKeyResponse::from(key)))
+}
+
 #[utoipa::path(
     patch,
     path = "/projects/{id}/keys/{key_id}",
+    description = "Enable or disable an API key, replace its scopes, or change its expiry, on a project you own. Su can update keys on any project.",
     params(
         ("id" = String, Path, description = "Project ID"),
         ("key_id" = String, Path, description = "API Key ID")
@@ -185,8 +338,8 @@ pub async fn list_api_keys(
     request_body = UpdateApiKeyRequest,
     responses(
         (status = 200, description = "API Key updated successfully"),
-        (status = 404, description = "Project or API Key not found"),
-        (status = 500, description = "Internal server error")
+        (status = 404, description = "Project not found or not owned by the caller, or API Key not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
     ),
     security(
         ("bearer_auth" = [])
@@ -199,12 +352,7 @@ pub async fn update_api_key(
     Path((project_id, key_id)): Path<(Uuid, Uuid)>,
     Json(payload): Json<UpdateApiKeyRequest>,
 ) -> Result<Json<serde_json::Value>, AppError> {
-    // Verify project ownership
-    let project = project::Entity::find_by_id(project_id)
-        .filter(project::Column::OwnerId.eq(auth_user.id))
-        .filter(project::Column::DeletedAt.is_null())
-        .one(&db)
-        .await?;
+    let project = find_manageable_project(&db, &auth_user, project_id, "PATCH /projects/{id}/keys/{key_id}").await?;
 
     match project {
         Some(p) => {
@@ -215,9 +363,23 @@ pub async fn update_api_key(
 
             match key {
                 Some(k) => {
+                    let previous_expiry = k.expires_at;
                     let mut active_key = k.into_active_model();
-                    active_key.is_active = Set(payload.is_active);
+                    if let Some(is_active) = payload.is_active {
+                        active_key.is_active = Set(is_active);
+                    }
+                    if let Some(scopes) = &payload.scopes {
+                        active_key.scopes = Set(scopes_json(scopes));
+                    }
+                    if let Some(expires_at) = payload.expires_at.map(|t| t.map(|t| t.and_utc())) {
+                        if expires_at != previous_expiry {
+                            active_key.expires_at = Set(expires_at);
+                            // A new date gets its own expiry notice when it comes into the window
+                            active_key.expiry_notified_at = Set(None);
+                        }
+                    }
                     active_key.update(&db).await?;
+                    key_cache::invalidate_key(key_id);
 
                     println!("ApiKey | PATCH /projects/{}/keys/{} | user={} | res=200", project_id, key_id, auth_user.username);
                     Ok(Json(serde_json::json!({ "message": "API Key updated successfully" })))
@@ -238,14 +400,15 @@ pub async fn update_api_key(
 #[utoipa::path(
     delete,
     path = "/projects/{id}/keys/{key_id}",
+    description = "Permanently delete an API key on a project you own. Su can delete keys on any project.",
     params(
         ("id" = String, Path, description = "Project ID"),
         ("key_id" = String, Path, description = "API Key ID")
     ),
     responses(
         (status = 200, description = "API Key deleted successfully"),
-        (status = 404, description = "Project or API Key not found"),
-        (status = 500, description = "Internal server error")
+        (status = 404, description = "Project not found or not owned by the caller, or API Key not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
     ),
     security(
         ("bearer_auth" = [])
@@ -257,12 +420,7 @@ pub async fn delete_api_key(
     auth_user: axum::Extension<AuthUser>,
     Path((project_id, key_id)): Path<(Uuid, Uuid)>,
 ) -> Result<Json<serde_json::Value>, AppError> {
-    // Verify project ownership
-    let project = project::Entity::find_by_id(project_id)
-        .filter(project::Column::OwnerId.eq(auth_user.id))
-        .filter(project::Column::DeletedAt.is_null())
-        .one(&db)
-        .await?;
+    let project = find_manageable_project(&db, &auth_user, project_id, "DELETE /projects/{id}/keys/{key_id}").await?;
 
     match project {
         Some(p) => {
@@ -273,7 +431,13 @@ pub async fn delete_api_key(
 
             match key {
                 Some(k) => {
+                    let name = k.name.clone();
                     api_key::Entity::delete(k.into_active_model()).exec(&db).await?;
+                    key_cache::invalidate_key(key_id);
+                    audit::record_by(&db, &auth_user, "api_key.delete", "api_key", Some(key_id), serde_json::json!({
+                        "project_id": project_id,
+                        "name": name,
+                    })).await;
 
                     println!("ApiKey | DELETE /projects/{}/keys/{} | user={} | res=200", project_id, key_id, auth_user.username);
                     Ok(Json(serde_json::json!({ "message": "API Key deleted successfully" })))
@@ -290,3 +454,80 @@ pub async fn delete_api_key(
         }
     }
 }
+
+#[utoipa::path(
+    get,
+    path = "/admin/keys/expiring",
+    description = "List active API keys on live projects that expire within the window, soonest first (superuser only).",
+    params(ExpiringKeysQuery),
+    responses(
+        (status = 200, description = "Keys expiring within the window", body = Vec<ExpiringApiKeyResponse>),
+        (status = 400, description = "Invalid window", body = ErrorResponse),
+        (status = 403, description = "Superuser access required", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Project API Keys"
+)]
+pub async fn list_expiring_api_keys(
+    State(db): State<DatabaseConnection>,
+    auth_user: axum::Extension<AuthUser>,
+    Query(query): Query<ExpiringKeysQuery>,
+) -> Result<Json<Vec<ExpiringApiKeyResponse>>, AppError> {
+    let within_days = query.within_days.unwrap_or(14);
+    if !(1..=365).contains(&within_days) {
+        println!("ApiKey | GET /admin/keys/expiring | user={} | res=400 | Invalid within_days", auth_user.username);
+        return Err(AppError::BadRequest("within_days must be between 1 and 365".to_string()));
+    }
+
+    let keys = find_expiring_keys(&db, within_days).await?;
+
+    let owner_ids: HashSet<Uuid> = keys.iter().map(|(_, p)| p.owner_id).collect();
+    let usernames: HashMap<Uuid, String> = user::Entity::find()
+        .filter(user::Column::Id.is_in(owner_ids))
+        .all(&db)
+        .await?
+        .into_iter()
+        .map(|u| (u.id, u.username))
+        .collect();
+
+    let responses: Vec<ExpiringApiKeyResponse> = keys
+        .into_iter()
+        .map(|(key, p)| ExpiringApiKeyResponse {
+            id: key.id,
+            name: key.name,
+            expires_at: key.expires_at,
+            is_active: key.is_active,
+            project_id: p.id,
+            project_name: p.name,
+            owner_id: p.owner_id,
+            owner_username: usernames.get(&p.owner_id).cloned().unwrap_or_default(),
+        })
+        .collect();
+
+    println!("ApiKey | GET /admin/keys/expiring | user={} | within_days={} | count={} | res=200", auth_user.username, within_days, responses.len());
+    Ok(Json(responses))
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/keys/cache",
+    description = "Hit rate and size of this instance's API key cache (superuser only). Counters start at zero when the process starts.",
+    responses(
+        (status = 200, description = "API key cache statistics", body = KeyCacheStats),
+        (status = 403, description = "Superuser access required", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Project API Keys"
+)]
+pub async fn get_key_cache_stats(
+    auth_user: axum::Extension<AuthUser>,
+) -> Json<KeyCacheStats> {
+    let stats = key_cache::stats();
+    println!("ApiKey | GET /admin/keys/cache | user={} | hits={} | misses={} | res=200", auth_user.username, stats.hits, stats.misses);
+    Json(stats)
+}
@@ -0,0 +1,180 @@
+use axum::{
+    extract::{Extension, Query, State},
+    response::Json,
+};
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::entities::{audit_log, login_attempt};
+use crate::error::AppError;
+use crate::middleware::auth::AuthUser;
+use crate::pagination::{Pagination, PaginatedResponse};
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct AuditLogQuery {
+    /// Only this action, e.g. `auth.login`
+    pub action: Option<String>,
+    /// Only events performed by this user
+    #[param(value_type = Option<String>)]
+    pub actor_user_id: Option<Uuid>,
+    pub page: Option<u64>,
+    pub limit: Option<u64>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct AuditLogResponse {
+    id: i64,
+    #[schema(value_type = Option<String>)]
+    actor_user_id: Option<Uuid>,
+    /// `auth.login`, `auth.logout`, `auth.refresh`, `auth.refresh_reuse`, `auth.2fa_enable`, `user.create`, `user.delete`,
+    /// `project.delete`, `api_key.create` or `api_key.delete`
+    action: String,
+    /// `user`, `project` or `api_key`
+    target_type: String,
+    #[schema(value_type = Option<String>)]
+    target_id: Option<Uuid>,
+    /// Event details; `impersonated_by` is set when the actor used an impersonation token
+    #[schema(value_type = Object)]
+    metadata: serde_json::Value,
+    created_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl From<audit_log::Model> for AuditLogResponse {
+    fn from(entry: audit_log::Model) -> Self {
+        AuditLogResponse {
+            id: entry.id,
+            actor_user_id: entry.actor_user_id,
+            action: entry.action,
+            target_type: entry.target_type,
+            target_id: entry.target_id,
+            metadata: entry.metadata,
+            created_at: entry.created_at,
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/audit",
+    description = "Authentication and admin events, newest first (superuser only). Entries are written best-effort, \
+so an event whose insert failed is missing rather than failing the request that caused it.",
+    params(AuditLogQuery),
+    responses(
+        (status = 200, description = "Audit log", body = PaginatedResponse<AuditLogResponse>),
+        (status = 400, description = "Invalid pagination parameters"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "User Management"
+)]
+pub async fn list_audit_logs(
+    State(db): State<DatabaseConnection>,
+    Extension(auth_user): Extension<AuthUser>,
+    Query(query): Query<AuditLogQuery>,
+) -> Result<Json<PaginatedResponse<AuditLogResponse>>, AppError> {
+    let (page, limit) = Pagination { page: query.page, limit: query.limit }.effective()?;
+
+    let mut select = audit_log::Entity::find();
+    if let Some(action) = &query.action {
+        select = select.filter(audit_log::Column::Action.eq(action.as_str()));
+    }
+    if let Some(actor_user_id) = query.actor_user_id {
+        select = select.filter(audit_log::Column::ActorUserId.eq(actor_user_id));
+    }
+    let paginator = select
+        .order_by_desc(audit_log::Column::CreatedAt)
+        .order_by_desc(audit_log::Column::Id)
+        .paginate(&db, limit);
+
+    let total_items = paginator.num_items().await?;
+    let entries = paginator.fetch_page(page.saturating_sub(1)).await?;
+    let responses: Vec<AuditLogResponse> = entries.into_iter().map(AuditLogResponse::from).collect();
+
+    println!("Audit | GET /admin/audit | user={} | count={} | res=200", auth_user.username, total_items);
+    Ok(Json(PaginatedResponse::new(responses, total_items, page, limit)))
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct LoginAttemptQuery {
+    /// Only attempts with exactly this login value (case-sensitive)
+    pub username: Option<String>,
+    /// Only successful (`true`) or failed (`false`) attempts
+    pub success: Option<bool>,
+    pub page: Option<u64>,
+    pub limit: Option<u64>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct LoginAttemptResponse {
+    id: i64,
+    /// Login value as sent: a username, or an email when it contains `@`
+    username: String,
+    /// Account the value resolved to; absent when it matched none
+    #[schema(value_type = Option<String>)]
+    user_id: Option<Uuid>,
+    success: bool,
+    /// `unknown_user`, `invalid_password`, `otp_required` or `invalid_otp`; absent on success
+    failure_reason: Option<String>,
+    ip: Option<String>,
+    created_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl From<login_attempt::Model> for LoginAttemptResponse {
+    fn from(attempt: login_attempt::Model) -> Self {
+        LoginAttemptResponse {
+            id: attempt.id,
+            username: attempt.username,
+            user_id: attempt.user_id,
+            success: attempt.success,
+            failure_reason: attempt.failure_reason,
+            ip: attempt.ip,
+            created_at: attempt.created_at,
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/login-attempts",
+    description = "`POST /auth/login` outcomes, newest first (superuser only). Attempts are written in batches about once \
+a second, so the latest may not be listed yet, and dropped if the buffer is full. They are kept for `LOGIN_ATTEMPT_RETENTION_DAYS`.",
+    params(LoginAttemptQuery),
+    responses(
+        (status = 200, description = "Login attempts", body = PaginatedResponse<LoginAttemptResponse>),
+        (status = 400, description = "Invalid pagination parameters"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "User Management"
+)]
+pub async fn list_login_attempts(
+    State(db): State<DatabaseConnection>,
+    Extension(auth_user): Extension<AuthUser>,
+    Query(query): Query<LoginAttemptQuery>,
+) -> Result<Json<PaginatedResponse<LoginAttemptResponse>>, AppError> {
+    let (page, limit) = Pagination { page: query.page, limit: query.limit }.effective()?;
+
+    let mut select = login_attempt::Entity::find();
+    if let Some(username) = &query.username {
+        select = select.filter(login_attempt::Column::Username.eq(username.as_str()));
+    }
+    if let Some(success) = query.success {
+        select = select.filter(login_attempt::Column::Success.eq(success));
+    }
+    let paginator = select
+        .order_by_desc(login_attempt::Column::CreatedAt)
+        .order_by_desc(login_attempt::Column::Id)
+        .paginate(&db, limit);
+
+    let total_items = paginator.num_items().await?;
+    let attempts = paginator.fetch_page(page.saturating_sub(1)).await?;
+    let responses: Vec<LoginAttemptResponse> = attempts.into_iter().map(LoginAttemptResponse::from).collect();
+
+    println!("Audit | GET /admin/login-attempts | user={} | count={} | res=200", auth_user.username, total_items);
+    Ok(Json(PaginatedResponse::new(responses, total_items, page, limit)))
+}
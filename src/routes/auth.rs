@@ -1,47 +1,85 @@
 use axum::{
-    extract::State,
+    extract::{ConnectInfo, Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
     response::Json,
+    Extension,
 };
-use sea_orm::{DatabaseConnection, EntityTrait, QueryFilter, ColumnTrait, ActiveModelTrait, Set, IntoActiveModel};
+use sea_orm::{
+    DatabaseConnection, EntityTrait, QueryFilter, ColumnTrait, ActiveModelTrait, Set, IntoActiveModel,
+    PaginatorTrait, QueryOrder, QuerySelect, ConnectionTrait, TransactionTrait,
+};
+use sea_orm::sea_query::{Expr, Func};
+use std::net::SocketAddr;
 use serde::{Deserialize, Serialize};
 use argon2::{
-    password_hash::{PasswordHash, PasswordVerifier},
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
     Argon2,
 };
-use jsonwebtoken::{encode, EncodingKey, Header};
-use crate::entities::{user::{self, Entity as User}, refresh_token::{self, Entity as RefreshToken}};
+use crate::entities::{
+    user::{self, Entity as User},
+    refresh_token::{self, Entity as RefreshToken},
+    password_reset_token::{self, Entity as PasswordResetToken},
+    file, project,
+};
 use sha2::{Sha256, Digest};
 use base64::{Engine as _, engine::general_purpose};
 use rand::Rng;
 use uuid::Uuid;
 use crate::error::AppError;
+use crate::services::{audit, jwt, token_versions, totp};
+use crate::services::login_attempts::LoginAttemptRecorder;
+use serde_json::json;
+use crate::pagination::{Pagination, PaginatedResponse};
 
 #[derive(Deserialize, utoipa::ToSchema)]
 pub struct LoginRequest {
+    /// Username, or the account's email when the value contains `@`
     username: String,
     password: String,
+    /// Current authenticator code, or an unused recovery code; required once two-factor login is enabled
+    #[serde(default)]
+    otp: Option<String>,
 }
 
 #[derive(Serialize, utoipa::ToSchema)]
 pub struct LoginResponse {
     access_token: String,
-    refresh_token: String,
+    /// Absent with `?cookie=true`, where it is set as the `refresh_token` cookie instead
+    #[serde(skip_serializing_if = "Option::is_none")]
+    refresh_token: Option<String>,
     expires_in: usize,
+    /// Only `POST /auth/change-password` is accepted with this token until the password is changed
+    must_change_password: bool,
 }
 
 #[derive(Deserialize, utoipa::ToSchema)]
 pub struct RefreshRequest {
-    refresh_token: String,
+    /// Read from the `refresh_token` cookie when absent
+    #[serde(default)]
+    refresh_token: Option<String>,
 }
 
 #[derive(Serialize, utoipa::ToSchema)]
 pub struct RefreshResponse {
     access_token: String,
+    /// Replaces the refresh token that was sent, which is revoked. Absent in cookie mode,
+    /// where the cookie is replaced instead
+    #[serde(skip_serializing_if = "Option::is_none")]
+    refresh_token: Option<String>,
 }
 
 #[derive(Deserialize, utoipa::ToSchema)]
 pub struct LogoutRequest {
-    refresh_token: String,
+    /// Read from the `refresh_token` cookie when absent
+    #[serde(default)]
+    refresh_token: Option<String>,
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct CookieModeQuery {
+    /// Set the refresh token as an `HttpOnly` cookie instead of returning it in the body
+    #[serde(default)]
+    pub cookie: bool,
 }
 
 #[derive(Serialize, utoipa::ToSchema)]
@@ -49,16 +87,136 @@ pub struct LogoutResponse {
     message: String,
 }
 
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct LogoutAllResponse {
+    message: String,
+    /// Refresh tokens revoked by this call
+    revoked_sessions: u64,
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct ChangePasswordRequest {
+    current_password: String,
+    new_password: String,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct ChangePasswordResponse {
+    message: String,
+    /// Refresh tokens revoked by the change; every other session has to log in again
+    revoked_sessions: u64,
+    /// Replaces the token the change was made with, which stops working. Only from change-password
+    #[serde(skip_serializing_if = "Option::is_none")]
+    access_token: Option<String>,
+    /// Absent from reset-password, and with `?cookie=true`, where it is set as the `refresh_token` cookie instead
+    #[serde(skip_serializing_if = "Option::is_none")]
+    refresh_token: Option<String>,
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct ForgotPasswordRequest {
+    /// Username, or the account's email when the value contains `@`
+    username: String,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct ForgotPasswordResponse {
+    message: String,
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct ResetPasswordRequest {
+    /// Token from `POST /auth/forgot-password` or `POST /admin/password-reset/{user_id}`
+    token: String,
+    new_password: String,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct PasswordResetTokenResponse {
+    /// One-time token for `POST /auth/reset-password`; it is not stored and cannot be shown again
+    token: String,
+    expires_at: chrono::DateTime<chrono::Utc>,
+    #[schema(value_type = String)]
+    user_id: Uuid,
+    username: String,
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct IntrospectRequest {
+    /// An access token (JWT) or a refresh token
+    token: String,
+}
+
+/// Subset of an RFC 7662 introspection response; only `active` is set for inactive tokens.
+#[derive(Serialize, Default, utoipa::ToSchema)]
+pub struct IntrospectResponse {
+    active: bool,
+    /// `access_token` or `refresh_token`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    token_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    role: Option<user::Role>,
+    /// Expiry as a Unix timestamp
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exp: Option<usize>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct ImpersonateResponse {
+    access_token: String,
+    expires_in: usize,
+    #[schema(value_type = String)]
+    user_id: Uuid,
+    username: String,
+    #[schema(value_type = String)]
+    impersonated_by: Uuid,
+}
+
+/// Impersonation tokens are capped at 15 minutes and are never refreshable.
+const IMPERSONATION_TOKEN_TTL_SECS: i64 = 15 * 60;
+
 use crate::config::get_config;
 
 #[derive(Serialize, Deserialize)]
 struct Claims {
     sub: String,
     exp: usize,
+    /// Issue time
+    #[serde(default)]
+    iat: usize,
     role: user::Role,
     user_id: Uuid,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    impersonated_by: Option<Uuid>,
+    /// Checked by `auth_middleware`, which then only lets `/auth/change-password` through
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    must_change_password: bool,
+    /// `users.token_version` at issue time; `auth_middleware` rejects the token once it moves on
+    #[serde(default)]
+    token_version: i32,
 }
 
+/// A one-hour access token for the user as they are now: role, `must_change_password` and
+/// `token_version` are taken from `user`.
+fn issue_access_token(user: &user::Model) -> Result<String, AppError> {
+    let claims = Claims {
+        sub: user.username.clone(),
+        exp: (chrono::Utc::now() + chrono::Duration::hours(1)).timestamp() as usize,
+        iat: chrono::Utc::now().timestamp() as usize,
+        role: user.role.clone(),
+        user_id: user.id,
+        impersonated_by: None,
+        must_change_password: user.must_change_password,
+        token_version: user.token_version,
+    };
+
+    jwt::encode(&claims).map_err(|e| {
+        eprintln!("Token creation error: {}", e);
+        AppError::InternalServerError("Token creation failed".to_string())
+    })
+}
 
 fn generate_refresh_token() -> String {
     let mut random_bytes = [0u8; 32];
@@ -72,29 +230,232 @@ fn hash_token(token: &str) -> String {
     format!("{:x}", hasher.finalize())
 }
 
+/// Matches the `refresh_tokens.user_agent` column width.
+const MAX_USER_AGENT_LEN: usize = 512;
+
+/// The `User-Agent` header cut to `MAX_USER_AGENT_LEN` characters; `None` when missing or blank.
+/// Bytes that are not UTF-8 are replaced rather than dropping the header.
+fn client_user_agent(headers: &HeaderMap) -> Option<String> {
+    let ua = String::from_utf8_lossy(headers.get(header::USER_AGENT)?.as_bytes()).trim().to_string();
+    (!ua.is_empty()).then(|| ua.chars().take(MAX_USER_AGENT_LEN).collect())
+}
+
+/// Lifetime of a refresh token, and `Max-Age` of the cookie carrying it.
+const REFRESH_TOKEN_TTL_DAYS: i64 = 1;
+
+/// Cookie holding the refresh token for browser clients that opt in with `?cookie=true`.
+/// Scoped to `/auth`, so it only travels with refresh and logout requests.
+const REFRESH_COOKIE: &str = "refresh_token";
+
+/// `Set-Cookie` storing `token` as the refresh cookie; an empty token with `max_age` 0 clears it.
+fn refresh_cookie(token: &str, max_age: i64) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    let cookie = format!(
+        "{}={}; Path=/auth; Max-Age={}; HttpOnly; Secure; SameSite=Strict",
+        REFRESH_COOKIE, token, max_age
+    );
+    if let Ok(value) = header::HeaderValue::from_str(&cookie) {
+        headers.insert(header::SET_COOKIE, value);
+    }
+    headers
+}
+
+fn refresh_token_from_cookie(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|h| h.to_str().ok())
+        .flat_map(|h| h.split(';'))
+        .find_map(|pair| {
+            let (name, value) = pair.trim().split_once('=')?;
+            (name == REFRESH_COOKIE && !value.is_empty()).then(|| value.to_string())
+        })
+}
+
+/// Stores a new refresh token (valid for a day) with the client's metadata and returns it.
+///
+/// `rotated_from` is the token a refresh replaces: the new one joins its family, and keeps
+/// its user agent and IP where the refresh request does not provide them, so a session
+/// stays recognizable after clients that refresh from a background task. `None` starts a
+/// new family.
+async fn issue_refresh_token(
+    db: &DatabaseConnection,
+    user_id: Uuid,
+    rotated_from: Option<&refresh_token::Model>,
+    headers: &HeaderMap,
+    connect_info: Option<Extension<ConnectInfo<SocketAddr>>>,
+) -> Result<String, AppError> {
+    let refresh_token_str = generate_refresh_token();
+    let expires_at = chrono::Utc::now() + chrono::Duration::days(REFRESH_TOKEN_TTL_DAYS);
+    let id = Uuid::new_v4();
+
+    let user_agent = client_user_agent(headers).or_else(|| rotated_from.and_then(|t| t.user_agent.clone()));
+    let ip = connect_info
+        .map(|Extension(ConnectInfo(addr))| addr.ip().to_string())
+        .or_else(|| rotated_from.and_then(|t| t.ip.clone()));
+
+    let refresh_token = refresh_token::ActiveModel {
+        id: Set(id),
+        family_id: Set(rotated_from.map(|t| t.family_id).unwrap_or(id)),
+        user_id: Set(user_id),
+        token_hash: Set(hash_token(&refresh_token_str)),
+        expires_at: Set(expires_at),
+        created_at: Set(chrono::Utc::now()),
+        revoked: Set(false),
+        user_agent: Set(user_agent),
+        ip: Set(ip),
+    };
+
+    refresh_token.insert(db).await.map_err(|e| {
+        eprintln!("Refresh token DB error: {}", e);
+        AppError::DatabaseError(e)
+    })?;
+
+    Ok(refresh_token_str)
+}
+
+async fn revoke_token_family(db: &DatabaseConnection, family_id: Uuid) -> Result<u64, AppError> {
+    let result = RefreshToken::update_many()
+        .col_expr(refresh_token::Column::Revoked, Expr::value(true))
+        .filter(refresh_token::Column::FamilyId.eq(family_id))
+        .filter(refresh_token::Column::Revoked.eq(false))
+        .exec(db)
+        .await?;
+    Ok(result.rows_affected)
+}
+
+/// Revokes the user's oldest active refresh tokens beyond `MAX_SESSIONS_PER_USER`.
+/// Not serialized across concurrent logins, so the cap can briefly be exceeded by a token or two.
+async fn enforce_session_cap(db: &DatabaseConnection, user_id: Uuid) -> Result<u64, AppError> {
+    let cap = get_config().max_sessions_per_user;
+    if cap == 0 {
+        return Ok(0);
+    }
+
+    let stale_ids: Vec<Uuid> = RefreshToken::find()
+        .select_only()
+        .column(refresh_token::Column::Id)
+        .filter(refresh_token::Column::UserId.eq(user_id))
+        .filter(refresh_token::Column::Revoked.eq(false))
+        .filter(refresh_token::Column::ExpiresAt.gt(chrono::Utc::now()))
+        .order_by_desc(refresh_token::Column::CreatedAt)
+        .offset(cap)
+        .into_tuple()
+        .all(db)
+        .await
+        .map_err(AppError::DatabaseError)?;
+
+    if stale_ids.is_empty() {
+        return Ok(0);
+    }
+
+    let result = RefreshToken::update_many()
+        .col_expr(refresh_token::Column::Revoked, Expr::value(true))
+        .filter(refresh_token::Column::Id.is_in(stale_ids))
+        .exec(db)
+        .await
+        .map_err(AppError::DatabaseError)?;
+
+    Ok(result.rows_affected)
+}
+
+/// Signs the user out everywhere: revokes every active refresh token and bumps their
+/// `token_version`, so access tokens issued before this call get 401 as well.
+///
+/// Inside a transaction, call `token_versions::forget` once it has committed; another
+/// request may have cached the old version in between.
+pub(crate) async fn revoke_all_sessions<C: ConnectionTrait>(conn: &C, user_id: Uuid) -> Result<u64, AppError> {
+    token_versions::bump(conn, user_id).await.map_err(AppError::DatabaseError)?;
+
+    let result = RefreshToken::update_many()
+        .col_expr(refresh_token::Column::Revoked, Expr::value(true))
+        .filter(refresh_token::Column::UserId.eq(user_id))
+        .filter(refresh_token::Column::Revoked.eq(false))
+        .exec(conn)
+        .await
+        .map_err(AppError::DatabaseError)?;
+
+    Ok(result.rows_affected)
+}
+
+fn hash_password(password: &str) -> Result<String, AppError> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| {
+            eprintln!("Password hash error: {}", e);
+            AppError::InternalServerError("Password hashing failed".to_string())
+        })
+}
+
+/// Stores a new password reset token for the user, valid for `PASSWORD_RESET_TTL_MINS`, and
+/// returns it with its expiry. Earlier tokens stay valid until one of them is used.
+async fn issue_password_reset_token(db: &DatabaseConnection, user_id: Uuid) -> Result<(String, chrono::DateTime<chrono::Utc>), AppError> {
+    let token = generate_refresh_token();
+    let now = chrono::Utc::now();
+    let expires_at = now + chrono::Duration::minutes(get_config().password_reset_ttl_mins);
+
+    password_reset_token::ActiveModel {
+        id: Set(Uuid::new_v4()),
+        user_id: Set(user_id),
+        token_hash: Set(hash_token(&token)),
+        expires_at: Set(expires_at),
+        used: Set(false),
+        created_at: Set(now),
+    }
+    .insert(db)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok((token, expires_at))
+}
+
+/// Values with an `@` are matched against emails (ignoring case) first; usernames created
+/// before emails existed may contain `@`, so those still match by username after that.
+async fn find_login_user(db: &DatabaseConnection, login: &str) -> Result<Option<user::Model>, sea_orm::DbErr> {
+    if login.contains('@') {
+        let by_email = User::find()
+            .filter(Expr::expr(Func::lower(Expr::col(user::Column::Email))).eq(login.trim().to_lowercase()))
+            .one(db)
+            .await?;
+        if by_email.is_some() {
+            return Ok(by_email);
+        }
+    }
+
+    User::find().filter(user::Column::Username.eq(login)).one(db).await
+}
+
 #[utoipa::path(
     post,
     path = "/auth/login",
     request_body = LoginRequest,
+    params(CookieModeQuery),
+    description = "With `?cookie=true` the refresh token is set as an `HttpOnly; Secure; SameSite=Strict` cookie \
+scoped to `/auth` and left out of the body, so browser clients never hold it in script-readable storage.",
     responses(
         (status = 200, description = "Login successful", body = LoginResponse),
-        (status = 401, description = "Invalid credentials")
+        (status = 401, description = "Invalid credentials; `code` is `otp_required` when the account needs a two-factor code \
+and none was sent, `invalid_otp` when the code was wrong")
     ),
     tag = "Authentication"
 )]
 pub async fn login(
     State(db): State<DatabaseConnection>,
+    State(attempts): State<LoginAttemptRecorder>,
+    // Absent when the app is served without connect info (e.g. in-process tests)
+    connect_info: Option<Extension<ConnectInfo<SocketAddr>>>,
+    headers: HeaderMap,
+    Query(mode): Query<CookieModeQuery>,
     Json(payload): Json<LoginRequest>,
-) -> Result<Json<LoginResponse>, AppError> {
+) -> Result<(HeaderMap, Json<LoginResponse>), AppError> {
+    let ip = connect_info.as_ref().map(|Extension(ConnectInfo(addr))| addr.ip().to_string());
 
-    let user = User::find()
-        .filter(user::Column::Username.eq(&payload.username))
-        .one(&db)
-        .await
-        .map_err(|e| {
-            println!("DB Error: {}", e);
-            AppError::DatabaseError(e)
-        })?;
+    let user = find_login_user(&db, &payload.username).await.map_err(|e| {
+        println!("DB Error: {}", e);
+        AppError::DatabaseError(e)
+    })?;
 
     if let Some(user) = user {
 
@@ -107,63 +468,141 @@ pub async fn login(
             .verify_password(payload.password.as_bytes(), &parsed_hash)
             .is_ok()
         {
+            if user.totp_enabled_at.is_some() {
+                if let Err(e) = check_login_otp(&db, &user, payload.otp.as_deref()).await {
+                    if let AppError::UnauthorizedWithCode(code, _) = &e {
+                        attempts.record(&payload.username, Some(user.id), Some(code), ip);
+                    }
+                    return Err(e);
+                }
+            }
 
-            let expiration = (chrono::Utc::now() + chrono::Duration::hours(1)).timestamp() as usize;
-            
-            let claims = Claims {
-                sub: user.username.clone(),
-                exp: expiration,
-                role: user.role.clone(),
-                user_id: user.id,
-            };
+            let access_token = issue_access_token(&user)?;
 
-            let config = get_config();
-            let secret = config.jwt_secret.as_str();
-            let access_token = encode(
-                &Header::default(),
-                &claims,
-                &EncodingKey::from_secret(secret.as_bytes()),
-            )
-            .map_err(|e| {
-                eprintln!("Token creation error: {}", e);
-                AppError::InternalServerError("Token creation failed".to_string())
-            })?;
-
-            // Generate refresh token
-            let refresh_token_str = generate_refresh_token();
-            let refresh_token_hash = hash_token(&refresh_token_str);
-            let expires_at = chrono::Utc::now() + chrono::Duration::days(1);
-
-            let refresh_token = refresh_token::ActiveModel {
-                id: Set(Uuid::new_v4()),
-                user_id: Set(user.id),
-                token_hash: Set(refresh_token_hash),
-                expires_at: Set(expires_at.naive_utc()),
-                created_at: Set(chrono::Utc::now().naive_utc()),
-                revoked: Set(false),
-            };
+            let refresh_token_str = issue_refresh_token(&db, user.id, None, &headers, connect_info).await?;
 
-            refresh_token.insert(&db).await.map_err(|e| {
-                eprintln!("Refresh token DB error: {}", e);
-                AppError::DatabaseError(e)
-            })?;
+            let revoked = enforce_session_cap(&db, user.id).await?;
+            if revoked > 0 {
+                println!("Auth | POST /auth/login | user={} | session cap reached, revoked {} oldest refresh token(s)", user.username, revoked);
+            }
 
+            User::update_many()
+                .col_expr(user::Column::LastLoginAt, Expr::value(chrono::Utc::now()))
+                .col_expr(user::Column::LoginCount, Expr::col(user::Column::LoginCount).add(1))
+                .filter(user::Column::Id.eq(user.id))
+                .exec(&db)
+                .await?;
+
+            audit::record(&db, Some(user.id), "auth.login", "user", Some(user.id), json!({})).await;
+            attempts.record(&payload.username, Some(user.id), None, ip);
             println!("Auth | POST /auth/login | user={} | res=200", user.username);
-            return Ok(Json(LoginResponse {
-                access_token: access_token,
-                refresh_token: refresh_token_str,
+            let (cookie, refresh_token) = if mode.cookie {
+                (refresh_cookie(&refresh_token_str, REFRESH_TOKEN_TTL_DAYS * 86400), None)
+            } else {
+                (HeaderMap::new(), Some(refresh_token_str))
+            };
+            return Ok((cookie, Json(LoginResponse {
+                access_token,
+                refresh_token,
                 expires_in: 3600,
-            }));
+                must_change_password: user.must_change_password,
+            })));
         } else {
+            attempts.record(&payload.username, Some(user.id), Some("invalid_password"), ip);
             println!("Auth | POST /auth/login | user={} | res=401 (invalid password)", user.username);
         }
     } else {
+        attempts.record(&payload.username, None, Some("unknown_user"), ip);
         println!("Auth | POST /auth/login | user={} | res=401 (not found)", payload.username);
     }
 
     Err(AppError::Unauthorized("Invalid credentials".to_string()))
 }
 
+/// Second login factor: a TOTP code, or a recovery code which is used up.
+///
+/// Both are claimed with a conditional update, so two logins racing with the same code
+/// cannot both succeed: a TOTP code must be for a later step than the last one accepted,
+/// and a recovery code must still be in the list when it is removed.
+async fn check_login_otp(db: &DatabaseConnection, user: &user::Model, otp: Option<&str>) -> Result<(), AppError> {
+    let Some(otp) = otp.map(str::trim).filter(|otp| !otp.is_empty()) else {
+        println!("Auth | POST /auth/login | user={} | res=401 (otp required)", user.username);
+        return Err(AppError::UnauthorizedWithCode("otp_required", "Two-factor code required".to_string()));
+    };
+
+    let (secret, plain) = totp::open_secret(user.id, user.totp_secret.as_deref().unwrap_or_default())?;
+    if let Some(step) = totp::verify(&secret, otp, chrono::Utc::now().timestamp()) {
+        let claimed = User::update_many()
+            .col_expr(user::Column::TotpLastStep, Expr::value(step))
+            .filter(user::Column::Id.eq(user.id))
+            .filter(
+                sea_orm::Condition::any()
+                    .add(user::Column::TotpLastStep.is_null())
+                    .add(user::Column::TotpLastStep.lt(step)),
+            )
+            .exec(db)
+            .await?;
+        if claimed.rows_affected == 0 {
+            println!("Auth | POST /auth/login | user={} | res=401 (otp already used)", user.username);
+            return Err(AppError::UnauthorizedWithCode("invalid_otp", "Invalid two-factor code".to_string()));
+        }
+        if plain {
+            seal_plain_secret(db, user, &secret).await;
+        }
+        return Ok(());
+    }
+
+    let hash = totp::hash_recovery_code(otp);
+    let spent = User::update_many()
+        .col_expr(
+            user::Column::TotpRecoveryCodes,
+            Expr::cust_with_values("(totp_recovery_codes::jsonb - $1)::json", [hash.clone()]),
+        )
+        .filter(user::Column::Id.eq(user.id))
+        .filter(Expr::cust_with_values("jsonb_exists(totp_recovery_codes::jsonb, $1)", [hash]))
+        .exec(db)
+        .await?;
+    if spent.rows_affected == 1 {
+        let left = recovery_code_hashes(user).len().saturating_sub(1);
+        println!("Auth | POST /auth/login | user={} | recovery code used, {} left", user.username, left);
+        if plain {
+            seal_plain_secret(db, user, &secret).await;
+        }
+        return Ok(());
+    }
+
+    println!("Auth | POST /auth/login | user={} | res=401 (invalid otp)", user.username);
+    Err(AppError::UnauthorizedWithCode("invalid_otp", "Invalid two-factor code".to_string()))
+}
+
+/// Replaces a secret stored before sealing was added. Login goes on if this fails, e.g.
+/// while `TOTP_SECRET_KEY` is unset.
+async fn seal_plain_secret(db: &DatabaseConnection, user: &user::Model, secret: &str) {
+    let sealed = match totp::seal_secret(user.id, secret) {
+        Ok(sealed) => sealed,
+        Err(e) => {
+            println!("Auth | POST /auth/login | user={} | two-factor secret left unsealed | {}", user.username, e);
+            return;
+        }
+    };
+    let result = User::update_many()
+        .col_expr(user::Column::TotpSecret, Expr::value(sealed))
+        .filter(user::Column::Id.eq(user.id))
+        .filter(user::Column::TotpSecret.eq(secret))
+        .exec(db)
+        .await;
+    if let Err(e) = result {
+        println!("Auth | POST /auth/login | user={} | two-factor secret left unsealed | {}", user.username, e);
+    }
+}
+
+fn recovery_code_hashes(user: &user::Model) -> Vec<String> {
+    user.totp_recovery_codes
+        .as_array()
+        .map(|codes| codes.iter().filter_map(|c| c.as_str()).map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
 #[derive(Serialize, utoipa::ToSchema)]
 pub struct ErrorResponse {
     error: String,
@@ -173,19 +612,35 @@ pub struct ErrorResponse {
     post,
     path = "/auth/refresh",
     request_body = RefreshRequest,
+    description = "Exchanges a refresh token for a new access token and a new refresh token. The token sent is revoked, \
+so each refresh token works once; keep the one returned for the next refresh. Sending a token that was already \
+revoked also revokes every token rotated from the same login, so a leaked token cannot outlive its reuse.\n\n\
+Without `refresh_token` in the body the `refresh_token` cookie is used, and the new token replaces the cookie rather \
+than being returned; `?cookie=true` does the same for a token sent in the body.",
+    params(CookieModeQuery),
     responses(
         (status = 200, description = "Token refreshed successfully", body = RefreshResponse),
+        (status = 400, description = "No refresh token in the body or cookie"),
         (status = 401, description = "Invalid or expired refresh token", body = ErrorResponse)
     ),
     tag = "Authentication"
 )]
 pub async fn refresh(
     State(db): State<DatabaseConnection>,
-    Json(payload): Json<RefreshRequest>,
-) -> Result<Json<RefreshResponse>, AppError> {
-
-    
-    let token_hash = hash_token(&payload.refresh_token);
+    connect_info: Option<Extension<ConnectInfo<SocketAddr>>>,
+    headers: HeaderMap,
+    Query(mode): Query<CookieModeQuery>,
+    payload: Option<Json<RefreshRequest>>,
+) -> Result<(HeaderMap, Json<RefreshResponse>), AppError> {
+    let (presented, from_cookie) = match payload.and_then(|Json(p)| p.refresh_token) {
+        Some(token) => (token, false),
+        None => (
+            refresh_token_from_cookie(&headers)
+                .ok_or(AppError::BadRequest("refresh_token is required".to_string()))?,
+            true,
+        ),
+    };
+    let token_hash = hash_token(&presented);
     
     // Find refresh token in database
     let refresh_token = RefreshToken::find()
@@ -198,14 +653,24 @@ pub async fn refresh(
         })?
         .ok_or(AppError::Unauthorized("Invalid refresh token".to_string()))?;
 
-    // Check if token is revoked
+    // A revoked token coming back means it leaked or the client replayed it; either way the
+    // tokens rotated from it can no longer be trusted
     if refresh_token.revoked {
-        println!("Token is revoked");
+        let revoked = revoke_token_family(&db, refresh_token.family_id).await?;
+        println!(
+            "Auth | POST /auth/refresh | SECURITY | revoked token reused | user_id={} | family={} | revoked {} token(s)",
+            refresh_token.user_id, refresh_token.family_id, revoked
+        );
+        audit::record(&db, Some(refresh_token.user_id), "auth.refresh_reuse", "user", Some(refresh_token.user_id), json!({
+            "family_id": refresh_token.family_id,
+            "token_id": refresh_token.id,
+            "revoked": revoked,
+        })).await;
         return Err(AppError::Unauthorized("User logged out. Please re-login.".to_string()));
     }
 
     // Check if token is expired
-    let now = chrono::Utc::now().naive_utc();
+    let now = chrono::Utc::now();
     if refresh_token.expires_at < now {
         println!("Token is expired");
         return Err(AppError::Unauthorized("Refresh token expired. Please re-login.".to_string()));
@@ -221,52 +686,60 @@ pub async fn refresh(
         })?
         .ok_or(AppError::Unauthorized("User not found. Please re-login.".to_string()))?;
 
-    // Mark token as used
-    let mut active_token = refresh_token.into_active_model();
-    active_token.revoked = Set(true);
-    active_token.update(&db).await.map_err(|e| {
-        eprintln!("DB Error: {}", e);
-        AppError::DatabaseError(e)
-    })?;
-
-    // Generate new access token
-    let expiration = (chrono::Utc::now() + chrono::Duration::hours(1)).timestamp() as usize;
-    let username = user.username.clone();
-
-    let claims = Claims {
-        sub: user.username,
-        exp: expiration,
-        role: user.role,
-        user_id: user.id,
-    };
-
-    let config = get_config();
-    let secret = config.jwt_secret.as_str();
-    let token = encode(&Header::default(), &claims, &EncodingKey::from_secret(secret.as_bytes()))
+    // Mark token as used. Conditional, so two concurrent refreshes with the same token
+    // cannot both get a replacement.
+    let used = RefreshToken::update_many()
+        .col_expr(refresh_token::Column::Revoked, Expr::value(true))
+        .filter(refresh_token::Column::Id.eq(refresh_token.id))
+        .filter(refresh_token::Column::Revoked.eq(false))
+        .exec(&db)
+        .await
         .map_err(|e| {
-            println!("Token Encode Error: {}", e);
-            AppError::InternalServerError("Failed to generate token".to_string())
+            eprintln!("DB Error: {}", e);
+            AppError::DatabaseError(e)
         })?;
+    if used.rows_affected == 0 {
+        println!("Token is revoked");
+        return Err(AppError::Unauthorized("User logged out. Please re-login.".to_string()));
+    }
+
+    let new_refresh_token = issue_refresh_token(&db, user.id, Some(&refresh_token), &headers, connect_info).await?;
 
+    let token = issue_access_token(&user)?;
+    let username = user.username;
+    let user_id = user.id;
+
+    audit::record(&db, Some(user_id), "auth.refresh", "user", Some(user_id), json!({})).await;
     println!("Auth | POST /auth/refresh | user={} | res=200", username);
-    Ok(Json(RefreshResponse { access_token: token }))
+    if from_cookie || mode.cookie {
+        let cookie = refresh_cookie(&new_refresh_token, REFRESH_TOKEN_TTL_DAYS * 86400);
+        return Ok((cookie, Json(RefreshResponse { access_token: token, refresh_token: None })));
+    }
+    Ok((HeaderMap::new(), Json(RefreshResponse { access_token: token, refresh_token: Some(new_refresh_token) })))
 }
 
 #[utoipa::path(
     post,
     path = "/auth/logout",
     request_body = LogoutRequest,
+    description = "Revokes the refresh token from the body, else the one in the `refresh_token` cookie, and clears that cookie.",
     responses(
         (status = 200, description = "Logged out successfully", body = LogoutResponse),
+        (status = 400, description = "No refresh token in the body or cookie"),
         (status = 404, description = "Refresh token not found")
     ),
     tag = "Authentication"
 )]
 pub async fn logout(
     State(db): State<DatabaseConnection>,
-    Json(payload): Json<LogoutRequest>,
-) -> Result<Json<LogoutResponse>, AppError> {
-    let refresh_token_hash = hash_token(&payload.refresh_token);
+    headers: HeaderMap,
+    payload: Option<Json<LogoutRequest>>,
+) -> Result<(HeaderMap, Json<LogoutResponse>), AppError> {
+    let presented = payload
+        .and_then(|Json(p)| p.refresh_token)
+        .or_else(|| refresh_token_from_cookie(&headers))
+        .ok_or(AppError::BadRequest("refresh_token is required".to_string()))?;
+    let refresh_token_hash = hash_token(&presented);
 
     let refresh_token = RefreshToken::find()
         .filter(refresh_token::Column::TokenHash.eq(&refresh_token_hash))
@@ -278,6 +751,7 @@ pub async fn logout(
         })?
         .ok_or(AppError::NotFound("Token not found".to_string()))?;
 
+    let user_id = refresh_token.user_id;
     let mut active_token = refresh_token.into_active_model();
     active_token.revoked = Set(true);
     active_token.update(&db).await.map_err(|e| {
@@ -285,26 +759,776 @@ pub async fn logout(
         AppError::DatabaseError(e)
     })?;
 
+    audit::record(&db, Some(user_id), "auth.logout", "user", Some(user_id), json!({})).await;
     println!("Auth | POST /auth/logout | res=200");
-    Ok(Json(LogoutResponse {
+    Ok((refresh_cookie("", 0), Json(LogoutResponse {
         message: "Logged out successfully".to_string(),
+    })))
+}
+
+#[utoipa::path(
+    post,
+    path = "/auth/change-password",
+    request_body = ChangePasswordRequest,
+    params(CookieModeQuery),
+    description = "Replaces the caller's password and signs them out everywhere: all of their refresh tokens \
+are revoked and access tokens already issued, including the one sent, get 401. Also clears `must_change_password`. \
+The response carries a new access and refresh token for the caller to continue with; with `?cookie=true` the refresh \
+token is set as a cookie, as on login.",
+    responses(
+        (status = 200, description = "Password changed", body = ChangePasswordResponse),
+        (status = 422, description = "New password does not meet the password policy; `violations` lists the broken rules"),
+        (status = 401, description = "Current password is wrong or token is invalid"),
+        (status = 403, description = "Not allowed with an impersonation token")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Authentication"
+)]
+pub async fn change_password(
+    State(db): State<DatabaseConnection>,
+    axum::Extension(auth_user): axum::Extension<crate::middleware::auth::AuthUser>,
+    connect_info: Option<Extension<ConnectInfo<SocketAddr>>>,
+    headers: HeaderMap,
+    Query(mode): Query<CookieModeQuery>,
+    Json(payload): Json<ChangePasswordRequest>,
+) -> Result<(HeaderMap, Json<ChangePasswordResponse>), AppError> {
+    if auth_user.impersonated_by.is_some() {
+        println!("Auth | POST /auth/change-password | user={} | res=403 | Impersonation token", auth_user.username);
+        return Err(AppError::Forbidden("Not allowed with an impersonation token".to_string()));
+    }
+
+    if let Err(e) = get_config().password_policy.check(&payload.new_password) {
+        println!("Auth | POST /auth/change-password | user={} | res=422 | {}", auth_user.username, e);
+        return Err(e);
+    }
+
+    let user = User::find_by_id(auth_user.id)
+        .one(&db)
+        .await
+        .map_err(AppError::DatabaseError)?
+        .ok_or(AppError::Unauthorized("User not found".to_string()))?;
+
+    let parsed_hash = PasswordHash::new(&user.password).map_err(|e| {
+        eprintln!("Password hash parse error: {}", e);
+        AppError::InternalServerError("Password validation failed".to_string())
+    })?;
+
+    if Argon2::default()
+        .verify_password(payload.current_password.as_bytes(), &parsed_hash)
+        .is_err()
+    {
+        println!("Auth | POST /auth/change-password | user={} | res=401 | Wrong current password", user.username);
+        return Err(AppError::Unauthorized("Current password is incorrect".to_string()));
+    }
+
+    let password_hash = hash_password(&payload.new_password)?;
+
+    let txn = db.begin().await.map_err(AppError::DatabaseError)?;
+    let mut active_user = user.into_active_model();
+    active_user.password = Set(password_hash);
+    active_user.must_change_password = Set(false);
+    active_user.update(&txn).await.map_err(AppError::DatabaseError)?;
+    let revoked = revoke_all_sessions(&txn, auth_user.id).await?;
+    txn.commit().await.map_err(AppError::DatabaseError)?;
+    token_versions::forget(auth_user.id);
+
+    // Reloaded for the bumped token_version; the token sent with this request no longer works
+    let user = User::find_by_id(auth_user.id)
+        .one(&db)
+        .await
+        .map_err(AppError::DatabaseError)?
+        .ok_or(AppError::Unauthorized("User not found".to_string()))?;
+    let access_token = issue_access_token(&user)?;
+    let refresh_token_str = issue_refresh_token(&db, user.id, None, &headers, connect_info).await?;
+
+    println!("Auth | POST /auth/change-password | user={} | res=200 | revoked {} refresh token(s)", user.username, revoked);
+    let (cookie, refresh_token) = if mode.cookie {
+        (refresh_cookie(&refresh_token_str, REFRESH_TOKEN_TTL_DAYS * 86400), None)
+    } else {
+        (HeaderMap::new(), Some(refresh_token_str))
+    };
+    Ok((cookie, Json(ChangePasswordResponse {
+        message: "Password changed".to_string(),
+        revoked_sessions: revoked,
+        access_token: Some(access_token),
+        refresh_token,
+    })))
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct EnableTwoFactorRequest {
+    /// Current password
+    password: String,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct EnableTwoFactorResponse {
+    /// Base32 secret, for authenticator apps that cannot scan `otpauth_uri`
+    secret: String,
+    /// Render as a QR code for the authenticator app
+    otpauth_uri: String,
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct VerifyTwoFactorRequest {
+    /// Current code from the authenticator app
+    otp: String,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct VerifyTwoFactorResponse {
+    /// Single-use codes accepted as `otp` at login when the authenticator is lost. Shown only once.
+    recovery_codes: Vec<String>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/auth/2fa/enable",
+    request_body = EnableTwoFactorRequest,
+    description = "Starts two-factor login for the calling superuser: generates a TOTP secret and returns it with an \
+`otpauth://` URI. Nothing changes at login until the secret is confirmed with `POST /auth/2fa/verify`; calling this \
+again before that replaces the pending secret.",
+    responses(
+        (status = 200, description = "Secret generated", body = EnableTwoFactorResponse),
+        (status = 401, description = "Password is wrong or token is invalid"),
+        (status = 403, description = "Not a superuser, or an impersonation token"),
+        (status = 409, description = "Two-factor login is already enabled"),
+        (status = 503, description = "TOTP_SECRET_KEY is not configured")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Authentication"
+)]
+pub async fn enable_two_factor(
+    State(db): State<DatabaseConnection>,
+    axum::Extension(auth_user): axum::Extension<crate::middleware::auth::AuthUser>,
+    Json(payload): Json<EnableTwoFactorRequest>,
+) -> Result<Json<EnableTwoFactorResponse>, AppError> {
+    if auth_user.impersonated_by.is_some() {
+        println!("Auth | POST /auth/2fa/enable | user={} | res=403 | Impersonation token", auth_user.username);
+        return Err(AppError::Forbidden("Not allowed with an impersonation token".to_string()));
+    }
+
+    let user = User::find_by_id(auth_user.id)
+        .one(&db)
+        .await?
+        .ok_or(AppError::Unauthorized("User not found".to_string()))?;
+
+    let parsed_hash = PasswordHash::new(&user.password).map_err(|e| {
+        eprintln!("Password hash parse error: {}", e);
+        AppError::InternalServerError("Password validation failed".to_string())
+    })?;
+    if Argon2::default()
+        .verify_password(payload.password.as_bytes(), &parsed_hash)
+        .is_err()
+    {
+        println!("Auth | POST /auth/2fa/enable | user={} | res=401 | Wrong password", user.username);
+        return Err(AppError::Unauthorized("Password is incorrect".to_string()));
+    }
+
+    if user.totp_enabled_at.is_some() {
+        println!("Auth | POST /auth/2fa/enable | user={} | res=409 | Already enabled", user.username);
+        return Err(AppError::Conflict("Two-factor login is already enabled".to_string()));
+    }
+
+    let secret = totp::generate_secret().map_err(AppError::InternalServerError)?;
+    let sealed = totp::seal_secret(user.id, &secret)?;
+    let otpauth_uri = totp::otpauth_uri(&get_config().totp_issuer, &user.username, &secret);
+
+    let username = user.username.clone();
+    let mut active_user = user.into_active_model();
+    active_user.totp_secret = Set(Some(sealed));
+    active_user.totp_last_step = Set(None);
+    active_user.update(&db).await?;
+
+    println!("Auth | POST /auth/2fa/enable | user={} | res=200", username);
+    Ok(Json(EnableTwoFactorResponse { secret, otpauth_uri }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/auth/2fa/verify",
+    request_body = VerifyTwoFactorRequest,
+    description = "Confirms the secret from `POST /auth/2fa/enable` with a code from the authenticator app. From then on \
+`POST /auth/login` requires `otp`. Returns recovery codes, which are stored hashed and cannot be shown again.",
+    responses(
+        (status = 200, description = "Two-factor login enabled", body = VerifyTwoFactorResponse),
+        (status = 400, description = "No pending secret, or the code is wrong"),
+        (status = 403, description = "Not a superuser, or an impersonation token"),
+        (status = 409, description = "Two-factor login is already enabled")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Authentication"
+)]
+pub async fn verify_two_factor(
+    State(db): State<DatabaseConnection>,
+    axum::Extension(auth_user): axum::Extension<crate::middleware::auth::AuthUser>,
+    Json(payload): Json<VerifyTwoFactorRequest>,
+) -> Result<Json<VerifyTwoFactorResponse>, AppError> {
+    if auth_user.impersonated_by.is_some() {
+        println!("Auth | POST /auth/2fa/verify | user={} | res=403 | Impersonation token", auth_user.username);
+        return Err(AppError::Forbidden("Not allowed with an impersonation token".to_string()));
+    }
+
+    let user = User::find_by_id(auth_user.id)
+        .one(&db)
+        .await?
+        .ok_or(AppError::Unauthorized("User not found".to_string()))?;
+
+    if user.totp_enabled_at.is_some() {
+        println!("Auth | POST /auth/2fa/verify | user={} | res=409 | Already enabled", user.username);
+        return Err(AppError::Conflict("Two-factor login is already enabled".to_string()));
+    }
+    let Some(stored) = user.totp_secret.as_deref() else {
+        println!("Auth | POST /auth/2fa/verify | user={} | res=400 | No pending secret", user.username);
+        return Err(AppError::BadRequest("Call /auth/2fa/enable first".to_string()));
+    };
+    let (secret, _) = totp::open_secret(user.id, stored)?;
+    let Some(step) = totp::verify(&secret, &payload.otp, chrono::Utc::now().timestamp()) else {
+        println!("Auth | POST /auth/2fa/verify | user={} | res=400 | Invalid code", user.username);
+        return Err(AppError::BadRequest("Invalid two-factor code".to_string()));
+    };
+
+    let recovery_codes = totp::generate_recovery_codes().map_err(AppError::InternalServerError)?;
+    let hashes: Vec<String> = recovery_codes.iter().map(|code| totp::hash_recovery_code(code)).collect();
+
+    let username = user.username.clone();
+    let mut active_user = user.into_active_model();
+    active_user.totp_enabled_at = Set(Some(chrono::Utc::now()));
+    active_user.totp_recovery_codes = Set(json!(hashes));
+    // The confirming code cannot be used to log in
+    active_user.totp_last_step = Set(Some(step));
+    active_user.update(&db).await?;
+
+    audit::record_by(&db, &auth_user, "auth.2fa_enable", "user", Some(auth_user.id), json!({})).await;
+    println!("Auth | POST /auth/2fa/verify | user={} | res=200", username);
+    Ok(Json(VerifyTwoFactorResponse { recovery_codes }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/auth/forgot-password",
+    request_body = ForgotPasswordRequest,
+    description = "Starts a password reset. The answer is the same whether or not the account exists. \
+There is no email sender: with `PASSWORD_RESET_LOG_TOKENS=true` the one-time token is printed to the server log, \
+otherwise the request is only logged and a superuser issues the token with `POST /admin/password-reset/{user_id}`.",
+    responses(
+        (status = 202, description = "Request accepted", body = ForgotPasswordResponse)
+    ),
+    tag = "Authentication"
+)]
+pub async fn forgot_password(
+    State(db): State<DatabaseConnection>,
+    Json(payload): Json<ForgotPasswordRequest>,
+) -> Result<(StatusCode, Json<ForgotPasswordResponse>), AppError> {
+    let accepted = (
+        StatusCode::ACCEPTED,
+        Json(ForgotPasswordResponse {
+            message: "If the account exists, a password reset has been requested".to_string(),
+        }),
+    );
+
+    let Some(user) = find_login_user(&db, &payload.username).await.map_err(AppError::DatabaseError)? else {
+        println!("Auth | POST /auth/forgot-password | res=202 | Unknown account");
+        return Ok(accepted);
+    };
+
+    if !get_config().password_reset_log_tokens {
+        println!("Auth | POST /auth/forgot-password | user={} | res=202 | Reset requested, no token issued (PASSWORD_RESET_LOG_TOKENS is off)", user.username);
+        return Ok(accepted);
+    }
+
+    let (token, expires_at) = issue_password_reset_token(&db, user.id).await?;
+    println!("Auth | POST /auth/forgot-password | user={} | res=202 | Reset token {} valid until {}", user.username, token, expires_at);
+    Ok(accepted)
+}
+
+#[utoipa::path(
+    post,
+    path = "/auth/reset-password",
+    request_body = ResetPasswordRequest,
+    description = "Sets a new password with a one-time reset token and revokes all of the user's refresh tokens. \
+The token is consumed, and so are any other outstanding reset tokens of the user.",
+    responses(
+        (status = 200, description = "Password reset", body = ChangePasswordResponse),
+        (status = 400, description = "Token is invalid, expired or already used"),
+        (status = 422, description = "New password does not meet the password policy; `violations` lists the broken rules")
+    ),
+    tag = "Authentication"
+)]
+pub async fn reset_password(
+    State(db): State<DatabaseConnection>,
+    Json(payload): Json<ResetPasswordRequest>,
+) -> Result<Json<ChangePasswordResponse>, AppError> {
+    if let Err(e) = get_config().password_policy.check(&payload.new_password) {
+        println!("Auth | POST /auth/reset-password | res=422 | {}", e);
+        return Err(e);
+    }
+
+    let invalid = || AppError::BadRequest("Invalid or expired reset token".to_string());
+    let now = chrono::Utc::now();
+    let token = PasswordResetToken::find()
+        .filter(password_reset_token::Column::TokenHash.eq(hash_token(&payload.token)))
+        .one(&db)
+        .await
+        .map_err(AppError::DatabaseError)?
+        .filter(|t| !t.used && t.expires_at > now)
+        .ok_or_else(|| {
+            println!("Auth | POST /auth/reset-password | res=400 | Invalid, used or expired token");
+            invalid()
+        })?;
+
+    // Claim the token first, so two concurrent resets with it cannot both go through
+    let claimed = PasswordResetToken::update_many()
+        .col_expr(password_reset_token::Column::Used, Expr::value(true))
+        .filter(password_reset_token::Column::Id.eq(token.id))
+        .filter(password_reset_token::Column::Used.eq(false))
+        .exec(&db)
+        .await
+        .map_err(AppError::DatabaseError)?;
+    if claimed.rows_affected == 0 {
+        println!("Auth | POST /auth/reset-password | res=400 | Token already used");
+        return Err(invalid());
+    }
+
+    let user = User::find_by_id(token.user_id)
+        .one(&db)
+        .await
+        .map_err(AppError::DatabaseError)?
+        .ok_or_else(invalid)?;
+
+    let username = user.username.clone();
+    let mut active_user = user.into_active_model();
+    active_user.password = Set(hash_password(&payload.new_password)?);
+    active_user.must_change_password = Set(false);
+    active_user.update(&db).await.map_err(AppError::DatabaseError)?;
+
+    PasswordResetToken::update_many()
+        .col_expr(password_reset_token::Column::Used, Expr::value(true))
+        .filter(password_reset_token::Column::UserId.eq(token.user_id))
+        .filter(password_reset_token::Column::Used.eq(false))
+        .exec(&db)
+        .await
+        .map_err(AppError::DatabaseError)?;
+
+    let revoked = revoke_all_sessions(&db, token.user_id).await?;
+
+    println!("Auth | POST /auth/reset-password | user={} | res=200 | revoked {} refresh token(s)", username, revoked);
+    Ok(Json(ChangePasswordResponse {
+        message: "Password reset".to_string(),
+        revoked_sessions: revoked,
+        access_token: None,
+        refresh_token: None,
     }))
 }
 
+#[utoipa::path(
+    post,
+    path = "/admin/password-reset/{user_id}",
+    params(
+        ("user_id" = String, Path, description = "ID of the user whose password is reset")
+    ),
+    description = "Issues a one-time password reset token for the user and returns it, to be handed over out of band \
+and redeemed with `POST /auth/reset-password`. Other superusers' accounts are refused; use `/auth/change-password` for your own.",
+    responses(
+        (status = 201, description = "Reset token issued", body = PasswordResetTokenResponse),
+        (status = 403, description = "Target is another superuser"),
+        (status = 404, description = "User not found")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "User Management"
+)]
+pub async fn issue_password_reset(
+    State(db): State<DatabaseConnection>,
+    axum::Extension(auth_user): axum::Extension<crate::middleware::auth::AuthUser>,
+    axum::extract::Path(user_id): axum::extract::Path<Uuid>,
+) -> Result<(StatusCode, Json<PasswordResetTokenResponse>), AppError> {
+    let target = User::find_by_id(user_id)
+        .one(&db)
+        .await?
+        .ok_or(AppError::NotFound("User not found".to_string()))?;
+
+    if target.role == user::Role::Su && target.id != auth_user.id {
+        println!("Auth | POST /admin/password-reset/{} | user={} | res=403 | Target is a superuser", user_id, auth_user.username);
+        return Err(AppError::Forbidden("Cannot reset another superuser's password".to_string()));
+    }
+
+    let (token, expires_at) = issue_password_reset_token(&db, target.id).await?;
+
+    println!("Auth | POST /admin/password-reset/{} | user={} | target={} | res=201", user_id, auth_user.username, target.username);
+    Ok((
+        StatusCode::CREATED,
+        Json(PasswordResetTokenResponse {
+            token,
+            expires_at,
+            user_id: target.id,
+            username: target.username,
+        }),
+    ))
+}
+
+/// Header carrying `INTROSPECTION_SECRET` on `/auth/introspect` calls.
+const INTROSPECTION_SECRET_HEADER: &str = "x-introspection-secret";
+
+/// Compares digests rather than the raw strings so the check doesn't leak the secret's prefix through timing.
+fn introspection_secret_matches(headers: &HeaderMap, expected: &str) -> bool {
+    headers
+        .get(INTROSPECTION_SECRET_HEADER)
+        .and_then(|h| h.to_str().ok())
+        .is_some_and(|provided| Sha256::digest(provided.as_bytes()) == Sha256::digest(expected.as_bytes()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/.well-known/jwks.json",
+    description = "Public keys that verify access tokens (RFC 7517), for services that check tokens themselves. \
+With `JWT_ALGORITHM=RS256` the set holds the signing key, matched by the `kid` in token headers. With the default HS256 \
+it is empty: the shared secret is never published, so use `POST /auth/introspect` instead.",
+    responses(
+        (status = 200, description = "JSON Web Key Set", body = Object)
+    ),
+    tag = "Authentication"
+)]
+pub async fn jwks() -> Json<serde_json::Value> {
+    Json(jwt::jwks().clone())
+}
+
+#[utoipa::path(
+    post,
+    path = "/auth/introspect",
+    request_body = IntrospectRequest,
+    description = "Lets an API gateway ask whether a token is still acceptable beyond its signature and expiry \
+(a subset of RFC 7662). Authenticated with the `X-Introspection-Secret` header, which must equal `INTROSPECTION_SECRET`; \
+the endpoint answers 404 while that is unset.\n\n\
+A token is inactive when it is malformed or expired, its user no longer exists, or its `token_version` was revoked \
+(password change, logout-all, `POST /users/{id}/revoke-tokens`, a role change). Refresh tokens are also inactive once revoked.",
+    params(
+        ("X-Introspection-Secret" = String, Header, description = "Gateway secret from INTROSPECTION_SECRET")
+    ),
+    responses(
+        (status = 200, description = "Introspection result", body = IntrospectResponse),
+        (status = 401, description = "Missing or wrong gateway secret"),
+        (status = 404, description = "Introspection is disabled")
+    ),
+    tag = "Authentication"
+)]
+pub async fn introspect(
+    State(db): State<DatabaseConnection>,
+    headers: HeaderMap,
+    Json(payload): Json<IntrospectRequest>,
+) -> Result<Json<IntrospectResponse>, AppError> {
+    let config = get_config();
+    let Some(secret) = &config.introspection_secret else {
+        return Err(AppError::NotFound("Introspection is disabled".to_string()));
+    };
+    if !introspection_secret_matches(&headers, secret) {
+        println!("Auth | POST /auth/introspect | res=401 | Invalid gateway secret");
+        return Err(AppError::Unauthorized("Invalid gateway secret".to_string()));
+    }
+
+    // Refresh tokens are base64 without dots; anything shaped like a JWT is an access token
+    let (response, reason) = if payload.token.contains('.') {
+        introspect_access_token(&db, &payload.token).await?
+    } else {
+        introspect_refresh_token(&db, &payload.token).await?
+    };
+
+    match reason {
+        None => println!("Auth | POST /auth/introspect | user={} | res=200 | active", response.username.as_deref().unwrap_or_default()),
+        Some(reason) => println!("Auth | POST /auth/introspect | res=200 | inactive ({})", reason),
+    }
+    Ok(Json(response))
+}
+
+/// Inactive results carry the reason for the log line; it is not returned to the gateway.
+type Introspection = (IntrospectResponse, Option<&'static str>);
+
+fn inactive(reason: &'static str) -> Introspection {
+    (IntrospectResponse::default(), Some(reason))
+}
+
+async fn introspect_access_token(db: &DatabaseConnection, token: &str) -> Result<Introspection, AppError> {
+    let Ok(data) = jwt::decode::<Claims>(token) else {
+        return Ok(inactive("invalid or expired access token"));
+    };
+    let claims = data.claims;
+
+    let Some(user) = User::find_by_id(claims.user_id).one(db).await.map_err(AppError::DatabaseError)? else {
+        return Ok(inactive("user not found"));
+    };
+    if claims.token_version != user.token_version {
+        return Ok(inactive("token version revoked"));
+    }
+
+    Ok((
+        IntrospectResponse {
+            active: true,
+            token_type: Some("access_token".to_string()),
+            username: Some(claims.sub),
+            role: Some(claims.role),
+            exp: Some(claims.exp),
+        },
+        None,
+    ))
+}
+
+async fn introspect_refresh_token(db: &DatabaseConnection, token: &str) -> Result<Introspection, AppError> {
+    let Some((refresh_token, user)) = RefreshToken::find()
+        .filter(refresh_token::Column::TokenHash.eq(hash_token(token)))
+        .find_also_related(User)
+        .one(db)
+        .await
+        .map_err(AppError::DatabaseError)?
+    else {
+        return Ok(inactive("unknown refresh token"));
+    };
+
+    if refresh_token.revoked {
+        return Ok(inactive("refresh token revoked"));
+    }
+    if refresh_token.expires_at < chrono::Utc::now() {
+        return Ok(inactive("refresh token expired"));
+    }
+    let Some(user) = user else {
+        return Ok(inactive("user not found"));
+    };
+
+    Ok((
+        IntrospectResponse {
+            active: true,
+            token_type: Some("refresh_token".to_string()),
+            username: Some(user.username),
+            role: Some(user.role),
+            exp: Some(refresh_token.expires_at.timestamp() as usize),
+        },
+        None,
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/auth/logout-all",
+    description = "Revokes every refresh token of the caller in one go, e.g. after losing a device. \
+Access tokens already issued, including the one sent, get 401 from now on.",
+    responses(
+        (status = 200, description = "All sessions logged out", body = LogoutAllResponse),
+        (status = 401, description = "Unauthorized - Invalid or missing token"),
+        (status = 403, description = "Not allowed with an impersonation token")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Authentication"
+)]
+pub async fn logout_all(
+    State(db): State<DatabaseConnection>,
+    axum::Extension(auth_user): axum::Extension<crate::middleware::auth::AuthUser>,
+) -> Result<Json<LogoutAllResponse>, AppError> {
+    if auth_user.impersonated_by.is_some() {
+        println!("Auth | POST /auth/logout-all | user={} | res=403 | Impersonation token", auth_user.username);
+        return Err(AppError::Forbidden("Not allowed with an impersonation token".to_string()));
+    }
+
+    let revoked = revoke_all_sessions(&db, auth_user.id).await?;
+
+    println!("Auth | POST /auth/logout-all | user={} | res=200 | revoked {} refresh token(s)", auth_user.username, revoked);
+    Ok(Json(LogoutAllResponse {
+        message: "Logged out of all sessions".to_string(),
+        revoked_sessions: revoked,
+    }))
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct ListSessionsQuery {
+    pub page: Option<u64>,
+    pub limit: Option<u64>,
+    /// Also list revoked and expired refresh tokens (every refresh revokes the token it replaces)
+    #[serde(default)]
+    pub include_revoked: bool,
+}
+
 #[derive(Serialize, utoipa::ToSchema)]
-pub struct UserProfile {
+pub struct SessionResponse {
     #[schema(value_type = String)]
     id: Uuid,
-    username: String,
-    role: user::Role,
-    created_at: chrono::NaiveDateTime,
+    created_at: chrono::DateTime<chrono::Utc>,
+    expires_at: chrono::DateTime<chrono::Utc>,
+    revoked: bool,
+    /// `User-Agent` of the request that issued the token, or of the login it was rotated from
+    user_agent: Option<String>,
+    ip: Option<String>,
+    /// Shared by a login's token and every token rotated from it
+    #[schema(value_type = String)]
+    family_id: Uuid,
+}
+
+impl From<refresh_token::Model> for SessionResponse {
+    fn from(token: refresh_token::Model) -> Self {
+        Self {
+            id: token.id,
+            family_id: token.family_id,
+            created_at: token.created_at,
+            expires_at: token.expires_at,
+            revoked: token.revoked,
+            user_agent: token.user_agent,
+            ip: token.ip,
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/auth/sessions",
+    description = "Lists the caller's refresh tokens, newest first. By default only the usable ones \
+(not revoked, not expired); pass `include_revoked=true` for the full history.",
+    params(ListSessionsQuery),
+    responses(
+        (status = 200, description = "Sessions of the caller", body = PaginatedResponse<SessionResponse>),
+        (status = 400, description = "Invalid pagination parameters", body = ErrorResponse),
+        (status = 401, description = "Unauthorized - Invalid or missing token")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Authentication"
+)]
+pub async fn list_sessions(
+    State(db): State<DatabaseConnection>,
+    axum::Extension(auth_user): axum::Extension<crate::middleware::auth::AuthUser>,
+    Query(query): Query<ListSessionsQuery>,
+) -> Result<Json<PaginatedResponse<SessionResponse>>, AppError> {
+    let (page, limit) = Pagination { page: query.page, limit: query.limit }.effective()?;
+
+    let mut select = RefreshToken::find().filter(refresh_token::Column::UserId.eq(auth_user.id));
+    if !query.include_revoked {
+        select = select
+            .filter(refresh_token::Column::Revoked.eq(false))
+            .filter(refresh_token::Column::ExpiresAt.gt(chrono::Utc::now()));
+    }
+
+    let paginator = select.order_by_desc(refresh_token::Column::CreatedAt).paginate(&db, limit);
+    let total_items = paginator.num_items().await.map_err(AppError::DatabaseError)?;
+    let tokens = paginator.fetch_page(page.saturating_sub(1)).await.map_err(AppError::DatabaseError)?;
+
+    println!("Auth | GET /auth/sessions | user={} | count={} | res=200", auth_user.username, total_items);
+    Ok(Json(PaginatedResponse::new(
+        tokens.into_iter().map(SessionResponse::from).collect(),
+        total_items,
+        page,
+        limit,
+    )))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/auth/sessions/{id}",
+    description = "Revokes one of the caller's refresh tokens. Access tokens it already handed out stay \
+valid until they expire; use `/auth/logout-all` to cut those off as well.",
+    params(
+        ("id" = String, Path, description = "Session (refresh token) ID")
+    ),
+    responses(
+        (status = 200, description = "Session revoked", body = LogoutResponse),
+        (status = 401, description = "Unauthorized - Invalid or missing token"),
+        (status = 403, description = "Not allowed with an impersonation token"),
+        (status = 404, description = "No such session for the caller", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Authentication"
+)]
+pub async fn revoke_session(
+    State(db): State<DatabaseConnection>,
+    axum::Extension(auth_user): axum::Extension<crate::middleware::auth::AuthUser>,
+    Path(session_id): Path<Uuid>,
+) -> Result<Json<LogoutResponse>, AppError> {
+    if auth_user.impersonated_by.is_some() {
+        println!("Auth | DELETE /auth/sessions/{} | user={} | res=403 | Impersonation token", session_id, auth_user.username);
+        return Err(AppError::Forbidden("Not allowed with an impersonation token".to_string()));
+    }
+
+    let token = RefreshToken::find_by_id(session_id)
+        .filter(refresh_token::Column::UserId.eq(auth_user.id))
+        .one(&db)
+        .await
+        .map_err(AppError::DatabaseError)?;
+
+    let Some(token) = token else {
+        println!("Auth | DELETE /auth/sessions/{} | user={} | res=404 | Session not found", session_id, auth_user.username);
+        return Err(AppError::NotFound("Session not found".to_string()));
+    };
+
+    if !token.revoked {
+        let mut active = token.into_active_model();
+        active.revoked = Set(true);
+        active.update(&db).await.map_err(AppError::DatabaseError)?;
+    }
+
+    println!("Auth | DELETE /auth/sessions/{} | user={} | res=200 | Session revoked", session_id, auth_user.username);
+    Ok(Json(LogoutResponse {
+        message: "Session revoked".to_string(),
+    }))
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct UserProfile {
+    #[serde(flatten)]
+    user: crate::routes::users::UserResponse,
+    /// Only present with `?include=stats`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stats: Option<UserStats>,
+}
+
+/// What the user owns, trashed projects and their files left out.
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct UserStats {
+    pub projects: u64,
+    pub files: u64,
+    /// `SUM(files.size)`; variants are not counted
+    pub bytes: i64,
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct MeQuery {
+    /// `stats` adds the `stats` object, which costs two aggregate queries
+    pub include: Option<String>,
+}
+
+async fn user_stats(db: &DatabaseConnection, user_id: Uuid) -> Result<UserStats, AppError> {
+    let projects = project::Entity::find()
+        .filter(project::Column::OwnerId.eq(user_id))
+        .filter(project::Column::DeletedAt.is_null())
+        .count(db)
+        .await?;
+
+    let (files, bytes): (i64, i64) = file::Entity::find_active()
+        .select_only()
+        .column_as(Expr::cust("COUNT(*)"), "files")
+        .column_as(Expr::cust("COALESCE(SUM(files.size), 0)::BIGINT"), "bytes")
+        .filter(project::Column::OwnerId.eq(user_id))
+        .into_tuple()
+        .one(db)
+        .await?
+        .unwrap_or((0, 0));
+
+    Ok(UserStats { projects, files: files as u64, bytes })
 }
 
 #[utoipa::path(
     get,
     path = "/auth/me",
+    params(MeQuery),
     responses(
         (status = 200, description = "User profile retrieved successfully", body = UserProfile),
+        (status = 400, description = "Unknown include"),
         (status = 401, description = "Unauthorized - Invalid or missing token")
     ),
     security(
@@ -315,9 +1539,17 @@ pub struct UserProfile {
 pub async fn me(
     State(db): State<DatabaseConnection>,
     auth_user: axum::Extension<crate::middleware::auth::AuthUser>,
-) -> Result<Json<crate::routes::users::UserResponse>, AppError> {
-    let user = User::find()
-        .filter(user::Column::Username.eq(&auth_user.username))
+    Query(query): Query<MeQuery>,
+) -> Result<Json<UserProfile>, AppError> {
+    let mut include_stats = false;
+    for include in query.include.iter().flat_map(|i| i.split(',')).map(str::trim).filter(|i| !i.is_empty()) {
+        match include {
+            "stats" => include_stats = true,
+            other => return Err(AppError::BadRequest(format!("Unknown include '{}' (expected: stats)", other))),
+        }
+    }
+
+    let user = User::find_by_id(auth_user.id)
         .one(&db)
         .await
         .map_err(|e| {
@@ -326,6 +1558,94 @@ pub async fn me(
         })?
         .ok_or(AppError::Unauthorized("User not found".to_string()))?;
 
-    println!("Auth | GET /auth/me | user={} | res=200", user.username);
-    Ok(Json(crate::routes::users::UserResponse::from(user)))
+    let stats = if include_stats { Some(user_stats(&db, user.id).await?) } else { None };
+
+    println!("Auth | GET /auth/me | user={} | stats={} | res=200", user.username, include_stats);
+    let mut profile = crate::routes::users::UserResponse::from(user);
+    if auth_user.role != user::Role::Su {
+        profile = profile.without_login_activity();
+    }
+    Ok(Json(UserProfile { user: profile, stats }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/impersonate/{user_id}",
+    params(
+        ("user_id" = String, Path, description = "ID of the user to impersonate")
+    ),
+    description = "Issues a short-lived access token for the target user so support staff can see exactly what the account sees.\n\n\
+**Security considerations:**\n\
+- Superuser only. Another superuser cannot be impersonated, and impersonation tokens cannot mint further impersonation tokens.\n\
+- The token expires after 15 minutes and no refresh token is issued, so the session cannot be extended.\n\
+- The token carries an `impersonated_by` claim; every request made with it is logged with both identities, and each issuance is recorded in the audit log (`user.impersonate`).\n\
+- Superuser-only routes such as `/users` reject impersonation tokens with 403.\n\
+- Destructive endpoints (user deletion, permanent project deletion) reject impersonation tokens with 403.",
+    responses(
+        (status = 200, description = "Impersonation token issued", body = ImpersonateResponse),
+        (status = 400, description = "Cannot impersonate yourself"),
+        (status = 403, description = "Target user cannot be impersonated"),
+        (status = 404, description = "User not found")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "User Management"
+)]
+pub async fn impersonate(
+    State(db): State<DatabaseConnection>,
+    axum::Extension(auth_user): axum::Extension<crate::middleware::auth::AuthUser>,
+    axum::extract::Path(user_id): axum::extract::Path<Uuid>,
+) -> Result<Json<ImpersonateResponse>, AppError> {
+    if auth_user.impersonated_by.is_some() {
+        println!("Auth | POST /admin/impersonate/{} | user={} | res=403 | Nested impersonation", user_id, auth_user.username);
+        return Err(AppError::Forbidden("Impersonation tokens cannot impersonate other users".to_string()));
+    }
+
+    if auth_user.id == user_id {
+        println!("Auth | POST /admin/impersonate/{} | user={} | res=400 | Cannot impersonate yourself", user_id, auth_user.username);
+        return Err(AppError::BadRequest("Cannot impersonate yourself".to_string()));
+    }
+
+    let target = User::find_by_id(user_id)
+        .one(&db)
+        .await?
+        .ok_or(AppError::NotFound("User not found".to_string()))?;
+
+    if target.role == user::Role::Su {
+        println!("Auth | POST /admin/impersonate/{} | user={} | res=403 | Target is a superuser", user_id, auth_user.username);
+        return Err(AppError::Forbidden("Superusers cannot be impersonated".to_string()));
+    }
+
+    let expiration = (chrono::Utc::now() + chrono::Duration::seconds(IMPERSONATION_TOKEN_TTL_SECS)).timestamp() as usize;
+
+    let claims = Claims {
+        sub: target.username.clone(),
+        exp: expiration,
+        iat: chrono::Utc::now().timestamp() as usize,
+        role: target.role.clone(),
+        user_id: target.id,
+        impersonated_by: Some(auth_user.id),
+        must_change_password: false,
+        token_version: target.token_version,
+    };
+
+    let access_token = jwt::encode(&claims).map_err(|e| {
+        eprintln!("Token creation error: {}", e);
+        AppError::InternalServerError("Token creation failed".to_string())
+    })?;
+
+    audit::record_by(&db, &auth_user, "user.impersonate", "user", Some(target.id), json!({
+        "username": target.username,
+        "expires_in": IMPERSONATION_TOKEN_TTL_SECS,
+    })).await;
+
+    println!("Auth | POST /admin/impersonate/{} | user={} | target={} | res=200", user_id, auth_user.username, target.username);
+    Ok(Json(ImpersonateResponse {
+        access_token,
+        expires_in: IMPERSONATION_TOKEN_TTL_SECS as usize,
+        user_id: target.id,
+        username: target.username,
+        impersonated_by: auth_user.id,
+    }))
 }
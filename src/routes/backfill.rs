@@ -0,0 +1,133 @@
+use axum::{
+    extract::{Extension, State},
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+};
+use sea_orm::{ActiveModelTrait, DatabaseConnection, EntityTrait, PaginatorTrait, Set};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::entities::{job, project};
+use crate::error::AppError;
+use crate::middleware::auth::AuthUser;
+use crate::models::job::{BackfillAttribute, BackfillProgress, JobPayload};
+use crate::services::{backfill, variant_keys};
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct BackfillRequest {
+    /// Attributes to compute for files that lack them
+    pub attributes: Vec<BackfillAttribute>,
+    /// Limit the backfill to one project
+    #[schema(value_type = Option<String>)]
+    pub project_id: Option<Uuid>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct BackfillResponse {
+    /// `null` when no file needs backfilling
+    #[schema(value_type = Option<String>)]
+    pub job_id: Option<Uuid>,
+    /// Files missing at least one requested attribute
+    pub files: u64,
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/backfill",
+    description = "Queue a `backfill` job that computes the requested attributes for existing files that lack them (superuser only). \
+Each original is downloaded once, at most `BACKFILL_READS_PER_SEC` per second; `variant_keys` rewrites \
+legacy URL entries in `variants_json` without downloading anything. Progress is kept under `progress` in the job payload \
+and the final counts under `result`.",
+    request_body = BackfillRequest,
+    responses(
+        (status = 202, description = "Backfill job queued", body = BackfillResponse),
+        (status = 200, description = "Nothing to backfill", body = BackfillResponse),
+        (status = 400, description = "No attributes given"),
+        (status = 403, description = "Superuser access required"),
+        (status = 404, description = "Project not found")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Jobs"
+)]
+pub async fn create_backfill(
+    State(db): State<DatabaseConnection>,
+    Extension(user): Extension<AuthUser>,
+    Json(payload): Json<BackfillRequest>,
+) -> Result<Response, AppError> {
+    let mut attributes: Vec<BackfillAttribute> = Vec::new();
+    for attribute in payload.attributes {
+        if !attributes.contains(&attribute) {
+            attributes.push(attribute);
+        }
+    }
+    if attributes.is_empty() {
+        println!("Backfill | POST /admin/backfill | user={} | res=400 | No attributes", user.username);
+        return Err(AppError::BadRequest("At least one attribute is required".to_string()));
+    }
+
+    if let Some(project_id) = payload.project_id {
+        if project::Entity::find_by_id(project_id).one(&db).await?.is_none() {
+            println!("Backfill | POST /admin/backfill | user={} | project={} | res=404 | Project not found", user.username, project_id);
+            return Err(AppError::NotFound("Project not found".to_string()));
+        }
+    }
+
+    let files = backfill::candidates(&attributes, payload.project_id).count(&db).await?;
+    if files == 0 {
+        println!("Backfill | POST /admin/backfill | user={} | files=0 | res=200", user.username);
+        return Ok(Json(BackfillResponse { job_id: None, files: 0 }).into_response());
+    }
+
+    let job = job::ActiveModel {
+        id: Set(Uuid::new_v4()),
+        file_id: Set(None),
+        project_id: Set(payload.project_id),
+        status: Set("pending".to_string()),
+        payload: Set(JobPayload::Backfill {
+            attributes,
+            project_id: payload.project_id,
+            progress: BackfillProgress::default(),
+        }.to_value()),
+        created_at: Set(chrono::Utc::now()),
+        updated_at: Set(chrono::Utc::now()),
+    };
+    let job = job.insert(&db).await?;
+
+    println!("Backfill | POST /admin/backfill | user={} | files={} | job={} | res=202", user.username, files, job.id);
+    Ok((StatusCode::ACCEPTED, Json(BackfillResponse { job_id: Some(job.id), files })).into_response())
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct LegacyVariantsResponse {
+    /// Active files with at least one variant stored as a full URL; `backfill` with
+    /// `variant_keys` rewrites them
+    pub files: u64,
+    /// Legacy entries this instance has read since the process started
+    pub seen: u64,
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/variants/legacy",
+    description = "How many files still store variants as full URLs instead of bare object keys, and how often this instance \
+has met such entries since it started (superuser only). Once `files` is 0 the URL fallbacks are no longer needed.",
+    responses(
+        (status = 200, description = "Legacy variant entries", body = LegacyVariantsResponse),
+        (status = 403, description = "Superuser access required")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Jobs"
+)]
+pub async fn get_legacy_variants(
+    State(db): State<DatabaseConnection>,
+    Extension(user): Extension<AuthUser>,
+) -> Result<Json<LegacyVariantsResponse>, AppError> {
+    let files = backfill::candidates(&[BackfillAttribute::VariantKeys], None).count(&db).await?;
+    let seen = variant_keys::legacy_seen();
+    println!("Backfill | GET /admin/variants/legacy | user={} | files={} | seen={} | res=200", user.username, files, seen);
+    Ok(Json(LegacyVariantsResponse { files, seen }))
+}
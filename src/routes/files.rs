@@ -1,28 +1,50 @@
 use axum::{
     extract::{Path, Query, State, Extension},
-    response::{Redirect},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Redirect, Response},
     Json,
 };
 use sea_orm::{
-    ColumnTrait, EntityTrait, QueryFilter, QueryOrder, QuerySelect, PaginatorTrait,
-    Condition,
+    ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, QueryOrder, QuerySelect, PaginatorTrait,
+    Set,
 };
+use sea_orm::sea_query::{Condition, Expr, LikeExpr};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::hash_map::{Entry, HashMap};
+use std::collections::HashSet;
+use std::sync::Arc;
 use std::time::Duration;
 use uuid::Uuid;
 
-use crate::entities::{file, project};
+use crate::entities::{file, job, project};
+use crate::entities::api_key::ApiKeyScope;
+use crate::entities::user::Role;
 use crate::error::AppError;
+use crate::middleware::api_key::ProjectContext;
 use crate::middleware::auth::AuthUser;
-use crate::pagination::PaginatedResponse;
-use crate::services::s3::S3Service;
+use crate::pagination::{PageGroup, Pagination, PaginatedResponse};
+use crate::models::job::JobPayload;
+use crate::models::settings::{ProjectSettings, VariantFallback};
+use crate::services::file_events::{self, FileEvent};
+use crate::services::integrity::{self, IntegrityReport};
+use crate::services::project_storage;
+use crate::services::tombstones::{self, DeletionReason};
+use crate::services::storage::Storage;
+use crate::services::urls::UrlBuilder;
+use crate::services::scope::{self, FileFilters, Scope};
+use crate::services::variant_keys;
+use crate::utils::{content_disposition, content_type, escape_like, normalize_folder};
+use crate::routes::expected_version;
 
 #[derive(Deserialize, utoipa::IntoParams)]
 pub struct ListFilesQuery {
     pub page: Option<u64>,
     pub limit: Option<u64>,
     pub project_id: Option<Uuid>,
+    /// Leave out `srcset` and `sizes`
+    #[serde(default)]
+    pub no_srcset: bool,
 }
 
 #[derive(Serialize, utoipa::ToSchema)]
@@ -35,25 +57,42 @@ pub struct FileResponse {
     pub url: String, // Public URL (if public) or Presigned
     #[schema(value_type = Object)]
     pub variants: Value,
-    pub created_at: String,
+    /// Hex SHA-256 of the original upload, when recorded
+    pub content_hash: Option<String>,
+    /// Pseudo-folder such as `a/b/`; empty at the project root
+    pub folder: String,
+    /// Source file this one was generated from, e.g. the video a poster came from
+    pub derived_from: Option<Uuid>,
+    /// Files generated from this one; only present with `?include=derived`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(no_recursion)]
+    pub derived: Option<Vec<FileResponse>>,
+    /// Last failure per variant, `{"thumb": {"error", "failed_at"}}`; only present with `?include=errors`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub variant_errors: Option<Value>,
+    /// The original changed after `variants` were generated; `sync-variants?only_stale=true` regenerates them
+    pub variants_stale: bool,
+    /// Pixel size per generated variant, `{"thumb": {"width", "height"}}`
+    #[schema(value_type = Object)]
+    pub variant_dimensions: Value,
+    /// Hex SHA-256 per generated variant, like `content_hash` for the original; unchanged by a sync that produced the same bytes
+    #[schema(value_type = Object)]
+    pub variant_hashes: Value,
+    /// Image variants by ascending width, `"<url> 320w, <url> 1280w"`; absent without variant widths or with `no_srcset`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub srcset: Option<String>,
+    /// The project's `sizes` setting, passed through alongside `srcset`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sizes: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    /// Send back in `If-Match` to update only if nobody changed the file meanwhile
+    pub version: i32,
 }
 
-impl From<file::Model> for FileResponse {
-    fn from(model: file::Model) -> Self {
-        // Construct public URL
-        // We need the config to get bucket name/endpoint, but simpler is to use S3Service helper if we had one.
-        // For now, let's assume standard S3 path structure for public or we can return the key.
-        // The requirement says "Public URL".
-        
-        let config = crate::config::get_config();
-        let base_url = if let Some(endpoint) = &config.s3_endpoint {
-            format!("{}/{}", endpoint, config.s3_bucket_name)
-        } else {
-             format!("https://{}.s3.{}.amazonaws.com", config.s3_bucket_name, config.aws_region)
-        };
-
-        let url = format!("{}/{}", base_url, model.s3_key);
-
+impl FileResponse {
+    fn new(model: file::Model, url: String) -> Self {
+        let variants_stale = model.variants_stale();
         Self {
             id: model.id,
             project_id: model.project_id,
@@ -62,9 +101,108 @@ impl From<file::Model> for FileResponse {
             size: model.size,
             url,
             variants: model.variants_json, // This is already Value
-            created_at: model.created_at.to_string(),
+            content_hash: model.content_hash,
+            folder: model.folder,
+            derived_from: model.derived_from,
+            derived: None,
+            variant_errors: None,
+            variants_stale,
+            variant_dimensions: model.variant_dimensions,
+            variant_hashes: model.variant_hashes,
+            srcset: None,
+            sizes: None,
+            created_at: model.created_at,
+            version: model.version,
         }
     }
+
+    /// `url` (and `srcset`, unless `no_srcset`) are built by `urls` against the bucket the
+    /// file's project stores into.
+    pub async fn build(db: &sea_orm::DatabaseConnection, urls: &UrlBuilder, model: file::Model, no_srcset: bool) -> Result<Self, AppError> {
+        let target = ResponseTarget::load(db, model.project_id, no_srcset).await?;
+        Self::build_for(urls, &target, model, no_srcset).await
+    }
+
+    /// Builds a page of responses, resolving each project's storage once.
+    pub async fn build_all(db: &sea_orm::DatabaseConnection, urls: &UrlBuilder, models: Vec<file::Model>, no_srcset: bool) -> Result<Vec<Self>, AppError> {
+        let mut targets: HashMap<Uuid, ResponseTarget> = HashMap::new();
+        let mut responses = Vec::with_capacity(models.len());
+        for model in models {
+            let target = match targets.entry(model.project_id) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => entry.insert(ResponseTarget::load(db, model.project_id, no_srcset).await?),
+            };
+            responses.push(Self::build_for(urls, target, model, no_srcset).await?);
+        }
+        Ok(responses)
+    }
+
+    async fn build_for(urls: &UrlBuilder, target: &ResponseTarget, model: file::Model, no_srcset: bool) -> Result<Self, AppError> {
+        variant_keys::check(model.id, &model.variants_json);
+        let url = urls.object_url(target.storage.as_ref(), &model.s3_key).await?;
+        let srcset = if no_srcset { None } else { srcset(urls, target.storage.as_ref(), &model).await? };
+        let sizes = srcset.as_ref().and(target.sizes.clone());
+
+        let mut response = Self::new(model, url);
+        response.srcset = srcset;
+        response.sizes = sizes;
+        Ok(response)
+    }
+}
+
+/// Per-project pieces of a `FileResponse`: the bucket and, when `srcset` is wanted, `sizes`.
+struct ResponseTarget {
+    storage: Arc<dyn Storage>,
+    sizes: Option<String>,
+}
+
+impl ResponseTarget {
+    async fn load(db: &sea_orm::DatabaseConnection, project_id: Uuid, no_srcset: bool) -> Result<Self, AppError> {
+        let storage = project_storage::for_project(db, project_id).await?;
+        let sizes = if no_srcset {
+            None
+        } else {
+            project::Entity::find_by_id(project_id)
+                .one(db)
+                .await?
+                .and_then(|p| serde_json::from_value::<ProjectSettings>(p.settings).ok())
+                .and_then(|settings| settings.sizes)
+        };
+        Ok(Self { storage, sizes })
+    }
+}
+
+/// `srcset` of an image's variants with a recorded width, narrowest first. Variants of equal
+/// width (e.g. the same size in two formats) keep only the first by name, since browsers
+/// reject repeated descriptors. `None` for non-images and files without variant widths.
+async fn srcset(urls: &UrlBuilder, storage: &dyn Storage, model: &file::Model) -> Result<Option<String>, AppError> {
+    if !model.mime_type.starts_with("image/") {
+        return Ok(None);
+    }
+
+    let mut candidates: Vec<(u64, &String, String)> = model
+        .variants_json
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter_map(|(name, value)| {
+            let width = model.variant_dimensions.get(name)?.get("width")?.as_u64()?;
+            let key = variant_keys::object_key(value.as_str()?, storage.bucket_name())?;
+            Some((width, name, key))
+        })
+        .collect();
+    candidates.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.cmp(b.1)));
+    candidates.dedup_by_key(|(width, _, _)| *width);
+
+    if candidates.is_empty() {
+        return Ok(None);
+    }
+
+    let mut entries = Vec::with_capacity(candidates.len());
+    for (width, _, key) in candidates {
+        entries.push(format!("{} {}w", urls.object_url(storage, &key).await?, width));
+    }
+    Ok(Some(entries.join(", ")))
 }
 
 #[utoipa::path(
@@ -73,10 +211,12 @@ impl From<file::Model> for FileResponse {
     params(
         ("page" = Option<u64>, Query, description = "Page number"),
         ("limit" = Option<u64>, Query, description = "Items per page"),
-        ("project_id" = Option<Uuid>, Query, description = "Filter by Project ID")
+        ("project_id" = Option<Uuid>, Query, description = "Filter by Project ID"),
+        ("no_srcset" = Option<bool>, Query, description = "Leave out `srcset` and `sizes`")
     ),
     responses(
         (status = 200, description = "List of files", body = PaginatedResponse<FileResponse>),
+        (status = 400, description = "Invalid pagination parameters"),
         (status = 500, description = "Internal server error")
     ),
     security(
@@ -87,77 +227,38 @@ impl From<file::Model> for FileResponse {
 pub async fn list_files(
     Extension(user): Extension<AuthUser>,
     State(db): State<sea_orm::DatabaseConnection>,
+    State(urls): State<UrlBuilder>,
     Query(query): Query<ListFilesQuery>,
 ) -> Result<Json<PaginatedResponse<FileResponse>>, AppError> {
-    let page = query.page.unwrap_or(1);
-    let limit = query.limit.unwrap_or(10);
-
-    // 2. Build Filter
-    let mut condition = Condition::all();
-
-    // Role-based Access Control
-    match user.role {
-        crate::entities::user::Role::Su => {
-            // SU can see all files, or filter by specific project
-            if let Some(pid) = query.project_id {
-                condition = condition.add(file::Column::ProjectId.eq(pid));
-            }
-        },
-        _ => {
-            // Admin/User can only see files from projects they own
-            // First, find all project IDs owned by this user
-            let user_projects: Vec<Uuid> = project::Entity::find()
-                .filter(project::Column::OwnerId.eq(user.id))
-                .select_only()
-                .column(project::Column::Id)
-                .into_tuple()
-                .all(&db)
-                .await
-                .map_err(|e| AppError::InternalServerError(e.to_string()))?;
-
-            if user_projects.is_empty() {
-                // If user has no projects, return empty list immediately
-                return Ok(Json(PaginatedResponse {
-                    data: vec![],
-                    total_items: 0,
-                    total_pages: 0,
-                    current_page: page,
-                    page_size: limit,
-                }));
-            }
+    let (page, limit) = Pagination { page: query.page, limit: query.limit }.effective_in(PageGroup::Files)?;
 
-            if let Some(pid) = query.project_id {
-                // If requesting specific project, verify ownership
-                if !user_projects.contains(&pid) {
-                    return Err(AppError::Forbidden("Access denied to this project".into()));
-                }
-                condition = condition.add(file::Column::ProjectId.eq(pid));
-            } else {
-                // Filter where project_id IN (user_projects)
-                condition = condition.add(file::Column::ProjectId.is_in(user_projects));
-            }
+    let scope = Scope::for_user(&user);
+    if let Some(pid) = query.project_id {
+        if !scope.includes_id(&db, pid).await? {
+            return Err(AppError::Forbidden("Access denied to this project".into()));
         }
     }
 
-    // 3. Execute Query
-    let paginator = file::Entity::find()
-        .filter(condition)
+    let filters = FileFilters { project_id: query.project_id, ..Default::default() };
+    let paginator = scope::files(scope, &filters)
         .order_by_desc(file::Column::CreatedAt)
         .paginate(&db, limit);
 
     let total_items = paginator.num_items().await.map_err(|e| AppError::InternalServerError(e.to_string()))?;
-    let total_pages = paginator.num_pages().await.map_err(|e| AppError::InternalServerError(e.to_string()))?;
-    let items = paginator.fetch_page(page - 1).await.map_err(|e| AppError::InternalServerError(e.to_string()))?;
+    let items = paginator.fetch_page(page.saturating_sub(1)).await.map_err(|e| AppError::InternalServerError(e.to_string()))?;
 
-    let data: Vec<FileResponse> = items.into_iter().map(FileResponse::from).collect();
+    let data = FileResponse::build_all(&db, &urls, items, query.no_srcset).await?;
 
-    Ok(Json(PaginatedResponse {
-        data,
-        total_items,
-        total_pages,
-        current_page: page,
-        page_size: limit,
-    }))
+    Ok(Json(PaginatedResponse::new_in(PageGroup::Files, data, total_items, page, limit)))
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct GetFileQuery {
+    /// Comma-separated extras: `derived` lists the files generated from this one, `errors` adds `variant_errors`
+    pub include: Option<String>,
+    /// Leave out `srcset` and `sizes`
+    #[serde(default)]
+    pub no_srcset: bool,
 }
 
 // GET /files/:id
@@ -165,11 +266,14 @@ pub async fn list_files(
     get,
     path = "/files/{id}",
     params(
-        ("id" = Uuid, Path, description = "File ID")
+        ("id" = Uuid, Path, description = "File ID"),
+        GetFileQuery
     ),
     responses(
         (status = 200, description = "File details", body = FileResponse),
+        (status = 400, description = "Unknown include"),
         (status = 404, description = "File not found"),
+        (status = 410, description = "File was permanently deleted; the body carries `deleted_at` and `reason`"),
         (status = 500, description = "Internal server error")
     ),
     security(
@@ -181,34 +285,307 @@ pub async fn get_file(
     Path(id): Path<Uuid>,
     Extension(user): Extension<AuthUser>,
     State(db): State<sea_orm::DatabaseConnection>,
+    State(urls): State<UrlBuilder>,
+    Query(query): Query<GetFileQuery>,
 ) -> Result<Json<FileResponse>, AppError> {
+    let mut include_derived = false;
+    let mut include_errors = false;
+    for include in query.include.iter().flat_map(|i| i.split(',')).map(str::trim).filter(|i| !i.is_empty()) {
+        match include {
+            "derived" => include_derived = true,
+            "errors" => include_errors = true,
+            other => return Err(AppError::BadRequest(format!("Unknown include '{}' (expected: derived, errors)", other))),
+        }
+    }
+
     // 1. Get File
-    let file = file::Entity::find_by_id(id)
+    let Some(file) = file::Entity::find_active()
+        .filter(file::Column::Id.eq(id))
         .one(&db)
         .await
         .map_err(|e| AppError::InternalServerError(e.to_string()))?
-        .ok_or(AppError::NotFound("File not found".into()))?;
+    else {
+        return Err(tombstones::missing_file(&db, id).await);
+    };
 
     // 3. Verify Access
-    if user.role != crate::entities::user::Role::Su {
-        // Check if user owns the project this file belongs to
-        let project = project::Entity::find_by_id(file.project_id)
-            .one(&db)
-            .await
-            .map_err(|e| AppError::InternalServerError(e.to_string()))?
-            .ok_or(AppError::NotFound("Project not found".into()))?; // Should not happen for valid file
-
-        if project.owner_id != user.id {
-            return Err(AppError::Forbidden("Access denied to this file".into()));
-        }
+    if !Scope::for_user(&user).includes_id(&db, file.project_id).await? {
+        return Err(AppError::Forbidden("Access denied to this file".into()));
+    }
+
+    let derived = if include_derived {
+        let models = file::Entity::find_active()
+            .filter(file::Column::DerivedFrom.eq(file.id))
+            .order_by_asc(file::Column::CreatedAt)
+            .all(&db)
+            .await?;
+        Some(FileResponse::build_all(&db, &urls, models, query.no_srcset).await?)
+    } else {
+        None
+    };
+
+    let variant_errors = include_errors.then(|| file.variant_errors.clone());
+    let mut response = FileResponse::build(&db, &urls, file, query.no_srcset).await?;
+    response.derived = derived;
+    response.variant_errors = variant_errors;
+    Ok(Json(response))
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct FolderQuery {
+    /// Project to browse
+    pub project_id: Uuid,
+    /// Folder to list, e.g. `a/b/` (the project root when omitted)
+    pub prefix: Option<String>,
+    /// Page of the files directly under `prefix`
+    pub page: Option<u64>,
+    pub limit: Option<u64>,
+    /// Leave out `srcset` and `sizes`
+    #[serde(default)]
+    pub no_srcset: bool,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct FolderEntry {
+    /// Last path segment, e.g. `c`
+    pub name: String,
+    /// Full folder path, e.g. `a/b/c/`; pass it back as `prefix` to descend
+    pub path: String,
+    /// Files anywhere below this folder
+    pub file_count: i64,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct FolderListingResponse {
+    pub project_id: Uuid,
+    /// The normalized prefix that was listed
+    pub prefix: String,
+    /// Immediate child folders, by name
+    pub folders: Vec<FolderEntry>,
+    /// Files directly under `prefix`, by filename
+    pub files: PaginatedResponse<FileResponse>,
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct UpdateFileRequest {
+    /// New pseudo-folder (`""` or `/` for the project root)
+    pub folder: String,
+    /// Fail with `412` unless the file is still at this version; same as `If-Match`
+    pub expected_version: Option<i32>,
+}
+
+// GET /files/folders
+#[utoipa::path(
+    get,
+    path = "/files/folders",
+    description = "Browse a project's files as a folder tree: the immediate child folders of `prefix` and the files stored directly under it. \
+Folders exist only as the `folder` recorded on files (set at upload or via `PATCH /files/{id}`).",
+    params(FolderQuery),
+    responses(
+        (status = 200, description = "Child folders and files under the prefix", body = FolderListingResponse),
+        (status = 400, description = "Invalid prefix or pagination parameters"),
+        (status = 403, description = "Access denied to this project"),
+        (status = 404, description = "Project not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "File Management"
+)]
+pub async fn list_folders(
+    Extension(user): Extension<AuthUser>,
+    State(db): State<sea_orm::DatabaseConnection>,
+    State(urls): State<UrlBuilder>,
+    Query(query): Query<FolderQuery>,
+) -> Result<Json<FolderListingResponse>, AppError> {
+    let (page, limit) = Pagination { page: query.page, limit: query.limit }.effective_in(PageGroup::Files)?;
+    let prefix = normalize_folder(query.prefix.as_deref().unwrap_or_default()).map_err(AppError::BadRequest)?;
+
+    let project = project::Entity::find_by_id(query.project_id)
+        .filter(project::Column::DeletedAt.is_null())
+        .one(&db)
+        .await?
+        .ok_or(AppError::NotFound("Project not found".into()))?;
+
+    let scope = Scope::for_user(&user);
+    if !scope.includes(&db, &project).await? {
+        return Err(AppError::Forbidden("Access denied to this project".into()));
+    }
+
+    // First segment after the prefix, grouped in SQL so only one row per child comes back
+    let child = format!("split_part(substr(files.folder, {}), '/', 1)", prefix.chars().count() + 1);
+    let in_project = FileFilters { project_id: Some(project.id), ..Default::default() };
+    let children: Vec<(String, i64)> = scope::files(scope, &in_project)
+        .filter(file::Column::Folder.like(LikeExpr::new(format!("{}%", escape_like(&prefix))).escape('\\')))
+        .filter(file::Column::Folder.ne(prefix.clone()))
+        .select_only()
+        .column_as(Expr::cust(child.clone()), "name")
+        .column_as(file::Column::Id.count(), "file_count")
+        .group_by(Expr::cust(child.clone()))
+        .order_by_asc(Expr::cust(child))
+        .into_tuple()
+        .all(&db)
+        .await?;
+
+    let folders = children
+        .into_iter()
+        .map(|(name, file_count)| FolderEntry {
+            path: format!("{}{}/", prefix, name),
+            name,
+            file_count,
+        })
+        .collect();
+
+    let paginator = scope::files(scope, &FileFilters { folder: Some(prefix.clone()), ..in_project })
+        .order_by_asc(file::Column::Filename)
+        .paginate(&db, limit);
+    let total_items = paginator.num_items().await?;
+    let items = paginator.fetch_page(page.saturating_sub(1)).await?;
+    let files = PaginatedResponse::new_in(
+        PageGroup::Files,
+        FileResponse::build_all(&db, &urls, items, query.no_srcset).await?,
+        total_items,
+        page,
+        limit,
+    );
+
+    Ok(Json(FolderListingResponse {
+        project_id: project.id,
+        prefix,
+        folders,
+        files,
+    }))
+}
+
+// PATCH /files/:id
+#[utoipa::path(
+    patch,
+    path = "/files/{id}",
+    description = "Move a file to another pseudo-folder. Only the `folder` metadata changes; the S3 object keeps its key.",
+    params(
+        ("id" = Uuid, Path, description = "File ID")
+    ),
+    request_body = UpdateFileRequest,
+    responses(
+        (status = 200, description = "File updated", body = FileResponse),
+        (status = 400, description = "Invalid folder or If-Match"),
+        (status = 403, description = "Access denied to this file"),
+        (status = 404, description = "File not found"),
+        (status = 412, description = "The file changed since `If-Match` / `expected_version`; the body carries `current_version`"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "File Management"
+)]
+pub async fn update_file(
+    Path(id): Path<Uuid>,
+    Extension(user): Extension<AuthUser>,
+    State(db): State<sea_orm::DatabaseConnection>,
+    State(urls): State<UrlBuilder>,
+    headers: HeaderMap,
+    Json(payload): Json<UpdateFileRequest>,
+) -> Result<Json<FileResponse>, AppError> {
+    let expected_version = expected_version(&headers, payload.expected_version)?;
+    let folder = normalize_folder(&payload.folder).map_err(|e| {
+        println!("Files | PATCH /files/{} | user={} | res=400 | {}", id, user.username, e);
+        AppError::BadRequest(e)
+    })?;
+
+    let file = file::Entity::find_active()
+        .filter(file::Column::Id.eq(id))
+        .one(&db)
+        .await?
+        .ok_or(AppError::NotFound("File not found".into()))?;
+
+    if !Scope::for_user(&user).includes_id(&db, file.project_id).await? {
+        return Err(AppError::Forbidden("Access denied to this file".into()));
+    }
+
+    // The version condition makes a concurrent edit show up as zero rows updated
+    let mut update = file::Entity::update_many()
+        .col_expr(file::Column::Folder, Expr::value(folder))
+        .col_expr(file::Column::UpdatedAt, Expr::value(chrono::Utc::now()))
+        .col_expr(file::Column::Version, Expr::col(file::Column::Version).add(1))
+        .filter(file::Column::Id.eq(id));
+    if let Some(expected) = expected_version {
+        update = update.filter(file::Column::Version.eq(expected));
+    }
+    let result = update.exec(&db).await?;
+
+    let file = file::Entity::find_by_id(id)
+        .one(&db)
+        .await?
+        .ok_or(AppError::NotFound("File not found".into()))?;
+    if result.rows_affected == 0 {
+        println!("Files | PATCH /files/{} | user={} | expected={:?} | current={} | res=412", id, user.username, expected_version, file.version);
+        return Err(AppError::PreconditionFailed {
+            message: "File was changed by someone else".to_string(),
+            current_version: file.version,
+        });
     }
 
-    Ok(Json(FileResponse::from(file)))
+    println!("Files | PATCH /files/{} | user={} | folder={} | res=200", id, user.username, file.folder);
+    Ok(Json(FileResponse::build(&db, &urls, file, false).await?))
 }
 
 #[derive(Deserialize, utoipa::IntoParams)]
 pub struct ContentQuery {
     pub variant: Option<String>,
+    /// Overrides the project's `variant_fallback` for this request
+    pub fallback: Option<VariantFallback>,
+    /// Serve the file as this type instead of its stored one; one of `DOWNLOAD_OVERRIDES`
+    pub content_type: Option<String>,
+}
+
+/// How often `?fallback=wait` re-reads the file row.
+const VARIANT_WAIT_POLL: Duration = Duration::from_millis(500);
+
+/// `Retry-After` sent when `?fallback=wait` gives up.
+const VARIANT_RETRY_AFTER_SECS: u64 = 5;
+
+/// Where a requested variant stands on a file row.
+enum VariantState {
+    /// Generated; holds the object key
+    Ready(String),
+    /// The last generation attempt failed with this error
+    Failed(String),
+    Missing,
+}
+
+fn variant_state(file: &file::Model, variant_name: &str, bucket: &str) -> Result<VariantState, AppError> {
+    let variants = file.variants_json.as_object().ok_or(AppError::InternalServerError("Invalid variants data".into()))?;
+
+    if let Some(variant_path) = variants.get(variant_name) {
+        let variant_value = variant_path.as_str().ok_or(AppError::NotFound("Invalid variant path".into()))?;
+        let key = variant_keys::resolve(file.id, variant_name, variant_value, bucket)
+            .ok_or(AppError::InternalServerError("Failed to parse variant URL".into()))?;
+        return Ok(VariantState::Ready(key));
+    }
+
+    match file.variant_errors.get(variant_name).and_then(|e| e.get("error")).and_then(|e| e.as_str()) {
+        Some(error) => Ok(VariantState::Failed(error.to_string())),
+        None => Ok(VariantState::Missing),
+    }
+}
+
+/// Whether variants of the file may still appear: it is being processed for the first
+/// time, or a later regeneration job for it is queued or running.
+async fn variants_in_progress(db: &sea_orm::DatabaseConnection, file: &file::Model) -> Result<bool, AppError> {
+    if file.status == "processing" {
+        return Ok(true);
+    }
+
+    let job_type = Expr::expr(Expr::cust(format!("COALESCE(payload->>'type', '{}')", JobPayload::LEGACY_TYPE)));
+    let open = job::Entity::find()
+        .filter(job::Column::FileId.eq(file.id))
+        .filter(job::Column::Status.is_in(["pending", "processing"]))
+        .filter(job_type.is_in([JobPayload::LEGACY_TYPE, "sync_file_variants"]))
+        .count(db)
+        .await?;
+    Ok(open > 0)
 }
 
 // GET /files/:id/content
@@ -217,12 +594,26 @@ pub struct ContentQuery {
     path = "/files/{id}/content",
     params(
         ("id" = Uuid, Path, description = "File ID"),
-        ("variant" = Option<String>, Query, description = "Image variant name (e.g. 'thumbnail')")
+        ("variant" = Option<String>, Query, description = "Image variant name (e.g. 'thumbnail')"),
+        ("fallback" = Option<VariantFallback>, Query, description = "What to do while the variant is not generated yet: \
+`404` (default), `original` (redirect to the original) or `wait` (hold up to `VARIANT_WAIT_TIMEOUT_SECS` while the file is still processing). \
+Defaults to the project's `variant_fallback` setting."),
+        ("content_type" = Option<String>, Query, description = "Serve the file as `application/octet-stream`, `text/plain`, `text/csv` \
+or `application/json` instead of its stored type")
     ),
     responses(
-        (status = 307, description = "Temporary redirect to S3 URL"),
-        (status = 404, description = "File not found"),
-        (status = 500, description = "Internal server error")
+        (status = 307, description = "Temporary redirect to S3 URL",
+            headers(
+                ("x-content-sha256" = String, description = "SHA-256 of the original, when the original is served and a checksum is recorded"),
+                ("x-variant-fallback" = String, description = "`original` when the original is served in place of a missing variant")
+            )),
+        (status = 400, description = "Invalid fallback or content_type"),
+        (status = 404, description = "File or variant not found"),
+        (status = 409, description = "The requested variant failed to generate (`code`: `variant_failed`)"),
+        (status = 410, description = "File was permanently deleted; the body carries `deleted_at` and `reason`"),
+        (status = 500, description = "Internal server error"),
+        (status = 503, description = "`fallback=wait` timed out while the variant was still being generated",
+            headers(("Retry-After" = u64, description = "Seconds to wait before asking again")))
     ),
     security(
         ("bearer_auth" = [])
@@ -234,202 +625,310 @@ pub async fn get_file_content(
     Query(query): Query<ContentQuery>,
     Extension(user): Extension<AuthUser>,
     State(db): State<sea_orm::DatabaseConnection>,
-) -> Result<Redirect, AppError> {
+) -> Result<Response, AppError> {
     // 1. Get File
-    let file = file::Entity::find_by_id(id)
+    let Some(mut file) = file::Entity::find_active()
+        .filter(file::Column::Id.eq(id))
         .one(&db)
         .await
         .map_err(|e| AppError::InternalServerError(e.to_string()))?
-        .ok_or(AppError::NotFound("File not found".into()))?;
+    else {
+        return Err(tombstones::missing_file(&db, id).await);
+    };
 
     // 3. Verify Access
-    if user.role != crate::entities::user::Role::Su {
-        let project = project::Entity::find_by_id(file.project_id)
-            .one(&db)
-            .await
-            .map_err(|e| AppError::InternalServerError(e.to_string()))?
-            .ok_or(AppError::NotFound("Project not found".into()))?;
-
-        if project.owner_id != user.id {
-            return Err(AppError::Forbidden("Access denied to this file".into()));
-        }
+    let project = project::Entity::find_by_id(file.project_id)
+        .one(&db)
+        .await
+        .map_err(|e| AppError::InternalServerError(e.to_string()))?
+        .ok_or(AppError::NotFound("Project not found".into()))?;
+
+    if !Scope::for_user(&user).includes(&db, &project).await? {
+        return Err(AppError::Forbidden("Access denied to this file".into()));
     }
 
+    let content_type_override = match query.content_type.as_deref() {
+        Some(requested) => Some(content_type::download_override(requested).ok_or_else(|| {
+            println!("Files | GET /files/{}/content | user={} | content_type={} | res=400 | Not allowed", id, user.username, requested);
+            AppError::BadRequest(format!(
+                "content_type must be one of: {}",
+                content_type::DOWNLOAD_OVERRIDES.join(", ")
+            ))
+        })?),
+        None => None,
+    };
+    let settings = serde_json::from_value::<ProjectSettings>(project.settings.clone()).unwrap_or_default();
+
     // 4. Resolve Key (Original vs Variant)
+    let s3_service = project_storage::for_project(&db, file.project_id).await?;
+    let mut content_hash = None;
+    let mut original = false;
+    let mut served_fallback = false;
     let key = if let Some(variant_name) = query.variant {
-        // Check if variant exists in JSON
-        let variants = file.variants_json.as_object().ok_or(AppError::InternalServerError("Invalid variants data".into()))?;
-        
-        // Variants map should be { "name": "url_or_path" } or similar structure? 
-        // Wait, in Upload Image phase we stored URLs in response, but what did we store in DB?
-        // Let's look at `worker.rs`.
-        // Worker calculates s3_key: `{project}-{id}/images/{variant}/{file_id}.{ext}`
-        // It doesn't seem to explicitly update the `variants_json` in DB with the new key/url?
-        // Let's re-read worker.rs logic.
-        
-        // Ah, in Phase 5 Upload API, we calculated *future* URLs.
-        // But the worker does NOT update the `variants_json` column in `files` table after processing?
-        // Let's assume for now we can dynamically reconstruct the path based on convention if needed, 
-        // OR we need to check if the DB actually has the variant data.
-        
-        // In `src/routes/upload.rs` (implied from docs), we calculated paths. 
-        // But standard implementation usually stores the resulting map in DB.
-        // Let's assume standard behavior: `variants_json` contains map of `variant_name` -> `s3_path` or `public_url`.
-        
-        if let Some(variant_path) = variants.get(&variant_name) {
-            // If it's a full URL, we might need to parse it to get the key?
-            // Or if we stored the relative S3 key?
-            // Let's assume we stored the full URL or S3 Key. 
-            // If it's a full URL, we can't easily presign it if it's pointing to a custom domain?
-            // Actually, for presigning, we need the Object Key.
-            
-            // Re-evaluating: In `worker.rs`:
-            // It updates status to "ready", but does NOT update `variants_json`!
-            // This is a missing link in previous phases or implies we must rely on convention.
-            // Convention from `worker.rs`: `{project_name}-{project_id}/images/{variant_name}/{file_id}.{ext}`
-            
-            // So we need to reconstruct the key.
-            // We need project name.
-
-                
-
-            // We need the extension. The original file has `mime_type`.
-            // The variant extension depends on the variant config (e.g. thumb -> webp).
-            // But we don't have the config here easily without querying project settings and re-parsing.
-            
-            // ALTERNATIVE: Use the `variants_json` if it WAS populated.
-            // If it wasn't populated, we have a problem: we don't know the extension of the variant (could be webp, avif, jpg).
-            
-            // Let's check `files` table schema in DB or migration.
-            // If `variants_json` is empty in DB, we can't trivially know which variants exist.
-            
-            // Assuming for now that `variants_json` IS populated by the upload handler with EXPECTED paths?
-            // `POST /upload/image` -> "Calculates future variant paths". 
-            // Did it save them to DB?
-            // If yes, `file.variants_json` has them.
-            // If they are full URLs, we must extract the Key.
-            // Format: `https://bucket.s3.region.amazonaws.com/KEY` or `endpoint/bucket/KEY`.
-            
-            let variant_value = variant_path.as_str().ok_or(AppError::NotFound("Invalid variant path".into()))?;
-            
-            // Extract Key from URL.
-            // Simple heuristic used in many systems: split by bucket name?
-            // Or just store keys in DB...
-            
-            // Since I cannot verify the DB content easily without running it, 
-            // I will implement a robust URL-to-Key extractor assuming standard format.
-            
-            let config = crate::config::get_config();
-            let bucket = &config.s3_bucket_name;
-            
-            // Try to find `/bucket_name/` in URL and take everything after.
-            if let Some(idx) = variant_value.find(&format!("/{}/", bucket)) {
-                 variant_value[idx + bucket.len() + 2..].to_string()
-            } else {
-                // S3 Vhost style: `bucket.s3.../KEY`
-                // Take path part.
-                let url = url::Url::parse(variant_value).map_err(|_| AppError::InternalServerError("Failed to parse variant URL".into()))?;
-                url.path().trim_start_matches('/').to_string()
+        let fallback = match query.fallback {
+            Some(fallback) => fallback,
+            None => settings.variant_fallback.unwrap_or_default(),
+        };
+
+        // A recorded failure may be about to be replaced by a queued regeneration, so `wait` holds for that too
+        let mut state = variant_state(&file, &variant_name, s3_service.bucket_name())?;
+        if fallback == VariantFallback::Wait && !matches!(state, VariantState::Ready(_)) && variants_in_progress(&db, &file).await? {
+            let timeout = Duration::from_secs(crate::config::get_config().variant_wait_timeout_secs);
+            let deadline = tokio::time::Instant::now() + timeout;
+            loop {
+                if tokio::time::Instant::now() + VARIANT_WAIT_POLL > deadline {
+                    println!("Files | GET /files/{}/content | user={} | variant={} | res=503 | Still processing after {}s", id, user.username, variant_name, timeout.as_secs());
+                    let mut response = AppError::ServiceUnavailable(format!(
+                        "Variant '{}' is still being generated",
+                        variant_name
+                    ))
+                    .into_response();
+                    response.headers_mut().insert(header::RETRY_AFTER, HeaderValue::from(VARIANT_RETRY_AFTER_SECS));
+                    return Ok(response);
+                }
+                tokio::time::sleep(VARIANT_WAIT_POLL).await;
+
+                file = file::Entity::find_active()
+                    .filter(file::Column::Id.eq(id))
+                    .one(&db)
+                    .await?
+                    .ok_or(AppError::NotFound("File not found".into()))?;
+                state = variant_state(&file, &variant_name, s3_service.bucket_name())?;
+                if matches!(state, VariantState::Ready(_)) || !variants_in_progress(&db, &file).await? {
+                    break;
+                }
+            }
+        }
+
+        match state {
+            VariantState::Ready(key) => key,
+            // Generation failed, so there is no object to presign. The stored error can quote
+            // an external command's stderr, so it stays in the log.
+            VariantState::Failed(error) => {
+                println!("Files | GET /files/{}/content | user={} | variant={} | res=409 | Generation failed: {}", id, user.username, variant_name, error);
+                return Err(AppError::ConflictWithCode(
+                    "variant_failed",
+                    format!("Variant '{}' failed to generate", variant_name),
+                ));
+            }
+            VariantState::Missing if fallback == VariantFallback::Original => {
+                served_fallback = true;
+                original = true;
+                content_hash = file.content_hash.clone();
+                file.s3_key.clone()
+            }
+            VariantState::Missing => {
+                return Err(AppError::NotFound(format!("Variant '{}' not found", variant_name)));
             }
-        } else {
-             return Err(AppError::NotFound(format!("Variant '{}' not found", variant_name)));
         }
     } else {
-        // Original File
-        file.s3_key
+        original = true;
+        content_hash = file.content_hash.clone();
+        file.s3_key.clone()
     };
 
-    // 5. Generate Presigned URL
-    let s3_service = S3Service::new().await;
-    let url = s3_service.get_presigned_url(&key, Duration::from_secs(3600)).await?;
+    // 5. Generate Presigned URL. The original is saved under its uploaded name and served as
+    // its stored type, which older objects may lack; HTML and SVG download unless the project
+    // allows them inline
+    let served_type = content_type_override.or_else(|| original.then(|| file.mime_type.clone()));
+    let disposition = original.then(|| {
+        let active = served_type.as_deref().is_some_and(content_type::is_active);
+        content_disposition(if active && !settings.inline_html { "attachment" } else { "inline" }, &file.filename)
+    });
+    let url = s3_service.get_presigned_url_as(&key, Duration::from_secs(3600), served_type, disposition).await?;
+
+
+    // 6. Redirect, advertising the original's checksum so clients can verify the download
+    let mut response = Redirect::temporary(&url).into_response();
+    if let Some(hash) = content_hash.and_then(|h| HeaderValue::from_str(&h).ok()) {
+        response.headers_mut().insert("x-content-sha256", hash);
+    }
+    if served_fallback {
+        response.headers_mut().insert("x-variant-fallback", HeaderValue::from_static("original"));
+    }
+    Ok(response)
+}
 
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct VerifyJobResponse {
+    #[schema(value_type = String)]
+    pub job_id: Uuid,
+}
 
-    // 6. Redirect
-    Ok(Redirect::temporary(&url))
+// GET /files/:id/verify
+#[utoipa::path(
+    get,
+    path = "/files/{id}/verify",
+    description = "Re-download the original server-side and compare its SHA-256 with the checksum recorded at upload. \
+A mismatch flips the file to `error` status. Variants with a recorded hash are checked too and reported under `variants`; \
+a bad variant leaves the status alone. Files larger than `VERIFY_INLINE_MAX_BYTES` are verified by a background job.",
+    params(
+        ("id" = Uuid, Path, description = "File ID")
+    ),
+    responses(
+        (status = 200, description = "Verification result", body = IntegrityReport),
+        (status = 202, description = "Verification queued as a job", body = VerifyJobResponse),
+        (status = 403, description = "Access denied"),
+        (status = 404, description = "File not found"),
+        (status = 409, description = "No checksum recorded for this file"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "File Management"
+)]
+pub async fn verify_file(
+    Path(id): Path<Uuid>,
+    Extension(user): Extension<AuthUser>,
+    State(db): State<sea_orm::DatabaseConnection>,
+) -> Result<Response, AppError> {
+    let file = file::Entity::find_active()
+        .filter(file::Column::Id.eq(id))
+        .one(&db)
+        .await?
+        .ok_or(AppError::NotFound("File not found".into()))?;
+
+    if !Scope::for_user(&user).includes_id(&db, file.project_id).await? {
+        return Err(AppError::Forbidden("Access denied to this file".into()));
+    }
+
+    if file.content_hash.is_none() {
+        println!("Files | GET /files/{}/verify | user={} | res=409 | No checksum recorded", id, user.username);
+        return Err(AppError::Conflict("No checksum recorded for this file".into()));
+    }
+
+    if file.size as u64 > crate::config::get_config().verify_inline_max_bytes {
+        let job = job::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            file_id: Set(Some(file.id)),
+            project_id: Set(Some(file.project_id)),
+            status: Set("pending".to_string()),
+            payload: Set(JobPayload::VerifyFile.to_value()),
+            created_at: Set(chrono::Utc::now()),
+            updated_at: Set(chrono::Utc::now()),
+        };
+        let job = job.insert(&db).await?;
+
+        println!("Files | GET /files/{}/verify | user={} | job={} | res=202", id, user.username, job.id);
+        return Ok((StatusCode::ACCEPTED, Json(VerifyJobResponse { job_id: job.id })).into_response());
+    }
+
+    let s3_service = project_storage::for_project(&db, file.project_id).await?;
+    let report = integrity::verify_file(&db, s3_service.as_ref(), &file).await?;
+
+    println!("Files | GET /files/{}/verify | user={} | result={} | res=200", id, user.username, report.result);
+    Ok(Json(report).into_response())
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct DeleteFileQuery {
+    /// Delete right away even when the project keeps a file trash
+    pub permanent: Option<bool>,
 }
 
 // DELETE /files/:id
 #[utoipa::path(
     delete,
     path = "/files/{id}",
+    description = "Delete a file, its variants and every file derived from it. When the project's `file_trash_days` (or `FILE_TRASH_DAYS`) is above 0, the files are moved to the trash instead and purged by the cleanup service once the window has passed; `permanent=true` skips the trash. With a bearer token the caller needs at least the User role and access to the file's project; with an API key the key needs the `delete` scope and the file must belong to the key's project.",
     params(
-        ("id" = Uuid, Path, description = "File ID")
+        ("id" = Uuid, Path, description = "File ID"),
+        DeleteFileQuery
     ),
     responses(
-        (status = 200, description = "File deleted successfully"),
-        (status = 404, description = "File not found"),
+        (status = 200, description = "File deleted, or moved to the trash (`trashed: true`)"),
+        (status = 401, description = "Missing or invalid credentials"),
+        (status = 403, description = "Role too low, no access to the project, or API key without the `delete` scope"),
+        (status = 404, description = "File not found (or not in the API key's project)"),
         (status = 500, description = "Internal server error")
     ),
     security(
-        ("bearer_auth" = [])
+        ("bearer_auth" = []),
+        ("api_key" = [])
     ),
     tag = "File Management"
 )]
 pub async fn delete_file(
     Path(id): Path<Uuid>,
-    Extension(user): Extension<AuthUser>,
+    Query(params): Query<DeleteFileQuery>,
+    user: Option<Extension<AuthUser>>,
+    key_project: Option<Extension<ProjectContext>>,
     State(db): State<sea_orm::DatabaseConnection>,
 ) -> Result<Json<serde_json::Value>, AppError> {
-    // 1. Get File
-    let file = file::Entity::find_by_id(id)
+    // 1. Get File; an API key only sees its own project's files
+    let mut query = file::Entity::find_active().filter(file::Column::Id.eq(id));
+    if let Some(Extension(ctx)) = &key_project {
+        if !ctx.api_key_scopes.contains(&ApiKeyScope::Delete) {
+            println!("Files | DELETE /files/{} | project={} | key={} | res=403 | Missing delete scope", id, ctx.name, ctx.api_key_name);
+            return Err(AppError::Forbidden("API key lacks the 'delete' scope".into()));
+        }
+        query = query.filter(file::Column::ProjectId.eq(ctx.id));
+    }
+    let file = query
         .one(&db)
         .await
         .map_err(|e| AppError::InternalServerError(e.to_string()))?
         .ok_or(AppError::NotFound("File not found".into()))?;
 
     // 2. Verify Access
-    if user.role != crate::entities::user::Role::Su {
-        let project = project::Entity::find_by_id(file.project_id)
-            .one(&db)
-            .await
-            .map_err(|e| AppError::InternalServerError(e.to_string()))?
-            .ok_or(AppError::NotFound("Project not found".into()))?;
-
-        if project.owner_id != user.id {
-            return Err(AppError::Forbidden("Access denied to this file".into()));
+    let project = project::Entity::find_by_id(file.project_id)
+        .one(&db)
+        .await
+        .map_err(|e| AppError::InternalServerError(e.to_string()))?
+        .ok_or(AppError::NotFound("Project not found".into()))?;
+
+    let caller = match (&key_project, &user) {
+        (Some(Extension(ctx)), _) => format!("project={} | key={}", ctx.name, ctx.api_key_name),
+        (None, Some(Extension(user))) => {
+            if !user.role.is_at_least(&Role::User) {
+                return Err(AppError::Forbidden("Insufficient role to delete files".into()));
+            }
+            if !Scope::for_user(user).includes(&db, &project).await? {
+                return Err(AppError::Forbidden("Access denied to this file".into()));
+            }
+            format!("user={}", user.username)
         }
-    }
+        (None, None) => return Err(AppError::Unauthorized("Missing credentials".into())),
+    };
 
-    // 3. Delete from S3 (Original + Variants)
-    let s3_service = S3Service::new().await;
+    let settings: ProjectSettings = serde_json::from_value(project.settings).unwrap_or_default();
+    let derived = derived_descendants(&db, file.id).await?;
+    let ids: Vec<Uuid> = derived.iter().chain(std::iter::once(&file)).map(|f| f.id).collect();
+    // Derived files trashed on their own earlier were already announced
+    let live: Vec<&file::Model> = derived.iter().chain(std::iter::once(&file)).filter(|f| f.deleted_at.is_none()).collect();
+
+    // 3. With a file trash, hide the files now and leave the objects to the cleanup service
+    let trash_days = settings.file_trash_days();
+    if trash_days > 0 && !params.permanent.unwrap_or(false) {
+        file::Entity::update_many()
+            .col_expr(file::Column::DeletedAt, Expr::value(chrono::Utc::now()))
+            .filter(file::Column::Id.is_in(ids))
+            .filter(file::Column::DeletedAt.is_null())
+            .exec(&db)
+            .await?;
+        for f in live {
+            file_events::emit(&db, &settings, FileEvent::Deleted, f);
+        }
 
-    // Delete Original
-    if let Err(e) = s3_service.delete_object(&file.s3_key).await {
-        eprintln!("Failed to delete original file from S3: {}", e);
-        // Continue to try deleting variants and DB record? 
-        // Or fail? Best effort is usually preferred for cleanup.
+        println!("Files | DELETE /files/{} | {} | derived={} | res=200 | Moved to trash for {} days", id, caller, derived.len(), trash_days);
+        return Ok(Json(serde_json::json!({
+            "message": "File moved to trash",
+            "id": id,
+            "derived_deleted": derived.len(),
+            "trashed": true,
+            "purge_after_days": trash_days
+        })));
     }
 
-    // Delete Variants
-    if let Some(variants) = file.variants_json.as_object() {
-        for (_variant_name, variant_path) in variants {
-            if let Some(variant_str) = variant_path.as_str() {
-                // Extract Key logic (similar to get_file_content but simplified or extract common logic)
-                // For now, let's copy the extraction logic or assume logic.
-                // Wait, if we stored full URLs, we need to extract key.
-                
-                let config = crate::config::get_config();
-                let bucket = &config.s3_bucket_name;
-                
-                let key_to_delete = if let Some(idx) = variant_str.find(&format!("/{}/", bucket)) {
-                     Some(variant_str[idx + bucket.len() + 2..].to_string())
-                } else if let Ok(url) = url::Url::parse(variant_str) {
-                     Some(url.path().trim_start_matches('/').to_string())
-                } else {
-                    None
-                };
-
-                if let Some(key) = key_to_delete {
-                    if let Err(e) = s3_service.delete_object(&key).await {
-                        eprintln!("Failed to delete variant from S3: {}", e);
-                    }
-                }
-            }
-        }
+    // 4. Delete from S3: this file and everything derived from it, whose rows the FK cascade removes
+    let s3_service = project_storage::for_project(&db, file.project_id).await?;
+    for f in derived.iter().chain(std::iter::once(&file)) {
+        delete_file_objects(s3_service.as_ref(), f).await;
     }
 
-    // 4. Delete from DB
-    // Use ActiveModel to delete
+    // 5. Delete from DB
+    tombstones::record(&db, Condition::all().add(file::Column::Id.is_in(ids)), DeletionReason::FileDeleted).await?;
     let res = file::Entity::delete_by_id(id)
         .exec(&db)
         .await
@@ -439,8 +938,48 @@ pub async fn delete_file(
          return Err(AppError::NotFound("File not found in DB".into()));
     }
 
+    for f in live {
+        file_events::emit(&db, &settings, FileEvent::Deleted, f);
+    }
+
+    println!("Files | DELETE /files/{} | {} | derived={} | res=200", id, caller, derived.len());
     Ok(Json(serde_json::json!({
         "message": "File deleted successfully",
-        "id": id
+        "id": id,
+        "derived_deleted": derived.len()
     })))
 }
+
+/// Every file generated from `root`, transitively (a poster rendered from a derived page, ...).
+async fn derived_descendants(db: &sea_orm::DatabaseConnection, root: Uuid) -> Result<Vec<file::Model>, AppError> {
+    let mut found = Vec::new();
+    let mut seen = HashSet::from([root]);
+    let mut frontier = vec![root];
+    while !frontier.is_empty() {
+        let children: Vec<file::Model> = file::Entity::find()
+            .filter(file::Column::DerivedFrom.is_in(frontier))
+            .all(db)
+            .await?
+            .into_iter()
+            .filter(|f| seen.insert(f.id))
+            .collect();
+        frontier = children.iter().map(|f| f.id).collect();
+        found.extend(children);
+    }
+    Ok(found)
+}
+
+/// Best-effort removal of a file's original and variant objects.
+async fn delete_file_objects(s3_service: &dyn Storage, file: &file::Model) {
+    // Delete Original
+    if let Err(e) = s3_service.delete_object(&file.s3_key).await {
+        eprintln!("Failed to delete original file from S3: {}", e);
+    }
+
+    // Delete Variants
+    for key in variant_keys::all(file.id, &file.variants_json, s3_service.bucket_name()) {
+        if let Err(e) = s3_service.delete_object(&key).await {
+            eprintln!("Failed to delete variant from S3: {}", e);
+        }
+    }
+}
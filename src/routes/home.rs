@@ -1,6 +1,14 @@
-use axum::response::Html;
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{Html, Json},
+};
+use sea_orm::DatabaseConnection;
 use serde::Serialize;
 
+use crate::build_info::{self, BuildInfo};
+use crate::services::s3::S3Service;
+
 #[derive(Serialize, utoipa::ToSchema)]
 pub struct RootResponse {
     pub message: String,
@@ -16,8 +24,11 @@ pub struct RootResponse {
     ),
     tag = "General"
 )]
-pub async fn root() -> Html<&'static str> {
-    Html(r#"
+pub async fn root() -> Html<String> {
+    Html(ROOT_PAGE.replace("{version}", &format!("v{} ({})", build_info::VERSION, build_info::COMMIT)))
+}
+
+const ROOT_PAGE: &str = r#"
         <!DOCTYPE html>
         <html lang="en">
         <head>
@@ -41,11 +52,17 @@ pub async fn root() -> Html<&'static str> {
                 p {
                     color: #666;
                 }
+                .version {
+                    font-family: monospace;
+                    font-size: 12px;
+                    color: #999;
+                }
             </style>
         </head>
         <body>
             <h1>Welcome to MediaBlobKit</h1>
             <p>Your solution for media blob management.</p>
+            <p class="version">{version}</p>
             <a href="/swagger-ui/" style="
                 margin-top: 20px;
                 padding: 10px 20px;
@@ -60,5 +77,58 @@ pub async fn root() -> Html<&'static str> {
             </a>
         </body>
         </html>
-    "#)
+    "#;
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct HealthResponse {
+    /// `ok` or `degraded`
+    pub status: String,
+    /// `ok` or `unavailable`
+    pub database: String,
+    /// `ok`, or `unavailable` while the S3 circuit breaker is open
+    pub storage: String,
+    /// Commit this instance was built from, as on `GET /version`
+    pub commit: &'static str,
+}
+
+#[utoipa::path(
+    get,
+    path = "/health",
+    description = "Liveness and dependency status. Storage is reported from the S3 circuit breaker, so this never waits on a hung S3 endpoint.",
+    responses(
+        (status = 200, description = "All dependencies reachable", body = HealthResponse),
+        (status = 503, description = "Database or storage unavailable", body = HealthResponse)
+    ),
+    tag = "General"
+)]
+pub async fn health(State(db): State<DatabaseConnection>) -> (StatusCode, Json<HealthResponse>) {
+    let database_ok = db.ping().await.is_ok();
+    let storage_ok = !S3Service::circuit_open();
+    let label = |ok: bool| if ok { "ok" } else { "unavailable" }.to_string();
+
+    let status = if database_ok && storage_ok { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    if status != StatusCode::OK {
+        println!("Health | GET /health | database={} | storage={} | res=503", label(database_ok), label(storage_ok));
+    }
+
+    (status, Json(HealthResponse {
+        status: if status == StatusCode::OK { "ok" } else { "degraded" }.to_string(),
+        database: label(database_ok),
+        storage: label(storage_ok),
+        commit: build_info::COMMIT,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/version",
+    description = "What this instance was built from: crate version, git commit, build time, Cargo features, and the storage \
+provider it uses. The same `<version>+<commit>` is sent on every response as `X-MBK-Version` unless `VERSION_HEADER_ENABLED=false`.",
+    responses(
+        (status = 200, description = "Build information", body = BuildInfo)
+    ),
+    tag = "General"
+)]
+pub async fn version() -> Json<BuildInfo> {
+    Json(build_info::info())
 }
@@ -11,13 +11,23 @@ use crate::entities::job::{self, Entity as Job};
 use crate::entities::file;
 use crate::error::AppError;
 use crate::middleware::api_key::ProjectContext;
-use crate::pagination::Pagination;
+use crate::pagination::{PageGroup, Pagination};
+use crate::services::scope::{self, JobFilters, Scope};
 
 #[derive(Deserialize)]
 pub struct JobFilter {
     pub status: Option<String>,
-    #[serde(flatten)]
-    pub pagination: Pagination,
+    /// Only honored by `/admin/jobs`; API-key callers are already scoped to their project
+    pub project_id: Option<uuid::Uuid>,
+    // Not a flattened `Pagination`: serde_urlencoded cannot parse numbers through `flatten`
+    pub page: Option<u64>,
+    pub limit: Option<u64>,
+}
+
+impl JobFilter {
+    fn pagination(&self) -> Pagination {
+        Pagination { page: self.page, limit: self.limit }
+    }
 }
 
 use utoipa::ToSchema;
@@ -25,11 +35,26 @@ use utoipa::ToSchema;
 #[derive(Serialize, ToSchema, Clone)]
 pub struct JobResponse {
     pub id: uuid::Uuid,
-    pub file_id: uuid::Uuid,
+    /// Null for project-level jobs (variant syncs, plans, backfills, reconciliations)
+    pub file_id: Option<uuid::Uuid>,
+    /// Set on `/admin/jobs`; null on `/jobs`, where the project is implied by the API key
+    pub project_id: Option<uuid::Uuid>,
+    pub filename: Option<String>,
     pub status: String,
     pub payload: serde_json::Value,
-    pub created_at: chrono::NaiveDateTime,
-    pub updated_at: chrono::NaiveDateTime,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl JobResponse {
+    /// Includes the project, and the filename when the job is about a file.
+    fn with_file(model: job::Model, file: Option<file::Model>) -> Self {
+        Self {
+            project_id: model.project_id,
+            filename: file.map(|f| f.filename),
+            ..Self::from(model)
+        }
+    }
 }
 
 impl From<job::Model> for JobResponse {
@@ -37,6 +62,8 @@ impl From<job::Model> for JobResponse {
         Self {
             id: model.id,
             file_id: model.file_id,
+            project_id: None,
+            filename: None,
             status: model.status,
             payload: model.payload,
             created_at: model.created_at,
@@ -52,11 +79,13 @@ impl From<job::Model> for JobResponse {
     params(
         ("status" = Option<String>, Query, description = "Filter by job status (pending, processing, completed, failed)"),
         ("page" = Option<u64>, Query, description = "Page number (default: 1)"),
-        ("limit" = Option<u64>, Query, description = "Items per page (default: 10)")
+        ("limit" = Option<u64>, Query, description = "Items per page (default and maximum from `PAGINATION_JOBS_*`, else `PAGINATION_*`)")
     ),
     responses(
         (status = 200, description = "List of jobs grouped by project", body = std::collections::HashMap<String, PaginatedProjectJobsResponse>),
+        (status = 400, description = "Invalid pagination parameters"),
         (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Project is deleted"),
         (status = 500, description = "Internal Server Error")
     ),
     security(
@@ -68,22 +97,15 @@ pub async fn list_jobs(
     axum::Extension(project): axum::Extension<ProjectContext>,
     Query(filter): Query<JobFilter>,
 ) -> Result<Json<std::collections::HashMap<String, PaginatedProjectJobsResponse>>, AppError> {
-    let page = filter.pagination.page.unwrap_or(1);
-    let limit = filter.pagination.limit.unwrap_or(10);
+    let (page, limit) = filter.pagination().effective_in(PageGroup::Jobs)?;
 
-    let mut query = Job::find()
-        .join(sea_orm::JoinType::InnerJoin, job::Relation::File.def())
-        .filter(file::Column::ProjectId.eq(project.id))
-        .order_by_desc(job::Column::CreatedAt);
-
-    if let Some(status) = filter.status {
-        query = query.filter(job::Column::Status.eq(status));
-    }
-
-    let paginator = query.paginate(&db, limit);
+    let filters = JobFilters { status: filter.status, ..Default::default() };
+    let paginator = scope::jobs(Scope::Project(project.id), &filters)
+        .order_by_desc(job::Column::CreatedAt)
+        .paginate(&db, limit);
     let total_items = paginator.num_items().await.map_err(AppError::DatabaseError)?;
     let total_pages = paginator.num_pages().await.map_err(AppError::DatabaseError)?;
-    let jobs = paginator.fetch_page(page - 1).await.map_err(AppError::DatabaseError)?;
+    let jobs = paginator.fetch_page(page.saturating_sub(1)).await.map_err(AppError::DatabaseError)?;
 
     let data: Vec<JobResponse> = jobs.into_iter().map(JobResponse::from).collect();
 
@@ -94,6 +116,7 @@ pub async fn list_jobs(
         total_pages,
         current_page: page,
         page_size: limit,
+        max_limit: crate::config::get_config().pagination.limits(PageGroup::Jobs).max_limit,
     };
 
     let mut result = std::collections::HashMap::new();
@@ -112,6 +135,8 @@ pub struct PaginatedProjectJobsResponse {
     pub total_pages: u64,
     pub current_page: u64,
     pub page_size: u64,
+    /// Largest `limit` this listing accepts
+    pub max_limit: u64,
 }
 
 
@@ -122,12 +147,16 @@ pub struct PaginatedProjectJobsResponse {
     tag = "Jobs",
     params(
         ("status" = Option<String>, Query, description = "Filter by job status (pending, processing, completed, failed)"),
+        ("project_id" = Option<uuid::Uuid>, Query, description = "Only return jobs of this project"),
         ("page" = Option<u64>, Query, description = "Page number (default: 1)"),
-        ("limit" = Option<u64>, Query, description = "Items per page (default: 10)")
+        ("limit" = Option<u64>, Query, description = "Items per page (default and maximum from `PAGINATION_JOBS_*`, else `PAGINATION_*`)")
     ),
     responses(
         (status = 200, description = "List of jobs grouped by project", body = std::collections::HashMap<String, PaginatedProjectJobsResponse>),
+        (status = 400, description = "Invalid pagination parameters"),
         (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Caller has the User role"),
+        (status = 404, description = "project_id is not a project visible to the caller"),
         (status = 500, description = "Internal Server Error")
     ),
     security(
@@ -140,38 +169,36 @@ pub async fn list_admin_jobs(
     Query(filter): Query<JobFilter>,
 ) -> Result<Json<std::collections::HashMap<String, PaginatedProjectJobsResponse>>, AppError> {
     use crate::entities::{project, user::Role};
-    use sea_orm::QuerySelect;
+
+    let (page, limit) = filter.pagination().effective_in(PageGroup::Jobs)?;
+    let max_limit = crate::config::get_config().pagination.limits(PageGroup::Jobs).max_limit;
 
     // 1. Fetch projects based on role
-    let projects = match user.role {
-        Role::Su => project::Entity::find().all(&db).await.map_err(AppError::DatabaseError)?,
-        Role::Admin => project::Entity::find()
-            .filter(project::Column::OwnerId.eq(user.id))
-            .all(&db)
-            .await
-            .map_err(AppError::DatabaseError)?,
-        Role::User => return Err(AppError::Unauthorized("Insufficient permissions".to_string())),
-    };
+    if user.role == Role::User {
+        return Err(AppError::Forbidden("Insufficient permissions".to_string()));
+    }
+    let scope = Scope::for_user(&user);
+    let mut project_query = scope::projects(scope);
+    if let Some(project_id) = filter.project_id {
+        project_query = project_query.filter(project::Column::Id.eq(project_id));
+    }
+    let projects = project_query.all(&db).await.map_err(AppError::DatabaseError)?;
 
+    if projects.is_empty() && filter.project_id.is_some() {
+        return Err(AppError::NotFound("Project not found".to_string()));
+    }
     if projects.is_empty() {
         return Ok(Json(std::collections::HashMap::new()));
     }
 
     // 2. Fetch jobs for these projects
-    let project_ids: Vec<uuid::Uuid> = projects.iter().map(|p| p.id).collect();
-    
-    let mut query = Job::find()
-        .join(sea_orm::JoinType::InnerJoin, job::Relation::File.def())
-        .join(sea_orm::JoinType::InnerJoin, file::Relation::Project.def())
-        .filter(file::Column::ProjectId.is_in(project_ids))
+    let filters = JobFilters { project_id: filter.project_id, status: filter.status, ..Default::default() };
+    let jobs = scope::jobs(scope, &filters)
         .order_by_desc(job::Column::CreatedAt)
-        .select_also(file::Entity);
-
-    if let Some(status) = &filter.status {
-        query = query.filter(job::Column::Status.eq(status));
-    }
-
-    let jobs = query.all(&db).await.map_err(AppError::DatabaseError)?;
+        .select_also(file::Entity)
+        .all(&db)
+        .await
+        .map_err(AppError::DatabaseError)?;
 
     // 3. Group and Paginate in memory
     let mut result: std::collections::HashMap<String, PaginatedProjectJobsResponse> = std::collections::HashMap::new();
@@ -179,21 +206,18 @@ pub async fn list_admin_jobs(
 
     // Group jobs by project_id
     for (job_model, file_opt) in jobs {
-        if let Some(file_model) = file_opt {
-            project_jobs.entry(file_model.project_id).or_default().push(JobResponse::from(job_model));
+        if let Some(project_id) = job_model.project_id {
+            project_jobs.entry(project_id).or_default().push(JobResponse::with_file(job_model, file_opt));
         }
     }
 
-    let page = filter.pagination.page.unwrap_or(1);
-    let limit = filter.pagination.limit.unwrap_or(10);
-
     for p in projects {
         let all_jobs = project_jobs.remove(&p.id).unwrap_or_default();
         let total_items = all_jobs.len() as u64;
         let total_pages = (total_items as f64 / limit as f64).ceil() as u64;
         
         // Slice for pagination
-        let start = ((page - 1) * limit) as usize;
+        let start = (page.saturating_sub(1) * limit) as usize;
         let end = std::cmp::min(start + limit as usize, all_jobs.len());
         
         let paginated_jobs = if start < all_jobs.len() {
@@ -209,6 +233,7 @@ pub async fn list_admin_jobs(
             total_pages,
             current_page: page,
             page_size: limit,
+            max_limit,
         });
     }
 
@@ -216,3 +241,260 @@ pub async fn list_admin_jobs(
 
     Ok(Json(result))
 }
+
+#[derive(Serialize, ToSchema)]
+pub struct JobEventResponse {
+    pub event: String,
+    pub data: serde_json::Value,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/jobs/{id}/events",
+    tag = "Jobs",
+    description = "Lifecycle events recorded by the worker for a job, oldest first. Events are only recorded while `JOB_EVENTS_ENABLED=true`, and writes are best-effort, so the list can be empty or incomplete.",
+    params(
+        ("id" = uuid::Uuid, Path, description = "Job ID")
+    ),
+    responses(
+        (status = 200, description = "Job events in order", body = Vec<JobEventResponse>),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Caller has the User role"),
+        (status = 404, description = "Job not found"),
+        (status = 500, description = "Internal Server Error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn list_job_events(
+    State(db): State<DatabaseConnection>,
+    axum::Extension(user): axum::Extension<crate::middleware::auth::AuthUser>,
+    axum::extract::Path(job_id): axum::extract::Path<uuid::Uuid>,
+) -> Result<Json<Vec<JobEventResponse>>, AppError> {
+    use crate::entities::{job_event, user::Role};
+
+    if user.role == Role::User {
+        return Err(AppError::Forbidden("Insufficient permissions".to_string()));
+    }
+
+    // Admins only see jobs in their own projects; hide others as not found
+    let visible = scope::jobs(Scope::for_user(&user), &JobFilters::default())
+        .filter(job::Column::Id.eq(job_id))
+        .count(&db)
+        .await
+        .map_err(AppError::DatabaseError)?
+        > 0;
+    if !visible {
+        return Err(AppError::NotFound("Job not found".to_string()));
+    }
+
+    let events = job_event::Entity::find()
+        .filter(job_event::Column::JobId.eq(job_id))
+        .order_by_asc(job_event::Column::CreatedAt)
+        .order_by_asc(job_event::Column::Id)
+        .all(&db)
+        .await
+        .map_err(AppError::DatabaseError)?;
+
+    println!("Jobs | GET /admin/jobs/{}/events | user={} | count={} | res=200", job_id, user.username, events.len());
+
+    Ok(Json(
+        events
+            .into_iter()
+            .map(|e| JobEventResponse { event: e.event, data: e.data, created_at: e.created_at })
+            .collect(),
+    ))
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct WorkerStatusResponse {
+    pub pools: Vec<crate::services::worker::PoolStats>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/worker",
+    tag = "Jobs",
+    description = "Utilization of this instance's worker pools (superuser only). Each job type runs in one pool; `in_use` counts jobs currently executing.",
+    responses(
+        (status = 200, description = "Worker pool utilization", body = WorkerStatusResponse),
+        (status = 403, description = "Superuser access required"),
+        (status = 404, description = "No worker is running in this process")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_worker_status(
+    axum::Extension(user): axum::Extension<crate::middleware::auth::AuthUser>,
+) -> Result<Json<WorkerStatusResponse>, AppError> {
+    let pools = crate::services::worker::pool_stats()
+        .ok_or_else(|| AppError::NotFound("No worker is running in this process".to_string()))?;
+
+    println!("Jobs | GET /admin/worker | user={} | pools={} | res=200", user.username, pools.len());
+    Ok(Json(WorkerStatusResponse { pools }))
+}
+
+/// Statuses reported by `/admin/queue`; `completed` jobs are history, not backlog.
+const QUEUE_STATUSES: [&str; 3] = ["pending", "processing", "failed"];
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct QueueQuery {
+    /// Projects listed in `top_projects` (default 10, max 100)
+    pub top: Option<u64>,
+}
+
+#[derive(Serialize, ToSchema, Default)]
+pub struct QueueCounts {
+    pub pending: i64,
+    pub processing: i64,
+    pub failed: i64,
+}
+
+impl QueueCounts {
+    fn add(&mut self, status: &str, count: i64) {
+        match status {
+            "pending" => self.pending += count,
+            "processing" => self.processing += count,
+            "failed" => self.failed += count,
+            _ => {}
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct QueueTypeCounts {
+    pub job_type: String,
+    #[serde(flatten)]
+    pub counts: QueueCounts,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct QueueProjectBacklog {
+    #[schema(value_type = String)]
+    pub project_id: uuid::Uuid,
+    pub project_name: String,
+    pub pending: i64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct QueueStatusResponse {
+    #[serde(flatten)]
+    pub counts: QueueCounts,
+    /// `created_at` of the oldest pending job
+    pub oldest_pending_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Seconds the oldest pending job has been waiting
+    pub oldest_pending_age_secs: Option<i64>,
+    /// Counts per job type, sorted by type
+    pub by_type: Vec<QueueTypeCounts>,
+    /// Projects with the most pending jobs, largest first
+    pub top_projects: Vec<QueueProjectBacklog>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/queue",
+    tag = "Jobs",
+    description = "Job backlog across all projects (superuser only), computed with grouped queries on the job status index.\n\n\
+**Suggested alerts:**\n\
+- `oldest_pending_age_secs` over 600 for 10 minutes: the workers are not keeping up (warn); over 3600: page.\n\
+- `pending` growing while `processing` stays at 0: no worker is claiming jobs.\n\
+- `failed` rising by more than a few percent of completed jobs per hour: look at `GET /admin/jobs?status=failed`.\n\
+- One project dominating `top_projects`: a bulk upload or sync that may need throttling.",
+    params(QueueQuery),
+    responses(
+        (status = 200, description = "Queue depth and age", body = QueueStatusResponse),
+        (status = 400, description = "Invalid top parameter"),
+        (status = 403, description = "Superuser access required")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_queue_status(
+    State(db): State<DatabaseConnection>,
+    axum::Extension(user): axum::Extension<crate::middleware::auth::AuthUser>,
+    Query(query): Query<QueueQuery>,
+) -> Result<Json<QueueStatusResponse>, AppError> {
+    use crate::entities::project;
+    use crate::models::job::JobPayload;
+    use sea_orm::sea_query::Expr;
+
+    let top = query.top.unwrap_or(10);
+    if !(1..=100).contains(&top) {
+        println!("Jobs | GET /admin/queue | user={} | res=400 | Invalid top", user.username);
+        return Err(AppError::BadRequest("top must be between 1 and 100".to_string()));
+    }
+
+    let job_type = Expr::cust(format!("COALESCE(payload->>'type', '{}')", JobPayload::LEGACY_TYPE));
+    let rows: Vec<(String, String, i64)> = Job::find()
+        .filter(job::Column::Status.is_in(QUEUE_STATUSES))
+        .select_only()
+        .column_as(job_type.clone(), "job_type")
+        .column(job::Column::Status)
+        .column_as(job::Column::Id.count(), "count")
+        .group_by(job_type.clone())
+        .group_by(job::Column::Status)
+        .order_by_asc(job_type)
+        .into_tuple()
+        .all(&db)
+        .await?;
+
+    let mut counts = QueueCounts::default();
+    let mut by_type: Vec<QueueTypeCounts> = Vec::new();
+    for (job_type, status, count) in rows {
+        counts.add(&status, count);
+        match by_type.last_mut() {
+            Some(last) if last.job_type == job_type => last.counts.add(&status, count),
+            _ => {
+                let mut type_counts = QueueCounts::default();
+                type_counts.add(&status, count);
+                by_type.push(QueueTypeCounts { job_type, counts: type_counts });
+            }
+        }
+    }
+
+    let oldest_pending_at: Option<chrono::DateTime<chrono::Utc>> = Job::find()
+        .filter(job::Column::Status.eq("pending"))
+        .select_only()
+        .column_as(job::Column::CreatedAt.min(), "oldest")
+        .into_tuple::<Option<chrono::DateTime<chrono::Utc>>>()
+        .one(&db)
+        .await?
+        .flatten();
+    let oldest_pending_age_secs =
+        oldest_pending_at.map(|at| (chrono::Utc::now() - at).num_seconds().max(0));
+
+    let top_projects: Vec<QueueProjectBacklog> = Job::find()
+        .filter(job::Column::Status.eq("pending"))
+        .join(sea_orm::JoinType::InnerJoin, job::Relation::Project.def())
+        .select_only()
+        .column(project::Column::Id)
+        .column(project::Column::Name)
+        .column_as(job::Column::Id.count(), "pending")
+        .group_by(project::Column::Id)
+        .group_by(project::Column::Name)
+        .order_by_desc(job::Column::Id.count())
+        .order_by_asc(project::Column::Name)
+        .limit(top)
+        .into_tuple::<(uuid::Uuid, String, i64)>()
+        .all(&db)
+        .await?
+        .into_iter()
+        .map(|(project_id, project_name, pending)| QueueProjectBacklog { project_id, project_name, pending })
+        .collect();
+
+    println!(
+        "Jobs | GET /admin/queue | user={} | pending={} | processing={} | failed={} | res=200",
+        user.username, counts.pending, counts.processing, counts.failed
+    );
+    Ok(Json(QueueStatusResponse {
+        counts,
+        oldest_pending_at,
+        oldest_pending_age_secs,
+        by_type,
+        top_projects,
+    }))
+}
@@ -2,37 +2,107 @@ mod home;
 mod auth;
 mod users;
 mod projects;
+mod project_storage;
+mod webhooks;
 mod api_keys;
 pub mod upload;
 mod jobs;
+mod backfill;
 mod files;
+mod storage;
+mod whoami;
+mod audit;
 
 use axum::{
+    http::{header, HeaderMap, HeaderName, StatusCode},
+    response::Json,
     routing::{get, post, delete},
     Router,
     middleware,
 };
+use axum::extract::FromRef;
 use sea_orm::DatabaseConnection;
+use crate::services::urls::UrlBuilder;
+use crate::services::request_log::RequestLogRecorder;
+use crate::services::key_failures::KeyFailureRecorder;
+use crate::services::login_attempts::LoginAttemptRecorder;
+use crate::middleware::api_key::ApiKeyAuthState;
 use crate::middleware::auth::auth_middleware;
-use crate::middleware::role::require_su;
+use crate::middleware::role::{require_role_at_least, require_su};
+use crate::entities::user::Role;
+use crate::middleware::docs_auth::docs_basic_auth;
+use crate::middleware::timeout::request_timeout;
+use std::time::Duration;
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 
+/// Response for resource-creating endpoints: 201 plus a `Location` header.
+pub type Created<T> = (StatusCode, [(HeaderName, String); 1], Json<T>);
+
+/// Builds a 201 response whose `Location` points at the resource's canonical GET path.
+pub fn created<T>(location: String, body: T) -> Created<T> {
+    (StatusCode::CREATED, [(header::LOCATION, location)], Json(body))
+}
+
+/// Version a client expects to overwrite, from `If-Match` (`"3"`, `W/"3"` or `3`) or the body's
+/// `expected_version`; `None` (no precondition) when neither is given or `If-Match: *`.
+pub fn expected_version(headers: &HeaderMap, body: Option<i32>) -> Result<Option<i32>, crate::error::AppError> {
+    let header = match headers.get(header::IF_MATCH) {
+        None => None,
+        Some(value) => {
+            let value = value.to_str().unwrap_or_default().trim();
+            if value == "*" {
+                None
+            } else {
+                let version = value.strip_prefix("W/").unwrap_or(value).trim_matches('"');
+                Some(version.parse::<i32>().map_err(|_| {
+                    crate::error::AppError::BadRequest("If-Match must be a version number, e.g. \"3\"".to_string())
+                })?)
+            }
+        }
+    };
+
+    match (header, body) {
+        (Some(a), Some(b)) if a != b => Err(crate::error::AppError::BadRequest(
+            "If-Match and expected_version disagree".to_string(),
+        )),
+        (header, body) => Ok(header.or(body)),
+    }
+}
+
 // Define the OpenAPI documentation
 #[derive(OpenApi)]
 #[openapi(
     paths(
         // General endpoints
         home::root,
+        home::health,
+        home::version,
         // Authentication endpoints
         auth::login,
         auth::refresh,
         auth::logout,
         auth::me,
+        auth::logout_all,
+        auth::list_sessions,
+        auth::revoke_session,
+        auth::change_password,
+        auth::enable_two_factor,
+        auth::verify_two_factor,
+        auth::forgot_password,
+        auth::reset_password,
+        auth::introspect,
+        auth::jwks,
+        auth::impersonate,
+        auth::issue_password_reset,
         // User management endpoints
         users::create_user,
         users::list_users,
+        users::get_user,
         users::delete_user,
+        users::require_password_change,
+        users::revoke_tokens,
+        users::update_user,
         // Project management endpoints
         projects::create_project,
         projects::list_projects,
@@ -40,27 +110,67 @@ use utoipa_swagger_ui::SwaggerUi;
         projects::update_project,
         projects::delete_project,
         projects::sync_variants,
+        projects::list_settings_history,
+        projects::list_request_logs,
+        projects::rollback_settings,
+        projects::suspend_project,
+        projects::unsuspend_project,
+        projects::list_project_members,
+        projects::add_project_member,
+        projects::remove_project_member,
         // API Key endpoints
         api_keys::create_api_key,
         api_keys::list_api_keys,
+        api_keys::get_api_key,
         api_keys::update_api_key,
         api_keys::delete_api_key,
+        api_keys::list_expiring_api_keys,
+        api_keys::get_key_cache_stats,
+        upload::get_upload_concurrency,
+        whoami::whoami,
         // Upload endpoints
         upload::upload_file,
         upload::upload_image,
+        upload::upload_images,
         // Jobs endpoints
         jobs::list_jobs,
         jobs::list_admin_jobs,
+        jobs::list_job_events,
+        jobs::get_worker_status,
+        jobs::get_queue_status,
+        backfill::create_backfill,
+        backfill::get_legacy_variants,
         // File endpoints
+        project_storage::get_project_storage,
+        project_storage::put_project_storage,
+        project_storage::verify_project_storage,
+        project_storage::delete_project_storage,
+        webhooks::get_project_webhook,
+        webhooks::put_project_webhook,
+        webhooks::delete_project_webhook,
         files::list_files,
         files::get_file,
+        files::list_folders,
+        files::update_file,
         files::get_file_content,
         files::delete_file,
+        files::verify_file,
+        // Storage endpoints
+        storage::verify_storage,
+        storage::storage_diagnostics,
+        storage::list_project_objects,
+        storage::reconcile_project_storage,
+        storage::list_storage_drift,
+        // Audit endpoints
+        audit::list_audit_logs,
+        audit::list_login_attempts,
     ),
     components(
         schemas(
             // Home schemas
             home::RootResponse,
+            home::HealthResponse,
+            crate::build_info::BuildInfo,
             // Auth schemas
             auth::LoginRequest,
             auth::LoginResponse,
@@ -68,30 +178,107 @@ use utoipa_swagger_ui::SwaggerUi;
             auth::RefreshResponse,
             auth::LogoutRequest,
             auth::LogoutResponse,
+            auth::LogoutAllResponse,
+            auth::SessionResponse,
+            auth::ChangePasswordRequest,
+            auth::ChangePasswordResponse,
+            auth::EnableTwoFactorRequest,
+            auth::EnableTwoFactorResponse,
+            auth::VerifyTwoFactorRequest,
+            auth::VerifyTwoFactorResponse,
+            auth::ForgotPasswordRequest,
+            auth::ForgotPasswordResponse,
+            auth::ResetPasswordRequest,
+            auth::PasswordResetTokenResponse,
+            auth::IntrospectRequest,
+            auth::IntrospectResponse,
             auth::ErrorResponse,
             auth::UserProfile,
+            auth::UserStats,
+            auth::ImpersonateResponse,
             // User schemas
             users::CreateUserRequest,
             users::UserResponse,
             users::UserRole,
+            users::RevokeTokensResponse,
+            users::UpdateUserRequest,
             crate::entities::user::Role,
             // Project schemas
             projects::CreateProjectRequest,
             projects::UpdateProjectRequest,
             projects::ProjectResponse,
+            projects::ProjectCounters,
+            projects::FileCounters,
+            projects::JobCounters,
+            projects::SettingsHistoryResponse,
+            projects::RequestLogResponse,
+            projects::SyncPlanJobResponse,
+            projects::ProjectMemberResponse,
+            crate::services::sync_plan::SyncPlan,
+            crate::services::sync_plan::FileSyncPlan,
             // API Key schemas
             api_keys::CreateApiKeyRequest,
             api_keys::UpdateApiKeyRequest,
             api_keys::ApiKeyResponse,
+            api_keys::ExpiringApiKeyResponse,
+            crate::entities::api_key::ApiKeyScope,
+            crate::services::key_cache::KeyCacheStats,
+            crate::middleware::upload_limit::UploadConcurrency,
+            crate::middleware::upload_limit::KeyConcurrency,
+            whoami::WhoamiResponse,
+            whoami::WhoamiApiKey,
+            whoami::WhoamiLimits,
+            whoami::WhoamiRetention,
             // Upload schemas
             upload::FileUploadResponse,
             upload::ImageUploadResponse,
+            upload::BatchImageUploadResponse,
+            upload::BatchImageUploadEntry,
+            upload::BatchUploadError,
             // Job schemas
             jobs::JobResponse,
+            jobs::WorkerStatusResponse,
+            jobs::QueueStatusResponse,
+            jobs::QueueCounts,
+            jobs::QueueTypeCounts,
+            jobs::QueueProjectBacklog,
+            jobs::JobEventResponse,
+            crate::services::worker::PoolStats,
+            backfill::BackfillRequest,
+            backfill::BackfillResponse,
+            backfill::LegacyVariantsResponse,
+            crate::models::job::BackfillAttribute,
+            crate::models::settings::VariantFallback,
+            crate::models::job::BackfillProgress,
             jobs::JobResponse,
         jobs::PaginatedProjectJobsResponse,
         // File schemas
+        project_storage::ProjectStorageRequest,
+        project_storage::ProjectStorageResponse,
+        project_storage::StorageVerifyResponse,
+        webhooks::ProjectWebhookRequest,
+        webhooks::ProjectWebhookResponse,
         files::FileResponse,
+        files::FolderEntry,
+        files::FolderListingResponse,
+        files::UpdateFileRequest,
+        files::VerifyJobResponse,
+        crate::services::integrity::IntegrityReport,
+        // Storage schemas
+        crate::services::s3::BucketReport,
+        crate::services::s3::BucketDiagnostics,
+        crate::services::s3::DiagnosticCheck,
+        storage::StorageObject,
+        storage::ObjectListResponse,
+        storage::StorageDiagnosticsResponse,
+        storage::ReconcileJobResponse,
+        storage::ProjectDriftEntry,
+        storage::StorageDriftResponse,
+        crate::services::reconcile::ReconcileReport,
+        crate::models::job::ReconcileProgress,
+        // Audit schemas
+        audit::AuditLogResponse,
+        audit::LoginAttemptResponse,
         )
     ),
     tags(
@@ -102,7 +289,8 @@ use utoipa_swagger_ui::SwaggerUi;
         (name = "Project API Keys", description = "API Key management endpoints"),
         (name = "File Upload", description = "File and Image upload endpoints"),
         (name = "File Management", description = "File retrieval and serving endpoints"),
-        (name = "Jobs", description = "Background job management endpoints")
+        (name = "Jobs", description = "Background job management endpoints"),
+        (name = "Storage", description = "Object storage maintenance endpoints (superuser access required)")
     ),
     info(
         title = "MediaBlobKit API",
@@ -138,59 +326,241 @@ impl utoipa::Modify for SecurityAddon {
     }
 }
 
+/// Router state. Handlers extract the parts they need (`State<DatabaseConnection>`,
+/// `State<UrlBuilder>`) through `FromRef`.
+#[derive(Clone)]
+pub struct AppState {
+    pub db: DatabaseConnection,
+    pub urls: UrlBuilder,
+    pub login_attempts: LoginAttemptRecorder,
+}
+
+impl FromRef<AppState> for DatabaseConnection {
+    fn from_ref(state: &AppState) -> Self {
+        state.db.clone()
+    }
+}
+
+impl FromRef<AppState> for UrlBuilder {
+    fn from_ref(state: &AppState) -> Self {
+        state.urls.clone()
+    }
+}
+
+impl FromRef<AppState> for LoginAttemptRecorder {
+    fn from_ref(state: &AppState) -> Self {
+        state.login_attempts.clone()
+    }
+}
+
+impl AppState {
+    /// State built from the global config (`FILE_URL_MODE`, ...). Spawns the login attempt
+    /// writer, so it has to be called inside the Tokio runtime.
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self {
+            login_attempts: LoginAttemptRecorder::new(db.clone()),
+            db,
+            urls: UrlBuilder::new(crate::config::get_config().file_url_mode.clone()),
+        }
+    }
+}
+
+/// Stops browsers from second-guessing the declared type of any API response, including
+/// the redirects to file content.
+async fn nosniff(mut response: axum::response::Response) -> axum::response::Response {
+    response.headers_mut().insert(
+        axum::http::header::X_CONTENT_TYPE_OPTIONS,
+        axum::http::HeaderValue::from_static("nosniff"),
+    );
+    response
+}
+
+/// `X-MBK-Version` on every response, so mixed versions show up during a rollout.
+async fn version_header(mut response: axum::response::Response) -> axum::response::Response {
+    if let Ok(value) = axum::http::HeaderValue::from_str(&crate::build_info::version_string()) {
+        response.headers_mut().insert(HeaderName::from_static("x-mbk-version"), value);
+    }
+    response
+}
+
+/// The full HTTP app for the server binary.
 pub fn create_routes(db: DatabaseConnection) -> Router {
-    // Swagger UI (stateless)  
-    let swagger_router: Router = SwaggerUi::new("/swagger-ui")
-        .url("/api-docs/openapi.json", ApiDoc::openapi())
-        .into();
+    create_app(AppState::new(db))
+}
+
+/// Builds every route, layer and the Swagger UI around `state`, so embedders can serve the
+/// app with their own listener (or drive it in-process with `tower::ServiceExt::oneshot`).
+pub fn create_app(state: AppState) -> Router {
+    let config = crate::config::get_config();
+    let db = state.db.clone();
+    // One flush task each, shared by every API-key route
+    let request_logs = RequestLogRecorder::new(db.clone());
+    let key_auth = ApiKeyAuthState { db: db.clone(), failures: KeyFailureRecorder::new(db.clone()) };
 
-    // Protected routes that require auth
+    // Protected read routes (plus managing your own sessions and password): any authenticated role, including Viewer
     let protected_routes = Router::new()
         .route("/auth/me", get(auth::me))
-        .route("/projects", post(projects::create_project))
+        .route("/auth/logout-all", post(auth::logout_all))
+        .route("/auth/sessions", get(auth::list_sessions))
+        .route("/auth/sessions/{id}", delete(auth::revoke_session))
+        .route("/auth/change-password", post(auth::change_password))
         .route("/projects", get(projects::list_projects))
         .route("/projects/{id}", get(projects::get_project))
+        .route("/projects/{id}/settings/history", get(projects::list_settings_history))
+        .route("/projects/{id}/request-logs", get(projects::list_request_logs))
+        .route("/projects/{id}/storage", get(project_storage::get_project_storage))
+        .route("/admin/jobs", get(jobs::list_admin_jobs))
+        .route("/admin/jobs/{id}/events", get(jobs::list_job_events))
+        .route("/files", get(files::list_files))
+        .route("/files/folders", get(files::list_folders))
+        .route("/files/{id}", get(files::get_file))
+        .route("/files/{id}/content", get(files::get_file_content))
+        .layer(middleware::from_fn_with_state(db.clone(), auth_middleware));
+
+    // Protected routes that change state (or touch keys): Viewer is refused with 403
+    let write_routes = Router::new()
+        .route("/projects", post(projects::create_project))
         .route("/projects/{id}", axum::routing::put(projects::update_project))
         .route("/projects/{id}", delete(projects::delete_project))
         .route("/projects/{id}/sync-variants", post(projects::sync_variants))
+        .route("/projects/{id}/settings/rollback/{history_id}", post(projects::rollback_settings))
+        .route("/projects/{id}/storage", axum::routing::put(project_storage::put_project_storage))
+        .route("/projects/{id}/storage", delete(project_storage::delete_project_storage))
+        .route("/projects/{id}/storage/verify", post(project_storage::verify_project_storage))
+        .route("/projects/{id}/webhook", get(webhooks::get_project_webhook))
+        .route("/projects/{id}/webhook", axum::routing::put(webhooks::put_project_webhook))
+        .route("/projects/{id}/webhook", delete(webhooks::delete_project_webhook))
         .route("/projects/{id}/keys", post(api_keys::create_api_key))
         .route("/projects/{id}/keys", get(api_keys::list_api_keys))
+        .route("/projects/{id}/keys/{key_id}", get(api_keys::get_api_key))
         .route("/projects/{id}/keys/{key_id}", axum::routing::patch(api_keys::update_api_key))
         .route("/projects/{id}/keys/{key_id}", delete(api_keys::delete_api_key))
-        .route("/admin/jobs", get(jobs::list_admin_jobs))
-        .route("/files", get(files::list_files))
-        .route("/files/{id}", get(files::get_file).delete(files::delete_file))
-        .route("/files/{id}/content", get(files::get_file_content))
-        .layer(middleware::from_fn(auth_middleware));
+        .route("/files/{id}", axum::routing::patch(files::update_file))
+        .route("/files/{id}/verify", get(files::verify_file))
+        .layer(middleware::from_fn(|req, next| require_role_at_least(Role::User, req, next)))
+        .layer(middleware::from_fn_with_state(db.clone(), auth_middleware));
 
     // Su-only routes
     let su_routes = Router::new()
         .route("/users", post(users::create_user))
         .route("/users", get(users::list_users))
+        .route("/users/{id}", get(users::get_user))
         .route("/users/{id}", delete(users::delete_user))
+        .route("/users/{id}", axum::routing::patch(users::update_user))
+        .route("/users/{id}/require-password-change", post(users::require_password_change))
+        .route("/users/{id}/revoke-tokens", post(users::revoke_tokens))
+        .route("/auth/2fa/enable", post(auth::enable_two_factor))
+        .route("/auth/2fa/verify", post(auth::verify_two_factor))
+        .route("/admin/impersonate/{user_id}", post(auth::impersonate))
+        .route("/admin/password-reset/{user_id}", post(auth::issue_password_reset))
+        .route("/admin/keys/expiring", get(api_keys::list_expiring_api_keys))
+        .route("/admin/keys/cache", get(api_keys::get_key_cache_stats))
+        .route("/admin/uploads", get(upload::get_upload_concurrency))
+        .route("/admin/worker", get(jobs::get_worker_status))
+        .route("/admin/queue", get(jobs::get_queue_status))
+        .route("/admin/backfill", post(backfill::create_backfill))
+        .route("/admin/variants/legacy", get(backfill::get_legacy_variants))
+        .route("/admin/storage/verify", post(storage::verify_storage))
+        .route("/admin/storage/diagnostics", get(storage::storage_diagnostics))
+        .route("/admin/projects/{id}/objects", get(storage::list_project_objects))
+        .route("/admin/projects/{id}/reconcile", post(storage::reconcile_project_storage))
+        .route("/admin/projects/{id}/suspend", post(projects::suspend_project))
+        .route("/admin/projects/{id}/unsuspend", post(projects::unsuspend_project))
+        .route("/admin/projects/{id}/members", get(projects::list_project_members))
+        .route("/admin/projects/{id}/members/{user_id}", axum::routing::put(projects::add_project_member))
+        .route("/admin/projects/{id}/members/{user_id}", delete(projects::remove_project_member))
+        .route("/admin/storage/drift", get(storage::list_storage_drift))
+        .route("/admin/audit", get(audit::list_audit_logs))
+        .route("/admin/login-attempts", get(audit::list_login_attempts))
         .layer(middleware::from_fn(require_su))
-        .layer(middleware::from_fn(auth_middleware));
+        .layer(middleware::from_fn_with_state(db.clone(), auth_middleware));
 
     // Public routes (no auth required) and merge all together
     let app_routes = Router::new()
         .route("/", get(home::root))
+        .route("/health", get(home::health))
+        .route("/version", get(home::version))
         .route("/favicon.ico", get(|| async { axum::http::StatusCode::NO_CONTENT }))
         .route("/auth/login", post(auth::login))
         .route("/auth/refresh", post(auth::refresh))
         .route("/auth/logout", post(auth::logout))
+        .route("/auth/introspect", post(auth::introspect))
+        .route("/.well-known/jwks.json", get(auth::jwks))
+        .route("/auth/forgot-password", post(auth::forgot_password))
+        .route("/auth/reset-password", post(auth::reset_password))
         .merge(protected_routes)
+        .merge(write_routes)
         .merge(su_routes)
+        // Served to bearer tokens and to API keys with the `delete` scope; the handler checks both
+        .merge(
+            Router::new()
+                .route("/files/{id}", delete(files::delete_file))
+                .route_layer(axum::middleware::from_fn_with_state(
+                    request_logs.clone(),
+                    crate::middleware::request_log::request_log,
+                ))
+                .route_layer(axum::middleware::from_fn_with_state(
+                    key_auth.clone(),
+                    crate::middleware::api_key::api_key_or_bearer_auth,
+                )),
+        )
+        // Auth and JSON endpoints; the upload routes below get their own, longer budget
+        .layer(middleware::from_fn_with_state(
+            Duration::from_secs(config.request_timeout_secs),
+            request_timeout,
+        ))
         .merge(
             Router::new()
                 .route("/upload/file", post(upload::upload_file))
                 .route("/upload/image", post(upload::upload_image))
+                .route("/upload/images", post(upload::upload_images))
+                // Room for a full batch of parts at their cap, plus the other fields; each part is
+                // checked against UPLOAD_MAX_PART_BYTES while it is read
+                .route_layer(axum::extract::DefaultBodyLimit::max(
+                    config.upload_max_part_bytes.saturating_mul(config.batch_upload_max_files.max(1)).saturating_add(1024 * 1024),
+                ))
+                // Upload routes only; inside api_key_auth so it can count per key
+                .route_layer(middleware::from_fn(crate::middleware::upload_limit::upload_limit))
                 .route("/jobs", get(jobs::list_jobs))
-                .layer(axum::middleware::from_fn_with_state(db.clone(), crate::middleware::api_key::api_key_auth))
+                .route("/whoami", get(whoami::whoami))
+                // Inner layer: sees the ProjectContext set by api_key_auth
+                .route_layer(axum::middleware::from_fn_with_state(
+                    request_logs.clone(),
+                    crate::middleware::request_log::request_log,
+                ))
+                .route_layer(axum::middleware::from_fn_with_state(
+                    key_auth.clone(),
+                    crate::middleware::api_key::api_key_auth,
+                ))
+                .route_layer(middleware::from_fn_with_state(
+                    Duration::from_secs(config.upload_timeout_secs),
+                    request_timeout,
+                ))
         )
-        .with_state(db);
+        .layer(middleware::map_response(nosniff))
+        .with_state(state);
     
-    // Merge Swagger UI (which has no state) with the rest
-    Router::new()
-        .merge(swagger_router)
-        .merge(app_routes)
+    let app = if !config.docs_enabled {
+        println!("Docs | Swagger UI and OpenAPI document disabled (DOCS_ENABLED=false)");
+        app_routes
+    } else {
+        // Swagger UI (stateless), optionally behind basic auth
+        let mut swagger_router: Router = SwaggerUi::new("/swagger-ui")
+            .url("/api-docs/openapi.json", ApiDoc::openapi())
+            .into();
+        if config.docs_basic_auth.is_some() {
+            swagger_router = swagger_router.layer(middleware::from_fn(docs_basic_auth));
+        }
+
+        // Merge Swagger UI (which has no state) with the rest
+        Router::new()
+            .merge(swagger_router)
+            .merge(app_routes)
+    };
+
+    if config.version_header {
+        app.layer(middleware::map_response(version_header))
+    } else {
+        app
+    }
 }
@@ -0,0 +1,368 @@
+use axum::{
+    extract::{Path, State},
+    response::Json,
+};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, IntoActiveModel, PaginatorTrait,
+    QueryFilter, Set,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::entities::project::{self, Entity as Project};
+use crate::entities::{file, project_storage_config};
+use crate::error::AppError;
+use crate::middleware::auth::AuthUser;
+use crate::services::project_storage;
+use crate::services::scope::{self, Scope};
+use crate::services::s3::{S3Service, StorageTarget};
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct ProjectStorageRequest {
+    pub bucket: String,
+    pub region: String,
+    /// S3-compatible endpoint; omit for AWS
+    pub endpoint: Option<String>,
+    pub access_key_id: String,
+    /// Stored encrypted and never returned
+    pub secret_access_key: String,
+    #[serde(default)]
+    pub force_path_style: bool,
+    /// Put objects with a public-read ACL
+    #[serde(default)]
+    pub public_objects: bool,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct ProjectStorageResponse {
+    pub project_id: Uuid,
+    pub bucket: String,
+    pub region: String,
+    pub endpoint: Option<String>,
+    pub access_key_id: String,
+    pub force_path_style: bool,
+    pub public_objects: bool,
+    /// Last successful test put/delete against the bucket
+    pub verified_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl From<project_storage_config::Model> for ProjectStorageResponse {
+    fn from(config: project_storage_config::Model) -> Self {
+        Self {
+            project_id: config.project_id,
+            bucket: config.bucket,
+            region: config.region,
+            endpoint: config.endpoint,
+            access_key_id: config.access_key_id,
+            force_path_style: config.force_path_style,
+            public_objects: config.public_objects,
+            verified_at: config.verified_at,
+            created_at: config.created_at,
+            updated_at: config.updated_at,
+        }
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct StorageVerifyResponse {
+    pub ok: bool,
+    /// Why the probe failed; S3's own reason is only logged
+    pub error: Option<String>,
+    pub verified_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl ProjectStorageRequest {
+    fn validate(&self) -> Result<(), String> {
+        for (field, value) in [
+            ("bucket", &self.bucket),
+            ("region", &self.region),
+            ("access_key_id", &self.access_key_id),
+            ("secret_access_key", &self.secret_access_key),
+        ] {
+            if value.trim().is_empty() {
+                return Err(format!("{} is required", field));
+            }
+        }
+        let bucket = self.bucket.trim();
+        let bucket_chars = bucket.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '.' || c == '-');
+        if !(3..=63).contains(&bucket.len()) || !bucket_chars {
+            return Err("bucket must be 3-63 lowercase letters, digits, dots or hyphens".to_string());
+        }
+        if !self.region.trim().chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-') {
+            return Err("region may only contain lowercase letters, digits and hyphens".to_string());
+        }
+        if let Some(endpoint) = &self.endpoint {
+            let valid = url::Url::parse(endpoint).is_ok_and(|u| matches!(u.scheme(), "http" | "https"));
+            if !valid {
+                return Err("endpoint must be an http(s) URL".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    fn target(&self) -> StorageTarget {
+        StorageTarget {
+            bucket: self.bucket.trim().to_string(),
+            region: self.region.trim().to_string(),
+            endpoint: self.endpoint.as_ref().map(|e| e.trim_end_matches('/').to_string()),
+            access_key_id: self.access_key_id.trim().to_string(),
+            secret_access_key: self.secret_access_key.clone(),
+            force_path_style: self.force_path_style,
+            public_objects: self.public_objects,
+        }
+    }
+}
+
+/// What a caller sees when the probe fails. S3's reason can carry whatever the endpoint
+/// answered, so it only goes to the log.
+const PROBE_FAILED: &str = "Storage check failed: could not write to the bucket with these settings";
+
+/// The caller's own live project; anything else is a 404.
+pub(super) async fn owned_project(db: &DatabaseConnection, user: &AuthUser, project_id: Uuid) -> Result<project::Model, AppError> {
+    Project::find_by_id(project_id)
+        .filter(project::Column::OwnerId.eq(user.id))
+        .filter(project::Column::DeletedAt.is_null())
+        .one(db)
+        .await?
+        .ok_or(AppError::NotFound("Project not found".to_string()))
+}
+
+/// Stored objects stay where they were written, so only an empty project may switch buckets.
+async fn ensure_no_files(db: &DatabaseConnection, project_id: Uuid) -> Result<(), AppError> {
+    let files = file::Entity::find()
+        .filter(file::Column::ProjectId.eq(project_id))
+        .count(db)
+        .await?;
+    if files > 0 {
+        return Err(AppError::Conflict(format!(
+            "Project has {} files; its bucket can only be changed while it is empty (credentials may still be rotated)",
+            files
+        )));
+    }
+    Ok(())
+}
+
+#[utoipa::path(
+    get,
+    path = "/projects/{id}/storage",
+    description = "The project's own bucket, if one is configured. The secret access key is never returned.",
+    params(
+        ("id" = Uuid, Path, description = "Project ID")
+    ),
+    responses(
+        (status = 200, description = "Storage config", body = ProjectStorageResponse),
+        (status = 404, description = "Project not found or it uses the default bucket"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Project Management"
+)]
+pub async fn get_project_storage(
+    State(db): State<DatabaseConnection>,
+    auth_user: axum::Extension<AuthUser>,
+    Path(project_id): Path<Uuid>,
+) -> Result<Json<ProjectStorageResponse>, AppError> {
+    let select = scope::projects(Scope::own(&auth_user))
+        .filter(project::Column::Id.eq(project_id))
+        .filter(project::Column::DeletedAt.is_null());
+    if select.one(&db).await?.is_none() {
+        return Err(AppError::NotFound("Project not found".to_string()));
+    }
+
+    let config = project_storage_config::Entity::find_by_id(project_id)
+        .one(&db)
+        .await?
+        .ok_or(AppError::NotFound("Project uses the default bucket".to_string()))?;
+
+    Ok(Json(ProjectStorageResponse::from(config)))
+}
+
+#[utoipa::path(
+    put,
+    path = "/projects/{id}/storage",
+    description = "Store the project's objects in its own bucket. The credentials are checked with a test put/delete before saving. \
+Changing the bucket or endpoint is only allowed while the project has no files; rotating credentials for the same bucket always is.",
+    params(
+        ("id" = Uuid, Path, description = "Project ID")
+    ),
+    request_body = ProjectStorageRequest,
+    responses(
+        (status = 200, description = "Storage config saved", body = ProjectStorageResponse),
+        (status = 400, description = "Invalid config, an endpoint on a private address, or the test put/delete failed"),
+        (status = 404, description = "Project not found"),
+        (status = 409, description = "Project already has files in another bucket"),
+        (status = 503, description = "STORAGE_CREDENTIALS_KEY is not configured")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Project Management"
+)]
+pub async fn put_project_storage(
+    State(db): State<DatabaseConnection>,
+    auth_user: axum::Extension<AuthUser>,
+    Path(project_id): Path<Uuid>,
+    Json(payload): Json<ProjectStorageRequest>,
+) -> Result<Json<ProjectStorageResponse>, AppError> {
+    let path = format!("/projects/{}/storage", project_id);
+    let project = owned_project(&db, &auth_user, project_id).await?;
+
+    payload.validate().map_err(|e| {
+        println!("Project | PUT {} | user={} | res=400 | {}", path, auth_user.username, e);
+        AppError::BadRequest(e)
+    })?;
+    let target = payload.target();
+    project_storage::check_target(&target).await.map_err(|e| {
+        println!("Project | PUT {} | user={} | res=400 | {}", path, auth_user.username, e);
+        AppError::BadRequest(e)
+    })?;
+    // Fails with 503 before any S3 traffic when there is no key to seal the secret with
+    let sealed = project_storage::seal_secret(project.id, &target.secret_access_key)?;
+
+    let existing = project_storage_config::Entity::find_by_id(project.id).one(&db).await?;
+    let same_bucket = existing
+        .as_ref()
+        .is_some_and(|c| c.bucket == target.bucket && c.endpoint == target.endpoint);
+    if !same_bucket {
+        ensure_no_files(&db, project.id).await.inspect_err(|e| {
+            println!("Project | PUT {} | user={} | res=409 | {}", path, auth_user.username, e);
+        })?;
+    }
+
+    S3Service::for_target(target.clone()).probe().await.map_err(|e| {
+        println!("Project | PUT {} | user={} | bucket={} | res=400 | {}", path, auth_user.username, target.bucket, e);
+        AppError::BadRequest(PROBE_FAILED.to_string())
+    })?;
+
+    let now = chrono::Utc::now();
+    let saved = match existing {
+        Some(config) => {
+            let mut active = config.into_active_model();
+            active.bucket = Set(target.bucket);
+            active.region = Set(target.region);
+            active.endpoint = Set(target.endpoint);
+            active.access_key_id = Set(target.access_key_id);
+            active.secret_access_key_sealed = Set(sealed);
+            active.force_path_style = Set(target.force_path_style);
+            active.public_objects = Set(target.public_objects);
+            active.verified_at = Set(Some(now));
+            active.updated_at = Set(now);
+            active.update(&db).await?
+        }
+        None => {
+            project_storage_config::ActiveModel {
+                project_id: Set(project.id),
+                bucket: Set(target.bucket),
+                region: Set(target.region),
+                endpoint: Set(target.endpoint),
+                access_key_id: Set(target.access_key_id),
+                secret_access_key_sealed: Set(sealed),
+                force_path_style: Set(target.force_path_style),
+                public_objects: Set(target.public_objects),
+                verified_at: Set(Some(now)),
+                created_at: Set(now),
+                updated_at: Set(now),
+            }
+            .insert(&db)
+            .await?
+        }
+    };
+    project_storage::invalidate(project.id);
+
+    println!("Project | PUT {} | user={} | bucket={} | res=200", path, auth_user.username, saved.bucket);
+    Ok(Json(ProjectStorageResponse::from(saved)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/projects/{id}/storage/verify",
+    description = "Re-run the test put/delete against the project's bucket with the stored credentials.",
+    params(
+        ("id" = Uuid, Path, description = "Project ID")
+    ),
+    responses(
+        (status = 200, description = "Probe result", body = StorageVerifyResponse),
+        (status = 404, description = "Project not found or it uses the default bucket"),
+        (status = 503, description = "STORAGE_CREDENTIALS_KEY is not configured")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Project Management"
+)]
+pub async fn verify_project_storage(
+    State(db): State<DatabaseConnection>,
+    auth_user: axum::Extension<AuthUser>,
+    Path(project_id): Path<Uuid>,
+) -> Result<Json<StorageVerifyResponse>, AppError> {
+    let project = owned_project(&db, &auth_user, project_id).await?;
+    let config = project_storage_config::Entity::find_by_id(project.id)
+        .one(&db)
+        .await?
+        .ok_or(AppError::NotFound("Project uses the default bucket".to_string()))?;
+
+    let target = project_storage::target_for(&config)?;
+    // The endpoint's DNS may have changed since it was saved
+    let probe = match project_storage::check_target(&target).await {
+        Ok(()) => S3Service::for_target(target).probe().await,
+        Err(e) => Err(e),
+    };
+    let response = match probe {
+        Ok(()) => {
+            let mut active = config.into_active_model();
+            active.verified_at = Set(Some(chrono::Utc::now()));
+            let config = active.update(&db).await?;
+            StorageVerifyResponse { ok: true, error: None, verified_at: config.verified_at }
+        }
+        Err(e) => {
+            println!("Project | POST /projects/{}/storage/verify | user={} | probe failed | {}", project_id, auth_user.username, e);
+            StorageVerifyResponse { ok: false, error: Some(PROBE_FAILED.to_string()), verified_at: config.verified_at }
+        }
+    };
+
+    println!("Project | POST /projects/{}/storage/verify | user={} | ok={} | res=200", project_id, auth_user.username, response.ok);
+    Ok(Json(response))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/projects/{id}/storage",
+    description = "Go back to the default bucket. Only allowed while the project has no files.",
+    params(
+        ("id" = Uuid, Path, description = "Project ID")
+    ),
+    responses(
+        (status = 200, description = "Storage config removed"),
+        (status = 404, description = "Project not found or it uses the default bucket"),
+        (status = 409, description = "Project still has files in its bucket")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Project Management"
+)]
+pub async fn delete_project_storage(
+    State(db): State<DatabaseConnection>,
+    auth_user: axum::Extension<AuthUser>,
+    Path(project_id): Path<Uuid>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let path = format!("/projects/{}/storage", project_id);
+    let project = owned_project(&db, &auth_user, project_id).await?;
+
+    ensure_no_files(&db, project.id).await.inspect_err(|e| {
+        println!("Project | DELETE {} | user={} | res=409 | {}", path, auth_user.username, e);
+    })?;
+
+    let result = project_storage_config::Entity::delete_by_id(project.id).exec(&db).await?;
+    if result.rows_affected == 0 {
+        return Err(AppError::NotFound("Project uses the default bucket".to_string()));
+    }
+    project_storage::invalidate(project.id);
+
+    println!("Project | DELETE {} | user={} | res=200", path, auth_user.username);
+    Ok(Json(serde_json::json!({ "message": "Project storage reset to the default bucket" })))
+}
@@ -1,23 +1,33 @@
 use axum::{
     extract::{Path, State},
-    http::StatusCode,
     response::Json,
 };
 use sea_orm::{
-    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, IntoActiveModel, QueryFilter,
-    QueryOrder, Set, PaginatorTrait,
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait, IntoActiveModel,
+    QueryFilter, QueryOrder, QuerySelect, Set, PaginatorTrait, TransactionTrait,
 };
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::HashMap;
 use uuid::Uuid;
 
 use crate::entities::project::{self, Entity as Project};
-use crate::entities::{file, job};
+use crate::entities::{file, job, project_member, project_settings_history, request_log, user::{self, Role}};
 use crate::error::AppError;
 use crate::middleware::auth::AuthUser;
-use crate::pagination::{Pagination, PaginatedResponse};
-use crate::services::s3::S3Service;
+use crate::middleware::timeout::RequestId;
+use crate::models::job::JobPayload;
+use crate::models::settings::ProjectSettings;
+use crate::pagination::{PageGroup, Pagination, PaginatedResponse};
+use crate::routes::{created, expected_version, Created};
+use crate::services::{audit, key_cache, project_storage, tombstones, variant_keys};
+use crate::services::scope::{self, Scope};
+use crate::services::tombstones::DeletionReason;
+use crate::services::sync_plan::{self, AutoSync, SyncPlan};
+use crate::config::get_config;
 use axum::extract::Query;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
 
 #[derive(Deserialize, utoipa::IntoParams)]
 pub struct DeleteProjectQuery {
@@ -38,6 +48,8 @@ pub struct UpdateProjectRequest {
     description: Option<String>,
     #[schema(value_type = Object)]
     settings: Option<Value>,
+    /// Fail with `412` unless the project is still at this version; same as `If-Match`
+    expected_version: Option<i32>,
 }
 
 #[derive(Serialize, utoipa::ToSchema)]
@@ -48,8 +60,23 @@ pub struct ProjectResponse {
     description: Option<String>,
     #[schema(value_type = Object)]
     settings: Value,
-    created_at: chrono::NaiveDateTime,
-    updated_at: chrono::NaiveDateTime,
+    created_at: chrono::DateTime<chrono::Utc>,
+    updated_at: chrono::DateTime<chrono::Utc>,
+    /// Send back in `If-Match` to update only if nobody changed the project meanwhile
+    version: i32,
+    /// API keys are refused and processing is paused until a superuser unsuspends the project
+    suspended: bool,
+    suspended_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Only present with `?include=counters`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    counters: Option<ProjectCounters>,
+    /// Jobs queued by this change, e.g. the `sync_project_variants` of `auto_sync_on_settings_change`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[schema(value_type = Vec<String>)]
+    triggered_jobs: Vec<Uuid>,
+    /// Why `auto_sync_on_settings_change` queued nothing although `variants` changed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    auto_sync_skipped: Option<String>,
 }
 
 impl From<project::Model> for ProjectResponse {
@@ -61,16 +88,131 @@ impl From<project::Model> for ProjectResponse {
             settings: project.settings,
             created_at: project.created_at,
             updated_at: project.updated_at,
+            version: project.version,
+            suspended: project.suspended_at.is_some(),
+            suspended_at: project.suspended_at,
+            counters: None,
+            triggered_jobs: Vec::new(),
+            auto_sync_skipped: None,
         }
     }
 }
 
+impl ProjectResponse {
+    fn with_auto_sync(mut self, auto_sync: &AutoSync) -> Self {
+        self.triggered_jobs.extend(auto_sync.job_id());
+        self.auto_sync_skipped = auto_sync.skipped();
+        self
+    }
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct ProjectIncludeQuery {
+    /// `counters` adds file and job counts per status
+    pub include: Option<String>,
+}
+
+impl ProjectIncludeQuery {
+    fn counters(&self) -> Result<bool, AppError> {
+        let mut counters = false;
+        for include in self.include.iter().flat_map(|i| i.split(',')).map(str::trim).filter(|i| !i.is_empty()) {
+            match include {
+                "counters" => counters = true,
+                other => return Err(AppError::BadRequest(format!("Unknown include '{}' (expected: counters)", other))),
+            }
+        }
+        Ok(counters)
+    }
+}
+
+/// A project's files and unfinished or failed jobs by status.
+#[derive(Serialize, utoipa::ToSchema, Default)]
+pub struct ProjectCounters {
+    pub files: FileCounters,
+    pub jobs: JobCounters,
+}
+
+#[derive(Serialize, utoipa::ToSchema, Default)]
+pub struct FileCounters {
+    pub ready: i64,
+    pub processing: i64,
+    pub error: i64,
+}
+
+#[derive(Serialize, utoipa::ToSchema, Default)]
+pub struct JobCounters {
+    pub pending: i64,
+    pub processing: i64,
+    pub failed: i64,
+}
+
+/// Fills in `counters` for a page of projects with two grouped queries, whatever the page size.
+async fn attach_counters(db: &DatabaseConnection, projects: &mut [ProjectResponse]) -> Result<(), AppError> {
+    let ids: Vec<Uuid> = projects.iter().map(|p| p.id).collect();
+    if ids.is_empty() {
+        return Ok(());
+    }
+    let mut counters: HashMap<Uuid, ProjectCounters> = HashMap::new();
+
+    let files: Vec<(Uuid, String, i64)> = file::Entity::find()
+        .select_only()
+        .column(file::Column::ProjectId)
+        .column(file::Column::Status)
+        .column_as(file::Column::Id.count(), "count")
+        .filter(file::Column::ProjectId.is_in(ids.clone()))
+        .filter(file::Column::DeletedAt.is_null())
+        .group_by(file::Column::ProjectId)
+        .group_by(file::Column::Status)
+        .into_tuple()
+        .all(db)
+        .await?;
+    for (project_id, status, count) in files {
+        let files = &mut counters.entry(project_id).or_default().files;
+        match status.as_str() {
+            "ready" => files.ready += count,
+            "processing" => files.processing += count,
+            "error" => files.error += count,
+            _ => {}
+        }
+    }
+
+    let jobs: Vec<(Uuid, String, i64)> = job::Entity::find()
+        .select_only()
+        .column(job::Column::ProjectId)
+        .column(job::Column::Status)
+        .column_as(job::Column::Id.count(), "count")
+        .filter(job::Column::ProjectId.is_in(ids))
+        .filter(job::Column::Status.is_in(["pending", "processing", "failed"]))
+        .group_by(job::Column::ProjectId)
+        .group_by(job::Column::Status)
+        .into_tuple()
+        .all(db)
+        .await?;
+    for (project_id, status, count) in jobs {
+        let jobs = &mut counters.entry(project_id).or_default().jobs;
+        match status.as_str() {
+            "pending" => jobs.pending += count,
+            "processing" => jobs.processing += count,
+            "failed" => jobs.failed += count,
+            _ => {}
+        }
+    }
+
+    for project in projects {
+        project.counters = Some(counters.remove(&project.id).unwrap_or_default());
+    }
+    Ok(())
+}
+
 #[utoipa::path(
     post,
     path = "/projects",
     request_body = CreateProjectRequest,
     responses(
-        (status = 201, description = "Project created successfully", body = ProjectResponse),
+        (status = 201, description = "Project created successfully", body = ProjectResponse,
+            headers(("Location" = String, description = "Path of the created project"))),
+        (status = 400, description = "Invalid project settings"),
+        (status = 422, description = "Variant names collide or are reserved"),
         (status = 500, description = "Internal server error")
     ),
     security(
@@ -82,24 +224,30 @@ pub async fn create_project(
     State(db): State<DatabaseConnection>,
     auth_user: axum::Extension<AuthUser>,
     Json(payload): Json<CreateProjectRequest>,
-) -> Result<(StatusCode, Json<ProjectResponse>), AppError> {
-
+) -> Result<Created<ProjectResponse>, AppError> {
+    let settings = ProjectSettings::normalize_value(payload.settings.unwrap_or(serde_json::json!({})))
+        .map_err(|e| {
+            println!("Project | POST /projects | user={} | res={} | {}", auth_user.username, e.status(), e);
+            AppError::from(e)
+        })?;
 
+    let project_id = Uuid::new_v4();
     let project = project::ActiveModel {
-        id: Set(Uuid::new_v4()),
+        id: Set(project_id),
+        storage_prefix: Set(project_id.to_string()),
         owner_id: Set(auth_user.id),
         name: Set(payload.name),
         description: Set(payload.description),
-        settings: Set(payload.settings.unwrap_or(serde_json::json!({}))),
-        created_at: Set(chrono::Utc::now().naive_utc()),
-        updated_at: Set(chrono::Utc::now().naive_utc()),
+        settings: Set(settings),
+        created_at: Set(chrono::Utc::now()),
+        updated_at: Set(chrono::Utc::now()),
         ..Default::default()
     };
 
     let created_project = project.insert(&db).await?;
 
     println!("Project | POST /projects | user={} | name={} | res=201", auth_user.username, created_project.name);
-    Ok((StatusCode::CREATED, Json(ProjectResponse::from(created_project))))
+    Ok(created(format!("/projects/{}", created_project.id), ProjectResponse::from(created_project)))
 }
 
 // GET /projects
@@ -108,10 +256,12 @@ pub async fn create_project(
     path = "/projects",
     params(
         ("page" = Option<u64>, Query, description = "Page number"),
-        ("limit" = Option<u64>, Query, description = "Items per page")
+        ("limit" = Option<u64>, Query, description = "Items per page"),
+        ProjectIncludeQuery
     ),
     responses(
         (status = 200, description = "List of user's projects", body = PaginatedResponse<ProjectResponse>),
+        (status = 400, description = "Invalid pagination parameters or unknown include"),
         (status = 500, description = "Internal server error")
     ),
     security(
@@ -123,34 +273,40 @@ pub async fn list_projects(
     State(db): State<DatabaseConnection>,
     auth_user: axum::Extension<AuthUser>,
     Query(pagination): Query<Pagination>,
+    Query(include): Query<ProjectIncludeQuery>,
 ) -> Result<Json<PaginatedResponse<ProjectResponse>>, AppError> {
 
-    let page = pagination.page.unwrap_or(1);
-    let limit = pagination.limit.unwrap_or(10);
+    let (page, limit) = pagination.effective_in(PageGroup::Projects)?;
+    let include_counters = include.counters()?;
 
-    let paginator = Project::find()
-        .filter(project::Column::OwnerId.eq(auth_user.id))
-        .filter(project::Column::DeletedAt.is_null())
+    let select = scope::projects(Scope::own(&auth_user)).filter(project::Column::DeletedAt.is_null());
+
+    let paginator = select
         .order_by_desc(project::Column::CreatedAt)
         .paginate(&db, limit);
 
     let total_items = paginator.num_items().await.map_err(AppError::DatabaseError)?;
-    let projects = paginator.fetch_page(page - 1).await.map_err(AppError::DatabaseError)?;
+    let projects = paginator.fetch_page(page.saturating_sub(1)).await.map_err(AppError::DatabaseError)?;
 
-    let responses: Vec<ProjectResponse> = projects.into_iter().map(ProjectResponse::from).collect();
+    let mut responses: Vec<ProjectResponse> = projects.into_iter().map(ProjectResponse::from).collect();
+    if include_counters {
+        attach_counters(&db, &mut responses).await?;
+    }
     
     println!("Project | GET /projects | user={} | count={} | res=200", auth_user.username, total_items);
-    Ok(Json(PaginatedResponse::new(responses, total_items, page, limit)))
+    Ok(Json(PaginatedResponse::new_in(PageGroup::Projects, responses, total_items, page, limit)))
 }
 
 #[utoipa::path(
     get,
     path = "/projects/{id}",
     params(
-        ("id" = Uuid, Path, description = "Project ID")
+        ("id" = Uuid, Path, description = "Project ID"),
+        ProjectIncludeQuery
     ),
     responses(
         (status = 200, description = "Project details", body = ProjectResponse),
+        (status = 400, description = "Unknown include"),
         (status = 404, description = "Project not found"),
         (status = 500, description = "Internal server error")
     ),
@@ -163,17 +319,23 @@ pub async fn get_project(
     State(db): State<DatabaseConnection>,
     auth_user: axum::Extension<AuthUser>,
     Path(project_id): Path<Uuid>,
+    Query(include): Query<ProjectIncludeQuery>,
 ) -> Result<Json<ProjectResponse>, AppError> {
-    let project = Project::find_by_id(project_id)
-        .filter(project::Column::OwnerId.eq(auth_user.id))
-        .filter(project::Column::DeletedAt.is_null())
-        .one(&db)
-        .await?;
+    let include_counters = include.counters()?;
+    let select = scope::projects(Scope::own(&auth_user))
+        .filter(project::Column::Id.eq(project_id))
+        .filter(project::Column::DeletedAt.is_null());
+    let project = select.one(&db).await?;
 
     match project {
         Some(p) => {
+            let mut response = [ProjectResponse::from(p)];
+            if include_counters {
+                attach_counters(&db, &mut response).await?;
+            }
+            let [response] = response;
             println!("Project | GET /projects/{} | user={} | res=200", project_id, auth_user.username);
-            Ok(Json(ProjectResponse::from(p)))
+            Ok(Json(response))
         }
         None => {
             println!("Project | GET /projects/{} | user={} | res=404 | Project not found", project_id, auth_user.username);
@@ -191,7 +353,10 @@ pub async fn get_project(
     request_body = UpdateProjectRequest,
     responses(
         (status = 200, description = "Project updated successfully", body = ProjectResponse),
+        (status = 400, description = "Invalid project settings or If-Match"),
+        (status = 422, description = "Variant names collide or are reserved"),
         (status = 404, description = "Project not found"),
+        (status = 412, description = "The project changed since `If-Match` / `expected_version`; the body carries `current_version`"),
         (status = 500, description = "Internal server error")
     ),
     security(
@@ -203,16 +368,33 @@ pub async fn update_project(
     State(db): State<DatabaseConnection>,
     auth_user: axum::Extension<AuthUser>,
     Path(project_id): Path<Uuid>,
+    headers: HeaderMap,
     Json(payload): Json<UpdateProjectRequest>,
 ) -> Result<Json<ProjectResponse>, AppError> {
+    let expected_version = expected_version(&headers, payload.expected_version)?;
+    let settings = payload
+        .settings
+        .map(ProjectSettings::normalize_value)
+        .transpose()
+        .map_err(|e| {
+            println!("Project | PUT /projects/{} | user={} | res={} | {}", project_id, auth_user.username, e.status(), e);
+            AppError::from(e)
+        })?;
+
+    // Row lock so concurrent edits record the settings they actually replaced
+    let txn = db.begin().await?;
     let project = Project::find_by_id(project_id)
         .filter(project::Column::OwnerId.eq(auth_user.id))
         .filter(project::Column::DeletedAt.is_null())
-        .one(&db)
+        .lock_exclusive()
+        .one(&txn)
         .await?;
 
     match project {
         Some(p) => {
+            check_version(&p, expected_version, &format!("PUT /projects/{}", project_id), &auth_user.username)?;
+            let old_settings = p.settings.clone();
+            let version = p.version;
             let mut active_project = p.into_active_model();
             
             if let Some(name) = payload.name {
@@ -221,15 +403,26 @@ pub async fn update_project(
             if let Some(description) = payload.description {
                 active_project.description = Set(Some(description));
             }
-            if let Some(settings) = payload.settings {
+            let settings_changed = settings.as_ref().is_some_and(|s| *s != old_settings);
+            if let Some(settings) = settings {
                 active_project.settings = Set(settings);
             }
             
-            active_project.updated_at = Set(chrono::Utc::now().naive_utc());
-            let updated_project = active_project.update(&db).await?;
+            active_project.updated_at = Set(chrono::Utc::now());
+            active_project.version = Set(version + 1);
+            let updated_project = active_project.update(&txn).await?;
+
+            let mut auto_sync = AutoSync::NotNeeded;
+            if settings_changed {
+                auto_sync = sync_plan::auto_sync(&txn, project_id, &old_settings, &updated_project.settings).await?;
+                record_settings_change(&txn, project_id, old_settings, updated_project.settings.clone(), auth_user.id, None).await?;
+            }
+            txn.commit().await?;
+            key_cache::invalidate_project(project_id);
 
+            log_auto_sync(&format!("PUT /projects/{}", project_id), &auth_user.username, &auto_sync);
             println!("Project | PUT /projects/{} | user={} | res=200", project_id, auth_user.username);
-            Ok(Json(ProjectResponse::from(updated_project)))
+            Ok(Json(ProjectResponse::from(updated_project).with_auto_sync(&auto_sync)))
         }
         None => {
             println!("Project | PUT /projects/{} | user={} | res=404 | Project not found", project_id, auth_user.username);
@@ -238,6 +431,319 @@ pub async fn update_project(
     }
 }
 
+fn log_auto_sync(route: &str, username: &str, auto_sync: &AutoSync) {
+    match auto_sync {
+        AutoSync::NotNeeded => {}
+        AutoSync::Queued(job_id) => println!("Project | {} | user={} | auto_sync | job={}", route, username, job_id),
+        AutoSync::NoImages | AutoSync::TooLarge(_) => println!(
+            "Project | {} | user={} | auto_sync skipped | {}",
+            route,
+            username,
+            auto_sync.skipped().unwrap_or_default()
+        ),
+    }
+}
+
+/// `412` when the caller expected another version. Callers hold the row lock, so the
+/// version cannot move between this check and their update.
+fn check_version(project: &project::Model, expected: Option<i32>, route: &str, username: &str) -> Result<(), AppError> {
+    match expected {
+        Some(expected) if expected != project.version => {
+            println!("Project | {} | user={} | expected={} | current={} | res=412", route, username, expected, project.version);
+            Err(AppError::PreconditionFailed {
+                message: "Project was changed by someone else".to_string(),
+                current_version: project.version,
+            })
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Appends a snapshot pair to `project_settings_history`; call inside the transaction
+/// that changes the settings.
+async fn record_settings_change<C: ConnectionTrait>(
+    conn: &C,
+    project_id: Uuid,
+    old_settings: Value,
+    new_settings: Value,
+    changed_by: Uuid,
+    rollback_of: Option<Uuid>,
+) -> Result<(), AppError> {
+    project_settings_history::ActiveModel {
+        id: Set(Uuid::new_v4()),
+        project_id: Set(project_id),
+        old_settings: Set(old_settings),
+        new_settings: Set(new_settings),
+        changed_by: Set(Some(changed_by)),
+        rollback_of: Set(rollback_of),
+        created_at: Set(chrono::Utc::now()),
+    }
+    .insert(conn)
+    .await?;
+    Ok(())
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct SettingsHistoryResponse {
+    #[schema(value_type = String)]
+    id: Uuid,
+    #[schema(value_type = Object)]
+    old_settings: Value,
+    #[schema(value_type = Object)]
+    new_settings: Value,
+    #[schema(value_type = Option<String>)]
+    changed_by: Option<Uuid>,
+    /// History entry undone by this change, when it was made by a rollback
+    #[schema(value_type = Option<String>)]
+    rollback_of: Option<Uuid>,
+    created_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl From<project_settings_history::Model> for SettingsHistoryResponse {
+    fn from(entry: project_settings_history::Model) -> Self {
+        SettingsHistoryResponse {
+            id: entry.id,
+            old_settings: entry.old_settings,
+            new_settings: entry.new_settings,
+            changed_by: entry.changed_by,
+            rollback_of: entry.rollback_of,
+            created_at: entry.created_at,
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/projects/{id}/settings/history",
+    description = "Every settings change for the project, newest first, with full snapshots before and after. Diff them client-side.",
+    params(
+        ("id" = Uuid, Path, description = "Project ID"),
+        ("page" = Option<u64>, Query, description = "Page number"),
+        ("limit" = Option<u64>, Query, description = "Items per page")
+    ),
+    responses(
+        (status = 200, description = "Settings history", body = PaginatedResponse<SettingsHistoryResponse>),
+        (status = 400, description = "Invalid pagination parameters"),
+        (status = 404, description = "Project not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Project Management"
+)]
+pub async fn list_settings_history(
+    State(db): State<DatabaseConnection>,
+    auth_user: axum::Extension<AuthUser>,
+    Path(project_id): Path<Uuid>,
+    Query(pagination): Query<Pagination>,
+) -> Result<Json<PaginatedResponse<SettingsHistoryResponse>>, AppError> {
+    let (page, limit) = pagination.effective_in(PageGroup::Projects)?;
+
+    let select = scope::projects(Scope::own(&auth_user))
+        .filter(project::Column::Id.eq(project_id))
+        .filter(project::Column::DeletedAt.is_null());
+    if select.one(&db).await?.is_none() {
+        println!("Project | GET /projects/{}/settings/history | user={} | res=404 | Project not found", project_id, auth_user.username);
+        return Err(AppError::NotFound("Project not found".to_string()));
+    }
+
+    let paginator = project_settings_history::Entity::find()
+        .filter(project_settings_history::Column::ProjectId.eq(project_id))
+        .order_by_desc(project_settings_history::Column::CreatedAt)
+        .paginate(&db, limit);
+
+    let total_items = paginator.num_items().await?;
+    let entries = paginator.fetch_page(page.saturating_sub(1)).await?;
+    let responses: Vec<SettingsHistoryResponse> = entries.into_iter().map(SettingsHistoryResponse::from).collect();
+
+    println!("Project | GET /projects/{}/settings/history | user={} | count={} | res=200", project_id, auth_user.username, total_items);
+    Ok(Json(PaginatedResponse::new_in(PageGroup::Projects, responses, total_items, page, limit)))
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct RequestLogQuery {
+    /// Only requests at or after this time (UTC)
+    #[param(value_type = Option<String>)]
+    pub from: Option<chrono::NaiveDateTime>,
+    /// Only requests before this time (UTC)
+    #[param(value_type = Option<String>)]
+    pub to: Option<chrono::NaiveDateTime>,
+    pub page: Option<u64>,
+    pub limit: Option<u64>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct RequestLogResponse {
+    id: i64,
+    #[schema(value_type = String)]
+    api_key_id: Uuid,
+    method: String,
+    path: String,
+    status: i16,
+    /// Request body size from `Content-Length` (0 when absent)
+    bytes: i64,
+    duration_ms: i32,
+    created_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl From<request_log::Model> for RequestLogResponse {
+    fn from(entry: request_log::Model) -> Self {
+        RequestLogResponse {
+            id: entry.id,
+            api_key_id: entry.api_key_id,
+            method: entry.method,
+            path: entry.path,
+            status: entry.status,
+            bytes: entry.bytes,
+            duration_ms: entry.duration_ms,
+            created_at: entry.created_at,
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/projects/{id}/request-logs",
+    description = "API-key requests the project received, newest first. Only recorded while the project's `request_logs` setting is on; entries are written in batches, so the last second or so may not be visible yet.",
+    params(
+        ("id" = Uuid, Path, description = "Project ID"),
+        RequestLogQuery
+    ),
+    responses(
+        (status = 200, description = "Request log", body = PaginatedResponse<RequestLogResponse>),
+        (status = 400, description = "Invalid pagination parameters or time range"),
+        (status = 404, description = "Project not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Project Management"
+)]
+pub async fn list_request_logs(
+    State(db): State<DatabaseConnection>,
+    auth_user: axum::Extension<AuthUser>,
+    Path(project_id): Path<Uuid>,
+    Query(query): Query<RequestLogQuery>,
+) -> Result<Json<PaginatedResponse<RequestLogResponse>>, AppError> {
+    let (page, limit) = Pagination { page: query.page, limit: query.limit }.effective_in(PageGroup::Projects)?;
+
+    if let (Some(from), Some(to)) = (query.from, query.to) {
+        if from >= to {
+            println!("Project | GET /projects/{}/request-logs | user={} | res=400 | from must be before to", project_id, auth_user.username);
+            return Err(AppError::BadRequest("from must be before to".to_string()));
+        }
+    }
+
+    let select = scope::projects(Scope::own(&auth_user))
+        .filter(project::Column::Id.eq(project_id))
+        .filter(project::Column::DeletedAt.is_null());
+    if select.one(&db).await?.is_none() {
+        println!("Project | GET /projects/{}/request-logs | user={} | res=404 | Project not found", project_id, auth_user.username);
+        return Err(AppError::NotFound("Project not found".to_string()));
+    }
+
+    let mut select = request_log::Entity::find().filter(request_log::Column::ProjectId.eq(project_id));
+    if let Some(from) = query.from {
+        select = select.filter(request_log::Column::CreatedAt.gte(from.and_utc()));
+    }
+    if let Some(to) = query.to {
+        select = select.filter(request_log::Column::CreatedAt.lt(to.and_utc()));
+    }
+    let paginator = select
+        .order_by_desc(request_log::Column::CreatedAt)
+        .order_by_desc(request_log::Column::Id)
+        .paginate(&db, limit);
+
+    let total_items = paginator.num_items().await?;
+    let entries = paginator.fetch_page(page.saturating_sub(1)).await?;
+    let responses: Vec<RequestLogResponse> = entries.into_iter().map(RequestLogResponse::from).collect();
+
+    println!("Project | GET /projects/{}/request-logs | user={} | count={} | res=200", project_id, auth_user.username, total_items);
+    Ok(Json(PaginatedResponse::new_in(PageGroup::Projects, responses, total_items, page, limit)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/projects/{id}/settings/rollback/{history_id}",
+    description = "Undo a settings change: restores the entry's `old_settings` (discarding any later changes too) and records the rollback as a new history entry. Existing variants are not regenerated; call sync-variants afterwards if needed.",
+    params(
+        ("id" = Uuid, Path, description = "Project ID"),
+        ("history_id" = Uuid, Path, description = "History entry to undo")
+    ),
+    responses(
+        (status = 200, description = "Settings restored", body = ProjectResponse),
+        (status = 400, description = "Invalid If-Match, or the restored settings are no longer valid"),
+        (status = 404, description = "Project or history entry not found"),
+        (status = 412, description = "The project changed since `If-Match`; the body carries `current_version`"),
+        (status = 422, description = "The restored settings have colliding or reserved variant names"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Project Management"
+)]
+pub async fn rollback_settings(
+    State(db): State<DatabaseConnection>,
+    auth_user: axum::Extension<AuthUser>,
+    Path((project_id, history_id)): Path<(Uuid, Uuid)>,
+    headers: HeaderMap,
+) -> Result<Json<ProjectResponse>, AppError> {
+    let path = format!("/projects/{}/settings/rollback/{}", project_id, history_id);
+    let expected_version = expected_version(&headers, None)?;
+
+    let txn = db.begin().await?;
+    let project = Project::find_by_id(project_id)
+        .filter(project::Column::OwnerId.eq(auth_user.id))
+        .filter(project::Column::DeletedAt.is_null())
+        .lock_exclusive()
+        .one(&txn)
+        .await?
+        .ok_or_else(|| {
+            println!("Project | POST {} | user={} | res=404 | Project not found", path, auth_user.username);
+            AppError::NotFound("Project not found".to_string())
+        })?;
+
+    let entry = project_settings_history::Entity::find_by_id(history_id)
+        .filter(project_settings_history::Column::ProjectId.eq(project_id))
+        .one(&txn)
+        .await?
+        .ok_or_else(|| {
+            println!("Project | POST {} | user={} | res=404 | History entry not found", path, auth_user.username);
+            AppError::NotFound("History entry not found".to_string())
+        })?;
+
+    check_version(&project, expected_version, &format!("POST {}", path), &auth_user.username)?;
+    // Old entries may predate today's rules (e.g. external commands or variant names)
+    let restored = match ProjectSettings::normalize_value(entry.old_settings) {
+        Ok(restored) => restored,
+        Err(e) => {
+            println!("Project | POST {} | user={} | res={} | {}", path, auth_user.username, e.status(), e);
+            // Release the row lock now rather than whenever the connection is next used
+            txn.rollback().await?;
+            return Err(AppError::from(e));
+        }
+    };
+    let old_settings = project.settings.clone();
+    let version = project.version;
+    let mut active_project = project.into_active_model();
+    active_project.settings = Set(restored.clone());
+    active_project.updated_at = Set(chrono::Utc::now());
+    active_project.version = Set(version + 1);
+    let updated_project = active_project.update(&txn).await?;
+
+    let auto_sync = sync_plan::auto_sync(&txn, project_id, &old_settings, &updated_project.settings).await?;
+    record_settings_change(&txn, project_id, old_settings, restored, auth_user.id, Some(history_id)).await?;
+    txn.commit().await?;
+    key_cache::invalidate_project(project_id);
+
+    log_auto_sync(&format!("POST {}", path), &auth_user.username, &auto_sync);
+    println!("Project | POST {} | user={} | res=200", path, auth_user.username);
+    Ok(Json(ProjectResponse::from(updated_project).with_auto_sync(&auto_sync)))
+}
+
 // DELETE /projects/:id
 #[utoipa::path(
     delete,
@@ -248,6 +754,7 @@ pub async fn update_project(
     ),
     responses(
         (status = 200, description = "Project deleted successfully"),
+        (status = 403, description = "Permanent deletion is not allowed with an impersonation token"),
         (status = 404, description = "Project not found"),
         (status = 500, description = "Internal server error")
     ),
@@ -259,6 +766,7 @@ pub async fn update_project(
 pub async fn delete_project(
     State(db): State<DatabaseConnection>,
     auth_user: axum::Extension<AuthUser>,
+    axum::Extension(request_id): axum::Extension<RequestId>,
     Path(project_id): Path<Uuid>,
     Query(query): Query<DeleteProjectQuery>,
 ) -> Result<Json<serde_json::Value>, AppError> {
@@ -266,6 +774,11 @@ pub async fn delete_project(
     // Check if hard delete requested
     let hard_delete = query.permanent.unwrap_or(false);
 
+    if hard_delete && auth_user.impersonated_by.is_some() {
+        println!("Project | DELETE /projects/{}?permanent=true | user={} | res=403 | Impersonation token", project_id, auth_user.username);
+        return Err(AppError::Forbidden("Permanent deletion is not allowed with an impersonation token".to_string()));
+    }
+
     let project = Project::find_by_id(project_id)
         .filter(project::Column::OwnerId.eq(auth_user.id))
         .filter(project::Column::DeletedAt.is_null()) // Always check soft delete first
@@ -284,7 +797,14 @@ pub async fn delete_project(
                     .await
                     .map_err(|e| AppError::InternalServerError(e.to_string()))?;
 
-                let s3_service = S3Service::new().await;
+                let s3_service = project_storage::for_project(&db, p.id).await?;
+                let mut progress = HardDeleteProgress {
+                    request_id,
+                    project_id: p.id,
+                    total: files.len(),
+                    done: 0,
+                    finished: false,
+                };
 
                 // 2. Iterate and delete from S3
                 for f in files {
@@ -292,26 +812,8 @@ pub async fn delete_project(
                     let _ = s3_service.delete_object(&f.s3_key).await;
 
                     // Delete Variants
-                    if let Some(variants) = f.variants_json.as_object() {
-                        for (_v_name, v_path) in variants {
-                            if let Some(v_str) = v_path.as_str() {
-                                // Extract key logic (simplified for now, ideally shared helper)
-                                let config = crate::config::get_config();
-                                let bucket = &config.s3_bucket_name;
-                                
-                                let key_to_delete = if let Some(idx) = v_str.find(&format!("/{}/", bucket)) {
-                                     Some(v_str[idx + bucket.len() + 2..].to_string())
-                                } else if let Ok(url) = url::Url::parse(v_str) {
-                                     Some(url.path().trim_start_matches('/').to_string())
-                                } else {
-                                    None
-                                };
-                                
-                                if let Some(k) = key_to_delete {
-                                    let _ = s3_service.delete_object(&k).await;
-                                }
-                            }
-                        }
+                    for key in variant_keys::all(f.id, &f.variants_json, s3_service.bucket_name()) {
+                        let _ = s3_service.delete_object(&key).await;
                     }
                     
                     // Delete File Row (Optional if cascade is set on DB, but SeaORM needs explicit handling if not relying on DB cascade entirely for logic)
@@ -319,14 +821,23 @@ pub async fn delete_project(
                     // But we will be safe and delete manually or rely on cascade. 
                     // Since schema has `on_delete="Cascade"`, deleting project *should* delete files.
                     // But good to clean up S3 first.
+                    progress.done += 1;
                 }
 
                 // 3. Delete Project from DB
+                tombstones::record(&db, tombstones::in_project(p.id), DeletionReason::ProjectDeleted).await?;
                 let res = Project::delete_by_id(p.id).exec(&db).await.map_err(|e| AppError::InternalServerError(e.to_string()))?;
+                progress.finished = true;
+                project_storage::invalidate(p.id);
+                key_cache::invalidate_project(p.id);
                  
                  if res.rows_affected == 0 {
                     return Err(AppError::InternalServerError("Failed to delete project".into()));
                  }
+                audit::record_by(&db, &auth_user, "project.delete", "project", Some(project_id), serde_json::json!({
+                    "name": p.name,
+                    "permanent": true,
+                })).await;
 
                 println!("Project | DELETE /projects/{}?permanent=true | user={} | res=200", project_id, auth_user.username);
                  Ok(Json(serde_json::json!({
@@ -335,9 +846,15 @@ pub async fn delete_project(
 
             } else {
                 // SOFT DELETE LOGIC (Existing)
+                let name = p.name.clone();
                 let mut active_project = p.into_active_model();
-                active_project.deleted_at = Set(Some(chrono::Utc::now().naive_utc()));
+                active_project.deleted_at = Set(Some(chrono::Utc::now()));
                 active_project.update(&db).await?;
+                key_cache::invalidate_project(project_id);
+                audit::record_by(&db, &auth_user, "project.delete", "project", Some(project_id), serde_json::json!({
+                    "name": name,
+                    "permanent": false,
+                })).await;
     
                 println!("Project | DELETE /projects/{} | user={} | res=200", project_id, auth_user.username);
                 Ok(Json(serde_json::json!({
@@ -355,12 +872,289 @@ pub async fn delete_project(
 
 #[utoipa::path(
     post,
-    path = "/projects/{id}/sync-variants",
+    path = "/admin/projects/{id}/suspend",
+    description = "Suspend a project (superuser only). Its API keys get `403` with code `project_suspended` and the worker \
+leaves its jobs pending; the owner can still read the project and its files. Nothing is deleted. Suspending a suspended \
+project keeps the original `suspended_at`.",
     params(
         ("id" = Uuid, Path, description = "Project ID")
     ),
     responses(
-        (status = 202, description = "Variant synchronization started"),
+        (status = 200, description = "Project suspended", body = ProjectResponse),
+        (status = 403, description = "Superuser access required"),
+        (status = 404, description = "Project not found")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Project Management"
+)]
+pub async fn suspend_project(
+    State(db): State<DatabaseConnection>,
+    auth_user: axum::Extension<AuthUser>,
+    Path(project_id): Path<Uuid>,
+) -> Result<Json<ProjectResponse>, AppError> {
+    set_suspended(&db, &auth_user, project_id, true).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/projects/{id}/unsuspend",
+    description = "Lift a suspension (superuser only). API keys work again and pending jobs are picked up in their original order.",
+    params(
+        ("id" = Uuid, Path, description = "Project ID")
+    ),
+    responses(
+        (status = 200, description = "Project unsuspended", body = ProjectResponse),
+        (status = 403, description = "Superuser access required"),
+        (status = 404, description = "Project not found")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Project Management"
+)]
+pub async fn unsuspend_project(
+    State(db): State<DatabaseConnection>,
+    auth_user: axum::Extension<AuthUser>,
+    Path(project_id): Path<Uuid>,
+) -> Result<Json<ProjectResponse>, AppError> {
+    set_suspended(&db, &auth_user, project_id, false).await
+}
+
+async fn set_suspended(
+    db: &DatabaseConnection,
+    auth_user: &AuthUser,
+    project_id: Uuid,
+    suspend: bool,
+) -> Result<Json<ProjectResponse>, AppError> {
+    let action = if suspend { "suspend" } else { "unsuspend" };
+    let Some(p) = Project::find_by_id(project_id).one(db).await? else {
+        println!("Project | POST /admin/projects/{}/{} | user={} | res=404 | Project not found", project_id, action, auth_user.username);
+        return Err(AppError::NotFound("Project not found".to_string()));
+    };
+
+    if p.suspended_at.is_some() == suspend {
+        println!("Project | POST /admin/projects/{}/{} | user={} | res=200 | Unchanged", project_id, action, auth_user.username);
+        return Ok(Json(ProjectResponse::from(p)));
+    }
+
+    let name = p.name.clone();
+    let mut active_project = p.into_active_model();
+    active_project.suspended_at = Set(suspend.then(chrono::Utc::now));
+    let updated_project = active_project.update(db).await?;
+    key_cache::invalidate_project(project_id);
+    audit::record_by(db, auth_user, &format!("project.{}", action), "project", Some(project_id), serde_json::json!({
+        "name": name,
+    })).await;
+
+    println!("Project | POST /admin/projects/{}/{} | user={} | res=200", project_id, action, auth_user.username);
+    Ok(Json(ProjectResponse::from(updated_project)))
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct ProjectMemberResponse {
+    #[schema(value_type = String)]
+    pub user_id: Uuid,
+    pub username: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/projects/{id}/members",
+    description = "Viewers the project is shared with (superuser only), oldest first.",
+    params(
+        ("id" = Uuid, Path, description = "Project ID")
+    ),
+    responses(
+        (status = 200, description = "Project members", body = Vec<ProjectMemberResponse>),
+        (status = 403, description = "Superuser access required"),
+        (status = 404, description = "Project not found")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Project Management"
+)]
+pub async fn list_project_members(
+    State(db): State<DatabaseConnection>,
+    auth_user: axum::Extension<AuthUser>,
+    Path(project_id): Path<Uuid>,
+) -> Result<Json<Vec<ProjectMemberResponse>>, AppError> {
+    if Project::find_by_id(project_id).one(&db).await?.is_none() {
+        println!("Project | GET /admin/projects/{}/members | user={} | res=404 | Project not found", project_id, auth_user.username);
+        return Err(AppError::NotFound("Project not found".to_string()));
+    }
+
+    let members = project_member::Entity::find()
+        .filter(project_member::Column::ProjectId.eq(project_id))
+        .find_also_related(user::Entity)
+        .order_by_asc(project_member::Column::CreatedAt)
+        .all(&db)
+        .await?
+        .into_iter()
+        .filter_map(|(member, user)| {
+            user.map(|u| ProjectMemberResponse { user_id: member.user_id, username: u.username, created_at: member.created_at })
+        })
+        .collect::<Vec<_>>();
+
+    println!("Project | GET /admin/projects/{}/members | user={} | count={} | res=200", project_id, auth_user.username, members.len());
+    Ok(Json(members))
+}
+
+#[utoipa::path(
+    put,
+    path = "/admin/projects/{id}/members/{user_id}",
+    description = "Share the project with a Viewer (superuser only), who can then read it, its files and its jobs. \
+Only Viewers can be added: other roles read the projects they own. Adding a member twice keeps the first `created_at`.",
+    params(
+        ("id" = Uuid, Path, description = "Project ID"),
+        ("user_id" = Uuid, Path, description = "User ID of a Viewer")
+    ),
+    responses(
+        (status = 200, description = "Viewer is a member", body = ProjectMemberResponse),
+        (status = 400, description = "User is not a Viewer"),
+        (status = 403, description = "Superuser access required"),
+        (status = 404, description = "Project or user not found")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Project Management"
+)]
+pub async fn add_project_member(
+    State(db): State<DatabaseConnection>,
+    auth_user: axum::Extension<AuthUser>,
+    Path((project_id, user_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<ProjectMemberResponse>, AppError> {
+    let path = format!("/admin/projects/{}/members/{}", project_id, user_id);
+    let Some(p) = Project::find_by_id(project_id).one(&db).await? else {
+        println!("Project | PUT {} | user={} | res=404 | Project not found", path, auth_user.username);
+        return Err(AppError::NotFound("Project not found".to_string()));
+    };
+    let Some(member) = user::Entity::find_by_id(user_id).one(&db).await? else {
+        println!("Project | PUT {} | user={} | res=404 | User not found", path, auth_user.username);
+        return Err(AppError::NotFound("User not found".to_string()));
+    };
+    if member.role != Role::Viewer {
+        println!("Project | PUT {} | user={} | res=400 | Not a viewer", path, auth_user.username);
+        return Err(AppError::BadRequest("Only viewers can be added to a project".to_string()));
+    }
+
+    let existing = project_member::Entity::find_by_id((project_id, user_id)).one(&db).await?;
+    let created_at = match existing {
+        Some(existing) => existing.created_at,
+        None => {
+            let saved = project_member::ActiveModel {
+                project_id: Set(project_id),
+                user_id: Set(user_id),
+                created_at: Set(chrono::Utc::now()),
+            }
+            .insert(&db)
+            .await?;
+            audit::record_by(&db, &auth_user, "project.member_added", "project", Some(project_id), serde_json::json!({
+                "name": p.name,
+                "user_id": user_id,
+                "username": member.username,
+            })).await;
+            saved.created_at
+        }
+    };
+
+    println!("Project | PUT {} | user={} | res=200", path, auth_user.username);
+    Ok(Json(ProjectMemberResponse { user_id, username: member.username, created_at }))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/admin/projects/{id}/members/{user_id}",
+    description = "Stop sharing the project with a Viewer (superuser only).",
+    params(
+        ("id" = Uuid, Path, description = "Project ID"),
+        ("user_id" = Uuid, Path, description = "User ID")
+    ),
+    responses(
+        (status = 200, description = "Member removed"),
+        (status = 403, description = "Superuser access required"),
+        (status = 404, description = "User is not a member of the project")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Project Management"
+)]
+pub async fn remove_project_member(
+    State(db): State<DatabaseConnection>,
+    auth_user: axum::Extension<AuthUser>,
+    Path((project_id, user_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let path = format!("/admin/projects/{}/members/{}", project_id, user_id);
+    let result = project_member::Entity::delete_by_id((project_id, user_id)).exec(&db).await?;
+    if result.rows_affected == 0 {
+        println!("Project | DELETE {} | user={} | res=404 | Not a member", path, auth_user.username);
+        return Err(AppError::NotFound("User is not a member of the project".to_string()));
+    }
+    audit::record_by(&db, &auth_user, "project.member_removed", "project", Some(project_id), serde_json::json!({
+        "user_id": user_id,
+    })).await;
+
+    println!("Project | DELETE {} | user={} | res=200", path, auth_user.username);
+    Ok(Json(serde_json::json!({ "message": "Project member removed" })))
+}
+
+/// Logs how far a permanent deletion got when the request is dropped before it finishes
+/// (e.g. by the request timeout). Deleted objects stay deleted and the project row is kept,
+/// so repeating the request completes it.
+struct HardDeleteProgress {
+    request_id: RequestId,
+    project_id: Uuid,
+    total: usize,
+    done: usize,
+    finished: bool,
+}
+
+impl Drop for HardDeleteProgress {
+    fn drop(&mut self) {
+        if !self.finished {
+            eprintln!(
+                "Project | DELETE /projects/{}?permanent=true | request={} | Stopped after removing the objects of {}/{} files; project kept",
+                self.project_id, self.request_id, self.done, self.total
+            );
+        }
+    }
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct SyncVariantsQuery {
+    /// Report the planned work instead of enqueueing jobs
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Files to include in the dry-run `breakdown` (default 0, max 1000)
+    #[serde(default)]
+    pub breakdown_limit: u64,
+    /// Only images whose original changed after their variants were generated (`variants_stale`)
+    #[serde(default)]
+    pub only_stale: bool,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct SyncPlanJobResponse {
+    #[schema(value_type = String)]
+    pub job_id: Uuid,
+}
+
+#[utoipa::path(
+    post,
+    path = "/projects/{id}/sync-variants",
+    description = "Regenerate every image's variants from the current settings, or only stale ones with `only_stale=true`. With `dry_run=true`, nothing is enqueued and the planned work is returned instead. Projects with more images than `SYNC_DRY_RUN_INLINE_MAX_FILES` get their plan as a `plan_project_sync` job; poll `GET /jobs` for `result` in its payload.",
+    params(
+        ("id" = Uuid, Path, description = "Project ID"),
+        SyncVariantsQuery
+    ),
+    responses(
+        (status = 200, description = "Variant synchronization started, or the dry-run plan", body = SyncPlan),
+        (status = 202, description = "Dry-run plan queued as a job", body = SyncPlanJobResponse),
         (status = 404, description = "Project not found"),
         (status = 500, description = "Internal server error")
     ),
@@ -373,7 +1167,8 @@ pub async fn sync_variants(
     State(db): State<DatabaseConnection>,
     auth_user: axum::Extension<AuthUser>,
     Path(project_id): Path<Uuid>,
-) -> Result<Json<serde_json::Value>, AppError> {
+    Query(query): Query<SyncVariantsQuery>,
+) -> Result<Response, AppError> {
     let project = Project::find_by_id(project_id)
         .filter(project::Column::OwnerId.eq(auth_user.id))
         .filter(project::Column::DeletedAt.is_null())
@@ -382,39 +1177,33 @@ pub async fn sync_variants(
 
     match project {
         Some(p) => {
-             // Create Sync Job Payload (Optional, if we want to log it or use it for the wrapper job logic in future)
-             // But we are spawning individual file jobs directly here.
-             
-             // 1. Find all image files
-            let files = file::Entity::find()
-                .filter(file::Column::ProjectId.eq(p.id))
-                .filter(file::Column::MimeType.like("image/%")) // SeaORM like? or contains?
-                // SeaORM uses LIKE for strings. 
-                // MimeType is String.
-                // .filter(file::Column::MimeType.contains("image")) Is safer if SeaORM supports it.
-                // Let's use `starts_with` or `contains`.
-                .filter(file::Column::MimeType.contains("image"))
+            if query.dry_run {
+                return plan_sync_variants(&db, &auth_user, p, &query).await;
+            }
+
+            // 1. Find all image files
+            let files = sync_plan::sync_candidates(p.id, query.only_stale)
                 .all(&db)
                 .await
                 .map_err(|e| AppError::InternalServerError(e.to_string()))?;
 
-            let variants_json = p.settings.get("variants").cloned().unwrap_or(serde_json::json!({}));
+            let settings: ProjectSettings = serde_json::from_value(p.settings.clone())
+                .map_err(|e| AppError::BadRequest(format!("Invalid project settings: {}", e)))?;
             
             let mut job_count = 0;
             for f in files {
-                let job_payload = serde_json::json!({
-                    "type": "sync_file_variants",
-                    "variants_config": variants_json 
-                });
+                let job_payload = JobPayload::SyncFileVariants {
+                    variants_config: settings.variants.clone(),
+                }.to_value();
 
                 let job = job::ActiveModel {
                     id: Set(Uuid::new_v4()),
-                    file_id: Set(f.id),
+                    file_id: Set(Some(f.id)),
+                    project_id: Set(Some(p.id)),
                     status: Set("pending".to_string()),
                     payload: Set(job_payload),
-                    created_at: Set(chrono::Utc::now().naive_utc()),
-                    updated_at: Set(chrono::Utc::now().naive_utc()),
-                    ..Default::default()
+                    created_at: Set(chrono::Utc::now()),
+                    updated_at: Set(chrono::Utc::now()),
                 };
 
                 job.insert(&db).await.map_err(|e| AppError::InternalServerError(e.to_string()))?;
@@ -425,10 +1214,48 @@ pub async fn sync_variants(
             Ok(Json(serde_json::json!({
                 "message": "Variant synchronization started",
                 "jobs_queued": job_count
-            })))
+            })).into_response())
         }
         None => {
             Err(AppError::NotFound("Project not found".to_string()))
         }
     }
 }
+
+/// Dry run of `sync_variants`: the plan inline, or a `PlanProjectSync` job for large projects.
+async fn plan_sync_variants(
+    db: &DatabaseConnection,
+    auth_user: &AuthUser,
+    project: project::Model,
+    query: &SyncVariantsQuery,
+) -> Result<Response, AppError> {
+    let settings: ProjectSettings = serde_json::from_value(project.settings.clone())
+        .map_err(|e| AppError::BadRequest(format!("Invalid project settings: {}", e)))?;
+    let target = settings.variants.unwrap_or_default();
+
+    let candidates = sync_plan::sync_candidates(project.id, query.only_stale).count(db).await?;
+    if candidates > get_config().sync_dry_run_inline_max_files {
+        let job = job::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            file_id: Set(None),
+            project_id: Set(Some(project.id)),
+            status: Set("pending".to_string()),
+            payload: Set(JobPayload::PlanProjectSync {
+                project_id: project.id,
+                variants_config: Some(target),
+                breakdown_limit: query.breakdown_limit.min(sync_plan::MAX_BREAKDOWN_ENTRIES),
+                only_stale: query.only_stale,
+            }.to_value()),
+            created_at: Set(chrono::Utc::now()),
+            updated_at: Set(chrono::Utc::now()),
+        };
+        let job = job.insert(db).await?;
+
+        println!("Project | POST /projects/{}/sync-variants?dry_run=true | user={} | files={} | job={} | res=202", project.id, auth_user.username, candidates, job.id);
+        return Ok((StatusCode::ACCEPTED, Json(SyncPlanJobResponse { job_id: job.id })).into_response());
+    }
+
+    let plan = sync_plan::plan_project_sync(db, project.id, &target, query.breakdown_limit, query.only_stale).await?;
+    println!("Project | POST /projects/{}/sync-variants?dry_run=true | user={} | files={} | res=200", project.id, auth_user.username, plan.files);
+    Ok(Json(plan).into_response())
+}
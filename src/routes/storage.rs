@@ -0,0 +1,331 @@
+use axum::{
+    extract::{Extension, Path, Query, State},
+    http::StatusCode,
+    response::Json,
+};
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder, QuerySelect};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use uuid::Uuid;
+
+use crate::entities::{file, project};
+use crate::error::AppError;
+use crate::middleware::auth::AuthUser;
+use crate::services::{project_storage, reconcile};
+use crate::config::get_config;
+use crate::services::s3::{BucketDiagnostics, BucketReport, S3Service};
+
+/// Upper bound for `limit` on object listings (S3's own page maximum).
+const MAX_OBJECTS_PAGE_SIZE: u64 = 1000;
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct ListObjectsQuery {
+    /// Keys per page (required, 1-1000)
+    pub limit: Option<u64>,
+    /// `next_continuation_token` from the previous page
+    pub continuation_token: Option<String>,
+    /// Annotate each key with whether a `files` row or variant entry references it
+    #[serde(default)]
+    pub diff: bool,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct StorageObject {
+    pub key: String,
+    pub size: i64,
+    pub last_modified: Option<String>,
+    /// Only present with `diff=true`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tracked: Option<bool>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct ObjectListResponse {
+    pub prefix: String,
+    pub objects: Vec<StorageObject>,
+    pub next_continuation_token: Option<String>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/storage/verify",
+    description = "Re-check that the bucket exists (creating it if needed) and re-apply the public-read policy when `S3_PUBLIC_OBJECTS` is enabled (superuser only).",
+    responses(
+        (status = 200, description = "Bucket is usable", body = BucketReport),
+        (status = 403, description = "Superuser access required"),
+        (status = 500, description = "Bucket is missing and could not be created")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Storage"
+)]
+pub async fn verify_storage(
+    Extension(user): Extension<AuthUser>,
+) -> Result<Json<BucketReport>, AppError> {
+    let s3_service = S3Service::new().await;
+    let report = s3_service.verify_bucket().await?;
+
+    println!("Storage | POST /admin/storage/verify | user={} | bucket={} | policy={} | res=200", user.username, report.bucket, report.policy);
+    Ok(Json(report))
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct StorageDiagnosticsResponse {
+    /// `S3_PROVIDER` (or the provider inferred from `S3_ENDPOINT`)
+    pub provider: &'static str,
+    pub force_path_style: bool,
+    /// `S3_PUBLIC_OBJECTS`: whether uploads use public-read ACLs and a bucket policy
+    pub public_objects: bool,
+    /// Whether the circuit breaker is currently failing storage calls fast
+    pub circuit_open: bool,
+    #[serde(flatten)]
+    pub bucket: BucketDiagnostics,
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/storage/diagnostics",
+    description = "Probe the global bucket and report each check separately (superuser only): reachability, bucket policy state, \
+public-read ACL support and a presigned-URL round trip through a temporary object, plus the effective endpoint, region and provider. \
+Always 200; read the individual checks.",
+    responses(
+        (status = 200, description = "Diagnostics report", body = StorageDiagnosticsResponse),
+        (status = 403, description = "Superuser access required")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Storage"
+)]
+pub async fn storage_diagnostics(
+    Extension(user): Extension<AuthUser>,
+) -> Json<StorageDiagnosticsResponse> {
+    let config = get_config();
+    let s3_service = S3Service::new().await;
+    let bucket = s3_service.diagnose().await;
+
+    println!(
+        "Storage | GET /admin/storage/diagnostics | user={} | bucket={} | reachable={} | acl={} | presign={} | res=200",
+        user.username, bucket.bucket, bucket.reachable.ok, bucket.acl.ok, bucket.presign.ok
+    );
+    Json(StorageDiagnosticsResponse {
+        provider: config.s3_provider.as_str(),
+        force_path_style: config.s3_force_path_style,
+        public_objects: config.s3_public_objects,
+        circuit_open: S3Service::circuit_open(),
+        bucket,
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/projects/{id}/objects",
+    description = "List what is actually stored under a project's key prefix, one S3 page at a time (superuser only). \
+`limit` is required; pass `next_continuation_token` back as `continuation_token` for the next page.",
+    params(
+        ("id" = Uuid, Path, description = "Project ID"),
+        ListObjectsQuery
+    ),
+    responses(
+        (status = 200, description = "One page of objects", body = ObjectListResponse),
+        (status = 400, description = "Missing or invalid limit"),
+        (status = 403, description = "Superuser access required"),
+        (status = 404, description = "Project not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Storage"
+)]
+pub async fn list_project_objects(
+    State(db): State<DatabaseConnection>,
+    Extension(user): Extension<AuthUser>,
+    Path(project_id): Path<Uuid>,
+    Query(query): Query<ListObjectsQuery>,
+) -> Result<Json<ObjectListResponse>, AppError> {
+    let limit = match query.limit {
+        Some(limit) if (1..=MAX_OBJECTS_PAGE_SIZE).contains(&limit) => limit,
+        _ => {
+            println!("Storage | GET /admin/projects/{}/objects | user={} | res=400 | Invalid limit", project_id, user.username);
+            return Err(AppError::BadRequest(format!("limit is required and must be between 1 and {}", MAX_OBJECTS_PAGE_SIZE)));
+        }
+    };
+
+    let project = project::Entity::find_by_id(project_id)
+        .one(&db)
+        .await?
+        .ok_or(AppError::NotFound("Project not found".into()))?;
+
+    // Same layout the upload handlers and worker use: {storage_prefix}/...
+    let prefix = format!("{}/", project.storage_prefix);
+
+    let s3_service = project_storage::for_project(&db, project.id).await?;
+    let page = s3_service.list_objects(&prefix, limit as i32, query.continuation_token).await?;
+
+    let known_keys = if query.diff {
+        Some(tracked_keys(&db, project.id).await?)
+    } else {
+        None
+    };
+
+    let objects: Vec<StorageObject> = page
+        .objects
+        .into_iter()
+        .map(|o| StorageObject {
+            tracked: known_keys.as_ref().map(|keys| keys.contains(&o.key)),
+            key: o.key,
+            size: o.size,
+            last_modified: o.last_modified,
+        })
+        .collect();
+
+    println!("Storage | GET /admin/projects/{}/objects | user={} | count={} | diff={} | res=200", project_id, user.username, objects.len(), query.diff);
+    Ok(Json(ObjectListResponse {
+        prefix,
+        objects,
+        next_continuation_token: page.next_continuation_token,
+    }))
+}
+
+/// Every key the database references for a project: originals plus variant keys.
+/// Legacy variant entries stored as full URLs are not matched.
+/// Includes trashed files and files of a trashed project, which keep their objects until purged.
+async fn tracked_keys(db: &DatabaseConnection, project_id: Uuid) -> Result<HashSet<String>, AppError> {
+    let rows: Vec<(String, serde_json::Value)> = file::Entity::find()
+        .select_only()
+        .column(file::Column::S3Key)
+        .column(file::Column::VariantsJson)
+        .filter(file::Column::ProjectId.eq(project_id))
+        .into_tuple()
+        .all(db)
+        .await?;
+
+    let mut keys = HashSet::new();
+    for (s3_key, variants) in rows {
+        keys.insert(s3_key);
+        if let Some(variants) = variants.as_object() {
+            keys.extend(variants.values().filter_map(|v| v.as_str()).map(str::to_string));
+        }
+    }
+
+    Ok(keys)
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct ReconcileJobResponse {
+    #[schema(value_type = String)]
+    pub job_id: Uuid,
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/projects/{id}/reconcile",
+    description = "Queue a `reconcile_storage` job for the project (superuser only). It lists everything under the project prefix, \
+at most `RECONCILE_LIST_PAGES_PER_SEC` pages of 1000 keys per second, and compares the total with `SUM(files.size)`. \
+The report is kept under `result` in the job payload and the totals on the project. If a reconciliation is already \
+pending or running, its job is returned instead.",
+    params(
+        ("id" = Uuid, Path, description = "Project ID")
+    ),
+    responses(
+        (status = 202, description = "Reconciliation queued (or already queued)", body = ReconcileJobResponse),
+        (status = 403, description = "Superuser access required"),
+        (status = 404, description = "Project not found")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Storage"
+)]
+pub async fn reconcile_project_storage(
+    State(db): State<DatabaseConnection>,
+    Extension(user): Extension<AuthUser>,
+    Path(project_id): Path<Uuid>,
+) -> Result<(StatusCode, Json<ReconcileJobResponse>), AppError> {
+    if project::Entity::find_by_id(project_id).one(&db).await?.is_none() {
+        println!("Storage | POST /admin/projects/{}/reconcile | user={} | res=404 | Project not found", project_id, user.username);
+        return Err(AppError::NotFound("Project not found".into()));
+    }
+
+    let job_id = reconcile::enqueue(&db, project_id).await?;
+    println!("Storage | POST /admin/projects/{}/reconcile | user={} | job={} | res=202", project_id, user.username, job_id);
+    Ok((StatusCode::ACCEPTED, Json(ReconcileJobResponse { job_id })))
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct StorageDriftQuery {
+    /// Only projects whose drift exceeds `STORAGE_DRIFT_THRESHOLD_BYTES`
+    #[serde(default)]
+    pub flagged: bool,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct ProjectDriftEntry {
+    #[schema(value_type = String)]
+    pub project_id: Uuid,
+    pub project_name: String,
+    pub last_reconciled_at: chrono::DateTime<chrono::Utc>,
+    pub reconciled_bytes: i64,
+    pub drift_bytes: i64,
+    pub flagged: bool,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct StorageDriftResponse {
+    pub threshold_bytes: i64,
+    /// Largest drift (either way) first
+    pub projects: Vec<ProjectDriftEntry>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/storage/drift",
+    description = "Result of the last storage reconciliation of every project that has one (superuser only), largest drift first. \
+Projects whose drift exceeds `STORAGE_DRIFT_THRESHOLD_BYTES` either way are flagged.",
+    params(StorageDriftQuery),
+    responses(
+        (status = 200, description = "Reconciled projects", body = StorageDriftResponse),
+        (status = 403, description = "Superuser access required")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Storage"
+)]
+pub async fn list_storage_drift(
+    State(db): State<DatabaseConnection>,
+    Extension(user): Extension<AuthUser>,
+    Query(query): Query<StorageDriftQuery>,
+) -> Result<Json<StorageDriftResponse>, AppError> {
+    let projects = project::Entity::find()
+        .filter(project::Column::LastReconciledAt.is_not_null())
+        .order_by_asc(project::Column::Name)
+        .all(&db)
+        .await?;
+
+    let mut entries: Vec<ProjectDriftEntry> = projects
+        .into_iter()
+        .filter_map(|p| {
+            let drift_bytes = p.reconciled_drift_bytes.unwrap_or(0);
+            Some(ProjectDriftEntry {
+                project_id: p.id,
+                project_name: p.name,
+                last_reconciled_at: p.last_reconciled_at?,
+                reconciled_bytes: p.reconciled_bytes.unwrap_or(0),
+                drift_bytes,
+                flagged: reconcile::exceeds_threshold(drift_bytes),
+            })
+        })
+        .filter(|e| !query.flagged || e.flagged)
+        .collect();
+    entries.sort_by_key(|e| std::cmp::Reverse(e.drift_bytes.unsigned_abs()));
+
+    let flagged = entries.iter().filter(|e| e.flagged).count();
+    println!("Storage | GET /admin/storage/drift | user={} | count={} | flagged={} | res=200", user.username, entries.len(), flagged);
+    Ok(Json(StorageDriftResponse {
+        threshold_bytes: get_config().storage_drift_threshold_bytes,
+        projects: entries,
+    }))
+}
@@ -1,15 +1,27 @@
 use axum::{
-    extract::{Multipart, State},
+    extract::{multipart::Field, Multipart, State},
+    http::StatusCode,
     response::Json,
     Extension,
 };
-use sea_orm::{ActiveModelTrait, DatabaseConnection, Set};
+use image::ImageFormat;
+use sea_orm::{ActiveModelTrait, DatabaseConnection, Set, TransactionError, TransactionTrait};
 use serde::Serialize;
+use std::collections::HashMap;
 use uuid::Uuid;
 use crate::entities::{file, job};
 use crate::error::AppError;
 use crate::middleware::api_key::ProjectContext;
-use crate::services::s3::S3Service;
+use crate::middleware::auth::AuthUser;
+use crate::middleware::upload_limit::{self, UploadConcurrency};
+use crate::models::job::JobPayload;
+use crate::models::settings::{ProjectSettings, VariantConfig};
+use crate::routes::{created, Created};
+use crate::services::file_events::{self, FileEvent};
+use crate::services::project_storage;
+use crate::services::storage::Storage;
+use crate::services::urls::UrlBuilder;
+use crate::utils::{content_disposition, content_type, file_extension, key_extension, normalize_folder, sanitize_filename, sha256_hex};
 
 #[derive(Serialize, utoipa::ToSchema)]
 pub struct FileUploadResponse {
@@ -24,39 +36,57 @@ pub struct FileUploadResponse {
 pub struct ImageUploadResponse {
     id: Uuid,
     original_url: String,
+    /// Pending variants; those from the upload's `variants` override carry `"override": true`
     variants: serde_json::Value,
 }
 
-// Helper to get file extension
-fn get_extension(filename: &str) -> String {
-    std::path::Path::new(filename)
-        .extension()
-        .and_then(std::ffi::OsStr::to_str)
-        .unwrap_or("bin")
-        .to_string()
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct BatchImageUploadResponse {
+    uploaded: Vec<BatchImageUploadEntry>,
+    errors: Vec<BatchUploadError>,
 }
 
-#[utoipa::path(
-    post,
-    path = "/upload/file",
-    tag = "File Upload",
-    request_body(content = Vec<u8>, content_type = "multipart/form-data"),
-    responses(
-        (status = 200, description = "File uploaded successfully", body = FileUploadResponse),
-        (status = 400, description = "Bad Request"),
-        (status = 401, description = "Unauthorized"),
-        (status = 500, description = "Internal Server Error")
-    ),
-    security(
-        ("api_key" = [])
-    )
-)]
-// Helper to sanitize bucket name
-fn sanitize_bucket_name(name: &str) -> String {
-    name.to_lowercase()
-        .chars()
-        .map(|c| if c.is_alphanumeric() { c } else { '-' })
-        .collect::<String>()
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct BatchImageUploadEntry {
+    /// Zero-based index of the `file` part in the request
+    index: usize,
+    id: Uuid,
+    original_url: String,
+    s3_key: String,
+    job_id: Uuid,
+    variants: serde_json::Value,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct BatchUploadError {
+    /// Zero-based index of the rejected `file` part
+    index: usize,
+    filename: Option<String>,
+    /// Stable reason clients can match on, e.g. `part_too_large`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code: Option<&'static str>,
+    error: String,
+}
+
+/// Rejects filenames whose final extension the project blocks or does not allow.
+fn check_extension(project: &ProjectContext, filename: &str) -> Result<(), AppError> {
+    match project.settings.rejected_extension(file_extension(filename).as_deref()) {
+        Some(ext) => Err(AppError::UnsupportedMediaType(format!("File extension '{}' is not allowed for this project", ext))),
+        None => Ok(()),
+    }
+}
+
+/// `Content-Disposition` stored with an object: `attachment` for HTML and SVG unless the
+/// project sets `inline_html`, so opening its URL downloads it instead of running it.
+fn stored_disposition(project: &ProjectContext, content_type: &str, filename: &str) -> Option<String> {
+    (content_type::is_active(content_type) && !project.settings.inline_html)
+        .then(|| content_disposition("attachment", filename))
+}
+
+/// Reads a `folder` part. It applies to the `file` parts that follow it, so clients send it first.
+async fn read_folder(field: Field<'_>) -> Result<String, String> {
+    let raw = field.text().await.map_err(|_| "Invalid multipart data".to_string())?;
+    normalize_folder(&raw)
 }
 
 #[utoipa::path(
@@ -65,9 +95,14 @@ fn sanitize_bucket_name(name: &str) -> String {
     tag = "File Upload",
     request_body(content = Vec<u8>, content_type = "multipart/form-data"),
     responses(
-        (status = 200, description = "File uploaded successfully", body = FileUploadResponse),
+        (status = 201, description = "File uploaded successfully", body = FileUploadResponse,
+            headers(("Location" = String, description = "Path of the created file"))),
         (status = 400, description = "Bad Request"),
         (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Project is deleted"),
+        (status = 413, description = "A part is larger than `UPLOAD_MAX_PART_BYTES` (`code`: `part_too_large`) or the body is over its limit"),
+        (status = 415, description = "File extension blocked by project settings"),
+        (status = 429, description = "Too many concurrent uploads for the instance or the API key; retry after `Retry-After`"),
         (status = 500, description = "Internal Server Error")
     ),
     security(
@@ -76,28 +111,45 @@ fn sanitize_bucket_name(name: &str) -> String {
 )]
 pub async fn upload_file(
     State(db): State<DatabaseConnection>,
+    State(urls): State<UrlBuilder>,
     Extension(project): Extension<ProjectContext>,
     mut multipart: Multipart,
-) -> Result<Json<FileUploadResponse>, AppError> {
-    let s3_service = S3Service::new().await;
+) -> Result<Created<FileUploadResponse>, AppError> {
+    let s3_service = project_storage::for_project(&db, project.id).await?;
+    let mut folder = String::new();
     
-    while let Some(field) = multipart.next_field().await.map_err(|_| AppError::BadRequest("Invalid multipart data".to_string()))? {
-        if field.name() == Some("file") {
-            let filename = field.file_name().unwrap_or("unknown").to_string();
-            let content_type = field.content_type().unwrap_or("application/octet-stream").to_string();
-            let data = field.bytes().await.map_err(|_| AppError::InternalServerError("Failed to read file bytes".to_string()))?;
+    while let Some(mut field) = multipart.next_field().await.map_err(|_| AppError::BadRequest("Invalid multipart data".to_string()))? {
+        if field.name() == Some("folder") {
+            folder = read_folder(field).await.map_err(|e| {
+                println!("Upload | POST /upload/file | project={} | res=400 | {}", project.name, e);
+                AppError::BadRequest(e)
+            })?;
+        } else if field.name() == Some("file") {
+            let filename = sanitize_filename(field.file_name().unwrap_or("unknown"));
+            let declared_type = field.content_type().unwrap_or("application/octet-stream").to_string();
+
+            if let Err(e) = check_extension(&project, &filename) {
+                println!("Upload | POST /upload/file | project={} | res=415 | {}", project.name, e);
+                return Err(e);
+            }
+
+            let data = read_part(&mut field).await.inspect_err(|e| {
+                println!("Upload | POST /upload/file | project={} | {}", project.name, e);
+            })?;
+            let content_type = content_type::normalize(&declared_type, &filename, &data);
             let size = data.len() as i64;
-            let ext = get_extension(&filename);
+            let ext = key_extension(&filename);
+            let content_hash = sha256_hex(&data);
             
             let file_id = Uuid::new_v4();
-            // Format: {project_name}-{project_id}/files/{file_id}.{ext}
-            let s3_key = format!("{}-{}/files/{}.{}", sanitize_bucket_name(&project.name), project.id, file_id, ext);
+            // Format: {storage_prefix}/files/{file_id}.{ext}
+            let s3_key = format!("{}/files/{}.{}", project.storage_prefix, file_id, ext);
             
             // Ensure bucket exists
             s3_service.ensure_bucket_exists().await?;
 
             // Upload to S3
-            s3_service.put_object(&s3_key, data.to_vec(), &content_type).await?;
+            s3_service.put_object_as(&s3_key, data.to_vec(), &content_type, stored_disposition(&project, &content_type, &filename)).await?;
             
             // Save to DB
             let file = file::ActiveModel {
@@ -109,22 +161,35 @@ pub async fn upload_file(
                 size: Set(size),
                 status: Set("ready".to_string()),
                 variants_json: Set(serde_json::json!({})),
-                created_at: Set(chrono::Utc::now().naive_utc()),
-                updated_at: Set(chrono::Utc::now().naive_utc()),
+                content_hash: Set(Some(content_hash)),
+                folder: Set(folder.clone()),
+                derived_from: Set(None),
+                variants_source_hash: Set(None),
+                variant_errors: Set(serde_json::json!({})),
+                variant_dimensions: Set(serde_json::json!({})),
+                variant_hashes: Set(serde_json::json!({})),
+                created_at: Set(chrono::Utc::now()),
+                updated_at: Set(chrono::Utc::now()),
+                version: Set(1),
+                deleted_at: Set(None),
             };
             
-            let saved_file = file.insert(&db).await.map_err(AppError::DatabaseError)?;
+            let saved_file = match file.insert(&db).await {
+                Ok(saved_file) => saved_file,
+                Err(e) => {
+                    eprintln!("Upload | Failed to record file {}: {}", file_id, e);
+                    discard_object(s3_service.as_ref(), &s3_key).await;
+                    return Err(AppError::DatabaseError(e));
+                }
+            };
+            file_events::emit(&db, &project.settings, FileEvent::Created, &saved_file);
+            file_events::emit(&db, &project.settings, FileEvent::Ready, &saved_file);
             
             // Construct URL
-            let config = crate::config::get_config();
-            let url = if let Some(endpoint) = &config.s3_endpoint {
-                format!("{}/{}/{}", endpoint, s3_service.bucket_name, s3_key)
-            } else {
-                format!("https://{}.s3.{}.amazonaws.com/{}", s3_service.bucket_name, config.aws_region, s3_key)
-            };
+            let url = urls.object_url(s3_service.as_ref(), &s3_key).await?;
 
-            println!("Upload | POST /upload/file | project={} | file={} | res=200", project.name, saved_file.filename);
-            return Ok(Json(FileUploadResponse {
+            println!("Upload | POST /upload/file | project={} | file={} | res=201", project.name, saved_file.filename);
+            return Ok(created(format!("/files/{}", saved_file.id), FileUploadResponse {
                 id: saved_file.id,
                 url,
                 filename: saved_file.filename,
@@ -142,11 +207,20 @@ pub async fn upload_file(
     post,
     path = "/upload/image",
     tag = "File Upload",
+    description = "Upload one image. An optional `variants` part (JSON, same schema as the project's `variants` setting) \
+adds or replaces variants for this upload only; send it before the `file` part. At most `UPLOAD_OVERRIDE_MAX_VARIANTS` \
+variants, none larger than `UPLOAD_OVERRIDE_MAX_DIMENSION` pixels.",
     request_body(content = Vec<u8>, content_type = "multipart/form-data"),
     responses(
-        (status = 200, description = "Image uploaded successfully", body = ImageUploadResponse),
-        (status = 400, description = "Bad Request"),
+        (status = 201, description = "Image uploaded successfully", body = ImageUploadResponse,
+            headers(("Location" = String, description = "Path of the created file"))),
+        (status = 400, description = "Bad Request or invalid variants override"),
         (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Project is deleted"),
+        (status = 413, description = "A part is larger than `UPLOAD_MAX_PART_BYTES` (`code`: `part_too_large`) or the body is over its limit"),
+        (status = 415, description = "File extension blocked by project settings"),
+        (status = 429, description = "Too many concurrent uploads for the instance or the API key; retry after `Retry-After`"),
+        (status = 422, description = "Override variant names collide or are reserved"),
         (status = 500, description = "Internal Server Error")
     ),
     security(
@@ -155,14 +229,28 @@ pub async fn upload_file(
 )]
 pub async fn upload_image(
     State(db): State<DatabaseConnection>,
+    State(urls): State<UrlBuilder>,
     Extension(project): Extension<ProjectContext>,
     mut multipart: Multipart,
-) -> Result<Json<ImageUploadResponse>, AppError> {
-    let s3_service = S3Service::new().await;
+) -> Result<Created<ImageUploadResponse>, AppError> {
+    let s3_service = project_storage::for_project(&db, project.id).await?;
+    let mut folder = String::new();
+    let mut overrides = HashMap::new();
 
-    while let Some(field) = multipart.next_field().await.map_err(|_| AppError::BadRequest("Invalid multipart data".to_string()))? {
-        if field.name() == Some("file") {
-            let filename = field.file_name().unwrap_or("unknown").to_string();
+    while let Some(mut field) = multipart.next_field().await.map_err(|_| AppError::BadRequest("Invalid multipart data".to_string()))? {
+        if field.name() == Some("folder") {
+            folder = read_folder(field).await.map_err(|e| {
+                println!("Upload | POST /upload/image | project={} | res=400 | {}", project.name, e);
+                AppError::BadRequest(e)
+            })?;
+        } else if field.name() == Some("variants") {
+            let raw = field.text().await.map_err(|_| AppError::BadRequest("Invalid multipart data".to_string()))?;
+            overrides = ProjectSettings::parse_variant_override(&raw).map_err(|e| {
+                println!("Upload | POST /upload/image | project={} | res={} | {}", project.name, e.status(), e);
+                AppError::from(e)
+            })?;
+        } else if field.name() == Some("file") {
+            let filename = client_filename(&field);
             let content_type = field.content_type().unwrap_or("application/octet-stream").to_string();
             
             // Basic validation for image type
@@ -171,99 +259,381 @@ pub async fn upload_image(
                 return Err(AppError::BadRequest("File is not an image".to_string()));
             }
 
-            let data = field.bytes().await.map_err(|_| AppError::InternalServerError("Failed to read file bytes".to_string()))?;
-            let size = data.len() as i64;
-            let ext = get_extension(&filename);
+            let data = read_part(&mut field).await.inspect_err(|e| {
+                println!("Upload | POST /upload/image | project={} | {}", project.name, e);
+            })?;
+            let name = resolve_image_name(filename, content_type, &data);
 
-            let file_id = Uuid::new_v4();
-            // Format: {project_name}-{project_id}/images/original/{file_id}.{ext}
-            let s3_key = format!("{}-{}/images/original/{}.{}", sanitize_bucket_name(&project.name), project.id, file_id, ext);
+            if let Err(e) = check_extension(&project, &name.filename) {
+                println!("Upload | POST /upload/image | project={} | res=415 | {}", project.name, e);
+                return Err(e);
+            }
 
             // Ensure bucket exists
             s3_service.ensure_bucket_exists().await?;
 
-            // Upload Original to S3
-            s3_service.put_object(&s3_key, data.to_vec(), &content_type).await?;
+            let stored = store_image(&db, s3_service.as_ref(), &project, &folder, &overrides, name, data.to_vec()).await?;
 
-            // Calculate future variant URLs
-            let mut variants_map = serde_json::Map::new();
-            
-            if let Some(variants_config) = &project.settings.variants {
-                for (variant_name, config) in variants_config {
-                    // Determine extension for variant
-                    let variant_ext = config.format.as_deref().unwrap_or(&ext);
-                    let variant_ext = if variant_ext == "original" { &ext } else { variant_ext };
-                    
-                    // Format: {project_name}-{project_id}/images/{variant_name}/{file_id}.{ext}
-                    let variant_key = format!("{}-{}/images/{}/{}.{}", 
-                        sanitize_bucket_name(&project.name), 
-                        project.id, 
-                        variant_name, 
-                        file_id, 
-                        variant_ext
-                    );
-
-                    // Construct URL
-                    let config = crate::config::get_config();
-                    let variant_url = if let Some(endpoint) = &config.s3_endpoint {
-                        format!("{}/{}/{}", endpoint, s3_service.bucket_name, variant_key)
-                    } else {
-                        format!("https://{}.s3.{}.amazonaws.com/{}", s3_service.bucket_name, config.aws_region, variant_key)
-                    };
-                    
-                    variants_map.insert(variant_name.clone(), serde_json::Value::String(variant_url));
+            let original_url = urls.object_url(s3_service.as_ref(), &stored.s3_key).await?;
+            println!("Upload | POST /upload/image | project={} | file={} | res=201", project.name, stored.id);
+            return Ok(created(format!("/files/{}", stored.id), ImageUploadResponse {
+                id: stored.id,
+                original_url,
+                variants: stored.variants,
+            }));
+        }
+    }
+
+    println!("Upload | POST /upload/image | project={} | res=400 | No file field found", project.name);
+    Err(AppError::BadRequest("No file field found".to_string()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/upload/images",
+    tag = "File Upload",
+    description = "Upload several images in one request, one `file` part each (at most `BATCH_UPLOAD_MAX_FILES`). \
+Parts are stored independently: each successful part is returned in `uploaded` even if a later part fails, \
+and rejected parts are listed in `errors` with their zero-based part index. A part whose database insert fails \
+has its S3 object removed.",
+    request_body(content = Vec<u8>, content_type = "multipart/form-data"),
+    responses(
+        (status = 201, description = "At least one part was stored; per-part results", body = BatchImageUploadResponse),
+        (status = 200, description = "Every part was rejected; per-part results", body = BatchImageUploadResponse),
+        (status = 400, description = "No file parts found"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Project is deleted"),
+        (status = 415, description = "File extension blocked by project settings"),
+        (status = 429, description = "Too many concurrent uploads for the instance or the API key; retry after `Retry-After`"),
+        (status = 500, description = "Internal Server Error")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+pub async fn upload_images(
+    State(db): State<DatabaseConnection>,
+    State(urls): State<UrlBuilder>,
+    Extension(project): Extension<ProjectContext>,
+    mut multipart: Multipart,
+) -> Result<(StatusCode, Json<BatchImageUploadResponse>), AppError> {
+    let s3_service = project_storage::for_project(&db, project.id).await?;
+    // Once for the whole batch rather than per part
+    s3_service.ensure_bucket_exists().await?;
+    let max_files = crate::config::get_config().batch_upload_max_files;
+
+    let mut uploaded = Vec::new();
+    let mut errors = Vec::new();
+    let mut index = 0;
+    let mut folder = String::new();
+
+    loop {
+        let mut field = match multipart.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(_) => {
+                // The rest of the body is unreadable; keep what has been stored so far
+                errors.push(BatchUploadError { index, filename: None, code: None, error: "Invalid multipart data".to_string() });
+                break;
+            }
+        };
+
+        if field.name() == Some("folder") {
+            match read_folder(field).await {
+                Ok(next) => folder = next,
+                Err(error) => {
+                    // Later parts would land in the wrong folder; stop like an unreadable body
+                    errors.push(BatchUploadError { index, filename: None, code: None, error });
+                    break;
                 }
             }
-            
-            let variants = serde_json::Value::Object(variants_map);
+            continue;
+        }
 
-            // Save to DB
-            let file = file::ActiveModel {
-                id: Set(file_id),
-                project_id: Set(project.id),
-                s3_key: Set(s3_key.clone()),
-                filename: Set(filename),
-                mime_type: Set(content_type),
-                size: Set(size),
-                status: Set("processing".to_string()), // Mark as processing for Phase 6 worker
-                variants_json: Set(variants.clone()),
-                created_at: Set(chrono::Utc::now().naive_utc()),
-                updated_at: Set(chrono::Utc::now().naive_utc()),
-            };
+        if field.name() != Some("file") {
+            continue;
+        }
 
-            let saved_file = file.insert(&db).await.map_err(AppError::DatabaseError)?;
-
-            // Create Image Processing Job
-            let job = job::ActiveModel {
-                id: Set(Uuid::new_v4()),
-                file_id: Set(saved_file.id),
-                status: Set("pending".to_string()),
-                payload: Set(serde_json::json!({
-                    "variants": project.settings.variants
-                })),
-                created_at: Set(chrono::Utc::now().naive_utc()),
-                updated_at: Set(chrono::Utc::now().naive_utc()),
-            };
+        let part_index = index;
+        index += 1;
+        let filename = client_filename(&field);
+        let content_type = field.content_type().unwrap_or("application/octet-stream").to_string();
+
+        if part_index >= max_files {
+            errors.push(BatchUploadError { index: part_index, filename, code: None, error: format!("Too many files (max {})", max_files) });
+            continue;
+        }
 
-            job.insert(&db).await.map_err(AppError::DatabaseError)?;
+        if !content_type.starts_with("image/") {
+            errors.push(BatchUploadError { index: part_index, filename, code: None, error: "File is not an image".to_string() });
+            continue;
+        }
 
-            // Construct URL
-            let config = crate::config::get_config();
-            let url = if let Some(endpoint) = &config.s3_endpoint {
-                format!("{}/{}/{}", endpoint, s3_service.bucket_name, s3_key)
-            } else {
-                format!("https://{}.s3.{}.amazonaws.com/{}", s3_service.bucket_name, config.aws_region, s3_key)
+        let data = match read_part(&mut field).await {
+            Ok(data) if data.is_empty() => {
+                errors.push(BatchUploadError { index: part_index, filename, code: None, error: "File is empty".to_string() });
+                continue;
+            }
+            Ok(data) => data,
+            Err(AppError::PayloadTooLarge(code, error)) => {
+                let whole_body = code != PART_TOO_LARGE;
+                errors.push(BatchUploadError { index: part_index, filename, code: Some(code), error });
+                // The next `next_field` skips the rest of an oversized part; a body over its limit cannot be read on
+                if whole_body {
+                    break;
+                }
+                continue;
+            }
+            Err(_) => {
+                errors.push(BatchUploadError { index: part_index, filename, code: None, error: "Failed to read file bytes".to_string() });
+                break;
+            }
+        };
+        let name = resolve_image_name(filename, content_type, &data);
+        let filename = name.filename.clone();
+
+        if let Err(AppError::UnsupportedMediaType(error)) = check_extension(&project, &filename) {
+            errors.push(BatchUploadError { index: part_index, filename: Some(filename), code: None, error });
+            continue;
+        }
+
+        match store_image(&db, s3_service.as_ref(), &project, &folder, &HashMap::new(), name, data.to_vec()).await {
+            Ok(stored) => uploaded.push(BatchImageUploadEntry {
+                index: part_index,
+                id: stored.id,
+                original_url: urls.object_url(s3_service.as_ref(), &stored.s3_key).await?,
+                s3_key: stored.s3_key,
+                job_id: stored.job_id,
+                variants: stored.variants,
+            }),
+            Err(e) => {
+                eprintln!("Upload | POST /upload/images | project={} | part={} | {}", project.name, part_index, e);
+                // Same rule as AppError::into_response: internal details stay in the logs
+                let error = match e {
+                    AppError::DatabaseError(_) | AppError::InternalServerError(_) => "Failed to store file".to_string(),
+                    other => other.to_string(),
+                };
+                errors.push(BatchUploadError { index: part_index, filename: Some(filename), code: None, error });
+            }
+        }
+    }
+
+    if index == 0 && errors.is_empty() {
+        println!("Upload | POST /upload/images | project={} | res=400 | No file field found", project.name);
+        return Err(AppError::BadRequest("No file field found".to_string()));
+    }
+
+    // Several files may be created, so there is no single Location to point at
+    let status = if uploaded.is_empty() { StatusCode::OK } else { StatusCode::CREATED };
+    println!("Upload | POST /upload/images | project={} | uploaded={} | errors={} | res={}", project.name, uploaded.len(), errors.len(), status.as_u16());
+    Ok((status, Json(BatchImageUploadResponse { uploaded, errors })))
+}
+
+/// Name, key extension and mime type an uploaded image is stored under.
+struct ImageName {
+    filename: String,
+    ext: String,
+    mime_type: String,
+}
+
+/// `code` of a `file` part over `UPLOAD_MAX_PART_BYTES`.
+const PART_TOO_LARGE: &str = "part_too_large";
+
+/// Reads a `file` part into memory, giving up with 413 as soon as it passes
+/// `UPLOAD_MAX_PART_BYTES` instead of buffering the rest.
+async fn read_part(field: &mut Field<'_>) -> Result<Vec<u8>, AppError> {
+    let max = crate::config::get_config().upload_max_part_bytes;
+    let mut data = Vec::new();
+    loop {
+        match field.chunk().await {
+            Ok(Some(chunk)) if data.len() + chunk.len() > max => {
+                return Err(AppError::PayloadTooLarge(PART_TOO_LARGE, format!("File is larger than {} bytes", max)));
+            }
+            Ok(Some(chunk)) => data.extend_from_slice(&chunk),
+            Ok(None) => return Ok(data),
+            Err(e) if e.status() == StatusCode::PAYLOAD_TOO_LARGE => {
+                return Err(AppError::PayloadTooLarge("body_too_large", "Request body is too large".to_string()));
+            }
+            Err(_) => return Err(AppError::InternalServerError("Failed to read file bytes".to_string())),
+        }
+    }
+}
+
+/// Client-supplied filename of a part, sanitized; `None` when the part has none.
+fn client_filename(field: &Field<'_>) -> Option<String> {
+    field.file_name()
+        .filter(|name| !name.trim().is_empty())
+        .map(sanitize_filename)
+}
+
+/// Trusts the bytes over the client. The key extension and mime type follow the sniffed
+/// format; the client's extension is kept only when it names the same format (`jpeg` for
+/// a JPEG). Parts without a filename are stored as `upload.<ext>`.
+fn resolve_image_name(filename: Option<String>, content_type: String, data: &[u8]) -> ImageName {
+    let client_ext = filename.as_deref().and_then(file_extension);
+
+    let (ext, mime_type) = match image::guess_format(data) {
+        Ok(format) => {
+            let ext = match client_ext {
+                Some(ext) if ImageFormat::from_extension(&ext) == Some(format) => ext,
+                _ => format.extensions_str().first().copied().unwrap_or("bin").to_string(),
             };
+            (ext, format.to_mime_type().to_string())
+        }
+        // Not a format the image crate knows (SVG, say); keep what the client said if it is key-safe
+        Err(_) => (
+            filename.as_deref().map(key_extension).unwrap_or_else(|| "bin".to_string()).to_lowercase(),
+            content_type::normalize(&content_type, filename.as_deref().unwrap_or_default(), data),
+        ),
+    };
 
-            println!("Upload | POST /upload/image | project={} | file={} | res=200", project.name, file_id);
-            return Ok(Json(ImageUploadResponse {
-                id: file_id,
-                original_url: url,
-                variants,
-            }));
+    let filename = filename.unwrap_or_else(|| format!("upload.{}", ext));
+    ImageName { filename, ext, mime_type }
+}
+
+struct StoredImage {
+    id: Uuid,
+    s3_key: String,
+    job_id: Uuid,
+    variants: serde_json::Value,
+}
+
+/// Uploads an original image and records its file row and processing job.
+///
+/// `overrides` are merged over the project's variants for this image only. The file and job
+/// are inserted in one transaction; if that fails the S3 object is removed so nothing is
+/// left untracked.
+async fn store_image(
+    db: &DatabaseConnection,
+    s3_service: &dyn Storage,
+    project: &ProjectContext,
+    folder: &str,
+    overrides: &HashMap<String, VariantConfig>,
+    name: ImageName,
+    data: Vec<u8>,
+) -> Result<StoredImage, AppError> {
+    let ImageName { filename, ext, mime_type: content_type } = name;
+    let size = data.len() as i64;
+    let content_hash = sha256_hex(&data);
+
+    let file_id = Uuid::new_v4();
+    // Format: {storage_prefix}/images/original/{file_id}.{ext}
+    let s3_key = format!("{}/images/original/{}.{}", project.storage_prefix, file_id, ext);
+
+    // Upload Original to S3
+    s3_service.put_object_as(&s3_key, data, &content_type, stored_disposition(project, &content_type, &filename)).await?;
+
+    let variants = if overrides.is_empty() {
+        project.settings.variants.clone()
+    } else {
+        let mut merged = project.settings.variants.clone().unwrap_or_default();
+        merged.extend(overrides.clone());
+        Some(merged)
+    };
+
+    // Variant keys depend on what the worker actually produces, so only
+    // report which variants are pending. The worker fills `variants_json`.
+    let mut variants_map = serde_json::Map::new();
+    for variant_name in variants.iter().flat_map(|v| v.keys()) {
+        let pending = if overrides.contains_key(variant_name) {
+            serde_json::json!({ "status": "pending", "override": true })
+        } else {
+            serde_json::json!({ "status": "pending" })
+        };
+        variants_map.insert(variant_name.clone(), pending);
+    }
+
+    let job_id = Uuid::new_v4();
+    let insert_result = db.transaction::<_, file::Model, sea_orm::DbErr>(|txn| {
+        let file = file::ActiveModel {
+            id: Set(file_id),
+            project_id: Set(project.id),
+            s3_key: Set(s3_key.clone()),
+            filename: Set(filename),
+            mime_type: Set(content_type),
+            size: Set(size),
+            status: Set("processing".to_string()), // Mark as processing for Phase 6 worker
+            variants_json: Set(serde_json::json!({})),
+            content_hash: Set(Some(content_hash)),
+            folder: Set(folder.to_string()),
+            derived_from: Set(None),
+            variants_source_hash: Set(None),
+            variant_errors: Set(serde_json::json!({})),
+            variant_dimensions: Set(serde_json::json!({})),
+            variant_hashes: Set(serde_json::json!({})),
+            created_at: Set(chrono::Utc::now()),
+            updated_at: Set(chrono::Utc::now()),
+            version: Set(1),
+            deleted_at: Set(None),
+        };
+
+        // Create Image Processing Job
+        let job = job::ActiveModel {
+            id: Set(job_id),
+            file_id: Set(Some(file_id)),
+            project_id: Set(Some(project.id)),
+            status: Set("pending".to_string()),
+            payload: Set(JobPayload::ProcessImage { variants }.to_value()),
+            created_at: Set(chrono::Utc::now()),
+            updated_at: Set(chrono::Utc::now()),
+        };
+
+        Box::pin(async move {
+            let saved_file = file.insert(txn).await?;
+            job.insert(txn).await?;
+            Ok(saved_file)
+        })
+    }).await;
+
+    let saved_file = match insert_result {
+        Ok(saved_file) => saved_file,
+        Err(e) => {
+            eprintln!("Upload | Failed to record image {}: {}", file_id, e);
+            discard_object(s3_service, &s3_key).await;
+            return Err(match e {
+                TransactionError::Connection(e) | TransactionError::Transaction(e) => AppError::DatabaseError(e),
+            });
         }
+    };
+    file_events::emit(db, &project.settings, FileEvent::Created, &saved_file);
+
+    Ok(StoredImage {
+        id: file_id,
+        s3_key,
+        job_id,
+        variants: serde_json::Value::Object(variants_map),
+    })
+}
+
+/// Best-effort removal of an object whose database row could not be written,
+/// so a failed upload leaves nothing untracked in the bucket.
+async fn discard_object(s3_service: &dyn Storage, key: &str) {
+    if let Err(e) = s3_service.delete_object(key).await {
+        eprintln!("Upload | Failed to remove orphaned object {}: {}", key, e);
     }
+}
 
-    println!("Upload | POST /upload/image | project={} | res=400 | No file field found", project.name);
-    Err(AppError::BadRequest("No file field found".to_string()))
+#[utoipa::path(
+    get,
+    path = "/admin/uploads",
+    description = "Uploads this instance is handling right now, its ceilings and how often they turned requests away \
+(superuser only), for tuning `UPLOAD_MAX_CONCURRENT` and `UPLOAD_MAX_CONCURRENT_PER_KEY`. Counters start at zero when the process starts.",
+    responses(
+        (status = 200, description = "Upload concurrency", body = UploadConcurrency),
+        (status = 403, description = "Superuser access required")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "File Upload"
+)]
+pub async fn get_upload_concurrency(
+    auth_user: Extension<AuthUser>,
+) -> Json<UploadConcurrency> {
+    let stats = upload_limit::stats();
+    println!(
+        "Upload | GET /admin/uploads | user={} | in_flight={} | peak={} | res=200",
+        auth_user.username, stats.in_flight, stats.peak
+    );
+    Json(stats)
 }
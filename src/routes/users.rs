@@ -1,12 +1,12 @@
 use axum::{
     extract::{Path, State},
-    http::StatusCode,
     response::Json,
 };
 use sea_orm::{
-    DatabaseConnection, EntityTrait, ActiveModelTrait, Set, ModelTrait, PaginatorTrait,
-    QueryOrder,
+    DatabaseConnection, EntityTrait, ActiveModelTrait, IntoActiveModel, Set, ModelTrait, PaginatorTrait,
+    QueryOrder, QuerySelect, Order, TransactionTrait,
 };
+use sea_orm::sea_query::NullOrdering;
 use serde::{Deserialize, Serialize};
 use argon2::{
     password_hash::{rand_core::OsRng, PasswordHasher, SaltString},
@@ -15,15 +15,45 @@ use argon2::{
 use crate::entities::user::{self, Entity as User};
 use crate::middleware::auth::AuthUser;
 use uuid::Uuid;
-use crate::pagination::{Pagination, PaginatedResponse};
+use crate::pagination::{PageGroup, Pagination, PaginatedResponse};
+use crate::routes::{created, Created};
 use axum::extract::Query;
 use crate::error::AppError;
+use crate::services::{audit, token_versions, tombstones};
+use crate::services::tombstones::DeletionReason;
+use crate::config::get_config;
 
 #[derive(Deserialize, utoipa::ToSchema)]
 pub struct CreateUserRequest {
+    /// Must not contain `@`, which marks an email at login
     username: String,
     password: String,
     role: UserRole,
+    /// Optional; can be used instead of the username to log in
+    #[serde(default)]
+    email: Option<String>,
+    /// For temporary passwords: the user must change it before using anything else
+    #[serde(default)]
+    must_change_password: bool,
+}
+
+/// Longest address accepted (RFC 5321 path limit).
+const MAX_EMAIL_LEN: usize = 254;
+
+/// Trims the address and checks its shape (`local@domain`, no whitespace). Case is kept;
+/// uniqueness and login ignore it.
+fn normalize_email(raw: &str) -> Result<String, String> {
+    let email = raw.trim();
+    let valid_shape = email
+        .split_once('@')
+        .is_some_and(|(local, domain)| !local.is_empty() && !domain.is_empty() && !domain.contains('@'));
+    if !valid_shape || email.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        return Err("Invalid email address".to_string());
+    }
+    if email.len() > MAX_EMAIL_LEN {
+        return Err(format!("Email is too long (max {} bytes)", MAX_EMAIL_LEN));
+    }
+    Ok(email.to_string())
 }
 
 #[derive(Deserialize, utoipa::ToSchema)]
@@ -31,6 +61,7 @@ pub struct CreateUserRequest {
 pub enum UserRole {
     Admin,
     User,
+    Viewer,
 }
 
 impl From<UserRole> for user::Role {
@@ -38,6 +69,7 @@ impl From<UserRole> for user::Role {
         match role {
             UserRole::Admin => user::Role::Admin,
             UserRole::User => user::Role::User,
+            UserRole::Viewer => user::Role::Viewer,
         }
     }
 }
@@ -47,8 +79,16 @@ pub struct UserResponse {
     #[schema(value_type = String)]
     id: Uuid,
     username: String,
+    email: Option<String>,
     role: user::Role,
-    created_at: chrono::NaiveDateTime,
+    created_at: chrono::DateTime<chrono::Utc>,
+    must_change_password: bool,
+    /// Last successful login; absent if the user never signed in, and for non-superusers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_login_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Successful logins so far; only shown to superusers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    login_count: Option<i64>,
 }
 
 impl From<user::Model> for UserResponse {
@@ -56,19 +96,50 @@ impl From<user::Model> for UserResponse {
         UserResponse {
             id: user.id,
             username: user.username,
+            email: user.email,
             role: user.role,
             created_at: user.created_at,
+            must_change_password: user.must_change_password,
+            last_login_at: user.last_login_at,
+            login_count: Some(user.login_count),
         }
     }
 }
 
+impl UserResponse {
+    /// Drops the login activity, which only superusers get to see.
+    pub fn without_login_activity(self) -> Self {
+        UserResponse { last_login_at: None, login_count: None, ..self }
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct UpdateUserRequest {
+    /// Must not contain `@`, as on creation
+    #[serde(default)]
+    username: Option<String>,
+    #[serde(default)]
+    role: Option<UserRole>,
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct ListUsersQuery {
+    pub page: Option<u64>,
+    pub limit: Option<u64>,
+    /// `created` (newest first, default) or `last_login` (longest dormant first, never signed in before everyone else)
+    pub sort: Option<String>,
+}
+
 #[utoipa::path(
     post,
     path = "/users",
     request_body = CreateUserRequest,
     responses(
-        (status = 201, description = "User created successfully", body = UserResponse),
-        (status = 409, description = "Username already exists"),
+        (status = 201, description = "User created successfully", body = UserResponse,
+            headers(("Location" = String, description = "Path of the created user"))),
+        (status = 400, description = "Username contains '@' or the email is invalid"),
+        (status = 409, description = "Username or email already exists"),
+        (status = 422, description = "Password does not meet the password policy; `violations` lists the broken rules"),
         (status = 500, description = "Internal server error")
     ),
     security(
@@ -80,8 +151,20 @@ pub async fn create_user(
     State(db): State<DatabaseConnection>,
     axum::Extension(auth_user): axum::Extension<AuthUser>,
     Json(payload): Json<CreateUserRequest>,
-) -> Result<(StatusCode, Json<UserResponse>), AppError> {
+) -> Result<Created<UserResponse>, AppError> {
+    if payload.username.contains('@') {
+        println!("User | POST /users | user={} | res=400 | Username contains '@'", auth_user.username);
+        return Err(AppError::BadRequest("Username must not contain '@'".to_string()));
+    }
+    let email = payload.email.as_deref().map(normalize_email).transpose().map_err(|e| {
+        println!("User | POST /users | user={} | res=400 | {}", auth_user.username, e);
+        AppError::BadRequest(e)
+    })?;
 
+    if let Err(e) = get_config().password_policy.check(&payload.password) {
+        println!("User | POST /users | user={} | res=422 | {}", auth_user.username, e);
+        return Err(e);
+    }
 
     // Hash password
     let salt = SaltString::generate(&mut OsRng);
@@ -100,19 +183,33 @@ pub async fn create_user(
         username: Set(payload.username),
         password: Set(password_hash),
         role: Set(payload.role.into()),
-        created_at: Set(chrono::Utc::now().naive_utc()),
-        ..Default::default()
+        created_at: Set(chrono::Utc::now()),
+        email: Set(email),
+        must_change_password: Set(payload.must_change_password),
+        totp_secret: Set(None),
+        totp_enabled_at: Set(None),
+        totp_recovery_codes: Set(serde_json::json!([])),
+        totp_last_step: Set(None),
+        last_login_at: Set(None),
+        login_count: Set(0),
+        token_version: Set(0),
     };
 
     match user.insert(&db).await {
         Ok(created_user) => {
+            audit::record_by(&db, &auth_user, "user.create", "user", Some(created_user.id), serde_json::json!({
+                "username": created_user.username,
+                "role": created_user.role,
+            })).await;
             println!("User | POST /users | user={} | created={} | res=201", auth_user.username, created_user.username);
-            Ok((StatusCode::CREATED, Json(UserResponse::from(created_user))))
+            Ok(created(format!("/users/{}", created_user.id), UserResponse::from(created_user)))
         }
         Err(e) => {
             eprintln!("Failed to create user: {}", e);
             if e.to_string().contains("duplicate key value violates unique constraint") {
-                return Err(AppError::Conflict("Username already exists".to_string()));
+                let what = if e.to_string().contains("idx_users_email_lower") { "Email" } else { "Username" };
+                println!("User | POST /users | user={} | res=409 | {} already exists", auth_user.username, what);
+                return Err(AppError::Conflict(format!("{} already exists", what)));
             }
             Err(AppError::DatabaseError(e))
         }
@@ -122,12 +219,10 @@ pub async fn create_user(
 #[utoipa::path(
     get,
     path = "/users",
-    params(
-        ("page" = Option<u64>, Query, description = "Page number"),
-        ("limit" = Option<u64>, Query, description = "Items per page")
-    ),
+    params(ListUsersQuery),
     responses(
         (status = 200, description = "List of all users", body = PaginatedResponse<UserResponse>),
+        (status = 400, description = "Invalid pagination or sort parameters"),
         (status = 500, description = "Internal server error")
     ),
     security(
@@ -138,24 +233,62 @@ pub async fn create_user(
 pub async fn list_users(
     State(db): State<DatabaseConnection>,
     _auth_user: axum::Extension<AuthUser>,
-    Query(pagination): Query<Pagination>,
+    Query(query): Query<ListUsersQuery>,
 ) -> Result<Json<PaginatedResponse<UserResponse>>, AppError> {
 
 
-    let page = pagination.page.unwrap_or(1);
-    let limit = pagination.limit.unwrap_or(10);
+    let (page, limit) = Pagination { page: query.page, limit: query.limit }.effective_in(PageGroup::Users)?;
+
+    let select = match query.sort.as_deref() {
+        None | Some("created") => User::find().order_by_desc(user::Column::CreatedAt),
+        Some("last_login") => User::find()
+            .order_by_with_nulls(user::Column::LastLoginAt, Order::Asc, NullOrdering::First)
+            .order_by_asc(user::Column::CreatedAt),
+        Some(other) => {
+            println!("User | GET /users | user={} | res=400 | Invalid sort", _auth_user.username);
+            return Err(AppError::BadRequest(format!("Invalid sort '{}', expected 'created' or 'last_login'", other)));
+        }
+    };
 
-    let paginator = User::find()
-        .order_by_desc(user::Column::CreatedAt)
-        .paginate(&db, limit);
+    let paginator = select.paginate(&db, limit);
 
     let total_items = paginator.num_items().await.map_err(AppError::DatabaseError)?;
-    let users = paginator.fetch_page(page - 1).await.map_err(AppError::DatabaseError)?;
+    let users = paginator.fetch_page(page.saturating_sub(1)).await.map_err(AppError::DatabaseError)?;
 
     let user_responses: Vec<UserResponse> = users.into_iter().map(UserResponse::from).collect();
     
     println!("User | GET /users | user={} | count={} | res=200", _auth_user.username, total_items);
-    Ok(Json(PaginatedResponse::new(user_responses, total_items, page, limit)))
+    Ok(Json(PaginatedResponse::new_in(PageGroup::Users, user_responses, total_items, page, limit)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/users/{id}",
+    params(
+        ("id" = String, Path, description = "User ID")
+    ),
+    responses(
+        (status = 200, description = "User", body = UserResponse),
+        (status = 404, description = "User not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "User Management"
+)]
+pub async fn get_user(
+    State(db): State<DatabaseConnection>,
+    axum::Extension(auth_user): axum::Extension<AuthUser>,
+    Path(user_id): Path<Uuid>,
+) -> Result<Json<UserResponse>, AppError> {
+    let Some(user) = User::find_by_id(user_id).one(&db).await? else {
+        println!("User | GET /users/{} | user={} | res=404 | User not found", user_id, auth_user.username);
+        return Err(AppError::NotFound("User not found".to_string()));
+    };
+
+    println!("User | GET /users/{} | user={} | res=200", user_id, auth_user.username);
+    Ok(Json(UserResponse::from(user)))
 }
 
 #[utoipa::path(
@@ -167,6 +300,7 @@ pub async fn list_users(
     responses(
         (status = 200, description = "User deleted successfully"),
         (status = 400, description = "Cannot delete yourself"),
+        (status = 403, description = "Not allowed with an impersonation token"),
         (status = 404, description = "User not found"),
         (status = 500, description = "Internal server error")
     ),
@@ -181,6 +315,11 @@ pub async fn delete_user(
     Path(user_id): Path<Uuid>,
 ) -> Result<Json<serde_json::Value>, AppError> {
 
+    if auth_user.impersonated_by.is_some() {
+        println!("User | DELETE /users/{} | user={} | res=403 | Impersonation token", user_id, auth_user.username);
+        return Err(AppError::Forbidden("Not allowed with an impersonation token".to_string()));
+    }
+
     // Prevent deleting self
     if auth_user.id == user_id {
         println!("User | DELETE /users/{} | user={} | res=400 | Cannot delete yourself", user_id, auth_user.username);
@@ -193,7 +332,14 @@ pub async fn delete_user(
     
     match user {
         Some(user) => {
+            let username = user.username.clone();
+            // Projects, and with them files, go by FK cascade
+            tombstones::record(&db, tombstones::owned_by(user_id), DeletionReason::OwnerDeleted).await?;
             user.delete(&db).await?;
+            crate::services::key_cache::invalidate_owner(user_id);
+            audit::record_by(&db, &auth_user, "user.delete", "user", Some(user_id), serde_json::json!({
+                "username": username,
+            })).await;
             println!("User | DELETE /users/{} | user={} | res=200", user_id, auth_user.username);
             Ok(Json(serde_json::json!({
                 "message": "User deleted successfully"
@@ -205,3 +351,206 @@ pub async fn delete_user(
         }
     }
 }
+
+#[utoipa::path(
+    patch,
+    path = "/users/{id}",
+    description = "Rename a user or change their role; projects stay with the account. \
+A role change signs the user out everywhere: their refresh tokens are revoked and their access tokens get 401, \
+so the next login issues a token with the new role.",
+    params(
+        ("id" = String, Path, description = "User ID")
+    ),
+    request_body = UpdateUserRequest,
+    responses(
+        (status = 200, description = "User updated", body = UserResponse),
+        (status = 400, description = "Username is empty or contains '@', or a superuser demoting themselves"),
+        (status = 404, description = "User not found"),
+        (status = 409, description = "Username already exists"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "User Management"
+)]
+pub async fn update_user(
+    State(db): State<DatabaseConnection>,
+    axum::Extension(auth_user): axum::Extension<AuthUser>,
+    Path(user_id): Path<Uuid>,
+    Json(payload): Json<UpdateUserRequest>,
+) -> Result<Json<UserResponse>, AppError> {
+    let username = payload.username.as_deref().map(str::trim);
+    if username.is_some_and(str::is_empty) {
+        println!("User | PATCH /users/{} | user={} | res=400 | Empty username", user_id, auth_user.username);
+        return Err(AppError::BadRequest("Username must not be empty".to_string()));
+    }
+    if username.is_some_and(|u| u.contains('@')) {
+        println!("User | PATCH /users/{} | user={} | res=400 | Username contains '@'", user_id, auth_user.username);
+        return Err(AppError::BadRequest("Username must not contain '@'".to_string()));
+    }
+
+    let Some(user) = User::find_by_id(user_id).one(&db).await? else {
+        println!("User | PATCH /users/{} | user={} | res=404 | User not found", user_id, auth_user.username);
+        return Err(AppError::NotFound("User not found".to_string()));
+    };
+
+    let role: Option<user::Role> = payload.role.map(Into::into).filter(|role| *role != user.role);
+    // Otherwise the last superuser could lock everyone out of user management
+    if auth_user.id == user_id && role.as_ref().is_some_and(|r| r.rank() < user.role.rank()) {
+        println!("User | PATCH /users/{} | user={} | res=400 | Cannot demote yourself", user_id, auth_user.username);
+        return Err(AppError::BadRequest("Cannot demote yourself".to_string()));
+    }
+    let username = username.filter(|u| *u != user.username).map(str::to_string);
+
+    let old_username = user.username.clone();
+    let old_role = user.role.clone();
+    let mut active_user = user.into_active_model();
+    if let Some(username) = &username {
+        active_user.username = Set(username.clone());
+    }
+    if let Some(role) = &role {
+        active_user.role = Set(role.clone());
+    }
+    let txn = db.begin().await?;
+    let user = match active_user.update(&txn).await {
+        Ok(user) => user,
+        Err(e) if e.to_string().contains("duplicate key value violates unique constraint") => {
+            println!("User | PATCH /users/{} | user={} | res=409 | Username already exists", user_id, auth_user.username);
+            return Err(AppError::Conflict("Username already exists".to_string()));
+        }
+        Err(e) => return Err(AppError::DatabaseError(e)),
+    };
+
+    let mut sessions_revoked = 0;
+    if role.is_some() {
+        // Refresh tokens and the access tokens they issued still carry the old role
+        sessions_revoked = super::auth::revoke_all_sessions(&txn, user_id).await?;
+    }
+    txn.commit().await?;
+    token_versions::forget(user_id);
+
+    if username.is_some() || role.is_some() {
+        audit::record_by(&db, &auth_user, "user.update", "user", Some(user_id), serde_json::json!({
+            "username": { "old": old_username, "new": user.username },
+            "role": { "old": old_role, "new": user.role },
+            "sessions_revoked": sessions_revoked,
+        })).await;
+    }
+    println!(
+        "User | PATCH /users/{} | user={} | target={} | role={:?} | sessions_revoked={} | res=200",
+        user_id, auth_user.username, user.username, user.role, sessions_revoked
+    );
+    Ok(Json(UserResponse::from(user)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/users/{id}/require-password-change",
+    description = "Flag a user so they must change their password before using any other endpoint. \
+Also signs them out everywhere: their refresh tokens are revoked and access tokens issued earlier get 401, so the \
+tokens from their next login carry the flag.",
+    params(
+        ("id" = String, Path, description = "User ID")
+    ),
+    responses(
+        (status = 200, description = "User flagged", body = UserResponse),
+        (status = 404, description = "User not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "User Management"
+)]
+pub async fn require_password_change(
+    State(db): State<DatabaseConnection>,
+    axum::Extension(auth_user): axum::Extension<AuthUser>,
+    Path(user_id): Path<Uuid>,
+) -> Result<Json<UserResponse>, AppError> {
+    let txn = db.begin().await?;
+    let Some(user) = User::find_by_id(user_id).one(&txn).await? else {
+        println!("User | POST /users/{}/require-password-change | user={} | res=404 | User not found", user_id, auth_user.username);
+        return Err(AppError::NotFound("User not found".to_string()));
+    };
+
+    let mut active_user = user.into_active_model();
+    active_user.must_change_password = Set(true);
+    let user = active_user.update(&txn).await?;
+    // Tokens issued before do not carry the flag
+    let revoked = super::auth::revoke_all_sessions(&txn, user_id).await?;
+    txn.commit().await?;
+    token_versions::forget(user_id);
+
+    audit::record_by(&db, &auth_user, "user.require_password_change", "user", Some(user_id), serde_json::json!({
+        "username": user.username,
+        "revoked_sessions": revoked,
+    })).await;
+    println!(
+        "User | POST /users/{}/require-password-change | user={} | target={} | revoked {} refresh token(s) | res=200",
+        user_id, auth_user.username, user.username, revoked
+    );
+    Ok(Json(UserResponse::from(user)))
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct RevokeTokensResponse {
+    #[schema(value_type = String)]
+    user_id: Uuid,
+    username: String,
+    /// The user's new `token_version`; access tokens carrying an older one get 401
+    token_version: i32,
+    /// Refresh tokens revoked along with them
+    revoked_sessions: u64,
+}
+
+#[utoipa::path(
+    post,
+    path = "/users/{id}/revoke-tokens",
+    description = "Sign a user out everywhere, e.g. after a compromise. Revokes all of their refresh tokens and bumps \
+their `token_version`; access tokens issued before carry the old version and get 401 from then on. Other instances \
+may accept them for up to `TOKEN_VERSION_CACHE_TTL_SECS`. The user has to log in again.",
+    params(
+        ("id" = String, Path, description = "User ID")
+    ),
+    responses(
+        (status = 200, description = "Tokens revoked", body = RevokeTokensResponse),
+        (status = 404, description = "User not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "User Management"
+)]
+pub async fn revoke_tokens(
+    State(db): State<DatabaseConnection>,
+    axum::Extension(auth_user): axum::Extension<AuthUser>,
+    Path(user_id): Path<Uuid>,
+) -> Result<Json<RevokeTokensResponse>, AppError> {
+    let txn = db.begin().await?;
+    // Locked, so the version reported is the one this call set
+    let Some(user) = User::find_by_id(user_id).lock_exclusive().one(&txn).await? else {
+        println!("User | POST /users/{}/revoke-tokens | user={} | res=404 | User not found", user_id, auth_user.username);
+        return Err(AppError::NotFound("User not found".to_string()));
+    };
+    let revoked = super::auth::revoke_all_sessions(&txn, user_id).await?;
+    let token_version = user.token_version + 1;
+    txn.commit().await?;
+    token_versions::forget(user_id);
+
+    audit::record_by(&db, &auth_user, "user.revoke_tokens", "user", Some(user_id), serde_json::json!({
+        "token_version": token_version,
+        "revoked_sessions": revoked,
+    })).await;
+    println!(
+        "User | POST /users/{}/revoke-tokens | user={} | target={} | token_version={} | revoked {} refresh token(s) | res=200",
+        user_id, auth_user.username, user.username, token_version, revoked
+    );
+    Ok(Json(RevokeTokensResponse {
+        user_id: user.id,
+        username: user.username,
+        token_version,
+        revoked_sessions: revoked,
+    }))
+}
@@ -0,0 +1,175 @@
+use axum::{
+    extract::{Path, State},
+    response::Json,
+};
+use sea_orm::{ActiveModelTrait, DatabaseConnection, EntityTrait, IntoActiveModel, Set};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::entities::project_webhook;
+use crate::error::AppError;
+use crate::middleware::auth::AuthUser;
+use crate::routes::project_storage::owned_project;
+use crate::services::webhooks;
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct ProjectWebhookRequest {
+    /// http(s) URL the project's events are POSTed to
+    pub url: String,
+    /// Replace the signing secret; a new webhook always gets one
+    #[serde(default)]
+    pub rotate_secret: bool,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct ProjectWebhookResponse {
+    pub project_id: Uuid,
+    pub url: String,
+    /// Signing secret, only returned when it was just generated
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl ProjectWebhookResponse {
+    fn new(webhook: project_webhook::Model, secret: Option<String>) -> Self {
+        Self {
+            project_id: webhook.project_id,
+            url: webhook.url,
+            secret,
+            created_at: webhook.created_at,
+            updated_at: webhook.updated_at,
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/projects/{id}/webhook",
+    description = "The URL the project's events are delivered to. The signing secret is never returned here.",
+    params(
+        ("id" = Uuid, Path, description = "Project ID")
+    ),
+    responses(
+        (status = 200, description = "Webhook", body = ProjectWebhookResponse),
+        (status = 404, description = "Project not found or it has no webhook")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Project Management"
+)]
+pub async fn get_project_webhook(
+    State(db): State<DatabaseConnection>,
+    auth_user: axum::Extension<AuthUser>,
+    Path(project_id): Path<Uuid>,
+) -> Result<Json<ProjectWebhookResponse>, AppError> {
+    let project = owned_project(&db, &auth_user, project_id).await?;
+    let webhook = project_webhook::Entity::find_by_id(project.id)
+        .one(&db)
+        .await?
+        .ok_or(AppError::NotFound("Project has no webhook".to_string()))?;
+
+    Ok(Json(ProjectWebhookResponse::new(webhook, None)))
+}
+
+#[utoipa::path(
+    put,
+    path = "/projects/{id}/webhook",
+    description = "Deliver the events listed in the project's `webhook_events` setting to `url`. \
+A new webhook, or `rotate_secret: true`, generates a signing secret that is returned once.",
+    params(
+        ("id" = Uuid, Path, description = "Project ID")
+    ),
+    request_body = ProjectWebhookRequest,
+    responses(
+        (status = 200, description = "Webhook saved", body = ProjectWebhookResponse),
+        (status = 400, description = "Not an http(s) URL, or its host is on a private address"),
+        (status = 404, description = "Project not found"),
+        (status = 503, description = "WEBHOOK_SECRET_KEY is not configured")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Project Management"
+)]
+pub async fn put_project_webhook(
+    State(db): State<DatabaseConnection>,
+    auth_user: axum::Extension<AuthUser>,
+    Path(project_id): Path<Uuid>,
+    Json(payload): Json<ProjectWebhookRequest>,
+) -> Result<Json<ProjectWebhookResponse>, AppError> {
+    let path = format!("/projects/{}/webhook", project_id);
+    let project = owned_project(&db, &auth_user, project_id).await?;
+
+    let url = payload.url.trim().to_string();
+    webhooks::check_url(&url).await.map_err(|e| {
+        println!("Project | PUT {} | user={} | res=400 | {}", path, auth_user.username, e);
+        AppError::BadRequest(e)
+    })?;
+
+    let existing = project_webhook::Entity::find_by_id(project.id).one(&db).await?;
+    let secret = (existing.is_none() || payload.rotate_secret).then(webhooks::generate_secret);
+    // Fails with 503 before anything is saved when there is no key to seal the secret with
+    let sealed = secret.as_deref().map(|s| webhooks::seal_secret(project.id, s)).transpose()?;
+
+    let now = chrono::Utc::now();
+    let saved = match existing {
+        Some(webhook) => {
+            let mut active = webhook.into_active_model();
+            active.url = Set(url);
+            if let Some(sealed) = sealed {
+                active.secret_sealed = Set(sealed);
+            }
+            active.updated_at = Set(now);
+            active.update(&db).await?
+        }
+        None => {
+            project_webhook::ActiveModel {
+                project_id: Set(project.id),
+                url: Set(url),
+                secret_sealed: Set(sealed.unwrap_or_default()),
+                created_at: Set(now),
+                updated_at: Set(now),
+            }
+            .insert(&db)
+            .await?
+        }
+    };
+
+    println!("Project | PUT {} | user={} | rotated={} | res=200", path, auth_user.username, secret.is_some());
+    Ok(Json(ProjectWebhookResponse::new(saved, secret)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/projects/{id}/webhook",
+    description = "Stop delivering the project's events. Events still queued are dropped.",
+    params(
+        ("id" = Uuid, Path, description = "Project ID")
+    ),
+    responses(
+        (status = 200, description = "Webhook removed"),
+        (status = 404, description = "Project not found or it has no webhook")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Project Management"
+)]
+pub async fn delete_project_webhook(
+    State(db): State<DatabaseConnection>,
+    auth_user: axum::Extension<AuthUser>,
+    Path(project_id): Path<Uuid>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let project = owned_project(&db, &auth_user, project_id).await?;
+
+    let result = project_webhook::Entity::delete_by_id(project.id).exec(&db).await?;
+    if result.rows_affected == 0 {
+        return Err(AppError::NotFound("Project has no webhook".to_string()));
+    }
+
+    println!("Project | DELETE /projects/{}/webhook | user={} | res=200", project_id, auth_user.username);
+    Ok(Json(serde_json::json!({ "message": "Project webhook removed" })))
+}
@@ -0,0 +1,113 @@
+use axum::{
+    response::Json,
+    Extension,
+};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::entities::api_key::ApiKeyScope;
+use crate::middleware::api_key::ProjectContext;
+use crate::pagination::PageGroup;
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct WhoamiResponse {
+    #[schema(value_type = String)]
+    project_id: Uuid,
+    project_name: String,
+    /// Variant names generated for uploaded images, sorted
+    variants: Vec<String>,
+    allowed_extensions: Option<Vec<String>>,
+    blocked_extensions: Option<Vec<String>>,
+    api_key: WhoamiApiKey,
+    limits: WhoamiLimits,
+    retention: WhoamiRetention,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct WhoamiApiKey {
+    #[schema(value_type = String)]
+    id: Uuid,
+    name: String,
+    expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Permissions granted beyond uploading
+    scopes: Vec<ApiKeyScope>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct WhoamiLimits {
+    /// Max `file` parts per `POST /upload/images`
+    batch_upload_max_files: usize,
+    /// Max variants in a `variants` override on `POST /upload/image`
+    upload_override_max_variants: usize,
+    /// Largest pixel size an override variant may request
+    upload_override_max_dimension: u32,
+    /// Max `limit` on `GET /jobs`
+    pagination_max_limit: u64,
+}
+
+/// Effective retention for the project: its `retention` settings, else the server defaults.
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct WhoamiRetention {
+    /// Days the project is kept after being deleted, before it is purged
+    project_trash_days: i64,
+    /// Days a deleted file stays in the trash before it is purged (0 = deleted right away)
+    file_trash_days: i64,
+    /// Days finished jobs are kept (0 = kept forever)
+    job_history_days: i64,
+}
+
+#[utoipa::path(
+    get,
+    path = "/whoami",
+    description = "Describe the project and API key this request is authenticated as, with the settings and limits that apply to it.",
+    responses(
+        (status = 200, description = "Project and key context", body = WhoamiResponse),
+        (status = 401, description = "Missing, invalid, inactive or expired API key"),
+        (status = 403, description = "Project is deleted")
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Project API Keys"
+)]
+pub async fn whoami(
+    Extension(project): Extension<ProjectContext>,
+) -> Json<WhoamiResponse> {
+    let config = crate::config::get_config();
+
+    let mut variants: Vec<String> = project
+        .settings
+        .variants
+        .as_ref()
+        .map(|v| v.keys().cloned().collect())
+        .unwrap_or_default();
+    variants.sort();
+
+    let retention = WhoamiRetention {
+        project_trash_days: project.settings.project_trash_days(),
+        file_trash_days: project.settings.file_trash_days(),
+        job_history_days: project.settings.job_history_days(),
+    };
+
+    println!("Whoami | GET /whoami | project={} | key={} | res=200", project.name, project.api_key_name);
+    Json(WhoamiResponse {
+        project_id: project.id,
+        project_name: project.name,
+        variants,
+        allowed_extensions: project.settings.allowed_extensions,
+        blocked_extensions: project.settings.blocked_extensions,
+        api_key: WhoamiApiKey {
+            id: project.api_key_id,
+            name: project.api_key_name,
+            expires_at: project.api_key_expires_at,
+            scopes: project.api_key_scopes,
+        },
+        limits: WhoamiLimits {
+            batch_upload_max_files: config.batch_upload_max_files,
+            upload_override_max_variants: config.upload_override_max_variants,
+            upload_override_max_dimension: config.upload_override_max_dimension,
+            pagination_max_limit: config.pagination.limits(PageGroup::Jobs).max_limit,
+        },
+        retention,
+    })
+}
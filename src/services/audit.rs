@@ -0,0 +1,48 @@
+//! Audit trail of authentication and admin events (`audit_logs`, read through `GET /admin/audit`).
+
+use sea_orm::{ActiveModelTrait, DatabaseConnection, Set};
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::entities::audit_log;
+use crate::middleware::auth::AuthUser;
+
+/// Stores one event. Best-effort: a failed insert is logged and the request carries on.
+pub async fn record(
+    db: &DatabaseConnection,
+    actor_user_id: Option<Uuid>,
+    action: &str,
+    target_type: &str,
+    target_id: Option<Uuid>,
+    metadata: Value,
+) {
+    let entry = audit_log::ActiveModel {
+        actor_user_id: Set(actor_user_id),
+        action: Set(action.to_string()),
+        target_type: Set(target_type.to_string()),
+        target_id: Set(target_id),
+        metadata: Set(metadata),
+        created_at: Set(chrono::Utc::now()),
+        ..Default::default()
+    };
+
+    if let Err(e) = entry.insert(db).await {
+        eprintln!("Audit | Failed to record {} on {} {:?}: {}", action, target_type, target_id, e);
+    }
+}
+
+/// `record` for an action taken by an authenticated user; impersonated requests also
+/// keep the superuser behind them under `metadata.impersonated_by`.
+pub async fn record_by(
+    db: &DatabaseConnection,
+    actor: &AuthUser,
+    action: &str,
+    target_type: &str,
+    target_id: Option<Uuid>,
+    mut metadata: Value,
+) {
+    if let (Some(impersonator), Some(fields)) = (actor.impersonated_by, metadata.as_object_mut()) {
+        fields.insert("impersonated_by".to_string(), Value::String(impersonator.to_string()));
+    }
+    record(db, Some(actor.id), action, target_type, target_id, metadata).await;
+}
@@ -0,0 +1,151 @@
+use std::collections::hash_map::{Entry, HashMap};
+use std::sync::Arc;
+use std::time::Duration;
+
+use sea_orm::sea_query::{Expr, SimpleExpr};
+use sea_orm::{ColumnTrait, Condition, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder, QuerySelect};
+use tokio::time::Instant;
+use uuid::Uuid;
+
+use crate::entities::{file, job};
+use crate::error::AppError;
+use crate::models::job::{BackfillAttribute, BackfillProgress, JobPayload};
+use crate::services::{project_storage, variant_keys};
+use crate::services::storage::Storage;
+use crate::services::worker::JobError;
+use crate::utils::sha256_hex;
+
+/// Files fetched per page; progress is saved after each.
+const PAGE_SIZE: u64 = 100;
+
+impl BackfillAttribute {
+    /// Files that still need this attribute.
+    fn missing(self) -> SimpleExpr {
+        match self {
+            BackfillAttribute::ContentHash => file::Column::ContentHash.is_null(),
+            BackfillAttribute::VariantKeys => variant_keys::has_legacy_entries(),
+        }
+    }
+}
+
+/// Active files missing at least one of `attributes`, in id order.
+pub fn candidates(attributes: &[BackfillAttribute], project_id: Option<Uuid>) -> sea_orm::Select<file::Entity> {
+    let missing = attributes
+        .iter()
+        .fold(Condition::any(), |condition, attribute| condition.add(attribute.missing()));
+
+    let mut query = file::Entity::find_active().filter(missing);
+    if let Some(project_id) = project_id {
+        query = query.filter(file::Column::ProjectId.eq(project_id));
+    }
+    query.order_by_asc(file::Column::Id)
+}
+
+/// Runs a `Backfill` job from `progress` onwards.
+///
+/// Each original is downloaded once and every requested attribute is computed from it;
+/// `variant_keys` alone needs no download. Downloads are throttled to `BACKFILL_READS_PER_SEC`.
+/// A storage outage aborts the run with the progress saved, so the requeued job picks up
+/// where it stopped.
+pub async fn run(
+    db: &DatabaseConnection,
+    job_id: Uuid,
+    attributes: &[BackfillAttribute],
+    project_id: Option<Uuid>,
+    mut progress: BackfillProgress,
+) -> Result<BackfillProgress, JobError> {
+    if attributes.is_empty() {
+        return Err("No attributes to backfill".into());
+    }
+
+    let reads_per_sec = crate::config::get_config().backfill_reads_per_sec;
+    let read_interval = (reads_per_sec > 0).then(|| Duration::from_secs(1) / reads_per_sec);
+    let mut next_read = Instant::now();
+    let mut storages: HashMap<Uuid, Arc<dyn Storage>> = HashMap::new();
+
+    loop {
+        let mut query = candidates(attributes, project_id);
+        if let Some(last) = progress.last_file_id {
+            query = query.filter(file::Column::Id.gt(last));
+        }
+        let files = query.limit(PAGE_SIZE).all(db).await.map_err(|e| e.to_string())?;
+        if files.is_empty() {
+            break;
+        }
+
+        for f in files {
+            let storage = match storages.entry(f.project_id) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => entry.insert(project_storage::for_project(db, f.project_id).await?),
+            };
+
+            let mut update = file::Entity::update_many().filter(file::Column::Id.eq(f.id));
+            let mut changed = false;
+
+            if attributes.contains(&BackfillAttribute::VariantKeys) {
+                if let Some(variants) = variant_keys::rewrite(&f.variants_json, storage.bucket_name()) {
+                    update = update.col_expr(file::Column::VariantsJson, Expr::value(variants));
+                    changed = true;
+                }
+            }
+
+            if attributes.contains(&BackfillAttribute::ContentHash) && f.content_hash.is_none() {
+                if let Some(interval) = read_interval {
+                    tokio::time::sleep_until(next_read).await;
+                    next_read = Instant::now() + interval;
+                }
+
+                match storage.get_object(&f.s3_key).await {
+                    Ok(data) => {
+                        update = update.col_expr(file::Column::ContentHash, Expr::value(sha256_hex(&data)));
+                        changed = true;
+                    }
+                    Err(AppError::ServiceUnavailable(e)) => {
+                        save_progress(db, job_id, attributes, project_id, &progress).await?;
+                        return Err(JobError::StorageUnavailable(e));
+                    }
+                    Err(e) => {
+                        eprintln!("Backfill | job={} | file={} | {}", job_id, f.id, e);
+                        progress.failed += 1;
+                    }
+                }
+            }
+
+            if changed {
+                update.exec(db).await.map_err(|e| e.to_string())?;
+                progress.updated += 1;
+            }
+
+            progress.processed += 1;
+            progress.last_file_id = Some(f.id);
+        }
+
+        save_progress(db, job_id, attributes, project_id, &progress).await?;
+    }
+
+    println!("Backfill | job={} | processed={} | updated={} | failed={}", job_id, progress.processed, progress.updated, progress.failed);
+    Ok(progress)
+}
+
+async fn save_progress(
+    db: &DatabaseConnection,
+    job_id: Uuid,
+    attributes: &[BackfillAttribute],
+    project_id: Option<Uuid>,
+    progress: &BackfillProgress,
+) -> Result<(), String> {
+    let payload = JobPayload::Backfill {
+        attributes: attributes.to_vec(),
+        project_id,
+        progress: progress.clone(),
+    };
+
+    job::Entity::update_many()
+        .col_expr(job::Column::Payload, Expr::value(payload.to_value()))
+        .col_expr(job::Column::UpdatedAt, Expr::value(chrono::Utc::now()))
+        .filter(job::Column::Id.eq(job_id))
+        .exec(db)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
@@ -0,0 +1,70 @@
+use std::future::Future;
+use std::time::Duration;
+
+use sea_orm::{ActiveModelTrait, DatabaseConnection, EntityTrait};
+use tokio::sync::mpsc;
+
+/// How a [`BatchWriter`] buffers and flushes.
+#[derive(Clone, Copy)]
+pub struct Batching {
+    /// Items buffered before new ones are dropped
+    pub capacity: usize,
+    /// Items handed to one flush
+    pub max_batch: usize,
+    /// Pause between flushes so items accumulate into batches
+    pub interval: Duration,
+}
+
+/// Best-effort, fire-and-forget buffer in front of the database.
+///
+/// `send` never waits: items go through a bounded channel to a background task that hands
+/// them to `flush` in batches. When the channel is full, items are discarded. The recorders
+/// for job events, request logs, key failures and login attempts are all built on this.
+pub struct BatchWriter<T> {
+    tx: mpsc::Sender<T>,
+}
+
+impl<T> Clone for BatchWriter<T> {
+    fn clone(&self) -> Self {
+        Self { tx: self.tx.clone() }
+    }
+}
+
+impl<T: Send + 'static> BatchWriter<T> {
+    /// Spawns the flush task.
+    pub fn spawn<F, Fut>(batching: Batching, mut flush: F) -> Self
+    where
+        F: FnMut(Vec<T>) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send,
+    {
+        let (tx, mut rx) = mpsc::channel(batching.capacity);
+        tokio::spawn(async move {
+            let mut batch = Vec::with_capacity(batching.max_batch);
+            while rx.recv_many(&mut batch, batching.max_batch).await > 0 {
+                flush(std::mem::take(&mut batch)).await;
+                tokio::time::sleep(batching.interval).await;
+            }
+        });
+        Self { tx }
+    }
+
+    pub fn send(&self, item: T) {
+        let _ = self.tx.try_send(item);
+    }
+}
+
+impl<A: ActiveModelTrait + Send + 'static> BatchWriter<A> {
+    /// Inserts each batch with one `INSERT`. A failed insert loses the whole batch and is
+    /// logged under `label`.
+    pub fn inserting(db: DatabaseConnection, batching: Batching, label: &'static str) -> Self {
+        Self::spawn(batching, move |batch: Vec<A>| {
+            let db = db.clone();
+            async move {
+                let count = batch.len();
+                if let Err(e) = A::Entity::insert_many(batch).exec(&db).await {
+                    eprintln!("{} | failed to write {} entries: {}", label, count, e);
+                }
+            }
+        })
+    }
+}
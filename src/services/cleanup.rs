@@ -1,8 +1,13 @@
-use sea_orm::{DatabaseConnection, EntityTrait, QueryFilter, ColumnTrait};
-use crate::entities::{project, file};
-use crate::services::s3::S3Service;
+use sea_orm::{ColumnTrait, Condition, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder, QuerySelect};
+use sea_orm::sea_query::Expr;
+use crate::entities::{api_key, login_attempt, password_reset_token, project, file, job, refresh_token, request_log};
+use crate::models::settings::ProjectSettings;
+use crate::services::{key_cache, project_storage, reconcile, tombstones, variant_keys, webhooks};
+use crate::services::storage::Storage;
+use crate::services::tombstones::DeletionReason;
 use std::time::Duration;
 use chrono::Utc;
+use uuid::Uuid;
 
 pub struct CleanupService {
     db: DatabaseConnection,
@@ -27,18 +32,267 @@ impl CleanupService {
             if let Err(e) = self.clean_soft_deleted_projects().await {
                 eprintln!("Cleanup Scheduler | Error cleaning projects: {}", e);
             }
+
+            if let Err(e) = self.purge_trashed_files().await {
+                eprintln!("Cleanup Scheduler | Error purging trashed files: {}", e);
+            }
+
+            if let Err(e) = self.notify_expiring_api_keys().await {
+                eprintln!("Cleanup Scheduler | Error checking expiring API keys: {}", e);
+            }
+
+            if let Err(e) = self.prune_request_logs().await {
+                eprintln!("Cleanup Scheduler | Error pruning request logs: {}", e);
+            }
+
+            if let Err(e) = self.prune_login_attempts().await {
+                eprintln!("Cleanup Scheduler | Error pruning login attempts: {}", e);
+            }
+
+            if let Err(e) = self.prune_file_tombstones().await {
+                eprintln!("Cleanup Scheduler | Error pruning file tombstones: {}", e);
+            }
+
+            if let Err(e) = self.prune_job_history().await {
+                eprintln!("Cleanup Scheduler | Error pruning job history: {}", e);
+            }
+
+            if let Err(e) = self.clean_refresh_tokens().await {
+                eprintln!("Cleanup Scheduler | Error cleaning refresh tokens: {}", e);
+            }
+
+            if let Err(e) = self.prune_password_reset_tokens().await {
+                eprintln!("Cleanup Scheduler | Error pruning password reset tokens: {}", e);
+            }
+
+            if let Err(e) = self.schedule_storage_reconciliation().await {
+                eprintln!("Cleanup Scheduler | Error scheduling storage reconciliation: {}", e);
+            }
         }
     }
 
+    /// Deletes finished jobs (and, by cascade, their events) past each project's `job_history_days`.
+    /// Pending and processing jobs are never touched.
+    pub async fn prune_job_history(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let now = Utc::now();
+
+        for p in project::Entity::find().all(&self.db).await? {
+            let retention_days = project_settings(&p).job_history_days();
+            if retention_days == 0 {
+                continue;
+            }
+            let threshold = now - chrono::Duration::days(retention_days);
+
+            let result = job::Entity::delete_many()
+                .filter(job::Column::Status.is_in(["completed", "failed"]))
+                .filter(job::Column::UpdatedAt.lt(threshold))
+                .filter(job::Column::ProjectId.eq(p.id))
+                .exec(&self.db)
+                .await?;
+
+            if result.rows_affected > 0 {
+                println!(
+                    "Cleanup Scheduler | Pruned {} jobs older than {} days | project={} ({})",
+                    result.rows_affected, retention_days, p.name, p.id
+                );
+            }
+        }
+
+        // Backfills across all projects follow JOB_HISTORY_DAYS
+        let retention_days = crate::config::get_config().job_history_days;
+        if retention_days > 0 {
+            let result = job::Entity::delete_many()
+                .filter(job::Column::Status.is_in(["completed", "failed"]))
+                .filter(job::Column::UpdatedAt.lt(now - chrono::Duration::days(retention_days)))
+                .filter(job::Column::ProjectId.is_null())
+                .exec(&self.db)
+                .await?;
+            if result.rows_affected > 0 {
+                println!(
+                    "Cleanup Scheduler | Pruned {} jobs older than {} days | project=none",
+                    result.rows_affected, retention_days
+                );
+            }
+        }
+        Ok(())
+    }
+
+    async fn prune_request_logs(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let retention_days = crate::config::get_config().request_log_retention_days;
+        let threshold = Utc::now() - chrono::Duration::days(retention_days);
+
+        let result = request_log::Entity::delete_many()
+            .filter(request_log::Column::CreatedAt.lt(threshold))
+            .exec(&self.db)
+            .await?;
+
+        if result.rows_affected > 0 {
+            println!("Cleanup Scheduler | Pruned {} request logs older than {} days", result.rows_affected, retention_days);
+        }
+        Ok(())
+    }
+
+    async fn prune_login_attempts(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let retention_days = crate::config::get_config().login_attempt_retention_days;
+        let threshold = Utc::now() - chrono::Duration::days(retention_days);
+
+        let result = login_attempt::Entity::delete_many()
+            .filter(login_attempt::Column::CreatedAt.lt(threshold))
+            .exec(&self.db)
+            .await?;
+
+        if result.rows_affected > 0 {
+            println!("Cleanup Scheduler | Pruned {} login attempts older than {} days", result.rows_affected, retention_days);
+        }
+        Ok(())
+    }
+
+    async fn prune_file_tombstones(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let retention_days = crate::config::get_config().file_tombstone_retention_days;
+        if retention_days == 0 {
+            return Ok(());
+        }
+
+        let pruned = tombstones::prune(&self.db, retention_days).await?;
+        if pruned > 0 {
+            println!("Cleanup Scheduler | Pruned {} file tombstones older than {} days", pruned, retention_days);
+        }
+        Ok(())
+    }
+
+    /// Queues a `reconcile_storage` job for live projects not reconciled in `RECONCILE_INTERVAL_DAYS`.
+    async fn schedule_storage_reconciliation(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let interval_days = crate::config::get_config().reconcile_interval_days;
+        if interval_days == 0 {
+            return Ok(());
+        }
+        let threshold = Utc::now() - chrono::Duration::days(interval_days);
+
+        let due = project::Entity::find()
+            .filter(project::Column::DeletedAt.is_null())
+            .filter(
+                sea_orm::Condition::any()
+                    .add(project::Column::LastReconciledAt.is_null())
+                    .add(project::Column::LastReconciledAt.lt(threshold)),
+            )
+            .all(&self.db)
+            .await?;
+
+        for p in &due {
+            reconcile::enqueue(&self.db, p.id).await.map_err(|e| e.to_string())?;
+        }
+        if !due.is_empty() {
+            println!("Cleanup Scheduler | Queued storage reconciliation for {} project(s)", due.len());
+        }
+        Ok(())
+    }
+
+    /// Deletes refresh tokens that expired, or were revoked, more than `REFRESH_TOKEN_GRACE_DAYS` ago.
+    /// Revocation time is not recorded, so revoked tokens age from `created_at`. Returns the
+    /// number of rows removed.
+    pub async fn clean_refresh_tokens(&self) -> Result<u64, Box<dyn std::error::Error>> {
+        let grace_days = crate::config::get_config().refresh_token_grace_days;
+        let threshold = Utc::now() - chrono::Duration::days(grace_days);
+
+        let result = refresh_token::Entity::delete_many()
+            .filter(
+                sea_orm::Condition::any()
+                    .add(refresh_token::Column::ExpiresAt.lt(threshold))
+                    .add(
+                        sea_orm::Condition::all()
+                            .add(refresh_token::Column::Revoked.eq(true))
+                            .add(refresh_token::Column::CreatedAt.lt(threshold)),
+                    ),
+            )
+            .exec(&self.db)
+            .await?;
+
+        println!(
+            "Cleanup Scheduler | Removed {} refresh tokens expired or revoked over {} days ago",
+            result.rows_affected, grace_days
+        );
+        Ok(result.rows_affected)
+    }
+
+    /// Used and expired reset tokens can never be redeemed again.
+    async fn prune_password_reset_tokens(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let result = password_reset_token::Entity::delete_many()
+            .filter(
+                sea_orm::Condition::any()
+                    .add(password_reset_token::Column::Used.eq(true))
+                    .add(password_reset_token::Column::ExpiresAt.lt(Utc::now())),
+            )
+            .exec(&self.db)
+            .await?;
+
+        if result.rows_affected > 0 {
+            println!("Cleanup Scheduler | Pruned {} used or expired password reset tokens", result.rows_affected);
+        }
+        Ok(())
+    }
+
+    /// Sends `api_key.expiring` for keys that came within `API_KEY_EXPIRY_NOTICE_DAYS` of their
+    /// expiry, to projects subscribed to it, and logs it for every project.
+    pub async fn notify_expiring_api_keys(&self) -> Result<(), Box<dyn std::error::Error>> {
+        // Each key is announced once when it enters the window; `expiry_notified_at` prevents
+        // repeats until the key is given another `expires_at`
+        let within_days = crate::config::get_config().api_key_expiry_notice_days;
+        let keys: Vec<_> = find_expiring_keys(&self.db, within_days)
+            .await?
+            .into_iter()
+            .filter(|(k, _)| k.expiry_notified_at.is_none())
+            .collect();
+        if keys.is_empty() {
+            return Ok(());
+        }
+
+        // Stamped first, so a failed update sends nothing rather than sending twice
+        api_key::Entity::update_many()
+            .col_expr(api_key::Column::ExpiryNotifiedAt, Expr::value(Utc::now()))
+            .filter(api_key::Column::Id.is_in(keys.iter().map(|(k, _)| k.id)))
+            .exec(&self.db)
+            .await?;
+
+        for (key, p) in keys {
+            println!(
+                "Cleanup Scheduler | api_key.expiring | project={} ({}) | key={} ({}) | expires_at={}",
+                p.name,
+                p.id,
+                key.name,
+                key.id,
+                key.expires_at.map(|t| t.to_string()).unwrap_or_default()
+            );
+            if project_settings(&p).webhook_events.iter().any(|e| e == webhooks::API_KEY_EXPIRING) {
+                let body = serde_json::json!({
+                    "event": webhooks::API_KEY_EXPIRING,
+                    "project_id": p.id,
+                    "project_name": p.name,
+                    "key_id": key.id,
+                    "key_name": key.name,
+                    "expires_at": key.expires_at,
+                });
+                webhooks::enqueue(&self.db, p.id, webhooks::API_KEY_EXPIRING, body.to_string());
+            }
+        }
+
+        Ok(())
+    }
+
     async fn clean_soft_deleted_projects(&self) -> Result<(), Box<dyn std::error::Error>> {
-        // Logic: Find projects deleted > 30 days ago
-        let threshold = Utc::now().naive_utc() - chrono::Duration::days(30);
+        // Each project may keep its trash longer or shorter than `PROJECT_TRASH_DAYS`, so the
+        // window is applied per project rather than in the query
+        let now = Utc::now();
 
-        let projects_to_delete = project::Entity::find()
+        let projects_to_delete: Vec<project::Model> = project::Entity::find()
             .filter(project::Column::DeletedAt.is_not_null())
-            .filter(project::Column::DeletedAt.lt(threshold))
             .all(&self.db)
-            .await?;
+            .await?
+            .into_iter()
+            .filter(|p| {
+                let threshold = now - chrono::Duration::days(project_settings(p).project_trash_days());
+                p.deleted_at.is_some_and(|deleted_at| deleted_at < threshold)
+            })
+            .collect();
 
         if projects_to_delete.is_empty() {
              return Ok(());
@@ -46,10 +300,16 @@ impl CleanupService {
 
         println!("Cleanup Scheduler | Found {} projects to hard delete", projects_to_delete.len());
 
-        let s3_service = S3Service::new().await;
-
         for p in projects_to_delete {
             println!("Cleanup Scheduler | Hard deleting project: {} ({})", p.name, p.id);
+            let s3_service = match project_storage::for_project(&self.db, p.id).await {
+                Ok(s3_service) => s3_service,
+                Err(e) => {
+                    // Purging the rows without the objects would orphan them; retry next run
+                    eprintln!("Cleanup Scheduler | Skipping project {}: {}", p.id, e);
+                    continue;
+                }
+            };
             
             // 1. Find Files
             let files = file::Entity::find()
@@ -58,39 +318,104 @@ impl CleanupService {
                 .await?;
 
             // 2. Delete S3 Objects
-            for f in files {
-                // Delete Original
-                let _ = s3_service.delete_object(&f.s3_key).await;
-
-                // Delete Variants
-                if let Some(variants) = f.variants_json.as_object() {
-                    for (_v_name, v_path) in variants {
-                        if let Some(v_str) = v_path.as_str() {
-                            // Extract Key logic (Duplicate from routes/projects.rs - TODO: Shared Helper)
-                            // Ideally we would have `S3Service::delete_from_url_or_key` or similar.
-                             let config = crate::config::get_config();
-                             let bucket = &config.s3_bucket_name;
-                             
-                             let key_to_delete = if let Some(idx) = v_str.find(&format!("/{}/", bucket)) {
-                                  Some(v_str[idx + bucket.len() + 2..].to_string())
-                             } else if let Ok(url) = url::Url::parse(v_str) {
-                                  Some(url.path().trim_start_matches('/').to_string())
-                             } else {
-                                 None
-                             };
-                             
-                             if let Some(k) = key_to_delete {
-                                 let _ = s3_service.delete_object(&k).await;
-                             }
-                        }
-                    }
-                }
+            for f in &files {
+                delete_objects(s3_service.as_ref(), f).await;
             }
 
             // 3. Delete Project from DB
+            tombstones::record(&self.db, tombstones::in_project(p.id), DeletionReason::ProjectPurged).await?;
             project::Entity::delete_by_id(p.id).exec(&self.db).await?;
+            project_storage::invalidate(p.id);
+            key_cache::invalidate_project(p.id);
+        }
+
+        Ok(())
+    }
+
+    /// Purges files that have been in the trash longer than their project's
+    /// `file_trash_days`: objects first, then the rows, leaving `file_deleted` tombstones.
+    pub async fn purge_trashed_files(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let now = Utc::now();
+
+        let project_ids: Vec<Uuid> = file::Entity::find()
+            .select_only()
+            .column(file::Column::ProjectId)
+            .distinct()
+            .filter(file::Column::DeletedAt.is_not_null())
+            .into_tuple()
+            .all(&self.db)
+            .await?;
+        if project_ids.is_empty() {
+            return Ok(());
+        }
+
+        let projects = project::Entity::find()
+            .filter(project::Column::Id.is_in(project_ids))
+            .all(&self.db)
+            .await?;
+        for p in projects {
+            let threshold = now - chrono::Duration::days(project_settings(&p).file_trash_days());
+            let files = file::Entity::find()
+                .filter(file::Column::ProjectId.eq(p.id))
+                .filter(file::Column::DeletedAt.lt(threshold))
+                .all(&self.db)
+                .await?;
+            if files.is_empty() {
+                continue;
+            }
+
+            let s3_service = match project_storage::for_project(&self.db, p.id).await {
+                Ok(s3_service) => s3_service,
+                Err(e) => {
+                    // Purging the rows without the objects would orphan them; retry next run
+                    eprintln!("Cleanup Scheduler | Skipping trashed files of project {}: {}", p.id, e);
+                    continue;
+                }
+            };
+            for f in &files {
+                delete_objects(s3_service.as_ref(), f).await;
+            }
+
+            let ids: Vec<Uuid> = files.iter().map(|f| f.id).collect();
+            tombstones::record(&self.db, Condition::all().add(file::Column::Id.is_in(ids.clone())), DeletionReason::FileDeleted).await?;
+            file::Entity::delete_many().filter(file::Column::Id.is_in(ids)).exec(&self.db).await?;
+            println!("Cleanup Scheduler | Purged {} trashed files of project {} ({})", files.len(), p.name, p.id);
         }
 
         Ok(())
     }
 }
+
+/// Best-effort removal of a file's original and variant objects.
+async fn delete_objects(s3_service: &dyn Storage, f: &file::Model) {
+    let _ = s3_service.delete_object(&f.s3_key).await;
+    for key in variant_keys::all(f.id, &f.variants_json, s3_service.bucket_name()) {
+        let _ = s3_service.delete_object(&key).await;
+    }
+}
+
+/// Stored settings that fail to parse fall back to the defaults, as they do for uploads.
+fn project_settings(p: &project::Model) -> ProjectSettings {
+    serde_json::from_value(p.settings.clone()).unwrap_or_default()
+}
+
+/// Active keys on live projects whose `expires_at` falls between now and `within_days` from now.
+pub async fn find_expiring_keys(
+    db: &DatabaseConnection,
+    within_days: i64,
+) -> Result<Vec<(api_key::Model, project::Model)>, sea_orm::DbErr> {
+    let now = chrono::Utc::now();
+    let until = now + chrono::Duration::days(within_days);
+
+    let keys = api_key::Entity::find()
+        .find_also_related(project::Entity)
+        .filter(api_key::Column::IsActive.eq(true))
+        .filter(api_key::Column::ExpiresAt.gt(now))
+        .filter(api_key::Column::ExpiresAt.lte(until))
+        .filter(project::Column::DeletedAt.is_null())
+        .order_by_asc(api_key::Column::ExpiresAt)
+        .all(db)
+        .await?;
+
+    Ok(keys.into_iter().filter_map(|(k, p)| p.map(|p| (k, p))).collect())
+}
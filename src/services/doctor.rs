@@ -0,0 +1,262 @@
+use std::path::Path;
+
+use migration::{Migrator, MigratorTrait};
+use sea_orm::Database;
+use serde::Serialize;
+
+use crate::config::{self, Config, JwtAlgorithm};
+use crate::services::jwt;
+use crate::services::s3::{BucketDiagnostics, S3Service};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+impl CheckStatus {
+    fn label(self) -> &'static str {
+        match self {
+            CheckStatus::Pass => "PASS",
+            CheckStatus::Warn => "WARN",
+            CheckStatus::Fail => "FAIL",
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Check {
+    pub name: String,
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct DoctorReport {
+    pub checks: Vec<Check>,
+    pub warnings: usize,
+    pub failures: usize,
+}
+
+impl DoctorReport {
+    fn push(&mut self, name: &str, status: CheckStatus, detail: impl Into<String>) {
+        match status {
+            CheckStatus::Warn => self.warnings += 1,
+            CheckStatus::Fail => self.failures += 1,
+            CheckStatus::Pass => {}
+        }
+        self.checks.push(Check { name: name.to_string(), status, detail: detail.into() });
+    }
+
+    /// One `STATUS  check  detail` line per check, then a summary line.
+    pub fn print_table(&self) {
+        let width = self.checks.iter().map(|c| c.name.len()).max().unwrap_or(0);
+        for check in &self.checks {
+            println!("{}  {:width$}  {}", check.status.label(), check.name, check.detail, width = width);
+        }
+        println!("Doctor | {} checks | {} warnings | {} failures", self.checks.len(), self.warnings, self.failures);
+    }
+}
+
+/// Runs every deployment check without changing anything beyond short-lived probe objects
+/// in the global bucket (the same ones `GET /admin/storage/diagnostics` writes).
+///
+/// Later checks need the configuration, so a config that does not load ends the run.
+pub async fn run() -> DoctorReport {
+    let mut report = DoctorReport::default();
+
+    let config = match load_config() {
+        Ok(config) => config,
+        Err(reason) => {
+            report.push("config", CheckStatus::Fail, reason);
+            return report;
+        }
+    };
+    // `from_env` only insists these are set, so an empty value still loads
+    let empty: Vec<&str> = [
+        ("DATABASE_URL", &config.database_url),
+        ("AWS_REGION", &config.aws_region),
+        ("AWS_ACCESS_KEY_ID", &config.aws_access_key_id),
+        ("AWS_SECRET_ACCESS_KEY", &config.aws_secret_access_key),
+        ("S3_BUCKET_NAME", &config.s3_bucket_name),
+    ]
+    .into_iter()
+    .filter(|(_, value)| value.trim().is_empty())
+    .map(|(name, _)| name)
+    .collect();
+    if !empty.is_empty() {
+        report.push("config", CheckStatus::Fail, format!("empty: {}", empty.join(", ")));
+        return report;
+    }
+    report.push("config", CheckStatus::Pass, "all required settings present and valid");
+    check_secrets(&mut report, config);
+    check_database(&mut report, config).await;
+    check_storage(&mut report, config).await;
+    check_worker(&mut report, config);
+    check_external_processors(&mut report, config);
+
+    report
+}
+
+/// `Config::from_env` panics on the first bad setting, which is exactly the report wanted
+/// here; the panic is caught and its message returned instead of aborting the process.
+fn load_config() -> Result<&'static Config, String> {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(|_| {}));
+    let result = std::panic::catch_unwind(config::get_config);
+    std::panic::set_hook(default_hook);
+
+    result.map_err(|payload| {
+        payload
+            .downcast_ref::<String>()
+            .cloned()
+            .or_else(|| payload.downcast_ref::<&str>().map(|s| s.to_string()))
+            .unwrap_or_else(|| "configuration failed to load".to_string())
+    })
+}
+
+fn check_secrets(report: &mut DoctorReport, config: &Config) {
+    match config.jwt_algorithm {
+        JwtAlgorithm::Hs256 => match config::jwt_secret_weakness(&config.jwt_secret) {
+            // Only reachable with ALLOW_WEAK_JWT_SECRET=true; otherwise loading the config failed
+            Some(reason) => report.push("jwt_secret", CheckStatus::Warn, format!("secret {} (allowed by ALLOW_WEAK_JWT_SECRET)", reason)),
+            None => report.push("jwt_secret", CheckStatus::Pass, format!("fingerprint {}", config.jwt_secret_fingerprint())),
+        },
+        JwtAlgorithm::Rs256 => match jwt::load(config) {
+            Ok(keys) => {
+                let mut detail = format!("RS256 keypair, kid {}", keys.kid().unwrap_or_default());
+                if let Some(previous) = keys.previous_kid() {
+                    detail.push_str(&format!(", still accepting kid {}", previous));
+                }
+                report.push("jwt_keys", CheckStatus::Pass, detail)
+            }
+            Err(e) => report.push("jwt_keys", CheckStatus::Fail, e),
+        },
+    }
+
+    match config.storage_credentials_key {
+        Some(_) => report.push("storage_credentials_key", CheckStatus::Pass, "set; per-project buckets enabled"),
+        None => report.push("storage_credentials_key", CheckStatus::Warn, "unset; per-project storage endpoints answer 503"),
+    }
+}
+
+async fn check_database(report: &mut DoctorReport, config: &Config) {
+    let db = match Database::connect(&config.database_url).await {
+        Ok(db) => db,
+        Err(e) => {
+            report.push("database", CheckStatus::Fail, format!("cannot connect: {}", e));
+            return;
+        }
+    };
+    match db.ping().await {
+        Ok(()) => report.push("database", CheckStatus::Pass, "connected"),
+        Err(e) => {
+            report.push("database", CheckStatus::Fail, format!("ping failed: {}", e));
+            return;
+        }
+    }
+
+    match Migrator::get_pending_migrations(&db).await {
+        Ok(pending) if pending.is_empty() => report.push("migrations", CheckStatus::Pass, "schema is up to date"),
+        Ok(pending) if config.auto_migrate => report.push(
+            "migrations",
+            CheckStatus::Warn,
+            format!("{} pending; AUTO_MIGRATE applies them on the next start", pending.len()),
+        ),
+        Ok(pending) => report.push(
+            "migrations",
+            CheckStatus::Fail,
+            format!("{} pending; run `migrate` or set AUTO_MIGRATE=true", pending.len()),
+        ),
+        Err(e) => report.push("migrations", CheckStatus::Fail, format!("cannot read migration state: {}", e)),
+    }
+    let _ = db.close().await;
+}
+
+async fn check_storage(report: &mut DoctorReport, config: &Config) {
+    let BucketDiagnostics { bucket, endpoint, reachable, policy, acl, presign, .. } = S3Service::new().await.diagnose().await;
+
+    if !reachable.ok {
+        report.push("storage", CheckStatus::Fail, format!("bucket {} at {}: {}", bucket, endpoint, reachable.detail.unwrap_or_default()));
+        return;
+    }
+    report.push("storage", CheckStatus::Pass, format!("bucket {} at {}", bucket, endpoint));
+
+    if presign.ok {
+        report.push("storage_presign", CheckStatus::Pass, "object written, read back through a presigned URL and removed");
+    } else {
+        report.push("storage_presign", CheckStatus::Fail, presign.detail.unwrap_or_default());
+    }
+
+    // ACLs and the bucket policy only matter when objects are meant to be public
+    let optional = if config.s3_public_objects { CheckStatus::Fail } else { CheckStatus::Warn };
+    if acl.ok {
+        report.push("storage_acl", CheckStatus::Pass, "public-read ACL accepted");
+    } else {
+        report.push("storage_acl", optional, acl.detail.unwrap_or_default());
+    }
+    match policy.as_str() {
+        "present" => report.push("storage_policy", CheckStatus::Pass, "bucket policy present"),
+        "none" if !config.s3_public_objects => report.push("storage_policy", CheckStatus::Pass, "no bucket policy (objects are private)"),
+        other => report.push("storage_policy", CheckStatus::Warn, other),
+    }
+}
+
+fn check_worker(report: &mut DoctorReport, config: &Config) {
+    let cpus = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    // Image jobs are CPU-bound and share the default pool unless they have their own
+    let image_pool = config.worker_concurrency_image.unwrap_or(config.worker_concurrency);
+
+    if config.worker_concurrency == 0 || image_pool == 0 {
+        report.push("worker", CheckStatus::Fail, "a worker pool has concurrency 0, so its jobs never run");
+    } else if image_pool > cpus {
+        report.push(
+            "worker",
+            CheckStatus::Warn,
+            format!("{} concurrent image jobs on {} CPUs; set WORKER_CONCURRENCY_IMAGE to at most {}", image_pool, cpus, cpus),
+        );
+    } else {
+        report.push("worker", CheckStatus::Pass, format!("{} concurrent image jobs on {} CPUs", image_pool, cpus));
+    }
+}
+
+fn check_external_processors(report: &mut DoctorReport, config: &Config) {
+    if config.external_processors.is_empty() {
+        return;
+    }
+    if !config.allow_external_processors {
+        report.push(
+            "external_processors",
+            CheckStatus::Warn,
+            format!("{} templates defined but ALLOW_EXTERNAL_PROCESSORS is off", config.external_processors.len()),
+        );
+        return;
+    }
+
+    let mut names: Vec<&String> = config.external_processors.keys().collect();
+    names.sort();
+    for name in names {
+        let check = format!("external_processor.{}", name);
+        let Some(program) = config.external_processors[name].split_whitespace().next() else {
+            report.push(&check, CheckStatus::Fail, "empty command template");
+            continue;
+        };
+        if find_program(program) {
+            report.push(&check, CheckStatus::Pass, format!("{} found", program));
+        } else {
+            report.push(&check, CheckStatus::Fail, format!("{} not found on PATH", program));
+        }
+    }
+}
+
+/// A path is checked as-is; a bare name is looked up in every `PATH` entry.
+fn find_program(program: &str) -> bool {
+    if program.contains('/') {
+        return Path::new(program).is_file();
+    }
+    std::env::var_os("PATH")
+        .map(|path| std::env::split_paths(&path).any(|dir| dir.join(program).is_file()))
+        .unwrap_or(false)
+}
@@ -0,0 +1,91 @@
+use sea_orm::DatabaseConnection;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::entities::file;
+use crate::models::settings::ProjectSettings;
+use crate::services::webhooks;
+
+/// File lifecycle event names; `webhooks::EVENTS` lists every name a project can subscribe to.
+pub const FILE_EVENTS: [&str; 3] = ["file.created", "file.ready", "file.deleted"];
+
+/// File lifecycle events a project can subscribe to.
+#[derive(Clone, Copy, Debug)]
+pub enum FileEvent {
+    /// The file row was inserted (images are still `processing`)
+    Created,
+    /// The status became `ready`: right away for plain files, after the first worker run for images
+    Ready,
+    /// The file row and its objects were removed
+    Deleted,
+}
+
+impl FileEvent {
+    pub fn name(self) -> &'static str {
+        match self {
+            FileEvent::Created => "file.created",
+            FileEvent::Ready => "file.ready",
+            FileEvent::Deleted => "file.deleted",
+        }
+    }
+}
+
+/// `FileResponse` without URLs, which may be presigned and expire before a consumer reads
+/// them. `s3_key` and the `variants` keys locate the objects instead.
+#[derive(Serialize)]
+struct FileEventPayload<'a> {
+    event: &'static str,
+    id: Uuid,
+    project_id: Uuid,
+    filename: &'a str,
+    mime_type: &'a str,
+    size: i64,
+    status: &'a str,
+    s3_key: &'a str,
+    variants: &'a serde_json::Value,
+    content_hash: Option<&'a str>,
+    folder: &'a str,
+    derived_from: Option<Uuid>,
+    variants_stale: bool,
+    variant_dimensions: &'a serde_json::Value,
+    created_at: chrono::DateTime<chrono::Utc>,
+    /// Only on `file.deleted`; files have no soft delete, so always `true` for now
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hard: Option<bool>,
+}
+
+/// Sends `event` for `file` to the project's webhook when the project lists it in
+/// `webhook_events`. Callers emit in the order things happen to a file, and the project's
+/// delivery queue keeps that order.
+pub fn emit(db: &DatabaseConnection, settings: &ProjectSettings, event: FileEvent, file: &file::Model) {
+    if !settings.webhook_events.iter().any(|name| name == event.name()) {
+        return;
+    }
+
+    let payload = FileEventPayload {
+        event: event.name(),
+        id: file.id,
+        project_id: file.project_id,
+        filename: &file.filename,
+        mime_type: &file.mime_type,
+        size: file.size,
+        status: &file.status,
+        s3_key: &file.s3_key,
+        variants: &file.variants_json,
+        content_hash: file.content_hash.as_deref(),
+        folder: &file.folder,
+        derived_from: file.derived_from,
+        variants_stale: file.variants_stale(),
+        variant_dimensions: &file.variant_dimensions,
+        created_at: file.created_at,
+        hard: matches!(event, FileEvent::Deleted).then_some(true),
+    };
+
+    match serde_json::to_string(&payload) {
+        Ok(body) => {
+            println!("Event | {} | project={} | file={}", event.name(), file.project_id, file.id);
+            webhooks::enqueue(db, file.project_id, event.name(), body);
+        }
+        Err(e) => eprintln!("Event | {} | file={} | failed to serialize: {}", event.name(), file.id, e),
+    }
+}
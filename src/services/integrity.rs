@@ -0,0 +1,89 @@
+use std::collections::BTreeMap;
+
+use sea_orm::{ActiveModelTrait, DatabaseConnection, Set};
+use serde::Serialize;
+use crate::entities::file;
+use crate::error::AppError;
+use crate::services::storage::Storage;
+use crate::services::variant_keys;
+use crate::utils::sha256_hex;
+
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub struct IntegrityReport {
+    #[schema(value_type = String)]
+    pub file_id: uuid::Uuid,
+    /// `match` or `mismatch`
+    pub result: String,
+    pub expected: String,
+    pub actual: String,
+    /// `match`, `mismatch` or `missing` per variant with a recorded hash. Variants generated
+    /// before hashes were recorded are left out.
+    pub variants: BTreeMap<String, String>,
+}
+
+impl IntegrityReport {
+    /// The original matches its checksum.
+    pub fn is_match(&self) -> bool {
+        self.result == "match"
+    }
+
+    /// Variants whose stored bytes differ from what the worker produced, or are gone.
+    pub fn bad_variants(&self) -> Vec<&str> {
+        self.variants.iter().filter(|(_, r)| *r != "match").map(|(name, _)| name.as_str()).collect()
+    }
+}
+
+/// Re-downloads the original object, recomputes its SHA-256 and compares it with the
+/// hash recorded at upload. On mismatch the file is flipped to `error` status.
+///
+/// Variants are checked the same way against `variant_hashes`. A bad variant is only
+/// reported: the next sync regenerates it from the original.
+pub async fn verify_file(
+    db: &DatabaseConnection,
+    s3: &dyn Storage,
+    file: &file::Model,
+) -> Result<IntegrityReport, AppError> {
+    let expected = file
+        .content_hash
+        .clone()
+        .ok_or_else(|| AppError::Conflict("No checksum recorded for this file".to_string()))?;
+
+    let data = s3.get_object(&file.s3_key).await?;
+    let actual = sha256_hex(&data);
+    let matches = actual == expected;
+
+    if !matches {
+        eprintln!("Integrity | file={} | checksum mismatch | expected={} | actual={}", file.id, expected, actual);
+        let mut file_active: file::ActiveModel = file.clone().into();
+        file_active.status = Set("error".to_string());
+        file_active.updated_at = Set(chrono::Utc::now());
+        file_active.update(db).await?;
+    }
+
+    let mut variants = BTreeMap::new();
+    for (name, hash) in file.variant_hashes.as_object().into_iter().flatten() {
+        let (Some(hash), Some(key)) = (
+            hash.as_str(),
+            file.variants_json.get(name).and_then(|v| v.as_str()).and_then(|v| variant_keys::object_key(v, s3.bucket_name())),
+        ) else {
+            continue;
+        };
+        let result = match s3.get_object(&key).await {
+            Ok(data) if sha256_hex(&data) == hash => "match",
+            Ok(_) => "mismatch",
+            Err(_) => "missing",
+        };
+        if result != "match" {
+            eprintln!("Integrity | file={} | variant={} | {}", file.id, name, result);
+        }
+        variants.insert(name.clone(), result.to_string());
+    }
+
+    Ok(IntegrityReport {
+        file_id: file.id,
+        result: if matches { "match" } else { "mismatch" }.to_string(),
+        expected,
+        actual,
+        variants,
+    })
+}
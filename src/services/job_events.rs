@@ -0,0 +1,46 @@
+use std::time::Duration;
+use sea_orm::{DatabaseConnection, Set};
+use uuid::Uuid;
+
+use crate::entities::job_event;
+use crate::services::batch_writer::{BatchWriter, Batching};
+
+const BATCHING: Batching = Batching {
+    capacity: 4096,
+    max_batch: 256,
+    interval: Duration::from_millis(500),
+};
+
+/// Best-effort, fire-and-forget writer for `job_events`.
+///
+/// `record` never waits on the database; events are inserted in batches by a
+/// [`BatchWriter`]. When its buffer is full, or `JOB_EVENTS_ENABLED` is off, events are
+/// discarded. A job deleted mid-flight fails the FK for the whole batch, which is then lost.
+#[derive(Clone)]
+pub struct JobEventRecorder {
+    writer: Option<BatchWriter<job_event::ActiveModel>>,
+}
+
+impl JobEventRecorder {
+    /// Spawns the flush task when job events are enabled.
+    pub fn new(db: DatabaseConnection) -> Self {
+        if !crate::config::get_config().job_events_enabled {
+            return Self { writer: None };
+        }
+        Self { writer: Some(BatchWriter::inserting(db, BATCHING, "Job events")) }
+    }
+
+    pub fn record(&self, job_id: Uuid, event: &str, data: serde_json::Value) {
+        let Some(writer) = &self.writer else {
+            return;
+        };
+
+        writer.send(job_event::ActiveModel {
+            job_id: Set(job_id),
+            event: Set(event.to_string()),
+            data: Set(data),
+            created_at: Set(chrono::Utc::now()),
+            ..Default::default()
+        });
+    }
+}
@@ -0,0 +1,277 @@
+//! Signing and verifying access tokens.
+//!
+//! With `JWT_ALGORITHM=HS256` (the default) tokens are signed and verified with `JWT_SECRET`.
+//! With `RS256` they are signed with the private key and verified with the public one, which
+//! [`jwks`] publishes so other services can verify tokens without a shared secret.
+//!
+//! To rotate the RS256 pair, point `JWT_PREVIOUS_PUBLIC_KEY_PATH` at the old public key: tokens
+//! it signed keep verifying (and it stays in the JWKS) until they expire and it is removed.
+//! Tokens are matched to a key by their `kid`; one naming no known key is rejected.
+
+use std::fs;
+use std::sync::OnceLock;
+
+use base64::{engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD}, Engine};
+use jsonwebtoken::errors::ErrorKind;
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, TokenData, Validation};
+use ring::rsa::{KeyPair, PublicKeyComponents};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+use crate::config::{get_config, Config, JwtAlgorithm};
+
+pub struct JwtKeys {
+    algorithm: Algorithm,
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+    /// RFC 7638 thumbprint of the public key, sent as `kid`; `None` with HS256
+    kid: Option<String>,
+    /// Key being rotated out, by its `kid`; verifies but never signs
+    previous: Option<(String, DecodingKey)>,
+    jwks: Value,
+}
+
+static KEYS: OnceLock<JwtKeys> = OnceLock::new();
+
+/// Keys for the configured algorithm; panics on unreadable or mismatched key files, so
+/// `main` calls this before serving.
+pub fn keys() -> &'static JwtKeys {
+    KEYS.get_or_init(|| load(get_config()).unwrap_or_else(|e| panic!("{}", e)))
+}
+
+/// Reads and checks the keys `config` points at.
+pub fn load(config: &Config) -> Result<JwtKeys, String> {
+    match config.jwt_algorithm {
+        JwtAlgorithm::Hs256 => Ok(JwtKeys::hs256(&config.jwt_secret)),
+        JwtAlgorithm::Rs256 => {
+            let private_pem = read_pem_file(config.jwt_private_key_path.as_deref(), "JWT_PRIVATE_KEY_PATH")?;
+            let public_pem = read_pem_file(config.jwt_public_key_path.as_deref(), "JWT_PUBLIC_KEY_PATH")?;
+
+            let encoding = EncodingKey::from_rsa_pem(&private_pem)
+                .map_err(|e| format!("JWT_PRIVATE_KEY_PATH is not an RSA private key: {}", e))?;
+            let decoding = DecodingKey::from_rsa_pem(&public_pem)
+                .map_err(|e| format!("JWT_PUBLIC_KEY_PATH is not an RSA public key: {}", e))?;
+
+            let components = public_components(&private_pem)?;
+            let (kid, jwk) = rsa_jwk(&components.n, &components.e);
+            let mut published = vec![jwk];
+
+            let previous = match config.jwt_previous_public_key_path.as_deref() {
+                Some(path) => {
+                    let previous_pem = read_pem_file(Some(path), "JWT_PREVIOUS_PUBLIC_KEY_PATH")?;
+                    let decoding = DecodingKey::from_rsa_pem(&previous_pem)
+                        .map_err(|e| format!("JWT_PREVIOUS_PUBLIC_KEY_PATH is not an RSA public key: {}", e))?;
+                    let (n, e) = rsa_public_pem_components(&previous_pem)
+                        .ok_or("JWT_PREVIOUS_PUBLIC_KEY_PATH is not an RSA public key")?;
+                    let (previous_kid, jwk) = rsa_jwk(&n, &e);
+                    if previous_kid == kid {
+                        return Err("JWT_PREVIOUS_PUBLIC_KEY_PATH is the current public key".to_string());
+                    }
+                    published.push(jwk);
+                    Some((previous_kid, decoding))
+                }
+                None => None,
+            };
+
+            let keys = JwtKeys {
+                algorithm: Algorithm::RS256,
+                encoding,
+                decoding,
+                previous,
+                jwks: json!({ "keys": published }),
+                kid: Some(kid),
+            };
+
+            // A public key from another pair would only show up as every login failing
+            let probe = keys.encode(&json!({ "sub": "probe", "exp": u32::MAX }))
+                .map_err(|e| format!("Failed to sign with JWT_PRIVATE_KEY_PATH: {}", e))?;
+            keys.decode::<Value>(&probe)
+                .map_err(|_| "JWT_PUBLIC_KEY_PATH does not match JWT_PRIVATE_KEY_PATH".to_string())?;
+            Ok(keys)
+        }
+    }
+}
+
+impl JwtKeys {
+    /// Signs and verifies with `secret`.
+    pub fn hs256(secret: &str) -> Self {
+        JwtKeys {
+            algorithm: Algorithm::HS256,
+            encoding: EncodingKey::from_secret(secret.as_bytes()),
+            decoding: DecodingKey::from_secret(secret.as_bytes()),
+            kid: None,
+            previous: None,
+            jwks: json!({ "keys": [] }),
+        }
+    }
+
+    pub fn encode<T: Serialize>(&self, claims: &T) -> jsonwebtoken::errors::Result<String> {
+        let mut header = Header::new(self.algorithm);
+        header.kid = self.kid.clone();
+        jsonwebtoken::encode(&header, claims, &self.encoding)
+    }
+
+    /// Checks signature and `exp` against the key named by `kid` (the current one without a
+    /// `kid`); tokens signed with another algorithm or naming an unknown key are rejected.
+    pub fn decode<T: DeserializeOwned>(&self, token: &str) -> jsonwebtoken::errors::Result<TokenData<T>> {
+        let kid = jsonwebtoken::decode_header(token)?.kid;
+        let key = match (kid, &self.previous) {
+            (None, _) => &self.decoding,
+            (Some(kid), _) if self.kid.as_deref() == Some(kid.as_str()) => &self.decoding,
+            (Some(kid), Some((previous_kid, previous))) if *previous_kid == kid => previous,
+            (Some(_), _) => return Err(ErrorKind::InvalidToken.into()),
+        };
+        jsonwebtoken::decode(token, key, &Validation::new(self.algorithm))
+    }
+
+    /// `{"keys": [...]}` with the RS256 public key; empty with HS256, whose secret is not shared.
+    pub fn jwks(&self) -> &Value {
+        &self.jwks
+    }
+
+    pub fn kid(&self) -> Option<&str> {
+        self.kid.as_deref()
+    }
+
+    /// `kid` of the key being rotated out, when one is configured.
+    pub fn previous_kid(&self) -> Option<&str> {
+        self.previous.as_ref().map(|(kid, _)| kid.as_str())
+    }
+}
+
+/// Signs `claims` with the configured key.
+pub fn encode<T: Serialize>(claims: &T) -> jsonwebtoken::errors::Result<String> {
+    keys().encode(claims)
+}
+
+/// Verifies a token signed by [`encode`].
+pub fn decode<T: DeserializeOwned>(token: &str) -> jsonwebtoken::errors::Result<TokenData<T>> {
+    keys().decode(token)
+}
+
+/// The published key set.
+pub fn jwks() -> &'static Value {
+    keys().jwks()
+}
+
+fn read_pem_file(path: Option<&str>, var: &str) -> Result<Vec<u8>, String> {
+    let path = path.ok_or_else(|| format!("{} must be set when JWT_ALGORITHM=RS256", var))?;
+    fs::read(path).map_err(|e| format!("Failed to read {} '{}': {}", var, path, e))
+}
+
+/// RFC 7638 thumbprint (the `kid`) and JWK of an RSA public key.
+fn rsa_jwk(n: &[u8], e: &[u8]) -> (String, Value) {
+    let n = URL_SAFE_NO_PAD.encode(n);
+    let e = URL_SAFE_NO_PAD.encode(e);
+    // Members in lexicographic order, as the thumbprint requires
+    let thumbprint = Sha256::digest(format!(r#"{{"e":"{}","kty":"RSA","n":"{}"}}"#, e, n));
+    let kid = URL_SAFE_NO_PAD.encode(thumbprint);
+    let jwk = json!({ "kty": "RSA", "use": "sig", "alg": "RS256", "kid": kid, "n": n, "e": e });
+    (kid, jwk)
+}
+
+/// DER body of the first PEM block.
+fn pem_der(pem: &[u8]) -> Option<Vec<u8>> {
+    let body: String = std::str::from_utf8(pem)
+        .ok()?
+        .lines()
+        .skip_while(|line| !line.trim().starts_with("-----BEGIN "))
+        .skip(1)
+        .take_while(|line| !line.trim().starts_with("-----END "))
+        .map(str::trim)
+        .collect();
+    STANDARD.decode(body).ok()
+}
+
+/// Modulus and exponent of an SPKI (`PUBLIC KEY`) or PKCS#1 (`RSA PUBLIC KEY`) PEM key.
+fn rsa_public_pem_components(pem: &[u8]) -> Option<(Vec<u8>, Vec<u8>)> {
+    rsa_public_der_components(&pem_der(pem)?)
+}
+
+fn rsa_public_der_components(der: &[u8]) -> Option<(Vec<u8>, Vec<u8>)> {
+    let (body, _) = der_element(der, 0x30)?;
+    // SPKI wraps the PKCS#1 key in a BIT STRING after the algorithm identifier
+    if let Some((_, rest)) = der_element(body, 0x30) {
+        let (bits, _) = der_element(rest, 0x03)?;
+        return rsa_public_der_components(bits.get(1..)?);
+    }
+    let (n, rest) = der_element(body, 0x02)?;
+    let (e, _) = der_element(rest, 0x02)?;
+    let unsigned = |int: &[u8]| int.iter().skip_while(|b| **b == 0).copied().collect();
+    Some((unsigned(n), unsigned(e)))
+}
+
+/// Contents of the DER element at the start of `input` if it has `tag`, and what follows it.
+fn der_element(input: &[u8], tag: u8) -> Option<(&[u8], &[u8])> {
+    if *input.first()? != tag {
+        return None;
+    }
+    let first = *input.get(1)? as usize;
+    let (len, header) = if first < 0x80 {
+        (first, 2)
+    } else {
+        let count = first & 0x7f;
+        if count == 0 || count > 4 {
+            return None;
+        }
+        let len = input.get(2..2 + count)?.iter().fold(0usize, |len, b| (len << 8) | *b as usize);
+        (len, 2 + count)
+    };
+    let contents = input.get(header..header.checked_add(len)?)?;
+    Some((contents, &input[header + len..]))
+}
+
+/// Modulus and exponent of a PKCS#8 (`PRIVATE KEY`) or PKCS#1 (`RSA PRIVATE KEY`) PEM key.
+fn public_components(pem: &[u8]) -> Result<PublicKeyComponents<Vec<u8>>, String> {
+    let pem = std::str::from_utf8(pem).map_err(|_| "JWT_PRIVATE_KEY_PATH is not a PEM file".to_string())?;
+    let label = pem
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("-----BEGIN ")?.strip_suffix("-----"))
+        .ok_or("JWT_PRIVATE_KEY_PATH is not a PEM file")?;
+    let der = pem_der(pem.as_bytes()).ok_or("JWT_PRIVATE_KEY_PATH is not a PEM file")?;
+
+    let key_pair = match label {
+        "PRIVATE KEY" => KeyPair::from_pkcs8(&der),
+        "RSA PRIVATE KEY" => KeyPair::from_der(&der),
+        other => return Err(format!("JWT_PRIVATE_KEY_PATH holds a '{}', expected an RSA private key", other)),
+    }
+    .map_err(|e| format!("JWT_PRIVATE_KEY_PATH is not a usable RSA key: {}", e))?;
+    Ok(PublicKeyComponents::from(key_pair.public()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "0123456789abcdef0123456789abcdef-config";
+
+    fn claims() -> Value {
+        json!({ "sub": "alice", "exp": chrono::Utc::now().timestamp() + 60 })
+    }
+
+    #[test]
+    fn hs256_verifies_tokens_signed_with_its_secret() {
+        let keys = JwtKeys::hs256(SECRET);
+        let token = keys.encode(&claims()).unwrap();
+        assert_eq!(keys.decode::<Value>(&token).unwrap().claims["sub"], "alice");
+    }
+
+    #[test]
+    fn hs256_rejects_tokens_signed_with_another_secret() {
+        let keys = JwtKeys::hs256(SECRET);
+        for other in ["secret", "0123456789abcdef0123456789abcdef-other"] {
+            let forged = JwtKeys::hs256(other).encode(&claims()).unwrap();
+            assert!(keys.decode::<Value>(&forged).is_err(), "accepted a token signed with {:?}", other);
+        }
+    }
+
+    #[test]
+    fn hs256_rejects_a_kid() {
+        let keys = JwtKeys::hs256(SECRET);
+        let mut header = Header::new(Algorithm::HS256);
+        header.kid = Some("anything".to_string());
+        let token = jsonwebtoken::encode(&header, &claims(), &EncodingKey::from_secret(SECRET.as_bytes())).unwrap();
+        assert!(keys.decode::<Value>(&token).is_err());
+    }
+}
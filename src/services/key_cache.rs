@@ -0,0 +1,125 @@
+//! Read-through cache for `api_key_auth`.
+//!
+//! Maps a key hash to the key row and its project, so repeated requests with the same key
+//! skip the lookup query. Entries expire after `API_KEY_CACHE_TTL_SECS`. Handlers that change
+//! a key or a project call [`invalidate_key`] / [`invalidate_project`], which only reaches
+//! this process: other instances keep serving their copy until it expires.
+
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use lru::LruCache;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::config::get_config;
+use crate::entities::{api_key, project};
+
+struct Entry {
+    key: api_key::Model,
+    project: project::Model,
+    cached_at: Instant,
+}
+
+static ENTRIES: OnceLock<Mutex<LruCache<String, Entry>>> = OnceLock::new();
+static HITS: AtomicU64 = AtomicU64::new(0);
+static MISSES: AtomicU64 = AtomicU64::new(0);
+
+fn entries() -> &'static Mutex<LruCache<String, Entry>> {
+    ENTRIES.get_or_init(|| {
+        let size = NonZeroUsize::new(get_config().api_key_cache_size).unwrap_or(NonZeroUsize::MIN);
+        Mutex::new(LruCache::new(size))
+    })
+}
+
+fn ttl() -> Duration {
+    Duration::from_secs(get_config().api_key_cache_ttl_secs)
+}
+
+/// A TTL of 0 turns the cache off; every request then reads the database.
+pub fn enabled() -> bool {
+    !ttl().is_zero()
+}
+
+/// The cached key and project for `key_hash`, if still fresh. Counts a hit or a miss.
+pub fn get(key_hash: &str) -> Option<(api_key::Model, project::Model)> {
+    if !enabled() {
+        return None;
+    }
+
+    let mut entries = entries().lock().unwrap();
+    let fresh = entries.get(key_hash).is_some_and(|e| e.cached_at.elapsed() < ttl());
+    if !fresh {
+        entries.pop(key_hash);
+        MISSES.fetch_add(1, Ordering::Relaxed);
+        return None;
+    }
+
+    HITS.fetch_add(1, Ordering::Relaxed);
+    entries.get(key_hash).map(|e| (e.key.clone(), e.project.clone()))
+}
+
+pub fn put(key_hash: &str, key: &api_key::Model, project: &project::Model) {
+    if !enabled() {
+        return;
+    }
+    entries().lock().unwrap().put(
+        key_hash.to_string(),
+        Entry { key: key.clone(), project: project.clone(), cached_at: Instant::now() },
+    );
+}
+
+/// Drops the entry of one key after it is updated or deleted.
+pub fn invalidate_key(key_id: Uuid) {
+    invalidate(|e| e.key.id == key_id);
+}
+
+/// Drops every key of a project after the project's settings, name or deletion state change.
+pub fn invalidate_project(project_id: Uuid) {
+    invalidate(|e| e.project.id == project_id);
+}
+
+/// Drops every key of a user's projects after the user (and, by cascade, the projects) is deleted.
+pub fn invalidate_owner(owner_id: Uuid) {
+    invalidate(|e| e.project.owner_id == owner_id);
+}
+
+fn invalidate(matches: impl Fn(&Entry) -> bool) {
+    let mut entries = entries().lock().unwrap();
+    let stale: Vec<String> = entries.iter().filter(|(_, e)| matches(e)).map(|(hash, _)| hash.clone()).collect();
+    for hash in stale {
+        entries.pop(&hash);
+    }
+}
+
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub struct KeyCacheStats {
+    pub enabled: bool,
+    pub ttl_secs: u64,
+    pub capacity: usize,
+    pub entries: usize,
+    /// Lookups answered from the cache since the process started
+    pub hits: u64,
+    /// Lookups that went to the database
+    pub misses: u64,
+    /// `hits / (hits + misses)`, 0 before the first lookup
+    pub hit_rate: f64,
+}
+
+pub fn stats() -> KeyCacheStats {
+    let hits = HITS.load(Ordering::Relaxed);
+    let misses = MISSES.load(Ordering::Relaxed);
+    let entries = entries().lock().unwrap();
+
+    KeyCacheStats {
+        enabled: enabled(),
+        ttl_secs: get_config().api_key_cache_ttl_secs,
+        capacity: entries.cap().get(),
+        entries: entries.len(),
+        hits,
+        misses,
+        hit_rate: if hits + misses == 0 { 0.0 } else { hits as f64 / (hits + misses) as f64 },
+    }
+}
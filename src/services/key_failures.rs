@@ -0,0 +1,61 @@
+use std::collections::HashMap;
+use std::time::Duration;
+use sea_orm::sea_query::Expr;
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
+use uuid::Uuid;
+
+use crate::entities::api_key;
+use crate::services::batch_writer::{BatchWriter, Batching};
+
+/// The interval is long so repeated failures of a key collapse into one update.
+const BATCHING: Batching = Batching {
+    capacity: 8192,
+    max_batch: 1024,
+    interval: Duration::from_secs(5),
+};
+
+/// Best-effort counter for requests made with inactive or expired keys.
+///
+/// `record` never waits on the database. A [`BatchWriter`] sums each batch of failures per
+/// key and adds them to `api_keys.failed_attempts` with one `UPDATE` per key; when its
+/// buffer is full failures are not counted.
+#[derive(Clone)]
+pub struct KeyFailureRecorder {
+    writer: BatchWriter<Uuid>,
+}
+
+impl KeyFailureRecorder {
+    /// Spawns the flush task.
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self {
+            writer: BatchWriter::spawn(BATCHING, move |batch| {
+                let db = db.clone();
+                async move { add_failures(&db, batch).await }
+            }),
+        }
+    }
+
+    pub fn record(&self, api_key_id: Uuid) {
+        self.writer.send(api_key_id);
+    }
+}
+
+async fn add_failures(db: &DatabaseConnection, batch: Vec<Uuid>) {
+    let mut counts: HashMap<Uuid, i64> = HashMap::new();
+    for id in batch {
+        *counts.entry(id).or_default() += 1;
+    }
+
+    let now = chrono::Utc::now();
+    for (id, count) in counts {
+        let result = api_key::Entity::update_many()
+            .col_expr(api_key::Column::FailedAttempts, Expr::col(api_key::Column::FailedAttempts).add(count))
+            .col_expr(api_key::Column::LastFailedAt, Expr::value(now))
+            .filter(api_key::Column::Id.eq(id))
+            .exec(db)
+            .await;
+        if let Err(e) = result {
+            eprintln!("Key failures | failed to add {} attempts to key {}: {}", count, id, e);
+        }
+    }
+}
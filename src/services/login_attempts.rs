@@ -0,0 +1,45 @@
+use std::time::Duration;
+use sea_orm::{DatabaseConnection, Set};
+use uuid::Uuid;
+
+use crate::entities::login_attempt;
+use crate::services::batch_writer::{BatchWriter, Batching};
+
+const BATCHING: Batching = Batching {
+    capacity: 4096,
+    max_batch: 256,
+    interval: Duration::from_secs(1),
+};
+
+/// Longest login value stored; the column is `varchar(255)`.
+const MAX_USERNAME_LEN: usize = 255;
+
+/// Best-effort writer for `login_attempts`.
+///
+/// `/auth/login` only hands attempts to a [`BatchWriter`], which inserts them in batches,
+/// so a burst of failed logins costs one `INSERT` per flush rather than one per attempt.
+/// When its buffer is full attempts are discarded.
+#[derive(Clone)]
+pub struct LoginAttemptRecorder {
+    writer: BatchWriter<login_attempt::ActiveModel>,
+}
+
+impl LoginAttemptRecorder {
+    /// Spawns the flush task.
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { writer: BatchWriter::inserting(db, BATCHING, "Login attempts") }
+    }
+
+    /// `failure_reason` is `None` for a successful login.
+    pub fn record(&self, username: &str, user_id: Option<Uuid>, failure_reason: Option<&str>, ip: Option<String>) {
+        self.writer.send(login_attempt::ActiveModel {
+            username: Set(username.chars().take(MAX_USERNAME_LEN).collect()),
+            user_id: Set(user_id),
+            success: Set(failure_reason.is_none()),
+            failure_reason: Set(failure_reason.map(str::to_string)),
+            ip: Set(ip),
+            created_at: Set(chrono::Utc::now()),
+            ..Default::default()
+        });
+    }
+}
@@ -0,0 +1,217 @@
+//! [`Storage`] kept in process memory, for tests and for trying the server without S3.
+//!
+//! Install it with `project_storage::set_default`. Failures and delays can be injected per
+//! key prefix, so tests sharing one instance stay apart by using their own project prefix.
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use async_trait::async_trait;
+
+use crate::error::AppError;
+use crate::services::storage::{ObjectPage, ObjectSummary, Storage};
+
+/// A storage call, for aiming an injected [`Fault`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operation {
+    Put,
+    Get,
+    Delete,
+    List,
+}
+
+/// What happens to matching calls.
+#[derive(Clone, Copy, Debug)]
+pub enum Fault {
+    /// Fail with `InternalServerError`, like S3 rejecting the request
+    Error,
+    /// Fail with `ServiceUnavailable`, like an outage or an open circuit breaker
+    Unavailable,
+    /// Sleep before carrying on
+    Delay(Duration),
+}
+
+#[derive(Clone, Debug)]
+pub struct StoredObject {
+    pub data: Vec<u8>,
+    pub content_type: String,
+    pub content_disposition: Option<String>,
+}
+
+struct Injected {
+    operation: Operation,
+    prefix: String,
+    fault: Fault,
+}
+
+pub struct MemoryStorage {
+    bucket: String,
+    default_bucket: bool,
+    objects: Mutex<BTreeMap<String, StoredObject>>,
+    faults: Mutex<Vec<Injected>>,
+}
+
+impl MemoryStorage {
+    pub fn new(bucket: &str) -> Self {
+        Self {
+            bucket: bucket.to_string(),
+            default_bucket: true,
+            objects: Mutex::new(BTreeMap::new()),
+            faults: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Stands in for a project's own bucket rather than the global one.
+    pub fn project_bucket(bucket: &str) -> Self {
+        Self { default_bucket: false, ..Self::new(bucket) }
+    }
+
+    /// Applies `fault` to every `operation` on keys under `prefix` until cleared. For
+    /// listings the listed prefix has to fall under it.
+    pub fn inject(&self, operation: Operation, prefix: &str, fault: Fault) {
+        self.faults.lock().unwrap().push(Injected { operation, prefix: prefix.to_string(), fault });
+    }
+
+    /// Removes the faults injected for exactly `prefix`.
+    pub fn clear_faults(&self, prefix: &str) {
+        self.faults.lock().unwrap().retain(|f| f.prefix != prefix);
+    }
+
+    pub fn object(&self, key: &str) -> Option<StoredObject> {
+        self.objects.lock().unwrap().get(key).cloned()
+    }
+
+    /// Stored keys under `prefix`, in order.
+    pub fn keys(&self, prefix: &str) -> Vec<String> {
+        self.objects
+            .lock()
+            .unwrap()
+            .keys()
+            .filter(|k| k.starts_with(prefix))
+            .cloned()
+            .collect()
+    }
+
+    /// Runs the faults matching the call; delays first, then the first failure.
+    async fn faults_for(&self, operation: Operation, key: &str) -> Result<(), AppError> {
+        let matching: Vec<Fault> = self
+            .faults
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|f| f.operation == operation && key.starts_with(&f.prefix))
+            .map(|f| f.fault)
+            .collect();
+
+        for fault in &matching {
+            if let Fault::Delay(delay) = fault {
+                tokio::time::sleep(*delay).await;
+            }
+        }
+        for fault in matching {
+            match fault {
+                Fault::Error => {
+                    return Err(AppError::InternalServerError(format!("Injected {:?} failure for {}", operation, key)));
+                }
+                Fault::Unavailable => {
+                    return Err(AppError::ServiceUnavailable("Storage is temporarily unavailable".to_string()));
+                }
+                Fault::Delay(_) => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Storage for MemoryStorage {
+    fn bucket_name(&self) -> &str {
+        &self.bucket
+    }
+
+    fn is_default_bucket(&self) -> bool {
+        self.default_bucket
+    }
+
+    fn object_url(&self, key: &str) -> String {
+        format!("memory://{}/{}", self.bucket, key)
+    }
+
+    async fn ensure_bucket_exists(&self) -> Result<(), AppError> {
+        Ok(())
+    }
+
+    async fn put_object_as(
+        &self,
+        key: &str,
+        data: Vec<u8>,
+        content_type: &str,
+        content_disposition: Option<String>,
+    ) -> Result<(), AppError> {
+        self.faults_for(Operation::Put, key).await?;
+        self.objects.lock().unwrap().insert(key.to_string(), StoredObject {
+            data,
+            content_type: content_type.to_string(),
+            content_disposition,
+        });
+        Ok(())
+    }
+
+    async fn get_object(&self, key: &str) -> Result<Vec<u8>, AppError> {
+        self.faults_for(Operation::Get, key).await?;
+        self.object(key)
+            .map(|o| o.data)
+            .ok_or_else(|| AppError::InternalServerError(format!("Failed to download file from storage: no object {}", key)))
+    }
+
+    async fn delete_object(&self, key: &str) -> Result<(), AppError> {
+        self.faults_for(Operation::Delete, key).await?;
+        self.objects.lock().unwrap().remove(key);
+        Ok(())
+    }
+
+    async fn list_objects(
+        &self,
+        prefix: &str,
+        max_keys: i32,
+        continuation_token: Option<String>,
+    ) -> Result<ObjectPage, AppError> {
+        self.faults_for(Operation::List, prefix).await?;
+        let objects = self.objects.lock().unwrap();
+        // The token is the last key of the previous page
+        let mut keys = objects
+            .iter()
+            .filter(|(k, _)| k.starts_with(prefix))
+            .filter(|(k, _)| continuation_token.as_ref().is_none_or(|after| *k > after));
+        let page: Vec<ObjectSummary> = keys
+            .by_ref()
+            .take(max_keys.max(1) as usize)
+            .map(|(k, o)| ObjectSummary { key: k.clone(), size: o.data.len() as i64, last_modified: None })
+            .collect();
+        let next_continuation_token = match keys.next() {
+            Some(_) => page.last().map(|o| o.key.clone()),
+            None => None,
+        };
+        Ok(ObjectPage { objects: page, next_continuation_token })
+    }
+
+    async fn get_presigned_url_as(
+        &self,
+        key: &str,
+        expires_in: Duration,
+        content_type: Option<String>,
+        content_disposition: Option<String>,
+    ) -> Result<String, AppError> {
+        let mut url = url::Url::parse(&self.object_url(key))
+            .map_err(|e| AppError::InternalServerError(format!("Failed to generate presigned URL: {}", e)))?;
+        url.query_pairs_mut().append_pair("expires", &expires_in.as_secs().to_string());
+        if let Some(content_type) = content_type {
+            url.query_pairs_mut().append_pair("response-content-type", &content_type);
+        }
+        if let Some(content_disposition) = content_disposition {
+            url.query_pairs_mut().append_pair("response-content-disposition", &content_disposition);
+        }
+        Ok(url.to_string())
+    }
+}
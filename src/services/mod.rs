@@ -1,3 +1,28 @@
 pub mod s3;
+pub mod project_storage;
+pub mod storage;
+pub mod memory_storage;
 pub mod worker;
 pub mod cleanup;
+pub mod integrity;
+pub mod batch_writer;
+pub mod job_events;
+pub mod request_log;
+pub mod key_failures;
+pub mod key_cache;
+pub mod file_events;
+pub mod webhooks;
+pub mod doctor;
+pub mod sync_plan;
+pub mod backfill;
+pub mod urls;
+pub mod scope;
+pub mod password_policy;
+pub mod reconcile;
+pub mod audit;
+pub mod totp;
+pub mod tombstones;
+pub mod variant_keys;
+pub mod jwt;
+pub mod token_versions;
+pub mod login_attempts;
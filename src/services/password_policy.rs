@@ -0,0 +1,168 @@
+//! Rules new passwords must meet, wherever one is set: `POST /users`, `/auth/change-password`,
+//! `/auth/reset-password` and the superuser CLI.
+
+use std::env;
+
+use serde::Serialize;
+
+/// Configured from `PASSWORD_MIN_LENGTH` (default 8) and the `PASSWORD_REQUIRE_*` flags (all off).
+#[derive(Debug, Clone)]
+pub struct PasswordPolicy {
+    /// Minimum length in characters, at least 1
+    pub min_length: usize,
+    pub require_uppercase: bool,
+    pub require_lowercase: bool,
+    pub require_digit: bool,
+    /// Anything that is not a letter, digit or whitespace
+    pub require_symbol: bool,
+}
+
+/// One rule a password broke, as returned in the `violations` of a `422`.
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+pub struct PasswordViolation {
+    /// `min_length`, `uppercase`, `lowercase`, `digit` or `symbol`
+    pub rule: &'static str,
+    pub message: String,
+}
+
+struct CharClass {
+    rule: &'static str,
+    what: &'static str,
+    matches: fn(char) -> bool,
+}
+
+const CHAR_CLASSES: [CharClass; 4] = [
+    CharClass { rule: "uppercase", what: "an uppercase letter", matches: char::is_uppercase },
+    CharClass { rule: "lowercase", what: "a lowercase letter", matches: char::is_lowercase },
+    CharClass { rule: "digit", what: "a digit", matches: |c| c.is_ascii_digit() },
+    CharClass { rule: "symbol", what: "a symbol", matches: |c| !c.is_alphanumeric() && !c.is_whitespace() },
+];
+
+impl PasswordPolicy {
+    pub fn from_env() -> Self {
+        let flag = |name: &str| env::var(name).map(|v| v == "true").unwrap_or(false);
+        Self {
+            min_length: env::var("PASSWORD_MIN_LENGTH")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(8)
+                .max(1),
+            require_uppercase: flag("PASSWORD_REQUIRE_UPPERCASE"),
+            require_lowercase: flag("PASSWORD_REQUIRE_LOWERCASE"),
+            require_digit: flag("PASSWORD_REQUIRE_DIGIT"),
+            require_symbol: flag("PASSWORD_REQUIRE_SYMBOL"),
+        }
+    }
+
+    fn requires(&self, rule: &str) -> bool {
+        match rule {
+            "uppercase" => self.require_uppercase,
+            "lowercase" => self.require_lowercase,
+            "digit" => self.require_digit,
+            "symbol" => self.require_symbol,
+            _ => false,
+        }
+    }
+
+    /// Every rule `password` breaks, in a fixed order; empty when it passes.
+    pub fn violations(&self, password: &str) -> Vec<PasswordViolation> {
+        let mut violations = Vec::new();
+        if password.chars().count() < self.min_length {
+            violations.push(PasswordViolation {
+                rule: "min_length",
+                message: format!("Must be at least {} characters long", self.min_length),
+            });
+        }
+        for class in CHAR_CLASSES {
+            if self.requires(class.rule) && !password.chars().any(class.matches) {
+                violations.push(PasswordViolation { rule: class.rule, message: format!("Must contain {}", class.what) });
+            }
+        }
+        violations
+    }
+
+    /// `Err(AppError::WeakPassword)` listing the broken rules.
+    pub fn check(&self, password: &str) -> Result<(), crate::error::AppError> {
+        let violations = self.violations(password);
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(crate::error::AppError::WeakPassword(violations))
+        }
+    }
+
+    /// Short description for prompts and logs, e.g. "at least 12 characters, a digit".
+    pub fn describe(&self) -> String {
+        let mut parts = vec![format!("at least {} characters", self.min_length)];
+        parts.extend(CHAR_CLASSES.iter().filter(|c| self.requires(c.rule)).map(|c| c.what.to_string()));
+        parts.join(", ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(min_length: usize) -> PasswordPolicy {
+        PasswordPolicy {
+            min_length,
+            require_uppercase: false,
+            require_lowercase: false,
+            require_digit: false,
+            require_symbol: false,
+        }
+    }
+
+    fn rules(policy: &PasswordPolicy, password: &str) -> Vec<&'static str> {
+        policy.violations(password).into_iter().map(|v| v.rule).collect()
+    }
+
+    #[test]
+    fn min_length_boundaries() {
+        let policy = policy(12);
+        assert_eq!(rules(&policy, &"a".repeat(11)), ["min_length"]);
+        assert!(rules(&policy, &"a".repeat(12)).is_empty());
+        assert!(rules(&policy, &"a".repeat(13)).is_empty());
+        assert_eq!(rules(&policy, ""), ["min_length"]);
+        // Counted in characters
+        assert_eq!(rules(&policy, &"ü".repeat(11)), ["min_length"]);
+        assert!(rules(&policy, &"ü".repeat(12)).is_empty());
+    }
+
+    #[test]
+    fn character_classes_in_a_fixed_order() {
+        let strict = PasswordPolicy {
+            min_length: 4,
+            require_uppercase: true,
+            require_lowercase: true,
+            require_digit: true,
+            require_symbol: true,
+        };
+        assert_eq!(rules(&strict, ""), ["min_length", "uppercase", "lowercase", "digit", "symbol"]);
+        assert_eq!(rules(&strict, "abcd"), ["uppercase", "digit", "symbol"]);
+        assert!(rules(&strict, "aB3!").is_empty());
+        // Whitespace is no symbol, and only ASCII digits count
+        assert_eq!(rules(&strict, "aB3 "), ["symbol"]);
+        assert_eq!(rules(&strict, "aB٣!"), ["digit"]);
+    }
+
+    #[test]
+    fn check_lists_the_violations() {
+        match policy(8).check("short") {
+            Err(crate::error::AppError::WeakPassword(violations)) => {
+                assert_eq!(violations[0].message, "Must be at least 8 characters long");
+            }
+            other => panic!("expected WeakPassword, got {:?}", other.map_err(|e| e.to_string())),
+        }
+        assert!(policy(8).check("long enough").is_ok());
+    }
+
+    #[test]
+    fn describe_names_the_required_classes() {
+        let mut policy = policy(10);
+        assert_eq!(policy.describe(), "at least 10 characters");
+        policy.require_digit = true;
+        policy.require_uppercase = true;
+        assert_eq!(policy.describe(), "at least 10 characters, an uppercase letter, a digit");
+    }
+}
@@ -0,0 +1,151 @@
+//! Resolves the bucket a project's objects live in.
+//!
+//! Projects with a `project_storage_configs` row use their own bucket and credentials;
+//! everything else uses the global `S3_*` bucket, or whatever [`set_default`] installed in
+//! its place. Every path that touches a project's objects (uploads, worker, presigning,
+//! deletes, purges) should go through [`for_project`] rather than `S3Service::new()`.
+//!
+//! Clients are cached for `STORAGE_CLIENT_CACHE_TTL_SECS`. [`invalidate`] only reaches this
+//! process, so other instances pick up changed credentials once their entry expires.
+
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use std::time::{Duration, Instant};
+
+use lru::LruCache;
+use sea_orm::{DatabaseConnection, EntityTrait};
+use uuid::Uuid;
+
+use crate::config::get_config;
+use crate::entities::project_storage_config;
+use crate::error::AppError;
+use crate::services::s3::{S3Service, StorageTarget};
+use crate::services::storage::Storage;
+use crate::utils::{net, secret_box};
+
+struct Entry {
+    service: Arc<dyn Storage>,
+    cached_at: Instant,
+}
+
+/// Resolved clients by project, including projects that use the global bucket.
+static CLIENTS: OnceLock<Mutex<LruCache<Uuid, Entry>>> = OnceLock::new();
+
+/// Replaces the global bucket when set.
+static DEFAULT_STORAGE: RwLock<Option<Arc<dyn Storage>>> = RwLock::new(None);
+
+fn clients() -> &'static Mutex<LruCache<Uuid, Entry>> {
+    CLIENTS.get_or_init(|| {
+        let size = NonZeroUsize::new(get_config().storage_client_cache_size).unwrap_or(NonZeroUsize::MIN);
+        Mutex::new(LruCache::new(size))
+    })
+}
+
+fn ttl() -> Duration {
+    Duration::from_secs(get_config().storage_client_cache_ttl_secs)
+}
+
+/// The storage client for `project_id`, served from the LRU while its entry is fresh.
+pub async fn for_project(db: &DatabaseConnection, project_id: Uuid) -> Result<Arc<dyn Storage>, AppError> {
+    {
+        let mut clients = clients().lock().unwrap();
+        match clients.get(&project_id) {
+            Some(entry) if entry.cached_at.elapsed() < ttl() => return Ok(entry.service.clone()),
+            Some(_) => {
+                clients.pop(&project_id);
+            }
+            None => {}
+        }
+    }
+
+    let service: Arc<dyn Storage> = match project_storage_config::Entity::find_by_id(project_id).one(db).await? {
+        Some(config) => Arc::new(S3Service::for_target(target_for(&config)?)),
+        None => default_storage().await,
+    };
+    if !ttl().is_zero() {
+        clients().lock().unwrap().put(project_id, Entry { service: service.clone(), cached_at: Instant::now() });
+    }
+    Ok(service)
+}
+
+/// Serves every project without its own bucket from `storage` instead of the global `S3_*`
+/// bucket, e.g. a `MemoryStorage` in tests. Clients resolved before are dropped.
+pub fn set_default(storage: Arc<dyn Storage>) {
+    *DEFAULT_STORAGE.write().unwrap() = Some(storage);
+    clients().lock().unwrap().clear();
+}
+
+async fn default_storage() -> Arc<dyn Storage> {
+    let installed = DEFAULT_STORAGE.read().unwrap().clone();
+    match installed {
+        Some(storage) => storage,
+        None => Arc::new(S3Service::new().await),
+    }
+}
+
+/// Drops the cached client after a project's storage config changes.
+pub fn invalidate(project_id: Uuid) {
+    clients().lock().unwrap().pop(&project_id);
+}
+
+/// Refuses a target whose S3 host resolves to a loopback, private, link-local or otherwise
+/// non-public address, so a project's bucket settings cannot point the server at internal
+/// services. Hosts listed in `STORAGE_PRIVATE_ENDPOINT_HOSTS` (and their subdomains) are let
+/// through. Without an endpoint the host is AWS's, built from the validated region.
+pub async fn check_target(target: &StorageTarget) -> Result<(), String> {
+    let Some(endpoint) = &target.endpoint else {
+        return Ok(());
+    };
+    let url = url::Url::parse(endpoint).map_err(|_| "endpoint must be an http(s) URL".to_string())?;
+    let port = url.port_or_known_default().unwrap_or(443);
+    let listed = &get_config().storage_private_endpoint_hosts;
+    let host = match url.host() {
+        Some(url::Host::Domain(host)) => host.to_lowercase(),
+        Some(literal) => {
+            if net::host_listed(&literal.to_string(), listed) {
+                return Ok(());
+            }
+            return net::check_public_host(&literal, port).await.map_err(|e| format!("endpoint {}", e));
+        }
+        None => return Err("endpoint must be an http(s) URL".to_string()),
+    };
+
+    // Virtual-hosted addressing puts the bucket in front of the endpoint's host
+    let mut hosts = vec![host.clone()];
+    if !target.force_path_style {
+        hosts.push(format!("{}.{}", target.bucket, host));
+    }
+    for host in hosts.iter().filter(|h| !net::host_listed(h, listed)) {
+        net::check_public_host(&url::Host::Domain(host.as_str()), port)
+            .await
+            .map_err(|e| format!("endpoint {}", e))?;
+    }
+    Ok(())
+}
+
+/// Decrypts a stored config into something `S3Service` can connect with.
+pub fn target_for(config: &project_storage_config::Model) -> Result<StorageTarget, AppError> {
+    let key = credentials_key()?;
+    let secret = secret_box::open(&key, config.project_id.as_bytes(), &config.secret_access_key_sealed)
+        .map_err(|e| AppError::InternalServerError(format!("Storage credentials for project {} cannot be decrypted: {}", config.project_id, e)))?;
+
+    Ok(StorageTarget {
+        bucket: config.bucket.clone(),
+        region: config.region.clone(),
+        endpoint: config.endpoint.clone(),
+        access_key_id: config.access_key_id.clone(),
+        secret_access_key: secret,
+        force_path_style: config.force_path_style,
+        public_objects: config.public_objects,
+    })
+}
+
+pub fn seal_secret(project_id: Uuid, secret: &str) -> Result<String, AppError> {
+    secret_box::seal(&credentials_key()?, project_id.as_bytes(), secret).map_err(AppError::InternalServerError)
+}
+
+fn credentials_key() -> Result<[u8; secret_box::KEY_LEN], AppError> {
+    get_config()
+        .storage_credentials_key
+        .ok_or_else(|| AppError::ServiceUnavailable("Per-project storage requires STORAGE_CREDENTIALS_KEY to be configured".to_string()))
+}
@@ -0,0 +1,195 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use sea_orm::sea_query::Expr;
+use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QuerySelect, Set};
+use serde::Serialize;
+use tokio::time::Instant;
+use uuid::Uuid;
+
+use crate::entities::{file, job, project};
+use crate::error::AppError;
+use crate::models::job::{JobPayload, ReconcileProgress};
+use crate::services::project_storage;
+use crate::services::worker::JobError;
+
+/// Keys requested per ListObjectsV2 call (S3's maximum).
+const LIST_PAGE_SIZE: i32 = 1000;
+
+/// Stored under `result` of a completed `reconcile_storage` job.
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub struct ReconcileReport {
+    pub objects: u64,
+    /// Every object under the project prefix
+    pub stored_bytes: i64,
+    /// Part of `stored_bytes` taken by generated variants, which `files.size` does not count
+    pub variant_bytes: i64,
+    /// `SUM(files.size)` of the project
+    pub db_bytes: i64,
+    /// `stored_bytes - variant_bytes - db_bytes`
+    pub drift_bytes: i64,
+    /// `|drift_bytes|` is above `STORAGE_DRIFT_THRESHOLD_BYTES`
+    pub flagged: bool,
+}
+
+/// Whether `drift_bytes` is large enough to show up as flagged.
+pub fn exceeds_threshold(drift_bytes: i64) -> bool {
+    drift_bytes.unsigned_abs() > crate::config::get_config().storage_drift_threshold_bytes as u64
+}
+
+fn job_type() -> sea_orm::sea_query::SimpleExpr {
+    Expr::cust(format!("COALESCE(payload->>'type', '{}')", JobPayload::LEGACY_TYPE))
+}
+
+/// Queues a `reconcile_storage` job for the project, or returns the one already pending or running.
+pub async fn enqueue(db: &DatabaseConnection, project_id: Uuid) -> Result<Uuid, AppError> {
+    let in_flight = job::Entity::find()
+        .filter(job::Column::Status.is_in(["pending", "processing"]))
+        .filter(Expr::expr(job_type()).eq("reconcile_storage"))
+        .filter(Expr::cust_with_values("payload->>'project_id' = $1", [project_id.to_string()]))
+        .one(db)
+        .await?;
+    if let Some(existing) = in_flight {
+        return Ok(existing.id);
+    }
+
+    let job = job::ActiveModel {
+        id: Set(Uuid::new_v4()),
+        file_id: Set(None),
+        project_id: Set(Some(project_id)),
+        status: Set("pending".to_string()),
+        payload: Set(JobPayload::ReconcileStorage {
+            project_id,
+            progress: ReconcileProgress::default(),
+        }.to_value()),
+        created_at: Set(chrono::Utc::now()),
+        updated_at: Set(chrono::Utc::now()),
+    };
+    let job = job.insert(db).await?;
+    Ok(job.id)
+}
+
+/// Runs a `ReconcileStorage` job from `progress` onwards.
+///
+/// Lists the project prefix page by page, at most `RECONCILE_LIST_PAGES_PER_SEC` pages per
+/// second, saving progress after each page. A failed listing aborts the run with the progress
+/// saved, so a job requeued during a storage outage resumes the listing. At the end the totals
+/// are compared with `files.size` and written to the project.
+pub async fn run(
+    db: &DatabaseConnection,
+    job_id: Uuid,
+    project_id: Uuid,
+    mut progress: ReconcileProgress,
+) -> Result<ReconcileReport, JobError> {
+    let project = project::Entity::find_by_id(project_id)
+        .one(db)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("Project not found")?;
+
+    let pages_per_sec = crate::config::get_config().reconcile_list_pages_per_sec;
+    let page_interval = (pages_per_sec > 0).then(|| Duration::from_secs(1) / pages_per_sec);
+    let mut next_page = Instant::now();
+
+    let storage = project_storage::for_project(db, project_id).await?;
+    let variant_keys = variant_keys(db, project_id).await.map_err(|e| e.to_string())?;
+    let prefix = format!("{}/", project.storage_prefix);
+
+    loop {
+        if let Some(interval) = page_interval {
+            tokio::time::sleep_until(next_page).await;
+            next_page = Instant::now() + interval;
+        }
+
+        let page = storage
+            .list_objects(&prefix, LIST_PAGE_SIZE, progress.continuation_token.clone())
+            .await?;
+
+        for object in &page.objects {
+            progress.objects += 1;
+            progress.stored_bytes += object.size;
+            if variant_keys.contains(&object.key) {
+                progress.variant_bytes += object.size;
+            }
+        }
+
+        progress.continuation_token = page.next_continuation_token;
+        save_progress(db, job_id, project_id, &progress).await?;
+        if progress.continuation_token.is_none() {
+            break;
+        }
+    }
+
+    let db_bytes: i64 = file::Entity::find()
+        .select_only()
+        .column_as(Expr::cust("COALESCE(SUM(size), 0)::BIGINT"), "bytes")
+        .filter(file::Column::ProjectId.eq(project_id))
+        .into_tuple()
+        .one(db)
+        .await
+        .map_err(|e| e.to_string())?
+        .unwrap_or(0);
+
+    let drift_bytes = progress.stored_bytes - progress.variant_bytes - db_bytes;
+    project::Entity::update_many()
+        .col_expr(project::Column::LastReconciledAt, Expr::value(chrono::Utc::now()))
+        .col_expr(project::Column::ReconciledBytes, Expr::value(progress.stored_bytes))
+        .col_expr(project::Column::ReconciledDriftBytes, Expr::value(drift_bytes))
+        .filter(project::Column::Id.eq(project_id))
+        .exec(db)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let report = ReconcileReport {
+        objects: progress.objects,
+        stored_bytes: progress.stored_bytes,
+        variant_bytes: progress.variant_bytes,
+        db_bytes,
+        drift_bytes,
+        flagged: exceeds_threshold(drift_bytes),
+    };
+    println!(
+        "Reconcile | job={} | project={} | objects={} | stored={} | variants={} | db={} | drift={} | flagged={}",
+        job_id, project_id, report.objects, report.stored_bytes, report.variant_bytes, report.db_bytes, report.drift_bytes, report.flagged
+    );
+    Ok(report)
+}
+
+/// Object keys of the project's variants. Legacy entries stored as full URLs are not matched,
+/// so their bytes count as drift.
+async fn variant_keys(db: &DatabaseConnection, project_id: Uuid) -> Result<HashSet<String>, sea_orm::DbErr> {
+    let rows: Vec<serde_json::Value> = file::Entity::find()
+        .select_only()
+        .column(file::Column::VariantsJson)
+        .filter(file::Column::ProjectId.eq(project_id))
+        .into_tuple()
+        .all(db)
+        .await?;
+
+    Ok(rows
+        .iter()
+        .filter_map(|variants| variants.as_object())
+        .flat_map(|variants| variants.values().filter_map(|v| v.as_str()).map(str::to_string))
+        .collect())
+}
+
+async fn save_progress(
+    db: &DatabaseConnection,
+    job_id: Uuid,
+    project_id: Uuid,
+    progress: &ReconcileProgress,
+) -> Result<(), String> {
+    let payload = JobPayload::ReconcileStorage {
+        project_id,
+        progress: progress.clone(),
+    };
+
+    job::Entity::update_many()
+        .col_expr(job::Column::Payload, Expr::value(payload.to_value()))
+        .col_expr(job::Column::UpdatedAt, Expr::value(chrono::Utc::now()))
+        .filter(job::Column::Id.eq(job_id))
+        .exec(db)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
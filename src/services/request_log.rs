@@ -0,0 +1,32 @@
+use std::time::Duration;
+use sea_orm::DatabaseConnection;
+
+use crate::entities::request_log;
+use crate::services::batch_writer::{BatchWriter, Batching};
+
+const BATCHING: Batching = Batching {
+    capacity: 8192,
+    max_batch: 512,
+    interval: Duration::from_secs(1),
+};
+
+/// Best-effort, fire-and-forget writer for `request_logs`.
+///
+/// `record` never waits on the database, so logging adds no latency to uploads. Entries
+/// are inserted in batches by a [`BatchWriter`] and discarded when its buffer is full. A
+/// project hard-deleted mid-flight fails the FK for the whole batch, which is then lost.
+#[derive(Clone)]
+pub struct RequestLogRecorder {
+    writer: BatchWriter<request_log::ActiveModel>,
+}
+
+impl RequestLogRecorder {
+    /// Spawns the flush task.
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { writer: BatchWriter::inserting(db, BATCHING, "Request logs") }
+    }
+
+    pub fn record(&self, entry: request_log::ActiveModel) {
+        self.writer.send(entry);
+    }
+}
@@ -1,117 +1,338 @@
 use aws_sdk_s3::Client;
+use aws_sdk_s3::error::{DisplayErrorContext, ProvideErrorMetadata, SdkError};
 use aws_sdk_s3::primitives::ByteStream;
 use crate::config::get_config;
 use crate::error::AppError;
+use crate::services::storage::{ObjectPage, ObjectSummary, Storage};
+use async_trait::async_trait;
+use serde::Serialize;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::OnceCell;
+
+/// Set once the bucket has been verified in this process.
+static BUCKET_READY: OnceCell<()> = OnceCell::const_new();
+
+/// Shared by every global-bucket `S3Service`, since handlers build their own client per request.
+static DEFAULT_BREAKER: LazyLock<Arc<CircuitBreaker>> = LazyLock::new(|| Arc::new(CircuitBreaker::new()));
+
+/// Counts consecutive storage outages (timeouts, connection failures, 5xx). Past
+/// `S3_CIRCUIT_BREAKER_THRESHOLD` calls fail fast with `ServiceUnavailable` until the
+/// cool-down ends; the next call is then let through as a trial and re-opens it on failure.
+struct CircuitBreaker {
+    failures: AtomicU32,
+    open_until: Mutex<Option<Instant>>,
+}
+
+impl CircuitBreaker {
+    const fn new() -> Self {
+        Self {
+            failures: AtomicU32::new(0),
+            open_until: Mutex::new(None),
+        }
+    }
+
+    fn is_open(&self) -> bool {
+        self.open_until
+            .lock()
+            .unwrap()
+            .is_some_and(|until| Instant::now() < until)
+    }
+
+    fn check(&self) -> Result<(), AppError> {
+        if self.is_open() {
+            return Err(AppError::ServiceUnavailable("Storage is temporarily unavailable".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Feeds an SDK result into the breaker.
+    fn observe<T, E>(&self, result: &Result<T, SdkError<E>>) {
+        match result {
+            Ok(_) => self.record_success(),
+            Err(e) if is_outage(e) => self.record_failure(),
+            // The service answered (missing key, access denied, ...), so it is up
+            Err(_) => self.record_success(),
+        }
+    }
+
+    fn record_success(&self) {
+        if self.failures.swap(0, Ordering::Relaxed) > 0 {
+            let mut open_until = self.open_until.lock().unwrap();
+            if open_until.take().is_some() {
+                println!("S3 | circuit closed | storage reachable again");
+            }
+        }
+    }
+
+    fn record_failure(&self) {
+        let config = get_config();
+        let failures = self.failures.fetch_add(1, Ordering::Relaxed) + 1;
+        if config.s3_circuit_breaker_threshold == 0 || failures < config.s3_circuit_breaker_threshold {
+            return;
+        }
+        let cooldown = Duration::from_secs(config.s3_circuit_breaker_cooldown_secs);
+        let mut open_until = self.open_until.lock().unwrap();
+        if !open_until.is_some_and(|until| Instant::now() < until) {
+            eprintln!("S3 | circuit open | {} consecutive failures | failing fast for {}s", failures, cooldown.as_secs());
+        }
+        *open_until = Some(Instant::now() + cooldown);
+    }
+}
+
+/// Short reason for showing to users: S3's error code and message, or the transport error chain.
+fn describe<E: ProvideErrorMetadata + std::error::Error + 'static>(e: &SdkError<E>) -> String {
+    match e {
+        SdkError::ServiceError(ctx) => {
+            let err = ctx.err();
+            format!("{}: {}", err.code().unwrap_or("UnknownError"), err.message().unwrap_or("no message"))
+        }
+        other => DisplayErrorContext(other).to_string(),
+    }
+}
+
+/// Failures that say nothing about the request itself, only that storage is unhealthy.
+fn is_outage<E>(e: &SdkError<E>) -> bool {
+    match e {
+        SdkError::TimeoutError(_) | SdkError::DispatchFailure(_) | SdkError::ResponseError(_) => true,
+        SdkError::ServiceError(ctx) => ctx.raw().status().is_server_error(),
+        _ => false,
+    }
+}
+
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub struct BucketReport {
+    pub bucket: String,
+    /// Whether the bucket had to be created
+    pub created: bool,
+    /// `applied`, `skipped` (S3_PUBLIC_OBJECTS=false) or `failed: <reason>`
+    pub policy: String,
+}
+
+/// Outcome of one storage diagnostics check.
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub struct DiagnosticCheck {
+    pub ok: bool,
+    /// Why the check failed, or why it was skipped
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+impl DiagnosticCheck {
+    fn passed() -> Self {
+        Self { ok: true, detail: None }
+    }
+
+    fn failed(detail: impl Into<String>) -> Self {
+        Self { ok: false, detail: Some(detail.into()) }
+    }
+
+    fn from_result(result: Result<(), String>) -> Self {
+        match result {
+            Ok(()) => Self::passed(),
+            Err(e) => Self::failed(e),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub struct BucketDiagnostics {
+    pub bucket: String,
+    pub region: String,
+    /// `S3_ENDPOINT`, or the AWS regional endpoint when unset
+    pub endpoint: String,
+    /// HeadBucket succeeded
+    pub reachable: DiagnosticCheck,
+    /// `present`, `none`, or `unavailable: <reason>` (GetBucketPolicy)
+    pub policy: String,
+    /// A temporary object was accepted with a public-read ACL
+    pub acl: DiagnosticCheck,
+    /// A temporary object was written and read back through a presigned URL
+    pub presign: DiagnosticCheck,
+}
+
+/// Where objects go: the global `S3_*` bucket or a project's own bucket.
+#[derive(Clone)]
+pub struct StorageTarget {
+    pub bucket: String,
+    pub region: String,
+    pub endpoint: Option<String>,
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub force_path_style: bool,
+    /// Put objects with a public-read ACL
+    pub public_objects: bool,
+}
+
+impl StorageTarget {
+    pub fn global() -> Self {
+        let config = get_config();
+        Self {
+            bucket: config.s3_bucket_name.clone(),
+            region: config.aws_region.clone(),
+            endpoint: config.s3_endpoint.clone(),
+            access_key_id: config.aws_access_key_id.clone(),
+            secret_access_key: config.aws_secret_access_key.clone(),
+            force_path_style: config.s3_force_path_style,
+            public_objects: config.s3_public_objects,
+        }
+    }
+}
 
 #[derive(Clone)]
 pub struct S3Service {
     client: Client,
     pub bucket_name: String,
+    region: String,
+    endpoint: Option<String>,
+    public_objects: bool,
+    /// The global bucket may be created and given a policy; project buckets are only probed
+    managed: bool,
+    breaker: Arc<CircuitBreaker>,
 }
 
 impl S3Service {
+    /// Client for the global `S3_*` bucket.
     pub async fn new() -> Self {
+        Self::build(StorageTarget::global(), true, DEFAULT_BREAKER.clone())
+    }
+
+    /// Client for a project's own bucket, with its own circuit breaker so one customer's
+    /// outage does not fail everyone else's uploads.
+    pub fn for_target(target: StorageTarget) -> Self {
+        Self::build(target, false, Arc::new(CircuitBreaker::new()))
+    }
+
+    fn build(target: StorageTarget, managed: bool, breaker: Arc<CircuitBreaker>) -> Self {
         let config = get_config();
         
         let credentials = aws_sdk_s3::config::Credentials::new(
-            config.aws_access_key_id.clone(),
-            config.aws_secret_access_key.clone(),
+            target.access_key_id,
+            target.secret_access_key,
             None,
             None,
             "manual_config",
         );
 
-        let region = aws_sdk_s3::config::Region::new(config.aws_region.clone());
+        let region = aws_sdk_s3::config::Region::new(target.region.clone());
         
         let mut s3_config_builder = aws_sdk_s3::config::Builder::new()
             .behavior_version(aws_sdk_s3::config::BehaviorVersion::latest())
             .region(region)
             .credentials_provider(credentials);
         
-        if let Some(endpoint) = &config.s3_endpoint {
-            s3_config_builder = s3_config_builder
-                .endpoint_url(endpoint)
-                .force_path_style(true);
+        if let Some(endpoint) = &target.endpoint {
+            s3_config_builder = s3_config_builder.endpoint_url(endpoint);
         }
+        s3_config_builder = s3_config_builder
+            .force_path_style(target.force_path_style)
+            .timeout_config(
+                aws_sdk_s3::config::timeout::TimeoutConfig::builder()
+                    .operation_timeout(Duration::from_secs(config.s3_operation_timeout_secs))
+                    .connect_timeout(Duration::from_secs(config.s3_connect_timeout_secs))
+                    .build(),
+            );
 
         let client = Client::from_conf(s3_config_builder.build());
 
         Self {
             client,
-            bucket_name: config.s3_bucket_name.clone(),
+            bucket_name: target.bucket,
+            region: target.region,
+            endpoint: target.endpoint,
+            public_objects: target.public_objects,
+            managed,
+            breaker,
         }
     }
 
-    pub async fn put_object(
-        &self,
-        key: &str,
-        data: Vec<u8>,
-        content_type: &str,
-    ) -> Result<(), AppError> {
+    /// Whether the global bucket's circuit breaker is currently failing storage calls fast.
+    pub fn circuit_open() -> bool {
+        DEFAULT_BREAKER.is_open()
+    }
+
+    /// Writes and deletes a small object, proving the credentials can store and clean up.
+    /// Errors carry S3's reason since they are shown to whoever is configuring the bucket.
+    pub async fn probe(&self) -> Result<(), String> {
+        let key = format!(".mediablobkit-probe-{}", uuid::Uuid::new_v4());
         self.client
             .put_object()
             .bucket(&self.bucket_name)
-            .key(key)
-            .body(ByteStream::from(data))
-            .content_type(content_type)
-            .acl(aws_sdk_s3::types::ObjectCannedAcl::PublicRead)
+            .key(&key)
+            .body(ByteStream::from_static(b"probe"))
             .send()
             .await
-            .map_err(|e| {
-                eprintln!("S3 Upload Error: {:?}", e);
-                AppError::InternalServerError(format!("Failed to upload file to S3: {}", e))
-            })?;
-
-        Ok(())
-    }
-
-    pub async fn get_object(&self, key: &str) -> Result<Vec<u8>, AppError> {
-        let resp = self.client
-            .get_object()
+            .map_err(|e| format!("PutObject failed: {}", describe(&e)))?;
+        self.client
+            .delete_object()
             .bucket(&self.bucket_name)
-            .key(key)
+            .key(&key)
             .send()
             .await
-            .map_err(|e| {
-                eprintln!("S3 Download Error: {:?}", e);
-                AppError::InternalServerError(format!("Failed to download file from S3: {}", e))
-            })?;
-
-        let data = resp.body.collect().await.map_err(|e| {
-             eprintln!("S3 Body Error: {:?}", e);
-             AppError::InternalServerError("Failed to read S3 body".to_string())
-        })?;
-
-        Ok(data.into_bytes().to_vec())
+            .map_err(|e| format!("DeleteObject failed: {}", describe(&e)))?;
+        Ok(())
     }
 
-    pub async fn ensure_bucket_exists(&self) -> Result<(), AppError> {
-        let resp = self.client.head_bucket().bucket(&self.bucket_name).send().await;
-        
-        match resp {
-            Ok(_) => {
-                // Bucket exists, ensure public policy
-                self.set_public_policy().await?;
-                Ok(())
-            },
+    /// Checks (and if needed creates) the bucket, then applies the public-read policy
+    /// when `S3_PUBLIC_OBJECTS` is enabled. Policy failures are reported, not fatal,
+    /// since many IAM roles lack `PutBucketPolicy` on an otherwise working bucket.
+    pub async fn verify_bucket(&self) -> Result<BucketReport, AppError> {
+        self.breaker.check()?;
+        let head = self.client.head_bucket().bucket(&self.bucket_name).send().await;
+        self.breaker.observe(&head);
+        let created = match head {
+            Ok(_) => false,
+            Err(e) if is_outage(&e) => {
+                eprintln!("S3 HeadBucket Error: {:?}", e);
+                return Err(AppError::ServiceUnavailable("Storage is unreachable".to_string()));
+            }
             Err(_) => {
                 // Bucket doesn't exist or no access, try to create it
                 println!("Bucket {} does not exist, attempting to create...", self.bucket_name);
-                self.client
+                let config = get_config();
+                let bucket_config = config
+                    .s3_provider
+                    .bucket_location_constraint(&config.aws_region)
+                    .map(|region| {
+                        aws_sdk_s3::types::CreateBucketConfiguration::builder()
+                            .location_constraint(aws_sdk_s3::types::BucketLocationConstraint::from(region))
+                            .build()
+                    });
+                let result = self.client
                     .create_bucket()
                     .bucket(&self.bucket_name)
+                    .set_create_bucket_configuration(bucket_config)
                     .send()
-                    .await
-                    .map_err(|e| {
-                        eprintln!("Failed to create bucket: {:?}", e);
-                        AppError::InternalServerError(format!("Failed to create S3 bucket: {}", e))
-                    })?;
-                
-                // Set public policy after creation
-                self.set_public_policy().await?;
-                Ok(())
+                    .await;
+                self.breaker.observe(&result);
+                result.map_err(|e| {
+                    eprintln!("Failed to create bucket: {:?}", e);
+                    AppError::InternalServerError(format!("Failed to create S3 bucket: {}", e))
+                })?;
+                true
             }
-        }
+        };
+
+        let policy = if !get_config().s3_public_objects {
+            "skipped".to_string()
+        } else {
+            match self.set_public_policy().await {
+                Ok(()) => "applied".to_string(),
+                Err(e) => {
+                    eprintln!("WARNING: Bucket {} is usable but its public-read policy could not be applied: {}", self.bucket_name, e);
+                    format!("failed: {}", e)
+                }
+            }
+        };
+
+        let _ = BUCKET_READY.set(());
+
+        Ok(BucketReport {
+            bucket: self.bucket_name.clone(),
+            created,
+            policy,
+        })
     }
 
     async fn set_public_policy(&self) -> Result<(), AppError> {
@@ -138,8 +359,6 @@ impl S3Service {
             .send()
             .await
             .map_err(|e| {
-                eprintln!("Failed to set bucket policy: {:?}", e);
-                // Don't fail the request if policy setting fails, just log it
                 // Some S3 providers might not support this or require different permissions
                 AppError::InternalServerError(format!("Failed to set bucket policy: {}", e))
             })?;
@@ -147,27 +366,246 @@ impl S3Service {
         Ok(())
     }
 
+    /// Runs every storage check and reports each outcome instead of stopping at the first
+    /// failure. Bypasses the circuit breaker so it still works while the breaker is open;
+    /// temporary objects are deleted afterwards.
+    pub async fn diagnose(&self) -> BucketDiagnostics {
+        let endpoint = self
+            .endpoint
+            .clone()
+            .unwrap_or_else(|| format!("https://s3.{}.amazonaws.com", self.region));
+
+        let reachable = match self.client.head_bucket().bucket(&self.bucket_name).send().await {
+            Ok(_) => DiagnosticCheck::passed(),
+            Err(e) => DiagnosticCheck::failed(describe(&e)),
+        };
+        if !reachable.ok {
+            return BucketDiagnostics {
+                bucket: self.bucket_name.clone(),
+                region: self.region.clone(),
+                endpoint,
+                reachable,
+                policy: "unavailable: bucket unreachable".to_string(),
+                acl: DiagnosticCheck::failed("skipped: bucket unreachable"),
+                presign: DiagnosticCheck::failed("skipped: bucket unreachable"),
+            };
+        }
+
+        let policy = match self.client.get_bucket_policy().bucket(&self.bucket_name).send().await {
+            Ok(_) => "present".to_string(),
+            Err(e) if e.code() == Some("NoSuchBucketPolicy") => "none".to_string(),
+            Err(e) => format!("unavailable: {}", describe(&e)),
+        };
+
+        BucketDiagnostics {
+            bucket: self.bucket_name.clone(),
+            region: self.region.clone(),
+            endpoint,
+            reachable,
+            policy,
+            acl: DiagnosticCheck::from_result(self.probe_acl().await),
+            presign: DiagnosticCheck::from_result(self.probe_presign().await),
+        }
+    }
 
+    async fn probe_acl(&self) -> Result<(), String> {
+        let key = format!(".mediablobkit-acl-probe-{}", uuid::Uuid::new_v4());
+        self.client
+            .put_object()
+            .bucket(&self.bucket_name)
+            .key(&key)
+            .body(ByteStream::from_static(b"probe"))
+            .acl(aws_sdk_s3::types::ObjectCannedAcl::PublicRead)
+            .send()
+            .await
+            .map_err(|e| format!("PutObject with public-read ACL failed: {}", describe(&e)))?;
+        self.remove_probe_object(&key).await;
+        Ok(())
+    }
 
-    pub async fn delete_object(&self, key: &str) -> Result<(), AppError> {
+    async fn probe_presign(&self) -> Result<(), String> {
+        let key = format!(".mediablobkit-presign-probe-{}", uuid::Uuid::new_v4());
+        let body = uuid::Uuid::new_v4().to_string();
         self.client
+            .put_object()
+            .bucket(&self.bucket_name)
+            .key(&key)
+            .body(ByteStream::from(body.clone().into_bytes()))
+            .send()
+            .await
+            .map_err(|e| format!("PutObject failed: {}", describe(&e)))?;
+
+        let result = async {
+            let url = self
+                .get_presigned_url(&key, Duration::from_secs(60))
+                .await
+                .map_err(|e| e.to_string())?;
+            let (status, fetched) = http_get(&url).await?;
+            if status != 200 {
+                return Err(format!("GET on the presigned URL returned {}", status));
+            }
+            if fetched != body.as_bytes() {
+                return Err("GET on the presigned URL returned different content".to_string());
+            }
+            Ok(())
+        }
+        .await;
+
+        self.remove_probe_object(&key).await;
+        result
+    }
+
+    async fn remove_probe_object(&self, key: &str) {
+        if let Err(e) = self.client.delete_object().bucket(&self.bucket_name).key(key).send().await {
+            eprintln!("WARNING: Could not delete diagnostics object {}/{}: {}", self.bucket_name, key, describe(&e));
+        }
+    }
+}
+
+#[async_trait]
+impl Storage for S3Service {
+    fn bucket_name(&self) -> &str {
+        &self.bucket_name
+    }
+
+    fn is_default_bucket(&self) -> bool {
+        self.managed
+    }
+
+    fn object_url(&self, key: &str) -> String {
+        match &self.endpoint {
+            Some(endpoint) => format!("{}/{}/{}", endpoint, self.bucket_name, key),
+            None => format!("https://{}.s3.{}.amazonaws.com/{}", self.bucket_name, self.region, key),
+        }
+    }
+
+    /// Makes sure the bucket is usable, at most once per process.
+    ///
+    /// Uploads call this on every request; only the first successful check talks to S3.
+    /// A failed check is not cached, so the next upload retries it.
+    async fn ensure_bucket_exists(&self) -> Result<(), AppError> {
+        if !self.managed {
+            // Project buckets are probed when their config is saved and never created here
+            return Ok(());
+        }
+        BUCKET_READY
+            .get_or_try_init(|| async { self.verify_bucket().await.map(|_| ()) })
+            .await?;
+        Ok(())
+    }
+
+    async fn put_object_as(
+        &self,
+        key: &str,
+        data: Vec<u8>,
+        content_type: &str,
+        content_disposition: Option<String>,
+    ) -> Result<(), AppError> {
+        self.breaker.check()?;
+        let result = self.client
+            .put_object()
+            .bucket(&self.bucket_name)
+            .key(key)
+            .body(ByteStream::from(data))
+            .content_type(content_type)
+            .set_content_disposition(content_disposition)
+            .set_acl(self.public_objects.then_some(aws_sdk_s3::types::ObjectCannedAcl::PublicRead))
+            .send()
+            .await;
+        self.breaker.observe(&result);
+        result.map_err(|e| {
+            eprintln!("S3 Upload Error: {:?}", e);
+            AppError::InternalServerError(format!("Failed to upload file to S3: {}", e))
+        })?;
+
+        Ok(())
+    }
+
+    async fn get_object(&self, key: &str) -> Result<Vec<u8>, AppError> {
+        self.breaker.check()?;
+        let result = self.client
+            .get_object()
+            .bucket(&self.bucket_name)
+            .key(key)
+            .send()
+            .await;
+        self.breaker.observe(&result);
+        let resp = result.map_err(|e| {
+            eprintln!("S3 Download Error: {:?}", e);
+            AppError::InternalServerError(format!("Failed to download file from S3: {}", e))
+        })?;
+
+        let data = resp.body.collect().await.map_err(|e| {
+             eprintln!("S3 Body Error: {:?}", e);
+             AppError::InternalServerError("Failed to read S3 body".to_string())
+        })?;
+
+        Ok(data.into_bytes().to_vec())
+    }
+
+    /// One page of keys under `prefix` (ListObjectsV2), resuming from `continuation_token`.
+    async fn list_objects(
+        &self,
+        prefix: &str,
+        max_keys: i32,
+        continuation_token: Option<String>,
+    ) -> Result<ObjectPage, AppError> {
+        self.breaker.check()?;
+        let result = self.client
+            .list_objects_v2()
+            .bucket(&self.bucket_name)
+            .prefix(prefix)
+            .max_keys(max_keys)
+            .set_continuation_token(continuation_token)
+            .send()
+            .await;
+        self.breaker.observe(&result);
+        let resp = result.map_err(|e| {
+            eprintln!("S3 List Error: {:?}", e);
+            AppError::InternalServerError(format!("Failed to list S3 objects: {}", e))
+        })?;
+
+        let objects = resp
+            .contents()
+            .iter()
+            .map(|o| ObjectSummary {
+                key: o.key().unwrap_or_default().to_string(),
+                size: o.size().unwrap_or_default(),
+                last_modified: o
+                    .last_modified()
+                    .and_then(|t| t.fmt(aws_sdk_s3::primitives::DateTimeFormat::DateTime).ok()),
+            })
+            .collect();
+
+        Ok(ObjectPage {
+            objects,
+            next_continuation_token: resp.next_continuation_token().map(str::to_string),
+        })
+    }
+
+    async fn delete_object(&self, key: &str) -> Result<(), AppError> {
+        self.breaker.check()?;
+        let result = self.client
             .delete_object()
             .bucket(&self.bucket_name)
             .key(key)
             .send()
-            .await
-            .map_err(|e| {
-                eprintln!("S3 Delete Error: {}", e);
-                AppError::InternalServerError("Failed to delete file from S3".to_string())
-            })?;
+            .await;
+        self.breaker.observe(&result);
+        result.map_err(|e| {
+            eprintln!("S3 Delete Error: {}", e);
+            AppError::InternalServerError("Failed to delete file from S3".to_string())
+        })?;
 
         Ok(())
     }
 
-    pub async fn get_presigned_url(
-        &self, 
-        key: &str, 
-        expires_in: std::time::Duration
+    async fn get_presigned_url_as(
+        &self,
+        key: &str,
+        expires_in: std::time::Duration,
+        content_type: Option<String>,
+        content_disposition: Option<String>,
     ) -> Result<String, AppError> {
         let presigning_config = aws_sdk_s3::presigning::PresigningConfig::expires_in(expires_in)
             .map_err(|e| {
@@ -179,6 +617,8 @@ impl S3Service {
             .get_object()
             .bucket(&self.bucket_name)
             .key(key)
+            .set_response_content_type(content_type)
+            .set_response_content_disposition(content_disposition)
             .presigned(presigning_config)
             .await
             .map_err(|e| {
@@ -189,3 +629,35 @@ impl S3Service {
         Ok(presigned_req.uri().to_string())
     }
 }
+
+/// Plain GET as an outside client would make it, bounded by `S3_OPERATION_TIMEOUT_SECS`.
+async fn http_get(url: &str) -> Result<(u16, Vec<u8>), String> {
+    use http_body_util::{BodyExt, Empty};
+
+    let connector = hyper_rustls::HttpsConnectorBuilder::new()
+        .with_provider_and_native_roots(rustls::crypto::ring::default_provider())
+        .map_err(|e| format!("Could not load TLS roots: {}", e))?
+        .https_or_http()
+        .enable_http1()
+        .build();
+    let client = hyper_util::client::legacy::Client::builder(hyper_util::rt::TokioExecutor::new())
+        .build::<_, Empty<axum::body::Bytes>>(connector);
+
+    let uri: axum::http::Uri = url.parse().map_err(|e| format!("Invalid presigned URL: {}", e))?;
+    let timeout = Duration::from_secs(get_config().s3_operation_timeout_secs);
+    let response = tokio::time::timeout(timeout, async {
+        let response = client.get(uri).await.map_err(|e| format!("GET on the presigned URL failed: {}", e))?;
+        let status = response.status().as_u16();
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .map_err(|e| format!("Reading the presigned URL response failed: {}", e))?
+            .to_bytes();
+        Ok::<_, String>((status, body.to_vec()))
+    })
+    .await
+    .map_err(|_| format!("GET on the presigned URL timed out after {}s", timeout.as_secs()))??;
+
+    Ok(response)
+}
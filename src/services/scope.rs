@@ -0,0 +1,184 @@
+//! Who may see which files and jobs, plus the filters the listings share.
+//!
+//! Listings start from [`files`] or [`jobs`] rather than re-deriving the role rules, so a
+//! new endpoint cannot drift from the others. Su sees every project, Admin and User only the
+//! projects they own, a Viewer the projects it owns or that Su shared with it, and an API key
+//! only its own project.
+
+use chrono::NaiveDateTime;
+use sea_orm::sea_query::{LikeExpr, Query};
+use sea_orm::{
+    ColumnTrait, Condition, DatabaseConnection, EntityTrait, JoinType, PaginatorTrait, QueryFilter, QuerySelect,
+    RelationTrait, Select,
+};
+use uuid::Uuid;
+
+use crate::entities::{file, job, project, project_member, user::Role};
+use crate::error::AppError;
+use crate::middleware::auth::AuthUser;
+use crate::utils::escape_like;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    /// Every project (Su)
+    All,
+    /// Projects owned by this user (Admin, User)
+    Owner(Uuid),
+    /// Projects owned by this user or shared with it through `project_members` (Viewer)
+    Member(Uuid),
+    /// One project, for API-key callers
+    Project(Uuid),
+}
+
+impl Scope {
+    pub fn for_user(user: &AuthUser) -> Self {
+        match user.role {
+            Role::Su => Scope::All,
+            Role::Viewer => Scope::Member(user.id),
+            Role::Admin | Role::User => Scope::Owner(user.id),
+        }
+    }
+
+    /// The projects `/projects` lists for the caller. Like [`Scope::for_user`], except that
+    /// Su only gets its own there.
+    pub fn own(user: &AuthUser) -> Self {
+        match user.role {
+            Role::Viewer => Scope::Member(user.id),
+            Role::Su | Role::Admin | Role::User => Scope::Owner(user.id),
+        }
+    }
+
+    pub async fn includes(&self, db: &DatabaseConnection, project: &project::Model) -> Result<bool, AppError> {
+        match *self {
+            Scope::All => Ok(true),
+            Scope::Owner(owner_id) => Ok(project.owner_id == owner_id),
+            Scope::Member(user_id) if project.owner_id == user_id => Ok(true),
+            Scope::Member(_) => self.includes_id(db, project.id).await,
+            Scope::Project(project_id) => Ok(project.id == project_id),
+        }
+    }
+
+    /// Whether `project_id` is a project inside the scope. `All` answers without a query,
+    /// so unknown IDs count as visible there (and simply match nothing).
+    pub async fn includes_id(&self, db: &DatabaseConnection, project_id: Uuid) -> Result<bool, AppError> {
+        match *self {
+            Scope::All => Ok(true),
+            Scope::Project(id) => Ok(id == project_id),
+            Scope::Owner(_) | Scope::Member(_) => Ok(self
+                .restrict(project::Entity::find_by_id(project_id))
+                .count(db)
+                .await?
+                > 0),
+        }
+    }
+
+    /// Narrows a select that already joins `projects`.
+    fn restrict<E: EntityTrait>(self, select: Select<E>) -> Select<E> {
+        match self {
+            Scope::All => select,
+            Scope::Owner(owner_id) => select.filter(project::Column::OwnerId.eq(owner_id)),
+            Scope::Member(user_id) => select.filter(
+                Condition::any().add(project::Column::OwnerId.eq(user_id)).add(
+                    project::Column::Id.in_subquery(
+                        Query::select()
+                            .column(project_member::Column::ProjectId)
+                            .from(project_member::Entity)
+                            .and_where(project_member::Column::UserId.eq(user_id))
+                            .to_owned(),
+                    ),
+                ),
+            ),
+            Scope::Project(project_id) => select.filter(project::Column::Id.eq(project_id)),
+        }
+    }
+}
+
+/// Projects in the scope, trashed ones included.
+pub fn projects(scope: Scope) -> Select<project::Entity> {
+    scope.restrict(project::Entity::find())
+}
+
+/// Optional file filters; unset fields match everything.
+#[derive(Debug, Clone, Default)]
+pub struct FileFilters {
+    pub project_id: Option<Uuid>,
+    /// `uploaded`, `processing`, `ready` or `error`
+    pub status: Option<String>,
+    /// An exact type (`image/png`), or a prefix ending in `/` (`image/`)
+    pub mime_type: Option<String>,
+    /// Exact normalized folder (`a/b/`, `""` for the root)
+    pub folder: Option<String>,
+    /// Read as UTC; `created_after` is inclusive, `created_before` exclusive
+    pub created_after: Option<NaiveDateTime>,
+    pub created_before: Option<NaiveDateTime>,
+}
+
+/// Active files (see `file::Entity::find_active`) in the scope matching `filters`, unordered.
+pub fn files(scope: Scope, filters: &FileFilters) -> Select<file::Entity> {
+    let mut select = scope.restrict(file::Entity::find_active());
+
+    if let Some(project_id) = filters.project_id {
+        select = select.filter(file::Column::ProjectId.eq(project_id));
+    }
+    if let Some(status) = &filters.status {
+        select = select.filter(file::Column::Status.eq(status.as_str()));
+    }
+    if let Some(mime_type) = &filters.mime_type {
+        select = if mime_type.ends_with('/') {
+            select.filter(file::Column::MimeType.like(LikeExpr::new(format!("{}%", escape_like(mime_type))).escape('\\')))
+        } else {
+            select.filter(file::Column::MimeType.eq(mime_type.as_str()))
+        };
+    }
+    if let Some(folder) = &filters.folder {
+        select = select.filter(file::Column::Folder.eq(folder.as_str()));
+    }
+    if let Some(after) = filters.created_after {
+        select = select.filter(file::Column::CreatedAt.gte(after.and_utc()));
+    }
+    if let Some(before) = filters.created_before {
+        select = select.filter(file::Column::CreatedAt.lt(before.and_utc()));
+    }
+    select
+}
+
+/// Optional job filters; unset fields match everything.
+#[derive(Debug, Clone, Default)]
+pub struct JobFilters {
+    pub project_id: Option<Uuid>,
+    pub file_id: Option<Uuid>,
+    /// `pending`, `processing`, `completed` or `failed`
+    pub status: Option<String>,
+    /// Read as UTC; `created_after` is inclusive, `created_before` exclusive
+    pub created_after: Option<NaiveDateTime>,
+    pub created_before: Option<NaiveDateTime>,
+}
+
+/// Jobs in the scope matching `filters`, joined with their project and file (if any), unordered.
+///
+/// Unlike [`files`] this keeps jobs of trashed projects, which the admin job views still show.
+/// A backfill across all projects has no project and is only in the `All` scope.
+pub fn jobs(scope: Scope, filters: &JobFilters) -> Select<job::Entity> {
+    let mut select = scope.restrict(
+        job::Entity::find()
+            .join(JoinType::LeftJoin, job::Relation::Project.def())
+            .join(JoinType::LeftJoin, job::Relation::File.def()),
+    );
+
+    if let Some(project_id) = filters.project_id {
+        select = select.filter(job::Column::ProjectId.eq(project_id));
+    }
+    if let Some(file_id) = filters.file_id {
+        select = select.filter(job::Column::FileId.eq(file_id));
+    }
+    if let Some(status) = &filters.status {
+        select = select.filter(job::Column::Status.eq(status.as_str()));
+    }
+    if let Some(after) = filters.created_after {
+        select = select.filter(job::Column::CreatedAt.gte(after.and_utc()));
+    }
+    if let Some(before) = filters.created_before {
+        select = select.filter(job::Column::CreatedAt.lt(before.and_utc()));
+    }
+    select
+}
@@ -0,0 +1,81 @@
+//! Object storage as the rest of the app uses it.
+//!
+//! [`S3Service`](crate::services::s3::S3Service) is the production implementation and
+//! [`MemoryStorage`](crate::services::memory_storage::MemoryStorage) keeps objects in memory.
+//! Handlers and jobs get theirs from `project_storage::for_project`; bucket administration
+//! (creating, diagnosing, probing) stays on `S3Service`.
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+
+use crate::error::AppError;
+
+pub struct ObjectSummary {
+    pub key: String,
+    pub size: i64,
+    pub last_modified: Option<String>,
+}
+
+pub struct ObjectPage {
+    pub objects: Vec<ObjectSummary>,
+    /// Present when more keys remain after this page
+    pub next_continuation_token: Option<String>,
+}
+
+/// A bucket of objects. Errors follow the S3 client's: `ServiceUnavailable` while the store
+/// is unreachable (or failing fast), `InternalServerError` for everything else.
+#[async_trait]
+pub trait Storage: Send + Sync {
+    /// Name of the bucket, as found in legacy variant URLs.
+    fn bucket_name(&self) -> &str;
+
+    /// Whether this is the global bucket rather than a project's own.
+    fn is_default_bucket(&self) -> bool;
+
+    /// Direct (non-presigned) URL of an object in this bucket.
+    fn object_url(&self, key: &str) -> String;
+
+    /// Makes sure the bucket is usable; called once per upload request.
+    async fn ensure_bucket_exists(&self) -> Result<(), AppError>;
+
+    async fn put_object(&self, key: &str, data: Vec<u8>, content_type: &str) -> Result<(), AppError> {
+        self.put_object_as(key, data, content_type, None).await
+    }
+
+    /// Like `put_object`, storing `content_disposition` with the object so direct and CDN
+    /// URLs answer with it too.
+    async fn put_object_as(
+        &self,
+        key: &str,
+        data: Vec<u8>,
+        content_type: &str,
+        content_disposition: Option<String>,
+    ) -> Result<(), AppError>;
+
+    async fn get_object(&self, key: &str) -> Result<Vec<u8>, AppError>;
+
+    async fn delete_object(&self, key: &str) -> Result<(), AppError>;
+
+    /// One page of keys under `prefix`, resuming from `continuation_token`.
+    async fn list_objects(
+        &self,
+        prefix: &str,
+        max_keys: i32,
+        continuation_token: Option<String>,
+    ) -> Result<ObjectPage, AppError>;
+
+    async fn get_presigned_url(&self, key: &str, expires_in: Duration) -> Result<String, AppError> {
+        self.get_presigned_url_as(key, expires_in, None, None).await
+    }
+
+    /// Like `get_presigned_url`, with the GET answered with `content_type` and
+    /// `content_disposition` instead of the object's stored headers when set.
+    async fn get_presigned_url_as(
+        &self,
+        key: &str,
+        expires_in: Duration,
+        content_type: Option<String>,
+        content_disposition: Option<String>,
+    ) -> Result<String, AppError>;
+}
@@ -0,0 +1,199 @@
+use std::collections::HashMap;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, DbErr, EntityTrait, PaginatorTrait,
+    QueryFilter, QueryOrder, QuerySelect, Set,
+};
+use sea_orm::sea_query::Expr;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::config::get_config;
+use crate::entities::{file, job};
+use crate::models::job::JobPayload;
+use crate::models::settings::{ProjectSettings, VariantConfig};
+
+/// Upper bound for the per-file breakdown of a sync plan.
+pub const MAX_BREAKDOWN_ENTRIES: u64 = 1000;
+
+/// What `POST /projects/{id}/sync-variants` would do, without enqueueing anything.
+///
+/// Sync regenerates every configured variant of every image, or of stale images only
+/// with `only_stale`.
+/// Variants no longer configured are dropped from `variants_json`, but their
+/// objects stay in the bucket.
+#[derive(Debug, Default, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct SyncPlan {
+    pub files: u64,
+    /// Configured variants the file does not have yet
+    pub variants_new: u64,
+    /// Configured variants that already exist and would be overwritten
+    pub variants_regenerated: u64,
+    /// Existing variants no longer configured
+    pub variants_dropped: u64,
+    /// Originals downloaded from S3 to run the sync (one per file)
+    pub estimated_download_bytes: i64,
+    pub breakdown: Vec<FileSyncPlan>,
+    /// More files were planned than the requested breakdown size
+    pub breakdown_truncated: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct FileSyncPlan {
+    #[schema(value_type = String)]
+    pub file_id: Uuid,
+    pub filename: String,
+    pub new: Vec<String>,
+    pub regenerated: Vec<String>,
+    pub dropped: Vec<String>,
+}
+
+/// Image files a project-wide sync would process, matching `sync_variants`.
+///
+/// With `only_stale`, just the files whose original changed after their variants were
+/// generated (see `file::Model::variants_stale`).
+pub fn sync_candidates(project_id: Uuid, only_stale: bool) -> sea_orm::Select<file::Entity> {
+    let query = file::Entity::find_active()
+        .filter(file::Column::ProjectId.eq(project_id))
+        .filter(file::Column::MimeType.like("image/%"));
+
+    if only_stale {
+        // `<>` is NULL when either hash is missing, which excludes unknowns like `variants_stale`
+        query.filter(Expr::col((file::Entity, file::Column::ContentHash)).ne(Expr::col((file::Entity, file::Column::VariantsSourceHash))))
+    } else {
+        query
+    }
+}
+
+/// Compares every candidate file's `variants_json` with `target` and sums up the work.
+pub async fn plan_project_sync(
+    db: &DatabaseConnection,
+    project_id: Uuid,
+    target: &HashMap<String, VariantConfig>,
+    breakdown_limit: u64,
+    only_stale: bool,
+) -> Result<SyncPlan, DbErr> {
+    let rows: Vec<(Uuid, String, i64, serde_json::Value)> = sync_candidates(project_id, only_stale)
+        .select_only()
+        .column(file::Column::Id)
+        .column(file::Column::Filename)
+        .column(file::Column::Size)
+        .column(file::Column::VariantsJson)
+        .order_by_asc(file::Column::CreatedAt)
+        .into_tuple()
+        .all(db)
+        .await?;
+
+    let breakdown_limit = breakdown_limit.min(MAX_BREAKDOWN_ENTRIES) as usize;
+    let mut plan = SyncPlan::default();
+
+    for (file_id, filename, size, variants_json) in rows {
+        let existing: Vec<&String> = variants_json.as_object().map(|m| m.keys().collect()).unwrap_or_default();
+
+        let mut new: Vec<String> = target.keys().filter(|name| !existing.contains(name)).cloned().collect();
+        let mut regenerated: Vec<String> = target.keys().filter(|name| existing.contains(name)).cloned().collect();
+        let mut dropped: Vec<String> = existing.into_iter().filter(|name| !target.contains_key(*name)).cloned().collect();
+
+        plan.files += 1;
+        plan.variants_new += new.len() as u64;
+        plan.variants_regenerated += regenerated.len() as u64;
+        plan.variants_dropped += dropped.len() as u64;
+        plan.estimated_download_bytes += size;
+
+        if plan.breakdown.len() < breakdown_limit {
+            new.sort();
+            regenerated.sort();
+            dropped.sort();
+            plan.breakdown.push(FileSyncPlan { file_id, filename, new, regenerated, dropped });
+        }
+    }
+    plan.breakdown_truncated = breakdown_limit > 0 && plan.files as usize > plan.breakdown.len();
+
+    Ok(plan)
+}
+
+/// What a settings change did about `auto_sync_on_settings_change`.
+pub enum AutoSync {
+    /// The flag is off, or `variants` did not change
+    NotNeeded,
+    /// The `sync_project_variants` job that will apply the change, new or already pending
+    Queued(Uuid),
+    /// The project has no images to sync
+    NoImages,
+    /// More images than `AUTO_SYNC_MAX_FILES`; left to an explicit `POST /projects/{id}/sync-variants`
+    TooLarge(u64),
+}
+
+impl AutoSync {
+    pub fn job_id(&self) -> Option<Uuid> {
+        match self {
+            AutoSync::Queued(id) => Some(*id),
+            _ => None,
+        }
+    }
+
+    /// Why no job was queued although `variants` changed.
+    pub fn skipped(&self) -> Option<String> {
+        match self {
+            AutoSync::NoImages => Some("The project has no images".to_string()),
+            AutoSync::TooLarge(images) => Some(format!(
+                "{} images exceed AUTO_SYNC_MAX_FILES ({}); run POST /projects/{{id}}/sync-variants",
+                images,
+                get_config().auto_sync_max_files
+            )),
+            AutoSync::NotNeeded | AutoSync::Queued(_) => None,
+        }
+    }
+}
+
+/// Queues a `sync_project_variants` job when `new_settings` enable `auto_sync_on_settings_change`
+/// and their variant configs differ from `old_settings`'. Only the parsed variants are compared,
+/// so key order and other settings do not count.
+///
+/// Call inside the transaction that holds the project's row lock, so two edits cannot both
+/// queue a job.
+pub async fn auto_sync<C: ConnectionTrait>(
+    conn: &C,
+    project_id: Uuid,
+    old_settings: &Value,
+    new_settings: &Value,
+) -> Result<AutoSync, DbErr> {
+    let parse = |value: &Value| serde_json::from_value::<ProjectSettings>(value.clone()).unwrap_or_default();
+    let (old, new) = (parse(old_settings), parse(new_settings));
+    if !new.auto_sync_on_settings_change || old.variants.unwrap_or_default() == new.variants.unwrap_or_default() {
+        return Ok(AutoSync::NotNeeded);
+    }
+
+    // The worker reads the settings when the job runs, so a pending one covers this change too
+    let pending = job::Entity::find()
+        .filter(job::Column::Status.eq("pending"))
+        .filter(Expr::cust("payload->>'type' = 'sync_project_variants'"))
+        .filter(Expr::cust_with_values("payload->>'project_id' = $1", [project_id.to_string()]))
+        .one(conn)
+        .await?;
+    if let Some(existing) = pending {
+        return Ok(AutoSync::Queued(existing.id));
+    }
+
+    // One job fans out into a job per image, all queued ahead of later uploads
+    let images = sync_candidates(project_id, false).count(conn).await?;
+    if images > get_config().auto_sync_max_files {
+        return Ok(AutoSync::TooLarge(images));
+    }
+    if images == 0 {
+        return Ok(AutoSync::NoImages);
+    }
+
+    let job = job::ActiveModel {
+        id: Set(Uuid::new_v4()),
+        file_id: Set(None),
+        project_id: Set(Some(project_id)),
+        status: Set("pending".to_string()),
+        payload: Set(JobPayload::SyncProjectVariants { project_id }.to_value()),
+        created_at: Set(chrono::Utc::now()),
+        updated_at: Set(chrono::Utc::now()),
+    }
+    .insert(conn)
+    .await?;
+    Ok(AutoSync::Queued(job.id))
+}
@@ -0,0 +1,78 @@
+//! Per-user `token_version`, checked by `auth_middleware` on every bearer request.
+//!
+//! Access tokens carry the version their user had when they were issued; bumping it with
+//! [`bump`] rejects all of them at once. Versions are cached for
+//! `TOKEN_VERSION_CACHE_TTL_SECS` so the check rarely reaches the database. [`bump`] only
+//! drops the entry on this process: other instances accept old tokens until theirs expires.
+
+use std::num::NonZeroUsize;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use lru::LruCache;
+use sea_orm::sea_query::Expr;
+use sea_orm::{ColumnTrait, ConnectionTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter, QuerySelect};
+use uuid::Uuid;
+
+use crate::config::get_config;
+use crate::entities::user::{self, Entity as User};
+
+/// Users whose version is kept in memory.
+const CACHE_SIZE: usize = 4096;
+
+static ENTRIES: OnceLock<Mutex<LruCache<Uuid, (i32, Instant)>>> = OnceLock::new();
+
+fn entries() -> &'static Mutex<LruCache<Uuid, (i32, Instant)>> {
+    ENTRIES.get_or_init(|| Mutex::new(LruCache::new(NonZeroUsize::new(CACHE_SIZE).unwrap())))
+}
+
+fn ttl() -> Duration {
+    Duration::from_secs(get_config().token_version_cache_ttl_secs)
+}
+
+/// The user's current version; `None` when the user no longer exists.
+pub async fn current(db: &DatabaseConnection, user_id: Uuid) -> Result<Option<i32>, DbErr> {
+    if !ttl().is_zero() {
+        if let Some((version, cached_at)) = entries().lock().unwrap().get(&user_id) {
+            if cached_at.elapsed() < ttl() {
+                return Ok(Some(*version));
+            }
+        }
+    }
+
+    let version: Option<i32> = User::find_by_id(user_id)
+        .select_only()
+        .column(user::Column::TokenVersion)
+        .into_tuple()
+        .one(db)
+        .await?;
+
+    match version {
+        Some(version) if !ttl().is_zero() => {
+            entries().lock().unwrap().put(user_id, (version, Instant::now()));
+        }
+        Some(_) => {}
+        None => {
+            entries().lock().unwrap().pop(&user_id);
+        }
+    }
+    Ok(version)
+}
+
+/// Increments the user's version, so access tokens issued before stop working. Returns
+/// `false` when the user does not exist.
+pub async fn bump<C: ConnectionTrait>(conn: &C, user_id: Uuid) -> Result<bool, DbErr> {
+    let result = User::update_many()
+        .col_expr(user::Column::TokenVersion, Expr::col(user::Column::TokenVersion).add(1))
+        .filter(user::Column::Id.eq(user_id))
+        .exec(conn)
+        .await?;
+    forget(user_id);
+    Ok(result.rows_affected > 0)
+}
+
+/// Drops the cached version, for callers that bumped it inside a transaction that has
+/// now committed.
+pub fn forget(user_id: Uuid) {
+    entries().lock().unwrap().pop(&user_id);
+}
@@ -0,0 +1,102 @@
+//! Tombstones for permanently deleted files, so `GET /files/{id}` and its content route can
+//! answer `410 Gone` instead of `404` for ids that used to exist.
+
+use sea_orm::sea_query::{Condition, Expr, OnConflict, Query};
+use sea_orm::{ColumnTrait, ConnectionTrait, DbErr, EntityTrait, QueryFilter};
+use uuid::Uuid;
+
+use crate::entities::{file, file_tombstone, project};
+use crate::error::AppError;
+
+#[derive(Debug, Clone, Copy)]
+pub enum DeletionReason {
+    /// `DELETE /files/{id}`, including files derived from it
+    FileDeleted,
+    /// `DELETE /projects/{id}?permanent=true`
+    ProjectDeleted,
+    /// Trashed project purged by the cleanup service
+    ProjectPurged,
+    /// The project owner's account was deleted
+    OwnerDeleted,
+}
+
+impl DeletionReason {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DeletionReason::FileDeleted => "file_deleted",
+            DeletionReason::ProjectDeleted => "project_deleted",
+            DeletionReason::ProjectPurged => "project_purged",
+            DeletionReason::OwnerDeleted => "owner_deleted",
+        }
+    }
+}
+
+/// Writes a tombstone for every file matching `files`. Call before deleting the rows.
+pub async fn record<C: ConnectionTrait>(db: &C, files: Condition, reason: DeletionReason) -> Result<u64, DbErr> {
+    let now = chrono::Utc::now();
+    let select = Query::select()
+        .column(file::Column::Id)
+        .column(file::Column::ProjectId)
+        .expr(Expr::value(reason.as_str()))
+        .expr(Expr::value(now))
+        .from(file::Entity)
+        .cond_where(files)
+        .to_owned();
+
+    let insert = Query::insert()
+        .into_table(file_tombstone::Entity)
+        .columns([
+            file_tombstone::Column::FileId,
+            file_tombstone::Column::ProjectId,
+            file_tombstone::Column::Reason,
+            file_tombstone::Column::DeletedAt,
+        ])
+        .select_from(select)
+        .map_err(|e| DbErr::Custom(e.to_string()))?
+        .on_conflict(OnConflict::column(file_tombstone::Column::FileId).do_nothing().to_owned())
+        .to_owned();
+
+    let result = db.execute(db.get_database_backend().build(&insert)).await?;
+    Ok(result.rows_affected())
+}
+
+/// Files of one project.
+pub fn in_project(project_id: Uuid) -> Condition {
+    Condition::all().add(file::Column::ProjectId.eq(project_id))
+}
+
+/// Files of every project owned by `owner_id`.
+pub fn owned_by(owner_id: Uuid) -> Condition {
+    Condition::all().add(
+        file::Column::ProjectId.in_subquery(
+            Query::select()
+                .column(project::Column::Id)
+                .from(project::Entity)
+                .and_where(project::Column::OwnerId.eq(owner_id))
+                .to_owned(),
+        ),
+    )
+}
+
+/// The error for a file id no live file has: `410` when a tombstone matches, `404` otherwise.
+pub async fn missing_file<C: ConnectionTrait>(db: &C, file_id: Uuid) -> AppError {
+    match file_tombstone::Entity::find_by_id(file_id).one(db).await {
+        Ok(Some(tombstone)) => AppError::Gone {
+            message: "File was permanently deleted".to_string(),
+            deleted_at: tombstone.deleted_at,
+            reason: tombstone.reason,
+        },
+        Ok(None) => AppError::NotFound("File not found".into()),
+        Err(e) => AppError::DatabaseError(e),
+    }
+}
+
+/// Removes tombstones older than `days`.
+pub async fn prune<C: ConnectionTrait>(db: &C, days: i64) -> Result<u64, DbErr> {
+    let threshold = chrono::Utc::now() - chrono::Duration::days(days);
+    let result = file_tombstone::Entity::delete_many()
+        .filter(file_tombstone::Column::DeletedAt.lt(threshold))
+        .exec(db)
+        .await?;
+    Ok(result.rows_affected)
+}
@@ -0,0 +1,231 @@
+//! RFC 6238 one-time passwords (HMAC-SHA1, 30-second steps, 6 digits) and recovery codes
+//! for two-factor login.
+//!
+//! Secrets are stored sealed with `TOTP_SECRET_KEY` and bound to the user's id.
+
+use ring::hmac;
+use ring::rand::{SecureRandom, SystemRandom};
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+use uuid::Uuid;
+
+use crate::config::get_config;
+use crate::error::AppError;
+use crate::utils::secret_box;
+
+const STEP_SECS: i64 = 30;
+const DIGITS: usize = 6;
+/// Steps accepted on either side of the current one, to absorb clock drift.
+const SKEW_STEPS: i64 = 1;
+const SECRET_BYTES: usize = 20;
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+pub const RECOVERY_CODE_COUNT: usize = 10;
+/// Base32 characters per recovery code (50 bits), shown as two groups of five.
+const RECOVERY_CODE_CHARS: usize = 10;
+
+/// New random secret, base32 without padding as authenticator apps expect.
+pub fn generate_secret() -> Result<String, String> {
+    let mut bytes = [0u8; SECRET_BYTES];
+    SystemRandom::new().fill(&mut bytes).map_err(|_| "Failed to generate TOTP secret")?;
+    Ok(base32_encode(&bytes))
+}
+
+/// `otpauth://` URI to render as a QR code for authenticator apps.
+pub fn otpauth_uri(issuer: &str, account: &str, secret: &str) -> String {
+    let mut uri = url::Url::parse("otpauth://totp/").expect("static URI");
+    uri.set_path(&format!("{}:{}", issuer, account));
+    // Set as a raw query so spaces become `%20`; some apps show `+` literally
+    let issuer_param = issuer.replace(['&', '#'], "");
+    uri.set_query(Some(&format!(
+        "secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+        secret, issuer_param, DIGITS, STEP_SECS
+    )));
+    uri.to_string()
+}
+
+/// The step `code` was issued for, if it is the password for `unix_secs` or a step next to
+/// it. Callers store the step and refuse codes for it or an earlier one, so a code works once.
+pub fn verify(secret: &str, code: &str, unix_secs: i64) -> Option<i64> {
+    let code = code.trim();
+    if code.len() != DIGITS || !code.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let key = hmac::Key::new(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, &base32_decode(secret)?);
+
+    let step = unix_secs.div_euclid(STEP_SECS);
+    (-SKEW_STEPS..=SKEW_STEPS)
+        .rev()
+        .map(|offset| step + offset)
+        .find(|candidate| bool::from(code_at(&key, *candidate as u64).as_bytes().ct_eq(code.as_bytes())))
+}
+
+/// The code an authenticator app shows for `secret` at `unix_secs`.
+pub fn code_for(secret: &str, unix_secs: i64) -> Option<String> {
+    let key = hmac::Key::new(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, &base32_decode(secret)?);
+    Some(code_at(&key, unix_secs.div_euclid(STEP_SECS) as u64))
+}
+
+fn code_at(key: &hmac::Key, counter: u64) -> String {
+    let tag = hmac::sign(key, &counter.to_be_bytes());
+    let digest = tag.as_ref();
+    // Dynamic truncation (RFC 4226 section 5.3)
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let value = u32::from_be_bytes([digest[offset], digest[offset + 1], digest[offset + 2], digest[offset + 3]])
+        & 0x7fff_ffff;
+    format!("{:0width$}", value % 10u32.pow(DIGITS as u32), width = DIGITS)
+}
+
+/// Seals a new secret for `users.totp_secret`.
+pub fn seal_secret(user_id: Uuid, secret: &str) -> Result<String, AppError> {
+    secret_box::seal(&secret_key()?, user_id.as_bytes(), secret).map_err(AppError::InternalServerError)
+}
+
+/// The base32 secret behind a stored `users.totp_secret`, and whether it was stored in the
+/// clear. Secrets saved before sealing was added are plain base32; login seals them again.
+pub fn open_secret(user_id: Uuid, stored: &str) -> Result<(String, bool), AppError> {
+    if is_plain_secret(stored) {
+        return Ok((stored.to_string(), true));
+    }
+    secret_box::open(&secret_key()?, user_id.as_bytes(), stored)
+        .map(|secret| (secret, false))
+        .map_err(|e| AppError::InternalServerError(format!("Two-factor secret of user {} cannot be decrypted: {}", user_id, e)))
+}
+
+/// `generate_secret` output: base32 of `SECRET_BYTES`. Sealed values are longer.
+fn is_plain_secret(stored: &str) -> bool {
+    stored.len() == SECRET_BYTES.div_ceil(5) * 8 && stored.bytes().all(|b| BASE32_ALPHABET.contains(&b))
+}
+
+fn secret_key() -> Result<[u8; secret_box::KEY_LEN], AppError> {
+    get_config()
+        .totp_secret_key
+        .ok_or_else(|| AppError::ServiceUnavailable("Two-factor login requires TOTP_SECRET_KEY to be configured".to_string()))
+}
+
+/// Fresh single-use recovery codes, formatted `xxxxx-xxxxx`.
+pub fn generate_recovery_codes() -> Result<Vec<String>, String> {
+    let rng = SystemRandom::new();
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let mut bytes = [0u8; RECOVERY_CODE_CHARS];
+            rng.fill(&mut bytes).map_err(|_| "Failed to generate recovery codes")?;
+            let chars: String = bytes
+                .iter()
+                .map(|b| BASE32_ALPHABET[(b & 0x1f) as usize].to_ascii_lowercase() as char)
+                .collect();
+            Ok(format!("{}-{}", &chars[..5], &chars[5..]))
+        })
+        .collect()
+}
+
+/// SHA-256 hex of a recovery code, ignoring case, spaces and dashes.
+pub fn hash_recovery_code(code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    format!("{:x}", Sha256::digest(normalized.as_bytes()))
+}
+
+fn base32_encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(5) * 8);
+    let (mut buffer, mut bits) = (0u32, 0u32);
+    for &byte in bytes {
+        buffer = (buffer << 8) | byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        out.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+    out
+}
+
+fn base32_decode(encoded: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(encoded.len() * 5 / 8);
+    let (mut buffer, mut bits) = (0u32, 0u32);
+    for c in encoded.trim_end_matches('=').bytes() {
+        let value = BASE32_ALPHABET.iter().position(|&a| a == c.to_ascii_uppercase())? as u32;
+        buffer = (buffer << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// RFC 4226 appendix D and RFC 6238 appendix B use this ASCII key.
+    const RFC_KEY: &[u8] = b"12345678901234567890";
+
+    fn rfc_secret() -> String {
+        base32_encode(RFC_KEY)
+    }
+
+    #[test]
+    fn hotp_matches_rfc_4226() {
+        let key = hmac::Key::new(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, RFC_KEY);
+        let expected = [
+            "755224", "287082", "359152", "969429", "338314", "254676", "287922", "162583", "399871", "520489",
+        ];
+        for (counter, code) in expected.iter().enumerate() {
+            assert_eq!(code_at(&key, counter as u64), *code, "counter {}", counter);
+        }
+    }
+
+    #[test]
+    fn totp_matches_rfc_6238() {
+        // The RFC lists 8 digits; 6-digit codes are their last six
+        for (time, code) in [
+            (59, "94287082"),
+            (1111111109, "07081804"),
+            (1111111111, "14050471"),
+            (1234567890, "89005924"),
+            (2000000000, "69279037"),
+            (20000000000, "65353130"),
+        ] {
+            let code = &code[2..];
+            assert_eq!(code_for(&rfc_secret(), time).as_deref(), Some(code), "time {}", time);
+            assert_eq!(verify(&rfc_secret(), code, time), Some(time / STEP_SECS), "time {}", time);
+        }
+    }
+
+    #[test]
+    fn verify_allows_one_step_of_drift() {
+        let secret = rfc_secret();
+        let code = code_for(&secret, 1111111111).unwrap();
+        assert_eq!(verify(&secret, &code, 1111111111 + STEP_SECS), Some(1111111111 / STEP_SECS));
+        assert_eq!(verify(&secret, &code, 1111111111 - STEP_SECS), Some(1111111111 / STEP_SECS));
+        assert_eq!(verify(&secret, &code, 1111111111 + 3 * STEP_SECS), None);
+        assert_eq!(verify(&secret, "12345", 1111111111), None);
+        assert_eq!(verify(&secret, "abcdef", 1111111111), None);
+    }
+
+    #[test]
+    fn base32_round_trips() {
+        assert_eq!(rfc_secret(), "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+        for len in 0..=SECRET_BYTES {
+            let bytes: Vec<u8> = (0..len as u8).map(|b| b.wrapping_mul(37)).collect();
+            assert_eq!(base32_decode(&base32_encode(&bytes)).unwrap(), bytes, "{} bytes", len);
+        }
+        assert_eq!(base32_decode("gezdgnbvgy3tqojq").unwrap(), b"1234567890");
+        assert!(base32_decode("GEZ1").is_none());
+    }
+
+    #[test]
+    fn plain_secrets_are_told_apart_from_sealed_ones() {
+        assert!(is_plain_secret(&generate_secret().unwrap()));
+        let sealed = secret_box::seal(&[7; secret_box::KEY_LEN], Uuid::nil().as_bytes(), &generate_secret().unwrap()).unwrap();
+        assert!(!is_plain_secret(&sealed));
+    }
+}
@@ -0,0 +1,75 @@
+use std::time::Duration;
+
+use crate::config::FileUrlMode;
+use crate::error::AppError;
+use crate::services::storage::Storage;
+
+/// Builds the file URLs returned by the API, per the deployment's `FILE_URL_MODE`.
+///
+/// Lives in the router state so handlers build URLs explicitly instead of response
+/// conversions reaching into the global config.
+#[derive(Clone, Debug)]
+pub struct UrlBuilder {
+    mode: FileUrlMode,
+}
+
+impl UrlBuilder {
+    pub fn new(mode: FileUrlMode) -> Self {
+        Self { mode }
+    }
+
+    /// URL for `key` in `storage`. Presigned URLs need no network round trip, only signing.
+    pub async fn object_url(&self, storage: &dyn Storage, key: &str) -> Result<String, AppError> {
+        match &self.mode {
+            FileUrlMode::Direct => Ok(storage.object_url(key)),
+            // The CDN fronts the default bucket only; a project's own bucket is served directly
+            FileUrlMode::Cdn { base } if storage.is_default_bucket() => Ok(format!("{}/{}", base, key)),
+            FileUrlMode::Cdn { .. } => Ok(storage.object_url(key)),
+            FileUrlMode::Presigned { expires_in_secs } => {
+                storage.get_presigned_url(key, Duration::from_secs(*expires_in_secs)).await
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::memory_storage::MemoryStorage;
+
+    const KEY: &str = "p1/images/original/f1.png";
+
+    async fn url(mode: FileUrlMode, storage: &MemoryStorage) -> String {
+        UrlBuilder::new(mode).object_url(storage, KEY).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn direct_uses_the_bucket_url() {
+        let storage = MemoryStorage::new("media");
+        assert_eq!(url(FileUrlMode::Direct, &storage).await, "memory://media/p1/images/original/f1.png");
+    }
+
+    #[tokio::test]
+    async fn cdn_fronts_the_default_bucket_only() {
+        let cdn = || FileUrlMode::Cdn { base: "https://cdn.example.com".to_string() };
+        assert_eq!(url(cdn(), &MemoryStorage::new("media")).await, "https://cdn.example.com/p1/images/original/f1.png");
+        assert_eq!(
+            url(cdn(), &MemoryStorage::project_bucket("tenant")).await,
+            "memory://tenant/p1/images/original/f1.png"
+        );
+    }
+
+    #[tokio::test]
+    async fn presigned_signs_for_the_configured_lifetime() {
+        let presigned = || FileUrlMode::Presigned { expires_in_secs: 900 };
+        assert_eq!(
+            url(presigned(), &MemoryStorage::new("media")).await,
+            "memory://media/p1/images/original/f1.png?expires=900"
+        );
+        // Project buckets are signed too
+        assert_eq!(
+            url(presigned(), &MemoryStorage::project_bucket("tenant")).await,
+            "memory://tenant/p1/images/original/f1.png?expires=900"
+        );
+    }
+}
@@ -0,0 +1,94 @@
+//! Reading `files.variants_json` entries.
+//!
+//! The worker stores bare object keys; uploads from before that stored full URLs
+//! (path-style `endpoint/bucket/KEY` or virtual-hosted `bucket.host/KEY`). Readers go through
+//! [`resolve`], [`all`] or [`check`], which log and count the legacy entries they meet so we
+//! can tell when none are left. `backfill` with `variant_keys` rewrites them in place.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use sea_orm::sea_query::{Expr, SimpleExpr};
+use serde_json::Value;
+use uuid::Uuid;
+
+static LEGACY_SEEN: AtomicU64 = AtomicU64::new(0);
+
+/// SQL condition on `files`: at least one variant entry is stored as a URL.
+pub fn has_legacy_entries() -> SimpleExpr {
+    Expr::cust(
+        "jsonb_typeof(files.variants_json::jsonb) = 'object' \
+         AND EXISTS (SELECT 1 FROM jsonb_each_text(files.variants_json::jsonb) v WHERE v.value LIKE '%://%')",
+    )
+}
+
+/// Whether an entry is a full URL rather than a bare key.
+pub fn is_legacy(value: &str) -> bool {
+    url::Url::parse(value).is_ok()
+}
+
+/// Object key of an entry, bare or legacy. `None` for values that are neither.
+pub fn object_key(value: &str, bucket: &str) -> Option<String> {
+    if let Some(idx) = value.find(&format!("/{}/", bucket)) {
+        return Some(value[idx + bucket.len() + 2..].to_string());
+    }
+    match url::Url::parse(value) {
+        Ok(url) => Some(url.path().trim_start_matches('/').to_string()),
+        Err(url::ParseError::RelativeUrlWithoutBase) => Some(value.to_string()),
+        Err(_) => None,
+    }
+}
+
+fn warn(file_id: Uuid, variant: &str, value: &str) {
+    if is_legacy(value) {
+        LEGACY_SEEN.fetch_add(1, Ordering::Relaxed);
+        eprintln!("WARNING: Variants | legacy entry | file={} | variant={}", file_id, variant);
+    }
+}
+
+/// [`object_key`] of one variant of a file, warning about a legacy entry.
+pub fn resolve(file_id: Uuid, variant: &str, value: &str, bucket: &str) -> Option<String> {
+    warn(file_id, variant, value);
+    object_key(value, bucket)
+}
+
+/// Warns about every legacy entry of a file without resolving them, for `variants_json`
+/// handed out as it is stored.
+pub fn check(file_id: Uuid, variants: &Value) {
+    for (name, value) in variants.as_object().into_iter().flatten() {
+        if let Some(value) = value.as_str() {
+            warn(file_id, name, value);
+        }
+    }
+}
+
+/// Object keys of every variant of a file, in no particular order.
+pub fn all(file_id: Uuid, variants: &Value, bucket: &str) -> Vec<String> {
+    variants
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter_map(|(name, value)| resolve(file_id, name, value.as_str()?, bucket))
+        .collect()
+}
+
+/// `variants` with every legacy entry replaced by its bare key; `None` when nothing changed.
+/// Entries whose key cannot be extracted are left as they are.
+pub fn rewrite(variants: &Value, bucket: &str) -> Option<Value> {
+    let mut rewritten = variants.as_object()?.clone();
+    let mut changed = false;
+    for value in rewritten.values_mut() {
+        let Some(entry) = value.as_str().filter(|v| is_legacy(v)) else {
+            continue;
+        };
+        if let Some(key) = object_key(entry, bucket) {
+            *value = Value::String(key);
+            changed = true;
+        }
+    }
+    changed.then_some(Value::Object(rewritten))
+}
+
+/// Legacy entries met by [`resolve`] since the process started.
+pub fn legacy_seen() -> u64 {
+    LEGACY_SEEN.load(Ordering::Relaxed)
+}
@@ -0,0 +1,232 @@
+//! Signed delivery of project events to the project's webhook URL.
+//!
+//! Events are queued in memory per project and POSTed one at a time, so a receiver sees a
+//! project's events in the order they were emitted and a slow endpoint only holds up its own
+//! project. A POST that fails (connection error, timeout, non-2xx answer) is retried with
+//! doubling delays up to `WEBHOOK_MAX_ATTEMPTS` tries, then the event is dropped and logged.
+//! Queues live in the process, so events not delivered yet are lost on restart.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use rand::RngCore;
+use ring::hmac;
+use sea_orm::{DatabaseConnection, EntityTrait};
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+use crate::config::get_config;
+use crate::entities::project_webhook;
+use crate::error::AppError;
+use crate::services::file_events::FILE_EVENTS;
+use crate::utils::{net, secret_box};
+
+/// Sent once per key when it comes within `API_KEY_EXPIRY_NOTICE_DAYS` of its expiry
+pub const API_KEY_EXPIRING: &str = "api_key.expiring";
+
+/// Names accepted in a project's `webhook_events` setting.
+pub const EVENTS: [&str; 4] = [FILE_EVENTS[0], FILE_EVENTS[1], FILE_EVENTS[2], API_KEY_EXPIRING];
+
+/// Event name, e.g. `file.created`
+pub const EVENT_HEADER: &str = "x-mbk-event";
+/// Id of the event, the same on every retry so receivers can drop duplicates
+pub const DELIVERY_HEADER: &str = "x-mbk-delivery";
+/// Unix seconds when this try was signed
+pub const TIMESTAMP_HEADER: &str = "x-mbk-timestamp";
+/// `sha256=<hex HMAC-SHA256 of "<timestamp>.<body>" keyed with the project's secret>`
+pub const SIGNATURE_HEADER: &str = "x-mbk-signature";
+
+/// A queue with nothing to send for this long is dropped; the next event starts a new one.
+const IDLE: Duration = Duration::from_secs(60);
+
+struct Delivery {
+    db: DatabaseConnection,
+    project_id: Uuid,
+    id: Uuid,
+    event: &'static str,
+    body: String,
+}
+
+static QUEUES: OnceLock<Mutex<HashMap<Uuid, mpsc::UnboundedSender<Delivery>>>> = OnceLock::new();
+
+fn queues() -> &'static Mutex<HashMap<Uuid, mpsc::UnboundedSender<Delivery>>> {
+    QUEUES.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Queues `body` for the project's webhook. Nothing is sent when the project has none.
+pub fn enqueue(db: &DatabaseConnection, project_id: Uuid, event: &'static str, body: String) {
+    let delivery = Delivery { db: db.clone(), project_id, id: Uuid::new_v4(), event, body };
+    let mut queues = queues().lock().unwrap();
+    let delivery = match queues.get(&project_id) {
+        Some(queue) => match queue.send(delivery) {
+            Ok(()) => return,
+            Err(mpsc::error::SendError(delivery)) => delivery,
+        },
+        None => delivery,
+    };
+
+    let (queue, rx) = mpsc::unbounded_channel();
+    let _ = queue.send(delivery);
+    queues.insert(project_id, queue);
+    tokio::spawn(run(project_id, rx));
+}
+
+async fn run(project_id: Uuid, mut rx: mpsc::UnboundedReceiver<Delivery>) {
+    loop {
+        let next = match tokio::time::timeout(IDLE, rx.recv()).await {
+            Ok(Some(delivery)) => Some(delivery),
+            _ => {
+                // `enqueue` sends under the lock, so once the entry is gone nothing else arrives here
+                let mut queues = queues().lock().unwrap();
+                let leftover = rx.try_recv().ok();
+                if leftover.is_none() {
+                    queues.remove(&project_id);
+                }
+                leftover
+            }
+        };
+        match next {
+            Some(delivery) => deliver(delivery).await,
+            None => return,
+        }
+    }
+}
+
+async fn deliver(delivery: Delivery) {
+    let config = get_config();
+    let mut delay = Duration::from_millis(config.webhook_retry_delay_ms);
+
+    for attempt in 1..=config.webhook_max_attempts {
+        // Read on every try, so a removed webhook or a new URL or secret applies right away
+        let result = match project_webhook::Entity::find_by_id(delivery.project_id).one(&delivery.db).await {
+            Ok(None) => return,
+            Ok(Some(webhook)) => post(&webhook, &delivery).await,
+            Err(e) => Err(e.to_string()),
+        };
+        match result {
+            Ok(status) => {
+                println!(
+                    "Webhook | {} | project={} | delivery={} | attempt={} | res={}",
+                    delivery.event, delivery.project_id, delivery.id, attempt, status
+                );
+                return;
+            }
+            Err(e) => {
+                println!(
+                    "Webhook | {} | project={} | delivery={} | attempt={} | failed: {}",
+                    delivery.event, delivery.project_id, delivery.id, attempt, e
+                );
+                if attempt < config.webhook_max_attempts {
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                }
+            }
+        }
+    }
+    eprintln!(
+        "Webhook | {} | project={} | delivery={} | dropped after {} attempts",
+        delivery.event, delivery.project_id, delivery.id, config.webhook_max_attempts
+    );
+}
+
+async fn post(webhook: &project_webhook::Model, delivery: &Delivery) -> Result<u16, String> {
+    use http_body_util::Full;
+
+    // The host may resolve differently than when the URL was saved
+    check_url(&webhook.url).await?;
+    let secret = open_secret(webhook).map_err(|e| e.to_string())?;
+    let timestamp = chrono::Utc::now().timestamp();
+
+    let request = axum::http::Request::post(&webhook.url)
+        .header(axum::http::header::CONTENT_TYPE, "application/json")
+        .header(EVENT_HEADER, delivery.event)
+        .header(DELIVERY_HEADER, delivery.id.to_string())
+        .header(TIMESTAMP_HEADER, timestamp.to_string())
+        .header(SIGNATURE_HEADER, sign(&secret, timestamp, &delivery.body))
+        .body(Full::new(axum::body::Bytes::from(delivery.body.clone())))
+        .map_err(|e| format!("Invalid request: {}", e))?;
+
+    let connector = hyper_rustls::HttpsConnectorBuilder::new()
+        .with_provider_and_native_roots(rustls::crypto::ring::default_provider())
+        .map_err(|e| format!("Could not load TLS roots: {}", e))?
+        .https_or_http()
+        .enable_http1()
+        .build();
+    let client = hyper_util::client::legacy::Client::builder(hyper_util::rt::TokioExecutor::new())
+        .build::<_, Full<axum::body::Bytes>>(connector);
+
+    let timeout = Duration::from_secs(get_config().webhook_timeout_secs);
+    let response = tokio::time::timeout(timeout, client.request(request))
+        .await
+        .map_err(|_| format!("timed out after {}s", timeout.as_secs()))?
+        .map_err(|e| format!("POST failed: {}", e))?;
+
+    let status = response.status();
+    if !status.is_success() {
+        return Err(format!("answered {}", status.as_u16()));
+    }
+    Ok(status.as_u16())
+}
+
+/// `sha256=` and the hex HMAC-SHA256 of `<timestamp>.<body>`, keyed with the project's secret.
+pub fn sign(secret: &str, timestamp: i64, body: &str) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    let tag = hmac::sign(&key, format!("{}.{}", timestamp, body).as_bytes());
+    let hex: String = tag.as_ref().iter().map(|b| format!("{:02x}", b)).collect();
+    format!("sha256={}", hex)
+}
+
+/// Refuses anything but an http(s) URL on a public address, unless its host is listed in
+/// `WEBHOOK_PRIVATE_HOSTS`.
+pub async fn check_url(raw: &str) -> Result<(), String> {
+    let url = url::Url::parse(raw)
+        .ok()
+        .filter(|u| matches!(u.scheme(), "http" | "https"))
+        .ok_or_else(|| "url must be an http(s) URL".to_string())?;
+    let host = url.host().ok_or_else(|| "url must be an http(s) URL".to_string())?;
+    let name = host.to_string().to_lowercase();
+    if net::host_listed(&name, &get_config().webhook_private_hosts) {
+        return Ok(());
+    }
+    let port = url.port_or_known_default().unwrap_or(443);
+    net::check_public_host(&host, port).await.map_err(|e| format!("url host {}", e))
+}
+
+/// A fresh signing secret, shown to the caller once.
+pub fn generate_secret() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    format!("whsec_{}", URL_SAFE_NO_PAD.encode(bytes))
+}
+
+pub fn seal_secret(project_id: Uuid, secret: &str) -> Result<String, AppError> {
+    secret_box::seal(&secret_key()?, project_id.as_bytes(), secret).map_err(AppError::InternalServerError)
+}
+
+fn open_secret(webhook: &project_webhook::Model) -> Result<String, AppError> {
+    secret_box::open(&secret_key()?, webhook.project_id.as_bytes(), &webhook.secret_sealed)
+        .map_err(|e| AppError::InternalServerError(format!("Webhook secret for project {} cannot be decrypted: {}", webhook.project_id, e)))
+}
+
+fn secret_key() -> Result<[u8; secret_box::KEY_LEN], AppError> {
+    get_config()
+        .webhook_secret_key
+        .ok_or_else(|| AppError::ServiceUnavailable("Webhooks require WEBHOOK_SECRET_KEY to be configured".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signature_covers_the_timestamp_and_the_body() {
+        let signature = sign("whsec_test", 1_700_000_000, r#"{"event":"file.created"}"#);
+        assert!(signature.starts_with("sha256="));
+        assert_eq!(signature.len(), "sha256=".len() + 64);
+        assert_ne!(signature, sign("whsec_test", 1_700_000_001, r#"{"event":"file.created"}"#));
+        assert_ne!(signature, sign("whsec_test", 1_700_000_000, r#"{"event":"file.deleted"}"#));
+        assert_ne!(signature, sign("whsec_other", 1_700_000_000, r#"{"event":"file.created"}"#));
+    }
+}
@@ -1,38 +1,150 @@
 use std::time::Duration;
-use std::sync::Arc;
-use tokio::sync::{Semaphore, OwnedSemaphorePermit};
+use std::sync::{Arc, OnceLock};
+use tokio::sync::{Notify, Semaphore, OwnedSemaphorePermit};
 use sea_orm::{
     ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, 
     QueryOrder, QuerySelect, Set, TransactionTrait, ConnectionTrait
 };
-use sea_orm::sea_query::{LockType, LockBehavior};
+use sea_orm::sea_query::{Expr, LockType, LockBehavior};
+use serde::Serialize;
 use tokio::time::sleep;
 use crate::entities::{job, file, project};
+use crate::error::AppError;
+use crate::services::backfill;
+use crate::services::reconcile;
+use crate::services::integrity;
+use crate::services::file_events::{self, FileEvent};
+use crate::services::job_events::JobEventRecorder;
+use crate::services::project_storage;
 use crate::services::s3::S3Service;
-use crate::utils::{image_processor, sanitize_bucket_name};
-use crate::models::settings::VariantConfig;
+use crate::services::sync_plan;
+use crate::services::variant_keys;
+use crate::utils::{external_processor, file_extension, image_processor, sha256_hex};
+use crate::utils::image_processor::{DefaultProcessor, ImageProcessor};
+use crate::models::job::JobPayload;
+use crate::models::settings::{variant_name_problems, variant_slug, ProjectSettings, VariantConfig};
 use std::collections::HashMap;
 use uuid::Uuid;
 
+/// Worker pools; every job type runs in exactly one of them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PoolKind {
+    /// CPU-bound image processing (`WORKER_CONCURRENCY_IMAGE`)
+    Image,
+    /// IO-bound jobs (`WORKER_CONCURRENCY_IO`)
+    Io,
+    /// Everything without a configured pool (`WORKER_CONCURRENCY`)
+    Default,
+}
+
+impl PoolKind {
+    fn name(self) -> &'static str {
+        match self {
+            PoolKind::Image => "image",
+            PoolKind::Io => "io",
+            PoolKind::Default => "default",
+        }
+    }
+
+    /// Job types routed to this pool when it is configured.
+    fn job_types(self) -> &'static [&'static str] {
+        match self {
+            PoolKind::Image => &["process_image", "sync_file_variants"],
+            PoolKind::Io => &["sync_project_variants", "verify_file", "plan_project_sync", "backfill", "reconcile_storage"],
+            PoolKind::Default => &[],
+        }
+    }
+}
+
+struct Pool {
+    kind: PoolKind,
+    size: usize,
+    semaphore: Arc<Semaphore>,
+}
+
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub struct PoolStats {
+    pub name: String,
+    pub size: usize,
+    pub in_use: usize,
+    pub job_types: Vec<String>,
+}
+
+/// The running worker's pools, for introspection from request handlers.
+static POOLS: OnceLock<Arc<Vec<Pool>>> = OnceLock::new();
+
+/// Utilization of each pool, or `None` if no worker has started in this process.
+pub fn pool_stats() -> Option<Vec<PoolStats>> {
+    let pools = POOLS.get()?;
+    Some(
+        pools
+            .iter()
+            .map(|p| PoolStats {
+                name: p.kind.name().to_string(),
+                size: p.size,
+                in_use: p.size - p.semaphore.available_permits(),
+                job_types: p.kind.job_types().iter().map(|t| t.to_string()).collect(),
+            })
+            .collect(),
+    )
+}
+
 #[derive(Clone)]
 pub struct Worker {
     db: DatabaseConnection,
-    s3: S3Service,
-    semaphore: Arc<Semaphore>,
+    pools: Arc<Vec<Pool>>,
+    /// Signalled whenever a job finishes and frees a permit
+    job_finished: Arc<Notify>,
+    events: JobEventRecorder,
+    processor: Arc<dyn ImageProcessor>,
 }
 
 
 
 impl Worker {
+    /// Worker rendering variants with the backend named by `IMAGE_PROCESSOR`.
     pub async fn new(db: DatabaseConnection) -> Self {
-        let s3 = S3Service::new().await;
+        let name = &crate::config::get_config().image_processor;
+        let processor = image_processor::by_name(name).unwrap_or_else(|| {
+            eprintln!("Worker | unknown IMAGE_PROCESSOR '{}', using the default processor", name);
+            Arc::new(DefaultProcessor)
+        });
+        Self::with_processor(db, processor).await
+    }
+
+    pub async fn with_processor(db: DatabaseConnection, processor: Arc<dyn ImageProcessor>) -> Self {
         let config = crate::config::get_config();
-        let semaphore = Arc::new(Semaphore::new(config.worker_concurrency));
-        Self { db, s3, semaphore }
+
+        // With no per-type sizes configured this is the single global pool
+        let mut pools = Vec::new();
+        for (kind, size) in [
+            (PoolKind::Image, config.worker_concurrency_image),
+            (PoolKind::Io, config.worker_concurrency_io),
+            (PoolKind::Default, Some(config.worker_concurrency)),
+        ] {
+            if let Some(size) = size {
+                pools.push(Pool { kind, size, semaphore: Arc::new(Semaphore::new(size)) });
+            }
+        }
+        let pools = Arc::new(pools);
+        let _ = POOLS.set(pools.clone());
+
+        let events = JobEventRecorder::new(db.clone());
+        println!("Worker | image processor={}", processor.name());
+        Self { db, pools, job_finished: Arc::new(Notify::new()), events, processor }
+    }
+
+    fn pool_for(&self, job_type: &str) -> usize {
+        self.pools
+            .iter()
+            .position(|p| p.kind.job_types().contains(&job_type))
+            .or_else(|| self.pools.iter().position(|p| p.kind == PoolKind::Default))
+            .unwrap_or(0)
     }
 
     pub async fn run(&self) {
-        println!("Worker started with concurrency: {}", crate::config::get_config().worker_concurrency);
+        let sizes: Vec<String> = self.pools.iter().map(|p| format!("{}={}", p.kind.name(), p.size)).collect();
+        println!("Worker started with pools: {} | commit={}", sizes.join(", "), crate::build_info::COMMIT);
         
         // Recover any jobs stuck in 'processing' state from previous runs
         if let Err(e) = self.recover_stuck_jobs().await {
@@ -40,30 +152,57 @@ impl Worker {
         }
 
         loop {
-            // Acquire permit before looking for work
-            let permit = match self.semaphore.clone().acquire_owned().await {
-                Ok(p) => p,
-                Err(e) => {
-                    eprintln!("Semaphore error: {}", e);
-                    break;
-                }
-            };
+            // Take a permit from every pool with room, then only claim jobs those pools can run
+            let mut permits: Vec<(usize, OwnedSemaphorePermit)> = self
+                .pools
+                .iter()
+                .enumerate()
+                .filter_map(|(i, p)| p.semaphore.clone().try_acquire_owned().ok().map(|permit| (i, permit)))
+                .collect();
+
+            if permits.is_empty() {
+                self.job_finished.notified().await;
+                continue;
+            }
 
-            match self.claim_next_job().await {
+            if S3Service::circuit_open() {
+                // Storage is failing fast; claiming now would only bounce jobs back to pending
+                drop(permits);
+                sleep(Duration::from_secs(5)).await;
+                continue;
+            }
+
+            let free: Vec<PoolKind> = permits.iter().map(|(i, _)| self.pools[*i].kind).collect();
+
+            match self.claim_next_job(&free).await {
                 Ok(Some(job_model)) => {
+                    let pool = self.pool_for(JobPayload::type_of(&job_model.payload));
+                    let Some(slot) = permits.iter().position(|(i, _)| *i == pool) else {
+                        // Unreachable given the claim filter; leave the job for recovery on restart
+                        eprintln!("Worker claimed job {} without a free permit for its pool", job_model.id);
+                        continue;
+                    };
+                    let (_, permit) = permits.swap_remove(slot);
+                    drop(permits);
+                    self.events.record(job_model.id, "claimed", serde_json::json!({ "pool": self.pools[pool].kind.name() }));
+
                     let worker = self.clone();
                     tokio::spawn(async move {
                         worker.perform_job(job_model, permit).await;
+                        worker.job_finished.notify_one();
                     });
                 }
                 Ok(None) => {
-                    // No jobs found, drop permit and sleep
-                    drop(permit);
-                    sleep(Duration::from_secs(5)).await;
+                    // Nothing claimable; a finishing job may free a pool with pending work
+                    drop(permits);
+                    tokio::select! {
+                        _ = sleep(Duration::from_secs(5)) => {}
+                        _ = self.job_finished.notified() => {}
+                    }
                 }
                 Err(e) => {
                     eprintln!("Worker error: {}", e);
-                    drop(permit);
+                    drop(permits);
                     sleep(Duration::from_secs(5)).await;
                 }
             }
@@ -89,13 +228,39 @@ impl Worker {
         Ok(())
     }
 
-    async fn claim_next_job(&self) -> Result<Option<job::Model>, String> {
+    async fn claim_next_job(&self, free: &[PoolKind]) -> Result<Option<job::Model>, String> {
         // Start transaction
         let txn = self.db.begin().await.map_err(|e| e.to_string())?;
 
-        // 1. Find pending job with lock
-        let job_opt = job::Entity::find()
-            .filter(job::Column::Status.eq("pending"))
+        // 1. Find pending job with lock, restricted to job types whose pool has a free permit
+        let mut query = job::Entity::find()
+            .filter(job::Column::Status.eq("pending"));
+
+        let job_type = Expr::expr(Expr::cust(format!(
+            "COALESCE(payload->>'type', '{}')",
+            JobPayload::LEGACY_TYPE
+        )));
+        if free.contains(&PoolKind::Default) {
+            let busy: Vec<&str> = self
+                .pools
+                .iter()
+                .filter(|p| !free.contains(&p.kind))
+                .flat_map(|p| p.kind.job_types().iter().copied())
+                .collect();
+            if !busy.is_empty() {
+                query = query.filter(job_type.is_not_in(busy));
+            }
+        } else {
+            let runnable: Vec<&str> = free.iter().flat_map(|k| k.job_types().iter().copied()).collect();
+            query = query.filter(job_type.is_in(runnable));
+        }
+
+        // Jobs of suspended projects stay pending, in place, until the project is unsuspended
+        query = query.filter(Expr::cust(
+            "NOT EXISTS (SELECT 1 FROM projects WHERE projects.id = jobs.project_id AND projects.suspended_at IS NOT NULL)",
+        ));
+
+        let job_opt = query
             .order_by_asc(job::Column::CreatedAt)
             .limit(1)
             .lock_with_behavior(LockType::Update, LockBehavior::SkipLocked)
@@ -113,7 +278,7 @@ impl Worker {
         // Update job status to processing
         let mut job_active: job::ActiveModel = job_model.clone().into();
         job_active.status = Set("processing".to_string());
-        job_active.updated_at = Set(chrono::Utc::now().naive_utc());
+        job_active.updated_at = Set(chrono::Utc::now());
         let job_model = job_active.update(&txn).await.map_err(|e| e.to_string())?;
 
         // Commit transaction to release lock and save 'processing' state
@@ -126,28 +291,61 @@ impl Worker {
         // The permit is held until this function returns (active job count logic)
         // Now process the job (outside transaction to avoid holding DB lock during S3 ops)
         let job_start_time = std::time::Instant::now();
-        
-        match self.handle_job(&job_model).await {
-            Ok(_) => {
+
+        // Run the handler in its own task so a panic (e.g. inside the image crate) becomes
+        // a job failure instead of leaving the job stuck in 'processing'.
+        let worker = self.clone();
+        let job_for_task = job_model.clone();
+        let result = match tokio::spawn(async move { worker.handle_job(&job_for_task).await }).await {
+            Ok(result) => result,
+            Err(e) => Err(JobError::Failed(join_error_message(e))),
+        };
+
+        match result {
+            Ok(output) => {
                 let duration = job_start_time.elapsed();
                 println!("Job {} completed successfully took {:.2?}", job_model.id, duration);
+                self.events.record(job_model.id, "completed", serde_json::json!({ "duration_ms": duration.as_millis() as u64 }));
+                let mut payload = self.current_payload(&job_model).await;
                 let mut job_active: job::ActiveModel = job_model.into();
+                if let (Some(output), Some(fields)) = (output, payload.as_object_mut()) {
+                    fields.insert("result".to_string(), output);
+                    job_active.payload = Set(payload);
+                }
                 job_active.status = Set("completed".to_string());
-                job_active.updated_at = Set(chrono::Utc::now().naive_utc());
+                job_active.updated_at = Set(chrono::Utc::now());
                 if let Err(e) = job_active.update(&self.db).await {
                     eprintln!("Failed to update job status to completed: {}", e);
                 }
             },
-            Err(e) => {
-                eprintln!("Job {} failed: {}", job_model.id, e);
-                let payload = job_model.payload.clone();
+            Err(JobError::StorageUnavailable(e)) => {
+                // Outage, not a bad job: put it back for when storage recovers. Only the default
+                // bucket's breaker pauses claiming, so wait here before a project bucket's job returns
+                sleep(Duration::from_secs(5)).await;
+                eprintln!("Job {} requeued, storage unavailable: {}", job_model.id, e);
+                self.events.record(job_model.id, "requeued", serde_json::json!({ "error": e }));
+                let mut job_active: job::ActiveModel = job_model.into();
+                job_active.status = Set("pending".to_string());
+                job_active.updated_at = Set(chrono::Utc::now());
+                if let Err(e) = job_active.update(&self.db).await {
+                    eprintln!("Failed to requeue job: {}", e);
+                }
+            }
+            Err(JobError::Failed(e)) => {
+                eprintln!("Job {} failed: {} | commit={}", job_model.id, e, crate::build_info::COMMIT);
+                self.events.record(job_model.id, "failed", serde_json::json!({
+                    "error": e,
+                    "commit": crate::build_info::COMMIT,
+                    "duration_ms": job_start_time.elapsed().as_millis() as u64
+                }));
+                let payload = self.current_payload(&job_model).await;
                 let mut job_active: job::ActiveModel = job_model.into();
                 job_active.status = Set("failed".to_string());
                 job_active.payload = Set(serde_json::json!({
                     "error": e,
                     "original_payload": payload
                 }));
-                job_active.updated_at = Set(chrono::Utc::now().naive_utc());
+                job_active.updated_at = Set(chrono::Utc::now());
                 if let Err(e) = job_active.update(&self.db).await {
                     eprintln!("Failed to update job status to failed: {}", e);
                 }
@@ -155,28 +353,41 @@ impl Worker {
         }
     }
 
-    async fn handle_job(&self, job: &job::Model) -> Result<(), String> {
-        let payload = job.payload.as_object().ok_or("Invalid payload")?;
+    /// The job's stored payload. Long jobs such as backfills save progress into it while
+    /// running, so the copy claimed at the start may be out of date.
+    async fn current_payload(&self, job_model: &job::Model) -> serde_json::Value {
+        match job::Entity::find_by_id(job_model.id).one(&self.db).await {
+            Ok(Some(current)) => current.payload,
+            _ => job_model.payload.clone(),
+        }
+    }
 
-        if let Some(job_type) = payload.get("type").and_then(|v| v.as_str()) {
-            match job_type {
-                "sync_project_variants" => self.handle_sync_project_variants(job).await,
-                "sync_file_variants" => self.handle_sync_file_variants(job).await,
-                _ => Err(format!("Unknown job type: {}", job_type)),
+    /// Runs the job; `Some` output is stored under `result` in the completed job's payload.
+    async fn handle_job(&self, job: &job::Model) -> Result<Option<serde_json::Value>, JobError> {
+        // Legacy untagged payloads are upgraded lazily by `JobPayload::from_value`
+        match JobPayload::from_value(&job.payload)? {
+            JobPayload::ProcessImage { variants } => self.handle_process_image(job, variants.unwrap_or_default()).await.map(Some),
+            JobPayload::SyncFileVariants { variants_config } => self.handle_sync_file_variants(job, variants_config.unwrap_or_default()).await.map(Some),
+            JobPayload::SyncProjectVariants { project_id } => self.handle_sync_project_variants(project_id).await.map(|_| None),
+            JobPayload::VerifyFile => self.handle_verify_file(job).await.map(|_| None),
+            JobPayload::PlanProjectSync { project_id, variants_config, breakdown_limit, only_stale } => {
+                let plan = sync_plan::plan_project_sync(&self.db, project_id, &variants_config.unwrap_or_default(), breakdown_limit, only_stale)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                serde_json::to_value(plan).map(Some).map_err(|e| e.to_string().into())
+            }
+            JobPayload::Backfill { attributes, project_id, progress } => {
+                let progress = backfill::run(&self.db, job.id, &attributes, project_id, progress).await?;
+                serde_json::to_value(progress).map(Some).map_err(|e| e.to_string().into())
+            }
+            JobPayload::ReconcileStorage { project_id, progress } => {
+                let report = reconcile::run(&self.db, job.id, project_id, progress).await?;
+                serde_json::to_value(report).map(Some).map_err(|e| e.to_string().into())
             }
-        } else if payload.contains_key("variants") {
-             // Backward compatibility for existing ProcessImage jobs
-             self.handle_process_image(job).await
-        } else {
-             Err("Unknown job payload structure".to_string())
         }
     }
 
-    async fn handle_sync_project_variants(&self, job: &job::Model) -> Result<(), String> {
-        let payload = job.payload.as_object().unwrap();
-        let project_id_str = payload.get("project_id").and_then(|v| v.as_str()).ok_or("Missing project_id")?;
-        let project_id = Uuid::parse_str(project_id_str).map_err(|e| e.to_string())?;
-
+    async fn handle_sync_project_variants(&self, project_id: Uuid) -> Result<(), JobError> {
         // 1. Get Project Settings
         let project = project::Entity::find_by_id(project_id)
             .one(&self.db)
@@ -184,11 +395,11 @@ impl Worker {
             .map_err(|e| e.to_string())?
             .ok_or("Project not found")?;
 
-        let settings = project.settings.as_object().ok_or("Invalid project settings")?;
-        let variants_json = settings.get("variants").cloned().unwrap_or(serde_json::json!({}));
+        let settings: ProjectSettings = serde_json::from_value(project.settings.clone())
+            .map_err(|e| format!("Invalid project settings: {}", e))?;
         
-        // 2. Find all image files
-        let files = file::Entity::find()
+        // 2. Find all image files (none if the project was trashed after the job was queued)
+        let files = file::Entity::find_active()
             .filter(file::Column::ProjectId.eq(project_id))
             .filter(file::Column::MimeType.contains("image"))
             .all(&self.db)
@@ -199,21 +410,20 @@ impl Worker {
 
         // 3. Spawn SyncFileVariants job for each file
         for f in files {
-            let job_payload = serde_json::json!({
-                "type": "sync_file_variants",
-                "file_id": f.id.to_string(),
-                "variants_config": variants_json // Pass config snapshot to ensure consistency
-            });
+            // Pass config snapshot to ensure consistency
+            let job_payload = JobPayload::SyncFileVariants {
+                variants_config: settings.variants.clone(),
+            };
 
             // Create Job
             let job = job::ActiveModel {
                 id: Set(Uuid::new_v4()),
-                file_id: Set(f.id), // Link to file so we can track it
+                file_id: Set(Some(f.id)), // Link to file so we can track it
+                project_id: Set(Some(project_id)),
                 status: Set("pending".to_string()),
-                payload: Set(job_payload),
-                created_at: Set(chrono::Utc::now().naive_utc()),
-                updated_at: Set(chrono::Utc::now().naive_utc()),
-                ..Default::default()
+                payload: Set(job_payload.to_value()),
+                created_at: Set(chrono::Utc::now()),
+                updated_at: Set(chrono::Utc::now()),
             };
 
             job.insert(&self.db).await.map_err(|e| e.to_string())?;
@@ -222,108 +432,175 @@ impl Worker {
         Ok(())
     }
 
-    async fn handle_sync_file_variants(&self, job: &job::Model) -> Result<(), String> {
-        // reuse process_image logic but with extra check for deleting obsolete?
-        // Actually, let's keep it simple: 
-        // 1. Generate missing variants.
-        // 2. Delete unknown variants (if variants_config is authoritative).
-        
-        let payload = job.payload.as_object().unwrap();
-        // let file_id_str = payload.get("file_id").and_then(|v| v.as_str()).unwrap();
-        // let file_id = Uuid::parse_str(file_id_str).unwrap(); 
-        // We have job.file_id already
+    async fn handle_sync_file_variants(&self, job: &job::Model, target_variants: HashMap<String, VariantConfig>) -> Result<serde_json::Value, JobError> {
+        // Generate the variants described by the settings snapshot taken at enqueue time.
+        // Obsolete variants are not deleted here.
+        let file = file::Entity::find_active()
+            .filter(file::Column::Id.eq(job.file_id))
+            .one(&self.db)
+            .await
+            .map_err(|e| e.to_string())?
+            .ok_or("File not found or its project is trashed")?;
 
-        let variants_config_json = payload.get("variants_config").ok_or("Missing variants_config")?;
-        let target_variants: HashMap<String, VariantConfig> = serde_json::from_value(variants_config_json.clone())
-            .map_err(|e| e.to_string())?;
+        self.process_image_logic(job.id, &file, target_variants).await
+    }
 
-        // Get File
-        let file = file::Entity::find_by_id(job.file_id)
+    async fn handle_process_image(&self, job: &job::Model, variants: HashMap<String, VariantConfig>) -> Result<serde_json::Value, JobError> {
+         // 1. Get File
+         let file = file::Entity::find_active()
+            .filter(file::Column::Id.eq(job.file_id))
             .one(&self.db)
             .await
             .map_err(|e| e.to_string())?
-            .ok_or("File not found")?;
-        
-        // Get Current Variants from File JSON
-        // Note: previous implementation didn't strictly update variants_json with results?? 
-        // Let's assume we start relying on it or just overwriting it.
-        // If we didn't update it before, it might be empty.
-        
-        // Let's reuse handle_process_image but ensuring we pass the new config.
-        // But handle_process_image assumes the payload has "variants" and does the work.
-        // It does NOT delete old variants.
-        // It DOES update DB status.
-        
-        // Refactoring handle_process_image to be reusable would be best.
-        // Let's just call `process_image_logic` here.
-        
-        // But first, let's look at `handle_process_image` (which I renamed/extracted below).
-        
-        self.process_image_logic(&file, target_variants).await
+            .ok_or("File not found or its project is trashed")?;
+
+         self.process_image_logic(job.id, &file, variants).await
     }
 
-    async fn handle_process_image(&self, job: &job::Model) -> Result<(), String> {
-         let payload = job.payload.as_object().ok_or("Invalid payload")?;
-         let variants_json = payload.get("variants").ok_or("No variants in payload")?;
-         let variants: HashMap<String, VariantConfig> = serde_json::from_value(variants_json.clone())
-             .map_err(|e| e.to_string())?;
-         
-         // 1. Get File
-         let file = file::Entity::find_by_id(job.file_id)
+    async fn handle_verify_file(&self, job: &job::Model) -> Result<(), JobError> {
+        let file = file::Entity::find_active()
+            .filter(file::Column::Id.eq(job.file_id))
             .one(&self.db)
             .await
             .map_err(|e| e.to_string())?
-            .ok_or("File not found")?;
+            .ok_or("File not found or its project is trashed")?;
+
+        let s3 = project_storage::for_project(&self.db, file.project_id).await?;
+        let report = integrity::verify_file(&self.db, s3.as_ref(), &file).await?;
+        if !report.is_match() {
+            // Fail the job so the mismatch is visible in job listings; the file is already flagged
+            return Err(format!("Checksum mismatch: expected {}, got {}", report.expected, report.actual).into());
+        }
+        let bad_variants = report.bad_variants();
+        if !bad_variants.is_empty() {
+            return Err(format!("Variants differ from what was generated: {}", bad_variants.join(", ")).into());
+        }
 
-         self.process_image_logic(&file, variants).await
+        Ok(())
     }
 
-    async fn process_image_logic(&self, file: &file::Model, variants: HashMap<String, VariantConfig>) -> Result<(), String> {
+    /// Generates `variants` and returns `{"variants": {"thumb": "uploaded" | "unchanged"}}` for
+    /// the job result. A variant whose bytes hash the same as the stored one under the same key
+    /// is not uploaded again, so routine syncs don't touch (or invalidate) unchanged objects.
+    async fn process_image_logic(&self, job_id: Uuid, file: &file::Model, variants: HashMap<String, VariantConfig>) -> Result<serde_json::Value, JobError> {
         let project = project::Entity::find_by_id(file.project_id)
             .one(&self.db)
             .await
             .map_err(|e| e.to_string())?
             .ok_or("Project not found")?;
 
+        // Reject unknown formats before downloading anything
+        let mut invalid_formats: Vec<String> = variants
+            .iter()
+            .filter_map(|(name, config)| {
+                config.format.as_deref()
+                    .filter(|f| !self.processor.supports_format(f))
+                    .map(|f| format!("{} ({})", name, f))
+            })
+            .collect();
+        if !invalid_formats.is_empty() {
+            invalid_formats.sort();
+            return Err(format!("Unsupported variant formats: {}", invalid_formats.join(", ")).into());
+        }
+
+        // Settings saved before names were validated may still hold unusable ones; never let
+        // them collide in (or escape) the key layout. `settings lint` lists such projects.
+        let name_problems = variant_name_problems(variants.keys());
+        if !name_problems.is_empty() {
+            return Err(format!("Invalid variant names: {}", name_problems.join("; ")).into());
+        }
+
+        let s3 = project_storage::for_project(&self.db, project.id).await?;
+
         // Download original file
-        let original_data = self.s3.get_object(&file.s3_key).await.map_err(|e| e.to_string())?;
+        self.events.record(job_id, "download_started", serde_json::json!({ "key": file.s3_key }));
+        let download_start = std::time::Instant::now();
+        let original_data = s3.get_object(&file.s3_key).await?;
+        self.events.record(job_id, "download_finished", serde_json::json!({
+            "bytes": original_data.len(),
+            "duration_ms": download_start.elapsed().as_millis() as u64
+        }));
 
         let mut successful_variants = serde_json::Map::new();
+        let mut variant_dimensions = serde_json::Map::new();
+        let mut variant_hashes = serde_json::Map::new();
+        let mut outcomes = serde_json::Map::new();
 
         // Process each variant
-        for (variant_name, config) in variants {
+        for (variant_name, config) in &variants {
             println!("Processing variant: {}", variant_name);
+            self.events.record(job_id, "variant_started", serde_json::json!({ "name": variant_name }));
+            let variant_start = std::time::Instant::now();
             
-            // Clone data to move into validation closure
-            let original_data_clone = original_data.clone();
-            let config_clone = config.clone();
-
-            // Process image in blocking thread
-            let (processed_data, mime_type) = tokio::task::spawn_blocking(move || {
-                image_processor::process_image(&original_data_clone, &config_clone)
-            }).await
-              .map_err(|e| format!("Task join error: {}", e))?
-              .map_err(|e| e.to_string())?;
-
-            let ext = match mime_type.as_str() {
-                "image/avif" => "avif",
-                "image/webp" => "webp",
-                "image/png" => "png",
-                "image/jpeg" => "jpg",
-                _ => "bin",
-            };
+            let rendered: Result<RenderedVariant, JobError> = async {
+                // Clone data to move into validation closure
+                let original_data_clone = original_data.clone();
+                let config_clone = config.clone();
+
+                let (processed_data, mime_type, dimensions) = if let Some(command) = &config.external_command {
+                    // Only consulted when the bytes match no known format
+                    let stored_extension = file_extension(&file.s3_key);
+                    let (data, mime_type) = external_processor::process(command, &original_data, stored_extension.as_deref(), config)
+                        .await
+                        .map_err(|e| e.to_string())?;
+                    let dimensions = image_processor::dimensions(&data);
+                    (data, mime_type, dimensions)
+                } else {
+                    // Process image in blocking thread
+                    let processor = self.processor.clone();
+                    tokio::task::spawn_blocking(move || {
+                        processor.process(&original_data_clone, &config_clone)
+                    }).await
+                      .map_err(join_error_message)?
+                      .map(|image| (image.data, image.mime_type, Some((image.width, image.height))))
+                      .map_err(|e| e.to_string())?
+                };
+
+                // The key is derived from what the processor actually produced
+                let ext = image_processor::extension_for_mime(&mime_type)
+                    .ok_or_else(|| format!("Processor produced unexpected mime type {}", mime_type))?;
+
+                let s3_key = format!("{}/images/{}/{}.{}",
+                    project.storage_prefix,
+                    variant_slug(variant_name),
+                    file.id,
+                    ext
+                );
+
+                let hash = sha256_hex(&processed_data);
+                let stored_key = file.variants_json.get(variant_name)
+                    .and_then(|v| v.as_str())
+                    .and_then(|v| variant_keys::object_key(v, s3.bucket_name()));
+                let unchanged = stored_key.as_deref() == Some(s3_key.as_str())
+                    && file.variant_hashes.get(variant_name).and_then(|h| h.as_str()) == Some(hash.as_str());
+
+                // Upload to S3
+                let output_bytes = processed_data.len();
+                if !unchanged {
+                    s3.put_object(&s3_key, processed_data, &mime_type).await?;
+                }
+                self.events.record(job_id, "variant_finished", serde_json::json!({
+                    "name": variant_name,
+                    "duration_ms": variant_start.elapsed().as_millis() as u64,
+                    "bytes": output_bytes,
+                    "unchanged": unchanged
+                }));
 
-            let s3_key = format!("{}-{}/images/{}/{}.{}", 
-                sanitize_bucket_name(&project.name), 
-                project.id, 
-                variant_name, 
-                file.id, 
-                ext
-            );
+                Ok(RenderedVariant { s3_key, dimensions, hash, unchanged })
+            }.await;
+
+            let RenderedVariant { s3_key, dimensions, hash, unchanged } = match rendered {
+                Ok(rendered) => rendered,
+                Err(JobError::Failed(e)) => {
+                    self.record_variant_error(file, variant_name, &e).await;
+                    return Err(JobError::Failed(format!("Variant {}: {}", variant_name, e)));
+                }
+                // Says nothing about the variant; the requeued job tries it again
+                Err(JobError::StorageUnavailable(e)) => {
+                    return Err(JobError::StorageUnavailable(format!("Variant {}: {}", variant_name, e)));
+                }
+            };
 
-            // Upload to S3
-            self.s3.put_object(&s3_key, processed_data, &mime_type).await.map_err(|e| e.to_string())?;
-            
             // Store successful variant path (future proofing)
             // Storing absolute key or URL? 
             // Previous code calculated it on the fly in `get_file_content`.
@@ -331,16 +608,113 @@ impl Worker {
             // Let's store the full S3 Key or relative path.
             // Consistency: store full S3 Key? Or just the URL?
             // Let's store the S3 Key.
-            successful_variants.insert(variant_name, serde_json::Value::String(s3_key));
+            successful_variants.insert(variant_name.clone(), serde_json::Value::String(s3_key));
+            if let Some((width, height)) = dimensions {
+                variant_dimensions.insert(variant_name.clone(), serde_json::json!({ "width": width, "height": height }));
+            }
+            variant_hashes.insert(variant_name.clone(), serde_json::Value::String(hash));
+            outcomes.insert(variant_name.clone(), serde_json::json!(if unchanged { "unchanged" } else { "uploaded" }));
         }
 
         // Update File status AND variants_json
         let mut file_active: file::ActiveModel = file.clone().into();
         file_active.status = Set("ready".to_string());
         file_active.variants_json = Set(serde_json::Value::Object(successful_variants));
-        file_active.updated_at = Set(chrono::Utc::now().naive_utc());
-        file_active.update(&self.db).await.map_err(|e| e.to_string())?;
+        file_active.variant_dimensions = Set(serde_json::Value::Object(variant_dimensions));
+        file_active.variant_hashes = Set(serde_json::Value::Object(variant_hashes));
+        file_active.variants_source_hash = Set(Some(sha256_hex(&original_data)));
+        // Every requested variant was just generated, so their earlier failures are resolved
+        let mut variant_errors = file.variant_errors.as_object().cloned().unwrap_or_default();
+        variant_errors.retain(|name, _| !variants.contains_key(name));
+        file_active.variant_errors = Set(serde_json::Value::Object(variant_errors));
+        file_active.updated_at = Set(chrono::Utc::now());
+        let updated = file_active.update(&self.db).await.map_err(|e| e.to_string())?;
+
+        // Later syncs regenerate variants of a file that is already ready; only the first run flips it
+        if file.status != "ready" {
+            let settings: ProjectSettings = serde_json::from_value(project.settings.clone()).unwrap_or_default();
+            file_events::emit(&self.db, &settings, FileEvent::Ready, &updated);
+        }
 
-        Ok(())
+        Ok(serde_json::json!({ "variants": outcomes }))
+    }
+
+    /// Records why `variant_name` could not be generated, for `?include=errors` and the content endpoint.
+    async fn record_variant_error(&self, file: &file::Model, variant_name: &str, error: &str) {
+        let mut variant_errors = file.variant_errors.as_object().cloned().unwrap_or_default();
+        variant_errors.insert(variant_name.to_string(), serde_json::json!({
+            "error": error,
+            "failed_at": chrono::Utc::now(),
+        }));
+
+        let mut file_active: file::ActiveModel = file.clone().into();
+        file_active.variant_errors = Set(serde_json::Value::Object(variant_errors));
+        if let Err(e) = file_active.update(&self.db).await {
+            eprintln!("Failed to record variant error for file {}: {}", file.id, e);
+        }
+    }
+}
+
+/// One variant written (or left alone) by `process_image_logic`.
+struct RenderedVariant {
+    s3_key: String,
+    dimensions: Option<(u32, u32)>,
+    /// Hex SHA-256 of the produced bytes
+    hash: String,
+    /// Same bytes as the stored object under the same key, so nothing was uploaded
+    unchanged: bool,
+}
+
+/// Why a job did not complete.
+#[derive(Debug)]
+pub enum JobError {
+    /// Storage is down or its circuit breaker is open: the job goes back to pending
+    StorageUnavailable(String),
+    /// Anything else: the job is marked failed
+    Failed(String),
+}
+
+impl std::fmt::Display for JobError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JobError::StorageUnavailable(msg) => write!(f, "Storage unavailable: {}", msg),
+            JobError::Failed(msg) => write!(f, "{}", msg),
+        }
     }
 }
+
+impl From<String> for JobError {
+    fn from(msg: String) -> Self {
+        JobError::Failed(msg)
+    }
+}
+
+impl From<&str> for JobError {
+    fn from(msg: &str) -> Self {
+        JobError::Failed(msg.to_string())
+    }
+}
+
+impl From<AppError> for JobError {
+    fn from(e: AppError) -> Self {
+        match e {
+            AppError::ServiceUnavailable(msg) => JobError::StorageUnavailable(msg),
+            e => JobError::Failed(e.to_string()),
+        }
+    }
+}
+
+/// Describes a failed task, surfacing the panic message when the task panicked.
+fn join_error_message(e: tokio::task::JoinError) -> String {
+    if !e.is_panic() {
+        return format!("Task join error: {}", e);
+    }
+
+    let payload = e.into_panic();
+    let message = payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string());
+    format!("Job panicked: {}", message)
+}
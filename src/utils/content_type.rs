@@ -0,0 +1,126 @@
+//! Content types of stored files.
+//!
+//! Clients declare whatever they like on upload, often without a charset and sometimes
+//! wrongly (HTML sent as `text/plain`). [`normalize`] decides the type a file is stored and
+//! served under; [`is_active`] types run script in the browser and are served as attachments
+//! unless the project opts into inline serving.
+
+/// Types `GET /files/{id}/content?content_type=` may serve a file as. None of them renders
+/// as a document, whatever the bytes are.
+pub const DOWNLOAD_OVERRIDES: [&str; 4] = ["application/octet-stream", "text/plain", "text/csv", "application/json"];
+
+/// Bytes looked at when sniffing markup.
+const SNIFF_LEN: usize = 1024;
+
+/// Tags that make a browser treat a text body as HTML (the WHATWG sniffing set).
+const HTML_TAGS: [&str; 17] = [
+    "<!doctype html", "<html", "<head", "<script", "<iframe", "<h1", "<div", "<font", "<table",
+    "<a", "<style", "<title", "<b", "<body", "<br", "<p", "<!--",
+];
+
+/// Mime type without parameters, lowercased: `Text/CSV; charset=utf-8` -> `text/csv`.
+pub fn essence(mime: &str) -> String {
+    mime.split(';').next().unwrap_or_default().trim().to_lowercase()
+}
+
+/// Rendered as text, so a charset applies.
+pub fn is_text_like(mime: &str) -> bool {
+    let essence = essence(mime);
+    essence.starts_with("text/")
+        || essence.ends_with("+json")
+        || essence.ends_with("+xml")
+        || matches!(essence.as_str(), "application/json" | "application/xml" | "application/javascript")
+}
+
+/// Documents that can run script when opened inline from the bucket.
+pub fn is_active(mime: &str) -> bool {
+    matches!(essence(mime).as_str(), "text/html" | "application/xhtml+xml" | "image/svg+xml")
+}
+
+/// The type a file is stored and served under.
+///
+/// A missing or generic declaration is replaced by the type its extension implies, and
+/// `text/plain` / `application/octet-stream` bodies that are really HTML or SVG are stored
+/// as such, so they get the attachment treatment. Text-like types carry a charset: the
+/// declared one, else one read from the bytes.
+pub fn normalize(declared: &str, filename: &str, data: &[u8]) -> String {
+    let mut mime = essence(declared);
+    if mime.is_empty() || mime == "application/octet-stream" {
+        if let Some(guess) = crate::utils::file_extension(filename).as_deref().and_then(from_extension) {
+            mime = guess.to_string();
+        }
+    }
+    if matches!(mime.as_str(), "text/plain" | "application/octet-stream" | "") {
+        if let Some(sniffed) = sniff_markup(data) {
+            mime = sniffed.to_string();
+        }
+    }
+    if mime.is_empty() {
+        return "application/octet-stream".to_string();
+    }
+
+    if !is_text_like(&mime) {
+        return mime;
+    }
+    let charset = declared_charset(declared).unwrap_or_else(|| detect_charset(data).to_string());
+    format!("{}; charset={}", mime, charset)
+}
+
+/// An allowlisted `content_type` override, with a charset when text-like.
+pub fn download_override(requested: &str) -> Option<String> {
+    let essence = essence(requested);
+    let allowed = DOWNLOAD_OVERRIDES.iter().find(|t| **t == essence)?;
+    Some(if is_text_like(allowed) { format!("{}; charset=utf-8", allowed) } else { allowed.to_string() })
+}
+
+fn from_extension(ext: &str) -> Option<&'static str> {
+    Some(match ext {
+        "txt" | "log" => "text/plain",
+        "csv" => "text/csv",
+        "tsv" => "text/tab-separated-values",
+        "md" => "text/markdown",
+        "json" => "application/json",
+        "xml" => "application/xml",
+        "html" | "htm" => "text/html",
+        "xhtml" => "application/xhtml+xml",
+        "svg" => "image/svg+xml",
+        _ => return None,
+    })
+}
+
+fn sniff_markup(data: &[u8]) -> Option<&'static str> {
+    let head = &data[..data.len().min(SNIFF_LEN)];
+    let head = head.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(head);
+    let start = head.iter().position(|b| !b.is_ascii_whitespace())?;
+    let head = String::from_utf8_lossy(&head[start..]).to_lowercase();
+
+    if head.starts_with("<svg") || (head.starts_with("<?xml") && head.contains("<svg")) {
+        return Some("image/svg+xml");
+    }
+    let is_html = HTML_TAGS.iter().any(|tag| {
+        head.strip_prefix(tag)
+            .is_some_and(|rest| tag.ends_with("--") || rest.starts_with([' ', '>', '\t', '\n', '\r']))
+    });
+    is_html.then_some("text/html")
+}
+
+fn declared_charset(declared: &str) -> Option<String> {
+    declared.split(';').skip(1).find_map(|param| {
+        let (name, value) = param.split_once('=')?;
+        let value = value.trim().trim_matches('"').to_lowercase();
+        (name.trim().eq_ignore_ascii_case("charset") && !value.is_empty()).then_some(value)
+    })
+}
+
+fn detect_charset(data: &[u8]) -> &'static str {
+    if data.starts_with(b"\xFF\xFE") {
+        "utf-16le"
+    } else if data.starts_with(b"\xFE\xFF") {
+        "utf-16be"
+    } else if std::str::from_utf8(data).is_ok() {
+        "utf-8"
+    } else {
+        // What browsers decode `iso-8859-1` as anyway
+        "windows-1252"
+    }
+}
@@ -0,0 +1,269 @@
+use std::path::Path;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::process::Command;
+use uuid::Uuid;
+
+use crate::config::get_config;
+use crate::models::settings::VariantConfig;
+use crate::utils::image_processor;
+
+/// Trailing stderr bytes kept in error messages; the rest is read and dropped as it arrives.
+const STDERR_TAIL_BYTES: usize = 500;
+
+/// How often the output file is measured while the command runs.
+const OUTPUT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Bounds on one command run.
+#[derive(Clone, Copy, Debug)]
+struct Limits {
+    timeout: Duration,
+    max_output_bytes: u64,
+}
+
+/// Produces a variant by running the server-configured command template `name`.
+///
+/// The template is split on whitespace and run directly (no shell), with `{input}`,
+/// `{input_type}`, `{output}`, `{width}` and `{height}` substituted per argument. The original is written
+/// to a private temp directory that is removed afterwards, under an extension chosen by
+/// [`input_extension`]. The command is killed when it exceeds
+/// `EXTERNAL_PROCESSOR_TIMEOUT_SECS` or its output grows past
+/// `EXTERNAL_PROCESSOR_MAX_OUTPUT_BYTES`.
+pub async fn process(
+    name: &str,
+    original: &[u8],
+    stored_extension: Option<&str>,
+    config: &VariantConfig,
+) -> Result<(Vec<u8>, String), String> {
+    let server = get_config();
+    if !server.allow_external_processors {
+        return Err("External processors are disabled (ALLOW_EXTERNAL_PROCESSORS)".to_string());
+    }
+    let template = server
+        .external_processors
+        .get(&name.to_lowercase())
+        .ok_or_else(|| format!("Unknown external processor '{}'", name))?;
+    let (_, mime) = config
+        .format
+        .as_deref()
+        .and_then(image_processor::output_format)
+        .ok_or("external_command needs an explicit output format")?;
+    let output_extension = image_processor::extension_for_mime(mime).ok_or("Unsupported output format")?;
+    let allowed = server.external_processor_inputs.get(&name.to_lowercase()).map(Vec::as_slice).unwrap_or_default();
+    let input_extension = input_extension(original, stored_extension, allowed)
+        .ok_or_else(|| format!("Input type is not accepted by external processor '{}'", name))?;
+    let limits = Limits {
+        timeout: Duration::from_secs(server.external_processor_timeout_secs),
+        max_output_bytes: server.external_processor_max_output_bytes,
+    };
+
+    let dir = std::env::temp_dir().join(format!("mbk-external-{}", Uuid::new_v4()));
+    tokio::fs::create_dir(&dir)
+        .await
+        .map_err(|e| format!("Failed to create temp dir: {}", e))?;
+
+    let result = run(&dir, template, original, &input_extension, output_extension, config, limits).await;
+    if let Err(e) = tokio::fs::remove_dir_all(&dir).await {
+        eprintln!("External processor | failed to remove {}: {}", dir.display(), e);
+    }
+
+    result.map(|data| (data, mime.to_string()))
+}
+
+/// Extension the original is handed to the command under. Many tools pick their decoder
+/// from it, so it never comes from the client unchecked: a format sniffed from the bytes
+/// wins, and otherwise the stored extension must be on the processor's `allowed` list.
+fn input_extension(original: &[u8], stored_extension: Option<&str>, allowed: &[String]) -> Option<String> {
+    if let Ok(format) = image::guess_format(original) {
+        return format.extensions_str().first().map(|ext| ext.to_string());
+    }
+    let ext = stored_extension?.to_lowercase();
+    (allowed.contains(&ext) && !crate::config::scripted_input_extension(&ext)).then_some(ext)
+}
+
+async fn run(
+    dir: &Path,
+    template: &str,
+    original: &[u8],
+    input_extension: &str,
+    output_extension: &str,
+    config: &VariantConfig,
+    limits: Limits,
+) -> Result<Vec<u8>, String> {
+    let input = dir.join(format!("input.{}", input_extension));
+    let output = dir.join(format!("output.{}", output_extension));
+    tokio::fs::write(&input, original)
+        .await
+        .map_err(|e| format!("Failed to write input file: {}", e))?;
+
+    let width = config.width.or(config.max_width).map(|w| w.to_string());
+    let height = config.height.or(config.max_height).map(|h| h.to_string());
+    let args = template
+        .split_whitespace()
+        .map(|arg| {
+            if (arg.contains("{width}") && width.is_none()) || (arg.contains("{height}") && height.is_none()) {
+                return Err("Command template uses {width}/{height} but the variant does not set them".to_string());
+            }
+            Ok(arg
+                .replace("{input_type}", input_extension)
+                .replace("{input}", &input.to_string_lossy())
+                .replace("{output}", &output.to_string_lossy())
+                .replace("{width}", width.as_deref().unwrap_or_default())
+                .replace("{height}", height.as_deref().unwrap_or_default()))
+        })
+        .collect::<Result<Vec<String>, String>>()?;
+    let (program, rest) = args.split_first().ok_or("Empty command template")?;
+
+    let mut child = Command::new(program)
+        .args(rest)
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| format!("Failed to run '{}': {}", program, e))?;
+    let stderr = tokio::spawn(stderr_tail(child.stderr.take().expect("stderr is piped"), STDERR_TAIL_BYTES));
+
+    let deadline = tokio::time::sleep(limits.timeout);
+    tokio::pin!(deadline);
+    let mut poll = tokio::time::interval(OUTPUT_POLL_INTERVAL);
+    let status = loop {
+        tokio::select! {
+            status = child.wait() => break status.map_err(|e| format!("Failed to run '{}': {}", program, e))?,
+            _ = &mut deadline => {
+                return Err(format!("External command timed out after {}s", limits.timeout.as_secs()));
+            }
+            _ = poll.tick() => {
+                let size = output_size(&output).await;
+                if size > limits.max_output_bytes {
+                    return Err(output_too_large(size, limits));
+                }
+            }
+        }
+    };
+    let stderr = stderr.await.unwrap_or_default();
+
+    if !status.success() {
+        return Err(format!("External command exited with {}: {}", status, String::from_utf8_lossy(&stderr).trim()));
+    }
+
+    let size = tokio::fs::metadata(&output)
+        .await
+        .map_err(|_| "External command did not produce an output file".to_string())?
+        .len();
+    if size > limits.max_output_bytes {
+        return Err(output_too_large(size, limits));
+    }
+
+    tokio::fs::read(&output)
+        .await
+        .map_err(|e| format!("Failed to read output file: {}", e))
+}
+
+async fn output_size(output: &Path) -> u64 {
+    tokio::fs::metadata(output).await.map(|m| m.len()).unwrap_or(0)
+}
+
+fn output_too_large(size: u64, limits: Limits) -> String {
+    format!("External command output is {} bytes, over the {} byte limit", size, limits.max_output_bytes)
+}
+
+/// Drains `stream` to the end, keeping only its last `keep` bytes.
+async fn stderr_tail(mut stream: impl AsyncRead + Unpin, keep: usize) -> Vec<u8> {
+    let mut tail = Vec::with_capacity(keep * 2);
+    let mut chunk = [0u8; 8192];
+    loop {
+        match stream.read(&mut chunk).await {
+            Ok(0) | Err(_) => break,
+            Ok(n) => {
+                tail.extend_from_slice(&chunk[..n]);
+                if tail.len() > keep {
+                    tail.drain(..tail.len() - keep);
+                }
+            }
+        }
+    }
+    tail
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variant() -> VariantConfig {
+        serde_json::from_value(serde_json::json!({ "format": "png" })).unwrap()
+    }
+
+    async fn run_template(template: &str, limits: Limits) -> Result<Vec<u8>, String> {
+        let dir = std::env::temp_dir().join(format!("mbk-external-test-{}", Uuid::new_v4()));
+        tokio::fs::create_dir(&dir).await.unwrap();
+        let result = run(&dir, template, b"input bytes", "png", "png", &variant(), limits).await;
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+        result
+    }
+
+    const LIMITS: Limits = Limits { timeout: Duration::from_secs(10), max_output_bytes: 1024 * 1024 };
+
+    #[test]
+    fn sniffed_format_wins_over_the_stored_extension() {
+        let png = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n', 0, 0, 0, 0];
+        assert_eq!(input_extension(&png, Some("msl"), &[]).as_deref(), Some("png"));
+        assert_eq!(input_extension(&png, None, &[]).as_deref(), Some("png"));
+    }
+
+    #[test]
+    fn unsniffed_input_needs_an_allowed_extension() {
+        let raw = b"not a format the image crate knows";
+        let allowed = vec!["cr2".to_string(), "svg".to_string()];
+        assert_eq!(input_extension(raw, Some("CR2"), &allowed).as_deref(), Some("cr2"));
+        assert_eq!(input_extension(raw, Some("nef"), &allowed), None);
+        assert_eq!(input_extension(raw, None, &allowed), None);
+        // Scripted coders stay refused even if listed
+        for ext in ["msl", "mvg", "svg", "txt"] {
+            assert_eq!(input_extension(raw, Some(ext), &allowed), None, "{}", ext);
+        }
+    }
+
+    #[tokio::test]
+    async fn stderr_keeps_only_the_tail() {
+        let noise: Vec<u8> = (0..100_000u32).map(|i| b'a' + (i % 26) as u8).collect();
+        let tail = stderr_tail(noise.as_slice(), 500).await;
+        assert_eq!(tail, &noise[noise.len() - 500..]);
+    }
+
+    #[tokio::test]
+    async fn output_is_returned() {
+        assert_eq!(run_template("cp {input} {output}", LIMITS).await.unwrap(), b"input bytes");
+        // `{input_type}` pins the decoder for tools that take a `type:path` prefix
+        let error = run_template("cp {input_type}:{input} {output}", LIMITS).await.unwrap_err();
+        assert!(error.contains("png:/"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn growing_output_is_stopped_at_the_limit() {
+        let started = std::time::Instant::now();
+        let error = run_template("dd if=/dev/zero of={output} bs=1M count=100000", LIMITS).await.unwrap_err();
+        assert!(error.contains("over the 1048576 byte limit"), "{}", error);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn slow_commands_are_killed() {
+        let limits = Limits { timeout: Duration::from_millis(200), ..LIMITS };
+        let error = run_template("sleep 30", limits).await.unwrap_err();
+        assert!(error.contains("timed out"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn failures_report_the_end_of_stderr() {
+        let error = run_template("dd if=/dev/zero of=/dev/stderr bs=1M count=20 iflag=fullblock status=none", LIMITS)
+            .await
+            .unwrap_err();
+        assert_eq!(error, "External command did not produce an output file");
+        let error = run_template("cp {input}", LIMITS).await.unwrap_err();
+        assert!(error.starts_with("External command exited with"), "{}", error);
+        assert!(error.len() < 600, "{}", error);
+    }
+}
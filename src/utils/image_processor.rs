@@ -1,12 +1,108 @@
 use image::ImageFormat;
+use std::fmt;
 use std::io::Cursor;
+use std::sync::Arc;
 use crate::models::settings::VariantConfig;
-use crate::error::AppError;
 
-pub fn process_image(data: &[u8], config: &VariantConfig) -> Result<(Vec<u8>, String), AppError> {
+/// One rendered variant.
+pub struct ProcessedImage {
+    pub data: Vec<u8>,
+    pub mime_type: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug)]
+pub enum ProcessError {
+    /// The input or the requested output format cannot be handled by this backend
+    Unsupported(String),
+    /// Decoding, resizing or encoding failed
+    Failed(String),
+}
+
+impl fmt::Display for ProcessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProcessError::Unsupported(msg) | ProcessError::Failed(msg) => f.write_str(msg),
+        }
+    }
+}
+
+impl std::error::Error for ProcessError {}
+
+/// Renders variants for the worker. Called from a blocking thread, so implementations may do
+/// CPU-heavy work synchronously.
+///
+/// Output should depend only on the input and the config: the worker skips uploading a
+/// variant whose bytes hash the same as the stored one, so nondeterministic output is
+/// re-uploaded on every sync.
+pub trait ImageProcessor: Send + Sync {
+    /// Name used to select the backend through `IMAGE_PROCESSOR`.
+    fn name(&self) -> &'static str;
+
+    fn process(&self, data: &[u8], config: &VariantConfig) -> Result<ProcessedImage, ProcessError>;
+
+    /// Whether a variant `format` setting can be produced; checked before anything is downloaded.
+    fn supports_format(&self, format: &str) -> bool {
+        is_supported_format(format)
+    }
+}
+
+/// Backend built on the `image` crate.
+pub struct DefaultProcessor;
+
+impl ImageProcessor for DefaultProcessor {
+    fn name(&self) -> &'static str {
+        "default"
+    }
+
+    fn process(&self, data: &[u8], config: &VariantConfig) -> Result<ProcessedImage, ProcessError> {
+        process_image(data, config)
+    }
+}
+
+/// The backend registered under `name`, `None` for unknown names.
+pub fn by_name(name: &str) -> Option<Arc<dyn ImageProcessor>> {
+    match name {
+        "default" => Some(Arc::new(DefaultProcessor)),
+        _ => None,
+    }
+}
+
+// Encoder settings are pinned rather than taken from the `image` crate's defaults, so an
+// upgrade cannot silently change every variant's bytes.
+const JPEG_QUALITY: u8 = 75;
+const AVIF_SPEED: u8 = 4;
+const AVIF_QUALITY: u8 = 80;
+/// rav1e splits frames into one tile per thread, so the thread count is part of the output;
+/// the default (every core) would make the bytes depend on the host.
+const AVIF_THREADS: usize = 4;
+
+/// Encodes without timestamps or other per-run metadata: the same pixels give the same bytes.
+fn encode(img: &image::DynamicImage, format: ImageFormat) -> image::ImageResult<Vec<u8>> {
+    use image::codecs::{avif::AvifEncoder, jpeg::JpegEncoder, png, webp::WebPEncoder};
+
+    let mut buffer = Cursor::new(Vec::new());
+    match format {
+        ImageFormat::Jpeg => img.write_with_encoder(JpegEncoder::new_with_quality(&mut buffer, JPEG_QUALITY))?,
+        ImageFormat::Png => img.write_with_encoder(png::PngEncoder::new_with_quality(
+            &mut buffer,
+            png::CompressionType::Fast,
+            png::FilterType::Adaptive,
+        ))?,
+        ImageFormat::WebP => img.write_with_encoder(WebPEncoder::new_lossless(&mut buffer))?,
+        ImageFormat::Avif => img.write_with_encoder(
+            AvifEncoder::new_with_speed_quality(&mut buffer, AVIF_SPEED, AVIF_QUALITY).with_num_threads(Some(AVIF_THREADS)),
+        )?,
+        other => img.write_to(&mut buffer, other)?,
+    }
+    Ok(buffer.into_inner())
+}
+
+fn process_image(data: &[u8], config: &VariantConfig) -> Result<ProcessedImage, ProcessError> {
     // 1. Load image
     let mut img = image::load_from_memory(data)
-        .map_err(|e| AppError::InternalServerError(format!("Failed to load image: {}", e)))?;
+        .map_err(|e| ProcessError::Failed(format!("Failed to load image: {}", e)))?;
 
     // 2. Resize if needed
     // 2. Resize if needed
@@ -48,45 +144,86 @@ pub fn process_image(data: &[u8], config: &VariantConfig) -> Result<(Vec<u8>, St
     // 3. Determine Output Format
     let format_str = config.format.as_deref().unwrap_or("original");
     let (output_format, mime_type) = match format_str {
-        "avif" => (ImageFormat::Avif, "image/avif"),
-        "webp" => (ImageFormat::WebP, "image/webp"),
-        "png" => (ImageFormat::Png, "image/png"),
-        "jpg" | "jpeg" => (ImageFormat::Jpeg, "image/jpeg"),
         "original" => {
             // Detect original format
             let fmt = image::guess_format(data)
-                .map_err(|e| AppError::InternalServerError(format!("Failed to guess format: {}", e)))?;
-            let mime = match fmt {
-                ImageFormat::Avif => "image/avif",
-                ImageFormat::WebP => "image/webp",
-                ImageFormat::Png => "image/png",
-                ImageFormat::Jpeg => "image/jpeg",
-                _ => "application/octet-stream",
-            };
+                .map_err(|e| ProcessError::Unsupported(format!("Failed to guess format: {}", e)))?;
+            let mime = mime_for_format(fmt)
+                .ok_or_else(|| ProcessError::Unsupported(format!("Cannot re-encode original format {:?}", fmt)))?;
             (fmt, mime)
         },
-        _ => (ImageFormat::Jpeg, "image/jpeg"), // Default fallback
+        other => output_format(other)
+            .ok_or_else(|| ProcessError::Unsupported(format!("Unsupported output format '{}'", other)))?,
     };
 
-    // 4. Encode with Quality
-    let mut buffer = Cursor::new(Vec::new());
-    
-    // Note: The `image` crate's `write_to` doesn't always expose quality controls for all formats easily 
-    // in the generic API, but for JPEG/WebP/AVIF it often uses defaults or we can use specific encoders.
-    // For simplicity in this phase, we'll use the generic `write_to` which uses reasonable defaults,
-    // but for JPEG/WebP/AVIF we can try to respect the quality setting if we use specific encoders.
-    // However, `DynamicImage::write_to` is the most robust way to handle multiple formats.
-    // To support quality specifically, we might need to match on format.
-
-    match output_format {
-        // For now, use default quality. To support custom quality, we'd need to use specific Encoders
-        // e.g. JpegEncoder::new_with_quality(&mut buffer, quality)
-        // But for simplicity and compilation, we stick to write_to with default settings.
-        _ => {
-            img.write_to(&mut buffer, output_format)
-                .map_err(|e| AppError::InternalServerError(format!("Failed to encode image: {}", e)))?;
-        }
+    // 4. Encode
+    let data = encode(&img, output_format)
+        .map_err(|e| ProcessError::Failed(format!("Failed to encode image: {}", e)))?;
+
+    Ok(ProcessedImage {
+        data,
+        mime_type: mime_type.to_string(),
+        width: img.width(),
+        height: img.height(),
+    })
+}
+
+/// `(width, height)` read from an encoded image's header, for output no `ImageProcessor`
+/// produced. `None` for formats that cannot be decoded here (AVIF).
+pub fn dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    image::ImageReader::new(Cursor::new(data))
+        .with_guessed_format()
+        .ok()?
+        .into_dimensions()
+        .ok()
+}
+
+/// Maps a variant `format` setting to the encoder and mime type used for it.
+/// `original` is resolved per image and is not handled here.
+pub fn output_format(format: &str) -> Option<(ImageFormat, &'static str)> {
+    match format {
+        "avif" => Some((ImageFormat::Avif, "image/avif")),
+        "webp" => Some((ImageFormat::WebP, "image/webp")),
+        "png" => Some((ImageFormat::Png, "image/png")),
+        "jpg" | "jpeg" => Some((ImageFormat::Jpeg, "image/jpeg")),
+        _ => None,
     }
+}
+
+/// Whether a variant `format` setting can be produced by `DefaultProcessor`.
+pub fn is_supported_format(format: &str) -> bool {
+    format == "original" || output_format(format).is_some()
+}
+
+fn mime_for_format(format: ImageFormat) -> Option<&'static str> {
+    match format {
+        ImageFormat::Avif => Some("image/avif"),
+        ImageFormat::WebP => Some("image/webp"),
+        ImageFormat::Png => Some("image/png"),
+        ImageFormat::Jpeg => Some("image/jpeg"),
+        _ => None,
+    }
+}
 
-    Ok((buffer.into_inner(), mime_type.to_string()))
+/// File extension used in variant keys for a mime type produced by a processor.
+pub fn extension_for_mime(mime: &str) -> Option<&'static str> {
+    match mime {
+        "image/avif" => Some("avif"),
+        "image/webp" => Some("webp"),
+        "image/png" => Some("png"),
+        "image/jpeg" => Some("jpg"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backends_are_selected_by_name() {
+        assert_eq!(by_name("default").map(|p| p.name()), Some("default"));
+        assert!(by_name("vips").is_none());
+        assert!(by_name("").is_none());
+    }
 }
@@ -1,8 +1,283 @@
 pub mod image_processor;
+pub mod external_processor;
+pub mod secret_box;
+pub mod net;
+pub mod content_type;
 
-pub fn sanitize_bucket_name(name: &str) -> String {
-    name.to_lowercase()
+use sha2::{Digest, Sha256};
+
+/// Lowercased final extension of a filename (`invoice.pdf.exe` -> `exe`), if any.
+pub fn file_extension(filename: &str) -> Option<String> {
+    std::path::Path::new(filename)
+        .extension()
+        .and_then(std::ffi::OsStr::to_str)
+        .map(|ext| ext.to_lowercase())
+}
+
+/// Longest stored filename, in bytes; longer names are cut before the extension.
+pub const MAX_FILENAME_BYTES: usize = 255;
+/// Longest extension kept in an S3 key; anything longer (or not ASCII alphanumeric) becomes `bin`.
+const MAX_KEY_EXTENSION_BYTES: usize = 16;
+
+/// Normalizes a client-supplied filename before it is stored, logged or sent back in headers.
+///
+/// Keeps only the last path component and drops control characters and invisible
+/// formatting characters (bidi overrides such as U+202E, zero-width spaces, BOM). Runs of
+/// whitespace collapse to one space. Names over `MAX_FILENAME_BYTES` are truncated on a
+/// character boundary, keeping the extension.
+pub fn sanitize_filename(name: &str) -> String {
+    let base = name.rsplit(['/', '\\']).next().unwrap_or_default();
+    let mut cleaned = String::with_capacity(base.len());
+    for c in base.chars() {
+        if (c.is_control() && !c.is_whitespace()) || is_invisible_format(c) {
+            continue;
+        }
+        if c.is_whitespace() {
+            if !cleaned.is_empty() && !cleaned.ends_with(' ') {
+                cleaned.push(' ');
+            }
+        } else {
+            cleaned.push(c);
+        }
+    }
+    let cleaned = truncate_filename(cleaned.trim_end());
+
+    if cleaned.is_empty() || cleaned == "." || cleaned == ".." {
+        "unknown".to_string()
+    } else {
+        cleaned
+    }
+}
+
+/// Unicode format characters that change how a name renders without being visible:
+/// bidi embeddings/overrides/isolates, zero-width characters, the BOM and the soft hyphen.
+fn is_invisible_format(c: char) -> bool {
+    matches!(c, '\u{00AD}' | '\u{061C}' | '\u{180E}' | '\u{200B}'..='\u{200F}' | '\u{202A}'..='\u{202E}' | '\u{2060}'..='\u{2069}' | '\u{FEFF}')
+}
+
+fn truncate_filename(name: &str) -> String {
+    if name.len() <= MAX_FILENAME_BYTES {
+        return name.to_string();
+    }
+
+    // Keep a short extension; a "name" that is mostly extension is just cut
+    let ext = name.rfind('.').filter(|i| *i > 0 && name.len() - i <= MAX_KEY_EXTENSION_BYTES + 1).map(|i| &name[i..]);
+    let ext = ext.unwrap_or_default();
+    let mut end = MAX_FILENAME_BYTES - ext.len();
+    while !name.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}{}", name[..end].trim_end(), ext)
+}
+
+/// Extension used in an object key: the filename's own extension when it is short ASCII
+/// alphanumeric (case kept), otherwise `bin`.
+pub fn key_extension(filename: &str) -> String {
+    std::path::Path::new(filename)
+        .extension()
+        .and_then(std::ffi::OsStr::to_str)
+        .filter(|ext| ext.len() <= MAX_KEY_EXTENSION_BYTES && ext.bytes().all(|b| b.is_ascii_alphanumeric()))
+        .unwrap_or("bin")
+        .to_string()
+}
+
+/// `Content-Disposition` value naming `filename`, e.g. `inline; filename="a_b.pdf"; filename*=UTF-8''a%C3%A9b.pdf`.
+///
+/// `filename` carries an ASCII fallback (non-ASCII, quotes and backslashes replaced with `_`);
+/// `filename*` carries the exact name, percent-encoded per RFC 5987.
+pub fn content_disposition(disposition: &str, filename: &str) -> String {
+    let fallback: String = filename
         .chars()
-        .map(|c| if c.is_alphanumeric() { c } else { '-' })
-        .collect::<String>()
+        .map(|c| if c.is_ascii() && !c.is_ascii_control() && c != '"' && c != '\\' { c } else { '_' })
+        .collect();
+
+    let mut encoded = String::with_capacity(filename.len());
+    for byte in filename.bytes() {
+        if byte.is_ascii_alphanumeric() || b"!#$&+-.^_`|~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+
+    format!("{}; filename=\"{}\"; filename*=UTF-8''{}", disposition, fallback, encoded)
+}
+
+/// Deepest pseudo-folder accepted for a file.
+pub const MAX_FOLDER_DEPTH: usize = 16;
+/// Matches the `files.folder` column width.
+pub const MAX_FOLDER_LEN: usize = 512;
+
+/// Normalizes a client-supplied folder to `a/b/` form (`""` for the root).
+///
+/// Leading, trailing and repeated slashes are dropped. `.`/`..` segments, backslashes and
+/// control characters are rejected rather than resolved, as are paths past the depth or
+/// length cap.
+pub fn normalize_folder(raw: &str) -> Result<String, String> {
+    let mut normalized = String::new();
+    let mut depth = 0;
+    for segment in raw.split('/').map(str::trim).filter(|s| !s.is_empty()) {
+        if segment == "." || segment == ".." {
+            return Err("Folder must not contain '.' or '..' segments".to_string());
+        }
+        if segment.chars().any(|c| c.is_control() || c == '\\') {
+            return Err("Folder must not contain backslashes or control characters".to_string());
+        }
+        depth += 1;
+        normalized.push_str(segment);
+        normalized.push('/');
+    }
+
+    if depth > MAX_FOLDER_DEPTH {
+        return Err(format!("Folder is nested too deeply (max {} levels)", MAX_FOLDER_DEPTH));
+    }
+    if normalized.len() > MAX_FOLDER_LEN {
+        return Err(format!("Folder is too long (max {} bytes)", MAX_FOLDER_LEN));
+    }
+    Ok(normalized)
+}
+
+/// Escapes `LIKE` wildcards and the `\` escape character, for patterns built with `.escape('\\')`.
+pub fn escape_like(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
+
+/// Lowercase hex SHA-256 of `data`.
+pub fn sha256_hex(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::seq::SliceRandom;
+    use rand::{Rng, SeedableRng};
+
+    /// Pieces nasty names are built from: separators, dot runs, whitespace and control
+    /// characters, bidi overrides and other invisibles, emoji (with ZWJ), combining marks, RTL text.
+    const PIECES: &[&str] = &[
+        "a", "Z", "7", "-", "_", " ", "  ", "\t", "\r\n", "\n", "\0", "\u{7f}", "\u{1b}[31m",
+        "/", "\\", "//", ".", "..", "../", "..\\", "./", ".pdf", ".tar.gz", ".EXE",
+        "\u{202E}", "\u{202D}", "\u{2066}", "\u{200B}", "\u{200D}", "\u{FEFF}", "\u{00AD}",
+        "😀", "👩\u{200D}👩\u{200D}👧", "🏳️\u{200D}🌈", "e\u{301}", "é", "ß", "名前", "שלום", "مرحبا",
+        "\u{85}", "\u{2028}", "\u{3000}", "%2e%2e%2f", "\"", "'", ";", "=",
+    ];
+
+    fn nasty(rng: &mut StdRng) -> String {
+        // Mostly short names, with the odd one far past every cap
+        let pieces = if rng.gen_ratio(1, 20) { rng.gen_range(2_000..10_000) } else { rng.gen_range(0..40) };
+        (0..pieces).map(|_| *PIECES.choose(rng).unwrap()).collect()
+    }
+
+    fn samples() -> impl Iterator<Item = String> {
+        let mut rng = StdRng::seed_from_u64(0x5eed_f11e);
+        let fixed = [
+            String::new(),
+            ".".to_string(),
+            "..".to_string(),
+            "../../etc/passwd".to_string(),
+            "C:\\Windows\\..\\evil.exe".to_string(),
+            "invoice\u{202E}fdp.exe".to_string(),
+            "nul\0byte.txt".to_string(),
+            "a".repeat(10_000),
+            format!("{}.pdf", "x".repeat(10_000)),
+            format!("{}.pdf", "😀".repeat(2_500)),
+            "é".repeat(10_000),
+        ];
+        fixed.into_iter().chain((0..3_000).map(move |_| nasty(&mut rng)))
+    }
+
+    #[test]
+    fn sanitized_filenames_are_safe() {
+        for input in samples() {
+            let name = sanitize_filename(&input);
+            assert!(!name.is_empty(), "{:?}", input);
+            assert!(!name.contains(['/', '\\']), "{:?} -> {:?}", input, name);
+            // Without separators the only way to climb out is a name of exactly `..`
+            assert!(name != "." && name != "..", "{:?} -> {:?}", input, name);
+            assert!(!name.chars().any(|c| c.is_control() || is_invisible_format(c)), "{:?} -> {:?}", input, name);
+            assert!(name.len() <= MAX_FILENAME_BYTES, "{:?} -> {} bytes", input, name.len());
+            assert_eq!(name.trim(), name, "{:?}", input);
+            assert!(!name.contains("  "), "{:?} -> {:?}", input, name);
+        }
+    }
+
+    #[test]
+    fn sanitizing_is_idempotent() {
+        for input in samples() {
+            let once = sanitize_filename(&input);
+            assert_eq!(sanitize_filename(&once), once, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn truncation_keeps_the_extension() {
+        assert_eq!(sanitize_filename(&"a".repeat(10_000)), "a".repeat(MAX_FILENAME_BYTES));
+        let long_pdf = sanitize_filename(&format!("{}.pdf", "x".repeat(10_000)));
+        assert!(long_pdf.ends_with(".pdf") && long_pdf.len() == MAX_FILENAME_BYTES);
+        // Cut on a character boundary: 4-byte emoji leave the budget short of 255
+        let emoji = sanitize_filename(&format!("{}.pdf", "😀".repeat(2_500)));
+        assert!(emoji.ends_with(".pdf") && emoji.len() == 4 * 62 + 4);
+    }
+
+    #[test]
+    fn invisible_and_control_characters_are_dropped() {
+        assert_eq!(sanitize_filename("invoice\u{202E}fdp.exe"), "invoicefdp.exe");
+        assert_eq!(sanitize_filename("nul\0byte.txt"), "nulbyte.txt");
+        assert_eq!(sanitize_filename("two\r\nlines\t.txt"), "two lines .txt");
+        assert_eq!(sanitize_filename("../../etc/passwd"), "passwd");
+        assert_eq!(sanitize_filename("..\\..\\"), "unknown");
+        assert_eq!(sanitize_filename("👩\u{200D}👩\u{200D}👧.png"), "👩👩👧.png");
+    }
+
+    #[test]
+    fn content_disposition_of_any_name_is_a_valid_header() {
+        for input in samples() {
+            let name = sanitize_filename(&input);
+            let value = content_disposition("attachment", &name);
+            assert!(value.is_ascii() && !value.chars().any(|c| c.is_ascii_control()), "{:?}", value);
+            assert!(axum::http::HeaderValue::from_str(&value).is_ok(), "{:?}", value);
+            // The quoted fallback holds no quote of its own to end it early
+            let fallback = value.split("; filename*=").next().unwrap();
+            assert_eq!(fallback.matches('"').count(), 2, "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn key_extensions_are_short_ascii() {
+        for input in samples() {
+            let ext = key_extension(&sanitize_filename(&input));
+            assert!(ext.len() <= MAX_KEY_EXTENSION_BYTES && ext.bytes().all(|b| b.is_ascii_alphanumeric()), "{:?}", ext);
+        }
+    }
+
+    #[test]
+    fn normalized_folders_are_safe_and_stable() {
+        let mut accepted = 0;
+        for input in samples() {
+            let Ok(folder) = normalize_folder(&input) else { continue };
+            accepted += 1;
+            assert!(folder.is_empty() || folder.ends_with('/'), "{:?} -> {:?}", input, folder);
+            assert!(!folder.starts_with('/') && !folder.contains("//"), "{:?} -> {:?}", input, folder);
+            assert!(!folder.contains('\\') && !folder.chars().any(char::is_control), "{:?} -> {:?}", input, folder);
+            for segment in folder.split('/').filter(|s| !s.is_empty()) {
+                assert!(segment != "." && segment != "..", "{:?} -> {:?}", input, folder);
+                assert_eq!(segment.trim(), segment, "{:?} -> {:?}", input, folder);
+            }
+            assert!(folder.matches('/').count() <= MAX_FOLDER_DEPTH && folder.len() <= MAX_FOLDER_LEN);
+            assert_eq!(normalize_folder(&folder).as_ref(), Ok(&folder), "{:?}", input);
+        }
+        // The generator is meant to reach both outcomes
+        assert!(accepted > 100, "only {} folders accepted", accepted);
+    }
+
+    #[test]
+    fn folder_traversal_is_rejected() {
+        for input in ["..", "a/../b", "./a", "a/./", "a/ .. /b", "a\\b", "a/\0/b"] {
+            assert!(normalize_folder(input).is_err(), "{:?}", input);
+        }
+        assert_eq!(normalize_folder("//a//b c/ ").unwrap(), "a/b c/");
+        assert_eq!(normalize_folder("a..b/").unwrap(), "a..b/");
+    }
 }
@@ -0,0 +1,95 @@
+//! Checks for outbound connections to hosts a project configures (storage endpoints,
+//! webhook URLs), so they cannot point the server at internal services.
+
+use std::net::IpAddr;
+
+/// Whether `host` is one of `listed` or a subdomain of one.
+pub fn host_listed(host: &str, listed: &[String]) -> bool {
+    listed.iter().any(|a| host == a || host.ends_with(&format!(".{}", a)))
+}
+
+/// Refuses `host` when it is, or resolves to, a loopback, private, link-local or otherwise
+/// non-public address. Every resolved address must be public.
+pub async fn check_public_host(host: &url::Host<&str>, port: u16) -> Result<(), String> {
+    let (name, addresses) = match host {
+        url::Host::Ipv4(ip) => (ip.to_string(), vec![IpAddr::V4(*ip)]),
+        url::Host::Ipv6(ip) => (ip.to_string(), vec![IpAddr::V6(*ip)]),
+        url::Host::Domain(name) => {
+            let addresses = tokio::net::lookup_host((*name, port))
+                .await
+                .map_err(|_| format!("host {} cannot be resolved", name))?
+                .map(|a| a.ip())
+                .collect();
+            (name.to_string(), addresses)
+        }
+    };
+    if addresses.is_empty() || addresses.iter().any(|ip| !is_public(*ip)) {
+        return Err(format!("{} resolves to a private or reserved address", name));
+    }
+    Ok(())
+}
+
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            let shared = a == 100 && (b & 0xc0) == 64;
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                || a == 0
+                || shared)
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(v4) => is_public(IpAddr::V4(v4)),
+            None => {
+                let first = ip.segments()[0];
+                let unique_local = (first & 0xfe00) == 0xfc00;
+                let link_local = (first & 0xffc0) == 0xfe80;
+                !(ip.is_loopback() || ip.is_unspecified() || ip.is_multicast() || unique_local || link_local)
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn internal_addresses_are_not_public() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "255.255.255.255",
+            "::1",
+            "::",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            "::ffff:169.254.169.254",
+        ] {
+            assert!(!is_public(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in ["52.216.8.1", "1.1.1.1", "2606:4700::1111", "::ffff:52.216.8.1"] {
+            assert!(is_public(ip.parse().unwrap()), "{}", ip);
+        }
+    }
+
+    #[test]
+    fn listed_hosts_cover_their_subdomains() {
+        let listed = vec!["minio.internal".to_string()];
+        assert!(host_listed("minio.internal", &listed));
+        assert!(host_listed("bucket.minio.internal", &listed));
+        assert!(!host_listed("evilminio.internal", &listed));
+    }
+}
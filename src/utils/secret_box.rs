@@ -0,0 +1,40 @@
+//! AES-256-GCM sealing for secrets stored in the database.
+//!
+//! Sealed values are `base64(nonce || ciphertext || tag)`. The row's id is bound in as
+//! associated data, so a ciphertext copied onto another row fails to open.
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+
+pub const KEY_LEN: usize = 32;
+
+pub fn seal(key: &[u8; KEY_LEN], associated: &[u8], plaintext: &str) -> Result<String, String> {
+    let key = LessSafeKey::new(UnboundKey::new(&AES_256_GCM, key).map_err(|_| "Invalid encryption key")?);
+    let mut nonce = [0u8; NONCE_LEN];
+    SystemRandom::new().fill(&mut nonce).map_err(|_| "Failed to generate nonce")?;
+
+    let mut sealed = plaintext.as_bytes().to_vec();
+    key.seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::from(associated), &mut sealed)
+        .map_err(|_| "Encryption failed")?;
+
+    let mut out = nonce.to_vec();
+    out.extend_from_slice(&sealed);
+    Ok(STANDARD.encode(out))
+}
+
+pub fn open(key: &[u8; KEY_LEN], associated: &[u8], sealed: &str) -> Result<String, String> {
+    let key = LessSafeKey::new(UnboundKey::new(&AES_256_GCM, key).map_err(|_| "Invalid encryption key")?);
+    let raw = STANDARD.decode(sealed).map_err(|_| "Sealed value is not valid base64")?;
+    if raw.len() < NONCE_LEN {
+        return Err("Sealed value is truncated".to_string());
+    }
+
+    let (nonce, ciphertext) = raw.split_at(NONCE_LEN);
+    let nonce = Nonce::try_assume_unique_for_key(nonce).map_err(|_| "Invalid nonce")?;
+    let mut buffer = ciphertext.to_vec();
+    let plaintext = key
+        .open_in_place(nonce, Aad::from(associated), &mut buffer)
+        .map_err(|_| "Decryption failed (wrong key or tampered value)")?;
+    String::from_utf8(plaintext.to_vec()).map_err(|_| "Decrypted value is not UTF-8".to_string())
+}
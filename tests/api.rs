@@ -0,0 +1,347 @@
+//! End-to-end tests of the main flows: login, projects, API keys, uploads, the worker and
+//! file serving. See `common` for how they get a database and storage.
+
+mod common;
+
+use std::sync::Arc;
+
+use axum::body::Body;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use axum::http::{header, Method, Request, StatusCode};
+use common::{png, storage, Auth, FakeProcessor, TestApp, PASSWORD};
+use media_blob_kit::entities::project;
+use media_blob_kit::entities::user::Role;
+use sea_orm::EntityTrait;
+use serde_json::json;
+use uuid::Uuid;
+
+#[tokio::test]
+async fn login_returns_a_usable_token() {
+    let app = TestApp::spawn().await;
+    let token = app.token_for("alice", Role::User).await;
+
+    let (status, body) = app.get("/auth/me", Auth::Bearer(&token)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["username"], "alice");
+}
+
+#[tokio::test]
+async fn login_token_carries_the_user_id() {
+    let app = TestApp::spawn().await;
+    let alice = app.create_user("alice", Role::User).await;
+    let bob = app.create_user("bob", Role::User).await;
+    let (_, login) = app.login("alice", PASSWORD).await;
+    let token = login["access_token"].as_str().unwrap();
+    let claims: serde_json::Value =
+        serde_json::from_slice(&URL_SAFE_NO_PAD.decode(token.split('.').nth(1).unwrap()).unwrap()).unwrap();
+    assert_eq!(claims["user_id"], alice.to_string());
+
+    let (status, body) = app.get("/auth/me", Auth::Bearer(token)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["id"], alice.to_string());
+    let project = app.create_project(token, "Owned").await;
+    let owner = project::Entity::find_by_id(project).one(&app.db).await.unwrap().unwrap().owner_id;
+    assert_eq!(owner, alice);
+
+    let resign = |claims: &serde_json::Value| {
+        let key = jsonwebtoken::EncodingKey::from_secret(common::JWT_SECRET.as_bytes());
+        jsonwebtoken::encode(&jsonwebtoken::Header::default(), claims, &key).unwrap()
+    };
+    // Identity comes from `user_id`, not from the username in `sub`
+    let mut as_bob = claims.clone();
+    as_bob["user_id"] = bob.to_string().into();
+    let (status, body) = app.get("/auth/me", Auth::Bearer(&resign(&as_bob))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!((body["id"].as_str(), body["username"].as_str()), (Some(bob.to_string().as_str()), Some("bob")));
+
+    let mut unknown = claims.clone();
+    unknown["user_id"] = Uuid::new_v4().to_string().into();
+    let (status, _) = app.get("/auth/me", Auth::Bearer(&resign(&unknown))).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let mut missing = claims;
+    missing.as_object_mut().unwrap().remove("user_id");
+    let (status, _) = app.get("/auth/me", Auth::Bearer(&resign(&missing))).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn login_with_a_wrong_password_is_refused() {
+    let app = TestApp::spawn().await;
+    app.create_user("alice", Role::User).await;
+
+    let (status, body) = app.login("alice", "not-the-password").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert!(body.get("access_token").is_none());
+
+    let (status, _) = app.login("alice", PASSWORD).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn protected_routes_need_a_token() {
+    let app = TestApp::spawn().await;
+
+    let (status, _) = app.get("/projects", Auth::None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = app.get("/projects", Auth::Bearer("not-a-jwt")).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn tokens_are_checked_against_the_configured_secret() {
+    let app = TestApp::spawn().await;
+    let token = app.token_for("alice", Role::User).await;
+    let payload = token.split('.').nth(1).unwrap();
+    let claims: serde_json::Value = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload).unwrap()).unwrap();
+    let sign = |secret: &str| {
+        jsonwebtoken::encode(&jsonwebtoken::Header::default(), &claims, &jsonwebtoken::EncodingKey::from_secret(secret.as_bytes()))
+            .unwrap()
+    };
+
+    let (status, _) = app.get("/auth/me", Auth::Bearer(&sign(common::JWT_SECRET))).await;
+    assert_eq!(status, StatusCode::OK);
+    // The fallback the middleware once used when JWT_SECRET was read separately
+    let (status, _) = app.get("/auth/me", Auth::Bearer(&sign("secret"))).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn project_crud_round_trip() {
+    let app = TestApp::spawn().await;
+    let token = app.token_for("alice", Role::User).await;
+    let auth = Auth::Bearer(&token);
+
+    let id = app.create_project(&token, "Photos").await;
+    let (status, body) = app.get(&format!("/projects/{}", id), auth).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["name"], "Photos");
+
+    let (status, body) = app
+        .call(Method::PUT, &format!("/projects/{}", id), auth, Some(json!({ "name": "Pictures" })))
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["name"], "Pictures");
+
+    let (status, body) = app.get("/projects", auth).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"].as_array().unwrap().len(), 1);
+    assert_eq!(body["data"][0]["id"], id.to_string());
+
+    let (status, _) = app.call(Method::DELETE, &format!("/projects/{}", id), auth, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, body) = app.get("/projects", auth).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body["data"].as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn projects_of_other_users_are_not_found() {
+    let app = TestApp::spawn().await;
+    let alice = app.token_for("alice", Role::User).await;
+    let bob = app.token_for("bob", Role::User).await;
+    let id = app.create_project(&alice, "Private").await;
+
+    let (status, _) = app.get(&format!("/projects/{}", id), Auth::Bearer(&bob)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn api_key_identifies_its_project() {
+    let app = TestApp::spawn().await;
+    let fixture = app.project_with_key().await;
+
+    let (status, body) = app.get("/whoami", Auth::Key(&fixture.key)).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["project_id"], fixture.project_id.to_string());
+}
+
+#[tokio::test]
+async fn upload_routes_refuse_missing_and_unknown_keys() {
+    let app = TestApp::spawn().await;
+    let fixture = app.project_with_key().await;
+
+    let (status, _) = app.get("/whoami", Auth::None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let mut unknown = fixture.key.clone();
+    unknown.pop();
+    unknown.push(if fixture.key.ends_with('a') { 'b' } else { 'a' });
+    let (status, _) = app.get("/whoami", Auth::Key(&unknown)).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn upload_file_stores_the_object_and_the_row() {
+    let app = TestApp::spawn().await;
+    let fixture = app.project_with_key().await;
+
+    let (status, body) = app
+        .upload("/upload/file", &fixture.key, &[("file", Some("notes.txt"), "text/plain", b"hello")])
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    let id: Uuid = body["id"].as_str().unwrap().parse().unwrap();
+
+    let file = app.file(id).await.expect("file row");
+    assert_eq!(file.filename, "notes.txt");
+    assert_eq!(file.size, 5);
+    assert!(file.s3_key.starts_with(&fixture.prefix));
+    assert_eq!(storage().object(&file.s3_key).unwrap().data, b"hello");
+    assert_eq!(body["url"], format!("memory://{}/{}", common::BUCKET, file.s3_key));
+}
+
+#[tokio::test]
+async fn upload_without_a_file_part_is_a_bad_request() {
+    let app = TestApp::spawn().await;
+    let fixture = app.project_with_key().await;
+
+    let (status, _) = app.upload("/upload/file", &fixture.key, &[("folder", None, "text/plain", b"docs")]).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(storage().keys(&fixture.prefix).is_empty());
+}
+
+#[tokio::test]
+async fn uploaded_files_are_listed_and_served() {
+    let app = TestApp::spawn().await;
+    let fixture = app.project_with_key().await;
+    let (_, body) = app
+        .upload("/upload/file", &fixture.key, &[("file", Some("report.pdf"), "application/pdf", b"%PDF-1.4")])
+        .await;
+    let id = body["id"].as_str().unwrap().to_string();
+    let auth = Auth::Bearer(&fixture.token);
+
+    let (status, body) = app.get(&format!("/files/{}", id), auth).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["filename"], "report.pdf");
+
+    let (status, body) = app.get(&format!("/files?project_id={}", fixture.project_id), auth).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["data"][0]["id"], id);
+
+    let request = Request::builder()
+        .uri(format!("/files/{}/content", id))
+        .header(header::AUTHORIZATION, format!("Bearer {}", fixture.token))
+        .body(Body::empty())
+        .unwrap();
+    let response = app.send(request).await;
+    assert_eq!(response.status(), StatusCode::TEMPORARY_REDIRECT);
+    let location = response.headers()[header::LOCATION].to_str().unwrap();
+    assert!(location.starts_with(&format!("memory://{}/{}", common::BUCKET, fixture.prefix)), "{}", location);
+}
+
+#[tokio::test]
+async fn html_uploaded_as_text_is_served_as_an_attachment() {
+    let app = TestApp::spawn().await;
+    let fixture = app.project_with_key().await;
+    let page = b"<!DOCTYPE html><html><script>alert(document.cookie)</script></html>";
+    let (status, body) = app
+        .upload("/upload/file", &fixture.key, &[("file", Some("notes.txt"), "text/plain", page)])
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    let id: Uuid = body["id"].as_str().unwrap().parse().unwrap();
+
+    // Stored as what it is, with a download disposition on the object itself
+    let file = app.file(id).await.unwrap();
+    let object = storage().object(&file.s3_key).unwrap();
+    assert!(object.content_type.starts_with("text/html"), "{}", object.content_type);
+    assert!(object.content_disposition.as_deref().is_some_and(|d| d.starts_with("attachment")), "{:?}", object.content_disposition);
+
+    // And the content redirect asks for the same
+    let request = Request::builder()
+        .uri(format!("/files/{}/content", id))
+        .header(header::AUTHORIZATION, format!("Bearer {}", fixture.token))
+        .body(Body::empty())
+        .unwrap();
+    let response = app.send(request).await;
+    assert_eq!(response.status(), StatusCode::TEMPORARY_REDIRECT);
+    let location = url::Url::parse(response.headers()[header::LOCATION].to_str().unwrap()).unwrap();
+    let disposition = location.query_pairs().find(|(k, _)| k == "response-content-disposition").map(|(_, v)| v.into_owned());
+    assert!(disposition.as_deref().is_some_and(|d| d.starts_with("attachment")), "{}", location);
+}
+
+#[tokio::test]
+async fn worker_generates_the_variants_of_an_uploaded_image() {
+    let app = TestApp::spawn().await;
+    let fixture = app
+        .project_with_settings(json!({ "variants": { "thumb": { "width": 16, "height": 16, "format": "webp" } } }))
+        .await;
+    let image = png(32, 32);
+
+    let (status, body) = app
+        .upload("/upload/image", &fixture.key, &[("file", Some("cat.png"), "image/png", &image)])
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    assert_eq!(body["variants"]["thumb"]["status"], "pending");
+    let id: Uuid = body["id"].as_str().unwrap().parse().unwrap();
+
+    let jobs = app.run_jobs(id, Arc::new(FakeProcessor)).await;
+    assert_eq!(jobs[0].status, "completed", "{}", jobs[0].payload);
+
+    let file = app.file(id).await.unwrap();
+    assert_eq!(file.status, "ready");
+    let thumb_key = file.variants_json["thumb"].as_str().unwrap();
+    assert!(thumb_key.starts_with(&fixture.prefix));
+    assert_eq!(storage().object(thumb_key).unwrap().data, b"RIFF-fake-webp");
+    assert_eq!(file.variant_dimensions["thumb"], json!({ "width": 16, "height": 16 }));
+}
+
+#[tokio::test]
+async fn deleting_a_file_removes_its_objects() {
+    let app = TestApp::spawn().await;
+    let fixture = app.project_with_key().await;
+    let (_, body) = app
+        .upload("/upload/file", &fixture.key, &[("file", Some("old.txt"), "text/plain", b"bye")])
+        .await;
+    let id = body["id"].as_str().unwrap().to_string();
+    assert_eq!(storage().keys(&fixture.prefix).len(), 1);
+
+    let (status, _) = app.call(Method::DELETE, &format!("/files/{}", id), Auth::Bearer(&fixture.token), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(storage().keys(&fixture.prefix).is_empty());
+
+    let (status, _) = app.get(&format!("/files/{}", id), Auth::Bearer(&fixture.token)).await;
+    assert_eq!(status, StatusCode::GONE);
+}
+
+#[tokio::test]
+async fn api_keys_need_the_delete_scope_to_delete() {
+    let app = TestApp::spawn().await;
+    let fixture = app.project_with_key().await;
+    let (_, body) = app
+        .upload("/upload/file", &fixture.key, &[("file", Some("keep.txt"), "text/plain", b"keep")])
+        .await;
+    let id = body["id"].as_str().unwrap().to_string();
+
+    let (status, _) = app.call(Method::DELETE, &format!("/files/{}", id), Auth::Key(&fixture.key), None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(storage().keys(&fixture.prefix).len(), 1);
+}
+
+#[tokio::test]
+async fn created_resources_are_readable_at_their_location() {
+    let app = TestApp::spawn().await;
+    let su = app.token_for("root", Role::Su).await;
+    let project_id = app.create_project(&su, "media").await;
+
+    for (uri, body) in [
+        ("/users".to_string(), json!({ "username": "carol", "password": PASSWORD, "role": "user" })),
+        ("/projects".to_string(), json!({ "name": "assets" })),
+        (format!("/projects/{}/keys", project_id), json!({ "name": "ci" })),
+    ] {
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri(&uri)
+            .header(header::AUTHORIZATION, format!("Bearer {}", su))
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let response = app.send(request).await;
+        assert_eq!(response.status(), StatusCode::CREATED, "{}", uri);
+        let location = response.headers()[header::LOCATION].to_str().unwrap().to_string();
+        let (_, created) = common::read_json(response).await;
+
+        let (status, fetched) = app.get(&location, Auth::Bearer(&su)).await;
+        assert_eq!(status, StatusCode::OK, "{} -> {}: {}", uri, location, fetched);
+        assert_eq!(fetched["id"], created["id"], "{}", location);
+        assert!(fetched.get("key").is_none_or(|k| k.is_null()), "the raw key is only shown once: {}", fetched);
+    }
+}
@@ -0,0 +1,153 @@
+//! Every way `api_key_auth` refuses a key, by the `code` it answers with, and the error bodies
+//! of the key management routes.
+
+mod common;
+
+use std::time::Duration;
+
+use axum::body::Body;
+use axum::http::{HeaderValue, Method, Request, StatusCode};
+use common::{read_json, Auth, Fixture, TestApp};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+async fn whoami(app: &TestApp, key: Option<HeaderValue>) -> (StatusCode, Value) {
+    let mut request = Request::builder().uri("/whoami");
+    if let Some(key) = key {
+        request = request.header("x-api-key", key);
+    }
+    read_json(app.send(request.body(Body::empty()).unwrap()).await).await
+}
+
+fn assert_refused(response: (StatusCode, Value), code: &str) {
+    let (status, body) = response;
+    assert_eq!(status, StatusCode::UNAUTHORIZED, "{}", body);
+    assert_eq!(body["code"], code, "{}", body);
+    assert!(body["error"].is_string(), "{}", body);
+}
+
+/// Creates a second key on the fixture's project, returning its id and plaintext.
+async fn create_key(app: &TestApp, fixture: &Fixture, body: Value) -> (String, String) {
+    let uri = format!("/projects/{}/keys", fixture.project_id);
+    let (status, body) = app.post(&uri, Auth::Bearer(&fixture.token), body).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    (body["id"].as_str().unwrap().to_string(), body["key"].as_str().unwrap().to_string())
+}
+
+/// Polls the key listing until the batched failure counter of `key_id` reaches `expected`.
+async fn wait_for_failed_attempts(app: &TestApp, fixture: &Fixture, key_id: &str, expected: i64) {
+    let uri = format!("/projects/{}/keys", fixture.project_id);
+    for _ in 0..100 {
+        let (_, body) = app.get(&uri, Auth::Bearer(&fixture.token)).await;
+        let key = body["data"].as_array().unwrap().iter().find(|k| k["id"] == key_id).cloned().unwrap();
+        if key["failed_attempts"] == expected {
+            assert!(key["last_failed_at"].is_string());
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("failed_attempts of key {} never reached {}", key_id, expected);
+}
+
+#[tokio::test]
+async fn missing_key() {
+    let app = TestApp::spawn().await;
+    assert_refused(whoami(&app, None).await, "missing_key");
+}
+
+#[tokio::test]
+async fn malformed_key() {
+    let app = TestApp::spawn().await;
+    assert_refused(whoami(&app, Some(HeaderValue::from_static("sk_live_123"))).await, "malformed_key");
+    assert_refused(whoami(&app, Some(HeaderValue::from_static(""))).await, "malformed_key");
+    let not_utf8 = HeaderValue::from_bytes(b"mbk_\xff\xfe").unwrap();
+    assert_refused(whoami(&app, Some(not_utf8)).await, "malformed_key");
+}
+
+#[tokio::test]
+async fn unknown_key() {
+    let app = TestApp::spawn().await;
+    let fixture = app.project_with_key().await;
+    let unknown = format!("mbk_{}", "0".repeat(fixture.key.len() - 4));
+    assert_refused(whoami(&app, Some(HeaderValue::from_str(&unknown).unwrap())).await, "unknown_key");
+}
+
+#[tokio::test]
+async fn inactive_key_is_refused_and_counted() {
+    let app = TestApp::spawn().await;
+    let fixture = app.project_with_key().await;
+    let (key_id, key) = create_key(&app, &fixture, json!({ "name": "old integration" })).await;
+    let uri = format!("/projects/{}/keys/{}", fixture.project_id, key_id);
+    let (status, _) = app
+        .call(Method::PATCH, &uri, Auth::Bearer(&fixture.token), Some(json!({ "is_active": false })))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    assert_refused(app.get("/whoami", Auth::Key(&key)).await, "inactive_key");
+    wait_for_failed_attempts(&app, &fixture, &key_id, 1).await;
+}
+
+#[tokio::test]
+async fn expired_key_is_refused_and_counted() {
+    let app = TestApp::spawn().await;
+    let fixture = app.project_with_key().await;
+    let (key_id, key) = create_key(&app, &fixture, json!({ "name": "lapsed", "expires_at": "2020-01-01T00:00:00" })).await;
+
+    assert_refused(app.get("/whoami", Auth::Key(&key)).await, "expired_key");
+    wait_for_failed_attempts(&app, &fixture, &key_id, 1).await;
+}
+
+#[tokio::test]
+async fn unknown_key_is_not_counted() {
+    let app = TestApp::spawn().await;
+    let fixture = app.project_with_key().await;
+    let unknown = format!("mbk_{}", "0".repeat(fixture.key.len() - 4));
+    assert_refused(app.get("/whoami", Auth::Key(&unknown)).await, "unknown_key");
+
+    let (status, _) = app.get("/whoami", Auth::Key(&fixture.key)).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, body) = app.get(&format!("/projects/{}/keys", fixture.project_id), Auth::Bearer(&fixture.token)).await;
+    assert_eq!(body["data"][0]["failed_attempts"], 0);
+}
+
+/// The `{"error": ...}` body every other route answers with, and nothing else.
+fn assert_error_body(response: (StatusCode, Value), status: StatusCode, message: &str) {
+    assert_eq!(response, (status, json!({ "error": message })));
+}
+
+#[tokio::test]
+async fn key_routes_answer_with_the_shared_error_body() {
+    let app = TestApp::spawn().await;
+    let fixture = app.project_with_key().await;
+    let missing_project = format!("/projects/{}/keys", Uuid::new_v4());
+    let missing_key = format!("/projects/{}/keys/{}", fixture.project_id, Uuid::new_v4());
+
+    // Same shape as a project route answering for a missing project
+    let response = app.get(&format!("/projects/{}", Uuid::new_v4()), Auth::Bearer(&fixture.token)).await;
+    assert_error_body(response, StatusCode::NOT_FOUND, "Project not found");
+
+    let cases = [
+        (Method::POST, missing_project.clone(), Some(json!({ "name": "ci" })), "Project not found"),
+        (Method::GET, missing_project.clone(), None, "Project not found"),
+        (Method::PATCH, format!("{}/{}", missing_project, Uuid::new_v4()), Some(json!({})), "Project not found"),
+        (Method::DELETE, format!("{}/{}", missing_project, Uuid::new_v4()), None, "Project not found"),
+        (Method::PATCH, missing_key.clone(), Some(json!({ "is_active": false })), "API Key not found"),
+        (Method::DELETE, missing_key, None, "API Key not found"),
+    ];
+    for (method, uri, body, message) in cases {
+        let response = app.call(method, &uri, Auth::Bearer(&fixture.token), body).await;
+        assert_error_body(response, StatusCode::NOT_FOUND, message);
+    }
+}
+
+#[tokio::test]
+async fn key_listing_rejects_bad_parameters_with_the_shared_error_body() {
+    let app = TestApp::spawn().await;
+    let fixture = app.project_with_key().await;
+    let uri = format!("/projects/{}/keys", fixture.project_id);
+
+    let response = app.get(&format!("{}?sort=name", uri), Auth::Bearer(&fixture.token)).await;
+    assert_error_body(response, StatusCode::BAD_REQUEST, "Invalid sort 'name', expected 'created' or 'expiry'");
+    let response = app.get(&format!("{}?page=0", uri), Auth::Bearer(&fixture.token)).await;
+    assert_error_body(response, StatusCode::BAD_REQUEST, "page must be at least 1");
+}
@@ -0,0 +1,79 @@
+//! `POST /admin/backfill` jobs belong to a project, or to none, rather than to one of its files.
+
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::{Auth, TestApp};
+use media_blob_kit::entities::{file, job, user::Role};
+use sea_orm::sea_query::Expr;
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+use serde_json::json;
+use uuid::Uuid;
+
+/// Uploads `count` text files and forgets their checksums, so a `content_hash` backfill visits them.
+async fn unhashed_files(app: &TestApp, key: &str, count: usize) -> Vec<Uuid> {
+    let mut ids = Vec::new();
+    for n in 0..count {
+        let name = format!("{}.txt", n);
+        let (status, body) = app.upload("/upload/file", key, &[("file", Some(&name), "text/plain", b"hello")]).await;
+        assert_eq!(status, StatusCode::CREATED, "{}", body);
+        ids.push(body["id"].as_str().unwrap().parse().unwrap());
+    }
+    file::Entity::update_many()
+        .col_expr(file::Column::ContentHash, Expr::value(Option::<String>::None))
+        .filter(file::Column::Id.is_in(ids.clone()))
+        .exec(&app.db)
+        .await
+        .unwrap();
+    ids
+}
+
+#[tokio::test]
+async fn backfill_jobs_outlive_the_files_they_visit() {
+    let app = TestApp::spawn().await;
+    let su = app.token_for("backfill-su", Role::Su).await;
+    let fixture = app.project_with_key().await;
+    let files = unhashed_files(&app, &fixture.key, 2).await;
+
+    let (status, body) = app
+        .post("/admin/backfill", Auth::Bearer(&su), json!({ "attributes": ["content_hash"], "project_id": fixture.project_id }))
+        .await;
+    assert_eq!(status, StatusCode::ACCEPTED, "{}", body);
+    assert_eq!(body["files"], 2);
+    let job_id: Uuid = body["job_id"].as_str().unwrap().parse().unwrap();
+
+    for id in &files {
+        let (status, body) = app.call(Method::DELETE, &format!("/files/{}", id), Auth::Bearer(&fixture.token), None).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+    }
+
+    let job = job::Entity::find_by_id(job_id).one(&app.db).await.unwrap().expect("job survives its files");
+    assert_eq!(job.file_id, None);
+    assert_eq!(job.project_id, Some(fixture.project_id));
+
+    let (status, body) = app.get(&format!("/admin/jobs?project_id={}", fixture.project_id), Auth::Bearer(&su)).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let listed = &body["Fixture"]["jobs"][0];
+    assert_eq!(listed["id"], job_id.to_string());
+    assert!(listed["file_id"].is_null() && listed["filename"].is_null(), "{}", listed);
+    assert_eq!(listed["project_id"], fixture.project_id.to_string());
+}
+
+#[tokio::test]
+async fn backfills_across_all_projects_have_no_project() {
+    let app = TestApp::spawn().await;
+    let su = app.token_for("backfill-su", Role::Su).await;
+    let fixture = app.project_with_key().await;
+    unhashed_files(&app, &fixture.key, 1).await;
+
+    let (status, body) = app.post("/admin/backfill", Auth::Bearer(&su), json!({ "attributes": ["content_hash"] })).await;
+    assert_eq!(status, StatusCode::ACCEPTED, "{}", body);
+    let job_id: Uuid = body["job_id"].as_str().unwrap().parse().unwrap();
+
+    let job = job::Entity::find_by_id(job_id).one(&app.db).await.unwrap().unwrap();
+    assert_eq!((job.file_id, job.project_id), (None, None));
+
+    // Still reachable for Su through the job views
+    let (status, body) = app.get(&format!("/admin/jobs/{}/events", job_id), Auth::Bearer(&su)).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+}
@@ -0,0 +1,102 @@
+//! The cleanup service's passes against the test schema, one at a time.
+
+mod common;
+
+use std::collections::BTreeSet;
+
+use chrono::{Duration, Utc};
+use common::{init_env, TestApp};
+use media_blob_kit::entities::{job, refresh_token, user::Role};
+use media_blob_kit::models::job::JobPayload;
+use media_blob_kit::services::cleanup::CleanupService;
+use sea_orm::{ActiveModelTrait, ActiveValue::Set, EntityTrait};
+use serde_json::json;
+use uuid::Uuid;
+
+fn env() {
+    init_env(&[("REFRESH_TOKEN_GRACE_DAYS", "7")]);
+}
+
+#[tokio::test]
+async fn only_stale_refresh_tokens_are_removed() {
+    env();
+    let app = TestApp::spawn().await;
+    let user_id = app.create_user("alice", Role::User).await;
+    let now = Utc::now();
+
+    // (name, created, expires, revoked, kept)
+    let cases = [
+        ("live", now, now + Duration::days(30), false, true),
+        ("old but unexpired", now - Duration::days(20), now + Duration::days(10), false, true),
+        ("expired within the grace", now - Duration::days(31), now - Duration::days(6), false, true),
+        ("expired past the grace", now - Duration::days(40), now - Duration::days(8), false, false),
+        ("revoked within the grace", now - Duration::days(6), now + Duration::days(24), true, true),
+        ("revoked past the grace", now - Duration::days(8), now + Duration::days(22), true, false),
+    ];
+    let mut kept = BTreeSet::new();
+    for (name, created_at, expires_at, revoked, keep) in cases {
+        let token = refresh_token::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            user_id: Set(user_id),
+            token_hash: Set(name.to_string()),
+            expires_at: Set(expires_at),
+            created_at: Set(created_at),
+            revoked: Set(revoked),
+            user_agent: Set(None),
+            ip: Set(None),
+            family_id: Set(Uuid::new_v4()),
+        }
+        .insert(&app.db)
+        .await
+        .unwrap();
+        if keep {
+            kept.insert(token.token_hash);
+        }
+    }
+
+    let removed = CleanupService::new(app.db.clone()).clean_refresh_tokens().await.unwrap();
+    assert_eq!(removed, 2);
+    let left: BTreeSet<String> =
+        refresh_token::Entity::find().all(&app.db).await.unwrap().into_iter().map(|t| t.token_hash).collect();
+    assert_eq!(left, kept);
+
+    // Nothing left to do on a second run
+    assert_eq!(CleanupService::new(app.db.clone()).clean_refresh_tokens().await.unwrap(), 0);
+}
+
+/// A finished reconcile job of `project_id`, last updated `age` ago.
+async fn finished_job(app: &TestApp, project_id: Uuid, age: Duration) -> Uuid {
+    let at = Utc::now() - age;
+    job::ActiveModel {
+        id: Set(Uuid::new_v4()),
+        file_id: Set(None),
+        project_id: Set(Some(project_id)),
+        status: Set("completed".to_string()),
+        payload: Set(JobPayload::ReconcileStorage { project_id, progress: Default::default() }.to_value()),
+        created_at: Set(at),
+        updated_at: Set(at),
+    }
+    .insert(&app.db)
+    .await
+    .unwrap()
+    .id
+}
+
+#[tokio::test]
+async fn job_history_follows_each_project_window() {
+    env();
+    let app = TestApp::spawn().await;
+    let short = app.project_with_settings(json!({ "retention": { "job_history_days": 2 } })).await;
+    let default = app.project_with_key().await;
+
+    let recent = finished_job(&app, short.project_id, Duration::days(1)).await;
+    let stale = finished_job(&app, short.project_id, Duration::days(3)).await;
+    let kept_forever = finished_job(&app, default.project_id, Duration::days(400)).await;
+
+    CleanupService::new(app.db.clone()).prune_job_history().await.unwrap();
+
+    let left: BTreeSet<Uuid> = job::Entity::find().all(&app.db).await.unwrap().into_iter().map(|j| j.id).collect();
+    assert!(!left.contains(&stale));
+    assert!(left.contains(&recent));
+    assert!(left.contains(&kept_forever), "JOB_HISTORY_DAYS defaults to keeping finished jobs");
+}
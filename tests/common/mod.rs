@@ -0,0 +1,368 @@
+//! Harness for the integration tests.
+//!
+//! Each [`TestApp`] runs the real router against its own Postgres schema under
+//! `TEST_DATABASE_URL`, migrated from scratch and dropped when the app goes out of scope.
+//! Without the variable the tests fail rather than pass untested. Objects go to one shared
+//! [`MemoryStorage`]; tests keep apart by the storage prefix of the projects they create.
+
+#![allow(dead_code)]
+
+use std::sync::{Arc, Once, OnceLock};
+use std::time::Duration;
+
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHasher, SaltString},
+    Argon2,
+};
+use axum::body::Body;
+use axum::http::{header, Method, Request, StatusCode};
+use axum::response::Response;
+use axum::Router;
+use http_body_util::BodyExt;
+use media_blob_kit::entities::{file, job, project, user};
+use media_blob_kit::models::settings::VariantConfig;
+use media_blob_kit::services::memory_storage::MemoryStorage;
+use media_blob_kit::services::project_storage;
+use media_blob_kit::services::worker::Worker;
+use media_blob_kit::utils::image_processor::{ImageProcessor, ProcessError, ProcessedImage};
+use media_blob_kit::{create_app, AppState};
+use migration::{Migrator, MigratorTrait};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectOptions, ConnectionTrait, Database, DatabaseConnection, EntityTrait, QueryFilter, Set,
+};
+use serde_json::{json, Value};
+use tower::ServiceExt;
+use uuid::Uuid;
+
+pub const JWT_SECRET: &str = "integration-tests-only-0123456789abcdef";
+pub const BUCKET: &str = "media-blob-kit-test";
+pub const PASSWORD: &str = "Correct-Horse-42";
+
+static ENV: Once = Once::new();
+
+/// Sets the variables the global config is read from. Only the first call in a test binary
+/// counts, so a binary needing other settings passes them before spawning any app.
+pub fn init_env(extra: &[(&str, &str)]) {
+    ENV.call_once(|| {
+        let database_url = std::env::var("TEST_DATABASE_URL").unwrap_or_default();
+        let defaults = [
+            ("DATABASE_URL", database_url.as_str()),
+            ("JWT_SECRET", JWT_SECRET),
+            ("AWS_REGION", "us-east-1"),
+            ("AWS_ACCESS_KEY_ID", "test"),
+            ("AWS_SECRET_ACCESS_KEY", "test"),
+            ("S3_BUCKET_NAME", BUCKET),
+            ("DOCS_ENABLED", "false"),
+        ];
+        for (name, value) in defaults.iter().chain(extra) {
+            std::env::set_var(name, value);
+        }
+    });
+}
+
+/// The storage every project without its own bucket uses in tests.
+pub fn storage() -> Arc<MemoryStorage> {
+    static STORAGE: OnceLock<Arc<MemoryStorage>> = OnceLock::new();
+    STORAGE
+        .get_or_init(|| {
+            let storage = Arc::new(MemoryStorage::new(BUCKET));
+            project_storage::set_default(storage.clone());
+            storage
+        })
+        .clone()
+}
+
+/// How a request authenticates.
+#[derive(Clone, Copy)]
+pub enum Auth<'a> {
+    None,
+    Bearer(&'a str),
+    Key(&'a str),
+}
+
+pub struct TestApp {
+    pub db: DatabaseConnection,
+    pub router: Router,
+    base_url: String,
+    schema: String,
+}
+
+impl TestApp {
+    /// Panics when `TEST_DATABASE_URL` is unset, so a run without a database fails instead
+    /// of passing without testing anything.
+    pub async fn spawn() -> Self {
+        Self::spawn_with(|_| {}).await
+    }
+
+    /// Like `spawn`, letting the test adjust the router state first.
+    pub async fn spawn_with(configure: impl FnOnce(&mut AppState)) -> Self {
+        let base_url = std::env::var("TEST_DATABASE_URL")
+            .expect("TEST_DATABASE_URL must point at a Postgres database for the integration tests");
+        init_env(&[]);
+        storage();
+
+        let schema = format!("test_{}", Uuid::new_v4().simple());
+        let admin = Database::connect(&base_url).await.expect("connect to TEST_DATABASE_URL");
+        admin
+            .execute_unprepared(&format!("CREATE SCHEMA {}", schema))
+            .await
+            .expect("create test schema");
+        let _ = admin.close().await;
+
+        let mut options = ConnectOptions::new(base_url.clone());
+        options
+            .set_schema_search_path(schema.clone())
+            .max_connections(5)
+            .sqlx_logging(false);
+        let db = Database::connect(options).await.expect("connect to test schema");
+        Migrator::up(&db, None).await.expect("run migrations");
+
+        let mut state = AppState::new(db.clone());
+        configure(&mut state);
+        let router = create_app(state);
+
+        Self { db, router, base_url, schema }
+    }
+
+    pub async fn send(&self, request: Request<Body>) -> Response {
+        self.router.clone().oneshot(request).await.expect("router is infallible")
+    }
+
+    /// Sends `body` as JSON (or nothing) and returns the status with the parsed response:
+    /// `Null` when empty, a string when it is not JSON.
+    pub async fn call(&self, method: Method, uri: &str, auth: Auth<'_>, body: Option<Value>) -> (StatusCode, Value) {
+        let mut request = authorized(Request::builder().method(method).uri(uri), auth);
+        let body = match body {
+            Some(body) => {
+                request = request.header(header::CONTENT_TYPE, "application/json");
+                Body::from(body.to_string())
+            }
+            None => Body::empty(),
+        };
+        read_json(self.send(request.body(body).unwrap()).await).await
+    }
+
+    pub async fn get(&self, uri: &str, auth: Auth<'_>) -> (StatusCode, Value) {
+        self.call(Method::GET, uri, auth, None).await
+    }
+
+    pub async fn post(&self, uri: &str, auth: Auth<'_>, body: Value) -> (StatusCode, Value) {
+        self.call(Method::POST, uri, auth, Some(body)).await
+    }
+
+    /// Posts a multipart form; each part is `(name, filename, content_type, bytes)`.
+    pub async fn upload(&self, uri: &str, key: &str, parts: &[(&str, Option<&str>, &str, &[u8])]) -> (StatusCode, Value) {
+        let (content_type, body) = multipart(parts);
+        let request = authorized(Request::builder().method(Method::POST).uri(uri), Auth::Key(key))
+            .header(header::CONTENT_TYPE, content_type)
+            .body(Body::from(body))
+            .unwrap();
+        read_json(self.send(request).await).await
+    }
+
+    /// Inserts a user with [`PASSWORD`] straight into the database.
+    pub async fn create_user(&self, username: &str, role: user::Role) -> Uuid {
+        let salt = SaltString::generate(&mut OsRng);
+        let password_hash = Argon2::default().hash_password(PASSWORD.as_bytes(), &salt).unwrap().to_string();
+        let id = Uuid::new_v4();
+        user::ActiveModel {
+            id: Set(id),
+            username: Set(username.to_string()),
+            password: Set(password_hash),
+            role: Set(role),
+            created_at: Set(chrono::Utc::now()),
+            email: Set(None),
+            must_change_password: Set(false),
+            totp_secret: Set(None),
+            totp_enabled_at: Set(None),
+            totp_recovery_codes: Set(json!([])),
+            totp_last_step: Set(None),
+            last_login_at: Set(None),
+            login_count: Set(0),
+            token_version: Set(0),
+        }
+        .insert(&self.db)
+        .await
+        .expect("insert user");
+        id
+    }
+
+    /// Logs in and returns the whole login response.
+    pub async fn login(&self, username: &str, password: &str) -> (StatusCode, Value) {
+        self.post("/auth/login", Auth::None, json!({ "username": username, "password": password })).await
+    }
+
+    /// Creates a user with `role` and returns an access token for them.
+    pub async fn token_for(&self, username: &str, role: user::Role) -> String {
+        self.create_user(username, role).await;
+        let (status, body) = self.login(username, PASSWORD).await;
+        assert_eq!(status, StatusCode::OK, "login failed: {}", body);
+        body["access_token"].as_str().unwrap().to_string()
+    }
+
+    /// Creates a project owned by the token's user and returns its id.
+    pub async fn create_project(&self, token: &str, name: &str) -> Uuid {
+        self.create_project_with(token, name, json!({})).await
+    }
+
+    pub async fn create_project_with(&self, token: &str, name: &str, settings: Value) -> Uuid {
+        let body = json!({ "name": name, "settings": settings });
+        let (status, body) = self.post("/projects", Auth::Bearer(token), body).await;
+        assert_eq!(status, StatusCode::CREATED, "project creation failed: {}", body);
+        body["id"].as_str().unwrap().parse().unwrap()
+    }
+
+    /// Creates an upload key for the project and returns the plaintext key.
+    pub async fn create_api_key(&self, token: &str, project_id: Uuid) -> String {
+        let uri = format!("/projects/{}/keys", project_id);
+        let (status, body) = self.post(&uri, Auth::Bearer(token), json!({ "name": "tests" })).await;
+        assert_eq!(status, StatusCode::CREATED, "key creation failed: {}", body);
+        body["key"].as_str().unwrap().to_string()
+    }
+
+    /// Where the project's objects live in [`storage`], with the trailing slash.
+    pub async fn storage_prefix(&self, project_id: Uuid) -> String {
+        let project = project::Entity::find_by_id(project_id).one(&self.db).await.unwrap().expect("project exists");
+        format!("{}/", project.storage_prefix)
+    }
+
+    /// A project with an upload key, owned by a fresh `user` of its own.
+    pub async fn project_with_key(&self) -> Fixture {
+        self.project_with_settings(json!({})).await
+    }
+
+    pub async fn project_with_settings(&self, settings: Value) -> Fixture {
+        let owner = format!("owner-{}", &Uuid::new_v4().simple().to_string()[..8]);
+        let token = self.token_for(&owner, user::Role::User).await;
+        let project_id = self.create_project_with(&token, "Fixture", settings).await;
+        let key = self.create_api_key(&token, project_id).await;
+        let prefix = self.storage_prefix(project_id).await;
+        Fixture { token, project_id, key, prefix }
+    }
+
+    /// Runs a worker until the file's jobs have all finished, then stops it and returns them.
+    pub async fn run_jobs(&self, file_id: Uuid, processor: Arc<dyn ImageProcessor>) -> Vec<job::Model> {
+        let worker = Worker::with_processor(self.db.clone(), processor).await;
+        let handle = tokio::spawn(async move { worker.run().await });
+        let jobs = self.wait_for_jobs(file_id).await;
+        handle.abort();
+        jobs
+    }
+
+    /// Polls until none of the file's jobs is pending or processing.
+    pub async fn wait_for_jobs(&self, file_id: Uuid) -> Vec<job::Model> {
+        for _ in 0..200 {
+            let jobs = job::Entity::find()
+                .filter(job::Column::FileId.eq(file_id))
+                .all(&self.db)
+                .await
+                .unwrap();
+            if !jobs.is_empty() && jobs.iter().all(|j| j.status != "pending" && j.status != "processing") {
+                return jobs;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        panic!("jobs of file {} did not finish", file_id);
+    }
+
+    /// A connection URL for this app's schema, for running the binary against it.
+    pub fn database_url(&self) -> String {
+        let separator = if self.base_url.contains('?') { '&' } else { '?' };
+        format!("{}{}options=-c%20search_path%3D{}", self.base_url, separator, self.schema)
+    }
+
+    pub async fn file(&self, file_id: Uuid) -> Option<file::Model> {
+        file::Entity::find_by_id(file_id).one(&self.db).await.unwrap()
+    }
+}
+
+/// Renders every variant as the same few bytes of "WebP", at the configured size.
+pub struct FakeProcessor;
+
+impl ImageProcessor for FakeProcessor {
+    fn name(&self) -> &'static str {
+        "fake"
+    }
+
+    fn process(&self, _data: &[u8], config: &VariantConfig) -> Result<ProcessedImage, ProcessError> {
+        Ok(ProcessedImage {
+            data: b"RIFF-fake-webp".to_vec(),
+            mime_type: "image/webp".to_string(),
+            width: config.width.unwrap_or(1),
+            height: config.height.unwrap_or(1),
+        })
+    }
+}
+
+impl Drop for TestApp {
+    fn drop(&mut self) {
+        let base_url = self.base_url.clone();
+        let schema = self.schema.clone();
+        // The test's runtime may be shutting down; drop the schema from a runtime of our own
+        let _ = std::thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+            runtime.block_on(async {
+                if let Ok(admin) = Database::connect(&base_url).await {
+                    let _ = admin.execute_unprepared(&format!("DROP SCHEMA IF EXISTS {} CASCADE", schema)).await;
+                    let _ = admin.close().await;
+                }
+            });
+        })
+        .join();
+    }
+}
+
+pub struct Fixture {
+    pub token: String,
+    pub project_id: Uuid,
+    pub key: String,
+    /// Storage prefix, with the trailing slash
+    pub prefix: String,
+}
+
+fn authorized(request: axum::http::request::Builder, auth: Auth<'_>) -> axum::http::request::Builder {
+    match auth {
+        Auth::None => request,
+        Auth::Bearer(token) => request.header(header::AUTHORIZATION, format!("Bearer {}", token)),
+        Auth::Key(key) => request.header("x-api-key", key),
+    }
+}
+
+pub async fn read_json(response: Response) -> (StatusCode, Value) {
+    let status = response.status();
+    let bytes = response.into_body().collect().await.unwrap().to_bytes();
+    let body = if bytes.is_empty() {
+        Value::Null
+    } else {
+        serde_json::from_slice(&bytes).unwrap_or_else(|_| Value::String(String::from_utf8_lossy(&bytes).into_owned()))
+    };
+    (status, body)
+}
+
+/// `multipart/form-data` content type and body for `parts`.
+pub fn multipart(parts: &[(&str, Option<&str>, &str, &[u8])]) -> (String, Vec<u8>) {
+    let boundary = format!("----media-blob-kit-{}", Uuid::new_v4().simple());
+    let mut body = Vec::new();
+    for (name, filename, content_type, data) in parts {
+        body.extend_from_slice(format!("--{}\r\n", boundary).as_bytes());
+        match filename {
+            Some(filename) => body.extend_from_slice(
+                format!("Content-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\n", name, filename).as_bytes(),
+            ),
+            None => body.extend_from_slice(format!("Content-Disposition: form-data; name=\"{}\"\r\n", name).as_bytes()),
+        }
+        body.extend_from_slice(format!("Content-Type: {}\r\n\r\n", content_type).as_bytes());
+        body.extend_from_slice(data);
+        body.extend_from_slice(b"\r\n");
+    }
+    body.extend_from_slice(format!("--{}--\r\n", boundary).as_bytes());
+    (format!("multipart/form-data; boundary={}", boundary), body)
+}
+
+/// A small PNG of `width` x `height`.
+pub fn png(width: u32, height: u32) -> Vec<u8> {
+    let image = image::RgbImage::from_fn(width, height, |x, y| image::Rgb([(x * 7) as u8, (y * 5) as u8, 128]));
+    let mut out = std::io::Cursor::new(Vec::new());
+    image.write_to(&mut out, image::ImageFormat::Png).unwrap();
+    out.into_inner()
+}
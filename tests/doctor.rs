@@ -0,0 +1,70 @@
+//! The `doctor` subcommand, run as the real binary against the test database.
+
+mod common;
+
+use std::process::Command;
+
+use common::{TestApp, BUCKET, JWT_SECRET};
+use serde_json::Value;
+
+/// Runs `doctor --json` with only `vars` set, outside the repo so no `.env` is picked up.
+fn doctor(vars: &[(&str, &str)]) -> (i32, Value) {
+    let output = Command::new(env!("CARGO_BIN_EXE_media-blob-kit"))
+        .args(["doctor", "--json"])
+        .env_clear()
+        .env("PATH", std::env::var("PATH").unwrap_or_default())
+        .envs(vars.iter().copied())
+        .current_dir(std::env::temp_dir())
+        .output()
+        .expect("run the binary");
+    let report = serde_json::from_slice(&output.stdout)
+        .unwrap_or_else(|e| panic!("{}: {}", e, String::from_utf8_lossy(&output.stdout)));
+    (output.status.code().unwrap(), report)
+}
+
+fn status<'a>(report: &'a Value, check: &str) -> &'a str {
+    report["checks"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|c| c["name"] == check)
+        .unwrap_or_else(|| panic!("no {} check in {}", check, report))["status"]
+        .as_str()
+        .unwrap()
+}
+
+#[test]
+fn config_that_does_not_load_ends_the_run() {
+    let (code, report) = doctor(&[("DATABASE_URL", "postgres://nowhere/db")]);
+    assert_eq!(code, 1);
+    assert_eq!(report["checks"].as_array().unwrap().len(), 1, "{}", report);
+    assert_eq!(status(&report, "config"), "fail");
+    assert_eq!(report["failures"], 1);
+}
+
+#[tokio::test]
+async fn database_and_storage_are_probed() {
+    let app = TestApp::spawn().await;
+    let database_url = app.database_url();
+    let vars = [
+        ("DATABASE_URL", database_url.as_str()),
+        ("JWT_SECRET", JWT_SECRET),
+        ("AWS_REGION", "us-east-1"),
+        ("AWS_ACCESS_KEY_ID", "test"),
+        ("AWS_SECRET_ACCESS_KEY", "test"),
+        ("S3_BUCKET_NAME", BUCKET),
+        // Nothing listens there, so every storage probe fails fast
+        ("S3_ENDPOINT", "http://127.0.0.1:9"),
+        ("S3_OPERATION_TIMEOUT_SECS", "1"),
+        ("WORKER_CONCURRENCY", "1"),
+    ];
+
+    let (code, report) = doctor(&vars);
+    assert_eq!(code, 1, "{}", report);
+    assert_eq!(status(&report, "config"), "pass");
+    assert_eq!(status(&report, "jwt_secret"), "pass");
+    assert_eq!(status(&report, "database"), "pass");
+    assert_eq!(status(&report, "migrations"), "pass");
+    assert_eq!(status(&report, "storage"), "fail");
+    assert_eq!(status(&report, "worker"), "pass");
+}
@@ -0,0 +1,106 @@
+//! How both image upload endpoints name what they store when the client's filename is missing
+//! or disagrees with the bytes: the sniffed format wins.
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{storage, Fixture, TestApp};
+use serde_json::json;
+use uuid::Uuid;
+
+fn jpeg() -> Vec<u8> {
+    let image = image::RgbImage::from_fn(8, 8, |x, y| image::Rgb([(x * 30) as u8, (y * 30) as u8, 64]));
+    let mut out = std::io::Cursor::new(Vec::new());
+    image.write_to(&mut out, image::ImageFormat::Jpeg).unwrap();
+    out.into_inner()
+}
+
+/// Uploads one JPEG part through `/upload/image` or `/upload/images`; returns the file id.
+async fn upload(app: &TestApp, fixture: &Fixture, endpoint: &str, filename: Option<&str>, content_type: &str) -> Uuid {
+    let data = jpeg();
+    let (status, body) = app.upload(endpoint, &fixture.key, &[("file", filename, content_type, &data)]).await;
+    let id = match endpoint {
+        "/upload/image" => {
+            assert_eq!(status, StatusCode::CREATED, "{}", body);
+            &body["id"]
+        }
+        _ => {
+            assert_eq!(status, StatusCode::CREATED, "{}", body);
+            assert_eq!(body["errors"], json!([]), "{}", body);
+            &body["uploaded"][0]["id"]
+        }
+    };
+    id.as_str().unwrap().parse().unwrap()
+}
+
+/// Stored (filename, key extension, mime type), with the object's content type checked against it.
+async fn stored(app: &TestApp, id: Uuid) -> (String, String, String) {
+    let file = app.file(id).await.unwrap();
+    let object = storage().object(&file.s3_key).unwrap();
+    assert_eq!(object.content_type, file.mime_type);
+    let ext = file.s3_key.rsplit_once('.').unwrap().1.to_string();
+    (file.filename, ext, file.mime_type)
+}
+
+const ENDPOINTS: [&str; 2] = ["/upload/image", "/upload/images"];
+
+#[tokio::test]
+async fn part_without_a_filename_is_named_after_its_format() {
+    let app = TestApp::spawn().await;
+    let fixture = app.project_with_key().await;
+
+    for endpoint in ENDPOINTS {
+        let id = upload(&app, &fixture, endpoint, None, "image/jpeg").await;
+        let expected = ("upload.jpg".to_string(), "jpg".to_string(), "image/jpeg".to_string());
+        assert_eq!(stored(&app, id).await, expected, "{}", endpoint);
+    }
+}
+
+#[tokio::test]
+async fn wrong_extension_and_content_type_give_way_to_the_bytes() {
+    let app = TestApp::spawn().await;
+    let fixture = app.project_with_key().await;
+
+    for endpoint in ENDPOINTS {
+        let id = upload(&app, &fixture, endpoint, Some("photo.png"), "image/png").await;
+        // The client's name is kept for display; the key and type are not derived from it
+        let expected = ("photo.png".to_string(), "jpg".to_string(), "image/jpeg".to_string());
+        assert_eq!(stored(&app, id).await, expected, "{}", endpoint);
+    }
+}
+
+#[tokio::test]
+async fn matching_extension_is_kept() {
+    let app = TestApp::spawn().await;
+    let fixture = app.project_with_key().await;
+
+    for endpoint in ENDPOINTS {
+        let id = upload(&app, &fixture, endpoint, Some("photo.jpeg"), "image/jpeg").await;
+        let expected = ("photo.jpeg".to_string(), "jpeg".to_string(), "image/jpeg".to_string());
+        assert_eq!(stored(&app, id).await, expected, "{}", endpoint);
+    }
+}
+
+#[tokio::test]
+async fn extension_rules_apply_to_the_resolved_name() {
+    let app = TestApp::spawn().await;
+    let fixture = app.project_with_settings(json!({ "allowed_extensions": ["jpg"] })).await;
+
+    for endpoint in ENDPOINTS {
+        // No filename: checked as `upload.jpg`, so allowed
+        let id = upload(&app, &fixture, endpoint, None, "image/jpeg").await;
+        assert_eq!(stored(&app, id).await.0, "upload.jpg");
+    }
+
+    let data = jpeg();
+    let (status, body) = app
+        .upload("/upload/image", &fixture.key, &[("file", Some("photo.png"), "image/png", &data)])
+        .await;
+    assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE, "{}", body);
+    let (status, body) = app
+        .upload("/upload/images", &fixture.key, &[("file", Some("photo.png"), "image/png", &data)])
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["uploaded"], json!([]));
+    assert_eq!(body["errors"][0]["filename"], "photo.png", "{}", body);
+}
@@ -0,0 +1,154 @@
+//! Access-token verification with RS256 while the key pair is being rotated: the current key
+//! signs, the previous one still verifies. Everything else a forged or stale token can vary
+//! (algorithm, `kid`, signature, `exp`) must end in a 401.
+
+mod common;
+
+use axum::http::StatusCode;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use common::{init_env, Auth, TestApp};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use media_blob_kit::entities::user::Role;
+use serde_json::Value;
+
+const KEYS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/jwt");
+
+fn init() {
+    let path = |name: &str| format!("{}/{}", KEYS, name);
+    let (private, public, previous) = (path("current.key"), path("current.pub"), path("previous.pub"));
+    init_env(&[
+        ("JWT_ALGORITHM", "RS256"),
+        ("JWT_PRIVATE_KEY_PATH", private.as_str()),
+        ("JWT_PUBLIC_KEY_PATH", public.as_str()),
+        ("JWT_PREVIOUS_PUBLIC_KEY_PATH", previous.as_str()),
+    ]);
+}
+
+fn fixture(name: &str) -> Vec<u8> {
+    std::fs::read(format!("{}/{}", KEYS, name)).unwrap()
+}
+
+fn rsa_key(name: &str) -> EncodingKey {
+    EncodingKey::from_rsa_pem(&fixture(name)).unwrap()
+}
+
+fn claims_of(token: &str) -> Value {
+    let payload = token.split('.').nth(1).unwrap();
+    serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload).unwrap()).unwrap()
+}
+
+fn sign(claims: &Value, algorithm: Algorithm, kid: Option<&str>, key: &EncodingKey) -> String {
+    let mut header = Header::new(algorithm);
+    header.kid = kid.map(str::to_string);
+    jsonwebtoken::encode(&header, claims, key).unwrap()
+}
+
+/// A logged-in user's token with its claims, and the current and previous `kid`.
+async fn setup(app: &TestApp) -> (String, Value, String, String) {
+    let token = app.token_for("alice", Role::User).await;
+    let (status, jwks) = app.get("/.well-known/jwks.json", Auth::None).await;
+    assert_eq!(status, StatusCode::OK);
+    let kid = |i: usize| jwks["keys"][i]["kid"].as_str().unwrap().to_string();
+    let claims = claims_of(&token);
+    (token, claims, kid(0), kid(1))
+}
+
+async fn me_status(app: &TestApp, token: &str) -> StatusCode {
+    app.get("/auth/me", Auth::Bearer(token)).await.0
+}
+
+#[tokio::test]
+async fn issued_tokens_carry_the_current_kid() {
+    init();
+    let app = TestApp::spawn().await;
+    let (token, _, current_kid, _) = setup(&app).await;
+
+    assert_eq!(jsonwebtoken::decode_header(&token).unwrap().kid.as_deref(), Some(current_kid.as_str()));
+    assert_eq!(me_status(&app, &token).await, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn previous_key_still_verifies_during_rotation() {
+    init();
+    let app = TestApp::spawn().await;
+    let (_, claims, _, previous_kid) = setup(&app).await;
+
+    let old = sign(&claims, Algorithm::RS256, Some(&previous_kid), &rsa_key("previous.key"));
+    assert_eq!(me_status(&app, &old).await, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn jwks_publishes_both_keys() {
+    init();
+    let app = TestApp::spawn().await;
+    let (_, claims, _, _) = setup(&app).await;
+    let (_, jwks) = app.get("/.well-known/jwks.json", Auth::None).await;
+    let keys = jwks["keys"].as_array().unwrap();
+    assert_eq!(keys.len(), 2);
+
+    // Each published key verifies what its private half signed
+    for (jwk, private) in keys.iter().zip(["current.key", "previous.key"]) {
+        let token = sign(&claims, Algorithm::RS256, None, &rsa_key(private));
+        let key = DecodingKey::from_rsa_components(jwk["n"].as_str().unwrap(), jwk["e"].as_str().unwrap()).unwrap();
+        jsonwebtoken::decode::<Value>(&token, &key, &Validation::new(Algorithm::RS256)).expect(private);
+    }
+}
+
+#[tokio::test]
+async fn unknown_kid_is_rejected() {
+    init();
+    let app = TestApp::spawn().await;
+    let (_, claims, _, _) = setup(&app).await;
+
+    let token = sign(&claims, Algorithm::RS256, Some("retired-long-ago"), &rsa_key("current.key"));
+    assert_eq!(me_status(&app, &token).await, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn signature_must_match_the_named_key() {
+    init();
+    let app = TestApp::spawn().await;
+    let (token, claims, current_kid, previous_kid) = setup(&app).await;
+
+    let swapped = sign(&claims, Algorithm::RS256, Some(&current_kid), &rsa_key("previous.key"));
+    assert_eq!(me_status(&app, &swapped).await, StatusCode::UNAUTHORIZED);
+    let swapped = sign(&claims, Algorithm::RS256, Some(&previous_kid), &rsa_key("current.key"));
+    assert_eq!(me_status(&app, &swapped).await, StatusCode::UNAUTHORIZED);
+
+    // Same signature, claims edited
+    let mut promoted = claims.clone();
+    promoted["role"] = "Su".into();
+    let mut parts: Vec<String> = token.split('.').map(str::to_string).collect();
+    parts[1] = URL_SAFE_NO_PAD.encode(promoted.to_string());
+    assert_eq!(me_status(&app, &parts.join(".")).await, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn other_algorithms_are_rejected() {
+    init();
+    let app = TestApp::spawn().await;
+    let (token, claims, current_kid, _) = setup(&app).await;
+
+    // The public key is no secret; HMAC with it must not pass for RS256
+    let hmac = EncodingKey::from_secret(&fixture("current.pub"));
+    let confused = sign(&claims, Algorithm::HS256, Some(&current_kid), &hmac);
+    assert_eq!(me_status(&app, &confused).await, StatusCode::UNAUTHORIZED);
+
+    let payload = token.split('.').nth(1).unwrap();
+    let unsigned = format!("{}.{}.", URL_SAFE_NO_PAD.encode(r#"{"alg":"none","typ":"JWT"}"#), payload);
+    assert_eq!(me_status(&app, &unsigned).await, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn expired_tokens_are_rejected() {
+    init();
+    let app = TestApp::spawn().await;
+    let (_, mut claims, current_kid, previous_kid) = setup(&app).await;
+    // Past the default 60s leeway
+    claims["exp"] = (chrono::Utc::now().timestamp() - 120).into();
+
+    let expired = sign(&claims, Algorithm::RS256, Some(&current_kid), &rsa_key("current.key"));
+    assert_eq!(me_status(&app, &expired).await, StatusCode::UNAUTHORIZED);
+    let expired = sign(&claims, Algorithm::RS256, Some(&previous_kid), &rsa_key("previous.key"));
+    assert_eq!(me_status(&app, &expired).await, StatusCode::UNAUTHORIZED);
+}
@@ -0,0 +1,53 @@
+//! The in-memory API key cache: repeated requests are served from it, and the key routes drop
+//! the entries of keys they change.
+
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::{Auth, TestApp};
+use media_blob_kit::entities::api_key;
+use sea_orm::sea_query::Expr;
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+use serde_json::json;
+use uuid::Uuid;
+
+#[tokio::test]
+async fn cached_key_is_dropped_when_the_key_is_updated() {
+    let app = TestApp::spawn().await;
+    let fixture = app.project_with_key().await;
+    let (status, body) = app.get("/whoami", Auth::Key(&fixture.key)).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let key_id = body["api_key"]["id"].as_str().unwrap().to_string();
+
+    // Behind the routes' back, the cached copy keeps answering until it expires
+    api_key::Entity::update_many()
+        .col_expr(api_key::Column::IsActive, Expr::value(false))
+        .filter(api_key::Column::Id.eq(key_id.parse::<Uuid>().unwrap()))
+        .exec(&app.db)
+        .await
+        .unwrap();
+    let (status, _) = app.get("/whoami", Auth::Key(&fixture.key)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let uri = format!("/projects/{}/keys/{}", fixture.project_id, key_id);
+    let (status, _) = app.call(Method::PATCH, &uri, Auth::Bearer(&fixture.token), Some(json!({ "is_active": false }))).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, body) = app.get("/whoami", Auth::Key(&fixture.key)).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED, "{}", body);
+    assert_eq!(body["code"], "inactive_key");
+}
+
+#[tokio::test]
+async fn deleted_key_stops_working_at_once() {
+    let app = TestApp::spawn().await;
+    let fixture = app.project_with_key().await;
+    let (status, body) = app.get("/whoami", Auth::Key(&fixture.key)).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    let uri = format!("/projects/{}/keys/{}", fixture.project_id, body["api_key"]["id"].as_str().unwrap());
+    let (status, _) = app.call(Method::DELETE, &uri, Auth::Bearer(&fixture.token), None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, body) = app.get("/whoami", Auth::Key(&fixture.key)).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED, "{}", body);
+    assert_eq!(body["code"], "unknown_key");
+}
@@ -0,0 +1,37 @@
+//! `/auth/login` outcomes are recorded in batches for `GET /admin/login-attempts`.
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{Auth, TestApp, PASSWORD};
+use media_blob_kit::entities::user::Role;
+use serde_json::Value;
+
+#[tokio::test]
+async fn login_outcomes_are_recorded() {
+    let app = TestApp::spawn().await;
+    let su = app.token_for("root", Role::Su).await;
+    let user_id = app.create_user("alice", Role::User).await;
+
+    let (status, _) = app.login("alice", "not-the-password").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = app.login("alice", PASSWORD).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let mut attempts = Value::Null;
+    for _ in 0..50 {
+        let (status, body) = app.get("/admin/login-attempts?username=alice", Auth::Bearer(&su)).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        attempts = body["data"].clone();
+        if attempts.as_array().is_some_and(|a| a.len() == 2) {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    let attempts = attempts.as_array().expect("attempts are listed");
+    assert_eq!(attempts.len(), 2, "{:?}", attempts);
+    let failed = attempts.iter().find(|a| a["success"] == false).unwrap();
+    assert_eq!(failed["failure_reason"], "invalid_password");
+    assert_eq!(failed["user_id"], user_id.to_string());
+    assert!(attempts.iter().any(|a| a["success"] == true && a["failure_reason"].is_null()));
+}
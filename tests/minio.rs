@@ -0,0 +1,129 @@
+//! `S3Service` against a real MinIO with `S3_PROVIDER=minio`: bucket creation, put, get,
+//! list, delete and presigned GETs.
+//!
+//! Ignored by default since they need a running MinIO:
+//!
+//! ```text
+//! docker run -d -p 9000:9000 minio/minio server /data
+//! MINIO_ENDPOINT=http://127.0.0.1:9000 cargo test --test minio -- --ignored
+//! ```
+//!
+//! `MINIO_ACCESS_KEY` and `MINIO_SECRET_KEY` default to MinIO's `minioadmin`, and
+//! `MINIO_BUCKET` to `mbk-minio-test`, which is created when missing.
+
+mod common;
+
+use std::time::Duration;
+
+use axum::http::HeaderMap;
+use common::init_env;
+use http_body_util::{BodyExt, Empty};
+use media_blob_kit::config::{get_config, S3Provider};
+use media_blob_kit::services::s3::S3Service;
+use media_blob_kit::services::storage::Storage;
+use uuid::Uuid;
+
+/// The global S3 service pointed at MinIO, with its bucket in place.
+async fn minio() -> S3Service {
+    let endpoint = std::env::var("MINIO_ENDPOINT").expect("MINIO_ENDPOINT must point at a running MinIO");
+    let var = |name: &str, default: &str| std::env::var(name).unwrap_or_else(|_| default.to_string());
+    let (access_key, secret_key, bucket) = (
+        var("MINIO_ACCESS_KEY", "minioadmin"),
+        var("MINIO_SECRET_KEY", "minioadmin"),
+        var("MINIO_BUCKET", "mbk-minio-test"),
+    );
+    init_env(&[
+        ("S3_PROVIDER", "minio"),
+        ("S3_ENDPOINT", &endpoint),
+        ("AWS_ACCESS_KEY_ID", &access_key),
+        ("AWS_SECRET_ACCESS_KEY", &secret_key),
+        ("S3_BUCKET_NAME", &bucket),
+    ]);
+
+    let s3 = S3Service::new().await;
+    s3.ensure_bucket_exists().await.expect("create or reach the MinIO bucket");
+    s3
+}
+
+/// A key of its own, so runs against the same bucket don't collide.
+fn key(name: &str) -> String {
+    format!("minio-test/{}/{}", Uuid::new_v4(), name)
+}
+
+/// GET as a browser would make it, without the S3 client's credentials.
+async fn fetch(url: &str) -> (u16, HeaderMap, Vec<u8>) {
+    let client = hyper_util::client::legacy::Client::builder(hyper_util::rt::TokioExecutor::new())
+        .build_http::<Empty<axum::body::Bytes>>();
+    let response = client.get(url.parse().unwrap()).await.unwrap();
+    let status = response.status().as_u16();
+    let headers = response.headers().clone();
+    let body = response.into_body().collect().await.unwrap().to_bytes().to_vec();
+    (status, headers, body)
+}
+
+#[tokio::test]
+#[ignore = "needs MinIO: set MINIO_ENDPOINT and run with --ignored"]
+async fn minio_profile_uses_path_style_without_acls() {
+    minio().await;
+    let config = get_config();
+    assert_eq!(config.s3_provider, S3Provider::Minio);
+    assert!(config.s3_force_path_style);
+    assert!(!config.s3_public_objects, "MinIO rejects canned ACLs by default");
+}
+
+#[tokio::test]
+#[ignore = "needs MinIO: set MINIO_ENDPOINT and run with --ignored"]
+async fn objects_round_trip() {
+    let s3 = minio().await;
+    let key = key("notes.txt");
+
+    s3.put_object(&key, b"hello minio".to_vec(), "text/plain").await.unwrap();
+    assert_eq!(s3.get_object(&key).await.unwrap(), b"hello minio");
+
+    let prefix = key.rsplit_once('/').unwrap().0;
+    let page = s3.list_objects(prefix, 10, None).await.unwrap();
+    assert_eq!(page.objects.len(), 1);
+    assert_eq!(page.objects[0].key, key);
+    assert_eq!(page.objects[0].size, 11);
+
+    s3.delete_object(&key).await.unwrap();
+    assert!(s3.get_object(&key).await.is_err());
+    assert!(s3.list_objects(prefix, 10, None).await.unwrap().objects.is_empty());
+}
+
+#[tokio::test]
+#[ignore = "needs MinIO: set MINIO_ENDPOINT and run with --ignored"]
+async fn presigned_urls_serve_the_object_with_overrides() {
+    let s3 = minio().await;
+    let key = key("page.html");
+    s3.put_object_as(&key, b"<p>hi</p>".to_vec(), "text/html", Some("attachment".to_string()))
+        .await
+        .unwrap();
+
+    let url = s3.get_presigned_url(&key, Duration::from_secs(60)).await.unwrap();
+    let (status, headers, body) = fetch(&url).await;
+    assert_eq!(status, 200);
+    assert_eq!(body, b"<p>hi</p>");
+    assert_eq!(headers["content-type"], "text/html");
+    assert_eq!(headers["content-disposition"], "attachment", "stored disposition is served");
+
+    let url = s3
+        .get_presigned_url_as(
+            &key,
+            Duration::from_secs(60),
+            Some("text/plain".to_string()),
+            Some("inline; filename=\"page.txt\"".to_string()),
+        )
+        .await
+        .unwrap();
+    let (status, headers, _) = fetch(&url).await;
+    assert_eq!(status, 200);
+    assert_eq!(headers["content-type"], "text/plain");
+    assert_eq!(headers["content-disposition"], "inline; filename=\"page.txt\"");
+
+    // Tampering with the signed query is refused
+    let (status, _, _) = fetch(&url.replace("X-Amz-Expires=60", "X-Amz-Expires=600")).await;
+    assert_eq!(status, 403);
+
+    s3.delete_object(&key).await.unwrap();
+}
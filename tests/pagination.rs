@@ -0,0 +1,63 @@
+//! Pagination edges over HTTP: zero and oversized `page`/`limit`, and pages past the end.
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{Auth, TestApp};
+use media_blob_kit::entities::user::Role;
+
+#[tokio::test]
+async fn zero_page_or_limit_is_a_bad_request() {
+    let app = TestApp::spawn().await;
+    let token = app.token_for("alice", Role::User).await;
+
+    for uri in ["/projects?page=0", "/projects?limit=0", "/files?page=0", "/files?limit=0"] {
+        let (status, body) = app.get(uri, Auth::Bearer(&token)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}: {}", uri, body);
+        assert!(body["error"].as_str().unwrap().contains("at least 1"), "{}: {}", uri, body);
+    }
+}
+
+#[tokio::test]
+async fn oversized_limit_is_clamped_to_the_maximum() {
+    let app = TestApp::spawn().await;
+    let token = app.token_for("alice", Role::User).await;
+
+    for limit in ["100", "101", "18446744073709551615"] {
+        let (status, body) = app.get(&format!("/projects?limit={}", limit), Auth::Bearer(&token)).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(body["page_size"], 100, "limit={}", limit);
+        assert_eq!(body["max_limit"], 100);
+    }
+}
+
+#[tokio::test]
+async fn pages_split_exactly_and_run_out_empty() {
+    let app = TestApp::spawn().await;
+    let token = app.token_for("alice", Role::User).await;
+    for i in 0..4 {
+        app.create_project(&token, &format!("Project {}", i)).await;
+    }
+
+    let page = |page: u64, limit: u64| {
+        let token = token.clone();
+        let app = &app;
+        async move { app.get(&format!("/projects?page={}&limit={}", page, limit), Auth::Bearer(&token)).await.1 }
+    };
+
+    // Exactly one full page
+    let body = page(1, 4).await;
+    assert_eq!((body["total_items"].as_u64(), body["total_pages"].as_u64()), (Some(4), Some(1)));
+    assert_eq!(body["data"].as_array().unwrap().len(), 4);
+
+    // One short of a second page
+    let body = page(2, 3).await;
+    assert_eq!(body["total_pages"], 2);
+    assert_eq!(body["data"].as_array().unwrap().len(), 1);
+
+    // Past the end: empty, not an error
+    let body = page(3, 3).await;
+    assert_eq!(body["current_page"], 3);
+    assert_eq!(body["total_items"], 4);
+    assert!(body["data"].as_array().unwrap().is_empty());
+}
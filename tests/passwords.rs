@@ -0,0 +1,53 @@
+//! `POST /auth/change-password`: the current password is checked, and a change signs the user out
+//! everywhere but hands back a fresh token pair.
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{Auth, TestApp, PASSWORD};
+use media_blob_kit::entities::user::Role;
+use serde_json::json;
+
+const NEW_PASSWORD: &str = "Staple-Battery-77";
+
+#[tokio::test]
+async fn wrong_current_password_changes_nothing() {
+    let app = TestApp::spawn().await;
+    let token = app.token_for("alice", Role::User).await;
+
+    let body = json!({ "current_password": "not-it", "new_password": NEW_PASSWORD });
+    let (status, _) = app.post("/auth/change-password", Auth::Bearer(&token), body).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, _) = app.get("/auth/me", Auth::Bearer(&token)).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = app.login("alice", PASSWORD).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn change_signs_out_every_session_and_returns_new_tokens() {
+    let app = TestApp::spawn().await;
+    app.create_user("alice", Role::User).await;
+    let (_, first) = app.login("alice", PASSWORD).await;
+    let (_, second) = app.login("alice", PASSWORD).await;
+    let token = first["access_token"].as_str().unwrap();
+
+    let body = json!({ "current_password": PASSWORD, "new_password": NEW_PASSWORD });
+    let (status, changed) = app.post("/auth/change-password", Auth::Bearer(token), body).await;
+    assert_eq!(status, StatusCode::OK, "{}", changed);
+    assert_eq!(changed["revoked_sessions"], 2);
+
+    let (status, _) = app.get("/auth/me", Auth::Bearer(token)).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED, "the token the change was made with stops working");
+    let refresh = json!({ "refresh_token": second["refresh_token"] });
+    let (status, _) = app.post("/auth/refresh", Auth::None, refresh).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED, "other sessions are revoked");
+
+    let (status, _) = app.get("/auth/me", Auth::Bearer(changed["access_token"].as_str().unwrap())).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = app.login("alice", PASSWORD).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = app.login("alice", NEW_PASSWORD).await;
+    assert_eq!(status, StatusCode::OK);
+}
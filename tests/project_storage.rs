@@ -0,0 +1,71 @@
+//! `PUT /projects/{id}/storage` only reaches public endpoints and never echoes S3's answer.
+
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::{init_env, Auth, TestApp};
+use serde_json::json;
+
+fn env() {
+    init_env(&[
+        ("STORAGE_CREDENTIALS_KEY", "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA="),
+        ("STORAGE_PRIVATE_ENDPOINT_HOSTS", "127.0.0.1"),
+        ("S3_OPERATION_TIMEOUT_SECS", "5"),
+    ]);
+}
+
+fn config(endpoint: &str, force_path_style: bool) -> serde_json::Value {
+    json!({
+        "bucket": "acme-media",
+        "region": "us-east-1",
+        "endpoint": endpoint,
+        "access_key_id": "AKIA",
+        "secret_access_key": "secret",
+        "force_path_style": force_path_style,
+    })
+}
+
+#[tokio::test]
+async fn private_endpoints_are_rejected() {
+    env();
+    let app = TestApp::spawn().await;
+    let fixture = app.project_with_key().await;
+    let uri = format!("/projects/{}/storage", fixture.project_id);
+
+    for (endpoint, force_path_style) in [
+        ("http://localhost:9000", true),
+        ("http://169.254.169.254", true),
+        ("http://10.0.0.5:9000", true),
+        ("http://[::1]:9000", true),
+        ("http://[::ffff:192.168.0.1]", true),
+    ] {
+        let (status, body) = app
+            .call(Method::PUT, &uri, Auth::Bearer(&fixture.token), Some(config(endpoint, force_path_style)))
+            .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}: {}", endpoint, body);
+        assert!(body["error"].as_str().unwrap().contains("private or reserved address"), "{}: {}", endpoint, body);
+    }
+
+    let mut bad_region = config("http://127.0.0.1:1", true);
+    bad_region["region"] = json!("us-east-1.evil.example#");
+    let (status, _) = app.call(Method::PUT, &uri, Auth::Bearer(&fixture.token), Some(bad_region)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = app.get(&uri, Auth::Bearer(&fixture.token)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND, "nothing was saved");
+}
+
+#[tokio::test]
+async fn failed_probe_returns_a_fixed_message() {
+    env();
+    let app = TestApp::spawn().await;
+    let fixture = app.project_with_key().await;
+    let uri = format!("/projects/{}/storage", fixture.project_id);
+
+    // Allowed by STORAGE_PRIVATE_ENDPOINT_HOSTS, but nothing listens there
+    let (status, body) = app
+        .call(Method::PUT, &uri, Auth::Bearer(&fixture.token), Some(config("http://127.0.0.1:1", true)))
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+    assert_eq!(body, json!({ "error": "Storage check failed: could not write to the bucket with these settings" }));
+}
@@ -0,0 +1,268 @@
+//! Role checks: which route groups each role reaches, and which files and jobs the shared
+//! `scope` builders let each caller see.
+
+mod common;
+
+use std::collections::BTreeSet;
+
+use axum::http::{Method, StatusCode};
+use common::{Auth, TestApp};
+use media_blob_kit::entities::user::Role;
+use media_blob_kit::services::scope::{self, FileFilters, JobFilters, Scope};
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, QuerySelect};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+const ROLES: [Role; 4] = [Role::Su, Role::Admin, Role::User, Role::Viewer];
+
+/// Expected status for Su, Admin, User and Viewer, in that order.
+struct Case {
+    method: Method,
+    path: &'static str,
+    body: Option<Value>,
+    expected: [StatusCode; 4],
+}
+
+const OK: StatusCode = StatusCode::OK;
+const CREATED: StatusCode = StatusCode::CREATED;
+const FORBIDDEN: StatusCode = StatusCode::FORBIDDEN;
+
+fn route_cases() -> Vec<Case> {
+    let case = |method: Method, path, body, expected| Case { method, path, body, expected };
+    vec![
+        // Protected: any role
+        case(Method::GET, "/auth/me", None, [OK, OK, OK, OK]),
+        case(Method::GET, "/projects", None, [OK, OK, OK, OK]),
+        case(Method::GET, "/files", None, [OK, OK, OK, OK]),
+        // Protected, but the job dashboard turns User away in the handler
+        case(Method::GET, "/admin/jobs", None, [OK, OK, FORBIDDEN, OK]),
+        // Write: User and up
+        case(Method::POST, "/projects", Some(json!({ "name": "Matrix" })), [CREATED, CREATED, CREATED, FORBIDDEN]),
+        // Su only
+        case(Method::GET, "/users", None, [OK, FORBIDDEN, FORBIDDEN, FORBIDDEN]),
+        case(Method::GET, "/admin/audit", None, [OK, FORBIDDEN, FORBIDDEN, FORBIDDEN]),
+        case(Method::GET, "/admin/queue", None, [OK, FORBIDDEN, FORBIDDEN, FORBIDDEN]),
+        case(Method::GET, "/admin/keys/expiring", None, [OK, FORBIDDEN, FORBIDDEN, FORBIDDEN]),
+        case(
+            Method::POST,
+            "/users",
+            Some(json!({ "username": "made-by-matrix", "password": "Another-Horse-77", "role": "user" })),
+            [CREATED, FORBIDDEN, FORBIDDEN, FORBIDDEN],
+        ),
+    ]
+}
+
+#[tokio::test]
+async fn route_groups_by_role() {
+    let app = TestApp::spawn().await;
+    let mut tokens = Vec::new();
+    for role in ROLES {
+        tokens.push(app.token_for(&format!("{:?}", role).to_lowercase(), role).await);
+    }
+
+    let mut mismatches = Vec::new();
+    for case in route_cases() {
+        for ((role, token), expected) in ROLES.iter().zip(&tokens).zip(case.expected) {
+            let (status, body) = app.call(case.method.clone(), case.path, Auth::Bearer(token), case.body.clone()).await;
+            if status != expected {
+                mismatches.push(format!("{:?} {} {} as {:?}: {} ({})", case.method, case.path, expected, role, status, body));
+            }
+        }
+    }
+    assert!(mismatches.is_empty(), "{:#?}", mismatches);
+}
+
+#[tokio::test]
+async fn project_routes_of_another_owner_are_refused() {
+    let app = TestApp::spawn().await;
+    let alice = app.token_for("alice", Role::User).await;
+    let bob = app.token_for("bob", Role::Admin).await;
+    let viewer = app.token_for("viewer", Role::Viewer).await;
+    let project = app.create_project(&alice, "Alice's").await;
+    let keys = format!("/projects/{}/keys", project);
+
+    // Admin is not Su: other owners' projects are out of reach (and their keys hidden)
+    let (status, _) = app.get(&keys, Auth::Bearer(&bob)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = app.get(&format!("/files?project_id={}", project), Auth::Bearer(&bob)).await;
+    assert_eq!(status, FORBIDDEN);
+    // Viewer reads nobody else's project until Su shares it, and touches none
+    let (status, _) = app.get(&format!("/files?project_id={}", project), Auth::Bearer(&viewer)).await;
+    assert_eq!(status, FORBIDDEN);
+    share(&app, project, "viewer").await;
+    let (status, _) = app.get(&format!("/files?project_id={}", project), Auth::Bearer(&viewer)).await;
+    assert_eq!(status, OK);
+    let (status, _) = app.get(&keys, Auth::Bearer(&viewer)).await;
+    assert_eq!(status, FORBIDDEN);
+}
+
+/// Shares `project` with the Viewer `username`, as a fresh Su.
+async fn share(app: &TestApp, project: Uuid, username: &str) {
+    let su = app.token_for(&format!("su-{}", Uuid::new_v4().simple()), Role::Su).await;
+    let user_id = media_blob_kit::entities::user::Entity::find()
+        .filter(media_blob_kit::entities::user::Column::Username.eq(username))
+        .one(&app.db)
+        .await
+        .unwrap()
+        .unwrap()
+        .id;
+    let uri = format!("/admin/projects/{}/members/{}", project, user_id);
+    let (status, body) = app.call(Method::PUT, &uri, Auth::Bearer(&su), None).await;
+    assert_eq!(status, OK, "{}", body);
+}
+
+/// Two owners with one project and one processed file each.
+struct World {
+    alice: Uuid,
+    bob: Uuid,
+    project_a: Uuid,
+    project_b: Uuid,
+    file_a: Uuid,
+    file_b: Uuid,
+}
+
+async fn world(app: &TestApp) -> World {
+    let mut ids = Vec::new();
+    for (owner, role) in [("alice", Role::User), ("bob", Role::Admin)] {
+        let user_id = app.create_user(owner, role).await;
+        let token = app.login(owner, common::PASSWORD).await.1["access_token"].as_str().unwrap().to_string();
+        let project = app.create_project(&token, owner).await;
+        let key = app.create_api_key(&token, project).await;
+        let (status, body) = app
+            .upload("/upload/image", &key, &[("file", Some("a.png"), "image/png", &common::png(4, 4))])
+            .await;
+        assert_eq!(status, StatusCode::CREATED, "{}", body);
+        ids.push((user_id, project, body["id"].as_str().unwrap().parse().unwrap()));
+    }
+    let [(alice, project_a, file_a), (bob, project_b, file_b)] = ids[..] else { unreachable!() };
+    World { alice, bob, project_a, project_b, file_a, file_b }
+}
+
+#[tokio::test]
+async fn scope_builders_matrix() {
+    let app = TestApp::spawn().await;
+    let w = world(&app).await;
+    let (a, b) = (Some(w.project_a), Some(w.project_b));
+    let viewer = app.create_user("viewer", Role::Viewer).await;
+    share(&app, w.project_a, "viewer").await;
+
+    // (scope, explicit project_id, files expected); jobs follow their files
+    let cases: Vec<(Scope, Option<Uuid>, Vec<Uuid>)> = vec![
+        (Scope::All, None, vec![w.file_a, w.file_b]),
+        (Scope::All, a, vec![w.file_a]),
+        (Scope::All, b, vec![w.file_b]),
+        (Scope::Owner(w.alice), None, vec![w.file_a]),
+        (Scope::Owner(w.alice), a, vec![w.file_a]),
+        (Scope::Owner(w.alice), b, vec![]),
+        (Scope::Owner(w.bob), None, vec![w.file_b]),
+        (Scope::Owner(w.bob), a, vec![]),
+        (Scope::Owner(w.bob), b, vec![w.file_b]),
+        (Scope::Member(viewer), None, vec![w.file_a]),
+        (Scope::Member(viewer), b, vec![]),
+        (Scope::Member(w.bob), None, vec![w.file_b]),
+        (Scope::Project(w.project_a), None, vec![w.file_a]),
+        (Scope::Project(w.project_a), b, vec![]),
+    ];
+
+    for (scope, project_id, expected) in cases {
+        let expected: BTreeSet<Uuid> = expected.into_iter().collect();
+        let files: BTreeSet<Uuid> = scope::files(scope, &FileFilters { project_id, ..Default::default() })
+            .all(&app.db)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|f| f.id)
+            .collect();
+        assert_eq!(files, expected, "files of {:?} with project_id {:?}", scope, project_id);
+
+        let jobs: BTreeSet<Uuid> = scope::jobs(scope, &JobFilters { project_id, ..Default::default() })
+            .select_only()
+            .column(media_blob_kit::entities::job::Column::FileId)
+            .into_tuple::<Uuid>()
+            .all(&app.db)
+            .await
+            .unwrap()
+            .into_iter()
+            .collect();
+        assert_eq!(jobs, expected, "jobs of {:?} with project_id {:?}", scope, project_id);
+    }
+}
+
+#[tokio::test]
+async fn file_listing_by_role() {
+    let app = TestApp::spawn().await;
+    let w = world(&app).await;
+    let su = app.token_for("su", Role::Su).await;
+    let viewer = app.token_for("viewer", Role::Viewer).await;
+    share(&app, w.project_a, "viewer").await;
+    let alice = app.login("alice", common::PASSWORD).await.1["access_token"].as_str().unwrap().to_string();
+    let bob = app.login("bob", common::PASSWORD).await.1["access_token"].as_str().unwrap().to_string();
+
+    let listed = |body: &Value| -> BTreeSet<String> {
+        body["data"].as_array().unwrap().iter().map(|f| f["id"].as_str().unwrap().to_string()).collect()
+    };
+    let both: BTreeSet<String> = [w.file_a, w.file_b].iter().map(Uuid::to_string).collect();
+    for (token, expected) in [
+        (&su, both.clone()),
+        (&viewer, BTreeSet::from([w.file_a.to_string()])),
+        (&alice, BTreeSet::from([w.file_a.to_string()])),
+        (&bob, BTreeSet::from([w.file_b.to_string()])),
+    ] {
+        let (status, body) = app.get("/files", Auth::Bearer(token)).await;
+        assert_eq!(status, OK);
+        assert_eq!(listed(&body), expected);
+    }
+
+    let (status, _) = app.get(&format!("/files/{}", w.file_b), Auth::Bearer(&alice)).await;
+    assert_eq!(status, FORBIDDEN);
+    let (status, _) = app.get(&format!("/admin/jobs?project_id={}", w.project_a), Auth::Bearer(&bob)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // The Viewer reads the shared project only
+    let (_, body) = app.get("/projects", Auth::Bearer(&viewer)).await;
+    let projects: Vec<&str> = body["data"].as_array().unwrap().iter().map(|p| p["id"].as_str().unwrap()).collect();
+    assert_eq!(projects, [w.project_a.to_string()]);
+    let (status, _) = app.get(&format!("/projects/{}", w.project_b), Auth::Bearer(&viewer)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = app.get(&format!("/files/{}", w.file_b), Auth::Bearer(&viewer)).await;
+    assert_eq!(status, FORBIDDEN);
+    let (status, _) = app.get(&format!("/admin/jobs?project_id={}", w.project_b), Auth::Bearer(&viewer)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, body) = app.get(&format!("/admin/jobs?project_id={}", w.project_a), Auth::Bearer(&viewer)).await;
+    assert_eq!(status, OK, "{}", body);
+}
+
+#[tokio::test]
+async fn project_members_are_viewers_managed_by_su() {
+    let app = TestApp::spawn().await;
+    let w = world(&app).await;
+    let su = app.token_for("su", Role::Su).await;
+    let viewer = app.create_user("viewer", Role::Viewer).await;
+    let viewer_token = app.login("viewer", common::PASSWORD).await.1["access_token"].as_str().unwrap().to_string();
+    let member = format!("/admin/projects/{}/members/{}", w.project_a, viewer);
+
+    // Only Viewers can be members
+    let (status, _) = app.call(Method::PUT, &format!("/admin/projects/{}/members/{}", w.project_a, w.bob), Auth::Bearer(&su), None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, first) = app.call(Method::PUT, &member, Auth::Bearer(&su), None).await;
+    assert_eq!(status, OK, "{}", first);
+    let (_, again) = app.call(Method::PUT, &member, Auth::Bearer(&su), None).await;
+    assert_eq!(again["created_at"], first["created_at"]);
+    let (_, body) = app.get(&format!("/admin/projects/{}/members", w.project_a), Auth::Bearer(&su)).await;
+    assert_eq!(body, json!([first]));
+
+    let (status, _) = app.get(&format!("/projects/{}", w.project_a), Auth::Bearer(&viewer_token)).await;
+    assert_eq!(status, OK);
+
+    let (status, _) = app.call(Method::DELETE, &member, Auth::Bearer(&su), None).await;
+    assert_eq!(status, OK);
+    let (status, _) = app.call(Method::DELETE, &member, Auth::Bearer(&su), None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = app.get(&format!("/projects/{}", w.project_a), Auth::Bearer(&viewer_token)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // Su only
+    let (status, _) = app.call(Method::PUT, &member, Auth::Bearer(&viewer_token), None).await;
+    assert_eq!(status, FORBIDDEN);
+}
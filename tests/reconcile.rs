@@ -0,0 +1,44 @@
+//! `POST /admin/projects/{id}/reconcile` and the `reconcile_storage` job it queues.
+
+mod common;
+
+use std::sync::Arc;
+
+use axum::http::{Method, StatusCode};
+use common::{storage, Auth, FakeProcessor, TestApp};
+use media_blob_kit::entities::{job, user::Role};
+use media_blob_kit::services::storage::Storage;
+use media_blob_kit::services::worker::Worker;
+use sea_orm::EntityTrait;
+use uuid::Uuid;
+
+#[tokio::test]
+async fn projects_without_files_are_reconciled_too() {
+    let app = TestApp::spawn().await;
+    let su = app.token_for("reconcile-su", Role::Su).await;
+    let fixture = app.project_with_key().await;
+    let stray = format!("{}stray.bin", fixture.prefix);
+    storage().put_object(&stray, vec![0; 10], "application/octet-stream").await.unwrap();
+
+    let uri = format!("/admin/projects/{}/reconcile", fixture.project_id);
+    let (status, body) = app.call(Method::POST, &uri, Auth::Bearer(&su), None).await;
+    assert_eq!(status, StatusCode::ACCEPTED, "{}", body);
+    let job_id: Uuid = body["job_id"].as_str().unwrap().parse().unwrap();
+
+    let worker = Worker::with_processor(app.db.clone(), Arc::new(FakeProcessor)).await;
+    let handle = tokio::spawn(async move { worker.run().await });
+    let mut job = job::Entity::find_by_id(job_id).one(&app.db).await.unwrap().unwrap();
+    for _ in 0..200 {
+        if job.status != "pending" && job.status != "processing" {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        job = job::Entity::find_by_id(job_id).one(&app.db).await.unwrap().unwrap();
+    }
+    handle.abort();
+
+    assert_eq!((job.file_id, job.project_id), (None, Some(fixture.project_id)));
+    assert_eq!(job.status, "completed", "{}", job.payload);
+    assert_eq!(job.payload["result"]["objects"], 1);
+    assert_eq!(job.payload["result"]["drift_bytes"], 10);
+}
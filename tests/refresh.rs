@@ -0,0 +1,102 @@
+//! Refresh-token rotation on `POST /auth/refresh`, in the body and in the cookie.
+
+mod common;
+
+use axum::body::Body;
+use axum::http::{header, Method, Request, StatusCode};
+use common::{read_json, Auth, TestApp, PASSWORD};
+use media_blob_kit::entities::user::Role;
+use serde_json::{json, Value};
+
+async fn refresh(app: &TestApp, refresh_token: &str) -> (StatusCode, Value) {
+    app.post("/auth/refresh", Auth::None, json!({ "refresh_token": refresh_token })).await
+}
+
+/// The `refresh_token` value set by a response, if any.
+fn set_cookie_token(response: &axum::response::Response) -> Option<String> {
+    let cookie = response.headers().get(header::SET_COOKIE)?.to_str().ok()?;
+    let value = cookie.split(';').next()?.strip_prefix("refresh_token=")?;
+    Some(value.to_string())
+}
+
+#[tokio::test]
+async fn rotated_token_can_be_used_again() {
+    let app = TestApp::spawn().await;
+    app.create_user("alice", Role::User).await;
+    let (_, login) = app.login("alice", PASSWORD).await;
+    let first = login["refresh_token"].as_str().unwrap();
+
+    let (status, body) = refresh(&app, first).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let second = body["refresh_token"].as_str().unwrap().to_string();
+    assert_ne!(second, first);
+    let (status, _) = app.get("/auth/me", Auth::Bearer(body["access_token"].as_str().unwrap())).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = refresh(&app, &second).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_ne!(body["refresh_token"], second.as_str());
+}
+
+#[tokio::test]
+async fn old_token_is_rejected_after_rotation() {
+    let app = TestApp::spawn().await;
+    app.create_user("alice", Role::User).await;
+    let (_, login) = app.login("alice", PASSWORD).await;
+    let old = login["refresh_token"].as_str().unwrap();
+    let (_, body) = refresh(&app, old).await;
+    let rotated = body["refresh_token"].as_str().unwrap().to_string();
+
+    let (status, body) = refresh(&app, old).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert!(body.get("access_token").is_none());
+    // Replaying the old token gives the whole chain away
+    let (status, _) = refresh(&app, &rotated).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn concurrent_refreshes_get_one_replacement() {
+    let app = TestApp::spawn().await;
+    app.create_user("alice", Role::User).await;
+    let (_, login) = app.login("alice", PASSWORD).await;
+    let token = login["refresh_token"].as_str().unwrap();
+
+    let (a, b) = tokio::join!(refresh(&app, token), refresh(&app, token));
+    let ok = [a.0, b.0].iter().filter(|s| **s == StatusCode::OK).count();
+    assert_eq!(ok, 1, "{:?} {:?}", a, b);
+}
+
+#[tokio::test]
+async fn cookie_mode_rotates_the_cookie() {
+    let app = TestApp::spawn().await;
+    app.create_user("alice", Role::User).await;
+    let login = Request::builder()
+        .method(Method::POST)
+        .uri("/auth/login?cookie=true")
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(json!({ "username": "alice", "password": PASSWORD }).to_string()))
+        .unwrap();
+    let response = app.send(login).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let old = set_cookie_token(&response).expect("login sets the cookie");
+
+    let with_cookie = |token: &str| {
+        Request::builder()
+            .method(Method::POST)
+            .uri("/auth/refresh")
+            .header(header::COOKIE, format!("theme=dark; refresh_token={}", token))
+            .body(Body::empty())
+            .unwrap()
+    };
+    let response = app.send(with_cookie(&old)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let rotated = set_cookie_token(&response).expect("refresh replaces the cookie");
+    assert_ne!(rotated, old);
+    let (_, body) = read_json(response).await;
+    assert!(body["access_token"].is_string());
+    assert!(body.get("refresh_token").is_none(), "{}", body);
+
+    let response = app.send(with_cookie(&old)).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
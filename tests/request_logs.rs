@@ -0,0 +1,39 @@
+//! API-key requests of projects with `request_logs` on are recorded in batches.
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{Auth, TestApp};
+use serde_json::{json, Value};
+
+#[tokio::test]
+async fn key_requests_are_recorded_when_enabled() {
+    let app = TestApp::spawn().await;
+    let logged = app.project_with_settings(json!({ "request_logs": true })).await;
+    let quiet = app.project_with_key().await;
+
+    for fixture in [&logged, &quiet] {
+        let (status, body) = app
+            .upload("/upload/file", &fixture.key, &[("file", Some("a.txt"), "text/plain", b"a")])
+            .await;
+        assert_eq!(status, StatusCode::CREATED, "{}", body);
+    }
+
+    let uri = format!("/projects/{}/request-logs", logged.project_id);
+    let mut entries = Value::Null;
+    for _ in 0..50 {
+        let (status, body) = app.get(&uri, Auth::Bearer(&logged.token)).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        entries = body["data"].clone();
+        if entries.as_array().is_some_and(|e| !e.is_empty()) {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    assert_eq!(entries[0]["method"], "POST", "{}", entries);
+    assert_eq!(entries[0]["path"], "/upload/file");
+    assert_eq!(entries[0]["status"], 201);
+
+    let (_, body) = app.get(&format!("/projects/{}/request-logs", quiet.project_id), Auth::Bearer(&quiet.token)).await;
+    assert!(body["data"].as_array().unwrap().is_empty(), "{}", body);
+}
@@ -0,0 +1,60 @@
+//! `GET /auth/sessions` and `DELETE /auth/sessions/{id}`: users see and revoke their own refresh
+//! tokens, and nobody else's.
+
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::{Auth, TestApp, PASSWORD};
+use media_blob_kit::entities::user::Role;
+use serde_json::json;
+use uuid::Uuid;
+
+#[tokio::test]
+async fn sessions_are_listed_and_revoked_one_at_a_time() {
+    let app = TestApp::spawn().await;
+    app.create_user("alice", Role::User).await;
+    let (_, first) = app.login("alice", PASSWORD).await;
+    let (_, second) = app.login("alice", PASSWORD).await;
+    let auth = Auth::Bearer(second["access_token"].as_str().unwrap());
+
+    let (status, body) = app.get("/auth/sessions", auth).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let sessions = body["data"].as_array().unwrap().clone();
+    assert_eq!(sessions.len(), 2);
+    assert!(sessions.iter().all(|s| s["revoked"] == false), "{}", body);
+
+    // Newest first, so the first login's session is the last one
+    let oldest = sessions[1]["id"].as_str().unwrap();
+    let (status, body) = app.call(Method::DELETE, &format!("/auth/sessions/{}", oldest), auth, None).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    let (status, _) = app.post("/auth/refresh", Auth::None, json!({ "refresh_token": first["refresh_token"] })).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = app.post("/auth/refresh", Auth::None, json!({ "refresh_token": second["refresh_token"] })).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, body) = app.get("/auth/sessions?include_revoked=true", auth).await;
+    let revoked = body["data"].as_array().unwrap().iter().filter(|s| s["revoked"] == true).count();
+    assert_eq!(revoked, 2, "the revoked session and the one the refresh replaced: {}", body);
+}
+
+#[tokio::test]
+async fn sessions_of_other_users_are_not_found() {
+    let app = TestApp::spawn().await;
+    app.create_user("alice", Role::User).await;
+    let (_, alice) = app.login("alice", PASSWORD).await;
+    let mallory = app.token_for("mallory", Role::User).await;
+
+    let (_, body) = app.get("/auth/sessions", Auth::Bearer(alice["access_token"].as_str().unwrap())).await;
+    let session = body["data"][0]["id"].as_str().unwrap().to_string();
+
+    let (_, body) = app.get("/auth/sessions", Auth::Bearer(&mallory)).await;
+    assert!(body["data"].as_array().unwrap().iter().all(|s| s["id"] != session.as_str()), "{}", body);
+    for id in [session, Uuid::new_v4().to_string()] {
+        let (status, _) = app.call(Method::DELETE, &format!("/auth/sessions/{}", id), Auth::Bearer(&mallory), None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    let (status, _) = app.post("/auth/refresh", Auth::None, json!({ "refresh_token": alice["refresh_token"] })).await;
+    assert_eq!(status, StatusCode::OK);
+}
@@ -0,0 +1,80 @@
+//! `POST /projects/{id}/settings/rollback/{history_id}` holds restored settings to today's rules.
+
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::{Auth, Fixture, TestApp};
+use media_blob_kit::entities::{project, project_settings_history};
+use sea_orm::{ActiveModelTrait, ActiveValue::Set, ColumnTrait, EntityTrait, IntoActiveModel, QueryFilter};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+/// Changes the settings once and rewrites the recorded `old_settings` to `old`, as if it had
+/// been saved under earlier rules. Returns the history entry.
+async fn history_with(app: &TestApp, fixture: &Fixture, old: Value) -> Uuid {
+    let (status, body) = app
+        .call(
+            Method::PUT,
+            &format!("/projects/{}", fixture.project_id),
+            Auth::Bearer(&fixture.token),
+            Some(json!({ "settings": { "variants": { "hd": { "width": 1280 } } } })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    let entry = project_settings_history::Entity::find()
+        .filter(project_settings_history::Column::ProjectId.eq(fixture.project_id))
+        .one(&app.db)
+        .await
+        .unwrap()
+        .unwrap();
+    let mut active = entry.into_active_model();
+    active.old_settings = Set(old);
+    active.update(&app.db).await.unwrap().id
+}
+
+async fn rollback(app: &TestApp, fixture: &Fixture, history_id: Uuid) -> (StatusCode, Value) {
+    let uri = format!("/projects/{}/settings/rollback/{}", fixture.project_id, history_id);
+    app.call(Method::POST, &uri, Auth::Bearer(&fixture.token), None).await
+}
+
+async fn settings(app: &TestApp, fixture: &Fixture) -> Value {
+    project::Entity::find_by_id(fixture.project_id).one(&app.db).await.unwrap().unwrap().settings
+}
+
+#[tokio::test]
+async fn invalid_old_settings_are_refused() {
+    let app = TestApp::spawn().await;
+    let fixture = app.project_with_key().await;
+
+    for (old, expected) in [
+        (json!({ "variants": "thumb" }), StatusCode::BAD_REQUEST),
+        (json!({ "variants": { "Thumb": { "width": 8 }, "thumb": { "width": 16 } } }), StatusCode::UNPROCESSABLE_ENTITY),
+    ] {
+        let history_id = history_with(&app, &fixture, old.clone()).await;
+        let (status, body) = rollback(&app, &fixture, history_id).await;
+        assert_eq!(status, expected, "{}: {}", old, body);
+        assert!(body["error"].is_string(), "{}", body);
+        assert_eq!(settings(&app, &fixture).await["variants"], json!({ "hd": { "width": 1280 } }), "{}", old);
+    }
+}
+
+#[tokio::test]
+async fn restored_settings_are_normalized() {
+    let app = TestApp::spawn().await;
+    let fixture = app.project_with_key().await;
+
+    let history_id = history_with(&app, &fixture, json!({ "blocked_extensions": [".EXE", "exe", "Bat"] })).await;
+    let (status, body) = rollback(&app, &fixture, history_id).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(settings(&app, &fixture).await["blocked_extensions"], json!(["exe", "bat"]));
+
+    // The rollback's own history entry records what was saved
+    let entry = project_settings_history::Entity::find()
+        .filter(project_settings_history::Column::RollbackOf.eq(history_id))
+        .one(&app.db)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(entry.new_settings["blocked_extensions"], json!(["exe", "bat"]));
+}
@@ -0,0 +1,221 @@
+//! What a trashed project's files look like to every consumer of `file::Entity::find_active`,
+//! and to the storage paths that deliberately keep seeing them until the purge. Also files
+//! trashed on their own under `retention.file_trash_days`.
+
+mod common;
+
+use std::sync::Arc;
+
+use axum::http::{Method, StatusCode};
+use common::{png, storage, Auth, FakeProcessor, Fixture, TestApp};
+use media_blob_kit::services::cleanup::CleanupService;
+use media_blob_kit::entities::user::Role;
+use media_blob_kit::entities::{file, job, project};
+use media_blob_kit::models::job::JobPayload;
+use sea_orm::{ActiveModelTrait, ActiveValue::Set, ColumnTrait, EntityTrait, IntoActiveModel, QueryFilter};
+use serde_json::json;
+use uuid::Uuid;
+
+/// A project with one processed image, then moved to the trash.
+async fn trashed(app: &TestApp) -> (Fixture, Uuid) {
+    let fixture = app.project_with_settings(json!({ "variants": { "thumb": { "width": 8, "height": 8 } } })).await;
+    let (status, body) = app
+        .upload("/upload/image", &fixture.key, &[("file", Some("a.png"), "image/png", &png(16, 16))])
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    let id: Uuid = body["id"].as_str().unwrap().parse().unwrap();
+    let jobs = app.run_jobs(id, Arc::new(FakeProcessor)).await;
+    assert_eq!(jobs[0].status, "completed", "{}", jobs[0].payload);
+
+    let (status, body) = app
+        .call(Method::DELETE, &format!("/projects/{}", fixture.project_id), Auth::Bearer(&fixture.token), None)
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    (fixture, id)
+}
+
+#[tokio::test]
+async fn trashed_files_are_not_listed_or_served() {
+    let app = TestApp::spawn().await;
+    let (fixture, id) = trashed(&app).await;
+    let su = app.token_for("su", Role::Su).await;
+
+    for token in [&fixture.token, &su] {
+        let (status, body) = app.get("/files", Auth::Bearer(token)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body["data"].as_array().unwrap().is_empty(), "{}", body);
+
+        for (method, uri) in [
+            (Method::GET, format!("/files/{}", id)),
+            (Method::GET, format!("/files/{}/content", id)),
+            (Method::GET, format!("/files/{}/content?variant=thumb", id)),
+            (Method::GET, format!("/files/{}/verify", id)),
+            (Method::DELETE, format!("/files/{}", id)),
+        ] {
+            let (status, body) = app.call(method.clone(), &uri, Auth::Bearer(token), None).await;
+            assert_eq!(status, StatusCode::NOT_FOUND, "{:?} {}: {}", method, uri, body);
+        }
+    }
+
+    // Display stats leave them out
+    let (_, body) = app.get("/auth/me?include=stats", Auth::Bearer(&fixture.token)).await;
+    assert_eq!(body["stats"], json!({ "projects": 0, "files": 0, "bytes": 0 }));
+}
+
+#[tokio::test]
+async fn trashed_files_are_not_reprocessed() {
+    let app = TestApp::spawn().await;
+    let (fixture, id) = trashed(&app).await;
+
+    // Queued before the trash, run after it
+    let queued = job::ActiveModel {
+        id: Set(Uuid::new_v4()),
+        file_id: Set(Some(id)),
+        project_id: Set(Some(fixture.project_id)),
+        status: Set("pending".to_string()),
+        payload: Set(JobPayload::SyncFileVariants { variants_config: None }.to_value()),
+        created_at: Set(chrono::Utc::now()),
+        updated_at: Set(chrono::Utc::now()),
+    }
+    .insert(&app.db)
+    .await
+    .unwrap();
+    let before = storage().keys(&fixture.prefix);
+
+    let jobs = app.run_jobs(id, Arc::new(FakeProcessor)).await;
+    let sync = jobs.iter().find(|j| j.id == queued.id).unwrap();
+    assert_eq!(sync.status, "failed");
+    assert_eq!(sync.payload["error"], "File not found or its project is trashed");
+    assert_eq!(storage().keys(&fixture.prefix), before);
+}
+
+#[tokio::test]
+async fn trashed_files_keep_their_storage_until_purged() {
+    let app = TestApp::spawn().await;
+    let (fixture, id) = trashed(&app).await;
+    let su = app.token_for("su", Role::Su).await;
+
+    assert!(file::Entity::find_active().filter(file::Column::Id.eq(id)).one(&app.db).await.unwrap().is_none());
+    let row = app.file(id).await.expect("the row stays until the purge");
+    let stored = storage().keys(&fixture.prefix);
+    assert_eq!(stored.len(), 2, "original and thumb: {:?}", stored);
+    assert!(stored.contains(&row.s3_key));
+
+    // Storage accounting still sees every object as tracked
+    let uri = format!("/admin/projects/{}/objects?limit=100&diff=true", fixture.project_id);
+    let (status, body) = app.get(&uri, Auth::Bearer(&su)).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let objects = body["objects"].as_array().unwrap();
+    assert_eq!(objects.len(), 2, "{}", body);
+    assert!(objects.iter().all(|o| o["tracked"] == true), "{}", body);
+}
+
+#[tokio::test]
+async fn restored_project_brings_its_files_back() {
+    let app = TestApp::spawn().await;
+    let (fixture, id) = trashed(&app).await;
+
+    let mut restored = project::Entity::find_by_id(fixture.project_id).one(&app.db).await.unwrap().unwrap().into_active_model();
+    restored.deleted_at = Set(None);
+    restored.update(&app.db).await.unwrap();
+
+    let (status, body) = app.get(&format!("/files/{}", id), Auth::Bearer(&fixture.token)).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert!(body["variants"]["thumb"].is_string(), "{}", body);
+    let (_, body) = app.get("/files", Auth::Bearer(&fixture.token)).await;
+    assert_eq!(body["data"][0]["id"], id.to_string());
+    let (status, _) = app.get(&format!("/files/{}/content?variant=thumb", id), Auth::Bearer(&fixture.token)).await;
+    assert_eq!(status, StatusCode::TEMPORARY_REDIRECT);
+}
+
+#[tokio::test]
+async fn keys_of_a_trashed_project_are_refused() {
+    let app = TestApp::spawn().await;
+    let (fixture, _) = trashed(&app).await;
+    let before = storage().keys(&fixture.prefix);
+
+    let (status, body) = app
+        .upload("/upload/file", &fixture.key, &[("file", Some("late.txt"), "text/plain", b"late")])
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN, "{}", body);
+    assert_eq!(body["error"], "Project is deleted");
+    assert_eq!(storage().keys(&fixture.prefix), before);
+}
+
+/// A project keeping deleted files for a week, with one uploaded file.
+async fn with_file_trash(app: &TestApp) -> (Fixture, Uuid) {
+    let fixture = app.project_with_settings(json!({ "retention": { "file_trash_days": 7 } })).await;
+    let (status, body) = app
+        .upload("/upload/file", &fixture.key, &[("file", Some("notes.txt"), "text/plain", b"hello")])
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    (fixture, body["id"].as_str().unwrap().parse().unwrap())
+}
+
+#[tokio::test]
+async fn deleted_files_wait_in_the_file_trash_until_purged() {
+    let app = TestApp::spawn().await;
+    let (fixture, id) = with_file_trash(&app).await;
+    let auth = Auth::Bearer(&fixture.token);
+    let uri = format!("/files/{}", id);
+
+    let (status, body) = app.call(Method::DELETE, &uri, auth, None).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["trashed"], true);
+    assert_eq!(body["purge_after_days"], 7);
+
+    let (status, _) = app.get(&uri, auth).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (_, body) = app.get("/files", auth).await;
+    assert!(body["data"].as_array().unwrap().is_empty(), "{}", body);
+    assert_eq!(storage().keys(&fixture.prefix).len(), 1, "the object stays until the purge");
+
+    // Inside the window nothing is purged
+    let cleanup = CleanupService::new(app.db.clone());
+    cleanup.purge_trashed_files().await.unwrap();
+    assert!(app.file(id).await.is_some());
+
+    let mut row = app.file(id).await.unwrap().into_active_model();
+    row.deleted_at = Set(Some(chrono::Utc::now() - chrono::Duration::days(8)));
+    row.update(&app.db).await.unwrap();
+    cleanup.purge_trashed_files().await.unwrap();
+
+    assert!(app.file(id).await.is_none());
+    assert!(storage().keys(&fixture.prefix).is_empty());
+    let (status, body) = app.get(&uri, auth).await;
+    assert_eq!(status, StatusCode::GONE, "{}", body);
+    assert_eq!(body["reason"], "file_deleted");
+}
+
+#[tokio::test]
+async fn permanent_deletes_skip_the_file_trash() {
+    let app = TestApp::spawn().await;
+    let (fixture, id) = with_file_trash(&app).await;
+
+    let uri = format!("/files/{}?permanent=true", id);
+    let (status, body) = app.call(Method::DELETE, &uri, Auth::Bearer(&fixture.token), None).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert!(body.get("trashed").is_none(), "{}", body);
+    assert!(app.file(id).await.is_none());
+    assert!(storage().keys(&fixture.prefix).is_empty());
+}
+
+#[tokio::test]
+async fn file_trash_days_is_validated_like_the_other_windows() {
+    let app = TestApp::spawn().await;
+    let fixture = app.project_with_key().await;
+    let uri = format!("/projects/{}", fixture.project_id);
+    let auth = Auth::Bearer(&fixture.token);
+
+    for days in [0, 366] {
+        let settings = json!({ "settings": { "retention": { "file_trash_days": days } } });
+        let (status, body) = app.call(Method::PUT, &uri, auth, Some(settings)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}: {}", days, body);
+    }
+
+    let settings = json!({ "settings": { "retention": { "file_trash_days": 90 } } });
+    let (status, body) = app.call(Method::PUT, &uri, auth, Some(settings)).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let (_, body) = app.get("/whoami", Auth::Key(&fixture.key)).await;
+    assert_eq!(body["retention"]["file_trash_days"], 90, "{}", body);
+}
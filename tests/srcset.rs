@@ -0,0 +1,112 @@
+//! The `srcset` and `sizes` convenience fields on image files: entry order and format, and
+//! when they are left out.
+
+mod common;
+
+use std::sync::Arc;
+
+use axum::http::StatusCode;
+use common::{png, Auth, FakeProcessor, Fixture, TestApp, BUCKET};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+const SIZES: &str = "(max-width: 600px) 100vw, 600px";
+
+/// A project whose variants are declared out of width order, with two variants of the same width.
+async fn project(app: &TestApp) -> Fixture {
+    app.project_with_settings(json!({
+        "sizes": SIZES,
+        "variants": {
+            "large": { "width": 1280, "height": 720 },
+            "thumb": { "width": 320, "height": 180 },
+            "medium": { "width": 640, "height": 360 },
+            "tile": { "width": 320, "height": 320 }
+        }
+    }))
+    .await
+}
+
+async fn processed_image(app: &TestApp, fixture: &Fixture) -> Uuid {
+    let (status, body) = app
+        .upload("/upload/image", &fixture.key, &[("file", Some("a.png"), "image/png", &png(32, 32))])
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    // Nothing to describe until the variants exist
+    assert!(body.get("srcset").is_none(), "{}", body);
+    let id = body["id"].as_str().unwrap().parse().unwrap();
+    let jobs = app.run_jobs(id, Arc::new(FakeProcessor)).await;
+    assert_eq!(jobs[0].status, "completed", "{}", jobs[0].payload);
+    id
+}
+
+fn url(file: &Value, variant: &str) -> String {
+    format!("memory://{}/{}", BUCKET, file["variants"][variant].as_str().unwrap())
+}
+
+#[tokio::test]
+async fn srcset_lists_variants_narrowest_first_once_per_width() {
+    let app = TestApp::spawn().await;
+    let fixture = project(&app).await;
+    let id = processed_image(&app, &fixture).await;
+
+    let (status, file) = app.get(&format!("/files/{}", id), Auth::Bearer(&fixture.token)).await;
+    assert_eq!(status, StatusCode::OK, "{}", file);
+    // `thumb` and `tile` share 320w; the first by name is kept
+    let expected = format!(
+        "{} 320w, {} 640w, {} 1280w",
+        url(&file, "thumb"),
+        url(&file, "medium"),
+        url(&file, "large")
+    );
+    assert_eq!(file["srcset"], expected);
+    assert_eq!(file["sizes"], SIZES);
+    assert_eq!(file["variant_dimensions"]["tile"], json!({ "width": 320, "height": 320 }));
+}
+
+#[tokio::test]
+async fn no_srcset_leaves_both_fields_out_everywhere() {
+    let app = TestApp::spawn().await;
+    let fixture = project(&app).await;
+    let id = processed_image(&app, &fixture).await;
+    let auth = Auth::Bearer(&fixture.token);
+
+    let (_, file) = app.get(&format!("/files/{}?no_srcset=true", id), auth).await;
+    let (_, list) = app.get("/files?no_srcset=true", auth).await;
+    let (_, folder) = app.get(&format!("/files/folders?project_id={}&no_srcset=true", fixture.project_id), auth).await;
+    for file in [&file, &list["data"][0], &folder["files"]["data"][0]] {
+        assert_eq!(file["id"], id.to_string());
+        assert!(file.get("srcset").is_none() && file.get("sizes").is_none(), "{}", file);
+    }
+
+    // And both are there by default
+    let (_, list) = app.get("/files", auth).await;
+    let (_, folder) = app.get(&format!("/files/folders?project_id={}", fixture.project_id), auth).await;
+    for file in [&list["data"][0], &folder["files"]["data"][0]] {
+        assert!(file["srcset"].as_str().unwrap().ends_with(" 1280w"), "{}", file);
+        assert_eq!(file["sizes"], SIZES);
+    }
+}
+
+#[tokio::test]
+async fn files_without_variant_widths_have_no_srcset() {
+    let app = TestApp::spawn().await;
+    let fixture = project(&app).await;
+    let auth = Auth::Bearer(&fixture.token);
+
+    // An image still waiting for its job
+    let (_, body) = app
+        .upload("/upload/image", &fixture.key, &[("file", Some("a.png"), "image/png", &png(32, 32))])
+        .await;
+    let (_, pending) = app.get(&format!("/files/{}", body["id"].as_str().unwrap()), auth).await;
+    // Not an image at all
+    let (_, body) = app
+        .upload("/upload/file", &fixture.key, &[("file", Some("notes.txt"), "text/plain", b"hello")])
+        .await;
+    let (_, text) = app.get(&format!("/files/{}", body["id"].as_str().unwrap()), auth).await;
+
+    for file in [&pending, &text] {
+        assert!(file.get("srcset").is_none(), "{}", file);
+        // `sizes` only ever comes with a `srcset`
+        assert!(file.get("sizes").is_none(), "{}", file);
+    }
+}
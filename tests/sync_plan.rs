@@ -0,0 +1,35 @@
+//! `POST /projects/{id}/sync-variants?dry_run=true` for projects too large to plan inline.
+
+mod common;
+
+use std::sync::Arc;
+
+use axum::http::{Method, StatusCode};
+use common::{init_env, png, Auth, FakeProcessor, TestApp};
+use media_blob_kit::entities::job;
+use sea_orm::EntityTrait;
+use serde_json::json;
+use uuid::Uuid;
+
+#[tokio::test]
+async fn plan_jobs_survive_the_deletion_of_any_image() {
+    init_env(&[("SYNC_DRY_RUN_INLINE_MAX_FILES", "0")]);
+    let app = TestApp::spawn().await;
+    let fixture = app.project_with_settings(json!({ "variants": { "thumb": { "width": 8 } } })).await;
+    let (status, body) = app
+        .upload("/upload/image", &fixture.key, &[("file", Some("a.png"), "image/png", &png(32, 32))])
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    let image: Uuid = body["id"].as_str().unwrap().parse().unwrap();
+    app.run_jobs(image, Arc::new(FakeProcessor)).await;
+
+    let uri = format!("/projects/{}/sync-variants?dry_run=true", fixture.project_id);
+    let (status, body) = app.call(Method::POST, &uri, Auth::Bearer(&fixture.token), None).await;
+    assert_eq!(status, StatusCode::ACCEPTED, "{}", body);
+    let plan_id: Uuid = body["job_id"].as_str().unwrap().parse().unwrap();
+
+    let (status, body) = app.call(Method::DELETE, &format!("/files/{}", image), Auth::Bearer(&fixture.token), None).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let plan = job::Entity::find_by_id(plan_id).one(&app.db).await.unwrap().expect("plan job kept");
+    assert_eq!((plan.file_id, plan.project_id), (None, Some(fixture.project_id)));
+}
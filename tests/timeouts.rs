@@ -0,0 +1,48 @@
+//! Request budgets, with a one-second budget for JSON routes and the default for uploads.
+//! A binary of its own because the budgets are read once from the environment.
+
+mod common;
+
+use std::time::Duration;
+
+use axum::http::{Method, StatusCode};
+use common::{init_env, storage, Auth, TestApp};
+use media_blob_kit::services::memory_storage::{Fault, Operation};
+
+const SLOW: Fault = Fault::Delay(Duration::from_secs(2));
+
+#[tokio::test]
+async fn slow_json_route_times_out_with_an_error_body() {
+    init_env(&[("REQUEST_TIMEOUT_SECS", "1")]);
+    let app = TestApp::spawn().await;
+    let fixture = app.project_with_key().await;
+    let (_, body) = app
+        .upload("/upload/file", &fixture.key, &[("file", Some("a.txt"), "text/plain", b"data")])
+        .await;
+    let id = body["id"].as_str().unwrap().to_string();
+    storage().inject(Operation::Delete, &fixture.prefix, SLOW);
+
+    let (status, body) = app
+        .call(Method::DELETE, &format!("/files/{}", id), Auth::Bearer(&fixture.token), None)
+        .await;
+    storage().clear_faults(&fixture.prefix);
+    assert_eq!(status, StatusCode::GATEWAY_TIMEOUT);
+    let message = body["error"].as_str().expect("JSON error body");
+    assert!(message.starts_with("Request ") && message.ends_with(" timed out after 1s"), "{}", message);
+    assert_eq!(body.as_object().unwrap().len(), 1, "{}", body);
+}
+
+#[tokio::test]
+async fn slow_upload_finishes_within_the_upload_budget() {
+    init_env(&[("REQUEST_TIMEOUT_SECS", "1")]);
+    let app = TestApp::spawn().await;
+    let fixture = app.project_with_key().await;
+    storage().inject(Operation::Put, &fixture.prefix, SLOW);
+
+    let (status, body) = app
+        .upload("/upload/file", &fixture.key, &[("file", Some("a.txt"), "text/plain", b"data")])
+        .await;
+    storage().clear_faults(&fixture.prefix);
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    assert_eq!(storage().keys(&fixture.prefix).len(), 1);
+}
@@ -0,0 +1,103 @@
+//! Two-factor login: secrets are stored sealed, and every code is accepted only once.
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{init_env, Auth, TestApp, PASSWORD};
+use media_blob_kit::entities::user::{self, Role};
+use media_blob_kit::services::totp;
+use sea_orm::{ActiveModelTrait, ActiveValue::Set, EntityTrait, IntoActiveModel};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+fn env() {
+    init_env(&[("TOTP_SECRET_KEY", "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=")]);
+}
+
+/// An su with two-factor login on; returns its id, the base32 secret and the recovery codes.
+async fn enrolled(app: &TestApp) -> (Uuid, String, Vec<String>) {
+    let token = app.token_for("admin", Role::Su).await;
+    let (status, body) = app.post("/auth/2fa/enable", Auth::Bearer(&token), json!({ "password": PASSWORD })).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let secret = body["secret"].as_str().unwrap().to_string();
+
+    let now = chrono::Utc::now().timestamp();
+    let otp = totp::code_for(&secret, now).unwrap();
+    let (status, body) = app.post("/auth/2fa/verify", Auth::Bearer(&token), json!({ "otp": otp })).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let codes = body["recovery_codes"].as_array().unwrap().iter().map(|c| c.as_str().unwrap().to_string()).collect();
+
+    let id = user::Entity::find().one(&app.db).await.unwrap().unwrap().id;
+    (id, secret, codes)
+}
+
+async fn login(app: &TestApp, otp: &str) -> (StatusCode, Value) {
+    app.post("/auth/login", Auth::None, json!({ "username": "admin", "password": PASSWORD, "otp": otp })).await
+}
+
+#[tokio::test]
+async fn secret_is_stored_sealed() {
+    env();
+    let app = TestApp::spawn().await;
+    let (id, secret, _) = enrolled(&app).await;
+
+    let stored = user::Entity::find_by_id(id).one(&app.db).await.unwrap().unwrap().totp_secret.unwrap();
+    assert_ne!(stored, secret);
+    assert!(!stored.contains(&secret));
+}
+
+#[tokio::test]
+async fn a_code_is_accepted_once() {
+    env();
+    let app = TestApp::spawn().await;
+    let (_, secret, _) = enrolled(&app).await;
+
+    // The step confirmed at enrolment is spent; the next one is within the allowed drift
+    let now = chrono::Utc::now().timestamp();
+    let (status, body) = login(&app, &totp::code_for(&secret, now).unwrap()).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED, "{}", body);
+    assert_eq!(body["code"], "invalid_otp");
+
+    let next = totp::code_for(&secret, now + 30).unwrap();
+    let (status, body) = login(&app, &next).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let (status, body) = login(&app, &next).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED, "{}", body);
+    assert_eq!(body["code"], "invalid_otp");
+}
+
+#[tokio::test]
+async fn racing_logins_spend_a_recovery_code_once() {
+    env();
+    let app = TestApp::spawn().await;
+    let (id, _, codes) = enrolled(&app).await;
+
+    let code = &codes[0];
+    let (a, b, c, d) = tokio::join!(login(&app, code), login(&app, code), login(&app, code), login(&app, code));
+    let results = [a, b, c, d];
+    let accepted = results.iter().filter(|(status, _)| *status == StatusCode::OK).count();
+    assert_eq!(accepted, 1, "{:?}", results);
+
+    let row = user::Entity::find_by_id(id).one(&app.db).await.unwrap().unwrap();
+    assert_eq!(row.totp_recovery_codes.as_array().unwrap().len(), codes.len() - 1);
+    let (status, _) = login(&app, &codes[1]).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn plain_secrets_from_before_sealing_still_work() {
+    env();
+    let app = TestApp::spawn().await;
+    let (id, _, _) = enrolled(&app).await;
+
+    let secret = totp::generate_secret().unwrap();
+    let mut row = user::Entity::find_by_id(id).one(&app.db).await.unwrap().unwrap().into_active_model();
+    row.totp_secret = Set(Some(secret.clone()));
+    row.totp_last_step = Set(None);
+    row.update(&app.db).await.unwrap();
+
+    let (status, body) = login(&app, &totp::code_for(&secret, chrono::Utc::now().timestamp()).unwrap()).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let stored = user::Entity::find_by_id(id).one(&app.db).await.unwrap().unwrap().totp_secret.unwrap();
+    assert_ne!(stored, secret, "sealed on login");
+}
@@ -0,0 +1,98 @@
+//! Uploads and deletes with storage or database failures injected, checking that bucket and
+//! database never disagree about which files exist.
+
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::{png, storage, Auth, TestApp};
+use media_blob_kit::entities::{file, job};
+use media_blob_kit::services::memory_storage::{Fault, Operation};
+use sea_orm::{ConnectionTrait, EntityTrait, PaginatorTrait};
+use serde_json::json;
+
+/// Makes every further insert into `files` fail, as a dropped connection or key collision would.
+async fn break_file_inserts(app: &TestApp) {
+    app.db
+        .execute_unprepared("ALTER TABLE files ADD CONSTRAINT reject_inserts CHECK (false) NOT VALID")
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn failed_put_records_nothing() {
+    let app = TestApp::spawn().await;
+    let fixture = app.project_with_key().await;
+    storage().inject(Operation::Put, &fixture.prefix, Fault::Error);
+
+    let (status, _) = app
+        .upload("/upload/file", &fixture.key, &[("file", Some("a.txt"), "text/plain", b"data")])
+        .await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(file::Entity::find().count(&app.db).await.unwrap(), 0);
+    assert!(storage().keys(&fixture.prefix).is_empty());
+}
+
+#[tokio::test]
+async fn failed_image_put_enqueues_no_job() {
+    let app = TestApp::spawn().await;
+    let fixture = app
+        .project_with_settings(json!({ "variants": { "thumb": { "width": 8, "height": 8 } } }))
+        .await;
+    storage().inject(Operation::Put, &fixture.prefix, Fault::Unavailable);
+
+    let (status, _) = app
+        .upload("/upload/image", &fixture.key, &[("file", Some("a.png"), "image/png", &png(8, 8))])
+        .await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(file::Entity::find().count(&app.db).await.unwrap(), 0);
+    assert_eq!(job::Entity::find().count(&app.db).await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn failed_file_insert_removes_the_object() {
+    let app = TestApp::spawn().await;
+    let fixture = app.project_with_key().await;
+    break_file_inserts(&app).await;
+
+    let (status, _) = app
+        .upload("/upload/file", &fixture.key, &[("file", Some("a.txt"), "text/plain", b"data")])
+        .await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    assert!(storage().keys(&fixture.prefix).is_empty());
+}
+
+#[tokio::test]
+async fn failed_image_insert_removes_the_object_and_enqueues_no_job() {
+    let app = TestApp::spawn().await;
+    let fixture = app
+        .project_with_settings(json!({ "variants": { "thumb": { "width": 8, "height": 8 } } }))
+        .await;
+    break_file_inserts(&app).await;
+
+    let (status, _) = app
+        .upload("/upload/image", &fixture.key, &[("file", Some("a.png"), "image/png", &png(8, 8))])
+        .await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    assert!(storage().keys(&fixture.prefix).is_empty());
+    assert_eq!(job::Entity::find().count(&app.db).await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn failed_object_delete_still_deletes_the_row() {
+    let app = TestApp::spawn().await;
+    let fixture = app.project_with_key().await;
+    let (_, body) = app
+        .upload("/upload/file", &fixture.key, &[("file", Some("a.txt"), "text/plain", b"data")])
+        .await;
+    let id = body["id"].as_str().unwrap().to_string();
+    storage().inject(Operation::Delete, &fixture.prefix, Fault::Error);
+
+    let (status, _) = app
+        .call(Method::DELETE, &format!("/files/{}", id), Auth::Bearer(&fixture.token), None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(file::Entity::find().count(&app.db).await.unwrap(), 0);
+    // Left behind as storage drift, which reconciling reports
+    assert_eq!(storage().keys(&fixture.prefix).len(), 1);
+    storage().clear_faults(&fixture.prefix);
+}
@@ -0,0 +1,59 @@
+//! `UPLOAD_MAX_PART_BYTES`: oversized parts are refused with 413 (or an `errors` entry in a
+//! batch) while the parts next to them still go through.
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{init_env, png, TestApp};
+use serde_json::json;
+
+const MAX_PART: usize = 4096;
+
+fn env() {
+    init_env(&[("UPLOAD_MAX_PART_BYTES", "4096")]);
+}
+
+#[tokio::test]
+async fn single_uploads_refuse_an_oversized_part() {
+    env();
+    let app = TestApp::spawn().await;
+    let fixture = app.project_with_key().await;
+    let big = vec![b'a'; MAX_PART + 1];
+
+    for (uri, content_type) in [("/upload/file", "text/plain"), ("/upload/image", "image/png")] {
+        let (status, body) = app.upload(uri, &fixture.key, &[("file", Some("big.bin"), content_type, &big)]).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE, "{}: {}", uri, body);
+        assert_eq!(body["code"], "part_too_large", "{}", body);
+    }
+
+    let (status, body) = app
+        .upload("/upload/file", &fixture.key, &[("file", Some("fits.txt"), "text/plain", &big[..MAX_PART])])
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+}
+
+#[tokio::test]
+async fn batch_lists_an_oversized_part_and_keeps_the_rest() {
+    env();
+    let app = TestApp::spawn().await;
+    let fixture = app.project_with_key().await;
+    let small = png(4, 4);
+    let big = vec![0u8; MAX_PART * 2];
+
+    let (status, body) = app
+        .upload(
+            "/upload/images",
+            &fixture.key,
+            &[
+                ("file", Some("a.png"), "image/png", &small),
+                ("file", Some("big.png"), "image/png", &big),
+                ("file", Some("c.png"), "image/png", &small),
+            ],
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    let indexes: Vec<_> = body["uploaded"].as_array().unwrap().iter().map(|u| u["index"].clone()).collect();
+    assert_eq!(indexes, vec![json!(0), json!(2)], "{}", body);
+    assert_eq!(body["errors"][0]["index"], 1);
+    assert_eq!(body["errors"][0]["code"], "part_too_large", "{}", body);
+}
@@ -0,0 +1,67 @@
+//! File URLs in API responses under each `FILE_URL_MODE`, set on the router state.
+
+mod common;
+
+use std::sync::Arc;
+
+use axum::http::StatusCode;
+use common::{png, Auth, FakeProcessor, TestApp, BUCKET};
+use media_blob_kit::config::FileUrlMode;
+use media_blob_kit::services::urls::UrlBuilder;
+use serde_json::{json, Value};
+
+/// Uploads and processes an image under `mode`; returns the upload response, the file as
+/// `GET /files/{id}` shows it afterwards, and the project prefix.
+async fn file_under(mode: FileUrlMode) -> (Value, Value, String) {
+    let app = TestApp::spawn_with(|state| state.urls = UrlBuilder::new(mode)).await;
+    let fixture = app.project_with_settings(json!({ "variants": { "thumb": { "width": 8, "height": 8 } } })).await;
+    let (status, uploaded) = app
+        .upload("/upload/image", &fixture.key, &[("file", Some("a.png"), "image/png", &png(16, 16))])
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", uploaded);
+    let id = uploaded["id"].as_str().unwrap().parse().unwrap();
+    app.run_jobs(id, Arc::new(FakeProcessor)).await;
+
+    let (status, file) = app.get(&format!("/files/{}", id), Auth::Bearer(&fixture.token)).await;
+    assert_eq!(status, StatusCode::OK, "{}", file);
+    (uploaded, file, fixture.prefix)
+}
+
+fn key_of(file: &Value, variant: &str) -> String {
+    file["variants"][variant].as_str().unwrap().to_string()
+}
+
+#[tokio::test]
+async fn direct_mode_links_the_bucket() {
+    let (uploaded, file, prefix) = file_under(FileUrlMode::Direct).await;
+    let bucket = format!("memory://{}/", BUCKET);
+
+    assert_eq!(uploaded["original_url"], file["url"]);
+    let url = file["url"].as_str().unwrap();
+    assert!(url.starts_with(&format!("{}{}", bucket, prefix)), "{}", url);
+    assert_eq!(file["srcset"], format!("{}{} 8w", bucket, key_of(&file, "thumb")));
+}
+
+#[tokio::test]
+async fn cdn_mode_links_the_cdn() {
+    let mode = FileUrlMode::Cdn { base: "https://cdn.example.com".to_string() };
+    let (uploaded, file, prefix) = file_under(mode).await;
+
+    assert_eq!(uploaded["original_url"], file["url"]);
+    let url = file["url"].as_str().unwrap();
+    assert!(url.starts_with(&format!("https://cdn.example.com/{}", prefix)), "{}", url);
+    assert_eq!(file["srcset"], format!("https://cdn.example.com/{} 8w", key_of(&file, "thumb")));
+}
+
+#[tokio::test]
+async fn presigned_mode_signs_every_link() {
+    let mode = FileUrlMode::Presigned { expires_in_secs: 900 };
+    let (uploaded, file, prefix) = file_under(mode).await;
+
+    for url in [&uploaded["original_url"], &file["url"]] {
+        let url = url.as_str().unwrap();
+        assert!(url.starts_with(&format!("memory://{}/{}", BUCKET, prefix)), "{}", url);
+        assert!(url.ends_with("?expires=900"), "{}", url);
+    }
+    assert_eq!(file["srcset"], format!("memory://{}/{}?expires=900 8w", BUCKET, key_of(&file, "thumb")));
+}
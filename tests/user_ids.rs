@@ -0,0 +1,68 @@
+//! User ids are UUIDs end to end: in the access token, on owned projects, on refresh tokens and
+//! in `/users/{id}` paths, through the login → create project → upload flow on a migrated schema.
+
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::{Auth, TestApp, PASSWORD};
+use media_blob_kit::entities::{file, project, refresh_token, user::Role};
+use sea_orm::{ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter};
+use serde_json::json;
+use uuid::Uuid;
+
+#[tokio::test]
+async fn login_project_upload_flow_keeps_the_user_uuid() {
+    let app = TestApp::spawn().await;
+    let alice = app.create_user("alice", Role::User).await;
+
+    let (status, login) = app.login("alice", PASSWORD).await;
+    assert_eq!(status, StatusCode::OK, "{}", login);
+    let token = login["access_token"].as_str().unwrap();
+    let (_, me) = app.get("/auth/me", Auth::Bearer(token)).await;
+    assert_eq!(me["id"], alice.to_string());
+
+    let sessions = refresh_token::Entity::find().filter(refresh_token::Column::UserId.eq(alice));
+    assert_eq!(sessions.clone().count(&app.db).await.unwrap(), 1);
+
+    let project_id = app.create_project(token, "Flow").await;
+    let project = project::Entity::find_by_id(project_id).one(&app.db).await.unwrap().unwrap();
+    assert_eq!(project.owner_id, alice);
+
+    let key = app.create_api_key(token, project_id).await;
+    let (status, body) = app.upload("/upload/file", &key, &[("file", Some("notes.txt"), "text/plain", b"hello")]).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    let file_id: Uuid = body["id"].as_str().unwrap().parse().unwrap();
+    let stored = file::Entity::find_by_id(file_id).one(&app.db).await.unwrap().unwrap();
+    assert_eq!(stored.project_id, project_id);
+
+    // The owner filter finds the file through the project
+    let (status, body) = app.get(&format!("/files?project_id={}", project_id), Auth::Bearer(token)).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["data"][0]["id"], file_id.to_string());
+
+    let refresh = login["refresh_token"].as_str().unwrap();
+    let (status, body) = app.post("/auth/refresh", Auth::None, json!({ "refresh_token": refresh })).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let (_, me) = app.get("/auth/me", Auth::Bearer(body["access_token"].as_str().unwrap())).await;
+    assert_eq!(me["id"], alice.to_string());
+}
+
+#[tokio::test]
+async fn users_are_deleted_by_uuid_with_what_they_own() {
+    let app = TestApp::spawn().await;
+    let su = app.token_for("root", Role::Su).await;
+    let bob = app.create_user("bob", Role::User).await;
+    let (_, login) = app.login("bob", PASSWORD).await;
+    let project_id = app.create_project(login["access_token"].as_str().unwrap(), "Owned").await;
+
+    let (status, _) = app.call(Method::DELETE, "/users/42", Auth::Bearer(&su), None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "integer ids are not user ids");
+    let (status, _) = app.call(Method::DELETE, &format!("/users/{}", Uuid::new_v4()), Auth::Bearer(&su), None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, body) = app.call(Method::DELETE, &format!("/users/{}", bob), Auth::Bearer(&su), None).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert!(project::Entity::find_by_id(project_id).one(&app.db).await.unwrap().is_none());
+    let sessions = refresh_token::Entity::find().filter(refresh_token::Column::UserId.eq(bob));
+    assert_eq!(sessions.count(&app.db).await.unwrap(), 0);
+}
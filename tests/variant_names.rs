@@ -0,0 +1,83 @@
+//! Variant names become key-safe slugs when settings are saved; names that collide, are reserved
+//! or have nothing left once slugged are refused with 422.
+
+mod common;
+
+use std::sync::Arc;
+
+use axum::http::{Method, StatusCode};
+use common::{png, storage, Auth, FakeProcessor, TestApp};
+use media_blob_kit::entities::project;
+use sea_orm::{ActiveModelTrait, EntityTrait, IntoActiveModel, Set};
+use serde_json::json;
+use uuid::Uuid;
+
+#[tokio::test]
+async fn variant_names_are_slugged_into_keys() {
+    let app = TestApp::spawn().await;
+    let fixture = app
+        .project_with_settings(json!({ "variants": { "Big Thumb!": { "width": 16, "height": 16, "format": "webp" } } }))
+        .await;
+
+    let (_, body) = app.get(&format!("/projects/{}", fixture.project_id), Auth::Bearer(&fixture.token)).await;
+    assert_eq!(body["settings"]["variants"], json!({ "big-thumb": { "width": 16, "height": 16, "format": "webp" } }));
+
+    let (status, body) = app
+        .upload("/upload/image", &fixture.key, &[("file", Some("cat.png"), "image/png", &png(32, 32))])
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    let id: Uuid = body["id"].as_str().unwrap().parse().unwrap();
+    let jobs = app.run_jobs(id, Arc::new(FakeProcessor)).await;
+    assert_eq!(jobs[0].status, "completed", "{}", jobs[0].payload);
+
+    let file = app.file(id).await.unwrap();
+    let key = file.variants_json["big-thumb"].as_str().unwrap();
+    assert!(key.contains("big-thumb") && !key.contains(' '), "{}", key);
+    assert!(storage().object(key).is_some());
+}
+
+#[tokio::test]
+async fn colliding_reserved_and_empty_names_are_refused() {
+    let app = TestApp::spawn().await;
+    let fixture = app.project_with_settings(json!({ "variants": { "hd": { "width": 1280 } } })).await;
+    let uri = format!("/projects/{}", fixture.project_id);
+
+    let variants = json!({
+        "Thumb": { "width": 8 },
+        "thumb": { "width": 16 },
+        "Original": { "width": 32 },
+        "!!!": { "width": 64 },
+    });
+    let (status, body) = app
+        .call(Method::PUT, &uri, Auth::Bearer(&fixture.token), Some(json!({ "settings": { "variants": variants } })))
+        .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{}", body);
+    let error = body["error"].as_str().unwrap();
+    for problem in ["'Thumb', 'thumb' all become 'thumb'", "'Original' is reserved", "'!!!' has no usable characters"] {
+        assert!(error.contains(problem), "{}: {}", problem, error);
+    }
+
+    let (_, body) = app.get(&uri, Auth::Bearer(&fixture.token)).await;
+    assert_eq!(body["settings"]["variants"], json!({ "hd": { "width": 1280 } }), "settings are left as they were");
+}
+
+#[tokio::test]
+async fn jobs_of_projects_with_broken_names_fail() {
+    let app = TestApp::spawn().await;
+    let fixture = app.project_with_key().await;
+
+    // Saved before the rules existed
+    let mut project = project::Entity::find_by_id(fixture.project_id).one(&app.db).await.unwrap().unwrap().into_active_model();
+    project.settings = Set(json!({ "variants": { "Thumb": { "width": 8 }, "thumb": { "width": 16 } } }));
+    project.update(&app.db).await.unwrap();
+
+    let (status, body) = app
+        .upload("/upload/image", &fixture.key, &[("file", Some("cat.png"), "image/png", &png(32, 32))])
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    let id: Uuid = body["id"].as_str().unwrap().parse().unwrap();
+    let jobs = app.run_jobs(id, Arc::new(FakeProcessor)).await;
+    assert_eq!(jobs[0].status, "failed", "{}", jobs[0].payload);
+    assert_eq!(jobs[0].payload["error"], "Invalid variant names: 'Thumb', 'thumb' all become 'thumb'");
+    assert!(storage().keys(&fixture.prefix).iter().all(|k| !k.contains("thumb")));
+}
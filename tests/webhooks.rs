@@ -0,0 +1,186 @@
+//! Project webhooks: subscribed events reach the configured URL signed, and failed tries are retried.
+
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use axum::http::{HeaderMap, Method, StatusCode};
+use common::{init_env, Auth, Fixture, TestApp};
+use media_blob_kit::services::cleanup::CleanupService;
+use media_blob_kit::services::webhooks;
+use serde_json::{json, Value};
+
+fn env() {
+    init_env(&[
+        ("WEBHOOK_SECRET_KEY", "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA="),
+        ("WEBHOOK_PRIVATE_HOSTS", "127.0.0.1"),
+        ("WEBHOOK_RETRY_DELAY_MS", "20"),
+        ("WEBHOOK_MAX_ATTEMPTS", "3"),
+    ]);
+}
+
+#[derive(Clone, Default)]
+struct Receiver {
+    received: Arc<Mutex<Vec<(HeaderMap, String)>>>,
+    /// Answers for the first requests; later ones get 200
+    failures: Arc<Mutex<Vec<StatusCode>>>,
+}
+
+impl Receiver {
+    /// Serves on a random local port and returns its URL.
+    async fn start(&self) -> String {
+        let receiver = self.clone();
+        let router = axum::Router::new().route(
+            "/hook",
+            axum::routing::post(move |headers: HeaderMap, body: String| {
+                let receiver = receiver.clone();
+                async move {
+                    receiver.received.lock().unwrap().push((headers, body));
+                    let mut failures = receiver.failures.lock().unwrap();
+                    if failures.is_empty() { StatusCode::OK } else { failures.remove(0) }
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+        format!("http://{}/hook", address)
+    }
+
+    async fn wait_for(&self, count: usize) -> Vec<(HeaderMap, String)> {
+        for _ in 0..200 {
+            if self.received.lock().unwrap().len() >= count {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        self.received.lock().unwrap().clone()
+    }
+}
+
+/// A project subscribed to `event`, with its webhook pointed at `receiver`; returns the secret.
+async fn subscribed(app: &TestApp, receiver: &Receiver, event: &str) -> (Fixture, String) {
+    let fixture = app.project_with_settings(json!({ "webhook_events": [event] })).await;
+    let url = receiver.start().await;
+    let (status, body) = app
+        .call(Method::PUT, &format!("/projects/{}/webhook", fixture.project_id), Auth::Bearer(&fixture.token), Some(json!({ "url": url })))
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let secret = body["secret"].as_str().unwrap().to_string();
+    (fixture, secret)
+}
+
+#[tokio::test]
+async fn events_are_posted_signed() {
+    env();
+    let app = TestApp::spawn().await;
+    let receiver = Receiver::default();
+    let (fixture, secret) = subscribed(&app, &receiver, "file.created").await;
+
+    let (status, body) = app
+        .upload("/upload/file", &fixture.key, &[("file", Some("notes.txt"), "text/plain", b"hello")])
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+
+    let received = receiver.wait_for(1).await;
+    assert_eq!(received.len(), 1, "only the subscribed event is sent");
+    let (headers, payload) = &received[0];
+    assert_eq!(headers[webhooks::EVENT_HEADER], "file.created");
+    let timestamp: i64 = headers[webhooks::TIMESTAMP_HEADER].to_str().unwrap().parse().unwrap();
+    assert_eq!(headers[webhooks::SIGNATURE_HEADER].to_str().unwrap(), webhooks::sign(&secret, timestamp, payload));
+
+    let payload: Value = serde_json::from_str(payload).unwrap();
+    assert_eq!(payload["event"], "file.created");
+    assert_eq!(payload["id"], body["id"]);
+    assert_eq!(payload["project_id"], fixture.project_id.to_string());
+}
+
+#[tokio::test]
+async fn failed_deliveries_are_retried() {
+    env();
+    let app = TestApp::spawn().await;
+    let receiver = Receiver::default();
+    *receiver.failures.lock().unwrap() = vec![StatusCode::INTERNAL_SERVER_ERROR, StatusCode::SERVICE_UNAVAILABLE];
+    let (fixture, _) = subscribed(&app, &receiver, "file.created").await;
+
+    app.upload("/upload/file", &fixture.key, &[("file", Some("a.txt"), "text/plain", b"a")]).await;
+
+    let received = receiver.wait_for(3).await;
+    assert_eq!(received.len(), 3, "two failures, then the delivery that succeeds");
+    let deliveries: Vec<_> = received.iter().map(|(h, _)| h[webhooks::DELIVERY_HEADER].clone()).collect();
+    assert!(deliveries.iter().all(|d| *d == deliveries[0]), "retries keep the delivery id");
+}
+
+#[tokio::test]
+async fn webhook_secret_is_only_shown_when_generated() {
+    env();
+    let app = TestApp::spawn().await;
+    let receiver = Receiver::default();
+    let (fixture, secret) = subscribed(&app, &receiver, "file.created").await;
+    let uri = format!("/projects/{}/webhook", fixture.project_id);
+    let auth = Auth::Bearer(&fixture.token);
+
+    let (status, body) = app.get(&uri, auth).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert!(body.get("secret").is_none(), "{}", body);
+
+    let url = body["url"].as_str().unwrap().to_string();
+    let (_, body) = app.call(Method::PUT, &uri, auth, Some(json!({ "url": url }))).await;
+    assert!(body.get("secret").is_none(), "{}", body);
+
+    let (_, body) = app.call(Method::PUT, &uri, auth, Some(json!({ "url": url, "rotate_secret": true }))).await;
+    assert!(body["secret"].as_str().is_some_and(|s| s.starts_with("whsec_") && s != secret), "{}", body);
+}
+
+#[tokio::test]
+async fn private_webhook_urls_are_rejected() {
+    env();
+    let app = TestApp::spawn().await;
+    let fixture = app.project_with_key().await;
+    let uri = format!("/projects/{}/webhook", fixture.project_id);
+
+    for url in ["http://169.254.169.254/latest", "http://10.0.0.5/hook", "http://[::1]/hook", "ftp://example.com/hook"] {
+        let (status, body) = app.call(Method::PUT, &uri, Auth::Bearer(&fixture.token), Some(json!({ "url": url }))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}: {}", url, body);
+    }
+}
+
+#[tokio::test]
+async fn expiring_keys_are_announced_once_per_expiry() {
+    env();
+    let app = TestApp::spawn().await;
+    let receiver = Receiver::default();
+    let (fixture, _) = subscribed(&app, &receiver, "api_key.expiring").await;
+    let keys = format!("/projects/{}/keys", fixture.project_id);
+    let soon = (chrono::Utc::now() + chrono::Duration::days(3)).naive_utc();
+    let (status, body) = app.post(&keys, Auth::Bearer(&fixture.token), json!({ "name": "ci", "expires_at": soon })).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    let key_id = body["id"].as_str().unwrap().to_string();
+
+    let cleanup = CleanupService::new(app.db.clone());
+    cleanup.notify_expiring_api_keys().await.unwrap();
+    let received = receiver.wait_for(1).await;
+    assert_eq!(received.len(), 1);
+    assert_eq!(received[0].0[webhooks::EVENT_HEADER], "api_key.expiring");
+    let payload: Value = serde_json::from_str(&received[0].1).unwrap();
+    assert_eq!(payload["key_id"], key_id);
+
+    // Already announced: the next pass stays quiet
+    cleanup.notify_expiring_api_keys().await.unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    assert_eq!(receiver.received.lock().unwrap().len(), 1);
+
+    // A new expiry is announced again once it is in the window
+    let later = (chrono::Utc::now() + chrono::Duration::days(5)).naive_utc();
+    let uri = format!("{}/{}", keys, key_id);
+    let (status, body) = app.call(Method::PATCH, &uri, Auth::Bearer(&fixture.token), Some(json!({ "expires_at": later }))).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    cleanup.notify_expiring_api_keys().await.unwrap();
+    assert_eq!(receiver.wait_for(2).await.len(), 2);
+
+    // And `null` takes the expiry away
+    let (status, _) = app.call(Method::PATCH, &uri, Auth::Bearer(&fixture.token), Some(json!({ "expires_at": null }))).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, body) = app.get(&uri, Auth::Bearer(&fixture.token)).await;
+    assert!(body["expires_at"].is_null(), "{}", body);
+}
@@ -0,0 +1,294 @@
+//! The background worker against the test database, with stand-in image processors.
+
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use axum::http::{Method, StatusCode};
+use common::{png, storage, Auth, FakeProcessor, Fixture, TestApp};
+use media_blob_kit::entities::job;
+use media_blob_kit::models::settings::VariantConfig;
+use media_blob_kit::services::worker::Worker;
+use media_blob_kit::utils::image_processor::{ImageProcessor, ProcessError, ProcessedImage};
+use sea_orm::EntityTrait;
+use serde_json::json;
+use uuid::Uuid;
+
+/// Panics on 13px wide variants and fakes the rest.
+struct PanickingProcessor;
+
+impl ImageProcessor for PanickingProcessor {
+    fn name(&self) -> &'static str {
+        "panicking"
+    }
+
+    fn process(&self, data: &[u8], config: &VariantConfig) -> Result<ProcessedImage, ProcessError> {
+        if config.width == Some(13) {
+            panic!("decoder blew up");
+        }
+        FakeProcessor.process(data, config)
+    }
+}
+
+/// Fakes every variant and remembers what it was asked for.
+#[derive(Default)]
+struct RecordingProcessor {
+    calls: Mutex<Vec<(usize, Option<u32>)>>,
+}
+
+impl ImageProcessor for RecordingProcessor {
+    fn name(&self) -> &'static str {
+        "recording"
+    }
+
+    fn process(&self, data: &[u8], config: &VariantConfig) -> Result<ProcessedImage, ProcessError> {
+        self.calls.lock().unwrap().push((data.len(), config.width));
+        FakeProcessor.process(data, config)
+    }
+}
+
+/// Refuses every image, like a backend that cannot decode it.
+struct FailingProcessor;
+
+impl ImageProcessor for FailingProcessor {
+    fn name(&self) -> &'static str {
+        "failing"
+    }
+
+    fn process(&self, _data: &[u8], _config: &VariantConfig) -> Result<ProcessedImage, ProcessError> {
+        Err(ProcessError::Failed("cannot decode".to_string()))
+    }
+}
+
+async fn upload_png(app: &TestApp, fixture: &Fixture) -> Uuid {
+    let (status, body) = app
+        .upload("/upload/image", &fixture.key, &[("file", Some("a.png"), "image/png", &png(32, 32))])
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    body["id"].as_str().unwrap().parse().unwrap()
+}
+
+#[tokio::test]
+async fn panicking_job_fails_and_the_worker_keeps_going() {
+    let app = TestApp::spawn().await;
+    let cursed = app
+        .project_with_settings(json!({ "variants": { "thumb": { "width": 13, "height": 13 } } }))
+        .await;
+    let fine = app
+        .project_with_settings(json!({ "variants": { "thumb": { "width": 16, "height": 16 } } }))
+        .await;
+
+    let worker = Worker::with_processor(app.db.clone(), Arc::new(PanickingProcessor)).await;
+    let handle = tokio::spawn(async move { worker.run().await });
+
+    let panicked = upload_png(&app, &cursed).await;
+    let jobs = app.wait_for_jobs(panicked).await;
+    assert_eq!(jobs[0].status, "failed");
+    let error = jobs[0].payload["error"].as_str().unwrap();
+    assert!(error.contains("panicked") && error.contains("decoder blew up"), "{}", error);
+
+    // Uploaded after the panic, so only a worker still running picks it up
+    let next = upload_png(&app, &fine).await;
+    let jobs = app.wait_for_jobs(next).await;
+    assert!(!handle.is_finished());
+    handle.abort();
+    assert_eq!(jobs[0].status, "completed", "{}", jobs[0].payload);
+}
+
+#[tokio::test]
+async fn unsupported_variant_format_fails_the_job_without_writing_a_key() {
+    let app = TestApp::spawn().await;
+    let fixture = app
+        .project_with_settings(json!({ "variants": { "scan": { "width": 16, "format": "tiff" }, "thumb": { "width": 8 } } }))
+        .await;
+
+    let (status, body) = app
+        .upload("/upload/image", &fixture.key, &[("file", Some("a.png"), "image/png", &png(32, 32))])
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    // Only placeholders: no key is guessed from the configured format
+    assert_eq!(body["variants"]["scan"], json!({ "status": "pending" }));
+    assert!(!body.to_string().contains(".tiff"), "{}", body);
+    let id: Uuid = body["id"].as_str().unwrap().parse().unwrap();
+
+    let jobs = app.run_jobs(id, Arc::new(FakeProcessor)).await;
+    assert_eq!(jobs[0].status, "failed");
+    assert_eq!(jobs[0].payload["error"], "Unsupported variant formats: scan (tiff)");
+
+    let file = app.file(id).await.unwrap();
+    assert_eq!(file.variants_json, json!({}));
+    assert_eq!(storage().keys(&fixture.prefix), [file.s3_key]);
+}
+
+#[tokio::test]
+async fn unsupported_override_format_is_refused_at_upload() {
+    let app = TestApp::spawn().await;
+    let fixture = app.project_with_key().await;
+
+    let (status, body) = app
+        .upload(
+            "/upload/image",
+            &fixture.key,
+            &[
+                ("variants", None, "application/json", br#"{"scan": {"width": 16, "format": "tiff"}}"#),
+                ("file", Some("a.png"), "image/png", &png(32, 32)),
+            ],
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body["error"].as_str().unwrap().contains("unsupported format 'tiff'"), "{}", body);
+    assert!(storage().keys(&fixture.prefix).is_empty());
+}
+
+#[tokio::test]
+async fn override_variants_apply_to_their_upload_only() {
+    let app = TestApp::spawn().await;
+    let fixture = app
+        .project_with_settings(json!({ "variants": { "thumb": { "width": 8, "height": 6 }, "wide": { "width": 24, "height": 12 } } }))
+        .await;
+
+    let (status, body) = app
+        .upload(
+            "/upload/image",
+            &fixture.key,
+            &[
+                ("variants", None, "application/json", br#"{"thumb": {"width": 4, "height": 4}, "square": {"width": 12, "height": 12}}"#),
+                ("file", Some("a.png"), "image/png", &png(32, 32)),
+            ],
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    assert_eq!(body["variants"]["thumb"], json!({ "status": "pending", "override": true }));
+    assert_eq!(body["variants"]["square"], json!({ "status": "pending", "override": true }));
+    assert_eq!(body["variants"]["wide"], json!({ "status": "pending" }));
+
+    let processor = Arc::new(RecordingProcessor::default());
+    let id: Uuid = body["id"].as_str().unwrap().parse().unwrap();
+    app.run_jobs(id, processor.clone()).await;
+    let mut widths: Vec<_> = processor.calls.lock().unwrap().iter().map(|(_, width)| *width).collect();
+    widths.sort();
+    assert_eq!(widths, [Some(4), Some(12), Some(24)]);
+
+    // The project's own variants are unchanged for the next upload
+    let processor = Arc::new(RecordingProcessor::default());
+    let id = upload_png(&app, &fixture).await;
+    app.run_jobs(id, processor.clone()).await;
+    let mut widths: Vec<_> = processor.calls.lock().unwrap().iter().map(|(_, width)| *width).collect();
+    widths.sort();
+    assert_eq!(widths, [Some(8), Some(24)]);
+}
+
+#[tokio::test]
+async fn override_limits_are_enforced() {
+    let app = TestApp::spawn().await;
+    let fixture = app.project_with_key().await;
+
+    let too_many = br#"{"a": {"width": 8}, "b": {"width": 8}, "c": {"width": 8}, "d": {"width": 8}}"#;
+    let too_wide = br#"{"huge": {"width": 5000}}"#;
+    for variants in [&too_many[..], &too_wide[..]] {
+        let (status, body) = app
+            .upload(
+                "/upload/image",
+                &fixture.key,
+                &[("variants", None, "application/json", variants), ("file", Some("a.png"), "image/png", &png(32, 32))],
+            )
+            .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+    }
+    assert!(storage().keys(&fixture.prefix).is_empty());
+}
+
+#[tokio::test]
+async fn injected_processor_renders_every_variant() {
+    let app = TestApp::spawn().await;
+    let fixture = app
+        .project_with_settings(json!({ "variants": { "thumb": { "width": 8, "height": 6 }, "wide": { "width": 24, "height": 12 } } }))
+        .await;
+    let original = png(32, 32);
+    let id = upload_png(&app, &fixture).await;
+
+    let processor = Arc::new(RecordingProcessor::default());
+    let jobs = app.run_jobs(id, processor.clone()).await;
+    assert_eq!(jobs[0].status, "completed", "{}", jobs[0].payload);
+
+    // Handed the original once per variant
+    let mut calls = processor.calls.lock().unwrap().clone();
+    calls.sort();
+    assert_eq!(calls, [(original.len(), Some(8)), (original.len(), Some(24))]);
+
+    // Stored exactly as the processor returned it, with its type and dimensions
+    let file = app.file(id).await.unwrap();
+    for (name, width, height) in [("thumb", 8, 6), ("wide", 24, 12)] {
+        let key = file.variants_json[name].as_str().unwrap();
+        assert!(key.ends_with(".webp"), "{}", key);
+        let object = storage().object(key).unwrap();
+        assert_eq!((object.data.as_slice(), object.content_type.as_str()), (&b"RIFF-fake-webp"[..], "image/webp"));
+        assert_eq!(file.variant_dimensions[name], json!({ "width": width, "height": height }));
+    }
+}
+
+#[tokio::test]
+async fn processor_errors_fail_the_job_and_are_recorded() {
+    let app = TestApp::spawn().await;
+    let fixture = app
+        .project_with_settings(json!({ "variants": { "thumb": { "width": 8, "height": 8 } } }))
+        .await;
+    let id = upload_png(&app, &fixture).await;
+
+    let jobs = app.run_jobs(id, Arc::new(FailingProcessor)).await;
+    assert_eq!(jobs[0].status, "failed");
+    assert_eq!(jobs[0].payload["error"], "Variant thumb: cannot decode");
+
+    let file = app.file(id).await.unwrap();
+    assert_eq!(file.variant_errors["thumb"]["error"], "cannot decode");
+    assert_eq!(storage().keys(&fixture.prefix), [file.s3_key]);
+
+    // The content endpoint says the variant failed without echoing the processor's output
+    let uri = format!("/files/{}/content?variant=thumb", id);
+    let (status, body) = app.get(&uri, Auth::Bearer(&fixture.token)).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["code"], "variant_failed");
+    assert!(!body.to_string().contains("cannot decode"), "{}", body);
+}
+
+#[tokio::test]
+async fn auto_sync_job_survives_the_deletion_of_any_image() {
+    let app = TestApp::spawn().await;
+    let fixture = app
+        .project_with_settings(json!({ "auto_sync_on_settings_change": true, "variants": { "thumb": { "width": 8 } } }))
+        .await;
+    let (oldest, other) = (upload_png(&app, &fixture).await, upload_png(&app, &fixture).await);
+    app.run_jobs(oldest, Arc::new(FakeProcessor)).await;
+    app.run_jobs(other, Arc::new(FakeProcessor)).await;
+
+    let (status, body) = app
+        .call(
+            Method::PUT,
+            &format!("/projects/{}", fixture.project_id),
+            Auth::Bearer(&fixture.token),
+            Some(json!({ "settings": { "auto_sync_on_settings_change": true, "variants": { "thumb": { "width": 12 } } } })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let sync_id: Uuid = body["triggered_jobs"][0].as_str().unwrap().parse().unwrap();
+
+    let (status, body) = app.call(Method::DELETE, &format!("/files/{}", oldest), Auth::Bearer(&fixture.token), None).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let sync = job::Entity::find_by_id(sync_id).one(&app.db).await.unwrap().expect("sync job kept");
+    assert_eq!((sync.file_id, sync.project_id), (None, Some(fixture.project_id)));
+
+    // And it still fans out to the images that are left
+    let worker = Worker::with_processor(app.db.clone(), Arc::new(FakeProcessor)).await;
+    let handle = tokio::spawn(async move { worker.run().await });
+    for _ in 0..200 {
+        let sync = job::Entity::find_by_id(sync_id).one(&app.db).await.unwrap().unwrap();
+        if sync.status == "completed" {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    let jobs = app.wait_for_jobs(other).await;
+    handle.abort();
+    assert_eq!(jobs.len(), 2, "upload and the synced regeneration");
+    assert!(jobs.iter().all(|j| j.status == "completed"));
+}